@@ -24,6 +24,112 @@ impl syn::parse::Parse for CopySpanInput {
     }
 }
 
+impl CopySpanInput {
+    /// The span covering all the sources, or the call site if there are none.
+    fn combined_span(&self) -> proc_macro2::Span {
+        let mut combined_span: Option<proc_macro2::Span> = None;
+        for inner_source in &self.sources {
+            let mut inner_source = inner_source;
+            while let syn::Expr::Group(g) = inner_source {
+                inner_source = &g.expr;
+            }
+
+            combined_span = Some(match combined_span {
+                None => inner_source.span(),
+                Some(span) => span.join(inner_source.span()).unwrap_or(span),
+            });
+        }
+        combined_span.unwrap_or_else(proc_macro2::Span::call_site)
+    }
+
+    /// The target tokens, with their spans set to the combined span of the sources.
+    fn expand(self) -> proc_macro2::TokenStream {
+        let span = self.combined_span();
+        respan_stream(self.target, span)
+    }
+}
+
+/// Sets the span of every token in `stream`, except for nested `copy_span!(...)` invocations,
+/// which are expanded in place using their own sources.
+fn respan_stream(
+    stream: proc_macro2::TokenStream,
+    span: proc_macro2::Span,
+) -> proc_macro2::TokenStream {
+    let mut output = proc_macro2::TokenStream::new();
+    let mut tokens = stream.into_iter().peekable();
+    while let Some(mut token) = tokens.next() {
+        if let Some(nested) = try_expand_nested(&token, &mut tokens) {
+            output.extend(std::iter::once(nested));
+            continue;
+        }
+
+        recursively_set_span(&mut token, span);
+        output.extend(std::iter::once(token));
+    }
+    output
+}
+
+fn is_colon(token: &proc_macro2::TokenTree) -> bool {
+    matches!(token, proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ':')
+}
+
+/// If `token` starts a `copy_span!(...)` invocation, either bare or through a path such as
+/// `$crate::macro_support::copy_span::copy_span!`, consumes the rest of the invocation from
+/// `rest` and returns its expansion, wrapped in an invisible group so that it is treated as a
+/// single expression.
+fn try_expand_nested(
+    token: &proc_macro2::TokenTree,
+    rest: &mut std::iter::Peekable<proc_macro2::token_stream::IntoIter>,
+) -> Option<proc_macro2::TokenTree> {
+    let mut lookahead = rest.clone();
+    let mut segment = token.clone();
+    if is_colon(&segment) {
+        // A leading `::`
+        if !is_colon(&lookahead.next()?) {
+            return None;
+        }
+        segment = lookahead.next()?;
+    }
+
+    // Skip the path segments up to the macro name.
+    loop {
+        let proc_macro2::TokenTree::Ident(ident) = &segment else {
+            return None;
+        };
+        match lookahead.next()? {
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == '!' => {
+                if ident != "copy_span" {
+                    return None;
+                }
+                break;
+            }
+            next if is_colon(&next) => {
+                if !is_colon(&lookahead.next()?) {
+                    return None;
+                }
+                segment = lookahead.next()?;
+            }
+            _ => return None,
+        }
+    }
+
+    let Some(proc_macro2::TokenTree::Group(args)) = lookahead.next() else {
+        return None;
+    };
+    if args.delimiter() == proc_macro2::Delimiter::None {
+        return None;
+    }
+
+    // Nested invocations which fail to parse are left as-is, and will report their own error
+    // when expanded by the compiler.
+    let nested = syn::parse2::<CopySpanInput>(args.stream()).ok()?;
+    *rest = lookahead;
+    Some(proc_macro2::TokenTree::Group(proc_macro2::Group::new(
+        proc_macro2::Delimiter::None,
+        nested.expand(),
+    )))
+}
+
 fn recursively_set_span(token: &mut proc_macro2::TokenTree, span: proc_macro2::Span) {
     match token {
         proc_macro2::TokenTree::Group(group)
@@ -35,14 +141,7 @@ fn recursively_set_span(token: &mut proc_macro2::TokenTree, span: proc_macro2::S
             // within the fragment and to keep the hygiene of its tokens intact.
         }
         proc_macro2::TokenTree::Group(group) => {
-            let new_stream = group
-                .stream()
-                .into_iter()
-                .map(|mut inner_token| {
-                    recursively_set_span(&mut inner_token, span);
-                    inner_token
-                })
-                .collect();
+            let new_stream = respan_stream(group.stream(), span);
 
             let mut new_group = proc_macro2::Group::new(group.delimiter(), new_stream);
             new_group.set_span(span);
@@ -60,35 +159,76 @@ fn recursively_set_span(token: &mut proc_macro2::TokenTree, span: proc_macro2::S
     }
 }
 
+/// Emits the target tokens with the span of the source expressions.
+///
+/// The syntax is `copy_span!(source_1, ..., source_n, target)`, where the target is an expression
+/// whose tokens are all given the span covering the sources. This makes errors within
+/// macro-generated target code point at the user's tokens instead of the macro invocation.
+///
+/// By default, every token of the target receives the same span, so all errors in a large target
+/// point at one location. To attribute parts of the target to different sources, the target may
+/// contain nested `copy_span!(sources..., subtree)` invocations, either bare or through a path
+/// (such as `$crate::macro_support::copy_span::copy_span!`). These are expanded eagerly by the
+/// outer invocation, so each subtree keeps the span of its own sources rather than being
+/// overwritten by the outer span.
 #[proc_macro]
 pub fn copy_span(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let CopySpanInput { sources, target } = syn::parse_macro_input!(input as CopySpanInput);
+    let input = syn::parse_macro_input!(input as CopySpanInput);
+    proc_macro::TokenStream::from(input.expand())
+}
 
-    let mut combined_span = None;
-    for mut inner_source in sources {
-        while let syn::Expr::Group(g) = inner_source {
-            inner_source = *g.expr;
-        }
+#[cfg(test)]
+mod tests {
+    use super::CopySpanInput;
 
-        if combined_span.is_none() {
-            combined_span = Some(inner_source.span());
-        } else {
-            combined_span = Some(
-                combined_span
-                    .unwrap()
-                    .join(inner_source.span())
-                    .unwrap_or(combined_span.unwrap()),
-            );
+    /// The name and starting column of every identifier in `stream`, in order.
+    fn ident_columns(stream: proc_macro2::TokenStream) -> Vec<(String, usize)> {
+        let mut idents = vec![];
+        for token in stream {
+            match token {
+                proc_macro2::TokenTree::Group(group) => {
+                    idents.extend(ident_columns(group.stream()))
+                }
+                proc_macro2::TokenTree::Ident(ident) => {
+                    idents.push((ident.to_string(), ident.span().start().column))
+                }
+                _ => {}
+            }
         }
+        idents
     }
 
-    let output = target
-        .into_iter()
-        .fold(proc_macro2::TokenStream::new(), |mut acc, mut token| {
-            recursively_set_span(&mut token, combined_span.unwrap());
-            acc.extend(std::iter::once(token));
-            acc
-        });
+    fn expand(input: &str) -> proc_macro2::TokenStream {
+        syn::parse_str::<CopySpanInput>(input).unwrap().expand()
+    }
+
+    #[test]
+    fn nested_invocations_keep_their_own_spans() {
+        let input = "outer, (a + ::copy_span::copy_span!(inner, b) + copy_span!(other, c))";
+        assert_eq!(
+            ident_columns(expand(input)),
+            vec![
+                ("a".to_owned(), 0),
+                ("b".to_owned(), input.find("inner").unwrap()),
+                ("c".to_owned(), input.find("other").unwrap()),
+            ]
+        );
+    }
 
-    proc_macro::TokenStream::from(output)
+    #[test]
+    fn other_tokens_take_the_outer_span() {
+        // Neither a different macro under a `copy_span` path nor an invocation that fails to
+        // parse is expanded.
+        let input = "outer, copy_span::other!(x) + copy_span!() + copy_span";
+        assert_eq!(
+            ident_columns(expand(input)),
+            vec![
+                ("copy_span".to_owned(), 0),
+                ("other".to_owned(), 0),
+                ("x".to_owned(), 0),
+                ("copy_span".to_owned(), 0),
+                ("copy_span".to_owned(), 0),
+            ]
+        );
+    }
 }
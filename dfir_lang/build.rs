@@ -3,7 +3,9 @@ use std::fs::File;
 use std::io::{BufWriter, Error, ErrorKind, Result, Write};
 use std::path::PathBuf;
 
-use syn::{AttrStyle, Expr, ExprLit, Item, Lit, Member, Meta, MetaNameValue, Path, parse_quote};
+use syn::{
+    AttrStyle, Expr, ExprLit, Item, ItemConst, Lit, Member, Meta, MetaNameValue, Path, parse_quote,
+};
 
 const OPS_PATH: &str = "src/graph/ops";
 
@@ -16,25 +18,20 @@ fn main() {
 
     hydro_build_utils::emit_nightly_configuration!();
 
+    let ops = parse_ops().expect("Failed to parse operator definitions.");
+    generate_op_metadata(&ops).expect("Failed to generate operator metadata.");
+
     if std::env::var_os(DFIR_GENERATE_DOCS).is_some()
-        && let Err(err) = generate_op_docs()
+        && let Err(err) = generate_op_docs(&ops)
     {
         eprintln!("{} error: {:?}", file!(), err);
     }
 }
 
-fn generate_op_docs() -> Result<()> {
-    let docgen_dir = PathBuf::from_iter([std::env!("CARGO_MANIFEST_DIR"), "../docs/docgen"]);
-    // Clear all existing docs.
-    for old_doc in std::fs::read_dir(&docgen_dir)? {
-        let old_entry = old_doc?;
-        if old_entry.file_type()?.is_file()
-            && old_entry.file_name().to_string_lossy().ends_with(".md")
-        {
-            std::fs::remove_file(old_entry.path())?;
-        }
-    }
-
+/// Parses every operator definition in [`OPS_PATH`], returning each operator's name along with
+/// the `const` item that defines it.
+fn parse_ops() -> Result<Vec<(String, ItemConst)>> {
+    let mut ops = Vec::new();
     for op_file in std::fs::read_dir(OPS_PATH)? {
         let op_file = op_file?;
         if !op_file.file_type()?.is_file()
@@ -51,7 +48,7 @@ fn generate_op_docs() -> Result<()> {
             let Item::Const(item_const) = item else {
                 continue;
             };
-            let Expr::Struct(expr_struct) = *item_const.expr else {
+            let Expr::Struct(expr_struct) = &*item_const.expr else {
                 continue;
             };
             if identity::<Path>(parse_quote!(OperatorConstraints)) != expr_struct.path {
@@ -70,60 +67,143 @@ fn generate_op_docs() -> Result<()> {
             else {
                 panic!("Unexpected non-literal or non-str `name` field value.")
             };
-            let op_name = op_name.value();
+            ops.push((op_name.value(), item_const));
+        }
+    }
+    ops.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(ops)
+}
 
-            let docgen_file = docgen_dir.join(format!("{}.md", op_name));
-            eprintln!("{:?}", docgen_file);
-            let mut docgen_write = BufWriter::new(File::create(docgen_file)?);
-            writeln!(docgen_write, "<!-- GENERATED BY {} -->", file!())?;
+/// Returns the lines of the outer `///` doc comment on `item_const`.
+fn doc_lines(item_const: &ItemConst) -> impl Iterator<Item = String> + '_ {
+    item_const.attrs.iter().filter_map(|attr| {
+        let AttrStyle::Outer = attr.style else {
+            return None;
+        };
+        let Meta::NameValue(MetaNameValue {
+            path,
+            eq_token: _,
+            value,
+        }) = &attr.meta
+        else {
+            return None;
+        };
+        if !path.is_ident("doc") {
+            return None;
+        }
+        let Expr::Lit(ExprLit {
+            attrs: _,
+            lit: Lit::Str(doc_lit_str),
+        }) = value
+        else {
+            return None;
+        };
+        let doc_str = doc_lit_str.value();
+        Some(doc_str.strip_prefix(' ').unwrap_or(&*doc_str).to_owned())
+    })
+}
 
-            let mut in_hf_doctest = false;
-            for attr in item_const.attrs.iter() {
-                let AttrStyle::Outer = attr.style else {
-                    continue;
-                };
-                let Meta::NameValue(MetaNameValue {
-                    path,
-                    eq_token: _,
-                    value,
-                }) = &attr.meta
-                else {
+fn generate_op_docs(ops: &[(String, ItemConst)]) -> Result<()> {
+    let docgen_dir = PathBuf::from_iter([std::env!("CARGO_MANIFEST_DIR"), "../docs/docgen"]);
+    // Clear all existing docs.
+    for old_doc in std::fs::read_dir(&docgen_dir)? {
+        let old_entry = old_doc?;
+        if old_entry.file_type()?.is_file()
+            && old_entry.file_name().to_string_lossy().ends_with(".md")
+        {
+            std::fs::remove_file(old_entry.path())?;
+        }
+    }
+
+    for (op_name, item_const) in ops {
+        let docgen_file = docgen_dir.join(format!("{}.md", op_name));
+        eprintln!("{:?}", docgen_file);
+        let mut docgen_write = BufWriter::new(File::create(docgen_file)?);
+        writeln!(docgen_write, "<!-- GENERATED BY {} -->", file!())?;
+
+        let mut in_hf_doctest = false;
+        for doc_str in doc_lines(item_const) {
+            // At this point we know we have a `#[doc = "..."]`.
+            if doc_str.trim_start().starts_with("```") {
+                if in_hf_doctest {
+                    in_hf_doctest = false;
+                    writeln!(docgen_write, "{}", DOCTEST_SUFFIX)?;
+                    // Output `doc_str` below.
+                } else if doc_str.trim() == "```dfir" {
+                    in_hf_doctest = true;
+
+                    writeln!(docgen_write, "```rust")?;
+                    writeln!(docgen_write, "{}", DOCTEST_PREFIX)?;
                     continue;
-                };
-                if !path.is_ident("doc") {
+                } else if doc_str.trim() == "```rustbook" {
+                    writeln!(docgen_write, "```rust")?;
                     continue;
                 }
-                let Expr::Lit(ExprLit { attrs: _, lit }) = value else {
-                    continue;
-                };
-                let Lit::Str(doc_lit_str) = lit else {
-                    continue;
-                };
-                // At this point we know we have a `#[doc = "..."]`.
-                let doc_str = doc_lit_str.value();
-                let doc_str = doc_str.strip_prefix(' ').unwrap_or(&*doc_str);
-                if doc_str.trim_start().starts_with("```") {
-                    if in_hf_doctest {
-                        in_hf_doctest = false;
-                        writeln!(docgen_write, "{}", DOCTEST_SUFFIX)?;
-                        // Output `doc_str` below.
-                    } else if doc_str.trim() == "```dfir" {
-                        in_hf_doctest = true;
-
-                        writeln!(docgen_write, "```rust")?;
-                        writeln!(docgen_write, "{}", DOCTEST_PREFIX)?;
-                        continue;
-                    } else if doc_str.trim() == "```rustbook" {
-                        writeln!(docgen_write, "```rust")?;
-                        continue;
-                    }
+            }
+            writeln!(docgen_write, "{}", doc_str)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `$OUT_DIR/op_docs.rs`, which contains the [`OperatorDocs`] of each operator, extracted
+/// from the leading paragraphs of its doc comment.
+///
+/// Block-quoted paragraphs before the first code block describe the operator's ports (e.g.
+/// `> 1 input stream, 1 output stream`) and arguments (e.g. `> Arguments: A Rust closure`), and
+/// the first plain paragraph is used as the summary.
+fn generate_op_metadata(ops: &[(String, ItemConst)]) -> Result<()> {
+    let out_path = PathBuf::from(std::env::var_os("OUT_DIR").unwrap()).join("op_docs.rs");
+    let mut write = BufWriter::new(File::create(out_path)?);
+    writeln!(write, "&[")?;
+    for (op_name, item_const) in ops {
+        let mut paragraphs: Vec<String> = Vec::new();
+        let mut current = String::new();
+        for line in doc_lines(item_const) {
+            if line.trim_start().starts_with("```") {
+                break;
+            }
+            if line.trim().is_empty() {
+                if !current.is_empty() {
+                    paragraphs.push(std::mem::take(&mut current));
                 }
-                writeln!(docgen_write, "{}", doc_str)?;
+                continue;
+            }
+            if !current.is_empty() {
+                current.push(' ');
             }
+            current.push_str(line.trim());
         }
+        if !current.is_empty() {
+            paragraphs.push(current);
+        }
+
+        let quoted = |paragraph: &String| {
+            paragraph
+                .strip_prefix('>')
+                .map(|rest| rest.replace(" > ", " ").trim().to_owned())
+        };
+        let ports = paragraphs
+            .iter()
+            .filter_map(quoted)
+            .find(|p| p.contains("input") || p.contains("output"));
+        let arguments = paragraphs
+            .iter()
+            .filter_map(quoted)
+            .find_map(|p| p.strip_prefix("Arguments:").map(|a| a.trim().to_owned()));
+        let summary = paragraphs
+            .iter()
+            .find(|p| !p.starts_with('>'))
+            .cloned()
+            .unwrap_or_default();
 
-        eprintln!("{:?}", op_file.file_name());
+        writeln!(
+            write,
+            "    OperatorDocs {{ name: {:?}, ports: {:?}, arguments: {:?}, summary: {:?} }},",
+            op_name, ports, arguments, summary,
+        )?;
     }
+    writeln!(write, "]")?;
     Ok(())
 }
 
@@ -3,21 +3,30 @@
 use std::borrow::Cow;
 use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::str::FromStr;
 
 use itertools::Itertools;
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
 use syn::spanned::Spanned;
-use syn::{Error, Ident, ItemUse};
+use syn::visit_mut::VisitMut;
+use syn::{Error, Expr, Ident, ItemUse, parse_quote, parse_quote_spanned};
 
 use crate::diagnostic::{Diagnostic, Diagnostics, Level};
 use crate::graph::meta_graph::ResolvedHandoffRef;
-use crate::graph::ops::{DelayType, FloType, Persistence, PortListSpec, RangeTrait};
+use crate::graph::ops::{
+    CHECKPOINT_OPERATORS, DelayType, FloType, OPERATORS, OperatorCategory, Persistence,
+    PortListSpec, RangeTrait,
+};
 use crate::graph::{
     DfirGraph, GraphEdgeId, GraphLoopId, GraphNode, GraphNodeId, HandoffKind, PortIndexValue,
     graph_algorithms,
 };
-use crate::parse::{DfirCode, DfirStatement, Operator, Pipeline};
+use crate::parse::{
+    DefStatement, DfirCode, DfirModule, DfirStatement, ModuleInvocation, ModuleItemKind, Operator,
+    ParamSubstituter, Pipeline,
+};
 use crate::pretty_span::PrettySpan;
 
 #[derive(Clone, Debug)]
@@ -55,6 +64,27 @@ impl VarnameInfo {
     }
 }
 
+/// The module currently being expanded by [`FlatGraphBuilder`], see
+/// [`FlatGraphBuilder::add_module`].
+#[derive(Debug)]
+struct ModuleScope {
+    /// Path of the module, e.g. `my_module`, for diagnostics and to detect recursive modules.
+    path: String,
+    /// Span of the `mod my_module(...)` invocation.
+    invocation_span: Span,
+    /// Prefix added to every variable name inside the module, so that they do not collide with
+    /// names outside the module (or in other invocations of the same module).
+    varname_prefix: String,
+    /// Input port names, with the node that `mod[port]` reads from and whether it was used.
+    inputs: BTreeMap<String, (GraphNodeId, bool)>,
+    /// Output port names, with the node that `[port]mod` writes to and whether it was used.
+    outputs: BTreeMap<String, (GraphNodeId, bool)>,
+    /// Parameters, substituted into operator arguments.
+    params: BTreeMap<Ident, Expr>,
+    /// The enclosing module, if this module was invoked from inside another module.
+    parent: Option<Box<ModuleScope>>,
+}
+
 /// Wraper around [`DfirGraph`] to build a flat graph from AST code.
 #[derive(Debug, Default)]
 pub struct FlatGraphBuilder {
@@ -71,9 +101,26 @@ pub struct FlatGraphBuilder {
     /// Use statements.
     uses: Vec<ItemUse>,
 
-    /// If the flat graph is being loaded as a module, then two initial ModuleBoundary nodes are inserted into the graph. One
-    /// for the input into the module and one for the output out of the module.
-    module_boundary_nodes: Option<(GraphNodeId, GraphNodeId)>,
+    /// Module code available to `mod my_module(...)`, by path. See [`Self::add_module`].
+    modules: BTreeMap<String, TokenStream>,
+    /// The module currently being expanded, if any.
+    module_scope: Option<ModuleScope>,
+    /// Number of module invocations expanded so far, used to generate unique varname prefixes.
+    module_invocations: usize,
+
+    /// Pipeline functions defined with `def name(...) = ...;`, by name.
+    defs: BTreeMap<String, DefStatement>,
+    /// Names of the pipeline functions currently being instantiated, to detect recursion.
+    def_stack: Vec<String>,
+
+    /// Set while adding a statement annotated with `#[allow(unused)]`.
+    allow_unused: bool,
+    /// Operators added by statements annotated with `#[allow(unused)]`, which are exempt from
+    /// [`Self::warn_unused_outputs`].
+    allow_unused_nodes: BTreeSet<GraphNodeId>,
+    /// Set while adding a statement annotated with `#[checkpoint]`: the span of the attribute,
+    /// and the checkpointable operators (see [`CHECKPOINT_OPERATORS`]) added so far.
+    checkpoint: Option<(Span, Vec<GraphNodeId>)>,
 }
 
 /// Output of [`FlatGraphBuilder::build`].
@@ -129,11 +176,56 @@ impl FlatGraphBuilder {
         current_loop: Option<GraphLoopId>,
         operator_tag: Option<&str>,
     ) {
-        for stmt in dfir.statements {
+        self.add_statements(dfir.statements, current_loop, operator_tag);
+    }
+
+    /// Adds a block of statements, such as the statements of a [`DfirCode`] or a `loop { ... }`.
+    fn add_statements(
+        &mut self,
+        statements: Vec<DfirStatement>,
+        current_loop: Option<GraphLoopId>,
+        operator_tag: Option<&str>,
+    ) {
+        // Pipeline functions may be used before they are defined in the block, so define them
+        // first.
+        let (defs, statements): (Vec<_>, Vec<_>) = statements
+            .into_iter()
+            .partition(|stmt| matches!(stmt, DfirStatement::Def(_)));
+        for stmt in defs.into_iter().chain(statements) {
             self.add_statement_internal(stmt, current_loop, operator_tag);
         }
     }
 
+    /// Registers a module, making it available to `mod path(...)` invocations in the surface
+    /// syntax, where `path` is e.g. `my_module` or `my_crate::my_module`.
+    ///
+    /// `module_code` must parse as a [`DfirModule`]: a signature declaring the module's typed input
+    /// ports, parameters, and output ports, followed by its statements. Inside the module,
+    /// `mod[port]` reads from an input port, `[port]mod` writes to an output port, and parameters
+    /// are referenced by name in operator arguments:
+    /// ```text
+    /// mod(in items: u32, param threshold: u32, out small: u32, out large: u32);
+    /// parts = mod[items] -> partition(|x: &u32, [small, large]| if *x < threshold { small } else { large });
+    /// parts[small] -> [small]mod;
+    /// parts[large] -> [large]mod;
+    /// ```
+    ///
+    /// The module is then invoked with one argument per input port (the name of a pipeline) and
+    /// parameter (an expression), in the order they are declared. Output ports are accessed by
+    /// indexing the invocation:
+    /// ```text
+    /// nums = source_iter(0..10);
+    /// split = mod my_module(nums, 5);
+    /// split[small] -> for_each(|x| println!("small: {}", x));
+    /// split[large] -> for_each(|x| println!("large: {}", x));
+    /// ```
+    ///
+    /// Modules that are not registered are loaded from the file `<path>.dfir` relative to the
+    /// crate's `CARGO_MANIFEST_DIR`, with `::` separators mapped to directories.
+    pub fn add_module(&mut self, path: impl Into<String>, module_code: TokenStream) {
+        self.modules.insert(path.into(), module_code);
+    }
+
     /// Add a single [`DfirStatement`] line to this [`DfirGraph`] in the root context.
     pub fn add_statement(&mut self, stmt: DfirStatement) {
         self.add_statement_internal(stmt, None, None);
@@ -171,24 +263,49 @@ impl FlatGraphBuilder {
             }
             DfirStatement::Named(named) => {
                 let stmt_span = named.span();
-                let ends = self.add_pipeline(
-                    named.pipeline,
-                    Some(&named.name),
-                    current_loop,
-                    operator_tag,
-                );
-                self.assign_varname_checked(named.name, stmt_span, ends);
+                let name = self.scoped_varname(named.name);
+                self.helper_apply_attrs(&named.attrs, Some(&name));
+                let ends =
+                    self.add_pipeline(named.pipeline, Some(&name), current_loop, operator_tag);
+                self.helper_finish_checkpoint(&name);
+                self.assign_varname_checked(name, stmt_span, ends);
+                self.allow_unused = false;
             }
             DfirStatement::Pipeline(pipeline_stmt) => {
+                self.helper_apply_attrs(&pipeline_stmt.attrs, None);
                 let ends =
                     self.add_pipeline(pipeline_stmt.pipeline, None, current_loop, operator_tag);
+                self.allow_unused = false;
                 Self::helper_check_unused_port(&mut self.diagnostics, &ends, true);
                 Self::helper_check_unused_port(&mut self.diagnostics, &ends, false);
             }
             DfirStatement::Loop(loop_statement) => {
                 let inner_loop = self.flat_graph.insert_loop(current_loop);
-                for stmt in loop_statement.statements {
-                    self.add_statement_internal(stmt, Some(inner_loop), operator_tag);
+                self.add_statements(loop_statement.statements, Some(inner_loop), operator_tag);
+            }
+            DfirStatement::Def(def) => {
+                let name = def.name.to_string();
+                if let Some(prev_def) = self.defs.get(&name) {
+                    self.diagnostics.push(Diagnostic::spanned(
+                        def.name.span(),
+                        Level::Error,
+                        format!(
+                            "Pipeline function `{}` is already defined: {}",
+                            name,
+                            PrettySpan(prev_def.name.span()),
+                        ),
+                    ));
+                } else if OPERATORS.iter().any(|op| op.name == name) {
+                    self.diagnostics.push(Diagnostic::spanned(
+                        def.name.span(),
+                        Level::Error,
+                        format!(
+                            "Pipeline function `{}` conflicts with the operator of the same name.",
+                            name,
+                        ),
+                    ));
+                } else {
+                    self.defs.insert(name, def);
                 }
             }
         }
@@ -294,6 +411,7 @@ impl FlatGraphBuilder {
             }
             Pipeline::Name(pipeline_name) => {
                 let (inn_port, ident, out_port) = PortIndexValue::from_ported(pipeline_name);
+                let ident = self.scoped_varname(ident);
 
                 // Mingwei: We could lookup non-forward references immediately, but easier to just
                 // have one consistent code path: `GraphDet::Undetermined`.
@@ -303,28 +421,63 @@ impl FlatGraphBuilder {
                 }
             }
             Pipeline::ModuleBoundary(pipeline_name) => {
-                let Some((input_node, output_node)) = self.module_boundary_nodes else {
-                    self.diagnostics.push(
-                        Error::new(
-                            pipeline_name.span(),
-                            "`mod` is only usable inside of a module.",
-                        )
-                        .into(),
-                    );
-
+                let span = pipeline_name.span();
+                let (inn_port, _, out_port) = PortIndexValue::from_ported(pipeline_name);
+                let Some(scope) = self.module_scope.as_mut() else {
+                    self.diagnostics
+                        .push(Error::new(span, "`mod` is only usable inside of a module.").into());
                     return Ends {
                         inn: None,
                         out: None,
                     };
                 };
+                if !inn_port.is_specified() && !out_port.is_specified() {
+                    self.diagnostics.push(Diagnostic::spanned(
+                        span,
+                        Level::Error,
+                        "`mod` must be indexed by a port name, either `mod[input_port]` or `[output_port]mod`.",
+                    ));
+                }
 
-                let (inn_port, _, out_port) = PortIndexValue::from_ported(pipeline_name);
-
+                let mut lookup = |port: PortIndexValue, is_input: bool| {
+                    if !port.is_specified() {
+                        return None;
+                    }
+                    let (ports, kind) = if is_input {
+                        (&mut scope.inputs, "input")
+                    } else {
+                        (&mut scope.outputs, "output")
+                    };
+                    let port_name = port.to_string();
+                    if let Some((node_id, used)) = ports.get_mut(&port_name) {
+                        *used = true;
+                        Some((
+                            PortIndexValue::Elided(Some(span)),
+                            GraphDet::Determined(*node_id),
+                        ))
+                    } else {
+                        self.diagnostics.push(Diagnostic::spanned(
+                            port.span(),
+                            Level::Error,
+                            format!(
+                                "Module `{}` has no {} port `{}`, expected one of: {}.",
+                                scope.path,
+                                kind,
+                                port_name,
+                                ports.keys().map(|name| format!("`{}`", name)).join(", "),
+                            ),
+                        ));
+                        None
+                    }
+                };
                 Ends {
-                    inn: Some((inn_port, GraphDet::Determined(output_node))),
-                    out: Some((out_port, GraphDet::Determined(input_node))),
+                    inn: lookup(inn_port, false),
+                    out: lookup(out_port, true),
                 }
             }
+            Pipeline::Module(invocation) => {
+                self.add_module_invocation(invocation, current_loop, operator_tag)
+            }
             Pipeline::Link(pipeline_link) => {
                 // Add the nested LHS and RHS of this link.
                 let lhs_ends = self.add_pipeline(
@@ -340,8 +493,27 @@ impl FlatGraphBuilder {
                     operator_tag,
                 );
 
+                // `-> [bounded(capacity)] next_op` places a `bounded_handoff(capacity)` on the edge.
+                if let Some(bound) = pipeline_link.bound {
+                    let span = bound.span();
+                    let capacity = bound.capacity;
+                    let operator: Operator =
+                        parse_quote_spanned!(span=> bounded_handoff(#capacity));
+                    let (node_id, bound_ends) =
+                        self.add_operator(current_varname, current_loop, operator, Some(span));
+                    if let Some(operator_tag) = operator_tag {
+                        self.flat_graph
+                            .set_operator_tag(node_id, operator_tag.to_owned());
+                    }
+                    let lhs_ends = self.connect_ends(lhs_ends, bound_ends);
+                    return self.connect_ends(lhs_ends, rhs_ends);
+                }
+
                 self.connect_ends(lhs_ends, rhs_ends)
             }
+            Pipeline::Operator(operator) if self.defs.contains_key(&operator.name_string()) => {
+                self.add_def_instance(operator, current_varname, current_loop, operator_tag)
+            }
             Pipeline::Operator(operator) => {
                 let op_span = Some(operator.span());
                 let (node_id, ends) =
@@ -355,6 +527,370 @@ impl FlatGraphBuilder {
         }
     }
 
+    /// Returns `varname` with the current module's varname prefix, if inside a module.
+    fn scoped_varname(&self, varname: Ident) -> Ident {
+        match &self.module_scope {
+            Some(scope) => Ident::new(
+                &format!("{}{}", scope.varname_prefix, varname),
+                varname.span(),
+            ),
+            None => varname,
+        }
+    }
+
+    /// Loads the code of the module at `path`, either registered via [`Self::add_module`] or from
+    /// a `.dfir` file.
+    fn load_module(&mut self, path: &str, span: Span) -> Option<TokenStream> {
+        if let Some(module_code) = self.modules.get(path) {
+            return Some(module_code.clone());
+        }
+
+        let mut file_path =
+            PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default());
+        file_path.extend(path.split("::"));
+        file_path.set_extension("dfir");
+        let module_code = std::fs::read_to_string(&file_path)
+            .map_err(|err| err.to_string())
+            .and_then(|source| TokenStream::from_str(&source).map_err(|err| err.to_string()));
+        match module_code {
+            Ok(module_code) => {
+                self.modules.insert(path.to_owned(), module_code.clone());
+                Some(module_code)
+            }
+            Err(err) => {
+                self.diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    format!(
+                        "Cannot find module `{}`; it was not registered and could not be loaded from `{}`: {}",
+                        path,
+                        file_path.display(),
+                        err,
+                    ),
+                ));
+                None
+            }
+        }
+    }
+
+    /// Expands a module invocation, `mod my_module(arg_port, config_expr)`, adding the module's
+    /// statements to the graph. Returns [`Ends`] whose output is indexed by the module's output
+    /// port names.
+    fn add_module_invocation(
+        &mut self,
+        invocation: ModuleInvocation,
+        current_loop: Option<GraphLoopId>,
+        operator_tag: Option<&str>,
+    ) -> Ends {
+        let no_ends = Ends {
+            inn: None,
+            out: None,
+        };
+        let span = invocation.span();
+        let path = invocation
+            .path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .join("::");
+
+        let mut ancestor = self.module_scope.as_ref();
+        while let Some(scope) = ancestor {
+            if scope.path == path {
+                self.diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    format!("Module `{}` cannot be used recursively.", path),
+                ));
+                return no_ends;
+            }
+            ancestor = scope.parent.as_deref();
+        }
+
+        let Some(module_code) = self.load_module(&path, span) else {
+            return no_ends;
+        };
+        let DfirModule {
+            signature,
+            statements,
+        } = match syn::parse2(module_code) {
+            Ok(module) => module,
+            Err(err) => {
+                self.diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    format!("Failed to parse module `{}`: {}", path, err),
+                ));
+                return no_ends;
+            }
+        };
+
+        // Bind arguments to input ports and params, in declaration order.
+        let bound_items = signature
+            .items
+            .iter()
+            .filter(|item| ModuleItemKind::Output != item.kind)
+            .collect::<Vec<_>>();
+        if bound_items.len() != invocation.args.len() {
+            let unbound = bound_items
+                .iter()
+                .skip(invocation.args.len())
+                .map(|item| {
+                    let kind = match item.kind {
+                        ModuleItemKind::Input => "input port",
+                        _ => "param",
+                    };
+                    format!("{} `{}`", kind, item.name)
+                })
+                .collect::<Vec<_>>();
+            self.diagnostics.push(Diagnostic::spanned(
+                span,
+                Level::Error,
+                if unbound.is_empty() {
+                    format!(
+                        "Module `{}` takes {} arguments but {} were given.",
+                        path,
+                        bound_items.len(),
+                        invocation.args.len(),
+                    )
+                } else {
+                    format!(
+                        "Module `{}` is missing arguments for unbound {}.",
+                        path,
+                        unbound.join(", "),
+                    )
+                },
+            ));
+            return no_ends;
+        }
+
+        let mut inputs = BTreeMap::new();
+        let mut params = BTreeMap::new();
+        for (item, mut arg) in bound_items.into_iter().zip(invocation.args) {
+            let (name, ty) = (&item.name, &item.ty);
+            match item.kind {
+                ModuleItemKind::Input => {
+                    let Expr::Path(arg_path) = &arg else {
+                        self.diagnostics.push(Diagnostic::spanned(
+                            arg.span(),
+                            Level::Error,
+                            format!(
+                                "Expected the name of a pipeline for input port `{}` of module `{}`.",
+                                name, path,
+                            ),
+                        ));
+                        return no_ends;
+                    };
+                    let Some(arg_ident) = arg_path.path.get_ident() else {
+                        self.diagnostics.push(Diagnostic::spanned(
+                            arg.span(),
+                            Level::Error,
+                            format!(
+                                "Expected the name of a pipeline for input port `{}` of module `{}`.",
+                                name, path,
+                            ),
+                        ));
+                        return no_ends;
+                    };
+                    let arg_ident = self.scoped_varname(arg_ident.clone());
+                    let (node_id, port_ends) = self.add_operator(
+                        None,
+                        current_loop,
+                        parse_quote_spanned!(span=> identity::<#ty>()),
+                        Some(span),
+                    );
+                    self.connect_ends(
+                        Ends {
+                            inn: None,
+                            out: Some((
+                                PortIndexValue::Elided(Some(arg_ident.span())),
+                                GraphDet::Undetermined(arg_ident),
+                            )),
+                        },
+                        port_ends,
+                    );
+                    inputs.insert(name.to_string(), (node_id, false));
+                }
+                ModuleItemKind::Param => {
+                    // Arguments may themselves reference params of an enclosing module.
+                    if let Some(scope) = &self.module_scope {
+                        ParamSubstituter::new(&scope.params).visit_expr_mut(&mut arg);
+                    }
+                    params.insert(
+                        name.clone(),
+                        parse_quote_spanned!(span=> { let #name: #ty = #arg; #name }),
+                    );
+                }
+                ModuleItemKind::Output => unreachable!(),
+            }
+        }
+
+        // Each output port is an `identity()` which feeds into the output boundary, indexed by the
+        // port name.
+        let mut outputs = BTreeMap::new();
+        let mut boundary = None;
+        for item in signature
+            .items
+            .iter()
+            .filter(|item| ModuleItemKind::Output == item.kind)
+        {
+            let (name, ty) = (&item.name, &item.ty);
+            let boundary = *boundary.get_or_insert_with(|| {
+                self.flat_graph.insert_node(
+                    GraphNode::ModuleBoundary {
+                        input: false,
+                        import_expr: span,
+                    },
+                    None,
+                    current_loop,
+                )
+            });
+            let (node_id, _) = self.add_operator(
+                None,
+                current_loop,
+                parse_quote_spanned!(span=> identity::<#ty>()),
+                Some(span),
+            );
+            self.links.push(Ends {
+                out: Some((
+                    PortIndexValue::Elided(Some(span)),
+                    GraphDet::Determined(node_id),
+                )),
+                inn: Some((
+                    PortIndexValue::Path(parse_quote!(#name)),
+                    GraphDet::Determined(boundary),
+                )),
+            });
+            outputs.insert(name.to_string(), (node_id, false));
+        }
+
+        let varname_prefix = format!(
+            "{}{}_{}__",
+            self.module_scope
+                .as_ref()
+                .map_or("", |scope| &*scope.varname_prefix),
+            path.replace("::", "_"),
+            self.module_invocations,
+        );
+        self.module_invocations += 1;
+        let parent = self.module_scope.take().map(Box::new);
+        self.module_scope = Some(ModuleScope {
+            path,
+            invocation_span: span,
+            varname_prefix,
+            inputs,
+            outputs,
+            params,
+            parent,
+        });
+
+        for stmt in statements {
+            self.add_statement_internal(stmt, current_loop, operator_tag);
+        }
+
+        let scope = self.module_scope.take().unwrap();
+        self.module_scope = scope.parent.map(|parent| *parent);
+        for (ports, kind, action) in [
+            (&scope.inputs, "input", "read"),
+            (&scope.outputs, "output", "written"),
+        ] {
+            let unbound = ports
+                .iter()
+                .filter(|(_, (_, used))| !used)
+                .map(|(name, _)| format!("`{}`", name))
+                .collect::<Vec<_>>();
+            if !unbound.is_empty() {
+                self.diagnostics.push(Diagnostic::spanned(
+                    scope.invocation_span,
+                    Level::Error,
+                    format!(
+                        "Module `{}` has unbound {} ports which are never {}: {}.",
+                        scope.path,
+                        kind,
+                        action,
+                        unbound.join(", "),
+                    ),
+                ));
+            }
+        }
+
+        Ends {
+            inn: None,
+            out: boundary.map(|boundary| {
+                (
+                    PortIndexValue::Elided(Some(span)),
+                    GraphDet::Determined(boundary),
+                )
+            }),
+        }
+    }
+
+    /// Instantiates the pipeline function invoked by `invocation`, `name::<generic_args>(args)`,
+    /// adding its pipeline to the graph. Returns the [`Ends`] of the pipeline.
+    fn add_def_instance(
+        &mut self,
+        invocation: Operator,
+        current_varname: Option<&Ident>,
+        current_loop: Option<GraphLoopId>,
+        operator_tag: Option<&str>,
+    ) -> Ends {
+        let no_ends = Ends {
+            inn: None,
+            out: None,
+        };
+        let span = invocation.span();
+        let name = invocation.name_string();
+        let def = &self.defs[&name];
+
+        let generic_args = invocation
+            .type_arguments()
+            .map(|args| args.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let args = invocation.args.iter().cloned().collect::<Vec<_>>();
+        if def.generics.len() != generic_args.len() || def.params.len() != args.len() {
+            self.diagnostics.push(Diagnostic::spanned(
+                span,
+                Level::Error,
+                format!(
+                    "Pipeline function `{}` takes {} generic arguments and {} arguments but {} and {} were given.",
+                    name,
+                    def.generics.len(),
+                    def.params.len(),
+                    generic_args.len(),
+                    args.len(),
+                ),
+            ));
+            return no_ends;
+        }
+        if self.def_stack.contains(&name) {
+            self.diagnostics.push(Diagnostic::spanned(
+                span,
+                Level::Error,
+                format!("Pipeline function `{}` cannot be used recursively.", name),
+            ));
+            return no_ends;
+        }
+
+        let pipeline = match def.instantiate(&generic_args, &args) {
+            Ok(pipeline) => pipeline,
+            Err(err) => {
+                self.diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    format!(
+                        "Failed to instantiate pipeline function `{}`: {}",
+                        name, err
+                    ),
+                ));
+                return no_ends;
+            }
+        };
+        self.def_stack.push(name);
+        let ends = self.add_pipeline(pipeline, current_varname, current_loop, operator_tag);
+        self.def_stack.pop();
+        ends
+    }
+
     /// Connects two [`Ends`] together. Returns the outer [`Ends`] for the connection.
     ///
     /// Links the inner ends together by adding it to `self.links`.
@@ -378,14 +914,31 @@ impl FlatGraphBuilder {
         &mut self,
         current_varname: Option<&Ident>,
         current_loop: Option<GraphLoopId>,
-        operator: Operator,
+        mut operator: Operator,
         op_span: Option<Span>,
     ) -> (GraphNodeId, Ends) {
+        if let Some(scope) = &self.module_scope {
+            if let Err(err) = operator.substitute_params(&scope.params) {
+                self.diagnostics.push(err.into());
+            }
+            for singleton_ref in operator.singletons_referenced.iter_mut() {
+                singleton_ref.ident = self.scoped_varname(singleton_ref.ident.clone());
+            }
+        }
+        let is_checkpointable = CHECKPOINT_OPERATORS.contains(&&*operator.name_string());
         let node_id = self.flat_graph.insert_node(
             GraphNode::Operator(operator),
             current_varname.cloned(),
             current_loop,
         );
+        if self.allow_unused {
+            self.allow_unused_nodes.insert(node_id);
+        }
+        if let Some((_, checkpointed)) = &mut self.checkpoint
+            && is_checkpointable
+        {
+            checkpointed.push(node_id);
+        }
         let ends = Ends {
             inn: Some((
                 PortIndexValue::Elided(op_span),
@@ -629,6 +1182,7 @@ impl FlatGraphBuilder {
         self.make_operator_instances();
         self.check_operator_errors();
         self.warn_unused_port_indexing();
+        self.warn_unused_outputs();
         self.check_loop_errors();
     }
 
@@ -727,15 +1281,16 @@ impl FlatGraphBuilder {
                         out_degree,
                         op_constraints.hard_range_out,
                         &mut self.diagnostics,
-                    ) || emit_arity_error(
-                        operator.span(),
-                        &op_name,
-                        false,
-                        false,
-                        out_degree,
-                        op_constraints.soft_range_out,
-                        &mut self.diagnostics,
-                    );
+                    ) || self.allow_unused_nodes.contains(&node_id)
+                        || emit_arity_error(
+                            operator.span(),
+                            &op_name,
+                            false,
+                            false,
+                            out_degree,
+                            op_constraints.soft_range_out,
+                            &mut self.diagnostics,
+                        );
 
                     fn emit_port_error<'a>(
                         op_span: Span,
@@ -876,7 +1431,9 @@ impl FlatGraphBuilder {
                 }
                 GraphNode::Handoff { kind, src_span, .. } => {
                     // Validate arity: handoff must have exactly 1 input and 1 output.
+                    let is_bounded = self.flat_graph.handoff_capacity(node_id).is_some();
                     let op_name = match kind {
+                        HandoffKind::Vec if is_bounded => "bounded_handoff",
                         HandoffKind::Vec => "handoff",
                         HandoffKind::Singleton => "singleton",
                         HandoffKind::Optional => "optional",
@@ -898,7 +1455,9 @@ impl FlatGraphBuilder {
                         false,
                         true,
                         out_degree,
-                        &(0..=1), // Handoffs may be no-output, for use only by ref.
+                        // Handoffs may be no-output, for use only by ref. Bounded handoffs must be
+                        // drained, or their upstream would be deferred forever.
+                        if is_bounded { &(1..=1) } else { &(0..=1) },
                         &mut self.diagnostics,
                     );
                 }
@@ -968,6 +1527,116 @@ impl FlatGraphBuilder {
         }
     }
 
+    /// Warns about multi-output operators with no outputs connected, as that usually indicates a
+    /// bug. Statements annotated with `#[allow(unused)]` are exempt, as is `demux_enum`, which
+    /// instead fails to type-check unless every variant of the enum has an output.
+    ///
+    /// Other operators with too few outputs (such as a dangling `tee()`) are already warned about
+    /// by [`Self::check_operator_errors`]. Names which are never referenced are not warned about,
+    /// since naming a pipeline which ends in a sink is common for readability.
+    fn warn_unused_outputs(&mut self) {
+        for (node_id, node) in self.flat_graph.nodes() {
+            let GraphNode::Operator(operator) = node else {
+                continue;
+            };
+            let Some(op_inst) = self.flat_graph.node_op_inst(node_id) else {
+                continue;
+            };
+            let op_constraints = op_inst.op_constraints;
+            if self.allow_unused_nodes.contains(&node_id)
+                || !op_constraints
+                    .categories
+                    .contains(&OperatorCategory::MultiOut)
+                || 0 != self.flat_graph.node_degree_out(node_id)
+                // `demux_enum` type-checks that every variant has an output, so it can only have
+                // no outputs if the enum has no variants, in which case nothing is dropped.
+                || "demux_enum" == op_constraints.name
+                // Otherwise an arity diagnostic was already emitted.
+                || !op_constraints.hard_range_out.contains(&0)
+                || !op_constraints.soft_range_out.contains(&0)
+            {
+                continue;
+            }
+            self.diagnostics.push(Diagnostic::spanned(
+                operator.span(),
+                Level::Warning,
+                format!(
+                    "`{}` has no outputs connected, so all of its items are dropped. Add `#[allow(unused)]` to the statement if this is intended.",
+                    operator.name_string(),
+                ),
+            ));
+        }
+    }
+
+    /// Finishes the `#[checkpoint]` statement named `name`, if any, checkpointing its operator
+    /// under the statement name. Emits an error unless the statement has exactly one
+    /// checkpointable operator, so that the key of each operator's state does not depend on the
+    /// rest of the pipeline.
+    fn helper_finish_checkpoint(&mut self, name: &Ident) {
+        let Some((attr_span, checkpointed)) = self.checkpoint.take() else {
+            return;
+        };
+        match checkpointed[..] {
+            [node_id] => self
+                .flat_graph
+                .set_operator_checkpoint(node_id, name.to_string()),
+            [] => self.diagnostics.push(Diagnostic::spanned(
+                attr_span,
+                Level::Error,
+                format!(
+                    "`#[checkpoint]` statement `{}` has no operator with state to checkpoint, expected one of: {}.",
+                    name,
+                    CHECKPOINT_OPERATORS.iter().map(|op| format!("`{}`", op)).join(", "),
+                ),
+            )),
+            [_, ..] => self.diagnostics.push(Diagnostic::spanned(
+                attr_span,
+                Level::Error,
+                format!(
+                    "`#[checkpoint]` statement `{}` has {} operators with state to checkpoint, split them into separate named statements so each is checkpointed under its own name.",
+                    name,
+                    checkpointed.len(),
+                ),
+            )),
+        }
+    }
+
+    /// Applies the attributes of a statement, named `name` if it is a named statement, to the
+    /// operators added for it: `#[allow(unused)]` and `#[checkpoint]`.
+    fn helper_apply_attrs(&mut self, attrs: &[syn::Attribute], name: Option<&Ident>) {
+        for attr in attrs {
+            if attr.path().is_ident("checkpoint") && matches!(attr.meta, syn::Meta::Path(_)) {
+                match name {
+                    Some(_) => self.checkpoint = Some((attr.span(), Vec::new())),
+                    None => self.diagnostics.push(Diagnostic::spanned(
+                        attr.span(),
+                        Level::Error,
+                        "`#[checkpoint]` is only supported on named statements, whose name identifies the checkpointed state.",
+                    )),
+                }
+                continue;
+            }
+
+            let lints = attr.path().is_ident("allow").then(|| {
+                attr.parse_args_with(
+                    syn::punctuated::Punctuated::<Ident, syn::Token![,]>::parse_terminated,
+                )
+            });
+            match lints {
+                Some(Ok(lints)) if lints.iter().all(|lint| lint == "unused") => {
+                    self.allow_unused = true;
+                }
+                _ => {
+                    self.diagnostics.push(Diagnostic::spanned(
+                        attr.span(),
+                        Level::Error,
+                        "Unsupported attribute, expected `#[allow(unused)]` or `#[checkpoint]`.",
+                    ));
+                }
+            }
+        }
+    }
+
     /// Emit a warning to `diagnostics` for an unused port (i.e. if the port is specified for
     /// reason).
     fn helper_check_unused_port(diagnostics: &mut Diagnostics, ends: &Ends, is_in: bool) {
@@ -1323,6 +1992,165 @@ mod test {
         assert_eq!(Some(outer_loop), flat_graph.loop_parent(inner_loop));
     }
 
+    /// Test that a registered module is expanded, with its parameter substituted and its ports
+    /// connected to the pipelines at the use site.
+    #[test]
+    fn test_module_invocation() {
+        let mut builder = FlatGraphBuilder::new();
+        builder.add_module(
+            "split",
+            quote::quote! {
+                mod(in items: u32, param threshold: u32, out small: u32, out large: u32);
+                parts = mod[items] -> partition(|x: &u32, [small, large]| if *x < threshold { small } else { large });
+                parts[small] -> [small]mod;
+                parts[large] -> [large]mod;
+            },
+        );
+        builder.add_dfir(
+            parse_quote! {
+                nums = source_iter(0..10_u32);
+                parts = mod split(nums, 5);
+                parts[small] -> for_each(std::mem::drop);
+                parts[large] -> for_each(std::mem::drop);
+            },
+            None,
+            None,
+        );
+
+        let output = builder.build().unwrap_or_else(|diagnostics| {
+            panic!("Should build without errors, got: {:?}", diagnostics);
+        });
+        let mut flat_graph = output.flat_graph;
+        flat_graph.merge_modules().unwrap();
+
+        let mut op_names = flat_graph
+            .nodes()
+            .filter_map(|(node_id, _)| flat_graph.node_op_inst(node_id))
+            .map(|op_inst| op_inst.op_constraints.name)
+            .collect::<Vec<_>>();
+        op_names.sort_unstable();
+        assert_eq!(
+            vec![
+                "for_each",
+                "for_each",
+                "identity",
+                "identity",
+                "identity",
+                "partition",
+                "source_iter",
+            ],
+            op_names,
+        );
+    }
+
+    /// Test that missing module arguments are reported, listing the unbound ports and params.
+    #[test]
+    fn test_module_invocation_unbound() {
+        let mut builder = FlatGraphBuilder::new();
+        builder.add_module(
+            "split",
+            quote::quote! {
+                mod(in items: u32, param threshold: u32, out small: u32);
+                mod[items] -> filter(|x: &u32| *x < threshold) -> [small]mod;
+            },
+        );
+        builder.add_dfir(
+            parse_quote! {
+                nums = source_iter(0..10_u32);
+                parts = mod split(nums);
+                parts[small] -> for_each(std::mem::drop);
+            },
+            None,
+            None,
+        );
+
+        let diagnostics = builder
+            .build()
+            .err()
+            .expect("Should fail to build with a missing argument.");
+        assert!(
+            diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.message.contains("unbound param `threshold`")),
+            "Expected unbound param diagnostic, got: {:?}",
+            diagnostics,
+        );
+    }
+
+    /// Test that a pipeline function is instantiated at each use, with its generic arguments and
+    /// arguments substituted, including uses before its definition.
+    #[test]
+    fn test_def_instances() {
+        let mut builder = FlatGraphBuilder::new();
+        builder.add_dfir(
+            parse_quote! {
+                source_iter(0..10_u32) -> dedupe_below::<'tick>(5) -> for_each(std::mem::drop);
+                def dedupe_below<P>(limit) = unique::<P>() -> filter(|x: &u32| *x < limit);
+                source_iter(0..10_u32) -> dedupe_below::<'static>(3) -> for_each(std::mem::drop);
+            },
+            None,
+            None,
+        );
+
+        let output = builder.build().unwrap_or_else(|diagnostics| {
+            panic!("Should build without errors, got: {:?}", diagnostics);
+        });
+        let flat_graph = output.flat_graph;
+
+        let mut ops = flat_graph
+            .nodes()
+            .filter_map(|(node_id, _)| flat_graph.node_op_inst(node_id))
+            .map(|op_inst| {
+                let args = op_inst
+                    .arguments_pre
+                    .iter()
+                    .map(|arg| arg.to_token_stream().to_string())
+                    .join(", ");
+                format!("{}({})", op_inst.op_constraints.name, args)
+            })
+            .collect::<Vec<_>>();
+        ops.sort_unstable();
+        assert_eq!(
+            vec![
+                "filter(| x : & u32 | * x < (3))",
+                "filter(| x : & u32 | * x < (5))",
+                "for_each(std :: mem :: drop)",
+                "for_each(std :: mem :: drop)",
+                "source_iter(0 .. 10_u32)",
+                "source_iter(0 .. 10_u32)",
+                "unique()",
+                "unique()",
+            ],
+            ops,
+        );
+    }
+
+    /// Test that invoking a pipeline function with the wrong number of arguments is reported.
+    #[test]
+    fn test_def_instance_arity() {
+        let mut builder = FlatGraphBuilder::new();
+        builder.add_dfir(
+            parse_quote! {
+                def below(limit) = filter(|x: &u32| *x < limit);
+                source_iter(0..10_u32) -> below() -> for_each(std::mem::drop);
+            },
+            None,
+            None,
+        );
+
+        let diagnostics = builder
+            .build()
+            .err()
+            .expect("Should fail to build with a missing argument.");
+        assert!(
+            diagnostics.iter().any(|diagnostic| diagnostic
+                .message
+                .contains("takes 0 generic arguments and 1 arguments")),
+            "Expected arity diagnostic, got: {:?}",
+            diagnostics,
+        );
+    }
+
     /// Test that loop validation (windowing operator required at loop entry) applies to
     /// programmatically-created loop contexts, same as parsed `loop { ... }` blocks.
     #[test]
@@ -1357,4 +2185,74 @@ mod test {
             diagnostics,
         );
     }
+
+    /// Test that `demux_enum` with no outputs does not produce a warning, and that
+    /// `#[allow(unused)]` suppresses warnings about operators with too few outputs.
+    #[test]
+    fn test_warn_unused_outputs() {
+        fn warnings(dfir: DfirCode) -> Vec<String> {
+            let mut builder = FlatGraphBuilder::new();
+            builder.add_dfir(dfir, None, None);
+            let output = builder.build().unwrap_or_else(|diagnostics| {
+                panic!("Should build without errors, got: {:?}", diagnostics);
+            });
+            output
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| Level::Warning == diagnostic.level)
+                .map(|diagnostic| diagnostic.message.clone())
+                .collect()
+        }
+
+        // Naming a pipeline which ends in a sink is fine.
+        let named = warnings(parse_quote! {
+            named = source_iter([1, 2, 3]) -> for_each(std::mem::drop);
+        });
+        assert!(named.is_empty(), "{:?}", named);
+
+        // A `demux_enum` without outputs only type-checks for an enum with no variants.
+        let zero_variants = warnings(parse_quote! {
+            source_iter(std::iter::empty::<Never>()) -> demux_enum::<Never>();
+        });
+        assert!(zero_variants.is_empty(), "{:?}", zero_variants);
+
+        let dangling = warnings(parse_quote! {
+            source_iter([1, 2, 3]) -> tee();
+        });
+        assert_eq!(1, dangling.len(), "{:?}", dangling);
+
+        let allowed = warnings(parse_quote! {
+            #[allow(unused)]
+            source_iter([1, 2, 3]) -> demux_enum::<Shape>();
+            #[allow(unused)]
+            source_iter([4, 5, 6]) -> tee();
+        });
+        assert!(allowed.is_empty(), "{:?}", allowed);
+    }
+
+    /// Test that attributes other than `#[allow(unused)]` are rejected.
+    #[test]
+    fn test_unsupported_attribute() {
+        let mut builder = FlatGraphBuilder::new();
+        builder.add_dfir(
+            parse_quote! {
+                #[inline]
+                source_iter([1, 2, 3]) -> for_each(std::mem::drop);
+            },
+            None,
+            None,
+        );
+
+        let Err(diagnostics) = builder.build() else {
+            panic!("Should fail to build due to an unsupported attribute.");
+        };
+        assert!(
+            diagnostics.iter().any(|diagnostic| {
+                Level::Error == diagnostic.level
+                    && diagnostic.message.contains("Unsupported attribute")
+            }),
+            "Expected an unsupported attribute error, got: {:?}",
+            diagnostics,
+        );
+    }
 }
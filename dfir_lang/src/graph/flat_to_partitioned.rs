@@ -1,6 +1,6 @@
 //! Subgraph partioning algorithm
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use itertools::Itertools;
 use slotmap::{SecondaryMap, SparseSecondaryMap};
@@ -9,6 +9,7 @@ use super::meta_graph::DfirGraph;
 use super::ops::DelayType;
 use super::{
     Color, GraphEdgeId, GraphLoopId, GraphNode, GraphNodeId, GraphSubgraphId, HandoffKind,
+    HandoffReason,
 };
 use crate::diagnostic::{Diagnostic, Level};
 use crate::graph::graph_algorithms::{SubgraphMerge, validate_topo_sort};
@@ -71,7 +72,13 @@ fn find_subgraph_unionfind(
     tick_edges: &SecondaryMap<GraphEdgeId, DelayType>,
     edge_barrier_pairs: &[(GraphNodeId, GraphNodeId)],
     access_group_pairs: &[(GraphNodeId, GraphNodeId)],
-) -> Result<(SubgraphMerge<GraphNodeId>, BTreeSet<GraphEdgeId>), Diagnostic> {
+) -> Result<
+    (
+        SubgraphMerge<GraphNodeId>,
+        BTreeMap<GraphEdgeId, HandoffReason>,
+    ),
+    Diagnostic,
+> {
     // Modality (color) of nodes, push or pull.
     // TODO(mingwei)? This does NOT consider `DelayType` barriers (which generally imply `Pull`),
     // which makes it inconsistant with the final output in `as_code()`. But this doesn't create
@@ -239,6 +246,35 @@ fn find_subgraph_unionfind(
         }
     }
 
+    // Explain why each remaining edge could not be joined.
+    let handoff_edges = handoff_edges
+        .into_iter()
+        .map(|edge_id| {
+            let (src, dst) = partitioned_graph.edge(edge_id);
+            let reason = if let Some(&delay_type) = tick_edges.get(edge_id) {
+                HandoffReason::Delay(delay_type)
+            } else if partitioned_graph.node_loop(src) != partitioned_graph.node_loop(dst) {
+                HandoffReason::LoopBoundary
+            } else if subgraph_unionfind.same_set(src, dst) {
+                HandoffReason::BackEdge
+            } else {
+                // Check the final colors, without inferring any new ones.
+                let mut colors = SparseSecondaryMap::new();
+                for node_id in [src, dst] {
+                    if let Some(&color) = node_color.get(node_id) {
+                        colors.insert(node_id, color);
+                    }
+                }
+                if can_connect_colorize(&mut colors, src, dst) {
+                    HandoffReason::Ordering
+                } else {
+                    HandoffReason::Polarity
+                }
+            };
+            (edge_id, reason)
+        })
+        .collect();
+
     Ok((subgraph_unionfind, handoff_edges))
 }
 
@@ -257,7 +293,7 @@ fn make_subgraphs(
     )?;
 
     // Insert handoffs between subgraphs (or on subgraph self-loop edges)
-    for edge_id in handoff_edges {
+    for (edge_id, reason) in handoff_edges {
         let (src_id, dst_id) = partitioned_graph.edge(edge_id);
 
         // Already has a handoff, no need to insert one.
@@ -274,7 +310,8 @@ fn make_subgraphs(
             src_span: src_node.span(),
             dst_span: dst_node.span(),
         };
-        let (_node_id, out_edge_id) = partitioned_graph.insert_intermediate_node(edge_id, hoff);
+        let (node_id, out_edge_id) = partitioned_graph.insert_intermediate_node(edge_id, hoff);
+        partitioned_graph.set_handoff_reason(node_id, reason);
 
         // Update tick_edges for inserted node.
         if let Some(delay_type) = tick_edges.remove(edge_id) {
@@ -509,6 +546,10 @@ fn mark_tick_boundary_handoffs(
 
 /// Main method for this module. Partitions a flat [`DfirGraph`] into one with subgraphs.
 ///
+/// Each handoff inserted between subgraphs records why it is needed, which can be queried with
+/// [`DfirGraph::handoff_reason`] or rendered by setting
+/// [`WriteConfig::explain_handoffs`](super::WriteConfig::explain_handoffs).
+///
 /// Returns an error if an intra-tick cycle exists in the graph.
 pub fn partition_graph(flat_graph: DfirGraph) -> Result<DfirGraph, Diagnostic> {
     let (mut tick_edges, edge_barrier_pairs) = find_edge_barriers(&flat_graph);
@@ -633,6 +674,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_handoff_reasons() {
+        use crate::graph::{FlatGraphBuilder, FlatGraphBuilderOutput};
+
+        let mut builder = FlatGraphBuilder::new();
+        builder.add_dfir(
+            syn::parse_quote! {
+                t = source_iter([(1, 'a')]) -> tee();
+                // `tee()` pushes into `join()`, which pulls.
+                t -> [0]j;
+                source_iter([(1, 'b')]) -> [1]j;
+                j = join() -> for_each(std::mem::drop);
+                t -> defer_tick() -> for_each(std::mem::drop);
+            },
+            None,
+            None,
+        );
+        let FlatGraphBuilderOutput { flat_graph, .. } =
+            builder.build().expect("should build without errors");
+        let partitioned = partition_graph(flat_graph).expect("should partition without errors");
+
+        let reasons = partitioned
+            .nodes()
+            .filter(|(_, node)| matches!(node, GraphNode::Handoff { .. }))
+            .map(|(hoff_id, _)| partitioned.handoff_reason(hoff_id))
+            .collect::<Vec<_>>();
+        assert_eq!(2, reasons.len(), "{:?}", reasons);
+        assert!(reasons.contains(&Some(HandoffReason::Polarity)));
+        assert!(reasons.contains(&Some(HandoffReason::Delay(DelayType::Tick))));
+    }
+
     fn make_op_node(graph: &mut DfirGraph, loop_ctx: Option<GraphLoopId>) -> GraphNodeId {
         let operator: crate::parse::Operator = syn::parse_quote! { identity() };
         graph.insert_node(GraphNode::Operator(operator), None, loop_ctx)
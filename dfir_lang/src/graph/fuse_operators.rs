@@ -0,0 +1,188 @@
+#![warn(missing_docs)]
+
+use proc_macro2::{Ident, Span};
+use quote::{quote, quote_spanned};
+
+use super::ops::filter::FILTER;
+use super::ops::filter_map::FILTER_MAP;
+use super::ops::map::MAP;
+use super::{DfirGraph, GraphNodeId, PortIndexValue};
+use crate::parse::Operator;
+
+/// If `node_id` is a stateless unary operator (`map`, `filter`, or `filter_map`) which can be
+/// fused with its neighbors.
+fn is_fusible(graph: &DfirGraph, node_id: GraphNodeId) -> bool {
+    let Some(op_inst) = graph.node_op_inst(node_id) else {
+        return false;
+    };
+    [MAP.name, FILTER.name, FILTER_MAP.name].contains(&op_inst.op_constraints.name)
+        && op_inst.singletons_referenced.is_empty()
+        && op_inst.generics.generic_args.is_none()
+        && graph.node_handoff_references(node_id).is_empty()
+        && !graph.node_is_annotated(node_id)
+        && 1 == graph.node_degree_in(node_id)
+        && 1 == graph.node_degree_out(node_id)
+}
+
+/// The successor of `node_id` if both are fusible and directly connected, within the same loop.
+fn fusible_successor(graph: &DfirGraph, node_id: GraphNodeId) -> Option<GraphNodeId> {
+    if !is_fusible(graph, node_id) {
+        return None;
+    }
+    let (edge_id, succ_id) = graph.node_successors(node_id).next()?;
+    let (src_port, dst_port) = graph.edge_ports(edge_id);
+    (is_fusible(graph, succ_id)
+        && matches!(src_port, PortIndexValue::Elided(_))
+        && matches!(dst_port, PortIndexValue::Elided(_))
+        && graph.node_loop(node_id) == graph.node_loop(succ_id))
+    .then_some(succ_id)
+}
+
+/// Replaces the operators of `chain` with a single operator, whose closure calls each of their
+/// closures in turn.
+///
+/// Each closure expression is evaluated once, outside of the fused closure, so that closures
+/// which keep state (`FnMut`) or are produced by a function call behave as they would unfused.
+fn fuse_chain(graph: &mut DfirGraph, chain: &[GraphNodeId]) {
+    let value = Ident::new("__dfir_fused", Span::call_site());
+    let mut all_maps = true;
+    let (bindings, steps): (Vec<_>, Vec<_>) = chain
+        .iter()
+        .enumerate()
+        .map(|(i, &node_id)| {
+            let op_inst = graph.node_op_inst(node_id).unwrap();
+            let func = &op_inst.arguments_pre[0];
+            let func_ident = Ident::new(&format!("__dfir_fused_fn_{}", i), Span::call_site());
+            // A `filter` closure bound by `let` would not be inferred to accept references of any
+            // lifetime, so it is passed through `__dfir_fused_filter` to give it that signature.
+            let func = if FILTER.name == op_inst.op_constraints.name {
+                quote! { __dfir_fused_filter(#func) }
+            } else {
+                quote! { #func }
+            };
+            let binding = quote! {
+                #[allow(unused_mut, reason = "the closure may be `Fn` or `FnMut`")]
+                let mut #func_ident = #func;
+            };
+            let step = match op_inst.op_constraints.name {
+                name if MAP.name == name => quote! {
+                    let #value = #func_ident(#value);
+                },
+                name if FILTER.name == name => {
+                    all_maps = false;
+                    quote! {
+                        if !#func_ident(&#value) {
+                            return ::std::option::Option::None;
+                        }
+                    }
+                }
+                _filter_map => {
+                    all_maps = false;
+                    quote! {
+                        let #value = #func_ident(#value)?;
+                    }
+                }
+            };
+            (binding, step)
+        })
+        .unzip();
+
+    let head = chain[0];
+    let span = graph.node(head).span();
+    let (op_constraints, result) = if all_maps {
+        (&MAP, quote! { #value })
+    } else {
+        (&FILTER_MAP, quote! { ::std::option::Option::Some(#value) })
+    };
+    let name = Ident::new(op_constraints.name, span);
+    let operator: Operator = syn::parse2(quote_spanned! {span=>
+        #name({
+            #[allow(dead_code, reason = "only used if the chain contains a `filter`")]
+            fn __dfir_fused_filter<T: ?Sized, F: FnMut(&T) -> bool>(f: F) -> F {
+                f
+            }
+            #( #bindings )*
+            move |#value| {
+                #( #steps )*
+                #result
+            }
+        })
+    })
+    .expect("Fused operator should parse.");
+
+    let mut op_inst = graph.node_op_inst(head).unwrap().clone();
+    op_inst.op_constraints = op_constraints;
+    op_inst.arguments_pre = operator.args.clone();
+    op_inst.arguments_raw = operator.args_raw.clone();
+    graph.replace_operator(head, operator, op_inst);
+
+    for &node_id in &chain[1..] {
+        graph.remove_intermediate_node(node_id);
+    }
+}
+
+/// Fuses chains of adjacent stateless unary operators (`map`, `filter`, and `filter_map`) into
+/// a single `map` or `filter_map` operator, avoiding the overhead of a separate pull/push stage
+/// for each operator. Must be applied BEFORE subgraph partitioning, i.e. on a flat graph.
+///
+/// Operators which reference singletons, have generic arguments, or are tagged or checkpointed
+/// are not fused.
+pub fn fuse_stateless_operators(graph: &mut DfirGraph) {
+    let heads = graph
+        .node_ids()
+        .filter(|&node_id| {
+            is_fusible(graph, node_id)
+                && graph
+                    .node_predecessor_nodes(node_id)
+                    .all(|pred_id| fusible_successor(graph, pred_id) != Some(node_id))
+        })
+        .collect::<Vec<_>>();
+
+    for head in heads {
+        let mut chain = vec![head];
+        while let Some(succ_id) = fusible_successor(graph, *chain.last().unwrap()) {
+            chain.push(succ_id);
+        }
+        if 1 < chain.len() {
+            fuse_chain(graph, &chain);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{FlatGraphBuilder, FlatGraphBuilderOutput};
+
+    #[test]
+    fn test_fuse_stateless_operators() {
+        let mut builder = FlatGraphBuilder::new();
+        builder.add_dfir(
+            syn::parse_quote! {
+                source_iter(0..10)
+                    -> map(|x| x + 1)
+                    -> filter(|x| x % 2 == 0)
+                    -> map(|x| x * 3)
+                    -> fold(|| 0, |acc: &mut i32, x| *acc += x)
+                    -> map(|x| x - 1)
+                    -> map(|x| x * 2)
+                    -> for_each(|x| println!("{}", x));
+            },
+            None,
+            None,
+        );
+        let FlatGraphBuilderOutput { mut flat_graph, .. } =
+            builder.build().expect("should build without errors");
+        fuse_stateless_operators(&mut flat_graph);
+
+        let names = flat_graph
+            .node_ids()
+            .filter_map(|node_id| flat_graph.node_op_inst(node_id))
+            .map(|op_inst| op_inst.op_constraints.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["source_iter", "filter_map", "fold", "map", "for_each"],
+            names
+        );
+    }
+}
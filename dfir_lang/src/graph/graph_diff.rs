@@ -0,0 +1,466 @@
+#![warn(missing_docs)]
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
+
+use super::graph_write::escape_mermaid;
+use super::{DfirGraph, GraphNodeId};
+
+/// How a node or edge differs between two graphs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DiffKind {
+    /// Present and identical in both graphs.
+    Unchanged,
+    /// Present in both graphs, but the operator (e.g. its arguments) changed. Only used for nodes.
+    Changed,
+    /// Only present in the `before` graph.
+    Removed,
+    /// Only present in the `after` graph.
+    Added,
+}
+impl DiffKind {
+    fn sigil(self) -> char {
+        match self {
+            DiffKind::Unchanged => ' ',
+            DiffKind::Changed => '~',
+            DiffKind::Removed => '-',
+            DiffKind::Added => '+',
+        }
+    }
+}
+
+/// A node in a [`GraphDiff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeDiff {
+    /// How this node differs.
+    pub kind: DiffKind,
+    /// The node's ID in the `before` graph, if present.
+    pub before: Option<GraphNodeId>,
+    /// The node's ID in the `after` graph, if present.
+    pub after: Option<GraphNodeId>,
+    /// The node's label in the `after` graph, or in the `before` graph if it was removed.
+    pub label: String,
+    /// The node's label in the `before` graph, if it [changed](DiffKind::Changed).
+    pub before_label: Option<String>,
+}
+
+/// An edge in a [`GraphDiff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EdgeDiff {
+    /// How this edge differs. Never [`DiffKind::Changed`].
+    pub kind: DiffKind,
+    /// Index of the source node in [`GraphDiff::nodes`].
+    pub src: usize,
+    /// Index of the destination node in [`GraphDiff::nodes`].
+    pub dst: usize,
+    /// The output port of the source node, e.g. `[]` or `pos`.
+    pub src_port: String,
+    /// The input port of the destination node, e.g. `[]` or `0`.
+    pub dst_port: String,
+}
+
+/// The semantic difference between two graphs, as produced by [`DfirGraph::diff`].
+///
+/// Node IDs are not stable between two compilations of the same code, so nodes are instead
+/// matched by their operator and their neighbors. The [`Display`] implementation lists every
+/// added, removed, and changed node and edge, which is suitable for snapshot tests.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphDiff {
+    /// All nodes of both graphs, with matched nodes appearing once.
+    pub nodes: Vec<NodeDiff>,
+    /// All edges of both graphs, with matched edges appearing once.
+    pub edges: Vec<EdgeDiff>,
+}
+
+impl DfirGraph {
+    /// Computes the difference from this graph (`before`) to `after`, for example to see how an
+    /// optimization pass changed a graph.
+    ///
+    /// Nodes are matched in passes, from most to least confident:
+    /// 1. Nodes with the same operator and the same neighboring operators.
+    /// 2. Nodes with the same operator.
+    /// 3. Nodes with the same operator name which share a matched neighbor. These are reported
+    ///    as [`DiffKind::Changed`].
+    ///
+    /// Any remaining nodes are reported as removed or added.
+    pub fn diff(&self, after: &DfirGraph) -> GraphDiff {
+        let before = self;
+        let before_labels = node_labels(before);
+        let after_labels = node_labels(after);
+
+        let mut before_to_after = HashMap::<GraphNodeId, GraphNodeId>::new();
+        let mut matched_after = HashSet::<GraphNodeId>::new();
+
+        // Passes 1 and 2: match nodes by key, in node ID order within each key.
+        let neighbors_key = |graph: &DfirGraph, labels: &HashMap<GraphNodeId, String>, node_id| {
+            let mut preds = graph
+                .node_predecessor_nodes(node_id)
+                .map(|pred| labels[&pred].clone())
+                .collect::<Vec<_>>();
+            let mut succs = graph
+                .node_successor_nodes(node_id)
+                .map(|succ| labels[&succ].clone())
+                .collect::<Vec<_>>();
+            preds.sort();
+            succs.sort();
+            (labels[&node_id].clone(), preds, succs)
+        };
+        match_by_key(
+            before,
+            after,
+            |node_id| neighbors_key(before, &before_labels, node_id),
+            |node_id| neighbors_key(after, &after_labels, node_id),
+            &mut before_to_after,
+            &mut matched_after,
+        );
+        match_by_key(
+            before,
+            after,
+            |node_id| before_labels[&node_id].clone(),
+            |node_id| after_labels[&node_id].clone(),
+            &mut before_to_after,
+            &mut matched_after,
+        );
+
+        // Pass 3: match changed operators by name and a shared matched neighbor.
+        let mut changed = HashSet::<GraphNodeId>::new();
+        for before_id in before.node_ids() {
+            if before_to_after.contains_key(&before_id) {
+                continue;
+            }
+            let name = before.node(before_id).to_name_string();
+            let mapped_neighbors = before
+                .node_predecessor_nodes(before_id)
+                .chain(before.node_successor_nodes(before_id))
+                .filter_map(|neighbor| before_to_after.get(&neighbor).copied())
+                .collect::<HashSet<_>>();
+            let found = after.node_ids().find(|&after_id| {
+                !matched_after.contains(&after_id)
+                    && after.node(after_id).to_name_string() == name
+                    && after
+                        .node_predecessor_nodes(after_id)
+                        .chain(after.node_successor_nodes(after_id))
+                        .any(|neighbor| mapped_neighbors.contains(&neighbor))
+            });
+            if let Some(after_id) = found {
+                before_to_after.insert(before_id, after_id);
+                matched_after.insert(after_id);
+                changed.insert(before_id);
+            }
+        }
+
+        let mut diff = GraphDiff::default();
+        let mut before_idx = HashMap::<GraphNodeId, usize>::new();
+        let mut after_idx = HashMap::<GraphNodeId, usize>::new();
+        for before_id in before.node_ids() {
+            before_idx.insert(before_id, diff.nodes.len());
+            let after_id = before_to_after.get(&before_id).copied();
+            if let Some(after_id) = after_id {
+                after_idx.insert(after_id, diff.nodes.len());
+            }
+            let is_changed = changed.contains(&before_id);
+            diff.nodes.push(NodeDiff {
+                kind: match after_id {
+                    None => DiffKind::Removed,
+                    Some(_) if is_changed => DiffKind::Changed,
+                    Some(_) => DiffKind::Unchanged,
+                },
+                before: Some(before_id),
+                after: after_id,
+                label: after_id
+                    .map_or(&before_labels[&before_id], |after_id| {
+                        &after_labels[&after_id]
+                    })
+                    .clone(),
+                before_label: is_changed.then(|| before_labels[&before_id].clone()),
+            });
+        }
+        for after_id in after.node_ids() {
+            if matched_after.contains(&after_id) {
+                continue;
+            }
+            after_idx.insert(after_id, diff.nodes.len());
+            diff.nodes.push(NodeDiff {
+                kind: DiffKind::Added,
+                before: None,
+                after: Some(after_id),
+                label: after_labels[&after_id].clone(),
+                before_label: None,
+            });
+        }
+
+        // Edges are identified by their endpoints (in `diff.nodes`) and ports.
+        let edge_keys = |graph: &DfirGraph, idx: &HashMap<GraphNodeId, usize>| {
+            graph
+                .edges()
+                .map(|(edge_id, (src, dst))| {
+                    let (src_port, dst_port) = graph.edge_ports(edge_id);
+                    (
+                        idx[&src],
+                        idx[&dst],
+                        src_port.to_string(),
+                        dst_port.to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut after_edges = edge_keys(after, &after_idx);
+        for (src, dst, src_port, dst_port) in edge_keys(before, &before_idx) {
+            let kind = if let Some(pos) = after_edges
+                .iter()
+                .position(|(s, d, sp, dp)| (*s, *d, sp, dp) == (src, dst, &src_port, &dst_port))
+            {
+                after_edges.swap_remove(pos);
+                DiffKind::Unchanged
+            } else {
+                DiffKind::Removed
+            };
+            diff.edges.push(EdgeDiff {
+                kind,
+                src,
+                dst,
+                src_port,
+                dst_port,
+            });
+        }
+        after_edges.sort();
+        for (src, dst, src_port, dst_port) in after_edges {
+            diff.edges.push(EdgeDiff {
+                kind: DiffKind::Added,
+                src,
+                dst,
+                src_port,
+                dst_port,
+            });
+        }
+
+        diff
+    }
+}
+
+/// Returns the pretty string of each node, used to match nodes and as their label.
+fn node_labels(graph: &DfirGraph) -> HashMap<GraphNodeId, String> {
+    graph
+        .nodes()
+        .map(|(node_id, node)| (node_id, node.to_pretty_string().into_owned()))
+        .collect()
+}
+
+/// Matches unmatched nodes of `before` and `after` which have equal keys, pairing them in node ID
+/// order when several nodes share a key.
+fn match_by_key<Key: Ord>(
+    before: &DfirGraph,
+    after: &DfirGraph,
+    before_key: impl Fn(GraphNodeId) -> Key,
+    after_key: impl Fn(GraphNodeId) -> Key,
+    before_to_after: &mut HashMap<GraphNodeId, GraphNodeId>,
+    matched_after: &mut HashSet<GraphNodeId>,
+) {
+    let mut candidates = BTreeMap::<Key, Vec<GraphNodeId>>::new();
+    for after_id in after.node_ids() {
+        if !matched_after.contains(&after_id) {
+            candidates
+                .entry(after_key(after_id))
+                .or_default()
+                .push(after_id);
+        }
+    }
+    for candidates in candidates.values_mut() {
+        candidates.reverse();
+    }
+    for before_id in before.node_ids() {
+        if before_to_after.contains_key(&before_id) {
+            continue;
+        }
+        if let Some(after_id) = candidates
+            .get_mut(&before_key(before_id))
+            .and_then(Vec::pop)
+        {
+            before_to_after.insert(before_id, after_id);
+            matched_after.insert(after_id);
+        }
+    }
+}
+
+impl GraphDiff {
+    /// Returns `true` if the two graphs are equivalent, i.e. there are no added, removed, or
+    /// changed nodes or edges.
+    pub fn is_empty(&self) -> bool {
+        self.nodes
+            .iter()
+            .all(|node| DiffKind::Unchanged == node.kind)
+            && self
+                .edges
+                .iter()
+                .all(|edge| DiffKind::Unchanged == edge.kind)
+    }
+
+    /// Writes this diff as a mermaid graph into a string, containing both graphs overlaid, with
+    /// added nodes and edges in green, removed ones in red, and changed nodes in yellow.
+    pub fn to_mermaid(&self) -> String {
+        let mut output = String::new();
+        self.write_mermaid(&mut output).unwrap();
+        output
+    }
+
+    /// Writes this diff as a mermaid graph into the given `Write`. See [`Self::to_mermaid`].
+    pub fn write_mermaid(&self, mut write: impl std::fmt::Write) -> std::fmt::Result {
+        writeln!(write, "flowchart TD")?;
+        writeln!(
+            write,
+            "classDef unchangedClass fill:#eee,stroke:#000,text-align:left,white-space:pre"
+        )?;
+        writeln!(
+            write,
+            "classDef changedClass fill:#ff8,stroke:#000,text-align:left,white-space:pre"
+        )?;
+        writeln!(
+            write,
+            "classDef removedClass fill:#f88,stroke:#000,text-align:left,white-space:pre"
+        )?;
+        writeln!(
+            write,
+            "classDef addedClass fill:#8f8,stroke:#000,text-align:left,white-space:pre"
+        )?;
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let code = if let Some(before_label) = &node.before_label {
+                format!(
+                    "<del>{}</del><br><ins>{}</ins>",
+                    escape_mermaid(before_label),
+                    escape_mermaid(&node.label)
+                )
+            } else {
+                escape_mermaid(&node.label)
+            };
+            writeln!(
+                write,
+                r#"n{idx}["<code>{code}</code>"]:::{class}"#,
+                class = match node.kind {
+                    DiffKind::Unchanged => "unchangedClass",
+                    DiffKind::Changed => "changedClass",
+                    DiffKind::Removed => "removedClass",
+                    DiffKind::Added => "addedClass",
+                },
+            )?;
+        }
+        for (link_idx, edge) in self.edges.iter().enumerate() {
+            let label = match (&*edge.src_port, &*edge.dst_port) {
+                ("[]", "[]") => String::new(),
+                (src_port, dst_port) => {
+                    format!("|{}|", escape_mermaid(&format!("{src_port} → {dst_port}")))
+                }
+            };
+            writeln!(write, "n{}-->{}n{}", edge.src, label, edge.dst)?;
+            match edge.kind {
+                DiffKind::Removed => {
+                    writeln!(write, "linkStyle {link_idx} stroke:red,stroke-dasharray:4")?
+                }
+                DiffKind::Added => writeln!(write, "linkStyle {link_idx} stroke:green")?,
+                DiffKind::Unchanged | DiffKind::Changed => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for GraphDiff {
+    /// Lists the nodes and edges that differ, one per line, prefixed by `+` (added), `-`
+    /// (removed), or `~` (changed). Unchanged nodes and edges are omitted.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let one_line = |label: &str| label.split_whitespace().collect::<Vec<_>>().join(" ");
+        for node in self.nodes.iter() {
+            match (node.kind, &node.before_label) {
+                (DiffKind::Unchanged, _) => {}
+                (DiffKind::Changed, Some(before_label)) => writeln!(
+                    f,
+                    "~ {} => {}",
+                    one_line(before_label),
+                    one_line(&node.label)
+                )?,
+                (kind, _) => writeln!(f, "{} {}", kind.sigil(), one_line(&node.label))?,
+            }
+        }
+        for edge in self.edges.iter() {
+            if DiffKind::Unchanged == edge.kind {
+                continue;
+            }
+            writeln!(
+                f,
+                "{} {}{} -> {}{}",
+                edge.kind.sigil(),
+                one_line(&self.nodes[edge.src].label),
+                if "[]" == edge.src_port {
+                    String::new()
+                } else {
+                    format!("[{}]", edge.src_port)
+                },
+                if "[]" == edge.dst_port {
+                    String::new()
+                } else {
+                    format!("[{}]", edge.dst_port)
+                },
+                one_line(&self.nodes[edge.dst].label),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{FlatGraphBuilder, FlatGraphBuilderOutput, partition_graph};
+
+    fn build(code: proc_macro2::TokenStream) -> DfirGraph {
+        let mut builder = FlatGraphBuilder::new();
+        builder.add_dfir(syn::parse2(code).unwrap(), None, None);
+        let FlatGraphBuilderOutput { flat_graph, .. } =
+            builder.build().expect("should build without errors");
+        partition_graph(flat_graph).expect("should partition without errors")
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let code = quote::quote! {
+            source_iter([1, 2, 3]) -> map(|x| x + 1) -> for_each(std::mem::drop);
+        };
+        let diff = build(code.clone()).diff(&build(code));
+        assert!(diff.is_empty(), "{}", diff);
+        assert_eq!("", diff.to_string());
+    }
+
+    #[test]
+    fn test_diff_changed_and_added() {
+        let before = build(quote::quote! {
+            source_iter([1, 2, 3]) -> map(|x| x + 1) -> for_each(std::mem::drop);
+        });
+        let after = build(quote::quote! {
+            source_iter([1, 2, 3]) -> map(|x| x + 2) -> filter(|x| x % 2 == 0) -> for_each(std::mem::drop);
+        });
+        let diff = before.diff(&after);
+        assert!(!diff.is_empty());
+
+        let kinds = diff
+            .nodes
+            .iter()
+            .map(|node| (node.kind, node.label.as_str()))
+            .collect::<Vec<_>>();
+        assert!(kinds.contains(&(DiffKind::Changed, "map(|x| x + 2)")));
+        assert!(kinds.contains(&(DiffKind::Added, "filter(|x| x % 2 == 0)")));
+        assert!(kinds.contains(&(DiffKind::Unchanged, "source_iter([1, 2, 3])")));
+        assert!(!kinds.iter().any(|(kind, _)| DiffKind::Removed == *kind));
+
+        let text = diff.to_string();
+        assert!(
+            text.contains("~ map(|x| x + 1) => map(|x| x + 2)"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("- map(|x| x + 2) -> for_each(std::mem::drop)"),
+            "{}",
+            text
+        );
+        assert!(diff.to_mermaid().contains("changedClass"));
+    }
+}
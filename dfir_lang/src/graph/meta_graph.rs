@@ -20,10 +20,12 @@ use super::ops::{
 };
 use super::{
     CONTEXT, Color, DiMulGraph, GRAPH, GraphEdgeId, GraphLoopId, GraphNode, GraphNodeId,
-    GraphSubgraphId, HANDOFF_NODE_STR, HandoffKind, MODULE_BOUNDARY_NODE_STR, OperatorInstance,
-    PortIndexValue, SINGLETON_SLOT_NODE_STR, Varname, change_spans, get_operator_generics,
+    GraphSubgraphId, HANDOFF_NODE_STR, HandoffKind, HandoffReason, MODULE_BOUNDARY_NODE_STR,
+    OperatorInstance, PortIndexValue, SINGLETON_SLOT_NODE_STR, Varname, change_spans,
+    get_operator_generics,
 };
 use crate::diagnostic::{Diagnostic, Diagnostics, Level};
+use crate::parse::Operator;
 use crate::pretty_span::{PrettyRowCol, PrettySpan};
 use crate::process_singletons;
 
@@ -58,6 +60,9 @@ pub struct DfirGraph {
     operator_instances: SecondaryMap<GraphNodeId, OperatorInstance>,
     /// Debugging/tracing tag for each operator node.
     operator_tag: SecondaryMap<GraphNodeId, String>,
+    /// The key each operator annotated with `#[checkpoint]` saves its state under.
+    #[serde(default)]
+    operator_checkpoint: SecondaryMap<GraphNodeId, String>,
     /// Graph data structure (two-way adjacency list).
     graph: DiMulGraph<GraphNodeId, GraphEdgeId>,
     /// Input and output port for each edge.
@@ -91,6 +96,14 @@ pub struct DfirGraph {
     /// Set by `order_subgraphs` for `defer_tick` / `defer_tick_lazy`, either on handoff nodes
     /// it injects or on existing handoff nodes that it marks as tick-boundary back-edges.
     handoff_delay_type: SparseSecondaryMap<GraphNodeId, DelayType>,
+    /// Capacity of bounded handoff nodes, created by the `bounded_handoff(capacity)`
+    /// pseudo-operator or the `-> [bounded(capacity)]` edge annotation: the most items handed to
+    /// the consumer in a tick. Unbounded handoffs are not present.
+    handoff_capacity: SparseSecondaryMap<GraphNodeId, usize>,
+    /// Why each handoff node inserted by `partition_graph` is needed. Handoffs written
+    /// explicitly (e.g. `handoff()`) are not present.
+    #[serde(default)]
+    handoff_reason: SparseSecondaryMap<GraphNodeId, HandoffReason>,
 }
 
 /// Basic methods.
@@ -261,6 +274,8 @@ impl DfirGraph {
         let mut op_insts = Vec::new();
         // Collect nodes that should be lowered to handoffs (the `handoff()`/`singleton()` pseudo-operators).
         let mut handoff_nodes: Vec<(GraphNodeId, HandoffKind, Span)> = Vec::new();
+        // Capacities of the `bounded_handoff(capacity)` pseudo-operators.
+        let mut handoff_capacities: Vec<(GraphNodeId, usize)> = Vec::new();
 
         for (node_id, node) in self.nodes() {
             let GraphNode::Operator(operator) = node else {
@@ -270,6 +285,37 @@ impl DfirGraph {
                 continue;
             };
 
+            // Recognize `bounded_handoff(capacity)`, a `HandoffKind::Vec` with a capacity.
+            if "bounded_handoff" == operator.name_string() {
+                let capacity = match operator.args.iter().collect::<Vec<_>>()[..] {
+                    [
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Int(lit_int),
+                            ..
+                        }),
+                    ] => lit_int.base10_parse::<usize>().ok().filter(|&cap| 0 < cap),
+                    _ => None,
+                };
+                let Some(capacity) = capacity else {
+                    diagnostics.push(Diagnostic::spanned(
+                        operator.path.span(),
+                        Level::Error,
+                        "`bounded_handoff` takes one argument, a positive integer literal capacity.",
+                    ));
+                    continue;
+                };
+                if operator.type_arguments().is_some() {
+                    diagnostics.push(Diagnostic::spanned(
+                        operator.path.span(),
+                        Level::Error,
+                        "`bounded_handoff` takes no generic arguments.",
+                    ));
+                }
+                handoff_nodes.push((node_id, HandoffKind::Vec, operator.path.span()));
+                handoff_capacities.push((node_id, capacity));
+                continue;
+            }
+
             // Recognize `handoff()`/`singleton()` pseudo-operators and lower to GraphNode::Handoff.
             let handoff_kind = match &*operator.name_string() {
                 "handoff" => Some(HandoffKind::Vec),
@@ -402,6 +448,9 @@ impl DfirGraph {
                 dst_span: span,
             };
         }
+        for (node_id, capacity) in handoff_capacities {
+            self.handoff_capacity.insert(node_id, capacity);
+        }
     }
 
     /// Inserts a node between two existing nodes connected by the given `edge_id`.
@@ -536,6 +585,30 @@ impl DfirGraph {
     pub fn set_operator_tag(&mut self, node_id: GraphNodeId, tag: String) {
         self.operator_tag.insert(node_id, tag);
     }
+
+    /// Set the key the operator's state is checkpointed under, see `#[checkpoint]`.
+    pub fn set_operator_checkpoint(&mut self, node_id: GraphNodeId, key: String) {
+        self.operator_checkpoint.insert(node_id, key);
+    }
+
+    /// If the operator has a tag or checkpoint key attached.
+    pub fn node_is_annotated(&self, node_id: GraphNodeId) -> bool {
+        self.operator_tag.contains_key(node_id) || self.operator_checkpoint.contains_key(node_id)
+    }
+
+    /// Replace the operator of the operator node `node_id`, along with its operator instance.
+    /// Panics if the node is not an operator.
+    pub fn replace_operator(
+        &mut self,
+        node_id: GraphNodeId,
+        operator: Operator,
+        op_inst: OperatorInstance,
+    ) {
+        let node = self.nodes.get_mut(node_id).expect("Node not found.");
+        assert!(matches!(node, GraphNode::Operator(_)));
+        *node = GraphNode::Operator(operator);
+        self.operator_instances.insert(node_id, op_inst);
+    }
 }
 
 /// Handoff references.
@@ -804,6 +877,22 @@ impl DfirGraph {
         self.handoff_delay_type.insert(node_id, delay_type);
     }
 
+    /// Gets why a handoff node was inserted by [`partition_graph`](super::partition_graph), if
+    /// it was. Handoffs written explicitly (e.g. with `handoff()`) have no reason.
+    pub fn handoff_reason(&self, node_id: GraphNodeId) -> Option<HandoffReason> {
+        self.handoff_reason.get(node_id).copied()
+    }
+
+    /// Sets why a handoff node was inserted.
+    pub fn set_handoff_reason(&mut self, node_id: GraphNodeId, reason: HandoffReason) {
+        self.handoff_reason.insert(node_id, reason);
+    }
+
+    /// Gets the capacity of a handoff node, if it is bounded.
+    pub fn handoff_capacity(&self, node_id: GraphNodeId) -> Option<usize> {
+        self.handoff_capacity.get(node_id).copied()
+    }
+
     /// Helper: finds the first index in `subgraph_nodes` where it transitions from pull to push.
     fn find_pull_to_push_idx(&self, subgraph_nodes: &[GraphNodeId]) -> usize {
         subgraph_nodes
@@ -854,6 +943,12 @@ impl DfirGraph {
         Ident::new(&format!("hoff_{:?}_buf", hoff_id.data()), span)
     }
 
+    /// The ident of the (unbounded) backlog of a bounded handoff, given its
+    /// [`Self::hoff_buf_ident`].
+    fn hoff_backlog_ident(buf_ident: &Ident) -> Ident {
+        format_ident!("{}_backlog", buf_ident)
+    }
+
     /// Helper to generate the back (double-buffer) `Ident` for a handoff node.
     fn hoff_back_ident(&self, hoff_id: GraphNodeId, span: Span) -> Ident {
         Ident::new(&format!("hoff_{:?}_back", hoff_id.data()), span)
@@ -1234,6 +1329,55 @@ impl DfirGraph {
             })
             .collect::<SparseSecondaryMap<_, _>>();
 
+        // Bounded handoffs (`bounded_handoff(capacity)` or `-> [bounded(capacity)]`) hand at most
+        // `capacity` items to their consumer each tick. A running producer cannot be paused, so
+        // items it sends past the capacity are held in an unbounded backlog, which refills the
+        // handoff in later ticks. Backpressure is applied by not scheduling the producer until the
+        // backlog is drained and the handoff has room again, so input is left in its sources. This
+        // bounds memory to `capacity` plus the output of a single run of the producer.
+        let bounded_hoffs = handoff_nodes
+            .iter()
+            .filter_map(|&(hoff_id, _kind, _)| Some((hoff_id, self.handoff_capacity(hoff_id)?)))
+            .collect::<BTreeMap<_, _>>();
+        for (&hoff_id, _capacity) in bounded_hoffs.iter() {
+            let span = self.nodes[hoff_id].span();
+            let in_loop = self
+                .node_predecessor_nodes(hoff_id)
+                .chain(self.node_successor_nodes(hoff_id))
+                .any(|node_id| self.node_loop(node_id).is_some());
+            if in_loop || self.handoff_delay_type(hoff_id).is_some() {
+                diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    "`bounded_handoff` is not supported inside loops or across tick boundaries.",
+                ));
+                continue;
+            }
+            // The producer subgraph is skipped while the buffer is full, which would lose the
+            // contents of any tick-local handoffs it receives from.
+            let Some(producer_sg) = self
+                .node_predecessor_nodes(hoff_id)
+                .next()
+                .and_then(|pred| self.node_subgraph(pred))
+            else {
+                continue;
+            };
+            let has_unbounded_input = self.subgraph_nodes[producer_sg]
+                .iter()
+                .flat_map(|&node_id| self.node_predecessor_nodes(node_id))
+                .any(|pred| {
+                    matches!(self.nodes[pred], GraphNode::Handoff { .. })
+                        && !bounded_hoffs.contains_key(&pred)
+                });
+            if has_unbounded_input {
+                diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    "The upstream of a `bounded_handoff` may be deferred to a later tick, so it must only receive input from sources or other `bounded_handoff`s.",
+                ));
+            }
+        }
+
         // Back buffer idents, buf idents, and if they are lazy.
         let back_buffer_idents_laziness = handoff_nodes
             .iter()
@@ -1339,6 +1483,7 @@ impl DfirGraph {
 
         let mut op_prologue_code = Vec::new();
         let mut op_tick_end_code = Vec::new();
+        let mut op_checkpoint_code = Vec::new();
 
         // Stack-based hierarchical code generation.
         // Each entry is (loop_id, body_tokens) for an open loop context.
@@ -1479,6 +1624,7 @@ impl DfirGraph {
                         // (e.g. dfir_expect_warnings!). TODO(#2781): define these once.
                         let work_done = Ident::new("__dfir_work_done", Span::call_site());
                         let metrics = Ident::new("__dfir_metrics", Span::call_site());
+                        let sg_items = Ident::new("__dfir_sg_items", Span::call_site());
 
                         // Compute len and drain expressions based on handoff kind.
                         let (len_expr, drain_expr) = match kind {
@@ -1486,6 +1632,14 @@ impl DfirGraph {
                                 quote! { if #buf_ident.is_some() { 1usize } else { 0usize } },
                                 quote! { #root::dfir_pipes::pull::iter(#buf_ident.take().into_iter()) },
                             ),
+                            HandoffKind::Vec if bounded_hoffs.contains_key(&hoff_id) => {
+                                // Bounded handoffs hold at most `capacity` items, so the consumer
+                                // takes them all.
+                                (
+                                    quote! { #buf_ident.len() },
+                                    quote! { #root::dfir_pipes::pull::iter(#buf_ident.drain(..)) },
+                                )
+                            }
                             HandoffKind::Vec => {
                                 // Special asymmetric handling for defer tick handoffs, which are double-buffered.
                                 // The producer writes to the regular buffer; at end-of-tick the buffers are swapped,
@@ -1513,6 +1667,7 @@ impl DfirGraph {
                                 ];
                                 hoff_metrics.total_items_count.update(|x| x + hoff_len);
                                 hoff_metrics.curr_items_count.set(hoff_len);
+                                #sg_items += hoff_len;
                             }
                             let #port_ident = #drain_expr;
                         }
@@ -1524,7 +1679,8 @@ impl DfirGraph {
                     .iter()
                     .zip(send_buf_idents.iter())
                     .zip(send_kinds.iter())
-                    .map(|((port_ident, buf_ident), &kind)| {
+                    .zip(send_hoffs.iter())
+                    .map(|(((port_ident, buf_ident), &kind), &hoff_id)| {
                         match kind {
                             HandoffKind::Singleton => {
                                 // Singleton slot: store exactly one item, panic on duplicate.
@@ -1546,6 +1702,21 @@ impl DfirGraph {
                                     });
                                 }
                             }
+                            HandoffKind::Vec if bounded_hoffs.contains_key(&hoff_id) => {
+                                // Fill the handoff up to its capacity, and defer the remainder to
+                                // the backlog.
+                                let capacity = bounded_hoffs[&hoff_id];
+                                let backlog_ident = Self::hoff_backlog_ident(buf_ident);
+                                quote_spanned! {port_ident.span()=>
+                                    let #port_ident = #root::dfir_pipes::push::for_each(|item| {
+                                        if #buf_ident.len() < #capacity {
+                                            #buf_ident.push_back(item);
+                                        } else {
+                                            #backlog_ident.push_back(item);
+                                        }
+                                    });
+                                }
+                            }
                             HandoffKind::Vec => {
                                 quote_spanned! {port_ident.span()=>
                                     // TODO(mingwei): use `#root::dfir_pipes::push::vec_push`?
@@ -1692,6 +1863,7 @@ impl DfirGraph {
                                 loop_id,
                                 op_span,
                                 op_tag: self.operator_tag.get(node_id).cloned(),
+                                checkpoint_key: self.operator_checkpoint.get(node_id).cloned(),
                                 work_fn: &work_fn,
                                 work_fn_async: &work_fn_async,
                                 ident: &ident,
@@ -1710,6 +1882,7 @@ impl DfirGraph {
                                 write_iterator,
                                 write_iterator_after,
                                 write_tick_end,
+                                write_checkpoint,
                             } = write_result.unwrap_or_else(|()| {
                                 assert!(
                                     diagnostics.has_error(),
@@ -1739,6 +1912,7 @@ impl DfirGraph {
                             });
                             op_prologue_code.push(write_prologue);
                             op_tick_end_code.push(write_tick_end);
+                            op_checkpoint_code.push(write_checkpoint);
                             subgraph_op_iter_code.push(write_iterator);
 
                             if include_type_guards {
@@ -1952,7 +2126,11 @@ impl DfirGraph {
                     .zip(send_hoffs.iter())
                     .filter_map(|((buf_ident, &kind), &hoff_id)| {
                         let span = buf_ident.span();
-                        if back_edge_hoffs_and_lazyness.contains_key(hoff_id) {
+                        if bounded_hoffs.contains_key(&hoff_id) {
+                            // Bounded buffers are declared outside the tick closure, and keep
+                            // any items which were not yet taken by the consumer.
+                            None
+                        } else if back_edge_hoffs_and_lazyness.contains_key(hoff_id) {
                             // Defer_tick send buffers are declared outside the tick closure
                             // as std::vec::Vec for O(1) swap. Just clear here.
                             Some(quote_spanned! {span=>
@@ -1992,7 +2170,10 @@ impl DfirGraph {
                 let recv_hoff_drop_code = recv_buf_idents
                     .iter()
                     .zip(recv_hoffs.iter())
-                    .filter(|&(_, &hoff_id)| !back_edge_hoffs_and_lazyness.contains_key(hoff_id))
+                    .filter(|&(_, &hoff_id)| {
+                        !back_edge_hoffs_and_lazyness.contains_key(hoff_id)
+                            && !bounded_hoffs.contains_key(&hoff_id)
+                    })
                     .map(|(buf_ident, _)| {
                         let span = buf_ident.span();
                         quote_spanned! {span=>
@@ -2000,11 +2181,26 @@ impl DfirGraph {
                         }
                     });
 
-                // Emit subgraph block to the current loop level (top of stack or root).
-                let sg_block = quote! {
-                    // Create the handoffs we are about to push to (send).
-                    #( #send_hoff_make_code )*
+                // Backpressure: refill bounded handoffs this subgraph sends to from their backlog,
+                // then defer the subgraph while any of them is full or still has a backlog.
+                let (bounded_send_buf_idents, bounded_send_capacities): (Vec<_>, Vec<_>) =
+                    send_hoffs
+                        .iter()
+                        .zip(send_buf_idents.iter())
+                        .filter_map(|(hoff_id, buf_ident)| {
+                            Some((buf_ident, *bounded_hoffs.get(hoff_id)?))
+                        })
+                        .unzip();
+                let bounded_send_backlog_idents = bounded_send_buf_idents
+                    .iter()
+                    .map(|buf_ident| Self::hoff_backlog_ident(buf_ident))
+                    .collect::<Vec<_>>();
 
+                // Emit subgraph block to the current loop level (top of stack or root).
+                let sg_run = quote! {
+                    // Execution trace: time the subgraph and count the items it reads, if enabled.
+                    let __dfir_trace_start = #df.execution_trace().start();
+                    let mut __dfir_sg_items = 0_usize;
                     let #sg_fut_ident = async {
                         let #context = &#df;
                         #( #recv_port_code )*
@@ -2021,6 +2217,11 @@ impl DfirGraph {
                             #sg_fut_ident, sg_metrics
                         ).await;
                         sg_metrics.total_run_count.update(|x| x + 1);
+                        if let ::std::option::Option::Some(start) = __dfir_trace_start {
+                            #df.execution_trace().record(
+                                #sg_metrics_ffi, #df.current_tick(), start, __dfir_sg_items
+                            );
+                        }
 
                         // Update send (output) handoff metrics.
                         #( #send_metrics_code )*
@@ -2029,6 +2230,28 @@ impl DfirGraph {
                         #( #recv_hoff_drop_code )*
                     }
                 };
+                let sg_run = if bounded_send_buf_idents.is_empty() {
+                    sg_run
+                } else {
+                    quote! {
+                        #(
+                            {
+                                let room = #bounded_send_capacities.saturating_sub(#bounded_send_buf_idents.len());
+                                let n = ::std::cmp::min(room, #bounded_send_backlog_idents.len());
+                                #bounded_send_buf_idents.extend(#bounded_send_backlog_idents.drain(..n));
+                            }
+                        )*
+                        if #( #bounded_send_backlog_idents.is_empty() && #bounded_send_buf_idents.len() < #bounded_send_capacities )&&* {
+                            #sg_run
+                        }
+                    }
+                };
+                let sg_block = quote! {
+                    // Create the handoffs we are about to push to (send).
+                    #( #send_hoff_make_code )*
+
+                    #sg_run
+                };
                 if let Some((_, body)) = loop_stack.last_mut() {
                     body.extend(sg_block);
                 } else {
@@ -2109,6 +2332,15 @@ impl DfirGraph {
         let defer_tick_buf_idents = back_buffer_idents_laziness
             .iter()
             .map(|(_, buf_ident, _)| buf_ident);
+        // For declaring bounded handoff buffers outside the closure.
+        let bounded_buf_idents = bounded_hoffs
+            .keys()
+            .map(|&hoff_id| self.hoff_buf_ident(hoff_id, self.nodes[hoff_id].span()))
+            .collect::<Vec<_>>();
+        let bounded_backlog_idents = bounded_buf_idents
+            .iter()
+            .map(Self::hoff_backlog_ident)
+            .collect::<Vec<_>>();
         // For checking if we should start the next tick (`schedule_subgraph`):
         // Collect the ident to check for each non-lazy back-edge handoff.
         // - For defer_tick handoffs in a root-level loop: check `back` (swap happened inside `if`)
@@ -2173,6 +2405,9 @@ impl DfirGraph {
                 // This enables O(1) mem::swap at end of tick for double-buffering.
                 #( let mut #back_buffer_idents = ::std::vec::Vec::new(); )*
                 #( let mut #defer_tick_buf_idents = ::std::vec::Vec::new(); )*
+                // Bounded handoffs keep their items across ticks.
+                #( let mut #bounded_buf_idents = ::std::collections::VecDeque::new(); )*
+                #( let mut #bounded_backlog_idents = ::std::collections::VecDeque::new(); )*
 
                 // Bump allocator for handoffs (except for back-edge handoffs, above).
                 let mut #bump_ident = #root::bumpalo::Bump::new();
@@ -2184,6 +2419,13 @@ impl DfirGraph {
                 let mut __dfir_work_done = true;
                 #[allow(unused_qualifications, unused_mut, unused_variables, clippy::await_holding_refcell_ref, clippy::deref_addrof)]
                 let __dfir_inline_tick = async move |#df: &mut #root::scheduled::context::Context| {
+                    // Called by `Dfir::checkpoint` between ticks: only save the state of
+                    // checkpointed operators, without running a tick.
+                    if #df.checkpoints().is_capturing() {
+                        #( #op_checkpoint_code )*
+                        return false;
+                    }
+
                     // Reset arena between ticks (start-of-tick)
                     #bump_ident.reset();
 
@@ -2194,7 +2436,9 @@ impl DfirGraph {
 
                         // For non-lazy defer_tick: if any deferred buffer has data,
                         // signal that another tick should run.
-                        if false #( || !#non_lazy_schedule_idents.is_empty() )* {
+                        // Likewise if any bounded handoff still has items, which were either not
+                        // yet taken by the consumer or are in its backlog, deferring the producer.
+                        if false #( || !#non_lazy_schedule_idents.is_empty() )* #( || !#bounded_buf_idents.is_empty() || !#bounded_backlog_idents.is_empty() )* {
                             #df.schedule_subgraph(true);
                         }
 
@@ -2324,9 +2568,14 @@ impl DfirGraph {
                 skipped_handoffs.insert(node_id);
                 continue;
             }
+            let handoff_reason = self
+                .handoff_reason(node_id)
+                .filter(|_| write_config.explain_handoffs);
             graph_write.write_node_definition(
                 node_id,
-                &if write_config.op_short_text {
+                &if let Some(reason) = handoff_reason {
+                    format!("{}\n({})", node.to_name_string(), reason).into()
+                } else if write_config.op_short_text {
                     node.to_name_string()
                 } else if write_config.op_text_no_imports {
                     // Remove any lines that start with "use" (imports)
@@ -2693,6 +2942,9 @@ pub struct WriteConfig {
     /// Will not render loops if set.
     #[cfg_attr(feature = "clap-derive", arg(long))]
     pub no_loops: bool,
+    /// Handoffs inserted by partitioning will be annotated with why they are needed if set.
+    #[cfg_attr(feature = "clap-derive", arg(long))]
+    pub explain_handoffs: bool,
 
     /// Op text will only be their name instead of the whole source.
     #[cfg_attr(feature = "clap-derive", arg(long))]
@@ -10,7 +10,7 @@ use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{Expr, ExprPath, GenericArgument, Token, Type};
 
-use self::ops::{OperatorConstraints, Persistence};
+use self::ops::{DelayType, OperatorConstraints, Persistence};
 use crate::diagnostic::{Diagnostic, Diagnostics, Level};
 use crate::parse::{DfirCode, IndexInt, Operator, PortIndex, Ported, SingletonRef};
 use crate::pretty_span::PrettySpan;
@@ -19,6 +19,8 @@ mod di_mul_graph;
 mod eliminate_extra_unions_tees;
 mod flat_graph_builder;
 mod flat_to_partitioned;
+mod fuse_operators;
+mod graph_diff;
 mod graph_write;
 mod meta_graph;
 mod meta_graph_debugging;
@@ -29,6 +31,8 @@ pub use di_mul_graph::DiMulGraph;
 pub use eliminate_extra_unions_tees::eliminate_extra_unions_tees;
 pub use flat_graph_builder::{FlatGraphBuilder, FlatGraphBuilderOutput};
 pub use flat_to_partitioned::partition_graph;
+pub use fuse_operators::fuse_stateless_operators;
+pub use graph_diff::{DiffKind, EdgeDiff, GraphDiff, NodeDiff};
 pub use meta_graph::{DfirGraph, WriteConfig, WriteGraphType};
 
 pub use crate::graph_ids::{GraphEdgeId, GraphLoopId, GraphNodeId, GraphSubgraphId};
@@ -101,6 +105,43 @@ pub enum HandoffKind {
     Optional,
 }
 
+/// Why [`partition_graph`] placed a handoff between two operators, rather than putting them in
+/// the same subgraph. See [`DfirGraph::handoff_reason`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandoffReason {
+    /// The input of the downstream operator is delayed to the next tick or loop iteration, e.g. by
+    /// `defer_tick()`.
+    Delay(DelayType),
+    /// The operators are in different loop contexts.
+    LoopBoundary,
+    /// The output of the upstream operator cannot feed the input of the downstream operator
+    /// directly, e.g. a push operator (like `tee()`) into a pull operator (like `union()` or
+    /// `join()`), or into or out of a blocking operator.
+    Polarity,
+    /// The edge goes back to an earlier operator of the same subgraph.
+    BackEdge,
+    /// Joining the operators would violate an ordering constraint, such as a singleton
+    /// reference being read after it is computed, or would create a cycle between subgraphs.
+    Ordering,
+}
+
+impl Display for HandoffReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandoffReason::Delay(DelayType::Tick | DelayType::TickLazy) => {
+                write!(f, "cross-tick dependency")
+            }
+            HandoffReason::Delay(DelayType::Loop | DelayType::LoopLazy) => {
+                write!(f, "cross-iteration dependency")
+            }
+            HandoffReason::LoopBoundary => write!(f, "loop boundary"),
+            HandoffReason::Polarity => write!(f, "push/pull boundary"),
+            HandoffReason::BackEdge => write!(f, "back edge"),
+            HandoffReason::Ordering => write!(f, "ordering constraint"),
+        }
+    }
+}
+
 /// A node, corresponding to an operator or a handoff.
 #[derive(Clone, Serialize, Deserialize)]
 pub enum GraphNode {
@@ -458,10 +499,27 @@ pub struct BuildDfirCodeOutput {
     pub diagnostics: Diagnostics,
 }
 
+/// Options for [`build_dfir_code_with_options`].
+#[derive(Clone, Debug, Default)]
+pub struct BuildDfirCodeOptions {
+    /// Fuse chains of stateless unary operators, see [`fuse_stateless_operators`]. Disabled by
+    /// default, so that generated code snapshots can be updated incrementally.
+    pub fuse_operators: bool,
+}
+
 /// Compiles a [`DfirCode`] AST into inline source code that runs the dataflow.
 pub fn build_dfir_code(
     dfir_code: DfirCode,
     root: &TokenStream,
+) -> Result<BuildDfirCodeOutput, Diagnostics> {
+    build_dfir_code_with_options(dfir_code, root, &BuildDfirCodeOptions::default())
+}
+
+/// Like [`build_dfir_code`], but with additional codegen `options`.
+pub fn build_dfir_code_with_options(
+    dfir_code: DfirCode,
+    root: &TokenStream,
+    options: &BuildDfirCodeOptions,
 ) -> Result<BuildDfirCodeOutput, Diagnostics> {
     let flat_graph_builder = FlatGraphBuilder::from_dfir(dfir_code);
 
@@ -480,6 +538,9 @@ pub fn build_dfir_code(
     };
 
     eliminate_extra_unions_tees(&mut flat_graph);
+    if options.fuse_operators {
+        fuse_stateless_operators(&mut flat_graph);
+    }
 
     // Detect adjacent handoffs (e.g. `handoff() -> handoff()` or `singleton() -> singleton()`),
     // which can arise after unary tee/union elimination.
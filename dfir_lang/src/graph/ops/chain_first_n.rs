@@ -52,6 +52,7 @@ pub const CHAIN_FIRST_N: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         } = (super::union::UNION.write_fn)(wc, diagnostics)?;
 
         let arg_n = &arguments[0];
@@ -66,6 +67,7 @@ pub const CHAIN_FIRST_N: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         })
     },
 };
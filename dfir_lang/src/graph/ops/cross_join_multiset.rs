@@ -110,6 +110,7 @@ pub const CROSS_JOIN_MULTISET: OperatorConstraints = OperatorConstraints {
                 #lhs_write_tick_end
                 #rhs_write_tick_end
             },
+            write_checkpoint: Default::default(),
         })
     },
 };
@@ -1,4 +1,4 @@
-use proc_macro2::Ident;
+use proc_macro2::{Ident, TokenStream};
 use quote::{ToTokens, quote, quote_spanned};
 use syn::spanned::Spanned;
 use syn::{PathArguments, PathSegment, Token, Type, TypePath};
@@ -7,7 +7,7 @@ use super::{
     OpInstGenerics, OperatorCategory, OperatorConstraints, OperatorInstance, OperatorWriteOutput,
     PortIndexValue, PortListSpec, RANGE_0, RANGE_1, WriteContextArgs,
 };
-use crate::diagnostic::{Diagnostic, Level};
+use crate::diagnostic::{Diagnostic, Diagnostics, Level};
 use crate::graph::change_spans;
 
 /// > Generic Argument: A enum type which has `#[derive(DemuxEnum)]`. Must match the items in the input stream.
@@ -31,10 +31,10 @@ use crate::graph::change_spans;
 ///         Shape::Triangle { w: 12.0, h: 13.0 },
 ///     ]) -> demux_enum::<Shape>();
 ///
-///     my_demux[Square] -> map(|s| s * s) -> out;
+///     my_demux[Square] -> map(|(s,)| s * s) -> out;
 ///     my_demux[Circle] -> map(|(r,)| std::f64::consts::PI * r * r) -> out;
 ///     my_demux[Rectangle] -> map(|(w, h)| w * h) -> out;
-///     my_demux[Circle] -> map(|(w, h)| 0.5 * w * h) -> out;
+///     my_demux[Triangle] -> map(|(w, h)| 0.5 * w * h) -> out;
 ///
 ///     out = union() -> for_each(|area| println!("Area: {}", area));
 /// };
@@ -75,55 +75,9 @@ pub const DEMUX_ENUM: OperatorConstraints = OperatorConstraints {
         let enum_type = &type_args[0];
 
         // Port idents supplied via port connections in the surface syntax.
-        let port_idents: Vec<_> = output_ports
-                    .iter()
-                    .filter_map(|output_port| {
-                        let PortIndexValue::Path(port_expr) = output_port else {
-                            diagnostics.push(Diagnostic::spanned(
-                                output_port.span(),
-                                Level::Error,
-                                format!(
-                                    "Output port from `{}(..)` must be specified and must be a valid identifier.",
-                                    op_name,
-                                ),
-                            ));
-                            return None;
-                        };
-                        let port_ident = syn::parse2::<Ident>(quote! { #port_expr })
-                            .map_err(|err| diagnostics.push(err.into()))
-                            .ok()?;
-
-                        Some(port_ident)
-                    })
-                    .collect();
-
-        // The entire purpose of this closure and match statement is to generate readable error messages:
-        // "missing match arm: `Variant(_)` not covered."
-        // Or "no variant named `Variant` found for enum `Shape`"
-        // Note this uses the `enum_type`'s span.
-        let enum_type_turbofish = ensure_turbofish(enum_type);
-        let port_variant_check_match_arms = port_idents
-            .iter()
-            .map(|port_ident| {
-                let enum_type_turbofish =
-                    change_spans(enum_type_turbofish.to_token_stream(), port_ident.span());
-                quote_spanned! {port_ident.span()=>
-                    #enum_type_turbofish::#port_ident { .. } => ()
-                }
-            })
-            .collect::<Vec<_>>();
-        let root_span = change_spans(root.clone(), enum_type.span());
-        let write_prologue = quote_spanned! {enum_type.span()=>
-            let _ = |__val: #enum_type| {
-                fn check_impl_demux_enum<T: ?Sized + #root_span::util::demux_enum::DemuxEnumBase>(_: &T) {}
-                check_impl_demux_enum(&__val);
-                match __val {
-                    #(
-                        #port_variant_check_match_arms,
-                    )*
-                };
-            };
-        };
+        let port_idents =
+            variant_port_idents(output_ports, "Output port from", op_name, diagnostics);
+        let write_prologue = variant_check_prologue(root, enum_type, &port_idents, true);
 
         let write_iterator = if 1 == outputs.len() {
             // Use `enum_type`'s span.
@@ -176,6 +130,81 @@ pub const DEMUX_ENUM: OperatorConstraints = OperatorConstraints {
     },
 };
 
+/// Returns the idents of the `ports` of a `demux_enum` or `mux_enum` operator, each of which must
+/// name a variant of the enum. Emits an error for each port which is not a valid identifier.
+pub(crate) fn variant_port_idents(
+    ports: &[PortIndexValue],
+    port_description: &str,
+    op_name: &str,
+    diagnostics: &mut Diagnostics,
+) -> Vec<Ident> {
+    ports
+        .iter()
+        .filter_map(|port| {
+            let PortIndexValue::Path(port_expr) = port else {
+                diagnostics.push(Diagnostic::spanned(
+                    port.span(),
+                    Level::Error,
+                    format!(
+                        "{} `{}(..)` must be specified and must be a valid identifier.",
+                        port_description, op_name,
+                    ),
+                ));
+                return None;
+            };
+            let port_ident = syn::parse2::<Ident>(quote! { #port_expr })
+                .map_err(|err| diagnostics.push(err.into()))
+                .ok()?;
+
+            Some(port_ident)
+        })
+        .collect()
+}
+
+/// Generates a prologue which checks that `enum_type` derives `DemuxEnum` and has a variant for
+/// each of the `port_idents`. If `exhaustive`, also checks that every variant has a port.
+pub(crate) fn variant_check_prologue(
+    root: &TokenStream,
+    enum_type: &Type,
+    port_idents: &[Ident],
+    exhaustive: bool,
+) -> TokenStream {
+    // The entire purpose of this closure and match statement is to generate readable error messages:
+    // "missing match arm: `Variant(_)` not covered."
+    // Or "no variant named `Variant` found for enum `Shape`"
+    // Note this uses the `enum_type`'s span.
+    let enum_type_turbofish = ensure_turbofish(enum_type);
+    let port_variant_check_match_arms = port_idents
+        .iter()
+        .map(|port_ident| {
+            let enum_type_turbofish =
+                change_spans(enum_type_turbofish.to_token_stream(), port_ident.span());
+            quote_spanned! {port_ident.span()=>
+                #enum_type_turbofish::#port_ident { .. } => ()
+            }
+        })
+        .collect::<Vec<_>>();
+    let wildcard_arm = (!exhaustive).then(|| {
+        quote_spanned! {enum_type.span()=>
+            #[allow(unreachable_patterns)]
+            _ => (),
+        }
+    });
+    let root_span = change_spans(root.clone(), enum_type.span());
+    quote_spanned! {enum_type.span()=>
+        let _ = |__val: #enum_type| {
+            fn check_impl_demux_enum<T: ?Sized + #root_span::util::demux_enum::DemuxEnumBase>(_: &T) {}
+            check_impl_demux_enum(&__val);
+            match __val {
+                #(
+                    #port_variant_check_match_arms,
+                )*
+                #wildcard_arm
+            };
+        };
+    }
+}
+
 /// Ensure enum type has double colon turbofish syntax.
 /// `my_mod::MyType<MyGeneric>` becomes `my_mod::MyType::<MyGeneric>`.
 fn ensure_turbofish(ty: &Type) -> Type {
@@ -78,6 +78,7 @@ pub const DEST_FILE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         } = (super::dest_sink::DEST_SINK.write_fn)(&wc, diagnostics)?;
 
         let write_prologue = quote_spanned! {op_span=>
@@ -94,6 +95,7 @@ pub const DEST_FILE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         })
     },
 };
@@ -0,0 +1,81 @@
+use quote::quote_spanned;
+use syn::parse_quote_spanned;
+
+use super::{
+    OperatorCategory, OperatorConstraints, OperatorWriteOutput, RANGE_0, RANGE_1, WriteContextArgs,
+};
+
+/// > 1 input stream, 0 output streams
+///
+/// > Arguments: The name of a Python module, and the name of a function in that module.
+///
+/// Calls the Python function once for each item, with the item (converted with
+/// `pyo3::IntoPy`) as its only argument. The return value of the function is ignored.
+///
+/// The Python interpreter runs in-process, so this requires the `python` feature of `dfir_rs`. The
+/// module is imported with the usual Python rules, so it must be on the `PYTHONPATH` (or in the
+/// working directory).
+///
+/// Will panic if the function cannot be loaded, or if it raises an exception.
+///
+/// ```dfir,ignore
+/// // scoring.py: `def record(score): print(score)`
+/// source_iter([0.25, 0.75]) -> dest_python("scoring", "record");
+/// ```
+pub const DEST_PYTHON: OperatorConstraints = OperatorConstraints {
+    name: "dest_python",
+    categories: &[OperatorCategory::Sink],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_0,
+    soft_range_out: RANGE_0,
+    num_args: 2,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   arguments,
+                   ..
+               },
+               diagnostics| {
+        let module_arg = &arguments[0];
+        let func_arg = &arguments[1];
+
+        let ident_pyfunc = wc.make_ident("pyfunc");
+
+        let write_prologue = quote_spanned! {op_span=>
+            let #ident_pyfunc = #root::util::PythonFunction::new(#module_arg, #func_arg);
+        };
+        let wc = WriteContextArgs {
+            arguments: &parse_quote_spanned!(op_span=> |item| #ident_pyfunc.call(item)),
+            ..wc.clone()
+        };
+
+        let OperatorWriteOutput {
+            write_prologue: write_prologue_for_each,
+            write_iterator,
+            write_iterator_after,
+            write_tick_end,
+            write_checkpoint,
+        } = (super::for_each::FOR_EACH.write_fn)(&wc, diagnostics)?;
+
+        let write_prologue = quote_spanned! {op_span=>
+            #write_prologue
+            #write_prologue_for_each
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            write_iterator_after,
+            write_tick_end,
+            write_checkpoint,
+        })
+    },
+};
@@ -0,0 +1,99 @@
+use quote::quote_spanned;
+
+use super::{
+    OperatorCategory, OperatorConstraints, OperatorWriteOutput, RANGE_0, RANGE_1, WriteContextArgs,
+};
+
+/// > Arguments: An [async `Sink`](https://docs.rs/futures/latest/futures/sink/trait.Sink.html),
+/// > a buffer capacity, and an [`OverflowPolicy`](https://docs.rs/sinktools/latest/sinktools/bounded/enum.OverflowPolicy.html).
+///
+/// Like [`dest_sink`](#dest_sink), but buffers at most `capacity` items in front of the sink,
+/// so that the memory used when the sink is slower than the items arriving is bounded. The
+/// policy decides what happens to items which arrive while the buffer is full:
+/// * `OverflowPolicy::Block` applies backpressure, pausing the subgraph until the sink catches up.
+/// * `OverflowPolicy::DropNewest` drops the arriving items.
+/// * `OverflowPolicy::DropOldest` drops the oldest buffered items to make room for the arriving
+///   ones.
+///
+/// With either drop policy, the subgraph never waits for the sink, which makes this suitable
+/// for high-rate pipelines writing to sockets where losing some items is preferable to growing
+/// without bound. To read how many items were dropped, build the sink with
+/// [`sinktools::bounded`](https://docs.rs/sinktools/latest/sinktools/fn.bounded.html) instead,
+/// take its `stats()`, and pass it to [`dest_sink`](#dest_sink).
+///
+/// Note this operator must be used within a Tokio runtime, and the DFIR program must be launched with `run_async`.
+///
+/// ```rustbook
+/// # #[dfir_rs::main]
+/// # async fn main() {
+/// use dfir_rs::sinktools::bounded::OverflowPolicy;
+///
+/// let (send, recv) = tokio::sync::mpsc::channel::<usize>(5);
+/// let send = tokio_util::sync::PollSender::new(send);
+///
+/// let mut flow = dfir_rs::dfir_syntax! {
+///     source_iter(0..10) -> dest_sink_bounded(send, 2, OverflowPolicy::DropOldest);
+/// };
+/// tokio::time::timeout(std::time::Duration::from_secs(1), flow.run())
+///     .await
+///     .expect_err("Expected time out");
+///
+/// let mut recv = tokio_stream::wrappers::ReceiverStream::new(recv);
+/// // The channel holds five items, and the buffer in front of it keeps only the last two.
+/// let out: Vec<_> = dfir_rs::util::ready_iter(&mut recv).collect();
+/// assert_eq!(&[0, 1, 2, 3, 4], &*out);
+/// # }
+/// ```
+pub const DEST_SINK_BOUNDED: OperatorConstraints = OperatorConstraints {
+    name: "dest_sink_bounded",
+    categories: &[OperatorCategory::Sink],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_0,
+    soft_range_out: RANGE_0,
+    num_args: 3,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   ident,
+                   is_pull,
+                   arguments,
+                   ..
+               },
+               _| {
+        assert!(!is_pull);
+
+        let sink_arg = &arguments[0];
+        let capacity_arg = &arguments[1];
+        let policy_arg = &arguments[2];
+        let sink_ident = wc.make_ident("sink");
+        let write_prologue = quote_spanned! {op_span=>
+            let mut #sink_ident = #root::sinktools::bounded(#capacity_arg, #policy_arg, #sink_arg);
+        };
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = {
+                fn sink_guard<Si, Item>(sink: Si) -> impl #root::dfir_pipes::push::Push<Item, ()>
+                where
+                    Si: #root::futures::sink::Sink<Item>,
+                    Si::Error: ::std::fmt::Debug,
+                {
+                    #root::dfir_pipes::push::sink(<Si as #root::futures::sink::SinkExt<Item>>::sink_map_err(sink, |e| panic!("{:?}", e)))
+                }
+                sink_guard(&mut #sink_ident)
+            };
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    },
+};
@@ -53,6 +53,7 @@ pub const DEST_SINK_SERDE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         } = (super::dest_sink::DEST_SINK.write_fn)(wc, diagnostics)?;
 
         let write_iterator = quote_spanned! {op_span=>
@@ -69,6 +70,7 @@ pub const DEST_SINK_SERDE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         })
     },
 };
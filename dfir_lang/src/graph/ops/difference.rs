@@ -51,6 +51,7 @@ pub const DIFFERENCE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         } = (super::anti_join::ANTI_JOIN.write_fn)(wc, diagnostics)?;
 
         let pos = &inputs[1];
@@ -65,6 +66,7 @@ pub const DIFFERENCE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         })
     },
 };
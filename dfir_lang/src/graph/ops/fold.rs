@@ -1,8 +1,8 @@
 use quote::quote_spanned;
 
 use super::{
-    OperatorCategory, OperatorConstraints, OperatorWriteOutput, Persistence, RANGE_0,
-    RANGE_1, WriteContextArgs,
+    OperatorCategory, OperatorConstraints, OperatorWriteOutput, Persistence, RANGE_0, RANGE_1,
+    WriteContextArgs,
 };
 
 /// > 1 input stream, 1 output stream
@@ -83,6 +83,13 @@ pub const FOLD: OperatorConstraints = OperatorConstraints {
             let mut #singleton_output_ident = #init;
         };
 
+        let restored_ident = wc.make_ident("restored");
+        let (write_restore, write_save) = wc.write_checkpoint(
+            quote_spanned! {op_span=> #singleton_output_ident },
+            &restored_ident,
+            quote_spanned! {op_span=> #singleton_output_ident = #restored_ident; },
+        );
+
         let write_tick_end = match persistence {
             Persistence::Tick => quote_spanned! {op_span=>
                 #[allow(clippy::redundant_closure_call)]
@@ -110,6 +117,7 @@ pub const FOLD: OperatorConstraints = OperatorConstraints {
 
         let write_iterator = if is_pull {
             quote_spanned! {op_span=>
+                #write_restore
                 #assign_accum_ident
 
                 // Eagerly consume input to ensure updated state.
@@ -129,6 +137,7 @@ pub const FOLD: OperatorConstraints = OperatorConstraints {
         } else if outputs.is_empty() {
             // Terminal push: fold is a singleton reference target with no downstream.
             quote_spanned! {op_span=>
+                #write_restore
                 let #ident = #root::dfir_pipes::push::for_each(|#item_ident| {
                     #assign_accum_ident
 
@@ -138,6 +147,7 @@ pub const FOLD: OperatorConstraints = OperatorConstraints {
         } else {
             let output = &outputs[0];
             quote_spanned! {op_span=>
+                #write_restore
                 let #ident = {
                     #[inline(always)]
                     fn __push_fold<'a, Acc, Item, CombFn, Next>(
@@ -171,6 +181,7 @@ pub const FOLD: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after: Default::default(),
             write_tick_end,
+            write_checkpoint: write_save,
         })
     },
 };
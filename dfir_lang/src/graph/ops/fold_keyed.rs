@@ -1,8 +1,8 @@
 use quote::{ToTokens, quote_spanned};
 
 use super::{
-    OpInstGenerics, OperatorCategory, OperatorConstraints, OperatorInstance,
-    OperatorWriteOutput, Persistence, RANGE_1, WriteContextArgs,
+    OpInstGenerics, OperatorCategory, OperatorConstraints, OperatorInstance, OperatorWriteOutput,
+    Persistence, RANGE_1, WriteContextArgs,
 };
 
 /// > 1 input stream of type `(K, V1)`, 1 output stream of type `(K, V2)`.
@@ -90,11 +90,7 @@ pub const FOLD_KEYED: OperatorConstraints = OperatorConstraints {
                    root,
                    op_inst:
                        OperatorInstance {
-                           generics:
-                               OpInstGenerics {
-                                   type_args,
-                                   ..
-                               },
+                           generics: OpInstGenerics { type_args, .. },
                            ..
                        },
                    arguments,
@@ -125,11 +121,22 @@ pub const FOLD_KEYED: OperatorConstraints = OperatorConstraints {
             let mut #singleton_output_ident = #root::rustc_hash::FxHashMap::<#( #generic_type_args ),*>::default();
         };
 
+        let write_introspection = wc.write_state_introspection(&[(
+            quote_spanned! {op_span=> #singleton_output_ident.len() },
+            quote_spanned! {op_span=> #singleton_output_ident.iter() },
+        )]);
+        let restored_ident = wc.make_ident("restored");
+        let (write_restore, write_save) = wc.write_checkpoint(
+            quote_spanned! {op_span=> #singleton_output_ident },
+            &restored_ident,
+            quote_spanned! {op_span=> #singleton_output_ident = #restored_ident; },
+        );
         let write_tick_end = match persistence {
             Persistence::Tick => quote_spanned! {op_span=>
+                #write_introspection
                 #singleton_output_ident.clear();
             },
-            _ => Default::default(),
+            _ => write_introspection,
         };
 
         let assign_hashtable_ident = quote_spanned! {op_span=>
@@ -139,6 +146,7 @@ pub const FOLD_KEYED: OperatorConstraints = OperatorConstraints {
         let write_iterator = if !is_pull {
             let output = &outputs[0];
             quote_spanned! {op_span=>
+                #write_restore
                 let #ident = #root::dfir_pipes::push::FoldKeyed::new(
                     &mut #singleton_output_ident,
                     #initfn,
@@ -174,6 +182,7 @@ pub const FOLD_KEYED: OperatorConstraints = OperatorConstraints {
             };
 
             quote_spanned! {op_span=>
+                #write_restore
                 #assign_hashtable_ident
 
                 {
@@ -216,6 +225,7 @@ pub const FOLD_KEYED: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after: Default::default(),
             write_tick_end,
+            write_checkpoint: write_save,
         })
     },
 };
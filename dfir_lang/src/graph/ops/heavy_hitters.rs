@@ -0,0 +1,149 @@
+use quote::quote_spanned;
+
+use super::{
+    OperatorCategory, OperatorConstraints, OperatorWriteOutput, Persistence, RANGE_0, RANGE_1,
+    WriteContextArgs,
+};
+use crate::diagnostic::{Diagnostic, Level};
+
+/// > 1 input stream, 1 output stream
+///
+/// > Arguments: the number of items `k` to report, and the error bound `epsilon`, an `f64` in
+/// > `(0, 1]`.
+///
+/// Outputs the (approximately) `k` most frequent items as `(item, count)` pairs, in descending
+/// order of count. Rather than counting every distinct item, this uses the Space-Saving sketch
+/// with `max(k, ceil(1 / epsilon))` counters: every item which occurred more than `epsilon * N`
+/// times (out of `N` items) is guaranteed to be tracked, and each reported count overestimates
+/// the true count by at most `epsilon * N`. Items must be `Clone + Eq + Hash`.
+///
+/// `heavy_hitters` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. With `'tick`, items are only counted
+/// within each tick. With `'static`, counts are accumulated across ticks, and the current heavy
+/// hitters over all ticks so far are output on each tick. When not explicitly specified
+/// persistence defaults to `'tick`.
+///
+/// ```dfir
+/// source_iter(["a", "b", "a", "c", "a", "b"])
+///     -> heavy_hitters(2, 0.1)
+///     -> assert_eq([("a", 3), ("b", 2)]);
+/// ```
+pub const HEAVY_HITTERS: OperatorConstraints = OperatorConstraints {
+    name: "heavy_hitters",
+    categories: &[OperatorCategory::Fold],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 2,
+    persistence_args: &(0..=1),
+    type_args: RANGE_0,
+    is_external_input: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   work_fn,
+                   work_fn_async,
+                   ident,
+                   is_pull,
+                   inputs,
+                   outputs,
+                   arguments,
+                   op_name,
+                   ..
+               },
+               diagnostics| {
+        let k = &arguments[0];
+        let epsilon = &arguments[1];
+        let [persistence] = wc.persistence_args(diagnostics);
+        let take = match persistence {
+            Persistence::None | Persistence::Tick => true,
+            Persistence::Static => false,
+            Persistence::Loop => {
+                diagnostics.push(Diagnostic::spanned(
+                    op_span,
+                    Level::Error,
+                    format!(
+                        "`{}()` can only have `'none`, `'tick`, or `'static` persistence.",
+                        op_name
+                    ),
+                ));
+                return Err(());
+            }
+        };
+
+        let state_ident = wc.make_ident("heavy_hitters");
+        let item_ident = wc.make_ident("item");
+
+        let write_prologue = quote_spanned! {op_span=>
+            let mut #state_ident = #root::util::sketch::SpaceSaving::new(#k, #epsilon);
+        };
+
+        let write_output = |state| {
+            if take {
+                quote_spanned! {op_span=>
+                    #root::util::sketch::SpaceSaving::take(#state)
+                }
+            } else {
+                quote_spanned! {op_span=>
+                    #root::util::sketch::SpaceSaving::to_vec(#state)
+                }
+            }
+        };
+
+        let write_iterator = if is_pull {
+            let input = &inputs[0];
+            let output = write_output(quote_spanned! {op_span=> &mut #state_ident });
+            quote_spanned! {op_span=>
+                // Eagerly consume input to ensure updated state.
+                {
+                    let __fut = #root::dfir_pipes::pull::Pull::for_each(#input, |#item_ident| {
+                        #root::util::sketch::SpaceSaving::insert(&mut #state_ident, #item_ident);
+                    });
+                    let () = #work_fn_async(__fut).await;
+                }
+
+                let #ident = #work_fn(|| #root::dfir_pipes::pull::iter(#output));
+            }
+        } else {
+            let output = &outputs[0];
+            let drain = write_output(quote_spanned! {op_span=> __state });
+            quote_spanned! {op_span=>
+                let #ident = {
+                    #[inline(always)]
+                    fn __push_fold<'a, Acc, Item, CombFn, Next>(
+                        acc_ref: &'a mut Acc,
+                        comb_fn: CombFn,
+                        next: Next,
+                    ) -> #root::dfir_pipes::push::Accumulate<
+                        #root::dfir_pipes::push::FoldState<&'a mut Acc, CombFn, Acc, Item>,
+                        Next,
+                    >
+                    where
+                        CombFn: ::std::ops::FnMut(&mut Acc, Item),
+                        Next: #root::dfir_pipes::push::Push<&'a mut Acc, ()>,
+                    {
+                        #root::dfir_pipes::push::fold(acc_ref, comb_fn, next)
+                    }
+                    __push_fold(
+                        &mut #state_ident,
+                        |__state: &mut _, #item_ident| {
+                            #root::util::sketch::SpaceSaving::insert(__state, #item_ident);
+                        },
+                        #root::dfir_pipes::push::flat_map(|__state: &mut _| #drain, #output),
+                    )
+                };
+            }
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    },
+};
@@ -169,9 +169,58 @@ pub const JOIN: OperatorConstraints = OperatorConstraints {
         let (rhs_prologue, rhs_tick_end, rhs_joindata_ident) =
             (make_joindata)(persistences[1], "rhs")?;
 
+        let write_introspection = wc.write_state_introspection(
+            &[&lhs_joindata_ident, &rhs_joindata_ident].map(|joindata_ident| {
+                (
+                    quote_spanned! {op_span=>
+                        #root::dfir_pipes::pull::HalfJoinState::len(&#joindata_ident)
+                    },
+                    quote_spanned! {op_span=>
+                        #root::dfir_pipes::pull::HalfJoinState::iter(&#joindata_ident)
+                    },
+                )
+            }),
+        );
+
+        let restored_ident = wc.make_ident("restored");
+        let [lhs_save, rhs_save] =
+            [&lhs_joindata_ident, &rhs_joindata_ident].map(|joindata_ident| {
+                quote_spanned! {op_span=>
+                    #root::dfir_pipes::pull::HalfJoinState::iter(&#joindata_ident)
+                        .map(|(k, vs)| (k, vs.as_slice()))
+                        .collect::<::std::vec::Vec<_>>()
+                }
+            });
+        let (write_restore, write_save) = wc.write_checkpoint(
+            quote_spanned! {op_span=> (#lhs_save, #rhs_save) },
+            &restored_ident,
+            quote_spanned! {op_span=>
+                #[inline(always)]
+                fn restore_half_join<K, VBuild, VProbe>(
+                    state: &mut impl #root::dfir_pipes::pull::HalfJoinState<K, VBuild, VProbe>,
+                    restored: ::std::vec::Vec<(K, ::std::vec::Vec<VBuild>)>,
+                )
+                where
+                    K: ::std::clone::Clone,
+                    VBuild: ::std::clone::Clone,
+                {
+                    state.clear();
+                    for (k, vs) in restored {
+                        for v in vs {
+                            state.build(::std::clone::Clone::clone(&k), ::std::borrow::Cow::Owned(v));
+                        }
+                    }
+                }
+                let (lhs_restored, rhs_restored) = #restored_ident;
+                restore_half_join(&mut #lhs_joindata_ident, lhs_restored);
+                restore_half_join(&mut #rhs_joindata_ident, rhs_restored);
+            },
+        );
+
         let lhs = &inputs[0];
         let rhs = &inputs[1];
         let write_iterator = quote_spanned! {op_span=>
+            #write_restore
             let #ident = {
                 // Limit error propagation by bounding locally, erasing output iterator type.
                 #[inline(always)]
@@ -216,9 +265,11 @@ pub const JOIN: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after: Default::default(),
             write_tick_end: quote_spanned! {op_span=>
+                #write_introspection
                 #lhs_tick_end
                 #rhs_tick_end
             },
+            write_checkpoint: write_save,
         })
     },
 };
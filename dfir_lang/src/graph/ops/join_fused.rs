@@ -179,6 +179,7 @@ pub const JOIN_FUSED: OperatorConstraints = OperatorConstraints {
                 #lhs_tick_end
                 #rhs_tick_end
             },
+            write_checkpoint: Default::default(),
         })
     },
 };
@@ -119,6 +119,7 @@ pub const JOIN_FUSED_LHS: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after: Default::default(),
             write_tick_end: lhs_tick_end,
+            write_checkpoint: Default::default(),
         })
     },
 };
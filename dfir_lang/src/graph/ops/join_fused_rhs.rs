@@ -44,6 +44,7 @@ pub const JOIN_FUSED_RHS: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         } = (super::join_fused_lhs::JOIN_FUSED_LHS.write_fn)(&wc, diagnostics)?;
 
         let write_iterator = quote_spanned! {op_span=>
@@ -56,6 +57,7 @@ pub const JOIN_FUSED_RHS: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         })
     },
 };
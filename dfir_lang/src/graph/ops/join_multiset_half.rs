@@ -161,6 +161,7 @@ pub const JOIN_MULTISET_HALF: OperatorConstraints = OperatorConstraints {
                 #write_tick_end_build
                 #write_tick_end_probe
             },
+            write_checkpoint: Default::default(),
         })
     },
 };
@@ -64,6 +64,7 @@ pub const LATTICE_REDUCE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         } = (super::reduce::REDUCE.write_fn)(&wc, diagnostics)?;
 
         let write_iterator = if is_pull {
@@ -93,6 +94,7 @@ pub const LATTICE_REDUCE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         })
     },
 };
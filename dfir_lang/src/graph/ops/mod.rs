@@ -126,8 +126,15 @@ pub struct OperatorWriteOutput {
     /// Code which runs at the end of each tick, after all subgraphs have run.
     /// Used for resetting state with `'tick` persistence.
     pub write_tick_end: TokenStream,
+    /// Code which saves the operator's state when a checkpoint is taken between ticks, see
+    /// [`WriteContextArgs::write_checkpoint`].
+    pub write_checkpoint: TokenStream,
 }
 
+/// The operators which save their state when annotated with `#[checkpoint]`, see
+/// [`WriteContextArgs::write_checkpoint`].
+pub const CHECKPOINT_OPERATORS: &[&str] = &["persist", "fold", "fold_keyed", "join"];
+
 /// Convenience range: zero or more (any number).
 pub const RANGE_ANY: &'static dyn RangeTrait<usize> = &(0..);
 /// Convenience range: exactly zero.
@@ -262,11 +269,11 @@ pub const NULL_WRITE_FN: WriteFn = |write_context_args, _| {
 };
 
 macro_rules! declare_ops {
-    ( $( $mod:ident :: $op:ident, )* ) => {
-        $( pub(crate) mod $mod; )*
+    ( $( $( #[$attr:meta] )* $mod:ident :: $op:ident, )* ) => {
+        $( $( #[$attr] )* pub(crate) mod $mod; )*
         /// All DFIR operators.
         pub const OPERATORS: &[OperatorConstraints] = &[
-            $( $mod :: $op, )*
+            $( $( #[$attr] )* $mod :: $op, )*
         ];
     };
 }
@@ -285,7 +292,10 @@ declare_ops![
     cross_singleton::CROSS_SINGLETON,
     demux_enum::DEMUX_ENUM,
     dest_file::DEST_FILE,
+    #[cfg(feature = "python")]
+    dest_python::DEST_PYTHON,
     dest_sink::DEST_SINK,
+    dest_sink_bounded::DEST_SINK_BOUNDED,
     dest_sink_serde::DEST_SINK_SERDE,
     difference::DIFFERENCE,
     enumerate::ENUMERATE,
@@ -298,6 +308,7 @@ declare_ops![
     fold::FOLD,
     fold_no_replay::FOLD_NO_REPLAY,
     for_each::FOR_EACH,
+    heavy_hitters::HEAVY_HITTERS,
     identity::IDENTITY,
     initialize::INITIALIZE,
     inspect::INSPECT,
@@ -318,6 +329,7 @@ declare_ops![
     map::MAP,
     union::UNION,
     multiset_delta::MULTISET_DELTA,
+    mux_enum::MUX_ENUM,
     defer_signal::DEFER_SIGNAL,
     defer_tick::DEFER_TICK,
     defer_tick_lazy::DEFER_TICK_LAZY,
@@ -335,22 +347,34 @@ declare_ops![
     spin::SPIN,
     sort::SORT,
     sort_by_key::SORT_BY_KEY,
+    source_dir_watch::SOURCE_DIR_WATCH,
     source_file::SOURCE_FILE,
+    source_file_lines::SOURCE_FILE_LINES,
     source_interval::SOURCE_INTERVAL,
     source_iter::SOURCE_ITER,
     source_json::SOURCE_JSON,
+    #[cfg(feature = "python")]
+    source_python::SOURCE_PYTHON,
     source_stdin::SOURCE_STDIN,
     source_stream::SOURCE_STREAM,
     source_stream_serde::SOURCE_STREAM_SERDE,
     state::STATE,
     state_by::STATE_BY,
     tee::TEE,
+    top_k::TOP_K,
     unique::UNIQUE,
     unzip::UNZIP,
     zip::ZIP,
     zip_longest::ZIP_LONGEST,
 ];
 
+mod registry;
+pub use registry::{
+    CountRange, OperatorDocs, OperatorInfo, OperatorRegistry, PortInfo, PortNames, PortsInfo,
+    REGISTRY_FORMAT_VERSION, export_registry_json, find_op_docs, find_operator_info,
+    operator_registry,
+};
+
 /// Get the operator lookup table, generating it if needed.
 pub fn operator_lookup() -> &'static HashMap<&'static str, &'static OperatorConstraints> {
     pub static OPERATOR_LOOKUP: OnceLock<HashMap<&'static str, &'static OperatorConstraints>> =
@@ -393,6 +417,9 @@ pub struct WriteContextArgs<'a> {
     pub op_span: Span,
     /// Tag for this operator appended to the generated identifier.
     pub op_tag: Option<String>,
+    /// The key this operator's state is checkpointed under, if its statement is annotated with
+    /// `#[checkpoint]`. See [`Self::write_checkpoint`].
+    pub checkpoint_key: Option<String>,
     /// Identifier for a function to call when doing work outside the stream.
     pub work_fn: &'a Ident,
     /// Identifier for a function to wrap futures when doing work outside the stream.
@@ -436,6 +463,82 @@ impl WriteContextArgs<'_> {
         )
     }
 
+    /// Generates code for the end of each tick which, when state introspection is enabled,
+    /// records the operator's state in the runtime's state registry. The state may consist of
+    /// several parts (e.g. the two sides of a join), each given as a pair of expressions: the
+    /// number of items held, and an iterator over (references to) them. Must be placed before any
+    /// code which clears `'tick` state.
+    pub fn write_state_introspection(&self, parts: &[(TokenStream, TokenStream)]) -> TokenStream {
+        let &Self {
+            root,
+            df_ident,
+            node_id,
+            op_name,
+            op_span,
+            ..
+        } = self;
+        let node_id_ffi = node_id.data().as_ffi();
+        let (lens, items): (Vec<_>, Vec<_>) = parts.iter().cloned().unzip();
+        quote_spanned! {op_span=>
+            #[allow(clippy::needless_borrow)]
+            #[allow(clippy::disallowed_methods, reason = "FxHasher is deterministic")]
+            if let ::std::option::Option::Some(sample_size) = #df_ident.state_registry().sample_size() {
+                #[allow(unused_imports)]
+                use #root::scheduled::introspection::{SampleDebug as _, SampleOpaque as _};
+                let mut sample = ::std::vec::Vec::<::std::string::String>::new();
+                #(
+                    let remaining = sample_size - sample.len();
+                    sample.extend(::std::iter::Iterator::map(
+                        ::std::iter::Iterator::take(#items, remaining),
+                        |item| (&#root::scheduled::introspection::Sample(&item)).sample(),
+                    ));
+                )*
+                #df_ident.state_registry().record(
+                    #root::slotmap::KeyData::from_ffi(#node_id_ffi).into(),
+                    #op_name,
+                    #df_ident.current_tick(),
+                    #( #lens )+*,
+                    sample,
+                );
+            }
+        }
+    }
+
+    /// Generates the code which checkpoints the operator's state, if the operator is annotated
+    /// with `#[checkpoint]`, returning code for the start of the iterator and for
+    /// [`OperatorWriteOutput::write_checkpoint`] (or nothing, if not annotated).
+    ///
+    /// At the start of the iterator, the state restored by `Dfir::restore` (if any) is bound to
+    /// the `restored` identifier, and loaded by the `restore` statements. The `save` expression is
+    /// only serialized when `Dfir::checkpoint` is called, between ticks.
+    pub fn write_checkpoint(
+        &self,
+        save: TokenStream,
+        restored: &Ident,
+        restore: TokenStream,
+    ) -> (TokenStream, TokenStream) {
+        let &Self {
+            context,
+            df_ident,
+            op_span,
+            ..
+        } = self;
+        let Some(key) = &self.checkpoint_key else {
+            return Default::default();
+        };
+        (
+            quote_spanned! {op_span=>
+                if let ::std::option::Option::Some(#restored) = #context.checkpoints().take_restored(#key) {
+                    #restore
+                }
+            },
+            quote_spanned! {op_span=>
+                #[allow(clippy::needless_borrow)]
+                #df_ident.checkpoints().save(#key, &#save);
+            },
+        )
+    }
+
     /// Returns the given number of persistence arguments, with loop-context-aware defaults
     /// (`'none` within a `loop { ... }` context, `'tick` otherwise) when not specified.
     pub fn persistence_args<const N: usize>(
@@ -573,7 +676,19 @@ fn make_missing_runtime_msg(op_name: &str) -> Literal {
 }
 
 /// Operator categories, for docs.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, DocumentedVariants)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    DocumentedVariants,
+    Serialize,
+    Deserialize,
+)]
 pub enum OperatorCategory {
     /// Maps: Simple one-in-one-out operators.
     Map,
@@ -618,7 +733,7 @@ impl OperatorCategory {
 }
 
 /// Operator type for Flo semantics.
-#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum FloType {
     /// A source operator, which must be at the top level.
     Source,
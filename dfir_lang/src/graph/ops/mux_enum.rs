@@ -0,0 +1,135 @@
+use quote::quote_spanned;
+
+use super::demux_enum::{variant_check_prologue, variant_port_idents};
+use super::{
+    OpInstGenerics, OperatorCategory, OperatorConstraints, OperatorInstance, OperatorWriteOutput,
+    PortListSpec, RANGE_0, RANGE_1, WriteContextArgs,
+};
+use crate::graph::change_spans;
+
+/// > Generic Argument: A enum type which has `#[derive(DemuxEnum)]`.
+///
+/// The inverse of [`demux_enum`](#demux_enum): takes a stream of field tuples on each input port,
+/// named after a variant of the enum, and constructs an enum value of that variant from each tuple.
+/// The fields of each variant are taken as a tuple in the same form `demux_enum` outputs them:
+/// `()`, `(a,)`, or `(a, b, ...)`. Not every variant needs an input port.
+///
+/// ```rustdoc
+/// #[derive(DemuxEnum, Debug)]
+/// enum Shape {
+///     Square(f64),
+///     Circle { r: f64 },
+///     Point,
+/// }
+///
+/// let mut df = dfir_syntax! {
+///     my_mux = mux_enum::<Shape>() -> for_each(|shape| println!("Shape: {:?}", shape));
+///
+///     source_iter([(9.0,)]) -> [Square]my_mux;
+///     source_iter([(5.0,)]) -> [Circle]my_mux;
+///     source_iter([()]) -> [Point]my_mux;
+/// };
+/// df.run_available();
+/// ```
+pub const MUX_ENUM: OperatorConstraints = OperatorConstraints {
+    name: "mux_enum",
+    categories: &[OperatorCategory::MultiIn],
+    hard_range_inn: &(1..),
+    soft_range_inn: &(1..),
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 0,
+    persistence_args: RANGE_0,
+    type_args: RANGE_1,
+    is_external_input: false,
+    flo_type: None,
+    ports_inn: Some(|| PortListSpec::Variadic),
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |&WriteContextArgs {
+                   root,
+                   op_span,
+                   ident,
+                   inputs,
+                   outputs,
+                   is_pull,
+                   op_name,
+                   op_inst:
+                       OperatorInstance {
+                           input_ports,
+                           generics: OpInstGenerics { type_args, .. },
+                           ..
+                       },
+                   ..
+               },
+               diagnostics| {
+        let enum_type = &type_args[0];
+
+        let port_idents = variant_port_idents(input_ports, "Input port to", op_name, diagnostics);
+        if port_idents.len() != inputs.len() {
+            return Err(());
+        }
+        let write_prologue = variant_check_prologue(root, enum_type, &port_idents, false);
+
+        // The constructor generated by `#[derive(DemuxEnum)]` for the variant of each port.
+        let constructors = port_idents
+            .iter()
+            .map(|port_ident| {
+                let root = change_spans(root.clone(), port_ident.span());
+                quote_spanned! {port_ident.span()=>
+                    <<#enum_type as #root::util::demux_enum::MuxEnum>::Constructors>::#port_ident
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let write_iterator = if is_pull {
+            let chains = inputs
+                .iter()
+                .zip(constructors.iter())
+                .map(|(input, constructor)| {
+                    quote_spanned! {op_span=>
+                        #root::dfir_pipes::pull::Pull::map(#input, #constructor)
+                    }
+                })
+                .reduce(|a, b| quote_spanned! {op_span=> check_inputs(#a, #b) })
+                .unwrap_or_else(|| quote_spanned! {op_span=> #root::dfir_pipes::pull::empty() });
+            quote_spanned! {op_span=>
+                let #ident = {
+                    #[allow(unused)]
+                    #[inline(always)]
+                    fn check_inputs<A, B, Item>(
+                        a: A, b: B
+                    ) -> impl #root::dfir_pipes::pull::Pull<
+                        Item = Item,
+                        Meta = A::Meta,
+                        CanPend = <A::CanPend as #root::dfir_pipes::Toggle>::Or<B::CanPend>,
+                        CanEnd = B::CanEnd,
+                    >
+                    where
+                        A: #root::dfir_pipes::pull::Pull<Item = Item, CanEnd = #root::dfir_pipes::Yes>,
+                        B: #root::dfir_pipes::pull::Pull<Item = Item, Meta = A::Meta>,
+                    {
+                        #root::dfir_pipes::pull::Pull::chain(
+                            #root::dfir_pipes::pull::Pull::fuse(a),
+                            b,
+                        )
+                    }
+                    #chains
+                };
+            }
+        } else {
+            // Only a single input can be pushed.
+            let output = &outputs[0];
+            let constructor = &constructors[0];
+            quote_spanned! {op_span=>
+                let #ident = #root::dfir_pipes::push::map(#constructor, #output);
+            }
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    },
+};
@@ -123,9 +123,27 @@ pub const PERSIST: OperatorConstraints = OperatorConstraints {
             }
         };
 
+        let restored_ident = wc.make_ident("restored");
+        let (write_restore, write_save) = wc.write_checkpoint(
+            quote_spanned! {op_span=> #persistdata_ident },
+            &restored_ident,
+            quote_spanned! {op_span=> #persistdata_ident = #restored_ident; },
+        );
+        let write_iterator = quote_spanned! {op_span=>
+            #write_restore
+            #write_iterator
+        };
+
+        let write_tick_end = wc.write_state_introspection(&[(
+            quote_spanned! {op_span=> #persistdata_ident.len() },
+            quote_spanned! {op_span=> #persistdata_ident.iter() },
+        )]);
+
         Ok(OperatorWriteOutput {
             write_prologue,
             write_iterator,
+            write_tick_end,
+            write_checkpoint: write_save,
             ..Default::default()
         })
     },
@@ -149,6 +149,7 @@ pub const REDUCE: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after: Default::default(),
             write_tick_end,
+            write_checkpoint: Default::default(),
         })
     },
 };
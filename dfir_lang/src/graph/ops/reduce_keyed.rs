@@ -215,6 +215,7 @@ pub const REDUCE_KEYED: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after: Default::default(),
             write_tick_end,
+            write_checkpoint: Default::default(),
         })
     },
 };
@@ -0,0 +1,257 @@
+//! Machine-readable descriptions of the DFIR operators, for use by external tooling such as
+//! editors and visual debuggers.
+
+use std::ops::Bound;
+use std::sync::OnceLock;
+
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    DelayType, FloType, OPERATORS, OperatorCategory, OperatorConstraints, PortListSpec, RangeTrait,
+};
+use crate::graph::PortIndexValue;
+
+/// Version of the format produced by [`export_registry_json`]. Incremented whenever a field is
+/// removed or changes meaning; new fields may be added without changing the version.
+pub const REGISTRY_FORMAT_VERSION: u32 = 1;
+
+/// Documentation of an operator, extracted from its doc comment at build time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OperatorDocs {
+    /// Operator's name.
+    pub name: &'static str,
+    /// Description of the operator's inputs and outputs, and their types, e.g.
+    /// `2 input streams of type <(K, V1)> and <(K, V2)>, 1 output stream of type <(K, (V1, V2))>`.
+    pub ports: Option<&'static str>,
+    /// Description of the operator's arguments, e.g. `A Rust closure`.
+    pub arguments: Option<&'static str>,
+    /// The first paragraph of the operator's description.
+    pub summary: &'static str,
+}
+
+/// Documentation of every operator in [`OPERATORS`], sorted by name.
+const OPERATOR_DOCS: &[OperatorDocs] = include!(concat!(env!("OUT_DIR"), "/op_docs.rs"));
+
+/// Find the [`OperatorDocs`] of an operator by name.
+pub fn find_op_docs(name: &str) -> Option<&'static OperatorDocs> {
+    OPERATOR_DOCS
+        .binary_search_by(|docs| docs.name.cmp(name))
+        .ok()
+        .map(|idx| &OPERATOR_DOCS[idx])
+}
+
+/// A range of allowed counts, e.g. of ports or arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CountRange {
+    /// The minimum count (inclusive).
+    pub min: usize,
+    /// The maximum count (inclusive), or `None` if unbounded.
+    pub max: Option<usize>,
+}
+impl CountRange {
+    fn from_range(range: &dyn RangeTrait<usize>) -> Self {
+        let min = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(&n) => Some(n),
+            Bound::Excluded(&n) => Some(n.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+        Self { min, max }
+    }
+}
+
+/// The names of an operator's input or output ports.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PortNames {
+    /// Ports are not named, and are connected without a port index.
+    Unnamed {
+        /// The delay (stratum barrier) on the input, if any. Always `None` for outputs.
+        delay: Option<DelayType>,
+    },
+    /// Any number of ports, named as specified in the operator's arguments.
+    Variadic,
+    /// A fixed set of named (or numbered) ports.
+    Fixed(Vec<PortInfo>),
+}
+
+/// A single named (or numbered) port of an operator.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortInfo {
+    /// The port's name, e.g. `pos` or `0`.
+    pub name: String,
+    /// The delay (stratum barrier) on this input, if any. Always `None` for outputs.
+    pub delay: Option<DelayType>,
+}
+
+/// Description of an operator's input or output ports.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortsInfo {
+    /// Number of ports required to not show an error.
+    pub hard_range: CountRange,
+    /// Number of ports required to not show a warning.
+    pub soft_range: CountRange,
+    /// The names of the ports.
+    pub names: PortNames,
+}
+
+/// Machine-readable description of a single operator, derived from its [`OperatorConstraints`]
+/// and [`OperatorDocs`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperatorInfo {
+    /// Operator's name.
+    pub name: String,
+    /// Operator categories.
+    pub categories: Vec<OperatorCategory>,
+    /// Input ports.
+    pub inputs: PortsInfo,
+    /// Output ports.
+    pub outputs: PortsInfo,
+    /// Number of arguments, i.e. `operator(a, b, c)` has `num_args = 3`.
+    pub num_args: usize,
+    /// How many persistence lifetime arguments can be provided.
+    pub persistence_args: CountRange,
+    /// How many generic type arguments can be provided.
+    pub type_args: CountRange,
+    /// If this operator receives external inputs and therefore must be in stratum 0.
+    pub is_external_input: bool,
+    /// Flo semantics type.
+    pub flo_type: Option<FloType>,
+    /// Description of the operator's ports and their types, see [`OperatorDocs::ports`].
+    pub ports_doc: Option<String>,
+    /// Description of the operator's arguments, see [`OperatorDocs::arguments`].
+    pub arguments_doc: Option<String>,
+    /// The first paragraph of the operator's description.
+    pub summary: String,
+}
+impl OperatorInfo {
+    /// Describe the given operator.
+    pub fn new(op: &OperatorConstraints) -> Self {
+        let docs = find_op_docs(op.name);
+        Self {
+            name: op.name.to_owned(),
+            categories: op.categories.to_vec(),
+            inputs: PortsInfo {
+                hard_range: CountRange::from_range(op.hard_range_inn),
+                soft_range: CountRange::from_range(op.soft_range_inn),
+                names: port_names(op.ports_inn, |port| (op.input_delaytype_fn)(port)),
+            },
+            outputs: PortsInfo {
+                hard_range: CountRange::from_range(op.hard_range_out),
+                soft_range: CountRange::from_range(op.soft_range_out),
+                names: port_names(op.ports_out, |_| None),
+            },
+            num_args: op.num_args,
+            persistence_args: CountRange::from_range(op.persistence_args),
+            type_args: CountRange::from_range(op.type_args),
+            is_external_input: op.is_external_input,
+            flo_type: op.flo_type,
+            ports_doc: docs.and_then(|docs| docs.ports).map(str::to_owned),
+            arguments_doc: docs.and_then(|docs| docs.arguments).map(str::to_owned),
+            summary: docs.map(|docs| docs.summary).unwrap_or_default().to_owned(),
+        }
+    }
+}
+
+fn port_names(
+    ports: Option<fn() -> PortListSpec>,
+    delay: impl Fn(&PortIndexValue) -> Option<DelayType>,
+) -> PortNames {
+    match ports.map(|ports_fn| (ports_fn)()) {
+        None => PortNames::Unnamed {
+            delay: (delay)(&PortIndexValue::Elided(None)),
+        },
+        Some(PortListSpec::Variadic) => PortNames::Variadic,
+        Some(PortListSpec::Fixed(port_names)) => PortNames::Fixed(
+            port_names
+                .into_iter()
+                .map(|idx| PortInfo {
+                    name: idx.to_token_stream().to_string(),
+                    delay: (delay)(&idx.into()),
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Get the descriptions of all operators, in the same order as [`OPERATORS`].
+pub fn operator_registry() -> &'static [OperatorInfo] {
+    static REGISTRY: OnceLock<Vec<OperatorInfo>> = OnceLock::new();
+    REGISTRY.get_or_init(|| OPERATORS.iter().map(OperatorInfo::new).collect())
+}
+
+/// Find the description of an operator by name.
+pub fn find_operator_info(name: &str) -> Option<&'static OperatorInfo> {
+    operator_registry().iter().find(|op| op.name == name)
+}
+
+/// The top-level object produced by [`export_registry_json`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperatorRegistry {
+    /// The [`REGISTRY_FORMAT_VERSION`] this registry was exported with.
+    pub version: u32,
+    /// All operators, sorted by name.
+    pub operators: Vec<OperatorInfo>,
+}
+
+/// Export the descriptions of all operators as pretty-printed JSON, in the form of an
+/// [`OperatorRegistry`].
+///
+/// Operators are sorted by name so that the output is stable across builds.
+pub fn export_registry_json() -> String {
+    let mut operators = operator_registry().to_vec();
+    operators.sort_by(|a, b| a.name.cmp(&b.name));
+    let registry = OperatorRegistry {
+        version: REGISTRY_FORMAT_VERSION,
+        operators,
+    };
+    serde_json::to_string_pretty(&registry).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_every_operator_has_docs() {
+        for op in OPERATORS {
+            assert!(find_op_docs(op.name).is_some(), "missing docs: {}", op.name);
+        }
+    }
+
+    #[test]
+    fn test_registry_describes_ports() {
+        let join = find_operator_info("join").unwrap();
+        let PortNames::Fixed(ports) = &join.inputs.names else {
+            panic!("expected fixed input ports: {:?}", join.inputs.names);
+        };
+        let names = ports.iter().map(|port| &*port.name).collect::<Vec<_>>();
+        assert_eq!(vec!["0", "1"], names);
+        assert_eq!(
+            CountRange {
+                min: 0,
+                max: Some(2)
+            },
+            join.persistence_args
+        );
+
+        let map = find_operator_info("map").unwrap();
+        assert_eq!(Some("A Rust closure"), map.arguments_doc.as_deref());
+        assert_eq!(
+            Some("1 input stream, 1 output stream"),
+            map.ports_doc.as_deref()
+        );
+    }
+
+    #[test]
+    fn test_registry_json_roundtrip() {
+        let json = export_registry_json();
+        let registry: OperatorRegistry = serde_json::from_str(&json).unwrap();
+        assert_eq!(REGISTRY_FORMAT_VERSION, registry.version);
+        assert_eq!(OPERATORS.len(), registry.operators.len());
+    }
+}
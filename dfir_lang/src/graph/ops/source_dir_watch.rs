@@ -0,0 +1,89 @@
+use quote::quote_spanned;
+use syn::parse_quote_spanned;
+
+use super::{
+    FloType, OperatorCategory, OperatorConstraints, OperatorWriteOutput, RANGE_0, RANGE_1,
+    WriteContextArgs, make_missing_runtime_msg,
+};
+
+/// > 0 input streams, 1 output stream
+///
+/// > Arguments: An [`AsRef`](https://doc.rust-lang.org/std/convert/trait.AsRef.html)`<`[`Path`](https://doc.rust-lang.org/nightly/std/path/struct.Path.html)`>`
+/// > for a directory to watch.
+///
+/// Emits a [`DirEvent`](https://hydro.run/rustdoc/dfir_rs/util/enum.DirEvent) whenever an entry
+/// of the directory is created, modified, or removed. Subdirectories are not watched, and entries
+/// which already exist when the operator starts are not reported. The directory is checked for
+/// changes periodically, so changes which are undone before the next check may be missed.
+///
+/// Will panic if the directory could not be read.
+///
+/// ```dfir
+/// source_dir_watch(".") -> for_each(|event| println!("{:?}", event));
+/// ```
+pub const SOURCE_DIR_WATCH: OperatorConstraints = OperatorConstraints {
+    name: "source_dir_watch",
+    categories: &[OperatorCategory::Source],
+    hard_range_inn: RANGE_0,
+    soft_range_inn: RANGE_0,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 1,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: true,
+    flo_type: Some(FloType::Source),
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   ident,
+                   op_name,
+                   arguments,
+                   ..
+               },
+               diagnostics| {
+        let dirname_arg = &arguments[0];
+
+        let ident_dirwatch = wc.make_ident("dirwatch");
+
+        let missing_runtime_msg = make_missing_runtime_msg(op_name);
+
+        let write_prologue = quote_spanned! {op_span=>
+            let #ident_dirwatch = ::std::boxed::Box::pin(#root::util::dir_watch(#dirname_arg));
+        };
+        let wc = WriteContextArgs {
+            arguments: &parse_quote_spanned!(op_span=> #ident_dirwatch),
+            ..wc.clone()
+        };
+
+        let OperatorWriteOutput {
+            write_prologue: write_prologue_stream,
+            write_iterator,
+            write_iterator_after,
+            write_tick_end,
+            write_checkpoint,
+        } = (super::source_stream::SOURCE_STREAM.write_fn)(&wc, diagnostics)?;
+
+        let write_prologue = quote_spanned! {op_span=>
+            #write_prologue
+            #write_prologue_stream
+        };
+        let write_iterator = quote_spanned! {op_span=>
+            ::std::debug_assert!(#root::tokio::runtime::Handle::try_current().is_ok(), #missing_runtime_msg);
+            #write_iterator
+            // Unwrap each event. Will panic if the directory could not be read.
+            let #ident = #root::dfir_pipes::pull::Pull::map(#ident, ::std::result::Result::unwrap);
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            write_iterator_after,
+            write_tick_end,
+            write_checkpoint,
+        })
+    },
+};
@@ -0,0 +1,95 @@
+use quote::quote_spanned;
+use syn::parse_quote_spanned;
+
+use super::{
+    FloType, OperatorCategory, OperatorConstraints, OperatorWriteOutput, RANGE_0, RANGE_1,
+    WriteContextArgs, make_missing_runtime_msg,
+};
+
+/// > 0 input streams, 1 output stream
+///
+/// > Arguments: An [`AsRef`](https://doc.rust-lang.org/std/convert/trait.AsRef.html)`<`[`Path`](https://doc.rust-lang.org/nightly/std/path/struct.Path.html)`>`
+/// > for a file to read, and a `bool` for whether to follow the file.
+///
+/// Streams the lines of the referenced file, which will NOT include the line endings.
+///
+/// If the second argument is `false`, the stream ends at the end of the file, like
+/// [`source_file`](#source_file). If it is `true`, the operator instead follows the file like
+/// `tail -F`, emitting each line appended to the file once it is complete. It starts over from the
+/// beginning of the file if it is truncated, and switches to the new file if the file is replaced
+/// (e.g. by log rotation).
+///
+/// Will panic if the file could not be read, or if the file contains bytes that are not valid UTF-8.
+///
+/// ```dfir
+/// source_file_lines("Cargo.toml", false) -> for_each(|line| println!("{}", line));
+/// ```
+pub const SOURCE_FILE_LINES: OperatorConstraints = OperatorConstraints {
+    name: "source_file_lines",
+    categories: &[OperatorCategory::Source],
+    hard_range_inn: RANGE_0,
+    soft_range_inn: RANGE_0,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 2,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: true,
+    flo_type: Some(FloType::Source),
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   ident,
+                   op_name,
+                   arguments,
+                   ..
+               },
+               diagnostics| {
+        let filename_arg = &arguments[0];
+        let follow_arg = &arguments[1];
+
+        let ident_filelines = wc.make_ident("filelines");
+
+        let missing_runtime_msg = make_missing_runtime_msg(op_name);
+
+        let write_prologue = quote_spanned! {op_span=>
+            let #ident_filelines = ::std::boxed::Box::pin(
+                #root::util::file_lines(#filename_arg, #follow_arg)
+            );
+        };
+        let wc = WriteContextArgs {
+            arguments: &parse_quote_spanned!(op_span=> #ident_filelines),
+            ..wc.clone()
+        };
+
+        let OperatorWriteOutput {
+            write_prologue: write_prologue_stream,
+            write_iterator,
+            write_iterator_after,
+            write_tick_end,
+            write_checkpoint,
+        } = (super::source_stream::SOURCE_STREAM.write_fn)(&wc, diagnostics)?;
+
+        let write_prologue = quote_spanned! {op_span=>
+            #write_prologue
+            #write_prologue_stream
+        };
+        let write_iterator = quote_spanned! {op_span=>
+            ::std::debug_assert!(#root::tokio::runtime::Handle::try_current().is_ok(), #missing_runtime_msg);
+            #write_iterator
+            // Unwrap each line. Will panic if the file could not be read, or invalid utf-8.
+            let #ident = #root::dfir_pipes::pull::Pull::map(#ident, ::std::result::Result::unwrap);
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            write_iterator_after,
+            write_tick_end,
+            write_checkpoint,
+        })
+    },
+};
@@ -81,6 +81,7 @@ pub const SOURCE_INTERVAL: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         } = (super::source_stream::SOURCE_STREAM.write_fn)(&wc, diagnostics)?;
         write_prologue.extend(write_prologue_stream);
         Ok(OperatorWriteOutput {
@@ -88,6 +89,7 @@ pub const SOURCE_INTERVAL: OperatorConstraints = OperatorConstraints {
             write_iterator,
             write_iterator_after,
             write_tick_end,
+            write_checkpoint,
         })
     },
 };
@@ -0,0 +1,60 @@
+use syn::parse_quote_spanned;
+
+use super::{FloType, OperatorCategory, OperatorConstraints, RANGE_0, RANGE_1, WriteContextArgs};
+
+/// > 0 input streams, 1 output stream
+///
+/// > Arguments: The name of a Python module, and the name of a function in that module.
+///
+/// Calls the Python function with no arguments, and emits each item of the iterable it returns
+/// (for example a list or a generator) as a `pyo3::Py<pyo3::PyAny>`. Items can be converted back
+/// into Rust values with `Python::with_gil(|py| item.extract::<T>(py))`.
+///
+/// The Python interpreter runs in-process, so this requires the `python` feature of `dfir_rs`. The
+/// module is imported with the usual Python rules, so it must be on the `PYTHONPATH` (or in the
+/// working directory).
+///
+/// Note that all items are emitted during the first tick, like [`source_iter`](#source_iter), so
+/// the iterable must be finite. Will panic if the function cannot be loaded or called, or if
+/// iterating over its result raises an exception.
+///
+/// ```dfir,ignore
+/// // scoring.py: `def inputs(): return [1.5, 2.5]`
+/// source_python("scoring", "inputs")
+///     -> map(|item| Python::with_gil(|py| item.extract::<f64>(py).unwrap()))
+///     -> for_each(|x| println!("{}", x));
+/// ```
+pub const SOURCE_PYTHON: OperatorConstraints = OperatorConstraints {
+    name: "source_python",
+    categories: &[OperatorCategory::Source],
+    hard_range_inn: RANGE_0,
+    soft_range_inn: RANGE_0,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 2,
+    persistence_args: RANGE_0,
+    type_args: RANGE_0,
+    is_external_input: false,
+    flo_type: Some(FloType::Source),
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   arguments,
+                   ..
+               },
+               diagnostics| {
+        let module_arg = &arguments[0];
+        let func_arg = &arguments[1];
+
+        let wc = WriteContextArgs {
+            arguments: &parse_quote_spanned!(op_span=>
+                #root::util::python_iter(#module_arg, #func_arg)
+            ),
+            ..wc.clone()
+        };
+        (super::source_iter::SOURCE_ITER.write_fn)(&wc, diagnostics)
+    },
+};
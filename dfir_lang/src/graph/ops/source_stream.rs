@@ -63,8 +63,9 @@ pub const SOURCE_STREAM: OperatorConstraints = OperatorConstraints {
         };
         let fused_ident = wc.make_ident("stream_fused");
         let write_iterator = quote_spanned! {op_span=>
+            // Stops reading once the tick passes its deadline, leaving the rest for the next tick.
             let mut #fused_ident = #root::dfir_pipes::pull::Pull::fuse(#root::dfir_pipes::pull::stream_ready(
-                &mut #stream_ident,
+                #context.tick_deadline().limit(&mut #stream_ident),
                 #context.waker(),
             ));
             let #ident = &mut #fused_ident;
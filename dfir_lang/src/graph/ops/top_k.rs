@@ -0,0 +1,156 @@
+use quote::quote_spanned;
+
+use super::{
+    OperatorCategory, OperatorConstraints, OperatorWriteOutput, Persistence, RANGE_0, RANGE_1,
+    WriteContextArgs,
+};
+use crate::diagnostic::{Diagnostic, Level};
+
+/// > 1 input stream, 1 output stream
+///
+/// > Arguments: the number of items `k` to keep, and a comparator closure which takes two
+/// > `&Item` arguments and returns an [`Ordering`](https://doc.rust-lang.org/std/cmp/enum.Ordering.html).
+///
+/// Outputs the `k` greatest items according to the comparator, in descending order. Unlike
+/// `sort_by_key`, only `k` items are ever held in memory, so this can be used to rank large or
+/// unbounded inputs. Items which compare equal are kept in the order they arrived.
+///
+/// > Note: The closure has access to the [`context` object](surface_flows.mdx#the-context-object).
+///
+/// `top_k` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. With `'tick`, the top items are computed
+/// within each tick. With `'static`, the top items are remembered across ticks, and the current
+/// top items over all ticks so far are output on each tick (which requires `Item: Clone`). When
+/// not explicitly specified persistence defaults to `'tick`.
+///
+/// ```dfir
+/// source_iter([5, 1, 4, 2, 3])
+///     -> top_k(3, Ord::cmp)
+///     -> assert_eq([5, 4, 3]);
+/// ```
+///
+/// ```dfir
+/// // the two longest words
+/// source_iter(["a", "bbb", "cc", "dddd"])
+///     -> top_k(2, |a: &&str, b: &&str| a.len().cmp(&b.len()))
+///     -> assert_eq(["dddd", "bbb"]);
+/// ```
+pub const TOP_K: OperatorConstraints = OperatorConstraints {
+    name: "top_k",
+    categories: &[OperatorCategory::Fold],
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    num_args: 2,
+    persistence_args: &(0..=1),
+    type_args: RANGE_0,
+    is_external_input: false,
+    flo_type: None,
+    ports_inn: None,
+    ports_out: None,
+    input_delaytype_fn: |_| None,
+    write_fn: |wc @ &WriteContextArgs {
+                   root,
+                   op_span,
+                   work_fn,
+                   work_fn_async,
+                   ident,
+                   is_pull,
+                   inputs,
+                   outputs,
+                   arguments,
+                   op_name,
+                   ..
+               },
+               diagnostics| {
+        let k = &arguments[0];
+        let cmp = &arguments[1];
+        let [persistence] = wc.persistence_args(diagnostics);
+        let take = match persistence {
+            Persistence::None | Persistence::Tick => true,
+            Persistence::Static => false,
+            Persistence::Loop => {
+                diagnostics.push(Diagnostic::spanned(
+                    op_span,
+                    Level::Error,
+                    format!(
+                        "`{}()` can only have `'none`, `'tick`, or `'static` persistence.",
+                        op_name
+                    ),
+                ));
+                return Err(());
+            }
+        };
+
+        let state_ident = wc.make_ident("top_k");
+        let item_ident = wc.make_ident("item");
+
+        let write_prologue = quote_spanned! {op_span=>
+            let mut #state_ident = #root::util::sketch::TopK::new(#k);
+        };
+
+        let write_output = |state| {
+            if take {
+                quote_spanned! {op_span=>
+                    #root::util::sketch::TopK::take(#state)
+                }
+            } else {
+                quote_spanned! {op_span=>
+                    #root::util::sketch::TopK::to_vec(#state)
+                }
+            }
+        };
+
+        let write_iterator = if is_pull {
+            let input = &inputs[0];
+            let output = write_output(quote_spanned! {op_span=> &mut #state_ident });
+            quote_spanned! {op_span=>
+                // Eagerly consume input to ensure updated state.
+                {
+                    let __fut = #root::dfir_pipes::pull::Pull::for_each(#input, |#item_ident| {
+                        #root::util::sketch::TopK::insert(&mut #state_ident, #item_ident, #cmp);
+                    });
+                    let () = #work_fn_async(__fut).await;
+                }
+
+                let #ident = #work_fn(|| #root::dfir_pipes::pull::iter(#output));
+            }
+        } else {
+            let output = &outputs[0];
+            let drain = write_output(quote_spanned! {op_span=> __state });
+            quote_spanned! {op_span=>
+                let #ident = {
+                    #[inline(always)]
+                    fn __push_fold<'a, Acc, Item, CombFn, Next>(
+                        acc_ref: &'a mut Acc,
+                        comb_fn: CombFn,
+                        next: Next,
+                    ) -> #root::dfir_pipes::push::Accumulate<
+                        #root::dfir_pipes::push::FoldState<&'a mut Acc, CombFn, Acc, Item>,
+                        Next,
+                    >
+                    where
+                        CombFn: ::std::ops::FnMut(&mut Acc, Item),
+                        Next: #root::dfir_pipes::push::Push<&'a mut Acc, ()>,
+                    {
+                        #root::dfir_pipes::push::fold(acc_ref, comb_fn, next)
+                    }
+                    __push_fold(
+                        &mut #state_ident,
+                        |__state: &mut _, #item_ident| {
+                            #root::util::sketch::TopK::insert(__state, #item_ident, #cmp);
+                        },
+                        #root::dfir_pipes::push::flat_map(|__state: &mut _| #drain, #output),
+                    )
+                };
+            }
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    },
+};
@@ -1,21 +1,31 @@
 //! AST for surface syntax, modelled on [`syn`]'s ASTs.
 #![allow(clippy::allow_attributes, missing_docs, reason = "internal use")]
 
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::ops::Range;
+use std::str::FromStr;
 
-use proc_macro2::{Span, TokenStream};
-use quote::ToTokens;
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use quote::{ToTokens, TokenStreamExt, format_ident};
 use syn::parse::discouraged::Speculative;
 use syn::parse::{Parse, ParseStream, Parser};
 use syn::punctuated::Punctuated;
 use syn::token::{Brace, Bracket, Paren};
+use syn::visit_mut::VisitMut;
 use syn::{
-    AngleBracketedGenericArguments, Expr, ExprPath, GenericArgument, Ident, ItemUse, LitInt, Path,
-    PathArguments, PathSegment, Token, braced, bracketed, parenthesized,
+    AngleBracketedGenericArguments, Attribute, Block, Expr, ExprPath, GenericArgument, Ident,
+    ItemUse, LitInt, Macro, Pat, PatIdent, Path, PathArguments, PathSegment, Stmt, Token, braced,
+    bracketed, parenthesized, parse_quote,
 };
 
-use crate::process_singletons::preprocess_singletons;
+use crate::diagnostic::{Diagnostic, Diagnostics, Level};
+use crate::process_singletons::{map_singletons, preprocess_singletons};
+
+mod kw {
+    syn::custom_keyword!(def);
+}
 
 pub struct DfirCode {
     pub statements: Vec<DfirStatement>,
@@ -37,14 +47,131 @@ impl ToTokens for DfirCode {
     }
 }
 
+/// The result of [`parse_partial`]: every statement that could be parsed, plus diagnostics for
+/// the parts of the source that could not.
+pub struct PartialDfirCode {
+    /// Successfully parsed statements, in source order, each with its byte range in the source.
+    pub statements: Vec<(Range<usize>, DfirStatement)>,
+    /// Parse errors, located by byte range in the source.
+    pub diagnostics: Diagnostics<Range<usize>>,
+}
+
+/// Parses DFIR surface syntax from source text, recovering from errors.
+///
+/// This is intended for tools such as language servers, which need to analyze code that is
+/// being edited and is therefore often incomplete. Unlike parsing [`DfirCode`], which fails on
+/// the first error, this parses each statement independently: a statement that fails to parse
+/// is reported in [`PartialDfirCode::diagnostics`] and skipped up to its terminating `;`, and
+/// parsing continues with the next statement.
+///
+/// All locations are byte ranges into `source`, rather than [`Span`]s, so this does not need to
+/// run inside a proc macro. Because each statement's range is returned, a caller can re-parse
+/// only the statements touched by an edit, by calling this on that slice of the source and
+/// offsetting the resulting ranges.
+pub fn parse_partial(source: &str) -> PartialDfirCode {
+    let mut diagnostics = Diagnostics::new();
+
+    let tokens = match TokenStream::from_str(source) {
+        Ok(tokens) => tokens,
+        Err(lex_err) => {
+            diagnostics.push(Diagnostic {
+                span: span_byte_range(lex_err.span(), source.len()),
+                level: Level::Error,
+                message: lex_err.to_string(),
+            });
+            return PartialDfirCode {
+                statements: Vec::new(),
+                diagnostics,
+            };
+        }
+    };
+
+    let mut statements = Vec::new();
+    let parser = |input: ParseStream| -> syn::Result<()> {
+        while !input.is_empty() {
+            let fork = input.fork();
+            match fork.parse::<DfirStatement>() {
+                Ok(statement) => {
+                    input.advance_to(&fork);
+                    let range = tokens_byte_range(statement.to_token_stream(), source.len());
+                    statements.push((range, statement));
+                }
+                Err(err) => {
+                    diagnostics.extend(err.into_iter().map(|err| Diagnostic {
+                        span: span_byte_range(err.span(), source.len()),
+                        level: Level::Error,
+                        message: err.to_string(),
+                    }));
+                    // Skip to the end of the erroneous statement.
+                    while !input.is_empty() {
+                        if let TokenTree::Punct(punct) = input.parse::<TokenTree>()?
+                            && ';' == punct.as_char()
+                        {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    };
+    // Only fails if a single token tree cannot be parsed, which cannot happen.
+    parser.parse2(tokens).unwrap();
+
+    PartialDfirCode {
+        statements,
+        diagnostics,
+    }
+}
+
+/// Returns the byte range of `span` in the source it was lexed from. Errors at the end of the
+/// input are reported at the call site, which has no location, so they are placed at `end`.
+fn span_byte_range(span: Span, end: usize) -> Range<usize> {
+    let range = span.byte_range();
+    if range.is_empty() && 0 == range.start {
+        end..end
+    } else {
+        range
+    }
+}
+
+/// Returns the byte range spanned by `tokens`, from the start of the first token to the end of the
+/// last.
+fn tokens_byte_range(tokens: TokenStream, end: usize) -> Range<usize> {
+    let mut tokens = tokens.into_iter();
+    let Some(first) = tokens.next() else {
+        return end..end;
+    };
+    let start = span_byte_range(first.span(), end).start;
+    let last = tokens.last().unwrap_or(first);
+    start..span_byte_range(last.span(), end).end
+}
+
 pub enum DfirStatement {
     Use(ItemUse),
     Named(NamedStatement),
     Pipeline(PipelineStatement),
     Loop(LoopStatement),
+    Def(DefStatement),
 }
 impl Parse for DfirStatement {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![#]) && input.peek2(Bracket) {
+            let attrs = input.call(Attribute::parse_outer)?;
+            let mut statement: Self = input.parse()?;
+            match &mut statement {
+                Self::Named(named) => named.attrs = attrs,
+                Self::Pipeline(pipeline) => pipeline.attrs = attrs,
+                Self::Use(_) | Self::Loop(_) | Self::Def(_) => {
+                    return Err(syn::Error::new_spanned(
+                        &attrs[0],
+                        "Attributes are only supported on pipeline statements.",
+                    ));
+                }
+            }
+            return Ok(statement);
+        }
+
         let lookahead1 = input.lookahead1();
         if lookahead1.peek(Token![use]) {
             Ok(Self::Use(ItemUse::parse(input)?))
@@ -53,6 +180,8 @@ impl Parse for DfirStatement {
             Ok(Self::Pipeline(PipelineStatement::parse(input)?))
         } else if lookahead1.peek(Token![loop]) {
             Ok(Self::Loop(LoopStatement::parse(input)?))
+        } else if input.peek(kw::def) && input.peek2(Ident) {
+            Ok(Self::Def(DefStatement::parse(input)?))
         } else if lookahead1.peek(Ident) {
             let fork = input.fork();
             let _: Path = fork.parse()?;
@@ -79,11 +208,179 @@ impl ToTokens for DfirStatement {
             Self::Named(x) => x.to_tokens(tokens),
             Self::Pipeline(x) => x.to_tokens(tokens),
             Self::Loop(x) => x.to_tokens(tokens),
+            Self::Def(x) => x.to_tokens(tokens),
+        }
+    }
+}
+
+/// A pipeline function definition, `def name<G>(p) = pipeline;`, declaring a reusable pipeline
+/// snippet which is instantiated wherever it is invoked like an operator, `name::<g>(p)`.
+///
+/// Each generic parameter `G` is substituted, by name, with the corresponding generic argument of
+/// the invocation anywhere within the pipeline. Each parameter `p` is substituted with the
+/// corresponding argument in operator arguments, except where a closure parameter or a binding
+/// of the same name shadows it (see [`ParamSubstituter`]).
+#[derive(Debug)]
+pub struct DefStatement {
+    pub def_token: Ident,
+    pub name: Ident,
+    pub lt_token: Option<Token![<]>,
+    pub generics: Punctuated<Ident, Token![,]>,
+    pub gt_token: Option<Token![>]>,
+    pub paren_token: Paren,
+    pub params: Punctuated<Ident, Token![,]>,
+    pub equals: Token![=],
+    /// The tokens of the pipeline, before substitution.
+    pub body: TokenStream,
+    pub semi_token: Token![;],
+}
+impl DefStatement {
+    /// Substitutes the generic parameters and parameters into the body, returning the resulting
+    /// pipeline.
+    pub fn instantiate(
+        &self,
+        generic_args: &[GenericArgument],
+        args: &[Expr],
+    ) -> syn::Result<Pipeline> {
+        let generics = self
+            .generics
+            .iter()
+            .zip(generic_args)
+            .map(|(param, arg)| (param.to_string(), arg.to_token_stream()))
+            .collect();
+        let mut pipeline = syn::parse2(substitute_idents(self.body.clone(), &generics))?;
+
+        // Parenthesize each argument so that it keeps its precedence within the pipeline.
+        let params = self
+            .params
+            .iter()
+            .cloned()
+            .zip(args.iter().map(|arg| parse_quote!((#arg))))
+            .collect();
+        substitute_pipeline_params(&mut pipeline, &params)?;
+        Ok(pipeline)
+    }
+}
+impl Parse for DefStatement {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let def_token: kw::def = input.parse()?;
+        let def_token = Ident::new("def", def_token.span);
+        let name = input.parse()?;
+        let (lt_token, generics, gt_token) = if input.peek(Token![<]) {
+            let lt_token = input.parse()?;
+            let mut generics = Punctuated::new();
+            while !input.peek(Token![>]) {
+                generics.push_value(input.parse()?);
+                if input.peek(Token![>]) {
+                    break;
+                }
+                generics.push_punct(input.parse()?);
+            }
+            (Some(lt_token), generics, Some(input.parse()?))
+        } else {
+            (None, Punctuated::new(), None)
+        };
+        let content;
+        let paren_token = parenthesized!(content in input);
+        let params = Punctuated::parse_terminated(&content)?;
+        let equals = input.parse()?;
+        let body = input.step(|cursor| {
+            let mut rest = *cursor;
+            let mut body = TokenStream::new();
+            while let Some((tt, next)) = rest.token_tree() {
+                if matches!(&tt, TokenTree::Punct(punct) if punct.as_char() == ';') {
+                    break;
+                }
+                body.append(tt);
+                rest = next;
+            }
+            Ok((body, rest))
+        })?;
+        // Check that the body is a pipeline, ignoring the substitutions.
+        let _: Pipeline = syn::parse2(body.clone())?;
+        let semi_token = input.parse()?;
+        Ok(Self {
+            def_token,
+            name,
+            lt_token,
+            generics,
+            gt_token,
+            paren_token,
+            params,
+            equals,
+            body,
+            semi_token,
+        })
+    }
+}
+impl ToTokens for DefStatement {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.def_token.to_tokens(tokens);
+        self.name.to_tokens(tokens);
+        self.lt_token.to_tokens(tokens);
+        self.generics.to_tokens(tokens);
+        self.gt_token.to_tokens(tokens);
+        self.paren_token.surround(tokens, |tokens| {
+            self.params.to_tokens(tokens);
+        });
+        self.equals.to_tokens(tokens);
+        self.body.to_tokens(tokens);
+        self.semi_token.to_tokens(tokens);
+    }
+}
+
+/// Substitutes `params` into the arguments of each operator and module invocation in `pipeline`.
+fn substitute_pipeline_params(
+    pipeline: &mut Pipeline,
+    params: &BTreeMap<Ident, Expr>,
+) -> syn::Result<()> {
+    match pipeline {
+        Pipeline::Paren(ported) => substitute_pipeline_params(&mut ported.inner.pipeline, params),
+        Pipeline::Link(link) => {
+            substitute_pipeline_params(&mut link.lhs, params)?;
+            substitute_pipeline_params(&mut link.rhs, params)
         }
+        Pipeline::Operator(operator) => operator.substitute_params(params),
+        Pipeline::Module(module) => {
+            for arg in module.args.iter_mut() {
+                ParamSubstituter::new(params).visit_expr_mut(arg);
+            }
+            Ok(())
+        }
+        Pipeline::Name(_) | Pipeline::ModuleBoundary(_) => Ok(()),
     }
 }
 
+/// Replaces each ident in `tokens` named by a key of `substitutions` with its value.
+fn substitute_idents(
+    tokens: TokenStream,
+    substitutions: &BTreeMap<String, TokenStream>,
+) -> TokenStream {
+    tokens
+        .into_iter()
+        .flat_map(|tt| -> TokenStream {
+            match tt {
+                TokenTree::Ident(ident) => match substitutions.get(&ident.to_string()) {
+                    Some(value) => value.clone(),
+                    None => TokenTree::Ident(ident).into(),
+                },
+                TokenTree::Group(group) => {
+                    let mut new_group = Group::new(
+                        group.delimiter(),
+                        substitute_idents(group.stream(), substitutions),
+                    );
+                    new_group.set_span(group.span());
+                    TokenTree::Group(new_group).into()
+                }
+                other => other.into(),
+            }
+        })
+        .collect()
+}
+
 pub struct NamedStatement {
+    /// Outer attributes, such as `#[allow(unused)]`.
+    pub attrs: Vec<Attribute>,
     pub name: Ident,
     pub equals: Token![=],
     pub pipeline: Pipeline,
@@ -96,6 +393,7 @@ impl Parse for NamedStatement {
         let pipeline = input.parse()?;
         let semi_token = input.parse()?;
         Ok(Self {
+            attrs: Vec::new(),
             name,
             equals,
             pipeline,
@@ -105,6 +403,7 @@ impl Parse for NamedStatement {
 }
 impl ToTokens for NamedStatement {
     fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.append_all(&self.attrs);
         self.name.to_tokens(tokens);
         self.equals.to_tokens(tokens);
         self.pipeline.to_tokens(tokens);
@@ -113,6 +412,8 @@ impl ToTokens for NamedStatement {
 }
 
 pub struct PipelineStatement {
+    /// Outer attributes, such as `#[allow(unused)]`.
+    pub attrs: Vec<Attribute>,
     pub pipeline: Pipeline,
     pub semi_token: Token![;],
 }
@@ -121,6 +422,7 @@ impl Parse for PipelineStatement {
         let pipeline = input.parse()?;
         let semi_token = input.parse()?;
         Ok(Self {
+            attrs: Vec::new(),
             pipeline,
             semi_token,
         })
@@ -128,6 +430,7 @@ impl Parse for PipelineStatement {
 }
 impl ToTokens for PipelineStatement {
     fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.append_all(&self.attrs);
         self.pipeline.to_tokens(tokens);
         self.semi_token.to_tokens(tokens);
     }
@@ -140,6 +443,7 @@ pub enum Pipeline {
     Link(PipelineLink),
     Operator(Operator),
     ModuleBoundary(Ported<Token![mod]>),
+    Module(ModuleInvocation),
 }
 impl Pipeline {
     fn parse_one(input: ParseStream) -> syn::Result<Self> {
@@ -168,9 +472,18 @@ impl Pipeline {
             else {
                 Err(lookahead2.error())
             }
-        // module input/output
+        // module invocation or module input/output
         } else if lookahead1.peek(Token![mod]) {
-            Ok(Self::ModuleBoundary(input.parse()?))
+            if input.peek2(Ident)
+                || input.peek2(Token![::])
+                || input.peek2(Token![crate])
+                || input.peek2(Token![self])
+                || input.peek2(Token![super])
+            {
+                Ok(Self::Module(input.parse()?))
+            } else {
+                Ok(Self::ModuleBoundary(input.parse()?))
+            }
         // Ident or macro-style expression
         } else if lookahead1.peek(Ident) {
             let speculative = input.fork();
@@ -205,9 +518,15 @@ impl Parse for Pipeline {
             Ok(lhs)
         } else {
             let arrow = input.parse()?;
+            let bound = input.call(EdgeBound::parse_opt)?;
             let rhs = input.parse()?;
             let lhs = Box::new(lhs);
-            Ok(Self::Link(PipelineLink { lhs, arrow, rhs }))
+            Ok(Self::Link(PipelineLink {
+                lhs,
+                arrow,
+                bound,
+                rhs,
+            }))
         }
     }
 }
@@ -219,10 +538,142 @@ impl ToTokens for Pipeline {
             Self::Name(x) => x.to_tokens(tokens),
             Self::Operator(x) => x.to_tokens(tokens),
             Self::ModuleBoundary(x) => x.to_tokens(tokens),
+            Self::Module(x) => x.to_tokens(tokens),
         }
     }
 }
 
+/// An invocation of a module, `mod my_module(arg_port, config_expr)`.
+///
+/// Arguments are bound to the module's input ports and parameters, in the order they are
+/// declared in the module's [`ModuleSignature`].
+#[derive(Clone, Debug)]
+pub struct ModuleInvocation {
+    pub mod_token: Token![mod],
+    pub path: Path,
+    pub paren_token: Paren,
+    pub args: Punctuated<Expr, Token![,]>,
+}
+impl Parse for ModuleInvocation {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mod_token = input.parse()?;
+        let path = input.call(Path::parse_mod_style)?;
+        let content;
+        let paren_token = parenthesized!(content in input);
+        let args = Punctuated::parse_terminated(&content)?;
+        Ok(Self {
+            mod_token,
+            path,
+            paren_token,
+            args,
+        })
+    }
+}
+impl ToTokens for ModuleInvocation {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.mod_token.to_tokens(tokens);
+        self.path.to_tokens(tokens);
+        self.paren_token.surround(tokens, |tokens| {
+            self.args.to_tokens(tokens);
+        });
+    }
+}
+
+/// The contents of a module: a [`ModuleSignature`] followed by the module's statements.
+///
+/// ```text
+/// mod(in items: u32, param threshold: u32, out small: u32, out large: u32);
+/// mod[items] -> partition(|x: &u32, [small, large]| if *x < threshold { small } else { large })
+///     -> [small]mod;
+/// ...
+/// ```
+pub struct DfirModule {
+    pub signature: ModuleSignature,
+    pub statements: Vec<DfirStatement>,
+}
+impl Parse for DfirModule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let signature = input.parse()?;
+        let DfirCode { statements } = input.parse()?;
+        Ok(Self {
+            signature,
+            statements,
+        })
+    }
+}
+
+/// The signature of a module, `mod(in items: u32, param threshold: u32, out small: u32);`,
+/// declaring its typed input ports, parameters, and output ports.
+pub struct ModuleSignature {
+    pub mod_token: Token![mod],
+    pub paren_token: Paren,
+    pub items: Punctuated<ModuleSignatureItem, Token![,]>,
+    pub semi_token: Token![;],
+}
+impl Parse for ModuleSignature {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mod_token = input.parse()?;
+        let content;
+        let paren_token = parenthesized!(content in input);
+        let items = Punctuated::parse_terminated(&content)?;
+        let semi_token = input.parse()?;
+        Ok(Self {
+            mod_token,
+            paren_token,
+            items,
+            semi_token,
+        })
+    }
+}
+
+/// The kind of a [`ModuleSignatureItem`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModuleItemKind {
+    /// `in`: an input port, bound to a pipeline name argument.
+    Input,
+    /// `out`: an output port, accessed as `module_name[port]`.
+    Output,
+    /// `param`: an expression parameter, substituted into the module's operator arguments.
+    Param,
+}
+
+/// A single input port, output port, or parameter of a [`ModuleSignature`], e.g. `in items: u32`.
+pub struct ModuleSignatureItem {
+    pub kind: ModuleItemKind,
+    pub kind_span: Span,
+    pub name: Ident,
+    pub colon_token: Token![:],
+    pub ty: syn::Type,
+}
+impl Parse for ModuleSignatureItem {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let (kind, kind_span) = if input.peek(Token![in]) {
+            let in_token: Token![in] = input.parse()?;
+            (ModuleItemKind::Input, in_token.span)
+        } else {
+            let kind_ident: Ident = input.parse()?;
+            let kind = match &*kind_ident.to_string() {
+                "out" => ModuleItemKind::Output,
+                "param" => ModuleItemKind::Param,
+                _ => {
+                    return Err(syn::Error::new(
+                        kind_ident.span(),
+                        "Expected `in`, `out`, or `param`.",
+                    ));
+                }
+            };
+            (kind, kind_ident.span())
+        };
+        Ok(Self {
+            kind,
+            kind_span,
+            name: input.parse()?,
+            colon_token: input.parse()?,
+            ty: input.parse()?,
+        })
+    }
+}
+
 pub struct LoopStatement {
     pub loop_token: Token![loop],
     pub ident: Option<Ident>,
@@ -329,25 +780,92 @@ impl ToTokens for PipelineParen {
 pub struct PipelineLink {
     pub lhs: Box<Pipeline>,
     pub arrow: Token![->],
+    pub bound: Option<EdgeBound>,
     pub rhs: Box<Pipeline>,
 }
 impl Parse for PipelineLink {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let lhs = input.parse()?;
         let arrow = input.parse()?;
+        let bound = input.call(EdgeBound::parse_opt)?;
         let rhs = input.parse()?;
 
-        Ok(Self { lhs, arrow, rhs })
+        Ok(Self {
+            lhs,
+            arrow,
+            bound,
+            rhs,
+        })
     }
 }
 impl ToTokens for PipelineLink {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         self.lhs.to_tokens(tokens);
         self.arrow.to_tokens(tokens);
+        self.bound.to_tokens(tokens);
         self.rhs.to_tokens(tokens);
     }
 }
 
+/// An edge annotation, `-> [bounded(capacity)] next_op`, which places a bounded handoff with the
+/// given capacity on the edge. It hands at most `capacity` items to `next_op` each tick, and
+/// defers the upstream subgraph until it has room. Items sent by a single run of the upstream
+/// subgraph past the capacity are still buffered.
+#[derive(Clone, Debug)]
+pub struct EdgeBound {
+    pub bracket_token: Bracket,
+    pub bounded_ident: Ident,
+    pub paren_token: Paren,
+    pub capacity: LitInt,
+}
+impl EdgeBound {
+    /// Parses an `[bounded(capacity)]` annotation if one is next. Any other bracketed content is
+    /// left to be parsed as a port [`Indexing`].
+    fn parse_opt(input: ParseStream) -> syn::Result<Option<Self>> {
+        if !input.peek(Bracket) {
+            return Ok(None);
+        }
+        let fork = input.fork();
+        let content;
+        bracketed!(content in fork);
+        let is_bound = content
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "bounded")
+            && content.peek(Paren);
+        if is_bound {
+            input.parse().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+impl Parse for EdgeBound {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        let bracket_token = bracketed!(content in input);
+        let bounded_ident = content.parse()?;
+        let capacity_content;
+        let paren_token = parenthesized!(capacity_content in content);
+        let capacity = capacity_content.parse()?;
+        Ok(Self {
+            bracket_token,
+            bounded_ident,
+            paren_token,
+            capacity,
+        })
+    }
+}
+impl ToTokens for EdgeBound {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.bracket_token.surround(tokens, |tokens| {
+            self.bounded_ident.to_tokens(tokens);
+            self.paren_token.surround(tokens, |tokens| {
+                self.capacity.to_tokens(tokens);
+            });
+        });
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Indexing {
     pub bracket_token: Bracket,
@@ -402,8 +920,169 @@ impl ToTokens for PortIndex {
     }
 }
 
+/// Substitutes expressions for the variables named by the keys of `params`, except where a
+/// variable is shadowed by a closure parameter, or by a `let`, `match` arm, `for`, `if let`, or
+/// `while let` binding.
+pub(crate) struct ParamSubstituter<'a> {
+    params: &'a BTreeMap<Ident, Expr>,
+    /// The variables bound in the scope being visited.
+    shadowed: Vec<Ident>,
+}
+impl<'a> ParamSubstituter<'a> {
+    pub fn new(params: &'a BTreeMap<Ident, Expr>) -> Self {
+        Self {
+            params,
+            shadowed: Vec::new(),
+        }
+    }
+
+    /// Visits within the scope of the variables bound by `pats`.
+    fn with_bindings(
+        &mut self,
+        pats: impl IntoIterator<Item = Pat>,
+        visit: impl FnOnce(&mut Self),
+    ) {
+        let outer_len = self.shadowed.len();
+        for mut pat in pats {
+            PatBindings(&mut self.shadowed).visit_pat_mut(&mut pat);
+        }
+        visit(self);
+        self.shadowed.truncate(outer_len);
+    }
+}
+impl VisitMut for ParamSubstituter<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Path(expr_path) => {
+                if expr_path.qself.is_none()
+                    && let Some(ident) = expr_path.path.get_ident()
+                    && !self.shadowed.contains(ident)
+                    && let Some(value) = self.params.get(ident)
+                {
+                    *expr = value.clone();
+                }
+            }
+            Expr::Closure(closure) => {
+                let inputs = closure.inputs.iter().cloned().collect::<Vec<_>>();
+                self.with_bindings(inputs, |this| this.visit_expr_mut(&mut closure.body));
+            }
+            Expr::ForLoop(for_loop) => {
+                self.visit_expr_mut(&mut for_loop.expr);
+                let pat = (*for_loop.pat).clone();
+                self.with_bindings([pat], |this| this.visit_block_mut(&mut for_loop.body));
+            }
+            Expr::Match(expr_match) => {
+                self.visit_expr_mut(&mut expr_match.expr);
+                for arm in expr_match.arms.iter_mut() {
+                    let pat = arm.pat.clone();
+                    self.with_bindings([pat], |this| {
+                        if let Some((_, guard)) = &mut arm.guard {
+                            this.visit_expr_mut(guard);
+                        }
+                        this.visit_expr_mut(&mut arm.body);
+                    });
+                }
+            }
+            Expr::If(expr_if) if matches!(&*expr_if.cond, Expr::Let(_)) => {
+                let Expr::Let(expr_let) = &mut *expr_if.cond else {
+                    unreachable!()
+                };
+                self.visit_expr_mut(&mut expr_let.expr);
+                let pat = (*expr_let.pat).clone();
+                self.with_bindings([pat], |this| this.visit_block_mut(&mut expr_if.then_branch));
+                if let Some((_, else_branch)) = &mut expr_if.else_branch {
+                    self.visit_expr_mut(else_branch);
+                }
+            }
+            Expr::While(expr_while) if matches!(&*expr_while.cond, Expr::Let(_)) => {
+                let Expr::Let(expr_let) = &mut *expr_while.cond else {
+                    unreachable!()
+                };
+                self.visit_expr_mut(&mut expr_let.expr);
+                let pat = (*expr_let.pat).clone();
+                self.with_bindings([pat], |this| this.visit_block_mut(&mut expr_while.body));
+            }
+            _ => syn::visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        let outer_len = self.shadowed.len();
+        for stmt in block.stmts.iter_mut() {
+            if let Stmt::Local(local) = stmt {
+                // The binding is only in scope after the `let`.
+                if let Some(init) = &mut local.init {
+                    self.visit_expr_mut(&mut init.expr);
+                    if let Some((_, diverge)) = &mut init.diverge {
+                        self.visit_expr_mut(diverge);
+                    }
+                }
+                PatBindings(&mut self.shadowed).visit_pat_mut(&mut local.pat);
+            } else {
+                self.visit_stmt_mut(stmt);
+            }
+        }
+        self.shadowed.truncate(outer_len);
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut Macro) {
+        // Macro arguments are opaque tokens, so every ident which is not shadowed is substituted.
+        let substitutions = self
+            .params
+            .iter()
+            .filter(|(param, _)| !self.shadowed.contains(param))
+            .map(|(param, value)| (param.to_string(), value.to_token_stream()))
+            .collect();
+        mac.tokens = substitute_idents(std::mem::take(&mut mac.tokens), &substitutions);
+    }
+}
+
+/// Collects the variables bound by a pattern.
+struct PatBindings<'a>(&'a mut Vec<Ident>);
+impl VisitMut for PatBindings<'_> {
+    fn visit_pat_ident_mut(&mut self, pat_ident: &mut PatIdent) {
+        self.0.push(pat_ident.ident.clone());
+        syn::visit_mut::visit_pat_ident_mut(self, pat_ident);
+    }
+
+    fn visit_expr_mut(&mut self, _expr: &mut Expr) {
+        // Expressions within patterns, such as constants, bind nothing.
+    }
+}
+
+/// Restores the singleton references replaced by `__dfir_singleton_{index}` placeholders, see
+/// [`Operator::substitute_params`].
+fn restore_singletons(tokens: TokenStream, singletons: &[SingletonRef]) -> TokenStream {
+    tokens
+        .into_iter()
+        .flat_map(|tt| -> TokenStream {
+            match tt {
+                TokenTree::Ident(ident) => {
+                    match ident
+                        .to_string()
+                        .strip_prefix("__dfir_singleton_")
+                        .and_then(|index| index.parse::<usize>().ok())
+                    {
+                        Some(index) => singletons[index].to_token_stream(),
+                        None => TokenTree::Ident(ident).into(),
+                    }
+                }
+                TokenTree::Group(group) => {
+                    let mut new_group = Group::new(
+                        group.delimiter(),
+                        restore_singletons(group.stream(), singletons),
+                    );
+                    new_group.set_span(group.span());
+                    TokenTree::Group(new_group).into()
+                }
+                other => other.into(),
+            }
+        })
+        .collect()
+}
+
 struct TypeHintRemover;
-impl syn::visit_mut::VisitMut for TypeHintRemover {
+impl VisitMut for TypeHintRemover {
     fn visit_expr_mut(&mut self, expr: &mut Expr) {
         if let Expr::Call(expr_call) = expr &&
             let Expr::Path(path) = expr_call.func.as_ref() &&
@@ -435,6 +1114,35 @@ pub struct Operator {
 }
 
 impl Operator {
+    /// Substitutes `params` into the arguments of this operator, see [`ParamSubstituter`].
+    /// Singleton references are kept as they are.
+    pub fn substitute_params(&mut self, params: &BTreeMap<Ident, Expr>) -> syn::Result<()> {
+        // Singleton references are not valid Rust, so they are replaced by placeholders while the
+        // arguments are parsed.
+        let mut singletons = Vec::new();
+        let marked = map_singletons(self.args_raw.clone(), |singleton| {
+            let placeholder = format_ident!(
+                "__dfir_singleton_{}",
+                singletons.len(),
+                span = singleton.ident.span()
+            );
+            singletons.push(singleton);
+            TokenTree::Ident(placeholder)
+        });
+        let mut args = Punctuated::<Expr, Token![,]>::parse_terminated.parse2(marked)?;
+        for arg in args.iter_mut() {
+            ParamSubstituter::new(params).visit_expr_mut(arg);
+        }
+
+        self.args_raw = restore_singletons(args.into_token_stream(), &singletons);
+        self.singletons_referenced.clear();
+        self.args = Punctuated::parse_terminated.parse2(preprocess_singletons(
+            self.args_raw.clone(),
+            &mut self.singletons_referenced,
+        ))?;
+        Ok(())
+    }
+
     pub fn name(&self) -> Path {
         Path {
             leading_colon: self.path.leading_colon,
@@ -696,4 +1404,28 @@ demux(|(msg, addr), var_args!(clients, msgs, errs)| match msg {
             op.to_pretty_string()
         );
     }
+
+    #[test]
+    fn test_parse_partial_recovers() {
+        let source = "a = source_iter([1, 2]) -> ;\nb = source_iter([3]) -> for_each(drop);";
+        let partial = parse_partial(source);
+
+        assert_eq!(1, partial.statements.len());
+        let (range, _statement) = &partial.statements[0];
+        assert_eq!(
+            &source[range.clone()],
+            "b = source_iter([3]) -> for_each(drop);"
+        );
+
+        assert_eq!(1, partial.diagnostics.len());
+        let diagnostic = partial.diagnostics.iter().next().unwrap();
+        assert!(diagnostic.span.end <= source.find('\n').unwrap());
+    }
+
+    #[test]
+    fn test_parse_partial_unbalanced() {
+        let partial = parse_partial("a = source_iter([1, 2] -> for_each(drop);");
+        assert!(partial.statements.is_empty());
+        assert!(partial.diagnostics.has_error());
+    }
 }
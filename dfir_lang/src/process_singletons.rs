@@ -23,6 +23,15 @@ pub fn preprocess_singletons(tokens: TokenStream, found: &mut Vec<SingletonRef>)
     })
 }
 
+/// Replaces each singleton reference with the token returned by `map_singleton_fn`, returning the
+/// transformed token stream.
+pub fn map_singletons(
+    tokens: TokenStream,
+    mut map_singleton_fn: impl FnMut(SingletonRef) -> TokenTree,
+) -> TokenStream {
+    process_singletons(tokens, &mut map_singleton_fn)
+}
+
 /// Replaces singleton references with the code needed to actually get the value inside.
 ///
 /// * `tokens` - The tokens to update singleton references within.
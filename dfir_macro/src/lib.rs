@@ -1,6 +1,7 @@
 use dfir_lang::diagnostic::Level;
 use dfir_lang::graph::{
-    BuildDfirCodeOutput, FlatGraphBuilder, FlatGraphBuilderOutput, build_dfir_code, partition_graph,
+    BuildDfirCodeOptions, BuildDfirCodeOutput, FlatGraphBuilder, FlatGraphBuilderOutput,
+    build_dfir_code_with_options, partition_graph,
 };
 use dfir_lang::parse::DfirCode;
 use proc_macro2::{Ident, Literal, Span};
@@ -19,7 +20,7 @@ use syn::{
 // TODO(mingwei): rustdoc examples inline.
 #[proc_macro]
 pub fn dfir_syntax(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    dfir_syntax_internal(input, Some(Level::Help))
+    dfir_syntax_internal(input, Some(Level::Help), &BuildDfirCodeOptions::default())
 }
 
 /// [`dfir_syntax!`] but will not emit any diagnostics (errors, warnings, etc.).
@@ -27,7 +28,20 @@ pub fn dfir_syntax(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// Used for testing, users will want to use [`dfir_syntax!`] instead.
 #[proc_macro]
 pub fn dfir_syntax_noemit(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    dfir_syntax_internal(input, None)
+    dfir_syntax_internal(input, None, &BuildDfirCodeOptions::default())
+}
+
+/// [`dfir_syntax!`] but fuses chains of `map`, `filter`, and `filter_map` operators into single
+/// operators, see [`dfir_lang::graph::fuse_stateless_operators`].
+#[proc_macro]
+pub fn dfir_syntax_fused(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    dfir_syntax_internal(
+        input,
+        Some(Level::Help),
+        &BuildDfirCodeOptions {
+            fuse_operators: true,
+        },
+    )
 }
 
 fn root() -> proc_macro2::TokenStream {
@@ -64,11 +78,12 @@ fn root() -> proc_macro2::TokenStream {
 fn dfir_syntax_internal(
     input: proc_macro::TokenStream,
     retain_diagnostic_level: Option<Level>,
+    options: &BuildDfirCodeOptions,
 ) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DfirCode);
     let root = root();
 
-    let (code, mut diagnostics) = match build_dfir_code(input, &root) {
+    let (code, mut diagnostics) = match build_dfir_code_with_options(input, &root, options) {
         Ok(BuildDfirCodeOutput {
             partitioned_graph: _,
             code,
@@ -225,6 +240,7 @@ pub fn derive_demux_enum(item: proc_macro::TokenStream) -> proc_macro::TokenStre
     let root = root();
 
     let ItemEnum {
+        vis,
         ident: item_ident,
         generics,
         variants,
@@ -448,7 +464,43 @@ pub fn derive_demux_enum(item: proc_macro::TokenStream) -> proc_macro::TokenStre
         }
     });
 
+    // Constructors of each variant from the tuple of its fields, for the `mux_enum` operator. These
+    // are associated functions of a hidden type, so they do not clash with methods of the enum.
+    let constructors_ident = format_ident!("__{}MuxEnumConstructors", item_ident);
+    let variant_constructors =
+        variants
+            .iter()
+            .zip(variant_output_types.iter())
+            .map(|(variant, output_type)| {
+                let Variant { ident, fields, .. } = variant;
+                let (fields_pat, push_item) = field_pattern_item(fields);
+                quote! {
+                    #[doc(hidden)]
+                    #[allow(non_snake_case, reason = "named after the variant")]
+                    #[allow(dead_code, reason = "only used by `mux_enum`")]
+                    #vis fn #ident(#push_item: #output_type) -> #item_ident #ty_generics {
+                        #item_ident::#ident #fields_pat
+                    }
+                }
+            });
+
     quote! {
+        #[doc(hidden)]
+        #[allow(dead_code, reason = "only used by `mux_enum`")]
+        #vis struct #constructors_ident #generics #where_clause_item {
+            _enum: ::std::marker::PhantomData<fn() -> #item_ident #ty_generics>,
+        }
+
+        impl #impl_generics_item #constructors_ident #ty_generics #where_clause_item {
+            #( #variant_constructors )*
+        }
+
+        impl #impl_generics_item #root::util::demux_enum::MuxEnum
+            for #item_ident #ty_generics #where_clause_item
+        {
+            type Constructors = #constructors_ident #ty_generics;
+        }
+
         impl #impl_generics_sink #root::util::demux_enum::DemuxEnumSink<#variant_generics_pinned_sink_all>
             for #item_ident #ty_generics #where_clause_sink
         {
@@ -65,26 +65,42 @@ async fn main() {
 
 #[test]
 fn test() {
-    use example_test::run_current_example;
-
-    let mut server = run_current_example!("--role server --name server --address 127.0.0.1:11247");
-    server.read_regex("Server live!");
-
-    let mut client1 =
-        run_current_example!("--role client --name client1 --address 127.0.0.1:11247");
-    let mut client2 =
-        run_current_example!("--role client --name client2 --address 127.0.0.1:11247");
-
-    client1.read_regex("Client is live!");
-    client2.read_regex("Client is live!");
-
-    // Wait for the server to ack both connect requests, so we know the server has registered
-    // both clients before any chat message is sent (otherwise the message would be dropped).
-    client1.read_string("Connected to server!");
-    client2.read_string("Connected to server!");
-
-    client1.write_line("Hello");
-    client2.read_regex(".*, .* client1: Hello");
+    use example_test::{Scenario, run_current_example};
+
+    Scenario::new()
+        .spawn("server", |_| {
+            run_current_example!("--role server --name server --address 127.0.0.1:0")
+        })
+        .capture("server", r"Server is live! Listening on (\S+)", "server")
+        .spawn("client1", |captures| {
+            run_current_example!([
+                "--role",
+                "client",
+                "--name",
+                "client1",
+                "--address",
+                &captures["server"]
+            ])
+        })
+        .wait_for("client1", "Client is live!")
+        .spawn("client2", |captures| {
+            run_current_example!([
+                "--role",
+                "client",
+                "--name",
+                "client2",
+                "--address",
+                &captures["server"]
+            ])
+        })
+        .wait_for("client2", "Client is live!")
+        // Wait for the server to ack both connect requests, so we know the server has registered
+        // both clients before any chat message is sent (otherwise the message would be dropped).
+        .wait_for("client1", "Connected to server!")
+        .wait_for("client2", "Connected to server!")
+        .write_line("client1", "Hello")
+        .wait_for_regex("client2", ".*, .* client1: Hello")
+        .run();
 }
 
 #[test]
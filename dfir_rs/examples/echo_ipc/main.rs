@@ -0,0 +1,89 @@
+use clap::{Parser, ValueEnum};
+use dfir_rs::dfir_syntax;
+use dfir_rs::tokio::task;
+use dfir_rs::util::{bind_ipc, connect_ipc, ipc_lines};
+
+/// This is the main entry-point for both `Client` and `Server`.
+#[dfir_rs::main]
+async fn main() {
+    let opts = Opts::parse();
+    match opts.role {
+        Role::Server => run_server(&opts.name).await,
+        Role::Client => run_client(&opts.name).await,
+    }
+}
+
+/// An echo server & client which talk over local IPC: a Unix domain socket, or a named pipe on
+/// Windows.
+#[derive(Parser, Debug)]
+struct Opts {
+    /// The role this application process should assume.
+    #[clap(value_enum, long)]
+    role: Role,
+
+    /// The name of the local IPC endpoint. The server creates it, and clients connect to it.
+    #[clap(long, default_value = "dfir-echo-ipc")]
+    name: String,
+}
+
+#[derive(Clone, ValueEnum, Debug)]
+enum Role {
+    Client,
+    Server,
+}
+
+/// Runs the server, which echoes every line back to the client that sent it. Each connection is
+/// served by its own flow.
+async fn run_server(name: &str) {
+    let mut listener = bind_ipc(name).unwrap();
+    println!("Server is live! Listening on {}", name);
+
+    loop {
+        let (outbound, inbound) = ipc_lines(listener.accept().await.unwrap());
+        task::spawn_local(async move {
+            let mut flow = dfir_syntax! {
+                source_stream(inbound)
+                    -> map(Result::unwrap)
+                    -> inspect(|line| println!("Got {:?}", line))
+                    -> dest_sink(outbound);
+            };
+            flow.run().await;
+        });
+    }
+}
+
+/// Runs the client, which sends each line of stdin to the server and prints the replies.
+async fn run_client(name: &str) {
+    let (outbound, inbound) = ipc_lines(connect_ipc(name).await.unwrap());
+    println!("Client is live! Talking to server on {}", name);
+
+    let mut flow = dfir_syntax! {
+        source_stdin()
+            -> map(Result::unwrap)
+            -> dest_sink(outbound);
+
+        source_stream(inbound)
+            -> map(Result::unwrap)
+            -> for_each(|line| println!("Got {:?} back", line));
+    };
+    flow.run().await;
+}
+
+#[test]
+fn test() {
+    use example_test::{Scenario, run_current_example};
+
+    let name = format!("dfir-echo-ipc-test-{}", std::process::id());
+    Scenario::new()
+        .spawn("server", |_| {
+            run_current_example!(["--role", "server", "--name", &name])
+        })
+        .wait_for("server", "Server is live!")
+        .spawn("client", |_| {
+            run_current_example!(["--role", "client", "--name", &name])
+        })
+        .wait_for("client", "Client is live!")
+        .write_line("client", "Hello")
+        .wait_for("client", "Got \"Hello\" back")
+        .run();
+}
@@ -73,16 +73,20 @@ enum Role {
 
 #[test]
 fn test() {
-    use example_test::run_current_example;
+    use example_test::{Scenario, run_current_example};
 
-    let mut server = run_current_example!("--role server --address 127.0.0.1:2049");
-    server.read_string("Server is live! Listening on 127.0.0.1:2049");
-
-    let mut client = run_current_example!("--role client --address 127.0.0.1:2049");
-    client.read_regex(
-        r"Client is live! Listening on 127\.0\.0\.1:\d+ and talking to server on 127\.0\.0\.1:2049",
-    );
-
-    client.write_line("Hello");
-    client.read_regex(r#"EchoMsg \{ payload: \"Hello\", ts: .* \}"#);
+    Scenario::new()
+        .spawn("server", |_| {
+            run_current_example!("--role server --address 127.0.0.1:0")
+        })
+        .capture("server", r"Server is live! Listening on (\S+)", "server")
+        .spawn("client", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server"]])
+        })
+        .wait_for_captured("client", |captures| {
+            format!("talking to server on {}", &captures["server"])
+        })
+        .write_line("client", "Hello")
+        .wait_for_regex("client", r#"EchoMsg \{ payload: \"Hello\", ts: .* \}"#)
+        .run();
 }
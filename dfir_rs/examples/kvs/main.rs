@@ -68,33 +68,40 @@ enum Role {
 
 #[test]
 fn test() {
-    use example_test::run_current_example;
-
-    let mut server = run_current_example!("--role server --address 127.0.0.1:2051");
-    server.read_string("Server is live! Listening on 127.0.0.1:2051");
-
-    let mut client1 = run_current_example!("--role client --address 127.0.0.1:2051");
-    client1.read_regex(
-        "Client is live! Listening on 127.0.0.1:\\d+ and talking to server on 127.0.0.1:2051",
-    );
-
-    client1.write_line("PUT a,7");
-    // Wait for the ack, so we know the server has applied the PUT before issuing GETs.
-    client1.read_string(r#"Got a Response: PutAck { key: "a" }"#);
-
-    let mut client2 = run_current_example!("--role client --address 127.0.0.1:2051");
-    client2.read_regex(
-        "Client is live! Listening on 127.0.0.1:\\d+ and talking to server on 127.0.0.1:2051",
-    );
-
-    client2.write_line("GET a");
-    client2.read_string(r#"Got a Response: GetResult { key: "a", value: "7" }"#);
-
-    client1.write_line("PUT a,8");
-    client1.read_string(r#"Got a Response: PutAck { key: "a" }"#);
-    client1.write_line("GET a");
-    client1.read_string(
-        r#"Got a Response: GetResult { key: "a", value: "7" }
+    use example_test::{Scenario, run_current_example};
+
+    Scenario::new()
+        .spawn("server", |_| {
+            run_current_example!("--role server --address 127.0.0.1:0")
+        })
+        .capture("server", r"Server is live! Listening on (\S+)", "server")
+        .spawn("client1", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server"]])
+        })
+        .wait_for_captured("client1", |captures| {
+            format!("talking to server on {}", &captures["server"])
+        })
+        .write_line("client1", "PUT a,7")
+        // Wait for the ack, so we know the server has applied the PUT before issuing GETs.
+        .wait_for("client1", r#"Got a Response: PutAck { key: "a" }"#)
+        .spawn("client2", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server"]])
+        })
+        .wait_for_captured("client2", |captures| {
+            format!("talking to server on {}", &captures["server"])
+        })
+        .write_line("client2", "GET a")
+        .wait_for(
+            "client2",
+            r#"Got a Response: GetResult { key: "a", value: "7" }"#,
+        )
+        .write_line("client1", "PUT a,8")
+        .wait_for("client1", r#"Got a Response: PutAck { key: "a" }"#)
+        .write_line("client1", "GET a")
+        .wait_for(
+            "client1",
+            r#"Got a Response: GetResult { key: "a", value: "7" }
 Got a Response: GetResult { key: "a", value: "8" }"#,
-    );
+        )
+        .run();
 }
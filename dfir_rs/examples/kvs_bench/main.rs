@@ -1,11 +1,13 @@
 mod buffer_pool;
 mod protocol;
+mod report;
 mod server;
+mod workload;
 
 use std::collections::HashMap;
 use std::num::ParseFloatError;
-use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use bytes::Bytes;
 use clap::{Parser, Subcommand};
@@ -16,7 +18,9 @@ use tokio_stream::StreamExt;
 use web_time::{Duration, Instant};
 
 use crate::protocol::NodeId;
-use crate::server::run_server;
+use crate::report::{Latencies, OpReport, Report};
+use crate::server::{BUFFER_SIZE, run_server};
+use crate::workload::Workload;
 
 #[derive(Debug, Parser)]
 struct Cli {
@@ -31,9 +35,8 @@ enum Commands {
         #[clap(long, default_value_t = 1)]
         threads: usize,
 
-        /// `s` value for the zipf sampling distribution for keys.
-        #[clap(long, default_value_t = 4.0)]
-        dist: f64,
+        #[clap(flatten)]
+        workload: Workload,
 
         /// How long to warm up for, in seconds.
         #[clap(long, default_value = "2", value_parser = clap_duration_from_secs)]
@@ -43,10 +46,15 @@ enum Commands {
         #[clap(long, default_value = "10", value_parser = clap_duration_from_secs)]
         duration: Duration,
 
-        /// Write the puts/s every second while running.
+        /// Write the requests/s every second while running.
         #[clap(long, default_value_t = false)]
         report: bool,
 
+        /// Write a JSON report of the throughput and latencies at the end, instead of only the
+        /// requests/s.
+        #[clap(long, default_value_t = false)]
+        json: bool,
+
         #[clap(long)]
         graph: Option<WriteGraphType>,
         #[clap(flatten)]
@@ -85,19 +93,27 @@ fn main() {
     match Cli::parse().command {
         Commands::Bench {
             threads,
-            dist,
+            workload,
             warmup,
             duration,
             report,
+            json,
             mut graph,
             mut write_config,
         } => {
+            assert!(
+                workload.value_size <= BUFFER_SIZE,
+                "`--value-size` must be at most {BUFFER_SIZE}"
+            );
+
             let mut throughputs = Vec::new();
+            let mut latencies = Vec::new();
             let mut nodes: HashMap<NodeId, Topology<_>> = HashMap::default();
             // let mut receivers_for: HashMap<NodeId, Vec<_>> = HashMap::default();
 
             for n1 in 0..threads {
                 throughputs.push(Arc::new(AtomicUsize::new(0)));
+                latencies.push(Arc::new(Mutex::new(Latencies::default())));
 
                 nodes.entry(n1).or_default();
 
@@ -129,8 +145,9 @@ fn main() {
                 run_server(
                     node_id,
                     topology,
-                    dist,
+                    workload.clone(),
                     throughputs[node_id].clone(),
+                    latencies[node_id].clone(),
                     // Only want one node to print the graph since it is the same for all of them.
                     graph.take(),
                     write_config.take(),
@@ -146,11 +163,14 @@ fn main() {
                 sum
             };
 
-            let mut total_writes_so_far = 0;
+            let mut total_requests_so_far = 0;
 
             std::thread::sleep(warmup);
 
             get_reset_throughputs();
+            for latencies in latencies.iter() {
+                latencies.lock().unwrap().reset();
+            }
             let start_time = Instant::now();
             let mut time_last_interval = start_time;
 
@@ -162,20 +182,37 @@ fn main() {
                 std::thread::sleep(Duration::from_secs(1));
 
                 if report {
-                    let writes_this_interval = get_reset_throughputs();
-                    let puts =
-                        writes_this_interval as f64 / time_last_interval.elapsed().as_secs_f64();
+                    let requests_this_interval = get_reset_throughputs();
+                    let ops =
+                        requests_this_interval as f64 / time_last_interval.elapsed().as_secs_f64();
                     time_last_interval = Instant::now();
-                    println!("{puts}");
+                    println!("{ops}");
 
-                    total_writes_so_far += writes_this_interval;
+                    total_requests_so_far += requests_this_interval;
                 }
             }
 
-            total_writes_so_far += get_reset_throughputs();
-            let puts = total_writes_so_far as f64 / start_time.elapsed().as_secs_f64();
+            total_requests_so_far += get_reset_throughputs();
+            let elapsed = start_time.elapsed().as_secs_f64();
+            let ops = total_requests_so_far as f64 / elapsed;
 
-            println!("{puts}");
+            if json {
+                let mut total = Latencies::default();
+                for latencies in latencies.iter() {
+                    total.add(&latencies.lock().unwrap());
+                }
+                let report = Report {
+                    workload,
+                    threads,
+                    duration_secs: elapsed,
+                    ops_per_sec: ops,
+                    put: OpReport::new(&total.put, elapsed),
+                    get: OpReport::new(&total.get, elapsed),
+                };
+                println!("{}", serde_json::to_string(&report).unwrap());
+            } else {
+                println!("{ops}");
+            }
         }
     }
 }
@@ -187,3 +224,13 @@ fn test() {
     let mut server = run_current_example!("bench --threads 2");
     server.read_regex(r#"[0-9]+\.[0-9]+"#);
 }
+
+#[test]
+fn test_json_report() {
+    use example_test::run_current_example;
+
+    let mut server = run_current_example!(
+        "bench --threads 2 --warmup 0 --duration 1 --read-ratio 0.5 --arrival poisson --rate 10000 --json"
+    );
+    server.read_regex(r#""ops_per_sec":[0-9.]+"#);
+}
@@ -8,6 +8,7 @@ use lattices::map_union::MapUnionHashMap;
 use lattices::set_union::SetUnionHashSet;
 use lattices::{DomPair, Max, Point, WithBot};
 pub use serialization::KvsRequestDeserializer;
+use web_time::Instant;
 
 use crate::buffer_pool::AutoReturnBuffer;
 
@@ -15,7 +16,8 @@ pub type NodeId = usize;
 
 pub type MyLastWriteWins<const SIZE: usize> =
     DomPair<Max<u128>, WithBot<Point<AutoReturnBuffer<SIZE>, ()>>>;
-pub type MySetUnion = SetUnionHashSet<(NodeId, usize)>;
+/// The gets waiting for a key: the client, a unique ID, and the arrival time of simulated gets.
+pub type MySetUnion = SetUnionHashSet<(NodeId, usize, Option<Instant>)>;
 
 #[derive(Clone, Debug)]
 pub enum KvsRequest<const SIZE: usize> {
@@ -51,6 +53,8 @@ pub enum KvsRequestDemux<const SIZE: usize> {
     Get {
         key: u64,
         addr: NodeId,
+        /// When the get arrived, if it was simulated by this server.
+        arrival: Option<Instant>,
     },
     /// Put or Delete.
     Update {
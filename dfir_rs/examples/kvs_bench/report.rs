@@ -0,0 +1,133 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use web_time::{Duration, Instant};
+
+use crate::workload::{Op, Workload};
+
+/// Latencies of the requests of a server, in microseconds, from when each request arrived until
+/// it was processed (puts) or responded to (gets).
+pub struct Latencies {
+    pub put: Histogram<u64>,
+    pub get: Histogram<u64>,
+}
+
+impl Default for Latencies {
+    fn default() -> Self {
+        // Up to a minute, with 3 significant digits.
+        let histogram = || Histogram::new_with_bounds(1, 60_000_000, 3).unwrap();
+        Self {
+            put: histogram(),
+            get: histogram(),
+        }
+    }
+}
+
+impl Latencies {
+    pub fn record(&mut self, op: Op, latency: Duration) {
+        let histogram = match op {
+            Op::Put => &mut self.put,
+            Op::Get => &mut self.get,
+        };
+        histogram.saturating_record(latency.as_micros() as u64);
+    }
+
+    pub fn reset(&mut self) {
+        self.put.reset();
+        self.get.reset();
+    }
+
+    pub fn add(&mut self, other: &Latencies) {
+        self.put.add(&other.put).unwrap();
+        self.get.add(&other.get).unwrap();
+    }
+}
+
+/// Records the completed requests of a server into local histograms, which are published to the
+/// shared throughput counter and latencies periodically, so that recording does not take a lock.
+pub struct Recorder {
+    local: Latencies,
+    completed: usize,
+    last_publish: Instant,
+    throughput: Arc<AtomicUsize>,
+    latencies: Arc<Mutex<Latencies>>,
+}
+
+impl Recorder {
+    const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn new(throughput: Arc<AtomicUsize>, latencies: Arc<Mutex<Latencies>>) -> Self {
+        Self {
+            local: Latencies::default(),
+            completed: 0,
+            last_publish: Instant::now(),
+            throughput,
+            latencies,
+        }
+    }
+
+    /// Records that a request which arrived at `arrival` was completed.
+    pub fn record(&mut self, op: Op, arrival: Instant) {
+        let now = Instant::now();
+        self.local.record(op, now - arrival);
+        self.completed += 1;
+
+        if now - self.last_publish >= Self::PUBLISH_INTERVAL {
+            self.last_publish = now;
+            self.throughput.fetch_add(self.completed, Ordering::SeqCst);
+            self.completed = 0;
+            self.latencies.lock().unwrap().add(&self.local);
+            self.local.reset();
+        }
+    }
+}
+
+/// The machine-readable results of a benchmark run, printed with `--json`.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub workload: Workload,
+    pub threads: usize,
+    pub duration_secs: f64,
+    /// Requests completed per second, across all servers.
+    pub ops_per_sec: f64,
+    pub put: OpReport,
+    pub get: OpReport,
+}
+
+/// The results of one type of request, see [`Report`].
+#[derive(Debug, Serialize)]
+pub struct OpReport {
+    /// Requests of this type completed per second, across all servers.
+    pub per_sec: f64,
+    pub latency_us: LatencySummary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub max: u64,
+}
+
+impl OpReport {
+    pub fn new(histogram: &Histogram<u64>, duration_secs: f64) -> Self {
+        Self {
+            per_sec: histogram.len() as f64 / duration_secs,
+            latency_us: LatencySummary {
+                count: histogram.len(),
+                mean: histogram.mean(),
+                p50: histogram.value_at_quantile(0.5),
+                p90: histogram.value_at_quantile(0.9),
+                p99: histogram.value_at_quantile(0.99),
+                p999: histogram.value_at_quantile(0.999),
+                max: histogram.max(),
+            },
+        }
+    }
+}
@@ -1,6 +1,7 @@
+use std::cell::RefCell;
 use std::rc::Rc;
-use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use bincode::options;
 use bytes::{BufMut, Bytes, BytesMut};
@@ -12,12 +13,11 @@ use futures::Stream;
 use lattices::map_union::{MapUnionHashMap, MapUnionSingletonMap};
 use lattices::set_union::SetUnionSingletonSet;
 use lattices::{Max, Point, WithBot};
-use rand::{Rng, SeedableRng};
 use serde::Serialize;
 use serde::de::DeserializeSeed;
 use tokio::task;
 use tokio_stream::StreamExt;
-use web_time::{Duration, SystemTime, UNIX_EPOCH};
+use web_time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::Topology;
 use crate::buffer_pool::BufferPool;
@@ -25,12 +25,20 @@ use crate::protocol::{
     KvsRequest, KvsRequestDemux, KvsRequestDeserializer, KvsResponse, MyLastWriteWins, MySetUnion,
     NodeId,
 };
+use crate::report::{Latencies, Recorder};
+use crate::workload::{Generator, Op, Workload};
+
+pub const BUFFER_SIZE: usize = 1024;
+
+/// The maximum number of requests generated at once.
+const GENERATE_BATCH: usize = 1024;
 
 pub fn run_server<RX>(
     server_id: usize,
     topology: Topology<RX>,
-    dist: f64,
+    workload: Workload,
     throughput: Arc<AtomicUsize>,
+    latencies: Arc<Mutex<Latencies>>,
     write_graph: Option<WriteGraphType>,
     write_config: Option<WriteConfig>,
 ) where
@@ -43,8 +51,6 @@ pub fn run_server<RX>(
             .unwrap();
 
         rt.block_on(async {
-            const BUFFER_SIZE: usize = 1024;
-
             let buffer_pool = BufferPool::<BUFFER_SIZE>::create_buffer_pool();
 
             let (process_to_peers_tx, mut process_to_peers_rx) =
@@ -52,7 +58,7 @@ pub fn run_server<RX>(
 
             let (client_to_process_tx, client_to_process_rx) =
                 dfir_rs::util::unsync_channel::<(KvsRequest<BUFFER_SIZE>, NodeId)>(None);
-            let (process_to_client_tx, mut _process_to_client_rx) =
+            let (process_to_client_tx, mut process_to_client_rx) =
                 dfir_rs::util::unsync_channel::<(KvsResponse<BUFFER_SIZE>, NodeId)>(None);
 
             let localset = task::LocalSet::new();
@@ -118,11 +124,14 @@ pub fn run_server<RX>(
                 }
             });
 
-            let mut rng = rand::rngs::SmallRng::from_entropy();
-            let dist = rand_distr::Zipf::new(1_000_000, dist).unwrap();
+            // The benchmark discards the responses to the simulated clients.
+            let discard_responses_task = localset.run_until(async move {
+                while process_to_client_rx.next().await.is_some() {}
+            });
 
-            let mut pre_gen_index = 0;
-            let pre_gen_random_numbers: Vec<u64> = (0..(128*1024)).map(|_| rng.sample(dist) as u64).collect();
+            let generator = RefCell::new(Generator::new(&workload));
+            let recorder = RefCell::new(Recorder::new(throughput, latencies));
+            let value_size = workload.value_size;
 
             let create_unique_id = move |server_id: u128, tick: TickInstant, e: u128| -> u128 {
                 assert!(tick < TickInstant(1_000_000_000));
@@ -137,53 +146,44 @@ pub fn run_server<RX>(
                     .checked_add(e).unwrap()
             };
 
-            let mut throughput_internal = 0usize;
-
             let mut df = dfir_syntax! {
 
-                simulated_put_requests = spin() -> flat_map(|_| {
+                simulated_requests = spin() -> flat_map(|_| {
                     let buffer_pool = buffer_pool.clone();
-                    let pre_gen_random_numbers = &pre_gen_random_numbers;
-                    std::iter::repeat_with(move || {
-                        let value = BufferPool::get_from_buffer_pool(&buffer_pool);
-
-                        // Did the original C++ benchmark do anything with the data..?
-                        // Can uncomment this to modify the buffers then.
-                        //
-                        // let mut borrow = buff.borrow_mut().unwrap();
-
-                        // let mut r = rng.sample(dist_uniform) as u64;
-                        // for i in 0..8 {
-                        //     borrow[i] = (r % 256) as u8;
-                        //     r /= 256;
-                        // }
-
-                        let key = pre_gen_random_numbers[pre_gen_index % pre_gen_random_numbers.len()];
-                        pre_gen_index += 1;
-
-                        (KvsRequest::Put {
-                            key,
-                            value,
-                        }, 99999999)
+                    let generator = &generator;
+                    let now = Instant::now();
+                    std::iter::from_fn(move || {
+                        let (op, key, arrival) = generator.borrow_mut().next(now)?;
+                        let req = match op {
+                            Op::Put => {
+                                let value = BufferPool::get_from_buffer_pool(&buffer_pool);
+                                for (i, byte) in value.borrow_mut()[..value_size].iter_mut().enumerate() {
+                                    *byte = (key >> (8 * (i % 8))) as u8;
+                                }
+                                KvsRequest::Put { key, value }
+                            }
+                            Op::Get => KvsRequest::Get { key },
+                        };
+                        Some((req, 99999999, Some(arrival)))
                     })
+                    .take(GENERATE_BATCH)
                 });
 
-                union_puts_and_gossip_requests = union();
+                union_simulated_and_gossip_requests = union();
 
-                simulated_put_requests -> union_puts_and_gossip_requests;
+                simulated_requests -> union_simulated_and_gossip_requests;
                 source_stream(client_to_process_rx)
                     // -> inspect(|x| println!("{server_id}:{:5}: from peers: {x:?}", context.current_tick()))
-                    -> union_puts_and_gossip_requests;
+                    -> map(|(req, addr)| (req, addr, None))
+                    -> union_simulated_and_gossip_requests;
 
-                client_input = union_puts_and_gossip_requests
+                client_input = union_simulated_and_gossip_requests
                     -> enumerate::<'tick>()
-                    -> map(|(e, (req, addr)): (usize, (KvsRequest<BUFFER_SIZE>, NodeId))| {
+                    -> map(|(e, (req, addr, arrival)): (usize, (KvsRequest<BUFFER_SIZE>, NodeId, Option<Instant>))| {
                         match req {
                             KvsRequest::Put {key, value} => {
-                                throughput_internal += 1;
-                                const GATE: usize = 2 * 1024;
-                                if throughput_internal.is_multiple_of(GATE) {
-                                    throughput.fetch_add(GATE, Ordering::SeqCst);
+                                if let Some(arrival) = arrival {
+                                    recorder.borrow_mut().record(Op::Put, arrival);
                                 }
                                 let marker = create_unique_id(server_id as u128, context.current_tick(), e as u128);
 
@@ -203,6 +203,7 @@ pub fn run_server<RX>(
                             KvsRequest::Get {key} => KvsRequestDemux::Get {
                                 key,
                                 addr,
+                                arrival,
                             },
                             KvsRequest::Delete {key} => {
                                 let marker = create_unique_id(server_id as u128, context.current_tick(), e as u128);
@@ -261,8 +262,8 @@ pub fn run_server<RX>(
 
                 // Feed gets into the join to make them do the actual matching.
                 demux_get = enumerate() // Ensure that two requests from the same client on the same tick do not get merged into a single request.
-                    -> map(|(id, (key, addr))| {
-                        (key, SetUnionSingletonSet::new_from((addr, id)))
+                    -> map(|(id, (key, addr, arrival))| {
+                        (key, SetUnionSingletonSet::new_from((addr, id, arrival)))
                     })
                     // -> inspect(|x| println!("{gossip_addr}:{:5}: gets-into-lookup: {x:?}", context.current_tick()))
                     -> [1]lookup;
@@ -274,13 +275,18 @@ pub fn run_server<RX>(
                         (k, (v.into_reveal()))
                     })
                     -> map(|(key, (reg, gets))| {
-                        gets.0.into_iter().map(move |(dest, _seq_num)| {
-                            (KvsResponse::GetResponse { key, reg: reg.clone() }, dest)
+                        gets.0.into_iter().map(move |(dest, _seq_num, arrival)| {
+                            (KvsResponse::GetResponse { key, reg: reg.clone() }, dest, arrival)
                         })
                     })
                     -> flatten()
                     // -> inspect(|x| println!("{gossip_addr}:{:5}: Response to client: {x:?}", context.current_tick()))
-                    -> for_each(|x| process_to_client_tx.try_send(x).unwrap());
+                    -> for_each(|(response, dest, arrival)| {
+                        if let Some(arrival) = arrival {
+                            recorder.borrow_mut().record(Op::Get, arrival);
+                        }
+                        process_to_client_tx.try_send((response, dest)).unwrap();
+                    });
 
             };
 
@@ -293,7 +299,13 @@ pub fn run_server<RX>(
 
             let df_task = df.run();
 
-            futures::join!(inbound_networking_task, outbound_networking_task, df_task, f3);
+            futures::join!(
+                inbound_networking_task,
+                outbound_networking_task,
+                discard_responses_task,
+                df_task,
+                f3
+            );
         });
     });
 }
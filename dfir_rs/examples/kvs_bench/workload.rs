@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+
+use clap::{Args, ValueEnum};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::Exp1;
+use serde::Serialize;
+use web_time::{Duration, Instant};
+
+/// The requests each server generates for itself.
+#[derive(Clone, Debug, Args, Serialize)]
+pub struct Workload {
+    /// Fraction of requests which are gets, the rest are puts.
+    #[clap(long, default_value_t = 0.0)]
+    pub read_ratio: f64,
+
+    /// Number of distinct keys.
+    #[clap(long, default_value_t = 1_000_000)]
+    pub keys: u64,
+
+    /// `s` value for the zipf sampling distribution for keys.
+    #[clap(long, default_value_t = 4.0)]
+    pub dist: f64,
+
+    /// Number of bytes written into the value of each put, at most the size of a value buffer.
+    #[clap(long, default_value_t = 0)]
+    pub value_size: usize,
+
+    /// How requests arrive at each server.
+    #[clap(long, value_enum, default_value_t = Arrival::Closed)]
+    pub arrival: Arrival,
+
+    /// Requests per second arriving at each server, for the `uniform` and `poisson` arrival
+    /// processes.
+    #[clap(long, default_value_t = 100_000.0)]
+    pub rate: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Arrival {
+    /// As many requests as the server can process (closed loop).
+    Closed,
+    /// Requests arrive at fixed intervals of `1 / rate` (open loop).
+    Uniform,
+    /// Requests arrive as a Poisson process with the given `rate` (open loop).
+    Poisson,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Put,
+    Get,
+}
+
+/// Generates the requests of a [`Workload`].
+pub struct Generator {
+    read_ratio: f64,
+    arrival: Arrival,
+    rate: f64,
+    rng: SmallRng,
+    keys: Vec<u64>,
+    next_key: usize,
+    next_arrival: Instant,
+    /// The keys which have been put, since gets of other keys would never be answered.
+    put_keys: HashSet<u64>,
+}
+
+impl Generator {
+    pub fn new(workload: &Workload) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&workload.read_ratio),
+            "`--read-ratio` must be between 0 and 1"
+        );
+        assert!(workload.rate > 0.0, "`--rate` must be positive");
+
+        let mut rng = SmallRng::from_entropy();
+        // Sampling the zipf distribution is slow, so keys are sampled ahead of time.
+        let dist = rand_distr::Zipf::new(workload.keys, workload.dist).unwrap();
+        let keys = (0..(128 * 1024)).map(|_| rng.sample(dist) as u64).collect();
+
+        Self {
+            read_ratio: workload.read_ratio,
+            arrival: workload.arrival,
+            rate: workload.rate,
+            rng,
+            keys,
+            next_key: 0,
+            next_arrival: Instant::now(),
+            put_keys: HashSet::new(),
+        }
+    }
+
+    /// The next request which arrived by `now`, if any, along with its key and arrival time. A get
+    /// of a key which has not been put yet is turned into a put.
+    pub fn next(&mut self, now: Instant) -> Option<(Op, u64, Instant)> {
+        let arrival = match self.arrival {
+            Arrival::Closed => now,
+            Arrival::Uniform | Arrival::Poisson => {
+                if self.next_arrival > now {
+                    return None;
+                }
+                let arrival = self.next_arrival;
+                let interval = match self.arrival {
+                    Arrival::Poisson => self.rng.sample::<f64, _>(Exp1) / self.rate,
+                    _ => 1.0 / self.rate,
+                };
+                self.next_arrival += Duration::from_secs_f64(interval);
+                arrival
+            }
+        };
+
+        let key = self.keys[self.next_key % self.keys.len()];
+        self.next_key += 1;
+        let op = if self.rng.gen_bool(self.read_ratio) && self.put_keys.contains(&key) {
+            Op::Get
+        } else {
+            self.put_keys.insert(key);
+            Op::Put
+        };
+        Some((op, key, arrival))
+    }
+}
@@ -73,31 +73,55 @@ enum Role {
 
 #[test]
 fn test() {
-    use example_test::run_current_example;
-
-    let mut server_1 = run_current_example!("--role server --address 127.0.0.1:2071");
-    server_1
-        .read_string("Server is live! Listening on 127.0.0.1:2071 and talking to peer server None");
-
-    let mut client_1 = run_current_example!("--role client --address 127.0.0.1:2071");
-    client_1.read_regex(
-        r"Client is live! Listening on 127\.0\.0\.1:\d+ and talking to server on 127\.0\.0\.1:2071",
-    );
-
-    client_1.write_line("PUT a,7");
-
-    let mut server_2 = run_current_example!(
-        "--role server --address 127.0.0.1:2073 --peer-address 127.0.0.1:2071"
-    );
-    server_2.read_string("Server is live! Listening on 127.0.0.1:2073 and talking to peer server Some(127.0.0.1:2071)");
-    server_2
-        .read_string(r#"Message received PeerGossip { key: "a", value: "7" } from 127.0.0.1:2071"#);
-
-    let mut client_2 = run_current_example!("--role client --address 127.0.0.1:2073");
-    client_2.read_regex(
-        r"Client is live! Listening on 127\.0\.0\.1:\d+ and talking to server on 127\.0\.0\.1:2073",
-    );
-
-    client_2.write_line("GET a");
-    client_2.read_string(r#"Got a Response: ServerResponse { key: "a", value: "7" }"#);
+    use example_test::{Scenario, run_current_example};
+
+    Scenario::new()
+        .spawn("server_1", |_| {
+            run_current_example!("--role server --address 127.0.0.1:0")
+        })
+        .capture(
+            "server_1",
+            r"Server is live! Listening on (\S+) and talking to peer server None",
+            "server_1",
+        )
+        .spawn("client_1", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server_1"]])
+        })
+        .wait_for_captured("client_1", |captures| {
+            format!("talking to server on {}", &captures["server_1"])
+        })
+        .write_line("client_1", "PUT a,7")
+        .spawn("server_2", |captures| {
+            run_current_example!([
+                "--role",
+                "server",
+                "--address",
+                "127.0.0.1:0",
+                "--peer-address",
+                &captures["server_1"]
+            ])
+        })
+        .capture(
+            "server_2",
+            r"Server is live! Listening on (\S+) and talking to peer server Some",
+            "server_2",
+        )
+        .wait_for_captured("server_2", |captures| {
+            format!(
+                r#"Message received PeerGossip {{ key: "a", value: "7" }} from {}"#,
+                &captures["server_1"]
+            )
+        })
+        .spawn("client_2", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server_2"]])
+        })
+        .wait_for_captured("client_2", |captures| {
+            format!("talking to server on {}", &captures["server_2"])
+        })
+        .write_line("client_2", "GET a")
+        .wait_for(
+            "client_2",
+            r#"Got a Response: ServerResponse { key: "a", value: "7" }"#,
+        )
+        .run();
 }
@@ -72,28 +72,38 @@ enum Role {
 
 #[test]
 fn test() {
-    use example_test::run_current_example;
-
-    let mut server = run_current_example!("--role server --address 127.0.0.1:11052");
-    server.read_string("Server is live! Listening on 127.0.0.1:11052");
-
-    let mut client1 = run_current_example!("--role client --address 127.0.0.1:11052");
-    let mut client2 = run_current_example!("--role client --address 127.0.0.1:11052");
-
-    client1.read_regex(
-        r"Client is live! Listening on 127\.0\.0\.1:\d+ and talking to server on 127\.0\.0\.1:11052",
-    );
-    client2.read_regex(
-        r"Client is live! Listening on 127\.0\.0\.1:\d+ and talking to server on 127\.0\.0\.1:11052",
-    );
-
-    client1.write_line("Hello1");
-    client1.read_string(
-        r#"UTC: Got EchoMsg { payload: "Hello1", lamport_clock: Max(1) } from 127.0.0.1:11052"#,
-    );
-
-    client2.write_line("Hello2");
-    client2.read_string(
-        r#"UTC: Got EchoMsg { payload: "Hello2", lamport_clock: Max(2) } from 127.0.0.1:11052"#,
-    );
+    use example_test::{Scenario, run_current_example};
+
+    Scenario::new()
+        .spawn("server", |_| {
+            run_current_example!("--role server --address 127.0.0.1:0")
+        })
+        .capture("server", r"Server is live! Listening on (\S+)", "server")
+        .spawn("client1", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server"]])
+        })
+        .wait_for_captured("client1", |captures| {
+            format!("talking to server on {}", &captures["server"])
+        })
+        .spawn("client2", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server"]])
+        })
+        .wait_for_captured("client2", |captures| {
+            format!("talking to server on {}", &captures["server"])
+        })
+        .write_line("client1", "Hello1")
+        .wait_for_captured("client1", |captures| {
+            format!(
+                r#"UTC: Got EchoMsg {{ payload: "Hello1", lamport_clock: Max(1) }} from {}"#,
+                &captures["server"]
+            )
+        })
+        .write_line("client2", "Hello2")
+        .wait_for_captured("client2", |captures| {
+            format!(
+                r#"UTC: Got EchoMsg {{ payload: "Hello2", lamport_clock: Max(2) }} from {}"#,
+                &captures["server"]
+            )
+        })
+        .run();
 }
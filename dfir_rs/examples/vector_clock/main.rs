@@ -68,24 +68,34 @@ enum Role {
 
 #[test]
 fn test_vector_clock() {
-    use example_test::run_current_example;
+    use example_test::{Scenario, run_current_example};
 
-    let mut server = run_current_example!("--role server --address 127.0.0.1:12053");
-    server.read_string("Server is live! Listening on 127.0.0.1:12053");
-
-    let mut client1 = run_current_example!("--role client --address 127.0.0.1:12053");
-    let mut client2 = run_current_example!("--role client --address 127.0.0.1:12053");
-
-    client1.read_string("Client is live! Listening on 127.0.0.1:");
-    client2.read_string("Client is live! Listening on 127.0.0.1:");
-
-    client1.write_line("Hello1");
-    client1.read_regex(
-        r#"payload: "Hello1", vc: .*"127.0.0.1:12053": Max\(1\).*from 127.0.0.1:12053"#,
-    );
-
-    client2.write_line("Hello2");
-    client2.read_regex(
-        r#"payload: "Hello2", vc: .*"127.0.0.1:12053": Max\(2\).*from 127.0.0.1:12053"#,
-    );
+    Scenario::new()
+        .spawn("server", |_| {
+            run_current_example!("--role server --address 127.0.0.1:0")
+        })
+        .capture("server", r"Server is live! Listening on (\S+)", "server")
+        .spawn("client1", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server"]])
+        })
+        .wait_for_captured("client1", |captures| {
+            format!("talking to server on {}", &captures["server"])
+        })
+        .spawn("client2", |captures| {
+            run_current_example!(["--role", "client", "--address", &captures["server"]])
+        })
+        .wait_for_captured("client2", |captures| {
+            format!("talking to server on {}", &captures["server"])
+        })
+        .write_line("client1", "Hello1")
+        .wait_for_regex_captured("client1", |captures| {
+            let server = regex::escape(&captures["server"]);
+            format!(r#"payload: "Hello1", vc: .*"{server}": Max\(1\).*from {server}"#)
+        })
+        .write_line("client2", "Hello2")
+        .wait_for_regex_captured("client2", |captures| {
+            let server = regex::escape(&captures["server"]);
+            format!(r#"payload: "Hello2", vc: .*"{server}": Max\(2\).*from {server}"#)
+        })
+        .run();
 }
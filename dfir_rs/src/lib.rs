@@ -30,6 +30,8 @@ pub use ::{
 pub use ::{tokio, tokio_stream, tokio_util};
 #[doc(hidden)]
 pub use dfir_pipes::itertools;
+#[cfg(feature = "python")]
+pub use pyo3;
 #[doc(hidden)]
 pub use variadics::{var_args, var_expr, var_type};
 
@@ -38,8 +40,8 @@ mod declarative_macro;
 #[cfg_attr(docsrs, doc(cfg(feature = "dfir_macro")))]
 #[cfg(feature = "dfir_macro")]
 pub use dfir_macro::{
-    DemuxEnum, dfir_main as main, dfir_parser, dfir_syntax, dfir_syntax_noemit, dfir_test as test,
-    monotonic_fn, morphism,
+    DemuxEnum, dfir_main as main, dfir_parser, dfir_syntax, dfir_syntax_fused, dfir_syntax_noemit,
+    dfir_test as test, monotonic_fn, morphism,
 };
 pub use futures::never::Never;
 
@@ -0,0 +1,108 @@
+//! Checkpointing the state of stateful operators, so that a graph can recover after its process
+//! restarts without replaying all of its history.
+//!
+//! Operators opt in with a `#[checkpoint]` attribute on their (named) statement in the surface
+//! syntax, which must contain exactly one participating operator (currently `persist`, `fold`,
+//! `fold_keyed`, or `join`). State is only serialized when
+//! [`Dfir::checkpoint`](super::context::Dfir::checkpoint) is called, between ticks, so it is a
+//! consistent snapshot as of the end of the last tick and costs nothing otherwise. After
+//! [`Dfir::restore`](super::context::Dfir::restore), each operator loads its restored state at the
+//! start of the next tick, before processing any input.
+//!
+//! State is serialized with [`bincode`], so the types held by participating operators must
+//! implement [`serde::Serialize`] and [`serde::de::DeserializeOwned`]. State is matched by the
+//! name of the statement it belongs to, so a checkpoint can only be restored into a graph with the
+//! same checkpointed statements.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// The version of the checkpoint format, written at the start of each checkpoint.
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// The registry which checkpointed operators save their state to, and load restored state from,
+/// owned by the [`Context`](super::context::Context).
+#[derive(Default)]
+pub struct CheckpointRegistry {
+    /// Set while [`Dfir::checkpoint`](super::context::Dfir::checkpoint) is capturing state.
+    capturing: Cell<bool>,
+    /// The serialized state of each operator captured so far, by checkpoint key.
+    saved: RefCell<BTreeMap<String, Vec<u8>>>,
+    /// Restored state which has not yet been loaded by its operator, by checkpoint key.
+    restored: RefCell<BTreeMap<String, Vec<u8>>>,
+}
+
+impl CheckpointRegistry {
+    /// Whether the tick closure is being called to capture a checkpoint, instead of to run a tick.
+    #[doc(hidden)] // Called by generated operator code.
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.get()
+    }
+
+    /// Saves the state of an operator into the checkpoint being captured.
+    #[doc(hidden)] // Called by generated operator code.
+    pub fn save<T: Serialize + ?Sized>(&self, key: &str, state: &T) {
+        let bytes = bincode::serialize(state).unwrap_or_else(|err| {
+            panic!("Failed to serialize the state of checkpointed operator `{key}`: {err}")
+        });
+        self.saved.borrow_mut().insert(key.to_owned(), bytes);
+    }
+
+    /// Takes the restored state of an operator, if any is waiting to be loaded.
+    #[doc(hidden)] // Called by generated operator code.
+    pub fn take_restored<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        if self.restored.borrow().is_empty() {
+            return None;
+        }
+        let bytes = self.restored.borrow_mut().remove(key)?;
+        Some(bincode::deserialize(&bytes).unwrap_or_else(|err| {
+            panic!(
+                "Failed to deserialize the restored state of checkpointed operator `{key}`: {err}"
+            )
+        }))
+    }
+
+    /// Starts capturing a checkpoint, after which the tick closure only saves operator state.
+    pub(super) fn start_capture(&self) {
+        self.saved.borrow_mut().clear();
+        self.capturing.set(true);
+    }
+
+    /// Finishes capturing a checkpoint, writing the saved state of all operators to `writer`.
+    ///
+    /// State which was restored but not yet loaded by its operator (as no tick has run since) is
+    /// written as restored, so that such a checkpoint is the same as the one restored.
+    pub(super) fn finish_capture(&self, writer: impl Write) -> std::io::Result<()> {
+        self.capturing.set(false);
+        let mut saved = self.saved.take();
+        saved.extend(self.restored.borrow().clone());
+        bincode::serialize_into(writer, &(CHECKPOINT_VERSION, &saved))
+            .map_err(|err| into_io_error(*err))
+    }
+
+    /// Reads the state of all operators from `reader`, to be loaded by each operator at the start
+    /// of the next tick.
+    pub(super) fn read(&self, reader: impl Read) -> std::io::Result<()> {
+        let (version, state): (u32, BTreeMap<String, Vec<u8>>) =
+            bincode::deserialize_from(reader).map_err(|err| into_io_error(*err))?;
+        if CHECKPOINT_VERSION != version {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unsupported checkpoint version {version}, expected {CHECKPOINT_VERSION}."),
+            ));
+        }
+        *self.restored.borrow_mut() = state;
+        Ok(())
+    }
+}
+
+fn into_io_error(err: bincode::ErrorKind) -> std::io::Error {
+    match err {
+        bincode::ErrorKind::Io(err) => err,
+        err => std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+    }
+}
@@ -8,13 +8,18 @@ use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
-use std::task::Wake;
+use std::task::{Poll, Wake};
+use std::time::Duration;
 
 #[cfg(feature = "meta")]
 use dfir_lang::diagnostic::{Diagnostic, Diagnostics, SerdeSpan};
 #[cfg(feature = "meta")]
 use dfir_lang::graph::DfirGraph;
+use web_time::Instant;
 
+use super::checkpoint::CheckpointRegistry;
+use super::execution_trace::{ExecutionTrace, SubgraphSpan};
+use super::introspection::{StateRegistry, StateSnapshot};
 use super::metrics::{DfirMetrics, DfirMetricsIntervals};
 use crate::scheduled::ticks::TickInstant;
 
@@ -31,6 +36,14 @@ pub struct WakeState {
     can_start_tick: std::sync::atomic::AtomicBool,
     /// Wakes the [`Dfir::run`](Dfir::run) task from its idle `poll_fn` sleep.
     task_waker: futures::task::AtomicWaker,
+    /// Set while a tick is running (including while it is suspended), see [`DfirProgress`].
+    in_tick: std::sync::atomic::AtomicBool,
+    /// Number of ticks which have completed, see [`DfirProgress`].
+    ticks_completed: std::sync::atomic::AtomicU64,
+    /// Set when a source stops early because the current tick passed its deadline.
+    deadline_exceeded: std::sync::atomic::AtomicBool,
+    /// Number of ticks which passed their deadline, see [`DfirProgress`].
+    ticks_over_deadline: std::sync::atomic::AtomicU64,
 }
 
 impl Default for WakeState {
@@ -38,10 +51,43 @@ impl Default for WakeState {
         Self {
             can_start_tick: std::sync::atomic::AtomicBool::new(false),
             task_waker: futures::task::AtomicWaker::new(),
+            in_tick: std::sync::atomic::AtomicBool::new(false),
+            ticks_completed: std::sync::atomic::AtomicU64::new(0),
+            deadline_exceeded: std::sync::atomic::AtomicBool::new(false),
+            ticks_over_deadline: std::sync::atomic::AtomicU64::new(0),
         }
     }
 }
 
+/// A thread-safe handle for observing whether a [`Dfir`] instance is making progress, created by
+/// [`Dfir::progress`]. Used by watchdogs to detect stuck flows.
+#[derive(Clone)]
+pub struct DfirProgress(Arc<WakeState>);
+
+impl DfirProgress {
+    /// Number of ticks which have completed so far.
+    pub fn ticks_completed(&self) -> u64 {
+        self.0.ticks_completed.load(Ordering::Relaxed)
+    }
+
+    /// Whether a tick is currently running, or is suspended waiting on an async operation (for
+    /// example a sink which is not ready).
+    pub fn in_tick(&self) -> bool {
+        self.0.in_tick.load(Ordering::Relaxed)
+    }
+
+    /// Whether external input has arrived which has not yet been processed by a tick.
+    pub fn input_pending(&self) -> bool {
+        self.0.can_start_tick.load(Ordering::Relaxed)
+    }
+
+    /// Number of ticks which ran past the deadline set with [`Dfir::set_tick_deadline`], and so
+    /// carried some of their input into the following tick.
+    pub fn ticks_over_deadline(&self) -> u64 {
+        self.0.ticks_over_deadline.load(Ordering::Relaxed)
+    }
+}
+
 impl Wake for WakeState {
     fn wake(self: Arc<Self>) {
         self.wake_by_ref();
@@ -68,6 +114,14 @@ pub struct Context {
     wake_state: Arc<WakeState>,
     /// Live-updating DFIR runtime metrics via interior mutability.
     metrics: Rc<DfirMetrics>,
+    /// State reported by stateful operators, see [`Dfir::state_snapshot`].
+    state_registry: StateRegistry,
+    /// Runs of each subgraph, see [`Dfir::execution_trace`].
+    execution_trace: ExecutionTrace,
+    /// State saved by checkpointed operators, see [`Dfir::checkpoint`].
+    checkpoints: CheckpointRegistry,
+    /// When the current tick should stop reading from sources, see [`Dfir::set_tick_deadline`].
+    tick_deadline: Option<Instant>,
     /// Tasks buffered via [`Self::request_task`], spawned by [`Dfir::spawn_tasks`]
     /// once the runtime is running inside a tokio `LocalSet`.
     #[cfg(feature = "tokio")]
@@ -81,6 +135,10 @@ impl Context {
             current_tick: TickInstant::default(),
             wake_state,
             metrics,
+            state_registry: StateRegistry::default(),
+            execution_trace: ExecutionTrace::default(),
+            checkpoints: CheckpointRegistry::default(),
+            tick_deadline: None,
             #[cfg(feature = "tokio")]
             tasks_to_spawn: Vec::new(),
         }
@@ -115,6 +173,21 @@ impl Context {
         &self.metrics
     }
 
+    /// Returns the registry which stateful operators report their state to.
+    pub fn state_registry(&self) -> &StateRegistry {
+        &self.state_registry
+    }
+
+    /// Returns the recorder which subgraphs report their runs to.
+    pub fn execution_trace(&self) -> &ExecutionTrace {
+        &self.execution_trace
+    }
+
+    /// Returns the registry which checkpointed operators save their state to.
+    pub fn checkpoints(&self) -> &CheckpointRegistry {
+        &self.checkpoints
+    }
+
     /// Signals that external data has arrived and a new tick should be started.
     pub fn schedule_subgraph(&self, is_external: bool) {
         if is_external {
@@ -127,6 +200,15 @@ impl Context {
         std::task::Waker::from(self.wake_state.clone())
     }
 
+    /// Returns the deadline of the current tick, used by sources to stop reading once it passes.
+    #[doc(hidden)]
+    pub fn tick_deadline(&self) -> TickDeadline {
+        TickDeadline {
+            at: self.tick_deadline,
+            wake_state: self.wake_state.clone(),
+        }
+    }
+
     /// Increments the tick counter.
     /// Called by the generated tick closure at the end of each tick.
     #[doc(hidden)]
@@ -135,6 +217,67 @@ impl Context {
     }
 }
 
+/// The wall-clock deadline of a tick, see [`Dfir::set_tick_deadline`].
+#[doc(hidden)]
+pub struct TickDeadline {
+    at: Option<Instant>,
+    wake_state: Arc<WakeState>,
+}
+
+impl TickDeadline {
+    /// Wraps `stream` so that it stops yielding items once the deadline has passed. At least one
+    /// item is always yielded, so that each tick makes progress.
+    pub fn limit<S>(self, stream: S) -> DeadlineStream<S> {
+        DeadlineStream {
+            stream,
+            deadline: self,
+            yielded: false,
+        }
+    }
+
+    /// Whether the deadline has passed. If so, schedules another tick to process the remaining
+    /// input.
+    fn exceeded(&self) -> bool {
+        if self.at.is_none_or(|at| Instant::now() < at) {
+            return false;
+        }
+        self.wake_state
+            .deadline_exceeded
+            .store(true, Ordering::Relaxed);
+        self.wake_state.wake_by_ref();
+        true
+    }
+}
+
+/// A stream which stops yielding items once its [`TickDeadline`] has passed, leaving the
+/// remaining items in the underlying stream for the next tick.
+#[doc(hidden)]
+pub struct DeadlineStream<S> {
+    stream: S,
+    deadline: TickDeadline,
+    yielded: bool,
+}
+
+impl<S> futures::Stream for DeadlineStream<S>
+where
+    S: futures::Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.yielded && self.deadline.exceeded() {
+            // Another tick has been scheduled, so there is no need to register the waker.
+            return Poll::Pending;
+        }
+        let next = Pin::new(&mut self.stream).poll_next(cx);
+        self.yielded |= matches!(next, Poll::Ready(Some(_)));
+        next
+    }
+}
+
 /// An executable DFIR dataflow, as created by
 /// [`dfir_syntax!`](crate::dfir_syntax). Provides the [`Self::run`],
 /// [`Self::run_available`], and [`Self::run_tick`] family of methods to
@@ -165,6 +308,8 @@ pub struct Dfir<Tick> {
     wake_state: Arc<WakeState>,
     /// The inline context, owned by `Dfir` and passed to the tick closure by reference.
     context: Context,
+    /// See [`Self::set_tick_deadline`].
+    tick_deadline: Option<Duration>,
     /// See [`Self::meta_graph()`].
     #[cfg(feature = "meta")]
     meta_graph: Option<DfirGraph>,
@@ -249,6 +394,7 @@ impl<Tick: TickClosure> Dfir<Tick> {
             tick_closure,
             wake_state: context.wake_state.clone(),
             context,
+            tick_deadline: None,
             #[cfg(feature = "meta")]
             meta_graph: meta_graph_json.map(|json| {
                 let mut meta_graph: DfirGraph =
@@ -293,6 +439,93 @@ impl<Tick: TickClosure> Dfir<Tick> {
         self.context.current_tick()
     }
 
+    /// Enables state introspection: at the end of each tick, stateful operators (such as `join`,
+    /// `fold_keyed`, and `persist`) record their size and a sample of up to `sample_size` items,
+    /// which can be read with [`Self::state_snapshot`].
+    pub fn enable_state_introspection(&mut self, sample_size: usize) {
+        self.context
+            .state_registry
+            .set_sample_size(Some(sample_size));
+    }
+
+    /// Disables state introspection, and clears any recorded state.
+    pub fn disable_state_introspection(&mut self) {
+        self.context.state_registry.set_sample_size(None);
+    }
+
+    /// Returns the size and sampled contents of the state of each stateful operator, as of the
+    /// end of the last tick. Empty unless
+    /// [`Self::enable_state_introspection`] has been called.
+    pub fn state_snapshot(&self) -> StateSnapshot {
+        self.context.state_registry.snapshot()
+    }
+
+    /// Enables execution tracing: each run of a subgraph records its start time, duration, and
+    /// number of items processed, which can be read with [`Self::execution_trace`] or exported
+    /// with [`Self::write_chrome_trace`]. Clears any previously recorded runs.
+    pub fn enable_execution_trace(&mut self) {
+        self.context.execution_trace.set_enabled(true);
+    }
+
+    /// Disables execution tracing, and clears any recorded runs.
+    pub fn disable_execution_trace(&mut self) {
+        self.context.execution_trace.set_enabled(false);
+    }
+
+    /// Returns each recorded run of a subgraph, in the order they finished. Empty unless
+    /// [`Self::enable_execution_trace`] has been called.
+    pub fn execution_trace(&self) -> Vec<SubgraphSpan> {
+        self.context.execution_trace.spans()
+    }
+
+    /// Writes the recorded runs of each subgraph to `writer` as a Chrome trace event JSON file,
+    /// which can be opened in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+    pub fn write_chrome_trace(&self, writer: impl std::io::Write) -> std::io::Result<()> {
+        self.context.execution_trace.write_chrome_trace(writer)
+    }
+
+    /// Writes the state of the operators annotated with `#[checkpoint]` to `writer`, as of the end
+    /// of the last tick, see [`crate::scheduled::checkpoint`]. Must not be called during a tick.
+    pub fn checkpoint(&mut self, writer: impl std::io::Write) -> std::io::Result<()> {
+        self.context.checkpoints.start_capture();
+        {
+            // While capturing, the tick closure only saves operator state, without awaiting.
+            let mut fut = std::pin::pin!(self.tick_closure.call_tick(&mut self.context));
+            let mut ctx = std::task::Context::from_waker(std::task::Waker::noop());
+            assert!(
+                fut.as_mut().poll(&mut ctx).is_ready(),
+                "Dfir::checkpoint: capturing operator state yielded asynchronously."
+            );
+        }
+        self.context.checkpoints.finish_capture(writer)
+    }
+
+    /// Reads the state of the operators annotated with `#[checkpoint]` from `reader`, as written
+    /// by [`Self::checkpoint`]. Each operator replaces its state with the restored state at the
+    /// start of the next tick, so this should be called before the first tick is run.
+    pub fn restore(&mut self, reader: impl std::io::Read) -> std::io::Result<()> {
+        self.context.checkpoints.read(reader)
+    }
+
+    /// Bounds the wall-clock time of each tick to `deadline`, or removes the bound if `None`.
+    ///
+    /// Once a tick passes its deadline, its sources (such as `source_stream`) stop reading input,
+    /// and the input they have not yet read is processed by the following tick instead. This
+    /// keeps one large batch of input from delaying timers and network IO in latency-sensitive
+    /// services, at the cost of splitting the batch across ticks. Ticks which pass their deadline
+    /// are counted by [`DfirProgress::ticks_over_deadline`].
+    ///
+    /// The deadline is only checked when sources read input, so work which does not depend on
+    /// new input, such as the rest of the operators in a tick, can still run past it.
+    pub fn set_tick_deadline(&mut self, deadline: Option<Duration>) {
+        self.tick_deadline = deadline;
+    }
+
+    /// Returns a thread-safe handle for observing whether this DFIR instance is making progress.
+    pub fn progress(&self) -> DfirProgress {
+        DfirProgress(Arc::clone(&self.wake_state))
+    }
+
     /// Returns a [`DfirMetricsIntervals`] handle where each call to
     /// [`DfirMetricsIntervals::take_interval`] ends the current interval and returns its metrics.
     ///
@@ -331,7 +564,22 @@ impl<Tick: TickClosure> Dfir<Tick> {
             .wake_state
             .can_start_tick
             .swap(false, Ordering::Relaxed);
+        self.wake_state.in_tick.store(true, Ordering::Relaxed);
+        self.context.tick_deadline = self.tick_deadline.map(|deadline| Instant::now() + deadline);
         let tick_had_work = self.tick_closure.call_tick(&mut self.context).await;
+        self.wake_state.in_tick.store(false, Ordering::Relaxed);
+        self.wake_state
+            .ticks_completed
+            .fetch_add(1, Ordering::Relaxed);
+        if self
+            .wake_state
+            .deadline_exceeded
+            .swap(false, Ordering::Relaxed)
+        {
+            self.wake_state
+                .ticks_over_deadline
+                .fetch_add(1, Ordering::Relaxed);
+        }
         had_external || tick_had_work || self.wake_state.can_start_tick.load(Ordering::Relaxed)
     }
 
@@ -341,8 +589,8 @@ impl<Tick: TickClosure> Dfir<Tick> {
         let mut fut = std::pin::pin!(self.run_tick());
         let mut ctx = std::task::Context::from_waker(std::task::Waker::noop());
         match fut.as_mut().poll(&mut ctx) {
-            std::task::Poll::Ready(result) => result,
-            std::task::Poll::Pending => {
+            Poll::Ready(result) => result,
+            Poll::Pending => {
                 panic!("Dfir::run_tick_sync: tick yielded asynchronously.")
             }
         }
@@ -397,12 +645,39 @@ impl<Tick: TickClosure> Dfir<Tick> {
                 // the check and the register, the waker is already in place.
                 self.wake_state.task_waker.register(cx.waker());
                 if self.wake_state.can_start_tick.load(Ordering::Relaxed) {
-                    std::task::Poll::Ready(())
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+        }
+    }
+
+    /// Run until `stop` completes, processing ticks when work is available and yielding when
+    /// idle. `stop` is only polled between ticks, and once it completes, any work which is still
+    /// available is processed before returning.
+    #[cfg(feature = "tokio")]
+    pub async fn run_until(&mut self, stop: impl Future) {
+        let mut stop = std::pin::pin!(stop);
+        loop {
+            self.run_available().await;
+            let stopped = std::future::poll_fn(|cx| {
+                if stop.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(true);
+                }
+                self.wake_state.task_waker.register(cx.waker());
+                if self.wake_state.can_start_tick.load(Ordering::Relaxed) {
+                    Poll::Ready(false)
                 } else {
-                    std::task::Poll::Pending
+                    Poll::Pending
                 }
             })
             .await;
+            if stopped {
+                self.run_available().await;
+                return;
+            }
         }
     }
 }
@@ -421,6 +696,7 @@ impl<Tick: 'static + for<'a> AsyncFnMut(&'a mut Context) -> bool> Dfir<Tick> {
             tick_closure: TickClosureErased(Box::new(self.tick_closure)),
             wake_state: self.wake_state,
             context: self.context,
+            tick_deadline: self.tick_deadline,
             #[cfg(feature = "meta")]
             meta_graph: self.meta_graph,
             #[cfg(feature = "meta")]
@@ -0,0 +1,139 @@
+//! Tracing of the execution of each subgraph, for profiling running graphs.
+//!
+//! When enabled with [`Dfir::enable_execution_trace`](super::context::Dfir::enable_execution_trace),
+//! each run of a subgraph records a [`SubgraphSpan`] with its start time, duration, and the number of
+//! items it processed. The recorded spans can be read with
+//! [`Dfir::execution_trace`](super::context::Dfir::execution_trace), or exported as a Chrome trace
+//! event file (viewable in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev)) with
+//! [`Dfir::write_chrome_trace`](super::context::Dfir::write_chrome_trace). Tracing is disabled by
+//! default, in which case subgraphs only check a flag each time they run.
+
+use std::cell::{Cell, RefCell};
+
+use dfir_lang::graph_ids::GraphSubgraphId;
+use serde_json::json;
+use slotmap::{Key, KeyData};
+use web_time::{Duration, Instant};
+
+use super::ticks::TickInstant;
+
+/// A single run of a subgraph.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SubgraphSpan {
+    /// The subgraph which ran.
+    pub subgraph: GraphSubgraphId,
+    /// The tick during which the subgraph ran.
+    pub tick: TickInstant,
+    /// When the subgraph started running, relative to when tracing was enabled.
+    pub start: Duration,
+    /// How long the subgraph took to run, including any time spent waiting on async events.
+    pub duration: Duration,
+    /// The number of items the subgraph read from its input handoffs.
+    pub items: usize,
+}
+
+/// The recorder which subgraphs report their runs to, owned by the
+/// [`Context`](super::context::Context).
+#[derive(Default)]
+pub struct ExecutionTrace {
+    /// When tracing was enabled, or `None` if tracing is disabled.
+    origin: Cell<Option<Instant>>,
+    spans: RefCell<Vec<SubgraphSpan>>,
+}
+
+impl ExecutionTrace {
+    /// If tracing is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.origin.get().is_some()
+    }
+
+    /// Enables or disables tracing. Enabling or disabling clears the recorded spans.
+    pub(super) fn set_enabled(&self, enabled: bool) {
+        self.origin.set(enabled.then(Instant::now));
+        self.spans.borrow_mut().clear();
+    }
+
+    /// Returns the current time if tracing is enabled, to be passed to [`Self::record`] once the
+    /// subgraph has run.
+    #[doc(hidden)] // Called by generated subgraph code.
+    pub fn start(&self) -> Option<Instant> {
+        self.origin.get().map(|_| Instant::now())
+    }
+
+    /// Records a run of a subgraph which started at `start`, as returned by [`Self::start`].
+    #[doc(hidden)] // Called by generated subgraph code.
+    pub fn record(&self, subgraph_ffi: u64, tick: TickInstant, start: Instant, items: usize) {
+        let Some(origin) = self.origin.get() else {
+            return;
+        };
+        self.spans.borrow_mut().push(SubgraphSpan {
+            subgraph: KeyData::from_ffi(subgraph_ffi).into(),
+            tick,
+            start: start.saturating_duration_since(origin),
+            duration: start.elapsed(),
+            items,
+        });
+    }
+
+    /// Returns the recorded spans, in the order the subgraphs finished running.
+    pub(super) fn spans(&self) -> Vec<SubgraphSpan> {
+        self.spans.borrow().clone()
+    }
+
+    /// Writes the recorded spans to `writer` in the Chrome trace event JSON format, as complete
+    /// (`"X"`) events with the tick and item count as arguments.
+    pub(super) fn write_chrome_trace(&self, writer: impl std::io::Write) -> std::io::Result<()> {
+        let trace_events = self
+            .spans
+            .borrow()
+            .iter()
+            .map(|span| {
+                json!({
+                    "name": format!("subgraph {:?}", span.subgraph.data()),
+                    "cat": "subgraph",
+                    "ph": "X",
+                    "ts": span.start.as_secs_f64() * 1e6,
+                    "dur": span.duration.as_secs_f64() * 1e6,
+                    "pid": 0,
+                    "tid": 0,
+                    "args": {
+                        "tick": span.tick.0,
+                        "items": span.items,
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_writer(writer, &json!({ "traceEvents": trace_events }))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_chrome_trace() {
+        let trace = ExecutionTrace::default();
+        let start = Instant::now();
+        trace.record(1, TickInstant(0), start, 3);
+        assert!(trace.spans().is_empty());
+
+        trace.set_enabled(true);
+        let start = trace.start().unwrap();
+        trace.record(1, TickInstant(2), start, 3);
+
+        let mut buf = Vec::new();
+        trace.write_chrome_trace(&mut buf).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        let event = &value["traceEvents"][0];
+        assert_eq!("X", event["ph"]);
+        assert_eq!(2, event["args"]["tick"]);
+        assert_eq!(3, event["args"]["items"]);
+
+        trace.set_enabled(false);
+        assert!(trace.start().is_none());
+        assert!(trace.spans().is_empty());
+    }
+}
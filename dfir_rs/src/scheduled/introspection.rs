@@ -0,0 +1,135 @@
+//! Introspection of the state held by stateful operators, for debugging running graphs.
+//!
+//! When enabled with [`Dfir::enable_state_introspection`](super::context::Dfir::enable_state_introspection),
+//! stateful operators (such as `join`, `fold_keyed`, and `persist`) register the size and a small
+//! sample of their state at the end of each tick, which can then be read with
+//! [`Dfir::state_snapshot`](super::context::Dfir::state_snapshot). Introspection is disabled by
+//! default, in which case operators only check a flag at the end of each tick.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use dfir_lang::graph_ids::GraphNodeId;
+
+use super::ticks::TickInstant;
+
+/// Default number of items sampled from each operator's state.
+pub const DEFAULT_SAMPLE_SIZE: usize = 8;
+
+/// A snapshot of the state of the stateful operators in a DFIR graph.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct StateSnapshot {
+    /// The state of each operator which has registered it, keyed by operator.
+    pub operators: BTreeMap<GraphNodeId, OperatorStateSnapshot>,
+}
+
+/// The state of a single stateful operator, as of the end of a tick.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct OperatorStateSnapshot {
+    /// The name of the operator, e.g. `"fold_keyed"`.
+    pub op_name: &'static str,
+    /// The tick at the end of which this snapshot was taken.
+    pub tick: TickInstant,
+    /// The number of items (or keys, for keyed state) held by the operator.
+    pub len: usize,
+    /// The [`Debug`] representation of up to the sample size items, in no particular order.
+    /// Items which do not implement [`Debug`] are shown as `<opaque>`.
+    pub sample: Vec<String>,
+}
+
+/// The registry which stateful operators report their state to, owned by the
+/// [`Context`](super::context::Context).
+#[derive(Default)]
+pub struct StateRegistry {
+    /// The number of items to sample, or `None` if introspection is disabled.
+    sample_size: Cell<Option<usize>>,
+    operators: RefCell<BTreeMap<GraphNodeId, OperatorStateSnapshot>>,
+}
+
+impl StateRegistry {
+    /// The number of items operators should sample, or `None` if introspection is disabled.
+    pub fn sample_size(&self) -> Option<usize> {
+        self.sample_size.get()
+    }
+
+    /// Enables (with the given sample size) or disables introspection. Disabling clears the
+    /// registered state.
+    pub(super) fn set_sample_size(&self, sample_size: Option<usize>) {
+        self.sample_size.set(sample_size);
+        if sample_size.is_none() {
+            self.operators.borrow_mut().clear();
+        }
+    }
+
+    /// Records the state of an operator, replacing its previous state.
+    #[doc(hidden)] // Called by generated operator code.
+    pub fn record(
+        &self,
+        node_id: GraphNodeId,
+        op_name: &'static str,
+        tick: TickInstant,
+        len: usize,
+        sample: Vec<String>,
+    ) {
+        self.operators.borrow_mut().insert(
+            node_id,
+            OperatorStateSnapshot {
+                op_name,
+                tick,
+                len,
+                sample,
+            },
+        );
+    }
+
+    /// Returns a snapshot of the recorded state.
+    pub(super) fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            operators: self.operators.borrow().clone(),
+        }
+    }
+}
+
+/// Wrapper used by generated code to format state items with [`Debug`] when it is implemented,
+/// via autoref specialization: call `(&Sample(&item)).sample()` with both [`SampleDebug`] and
+/// [`SampleOpaque`] in scope.
+#[doc(hidden)]
+pub struct Sample<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub trait SampleDebug {
+    fn sample(&self) -> String;
+}
+
+impl<T: Debug> SampleDebug for Sample<'_, T> {
+    fn sample(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+#[doc(hidden)]
+pub trait SampleOpaque {
+    fn sample(&self) -> String;
+}
+
+impl<T> SampleOpaque for &Sample<'_, T> {
+    fn sample(&self) -> String {
+        "<opaque>".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct NotDebug;
+
+    #[test]
+    fn test_sample_specialization() {
+        assert_eq!("(1, \"a\")", (&Sample(&(1, "a"))).sample());
+        assert_eq!("<opaque>", (&Sample(&NotDebug)).sample());
+    }
+}
@@ -1,6 +1,9 @@
 //! DFIR runtime module. Contains the inline execution engine, context, and metrics.
 
+pub mod checkpoint;
 pub mod context;
+pub mod execution_trace;
+pub mod introspection;
 pub mod metrics;
 pub mod net;
 
@@ -75,6 +75,18 @@ pub trait SingleVariant: DemuxEnumBase {
     fn single_variant(self) -> Self::Output;
 }
 
+/// Trait for use with the `mux_enum` operator.
+///
+/// This trait is meant to be derived: `#[derive(DemuxEnum)]`.
+///
+/// The derive will implement this such that `Constructors` has an associated function for each
+/// variant, named after the variant, which constructs it from a tuple of its fields.
+#[doc(hidden)]
+pub trait MuxEnum: DemuxEnumBase {
+    /// A hidden type with the constructor of each variant.
+    type Constructors;
+}
+
 /// Base implementation to constrain that [`DemuxEnum<SOMETHING>`] is implemented.
 #[diagnostic::on_unimplemented(note = "use `#[derive(dfir_rs::DemuxEnum)]`")]
 pub trait DemuxEnumBase {}
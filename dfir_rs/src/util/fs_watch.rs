@@ -0,0 +1,288 @@
+//! Streams of file contents and filesystem events, used by the `source_file_lines` and
+//! `source_dir_watch` operators.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use futures::Stream;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
+
+/// How often files and directories are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Streams the lines of the file at `path`, without their line endings.
+///
+/// If `follow` is `false`, the stream ends at the end of the file. Otherwise, like `tail -F`, the
+/// stream waits for lines to be appended to the file, and only emits a line once it is complete
+/// (i.e. ends with a newline). If the file is truncated, the stream continues from the start of
+/// the file. If the file is replaced (for example by log rotation, which renames it and creates a
+/// new file at `path`), the stream finishes reading the old file and then continues from the
+/// start of the new one. Replaced files are only detected on Unix.
+pub fn file_lines(path: impl AsRef<Path>, follow: bool) -> impl Stream<Item = io::Result<String>> {
+    file_lines_polled(path, follow, || tokio::time::sleep(POLL_INTERVAL))
+}
+
+/// [`file_lines`], which waits for `poll()` between checks for changes to the followed file.
+fn file_lines_polled<Poll, PollFut>(
+    path: impl AsRef<Path>,
+    follow: bool,
+    poll: Poll,
+) -> impl Stream<Item = io::Result<String>>
+where
+    Poll: FnMut() -> PollFut,
+    PollFut: Future<Output = ()>,
+{
+    struct State<Poll> {
+        path: PathBuf,
+        reader: Option<BufReader<File>>,
+        /// The start of a line which has not been completely written yet.
+        partial: String,
+        poll: Poll,
+    }
+
+    let state = State {
+        path: path.as_ref().to_owned(),
+        reader: None,
+        partial: String::new(),
+        poll,
+    };
+    futures::stream::try_unfold(state, move |mut state| async move {
+        if state.reader.is_none() {
+            state.reader = Some(BufReader::new(File::open(&state.path).await?));
+        }
+
+        loop {
+            let reader = state.reader.as_mut().unwrap();
+            let read = reader.read_line(&mut state.partial).await?;
+            if state.partial.ends_with('\n') {
+                let mut line = std::mem::take(&mut state.partial);
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+                return Ok(Some((line, state)));
+            }
+            if 0 != read {
+                // Part of a line, keep reading.
+                continue;
+            }
+
+            // End of file.
+            if !follow {
+                if state.partial.is_empty() {
+                    return Ok(None);
+                }
+                let line = std::mem::take(&mut state.partial);
+                return Ok(Some((line, state)));
+            }
+
+            // While a file is being replaced, there may be no file at `path` for a moment.
+            let current = match tokio::fs::metadata(&state.path).await {
+                Ok(metadata) => Some(metadata),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            };
+            if let Some(current) = current {
+                if !same_file(&reader.get_ref().metadata().await?, &current) {
+                    // The old file was read to its end, so continue with the new one.
+                    state.reader = Some(BufReader::new(File::open(&state.path).await?));
+                    if !state.partial.is_empty() {
+                        let line = std::mem::take(&mut state.partial);
+                        return Ok(Some((line, state)));
+                    }
+                    continue;
+                }
+                if current.len() < reader.stream_position().await? {
+                    reader.seek(io::SeekFrom::Start(0)).await?;
+                    state.partial.clear();
+                    continue;
+                }
+            }
+            (state.poll)().await;
+        }
+    })
+}
+
+/// Whether `a` and `b` are the metadata of the same file.
+#[cfg(unix)]
+fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+/// Files cannot be identified on stable Rust on this platform, so only truncation is detected.
+#[cfg(not(unix))]
+fn same_file(_a: &std::fs::Metadata, _b: &std::fs::Metadata) -> bool {
+    true
+}
+
+/// A change to an entry of a watched directory, see [`dir_watch`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DirEvent {
+    /// An entry was added to the directory.
+    Created(PathBuf),
+    /// The contents of an entry were modified.
+    Modified(PathBuf),
+    /// An entry was removed from the directory.
+    Removed(PathBuf),
+}
+
+/// Streams changes to the entries of the directory at `path` (not including subdirectories).
+///
+/// Entries which already exist when the stream is first polled are not reported. Changes are
+/// detected by periodically comparing the modification time and size of each entry, so changes
+/// which are undone before the next check may be missed.
+pub fn dir_watch(path: impl AsRef<Path>) -> impl Stream<Item = io::Result<DirEvent>> {
+    struct State {
+        path: PathBuf,
+        /// The modification time and size of each entry as of the last check, or `None` before
+        /// the first check.
+        entries: Option<BTreeMap<PathBuf, (SystemTime, u64)>>,
+        /// Events found by the last check which have not been emitted yet.
+        pending: Vec<DirEvent>,
+    }
+
+    async fn scan(path: &Path) -> io::Result<BTreeMap<PathBuf, (SystemTime, u64)>> {
+        let mut entries = BTreeMap::new();
+        let mut read_dir = tokio::fs::read_dir(path).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            entries.insert(entry.path(), (metadata.modified()?, metadata.len()));
+        }
+        Ok(entries)
+    }
+
+    let state = State {
+        path: path.as_ref().to_owned(),
+        entries: None,
+        pending: Vec::new(),
+    };
+    futures::stream::try_unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop() {
+                return Ok(Some((event, state)));
+            }
+
+            let Some(previous) = state.entries.take() else {
+                state.entries = Some(scan(&state.path).await?);
+                continue;
+            };
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let current = scan(&state.path).await?;
+
+            for (path, stamp) in &current {
+                match previous.get(path) {
+                    None => state.pending.push(DirEvent::Created(path.clone())),
+                    Some(previous_stamp) if previous_stamp != stamp => {
+                        state.pending.push(DirEvent::Modified(path.clone()))
+                    }
+                    Some(_) => {}
+                }
+            }
+            for path in previous.keys() {
+                if !current.contains_key(path) {
+                    state.pending.push(DirEvent::Removed(path.clone()));
+                }
+            }
+            // Emitted by popping, so reverse to emit in path order.
+            state.pending.reverse();
+            state.entries = Some(current);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use futures::StreamExt;
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    /// Follows the lines of `path`, only checking for changes once `notify` is notified.
+    fn follow(path: &Path, notify: &Arc<Notify>) -> impl Stream<Item = io::Result<String>> {
+        let notify = notify.clone();
+        file_lines_polled(path, true, move || {
+            let notify = notify.clone();
+            async move { notify.notified().await }
+        })
+    }
+
+    async fn next_line(lines: &mut (impl Stream<Item = io::Result<String>> + Unpin)) -> String {
+        lines.next().await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_file_lines_follow_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lines.txt");
+        std::fs::write(&path, "first\n").unwrap();
+
+        let notify = Arc::new(Notify::new());
+        let mut lines = Box::pin(follow(&path, &notify));
+        assert_eq!("first", next_line(&mut lines).await);
+
+        std::fs::write(&path, "a\n").unwrap();
+        notify.notify_one();
+        assert_eq!("a", next_line(&mut lines).await);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_file_lines_follow_rotated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lines.txt");
+        let rotated = dir.path().join("lines.txt.1");
+        std::fs::write(&path, "a\n").unwrap();
+
+        let notify = Arc::new(Notify::new());
+        let mut lines = Box::pin(follow(&path, &notify));
+        assert_eq!("a", next_line(&mut lines).await);
+
+        // The writer appends to the old file after it was renamed, before switching to the new one.
+        std::fs::rename(&path, &rotated).unwrap();
+        std::fs::write(&path, "c\n").unwrap();
+        let mut old = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&rotated)
+            .unwrap();
+        old.write_all(b"b\n").unwrap();
+        notify.notify_one();
+        assert_eq!("b", next_line(&mut lines).await);
+        assert_eq!("c", next_line(&mut lines).await);
+
+        // Appends to the old file are no longer seen.
+        old.write_all(b"x\n").unwrap();
+        let mut new = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        new.write_all(b"d\n").unwrap();
+        notify.notify_one();
+        assert_eq!("d", next_line(&mut lines).await);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_file_lines_follow_recreated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lines.txt");
+        std::fs::write(&path, "a\n").unwrap();
+
+        let notify = Arc::new(Notify::new());
+        let mut lines = Box::pin(follow(&path, &notify));
+        assert_eq!("a", next_line(&mut lines).await);
+
+        std::fs::remove_file(&path).unwrap();
+        // Keeps waiting while there is no file.
+        notify.notify_one();
+        std::fs::write(&path, "b\n").unwrap();
+        assert_eq!("b", next_line(&mut lines).await);
+    }
+}
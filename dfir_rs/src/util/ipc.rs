@@ -0,0 +1,309 @@
+//! Local inter-process communication over named endpoints, with the same API on every platform.
+//!
+//! An endpoint is identified by a plain name, which is mapped to the native local IPC mechanism:
+//! * Linux: a Unix domain socket in the abstract namespace, which needs no filesystem cleanup.
+//! * Other Unix platforms: a Unix domain socket file in the temporary directory.
+//! * Windows: the named pipe `\\.\pipe\<name>`.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf, ReadHalf, WriteHalf};
+use tokio_util::codec::{
+    BytesCodec, Decoder, FramedRead, FramedWrite, LengthDelimitedCodec, LinesCodec,
+};
+
+/// A connection to a local IPC endpoint, created by [`IpcListener::accept`] or [`connect_ipc`].
+#[derive(Debug)]
+pub struct IpcStream {
+    #[cfg(unix)]
+    inner: tokio::net::UnixStream,
+    #[cfg(windows)]
+    inner: windows::Pipe,
+}
+
+/// A local IPC endpoint which accepts connections, created by [`bind_ipc`].
+#[derive(Debug)]
+pub struct IpcListener {
+    #[cfg(unix)]
+    inner: tokio::net::UnixListener,
+    #[cfg(windows)]
+    inner: windows::PipeListener,
+}
+
+impl IpcListener {
+    /// Accepts a new connection to this endpoint.
+    pub async fn accept(&mut self) -> io::Result<IpcStream> {
+        #[cfg(unix)]
+        let inner = self.inner.accept().await?.0;
+        #[cfg(windows)]
+        let inner = self.inner.accept().await?;
+        Ok(IpcStream { inner })
+    }
+}
+
+/// Creates the local IPC endpoint `name`, to accept connections from [`connect_ipc`]. Must be
+/// called within a Tokio runtime.
+///
+/// On non-Linux Unix platforms, any stale socket file left behind by a previous endpoint with the
+/// same name is replaced.
+pub fn bind_ipc(name: &str) -> io::Result<IpcListener> {
+    #[cfg(target_os = "linux")]
+    let inner = {
+        use std::os::linux::net::SocketAddrExt;
+
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        let listener = std::os::unix::net::UnixListener::bind_addr(&addr)?;
+        listener.set_nonblocking(true)?;
+        tokio::net::UnixListener::from_std(listener)?
+    };
+    #[cfg(all(unix, not(target_os = "linux")))]
+    let inner = {
+        let path = socket_path(name);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        tokio::net::UnixListener::bind(path)?
+    };
+    #[cfg(windows)]
+    let inner = windows::PipeListener::bind(windows::pipe_path(name))?;
+    Ok(IpcListener { inner })
+}
+
+/// Connects to the local IPC endpoint `name`, created by [`bind_ipc`].
+pub async fn connect_ipc(name: &str) -> io::Result<IpcStream> {
+    #[cfg(target_os = "linux")]
+    let inner = {
+        use std::os::linux::net::SocketAddrExt;
+
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        let stream = std::os::unix::net::UnixStream::connect_addr(&addr)?;
+        stream.set_nonblocking(true)?;
+        let stream = tokio::net::UnixStream::from_std(stream)?;
+        // Wait for the connection to be established, as `tokio::net::UnixStream::connect` does.
+        stream.writable().await?;
+        if let Some(err) = stream.take_error()? {
+            return Err(err);
+        }
+        stream
+    };
+    #[cfg(all(unix, not(target_os = "linux")))]
+    let inner = tokio::net::UnixStream::connect(socket_path(name)).await?;
+    #[cfg(windows)]
+    let inner = windows::connect(windows::pipe_path(name)).await?;
+    Ok(IpcStream { inner })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn socket_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("{name}.sock"))
+}
+
+impl AsyncRead for IpcStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for IpcStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Helper creates a local IPC `Stream` and `Sink` from the given connection, using the given
+/// `Codec` to handle delineation between inputs/outputs.
+pub fn ipc_framed<Codec>(
+    stream: IpcStream,
+    codec: Codec,
+) -> (
+    FramedWrite<WriteHalf<IpcStream>, Codec>,
+    FramedRead<ReadHalf<IpcStream>, Codec>,
+)
+where
+    Codec: Clone + Decoder,
+{
+    let (recv, send) = tokio::io::split(stream);
+    let send = FramedWrite::new(send, codec.clone());
+    let recv = FramedRead::new(recv, codec);
+    (send, recv)
+}
+
+/// Helper creates a local IPC `Stream` and `Sink` for `Bytes` strings where each string is
+/// length-delimited.
+pub fn ipc_bytes(
+    stream: IpcStream,
+) -> (
+    FramedWrite<WriteHalf<IpcStream>, LengthDelimitedCodec>,
+    FramedRead<ReadHalf<IpcStream>, LengthDelimitedCodec>,
+) {
+    ipc_framed(stream, LengthDelimitedCodec::new())
+}
+
+/// Helper creates a local IPC `Stream` and `Sink` for undelimited streams of `Bytes`.
+pub fn ipc_bytestream(
+    stream: IpcStream,
+) -> (
+    FramedWrite<WriteHalf<IpcStream>, BytesCodec>,
+    FramedRead<ReadHalf<IpcStream>, BytesCodec>,
+) {
+    ipc_framed(stream, BytesCodec::new())
+}
+
+/// Helper creates a local IPC `Stream` and `Sink` for `str`ings delimited by newlines.
+pub fn ipc_lines(
+    stream: IpcStream,
+) -> (
+    FramedWrite<WriteHalf<IpcStream>, LinesCodec>,
+    FramedRead<ReadHalf<IpcStream>, LinesCodec>,
+) {
+    ipc_framed(stream, LinesCodec::new())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio::net::windows::named_pipe::{
+        ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions,
+    };
+
+    /// All pipe instances are busy.
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    /// How long to wait for a busy pipe to create its next instance before giving up.
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn pipe_path(name: &str) -> String {
+        format!(r"\\.\pipe\{name}")
+    }
+
+    /// Either end of a named pipe connection.
+    #[derive(Debug)]
+    pub enum Pipe {
+        Server(NamedPipeServer),
+        Client(NamedPipeClient),
+    }
+
+    /// A named pipe which accepts connections. Each named pipe instance serves a single
+    /// connection, so a new instance is created as each connection is accepted.
+    #[derive(Debug)]
+    pub struct PipeListener {
+        path: String,
+        next: NamedPipeServer,
+    }
+
+    impl PipeListener {
+        pub fn bind(path: String) -> io::Result<Self> {
+            let next = ServerOptions::new()
+                .first_pipe_instance(true)
+                .create(&path)?;
+            Ok(Self { path, next })
+        }
+
+        pub async fn accept(&mut self) -> io::Result<Pipe> {
+            self.next.connect().await?;
+            let instance = ServerOptions::new().create(&self.path)?;
+            Ok(Pipe::Server(std::mem::replace(&mut self.next, instance)))
+        }
+    }
+
+    pub async fn connect(path: String) -> io::Result<Pipe> {
+        let deadline = tokio::time::Instant::now() + BUSY_TIMEOUT;
+        loop {
+            match ClientOptions::new().open(&path) {
+                Ok(client) => return Ok(Pipe::Client(client)),
+                // Wait for the server to create its next pipe instance, unless it has stopped
+                // accepting connections.
+                Err(e)
+                    if e.raw_os_error() == Some(ERROR_PIPE_BUSY)
+                        && tokio::time::Instant::now() < deadline =>
+                {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    impl AsyncRead for Pipe {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            match self.get_mut() {
+                Pipe::Server(server) => Pin::new(server).poll_read(cx, buf),
+                Pipe::Client(client) => Pin::new(client).poll_read(cx, buf),
+            }
+        }
+    }
+
+    impl AsyncWrite for Pipe {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            match self.get_mut() {
+                Pipe::Server(server) => Pin::new(server).poll_write(cx, buf),
+                Pipe::Client(client) => Pin::new(client).poll_write(cx, buf),
+            }
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            match self.get_mut() {
+                Pipe::Server(server) => Pin::new(server).poll_flush(cx),
+                Pipe::Client(client) => Pin::new(client).poll_flush(cx),
+            }
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            match self.get_mut() {
+                Pipe::Server(server) => Pin::new(server).poll_shutdown(cx),
+                Pipe::Client(client) => Pin::new(client).poll_shutdown(cx),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{SinkExt, StreamExt};
+
+    use super::*;
+
+    #[crate::test]
+    async fn test_ipc_lines() {
+        let name = format!("dfir-test-ipc-{}", std::process::id());
+        let mut listener = bind_ipc(&name).unwrap();
+        let (client, server) = tokio::join!(connect_ipc(&name), listener.accept());
+
+        let (mut client_send, _client_recv) = ipc_lines(client.unwrap());
+        let (_server_send, mut server_recv) = ipc_lines(server.unwrap());
+        client_send.send("hello").await.unwrap();
+        assert_eq!("hello", server_recv.next().await.unwrap().unwrap());
+    }
+}
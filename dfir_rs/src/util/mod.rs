@@ -5,18 +5,34 @@
 #[cfg_attr(docsrs, doc(cfg(feature = "dfir_macro")))]
 pub mod demux_enum;
 pub mod multiset;
+pub mod sketch;
 #[cfg(feature = "tokio")]
 pub mod unsync;
 
 mod monotonic;
 pub use monotonic::*;
 
+mod server_stream;
+pub use server_stream::*;
+
 #[cfg(feature = "tokio")]
 mod udp;
 #[cfg(feature = "tokio")]
 #[cfg(not(target_arch = "wasm32"))]
 pub use udp::*;
 
+#[cfg(feature = "tokio")]
+#[cfg(not(target_arch = "wasm32"))]
+mod fs_watch;
+#[cfg(feature = "tokio")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use fs_watch::*;
+
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "python")]
+pub use python::*;
+
 #[cfg(feature = "tokio")]
 mod tcp;
 #[cfg(feature = "tokio")]
@@ -26,6 +42,7 @@ pub use tcp::*;
 #[cfg(feature = "tokio")]
 #[cfg(unix)]
 mod socket;
+
 use std::net::SocketAddr;
 #[cfg(feature = "tokio")]
 use std::num::NonZeroUsize;
@@ -38,6 +55,13 @@ use serde::ser::Serialize;
 #[cfg(unix)]
 pub use socket::*;
 
+#[cfg(feature = "tokio")]
+#[cfg(any(unix, windows))]
+mod ipc;
+#[cfg(feature = "tokio")]
+#[cfg(any(unix, windows))]
+pub use ipc::*;
+
 /// Returns a channel as a (1) unbounded sender and (2) unbounded receiver `Stream` for use in DFIR.
 #[cfg(feature = "tokio")]
 pub fn unbounded_channel<T>() -> (
@@ -0,0 +1,71 @@
+//! In-process interop with Python, used by the `source_python` and `dest_python` operators.
+
+use pyo3::prelude::*;
+
+/// Initializes the embedded Python interpreter, if it is not already running.
+///
+/// The `auto-initialize` feature of `pyo3` is not used, as it requires a shared `libpython` to
+/// build.
+fn init() {
+    pyo3::prepare_freethreaded_python();
+}
+
+/// Imports `module` and gets its attribute `func`, panicking if either fails.
+fn import_function(py: Python<'_>, module: &str, func: &str) -> Py<PyAny> {
+    py.import_bound(module)
+        .and_then(|module| module.getattr(func))
+        .unwrap_or_else(|err| panic!("Failed to load Python function `{module}.{func}`: {err}"))
+        .unbind()
+}
+
+/// Iterates over the iterable returned by calling the Python function `func` of `module` with no
+/// arguments (for example a list, or a generator).
+///
+/// Panics if the function cannot be loaded or called, or if iterating raises an exception.
+pub fn python_iter(module: &str, func: &str) -> impl Iterator<Item = Py<PyAny>> {
+    init();
+    let name = format!("{module}.{func}");
+    let iter = Python::with_gil(|py| {
+        import_function(py, module, func)
+            .bind(py)
+            .call0()
+            .and_then(|iterable| iterable.iter())
+            .unwrap_or_else(|err| panic!("Failed to iterate over `{name}()`: {err}"))
+            .unbind()
+    });
+    std::iter::from_fn(move || {
+        Python::with_gil(|py| {
+            let item = iter.bind(py).clone().next()?;
+            Some(
+                item.unwrap_or_else(|err| panic!("Failed to iterate over `{name}()`: {err}"))
+                    .unbind(),
+            )
+        })
+    })
+}
+
+/// A Python function which is called once for each item, see [`PythonFunction::call`].
+pub struct PythonFunction {
+    name: String,
+    func: Py<PyAny>,
+}
+
+impl PythonFunction {
+    /// Loads the Python function `func` of `module`, panicking if it cannot be loaded.
+    pub fn new(module: &str, func: &str) -> Self {
+        init();
+        Self {
+            name: format!("{module}.{func}"),
+            func: Python::with_gil(|py| import_function(py, module, func)),
+        }
+    }
+
+    /// Calls the function with `item` as its only argument, panicking if it raises an exception.
+    pub fn call<T: IntoPy<Py<PyAny>>>(&self, item: T) {
+        Python::with_gil(|py| {
+            self.func
+                .call1(py, (item,))
+                .unwrap_or_else(|err| panic!("Python function `{}` failed: {err}", self.name));
+        });
+    }
+}
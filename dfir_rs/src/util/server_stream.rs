@@ -0,0 +1,199 @@
+//! Adapters which turn the request streams of async servers into streams for `source_stream`,
+//! for embedding DFIR graphs inside existing gRPC or HTTP servers.
+//!
+//! A [tonic](https://docs.rs/tonic) client-streaming or bidirectional handler receives a
+//! `tonic::Streaming<T>`, which is a `Stream<Item = Result<T, tonic::Status>>`, and can be passed
+//! to [`request_stream`] directly. A [hyper](https://docs.rs/hyper) request body implements
+//! `http_body::Body`, and can be passed to `body_stream` (with the `http-body` feature).
+//!
+//! ```rust,ignore
+//! async fn record_route(
+//!     &self,
+//!     request: tonic::Request<tonic::Streaming<Point>>,
+//! ) -> Result<tonic::Response<RouteSummary>, tonic::Status> {
+//!     let points = dfir_rs::util::request_stream(request.into_inner(), self.shutdown.clone());
+//!     let (summary_send, mut summary_recv) = dfir_rs::util::unbounded_channel();
+//!     let mut df = dfir_syntax! {
+//!         source_stream(points)
+//!             -> map(Result::unwrap)
+//!             -> fold::<'static>(RouteSummary::default, RouteSummary::add)
+//!             -> for_each(|summary| summary_send.send(summary).unwrap());
+//!     };
+//!     df.run_available().await;
+//!     // ...
+//! }
+//! ```
+//!
+//! Both adapters end their stream when the request ends, after the first error (which is still
+//! emitted, so the graph can handle it), or when the `shutdown` future completes, whichever is
+//! first. Items which have already been emitted are processed by the graph as usual, so
+//! completing `shutdown` when the server starts shutting down (for example with the same signal
+//! passed to `serve_with_shutdown`) stops accepting new items while letting the graph drain.
+//! Use [`std::future::pending`] if the stream should only end with the request.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use futures::Stream;
+use futures::stream::FusedStream;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// A stream of the items of a request, which ends after the first error or when the shutdown
+    /// future completes. Created by [`request_stream`] and `body_stream`.
+    #[must_use = "streams do nothing unless polled"]
+    pub struct RequestStream<S, F> {
+        #[pin]
+        stream: S,
+        #[pin]
+        shutdown: F,
+        done: bool,
+    }
+}
+
+/// Adapts a stream of request items, such as a `tonic::Streaming<T>`, for `source_stream`, ending
+/// it after the first error or once `shutdown` completes.
+pub fn request_stream<S, F, T, E>(stream: S, shutdown: F) -> RequestStream<S, F>
+where
+    S: Stream<Item = Result<T, E>>,
+    F: Future<Output = ()>,
+{
+    RequestStream {
+        stream,
+        shutdown,
+        done: false,
+    }
+}
+
+impl<S, F, T, E> Stream for RequestStream<S, F>
+where
+    S: Stream<Item = Result<T, E>>,
+    F: Future<Output = ()>,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+        if this.shutdown.poll(cx).is_ready() {
+            *this.done = true;
+            return Poll::Ready(None);
+        }
+
+        let item = ready!(this.stream.poll_next(cx));
+        // Request streams can't be resumed after an error.
+        *this.done = !matches!(item, Some(Ok(_)));
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            (0, Some(0))
+        } else {
+            (0, self.stream.size_hint().1)
+        }
+    }
+}
+
+impl<S, F, T, E> FusedStream for RequestStream<S, F>
+where
+    S: Stream<Item = Result<T, E>>,
+    F: Future<Output = ()>,
+{
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(feature = "http-body")]
+pin_project! {
+    /// The data frames of an [`http_body::Body`] as a stream, skipping any trailers.
+    #[must_use = "streams do nothing unless polled"]
+    pub struct BodyData<B> {
+        #[pin]
+        body: B,
+    }
+}
+
+#[cfg(feature = "http-body")]
+impl<B> Stream for BodyData<B>
+where
+    B: http_body::Body,
+{
+    type Item = Result<B::Data, B::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            match ready!(this.body.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    if let Ok(data) = frame.into_data() {
+                        return Poll::Ready(Some(Ok(data)));
+                    }
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// Adapts a request body, such as a `hyper::body::Incoming`, into a stream of its data chunks
+/// for `source_stream`, ending it after the first error or once `shutdown` completes.
+///
+/// Chunks are emitted as they are received, and are not split into lines or messages.
+#[cfg(feature = "http-body")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http-body")))]
+pub fn body_stream<B, F>(body: B, shutdown: F) -> RequestStream<BodyData<B>, F>
+where
+    B: http_body::Body,
+    F: Future<Output = ()>,
+{
+    request_stream(BodyData { body }, shutdown)
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[crate::test]
+    async fn request_stream_ends_after_error() {
+        let stream = futures::stream::iter([Ok(1), Ok(2), Err("broken"), Ok(3)]);
+        let items = request_stream(stream, std::future::pending())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(items, vec![Ok(1), Ok(2), Err("broken")]);
+    }
+
+    #[crate::test]
+    async fn request_stream_ends_on_shutdown() {
+        let (send, recv) = crate::util::unbounded_channel::<Result<u32, ()>>();
+        let (shutdown_send, shutdown_recv) = tokio::sync::oneshot::channel::<()>();
+        let mut stream = std::pin::pin!(request_stream(recv, async move {
+            let _ = shutdown_recv.await;
+        }));
+
+        send.send(Ok(1)).unwrap();
+        assert_eq!(stream.next().await, Some(Ok(1)));
+
+        shutdown_send.send(()).unwrap();
+        send.send(Ok(2)).unwrap();
+        assert_eq!(stream.next().await, None);
+        assert!(stream.is_terminated());
+    }
+
+    #[cfg(feature = "http-body")]
+    #[crate::test]
+    async fn body_stream_emits_data() {
+        let chunks = body_stream(String::from("hello"), std::future::pending())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(&chunks[0].as_ref().unwrap()[..], b"hello");
+    }
+}
@@ -0,0 +1,206 @@
+//! Bounded-memory summaries used by the `top_k` and `heavy_hitters` operators.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Keeps the `k` greatest items seen so far, according to a comparator.
+#[derive(Clone, Debug)]
+pub struct TopK<T> {
+    k: usize,
+    /// Sorted in descending order, with at most `k` items.
+    items: Vec<T>,
+}
+
+impl<T> TopK<T> {
+    /// Creates an empty summary which keeps (at most) `k` items.
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            items: Vec::with_capacity(k),
+        }
+    }
+
+    /// Inserts `item`, evicting the least item if there are more than `k`. Items which compare
+    /// equal keep their insertion order.
+    pub fn insert(&mut self, item: T, mut cmp: impl FnMut(&T, &T) -> Ordering) {
+        if self.items.len() == self.k
+            && self
+                .items
+                .last()
+                .is_none_or(|least| cmp(&item, least) != Ordering::Greater)
+        {
+            return;
+        }
+
+        let index = self
+            .items
+            .partition_point(|existing| cmp(existing, &item) != Ordering::Less);
+        self.items.insert(index, item);
+        self.items.truncate(self.k);
+    }
+
+    /// Returns the kept items in descending order.
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.items.clone()
+    }
+
+    /// Removes and returns the kept items in descending order, leaving the summary empty.
+    pub fn take(&mut self) -> Vec<T> {
+        std::mem::replace(&mut self.items, Vec::with_capacity(self.k))
+    }
+}
+
+/// Tracks the most frequent items in a stream using the Space-Saving algorithm
+/// (Metwally et al., 2005), which uses a fixed number of counters.
+///
+/// With `ceil(1 / epsilon)` counters, after `N` items every item which occurred more than
+/// `epsilon * N` times is tracked, and the count of each tracked item overestimates its true
+/// count by at most `epsilon * N`.
+#[derive(Clone, Debug)]
+pub struct SpaceSaving<T> {
+    k: usize,
+    capacity: usize,
+    /// `(item, count)` for each counter, as a binary min-heap by count so that the counter to
+    /// evict is always at the root.
+    counters: Vec<(T, u64)>,
+    /// Index into `counters` for each tracked item.
+    index: HashMap<T, usize>,
+}
+
+impl<T> SpaceSaving<T>
+where
+    T: Clone + Eq + Hash,
+{
+    /// Creates an empty summary which reports the `k` most frequent items, with an error of at
+    /// most `epsilon` times the number of items seen.
+    ///
+    /// # Panics
+    /// If `epsilon` is not in `(0, 1]`.
+    pub fn new(k: usize, epsilon: f64) -> Self {
+        assert!(
+            epsilon > 0.0 && epsilon <= 1.0,
+            "epsilon must be in (0, 1], got {epsilon}"
+        );
+        let capacity = ((1.0 / epsilon).ceil() as usize).max(k).max(1);
+        Self {
+            k,
+            capacity,
+            counters: Vec::with_capacity(capacity),
+            index: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Counts one occurrence of `item`. If all counters are in use, the item replaces the one
+    /// with the smallest count and inherits that count.
+    ///
+    /// Takes `O(log(1 / epsilon))` time.
+    pub fn insert(&mut self, item: T) {
+        if let Some(&i) = self.index.get(&item) {
+            self.counters[i].1 += 1;
+            self.sift_down(i);
+        } else if self.counters.len() < self.capacity {
+            let i = self.counters.len();
+            self.index.insert(item.clone(), i);
+            self.counters.push((item, 1));
+            self.sift_up(i);
+        } else {
+            let (evicted, count) = std::mem::replace(&mut self.counters[0], (item.clone(), 0));
+            self.counters[0].1 = count + 1;
+            self.index.remove(&evicted);
+            self.index.insert(item, 0);
+            self.sift_down(0);
+        }
+    }
+
+    /// Swaps two counters in the heap, keeping `index` up to date.
+    fn swap(&mut self, i: usize, j: usize) {
+        self.counters.swap(i, j);
+        *self.index.get_mut(&self.counters[i].0).unwrap() = i;
+        *self.index.get_mut(&self.counters[j].0).unwrap() = j;
+    }
+
+    /// Restores the heap order after the count at `i` decreased (or was added).
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.counters[parent].1 <= self.counters[i].1 {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    /// Restores the heap order after the count at `i` increased.
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let mut least = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.counters.len() && self.counters[child].1 < self.counters[least].1 {
+                    least = child;
+                }
+            }
+            if least == i {
+                break;
+            }
+            self.swap(i, least);
+            i = least;
+        }
+    }
+
+    /// Returns the `k` items with the largest estimated counts, along with those counts, in
+    /// descending order of count.
+    pub fn to_vec(&self) -> Vec<(T, u64)> {
+        let mut top = self.counters.clone();
+        top.sort_by(|(_, a), (_, b)| b.cmp(a));
+        top.truncate(self.k);
+        top
+    }
+
+    /// Like [`Self::to_vec`], but also resets the summary.
+    pub fn take(&mut self) -> Vec<(T, u64)> {
+        let top = self.to_vec();
+        self.counters.clear();
+        self.index.clear();
+        top
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn top_k_keeps_greatest() {
+        let mut top = TopK::new(3);
+        for item in [5, 1, 4, 2, 3, 5] {
+            top.insert(item, Ord::cmp);
+        }
+        assert_eq!(top.to_vec(), vec![5, 5, 4]);
+        assert_eq!(top.take(), vec![5, 5, 4]);
+        assert!(top.take().is_empty());
+    }
+
+    #[test]
+    fn space_saving_finds_frequent_items() {
+        let mut sketch = SpaceSaving::new(2, 0.25);
+        for item in ["a", "b", "a", "c", "a", "d", "b", "e", "a", "b"] {
+            sketch.insert(item);
+        }
+        assert_eq!(sketch.to_vec(), vec![("a", 4), ("b", 3)]);
+    }
+
+    #[test]
+    fn space_saving_evicts_least_counter() {
+        let mut sketch = SpaceSaving::new(2, 0.5);
+        for item in [1, 1, 1, 2, 3, 3, 4] {
+            sketch.insert(item);
+        }
+        // `3` replaced `2` and inherited its count of 1, then `4` replaced `3` (now the least
+        // counter) and inherited its count of 3.
+        assert_eq!(sketch.to_vec(), vec![(4, 4), (1, 3)]);
+    }
+}
@@ -0,0 +1,17 @@
+use dfir_rs::util::demux_enum::DemuxEnum;
+use dfir_rs::dfir_syntax;
+
+fn main() {
+    #[derive(DemuxEnum)]
+    enum Shape {
+        Square(f64),
+        Point,
+    }
+
+    let mut df = dfir_syntax! {
+        my_mux = mux_enum::<Shape>() -> for_each(std::mem::drop);
+        source_iter([(9.0,)]) -> [Square]my_mux;
+        source_iter([()]) -> my_mux;
+    };
+    df.run_available_sync();
+}
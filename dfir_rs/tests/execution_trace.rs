@@ -0,0 +1,45 @@
+use dfir_rs::dfir_syntax;
+use multiplatform_test::multiplatform_test;
+
+/// Tests that no runs are recorded unless tracing is enabled.
+#[multiplatform_test(dfir)]
+async fn test_disabled_by_default() {
+    let mut flow = dfir_syntax! {
+        source_iter(0..10) -> fold::<'tick>(|| 0, |acc: &mut i32, x| *acc += x) -> null();
+    };
+    flow.run_available().await;
+
+    assert!(flow.execution_trace().is_empty());
+}
+
+/// Tests that each subgraph records its runs, with the items it read, and that the trace is
+/// exported as Chrome trace events.
+#[multiplatform_test(dfir)]
+async fn test_execution_trace() {
+    // `next_stratum()` splits the graph into two subgraphs, connected by a handoff.
+    let mut flow = dfir_syntax! {
+        source_iter(0..10)
+            -> next_stratum()
+            -> fold::<'tick>(|| 0, |acc: &mut i32, x| *acc += x)
+            -> null();
+    };
+    flow.enable_execution_trace();
+    flow.run_tick().await;
+
+    let trace = flow.execution_trace();
+    assert_eq!(2, trace.len());
+    assert!(trace.iter().all(|span| span.tick.0 == 0));
+    // The first subgraph has no input handoffs, and the second reads all ten items.
+    assert_eq!(
+        vec![0, 10],
+        trace.iter().map(|span| span.items).collect::<Vec<_>>()
+    );
+
+    let mut buf = Vec::new();
+    flow.write_chrome_trace(&mut buf).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+    assert_eq!(2, value["traceEvents"].as_array().unwrap().len());
+
+    flow.disable_execution_trace();
+    assert!(flow.execution_trace().is_empty());
+}
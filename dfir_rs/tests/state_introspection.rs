@@ -0,0 +1,58 @@
+use dfir_rs::dfir_syntax;
+use multiplatform_test::multiplatform_test;
+
+/// Tests that no state is recorded unless introspection is enabled.
+#[multiplatform_test(dfir)]
+async fn test_disabled_by_default() {
+    let mut flow = dfir_syntax! {
+        source_iter([("a", 1), ("b", 2)])
+            -> fold_keyed::<'static>(|| 0, |acc: &mut i32, x| *acc += x)
+            -> null();
+    };
+    flow.run_available().await;
+
+    assert!(flow.state_snapshot().operators.is_empty());
+}
+
+/// Tests that `fold_keyed`, `join`, and `persist` record their state, including `'tick` state
+/// before it is cleared.
+#[multiplatform_test(dfir)]
+async fn test_snapshot() {
+    let mut flow = dfir_syntax! {
+        source_iter([("a", 1), ("b", 2), ("a", 3)])
+            -> fold_keyed::<'static>(|| 0, |acc: &mut i32, x| *acc += x)
+            -> null();
+
+        j = join::<'tick, 'static>() -> null();
+        source_iter([(1, "x")]) -> [0]j;
+        source_iter([(1, "y"), (2, "z")]) -> [1]j;
+
+        source_iter(0..20) -> persist::<'static>() -> null();
+    };
+    flow.enable_state_introspection(4);
+    flow.run_available().await;
+
+    let snapshot = flow.state_snapshot();
+    let mut operators = snapshot
+        .operators
+        .values()
+        .map(|op| (op.op_name, op.len, op.sample.len()))
+        .collect::<Vec<_>>();
+    operators.sort();
+    assert_eq!(
+        vec![("fold_keyed", 2, 2), ("join", 3, 3), ("persist", 20, 4)],
+        operators
+    );
+
+    let fold_keyed = snapshot
+        .operators
+        .values()
+        .find(|op| op.op_name == "fold_keyed")
+        .unwrap();
+    let mut sample = fold_keyed.sample.clone();
+    sample.sort();
+    assert_eq!(vec![r#"("a", 4)"#, r#"("b", 2)"#], sample);
+
+    flow.disable_state_introspection();
+    assert!(flow.state_snapshot().operators.is_empty());
+}
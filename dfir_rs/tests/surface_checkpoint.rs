@@ -0,0 +1,177 @@
+use dfir_rs::dfir_syntax;
+use dfir_rs::scheduled::context::DfirErased;
+use dfir_rs::util::collect_ready;
+use multiplatform_test::multiplatform_test;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+/// Counts words, and remembers every word seen, in checkpointed operators.
+fn word_count(
+    words: UnboundedReceiverStream<String>,
+    counts_send: UnboundedSender<(String, usize)>,
+    seen_send: UnboundedSender<String>,
+) -> DfirErased {
+    dfir_syntax! {
+        words = source_stream(words) -> tee();
+
+        #[checkpoint]
+        counts = words -> map(|word| (word, ())) -> fold_keyed::<'static>(|| 0, |acc: &mut usize, ()| *acc += 1);
+        counts -> for_each(|kv| counts_send.send(kv).unwrap());
+
+        #[checkpoint]
+        seen = words -> persist::<'static>();
+        seen -> for_each(|word| seen_send.send(word).unwrap());
+    }
+    .into_erased()
+}
+
+/// Tests that the state of checkpointed operators is restored into a new graph.
+#[multiplatform_test(dfir)]
+async fn test_checkpoint_restore() {
+    let (words_send, words_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let (counts_send, mut counts_recv) = dfir_rs::util::unbounded_channel::<(String, usize)>();
+    let (seen_send, mut seen_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let mut flow = word_count(words_recv, counts_send, seen_send);
+
+    for word in ["a", "b", "a"] {
+        words_send.send(word.to_owned()).unwrap();
+    }
+    flow.run_available().await;
+    collect_ready::<Vec<_>, _>(&mut counts_recv);
+    collect_ready::<Vec<_>, _>(&mut seen_recv);
+
+    let mut checkpoint = Vec::new();
+    flow.checkpoint(&mut checkpoint).unwrap();
+    drop(flow);
+
+    let (words_send, words_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let (counts_send, mut counts_recv) = dfir_rs::util::unbounded_channel::<(String, usize)>();
+    let (seen_send, mut seen_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let mut flow = word_count(words_recv, counts_send, seen_send);
+    flow.restore(&*checkpoint).unwrap();
+
+    words_send.send("b".to_owned()).unwrap();
+    flow.run_available().await;
+
+    let mut counts = collect_ready::<Vec<_>, _>(&mut counts_recv);
+    counts.sort();
+    assert_eq!(vec![("a".to_owned(), 2), ("b".to_owned(), 2)], counts);
+    assert_eq!(
+        vec!["a", "b", "a", "b"],
+        collect_ready::<Vec<_>, _>(&mut seen_recv)
+    );
+}
+
+/// Tests that a checkpoint in an unsupported format is rejected.
+#[multiplatform_test(dfir)]
+async fn test_restore_invalid() {
+    let (_words_send, words_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let (counts_send, _counts_recv) = dfir_rs::util::unbounded_channel::<(String, usize)>();
+    let (seen_send, _seen_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let mut flow = word_count(words_recv, counts_send, seen_send);
+
+    let err = flow.restore(&[0xFF; 16][..]).unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+}
+
+/// Tests that a checkpointed `fold` accumulator is restored.
+#[multiplatform_test(dfir)]
+async fn test_checkpoint_restore_fold() {
+    fn sum(nums: UnboundedReceiverStream<usize>, out_send: UnboundedSender<usize>) -> DfirErased {
+        dfir_syntax! {
+            #[checkpoint]
+            total = source_stream(nums) -> fold::<'static>(|| 0, |acc: &mut usize, x| *acc += x);
+            total -> for_each(|total| out_send.send(total).unwrap());
+        }
+        .into_erased()
+    }
+
+    let (nums_send, nums_recv) = dfir_rs::util::unbounded_channel::<usize>();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<usize>();
+    let mut flow = sum(nums_recv, out_send);
+    for num in [1, 2, 3] {
+        nums_send.send(num).unwrap();
+    }
+    flow.run_available().await;
+    assert_eq!(Some(&6), collect_ready::<Vec<_>, _>(&mut out_recv).last());
+
+    let mut checkpoint = Vec::new();
+    flow.checkpoint(&mut checkpoint).unwrap();
+    drop(flow);
+
+    let (nums_send, nums_recv) = dfir_rs::util::unbounded_channel::<usize>();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<usize>();
+    let mut flow = sum(nums_recv, out_send);
+    flow.restore(&*checkpoint).unwrap();
+    nums_send.send(10).unwrap();
+    flow.run_available().await;
+    assert_eq!(Some(&16), collect_ready::<Vec<_>, _>(&mut out_recv).last());
+}
+
+/// Tests that the build tables of a checkpointed `join` are restored, so that new input on one
+/// side matches input received on the other side before the restart.
+#[multiplatform_test(dfir)]
+async fn test_checkpoint_restore_join() {
+    fn join(
+        lhs: UnboundedReceiverStream<(char, usize)>,
+        rhs: UnboundedReceiverStream<(char, String)>,
+        out_send: UnboundedSender<(char, (usize, String))>,
+    ) -> DfirErased {
+        dfir_syntax! {
+            #[checkpoint]
+            joined = join::<'static, 'static>();
+            source_stream(lhs) -> [0]joined;
+            source_stream(rhs) -> [1]joined;
+            joined -> for_each(|kv| out_send.send(kv).unwrap());
+        }
+        .into_erased()
+    }
+
+    let (lhs_send, lhs_recv) = dfir_rs::util::unbounded_channel();
+    let (_rhs_send, rhs_recv) = dfir_rs::util::unbounded_channel();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel();
+    let mut flow = join(lhs_recv, rhs_recv, out_send);
+    lhs_send.send(('a', 1)).unwrap();
+    lhs_send.send(('b', 2)).unwrap();
+    flow.run_available().await;
+    assert!(collect_ready::<Vec<_>, _>(&mut out_recv).is_empty());
+
+    let mut checkpoint = Vec::new();
+    flow.checkpoint(&mut checkpoint).unwrap();
+    drop(flow);
+
+    let (_lhs_send, lhs_recv) = dfir_rs::util::unbounded_channel();
+    let (rhs_send, rhs_recv) = dfir_rs::util::unbounded_channel();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel();
+    let mut flow = join(lhs_recv, rhs_recv, out_send);
+    flow.restore(&*checkpoint).unwrap();
+    rhs_send.send(('b', "x".to_owned())).unwrap();
+    flow.run_available().await;
+    assert_eq!(
+        vec![('b', (2, "x".to_owned()))],
+        collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+}
+
+/// Tests that a checkpoint taken after a restore, but before the restored state is loaded by a
+/// tick, is the same as the one restored.
+#[multiplatform_test(dfir)]
+async fn test_checkpoint_before_first_tick() {
+    let (words_send, words_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let (counts_send, _counts_recv) = dfir_rs::util::unbounded_channel::<(String, usize)>();
+    let (seen_send, _seen_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let mut flow = word_count(words_recv, counts_send, seen_send);
+    words_send.send("a".to_owned()).unwrap();
+    flow.run_available().await;
+    let mut checkpoint = Vec::new();
+    flow.checkpoint(&mut checkpoint).unwrap();
+
+    let (_words_send, words_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let (counts_send, _counts_recv) = dfir_rs::util::unbounded_channel::<(String, usize)>();
+    let (seen_send, _seen_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let mut flow = word_count(words_recv, counts_send, seen_send);
+    flow.restore(&*checkpoint).unwrap();
+    let mut again = Vec::new();
+    flow.checkpoint(&mut again).unwrap();
+    assert_eq!(checkpoint, again);
+}
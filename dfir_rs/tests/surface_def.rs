@@ -0,0 +1,62 @@
+use dfir_rs::dfir_syntax;
+use dfir_rs::util::collect_ready;
+use multiplatform_test::multiplatform_test;
+
+#[multiplatform_test]
+pub fn test_def_instances() {
+    let (small_send, mut small_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let (large_send, mut large_recv) = dfir_rs::util::unbounded_channel::<u32>();
+
+    let mut df = dfir_syntax! {
+        def dedupe_scaled<P>(factor) = unique::<P>() -> map(|x: u32| x * factor);
+
+        nums = source_iter([1, 2, 2, 3, 1]) -> tee();
+        nums -> dedupe_scaled::<'tick>(1) -> for_each(|x| small_send.send(x).unwrap());
+        nums -> dedupe_scaled::<'static>(100) -> for_each(|x| large_send.send(x).unwrap());
+    };
+    df.run_available_sync();
+
+    assert_eq!(&[1, 2, 3], &*collect_ready::<Vec<_>, _>(&mut small_recv));
+    assert_eq!(
+        &[100, 200, 300],
+        &*collect_ready::<Vec<_>, _>(&mut large_recv)
+    );
+}
+
+/// Test that closure parameters shadow the parameters of a pipeline function.
+#[multiplatform_test]
+pub fn test_def_shadowed_param() {
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<u32>();
+
+    let mut df = dfir_syntax! {
+        def inc_scaled(x) = map(|x: u32| x + 1) -> map(|y: u32| {
+            let scaled = y * x;
+            match scaled {
+                x if x > 0 => x,
+                _ => 0,
+            }
+        });
+
+        source_iter([1, 2, 3]) -> inc_scaled(10) -> for_each(|x| out_send.send(x).unwrap());
+    };
+    df.run_available_sync();
+
+    assert_eq!(&[20, 30, 40], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+/// Test that pipeline functions defined inside a loop may be used before their definition.
+#[multiplatform_test]
+pub fn test_def_in_loop() {
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<u32>();
+
+    let mut df = dfir_syntax! {
+        inp = source_iter([1, 2, 3]);
+        loop {
+            inp -> batch() -> double() -> for_each(|x| out_send.send(x).unwrap());
+            def double() = map(|x: u32| x * 2);
+        };
+    };
+    df.run_available_sync();
+
+    assert_eq!(&[2, 4, 6], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
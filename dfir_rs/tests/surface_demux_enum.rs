@@ -1,6 +1,6 @@
-use dfir_rs::dfir_syntax;
 use dfir_rs::util::collect_ready;
 use dfir_rs::util::demux_enum::DemuxEnum;
+use dfir_rs::{dfir_expect_warnings, dfir_syntax};
 use multiplatform_test::multiplatform_test;
 
 #[multiplatform_test]
@@ -102,8 +102,11 @@ fn test_zero_variants() {
     #[derive(DemuxEnum)]
     enum Never {}
 
-    let mut df = dfir_syntax! {
-        source_iter(std::iter::empty::<Never>()) -> demux_enum::<Never>();
+    // No outputs are needed, so no unused output warning should be emitted.
+    let mut df = dfir_expect_warnings! {
+        {
+            source_iter(std::iter::empty::<Never>()) -> demux_enum::<Never>();
+        },
     };
     df.run_available_sync();
 }
@@ -125,3 +128,67 @@ fn test_one_variant() {
 
     assert_eq!(&["hi"], &*collect_ready::<Vec<_>, _>(out_recv));
 }
+
+#[multiplatform_test]
+fn test_mux_enum() {
+    #[derive(DemuxEnum, Debug, PartialEq)]
+    enum Shape {
+        Square(f64),
+        Rectangle { w: f64, h: f64 },
+        Circle { r: f64 },
+        Point,
+    }
+
+    let (out_send, out_recv) = dfir_rs::util::unbounded_channel();
+
+    let mut df = dfir_syntax! {
+        my_demux = source_iter([
+            Shape::Square(9.0),
+            Shape::Rectangle { w: 10.0, h: 8.0 },
+            Shape::Circle { r: 5.0 },
+            Shape::Point,
+        ]) -> demux_enum::<Shape>();
+
+        // Swap the sides of rectangles, and drop circles.
+        my_demux[Square] -> [Square]my_mux;
+        my_demux[Rectangle] -> map(|(w, h)| (h, w)) -> [Rectangle]my_mux;
+        my_demux[Circle] -> for_each(drop);
+        my_demux[Point] -> [Point]my_mux;
+
+        my_mux = mux_enum::<Shape>() -> for_each(|shape| out_send.send(shape).unwrap());
+    };
+    df.run_available_sync();
+
+    let mut shapes = collect_ready::<Vec<_>, _>(out_recv);
+    shapes.sort_by_key(|shape| format!("{:?}", shape));
+    assert_eq!(
+        vec![
+            Shape::Point,
+            Shape::Rectangle { w: 8.0, h: 10.0 },
+            Shape::Square(9.0),
+        ],
+        shapes
+    );
+}
+
+#[multiplatform_test]
+fn test_mux_enum_one_input() {
+    #[derive(DemuxEnum, Debug, PartialEq)]
+    enum Request<T> {
+        Get(T),
+    }
+
+    let (out_send, out_recv) = dfir_rs::util::unbounded_channel();
+
+    let mut df = dfir_syntax! {
+        my_mux = mux_enum::<Request<&'static str>>()
+            -> for_each(|request| out_send.send(request).unwrap());
+        source_iter([("a",), ("b",)]) -> [Get]my_mux;
+    };
+    df.run_available_sync();
+
+    assert_eq!(
+        &[Request::Get("a"), Request::Get("b")],
+        &*collect_ready::<Vec<_>, _>(out_recv)
+    );
+}
@@ -0,0 +1,69 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use dfir_rs::util::collect_ready;
+use dfir_rs::{dfir_syntax, dfir_syntax_fused};
+use multiplatform_test::multiplatform_test;
+
+#[multiplatform_test]
+pub fn test_fused_matches_unfused() {
+    let (unfused_send, mut unfused_recv) = dfir_rs::util::unbounded_channel::<(usize, u32)>();
+    let mut unfused = dfir_syntax! {
+        source_iter(0..10_u32)
+            -> map(|x| x + 1)
+            -> filter(|x| x % 2 == 0)
+            -> filter_map(|x| (x < 9).then_some(x * 3))
+            -> enumerate()
+            -> for_each(|pair| unfused_send.send(pair).unwrap());
+    };
+    unfused.run_available_sync();
+
+    let (fused_send, mut fused_recv) = dfir_rs::util::unbounded_channel::<(usize, u32)>();
+    let mut fused = dfir_syntax_fused! {
+        source_iter(0..10_u32)
+            -> map(|x| x + 1)
+            -> filter(|x| x % 2 == 0)
+            -> filter_map(|x| (x < 9).then_some(x * 3))
+            -> enumerate()
+            -> for_each(|pair| fused_send.send(pair).unwrap());
+    };
+    fused.run_available_sync();
+
+    let expected = collect_ready::<Vec<_>, _>(&mut unfused_recv);
+    assert_eq!(vec![(0, 6), (1, 12), (2, 18), (3, 24)], expected);
+    assert_eq!(expected, collect_ready::<Vec<_>, _>(&mut fused_recv));
+}
+
+#[multiplatform_test]
+pub fn test_fused_closures_evaluated_once() {
+    let made = Rc::new(Cell::new(0));
+    let make_filter = {
+        let made = Rc::clone(&made);
+        move || {
+            made.set(made.get() + 1);
+            |x: &u32| x % 2 == 0
+        }
+    };
+
+    let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel::<(u32, u32)>();
+    let mut df = dfir_syntax_fused! {
+        source_iter(0..6_u32)
+            -> filter(make_filter())
+            -> map({
+                // stateful `FnMut` closure, which must keep its state between items
+                let mut count = 0;
+                move |x| {
+                    count += 1;
+                    (count, x)
+                }
+            })
+            -> for_each(|pair| result_send.send(pair).unwrap());
+    };
+    df.run_available_sync();
+
+    assert_eq!(
+        &[(1, 0), (2, 2), (3, 4)],
+        &*collect_ready::<Vec<_>, _>(&mut result_recv)
+    );
+    assert_eq!(1, made.get());
+}
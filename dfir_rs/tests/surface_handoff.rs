@@ -1,4 +1,4 @@
-//! Tests for the `handoff()` and `singleton()` pseudo-operators.
+//! Tests for the `handoff()`, `bounded_handoff()`, and `singleton()` pseudo-operators.
 
 use dfir_rs::assert_graphvis_snapshots;
 
@@ -350,3 +350,114 @@ pub async fn test_iter_ref_multi_tick() {
     flow.run_tick().await;
     assert_eq!(Vec::<i32>::new(), *output.borrow());
 }
+
+/// Test: `bounded_handoff(capacity)` hands at most `capacity` items to its consumer each tick.
+#[dfir_rs::test]
+pub async fn test_bounded_handoff_basic() {
+    let output = std::rc::Rc::new(std::cell::RefCell::new(Vec::<i32>::new()));
+    let out = output.clone();
+    let mut flow = dfir_rs::dfir_syntax! {
+        source_iter(0..5_i32) -> bounded_handoff(2) -> for_each(|v: i32| out.borrow_mut().push(v));
+    };
+    flow.run_tick().await;
+    assert_eq!(vec![0, 1], *output.borrow());
+
+    flow.run_tick().await;
+    assert_eq!(vec![0, 1, 2, 3], *output.borrow());
+
+    // Remaining items schedule more ticks.
+    flow.run_available().await;
+    assert_eq!(vec![0, 1, 2, 3, 4], *output.borrow());
+}
+
+/// Test: `-> [bounded(capacity)]` defers its upstream subgraph while the handoff is full or has
+/// a backlog, leaving input in the source.
+#[dfir_rs::test]
+pub async fn test_bounded_handoff_backpressure() {
+    let (send, recv) = dfir_rs::util::unbounded_channel::<i32>();
+    let produced = std::rc::Rc::new(std::cell::Cell::new(0));
+    let prod = produced.clone();
+    let output = std::rc::Rc::new(std::cell::RefCell::new(Vec::<i32>::new()));
+    let out = output.clone();
+    let mut flow = dfir_rs::dfir_syntax! {
+        source_stream(recv)
+            -> inspect(|_| prod.set(prod.get() + 1))
+            -> [bounded(3)] for_each(|v: i32| out.borrow_mut().push(v));
+    };
+
+    for x in 0..5 {
+        send.send(x).unwrap();
+    }
+    flow.run_tick().await;
+    assert_eq!(5, produced.get());
+    assert_eq!(vec![0, 1, 2], *output.borrow());
+
+    // Backlog `[3, 4]` refills the handoff, which then has room: upstream runs, filling the
+    // handoff to capacity and deferring `[6, 7, 8, 9]` to the backlog.
+    for x in 5..10 {
+        send.send(x).unwrap();
+    }
+    flow.run_tick().await;
+    assert_eq!(10, produced.get());
+    assert_eq!(vec![0, 1, 2, 3, 4, 5], *output.borrow());
+
+    // Backlog refills the handoff to capacity, with `[9]` left over: upstream is deferred.
+    send.send(10).unwrap();
+    flow.run_tick().await;
+    assert_eq!(10, produced.get());
+    assert_eq!(vec![0, 1, 2, 3, 4, 5, 6, 7, 8], *output.borrow());
+
+    flow.run_available().await;
+    assert_eq!(11, produced.get());
+    assert_eq!((0..=10).collect::<Vec<_>>(), *output.borrow());
+}
+
+/// Test: a bounded handoff never hands more than `capacity` items to its consumer in a tick, even
+/// when a single run of the producer sends many more, which are held in its backlog.
+#[dfir_rs::test]
+pub async fn test_bounded_handoff_burst() {
+    let batches = std::rc::Rc::new(std::cell::RefCell::new(Vec::<Vec<i32>>::new()));
+    let batches_send = batches.clone();
+    let mut flow = dfir_rs::dfir_syntax! {
+        source_iter([0..4, 4..10])
+            -> flat_map(|range| range)
+            -> [bounded(3)] fold::<'tick>(Vec::new, |batch: &mut Vec<i32>, v| batch.push(v))
+            -> for_each(|batch| batches_send.borrow_mut().push(batch));
+    };
+    flow.run_available().await;
+
+    let batches = batches.borrow();
+    assert!(
+        batches.iter().all(|batch| batch.len() <= 3),
+        "{:?}",
+        batches
+    );
+    assert_eq!(
+        (0..10).collect::<Vec<_>>(),
+        batches.iter().flatten().copied().collect::<Vec<_>>()
+    );
+}
+
+/// Test: `-> [bounded(capacity)]` composes with an input port on the next operator.
+#[dfir_rs::test]
+pub async fn test_bounded_handoff_edge_port() {
+    let output = std::rc::Rc::new(std::cell::RefCell::new(Vec::<(i32, (char, i32))>::new()));
+    let out = output.clone();
+    let mut flow = dfir_rs::dfir_syntax! {
+        my_join = join::<'static, 'static>() -> for_each(|item| out.borrow_mut().push(item));
+        source_iter([(1, 'a'), (2, 'b')]) -> [bounded(1)][0]my_join;
+        source_iter([(1, 10), (2, 20)]) -> [1]my_join;
+    };
+    flow.run_available().await;
+
+    let output = output
+        .take()
+        .into_iter()
+        .collect::<std::collections::BTreeSet<_>>();
+    assert_eq!(
+        [(1, ('a', 10)), (2, ('b', 20))]
+            .into_iter()
+            .collect::<std::collections::BTreeSet<_>>(),
+        output
+    );
+}
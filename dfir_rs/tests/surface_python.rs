@@ -0,0 +1,60 @@
+#![cfg(feature = "python")]
+
+//! Surface syntax tests of the Python interop operators, `source_python` and `dest_python`.
+
+use dfir_rs::dfir_syntax;
+use dfir_rs::pyo3::prelude::*;
+use dfir_rs::pyo3::types::PyModule;
+use dfir_rs::util::{collect_ready, unbounded_channel};
+
+const MODULE: &str = "
+received = []
+
+def inputs():
+    yield from range(3)
+
+def record(x):
+    received.append(x * 10)
+";
+
+/// Registers [`MODULE`] under the given name, so that it can be imported by the operators.
+fn load_module(name: &str) {
+    dfir_rs::pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        PyModule::from_code_bound(py, MODULE, &format!("{name}.py"), name).unwrap();
+    });
+}
+
+#[test]
+fn test_source_python() {
+    load_module("dfir_test_source_python");
+
+    let (out_send, mut out_recv) = unbounded_channel::<i64>();
+    let mut flow = dfir_syntax! {
+        source_python("dfir_test_source_python", "inputs")
+            -> map(|item: Py<PyAny>| Python::with_gil(|py| item.extract::<i64>(py).unwrap()))
+            -> for_each(|x| out_send.send(x).unwrap());
+    };
+    flow.run_available_sync();
+
+    let out: Vec<_> = collect_ready(&mut out_recv);
+    assert_eq!(&[0, 1, 2], &*out);
+}
+
+#[test]
+fn test_dest_python() {
+    load_module("dfir_test_dest_python");
+
+    let mut flow = dfir_syntax! {
+        source_iter([1, 2, 3]) -> dest_python("dfir_test_dest_python", "record");
+    };
+    flow.run_available_sync();
+
+    let received = Python::with_gil(|py| {
+        py.import_bound("dfir_test_dest_python")
+            .and_then(|module| module.getattr("received"))
+            .and_then(|received| received.extract::<Vec<i64>>())
+            .unwrap()
+    });
+    assert_eq!(vec![10, 20, 30], received);
+}
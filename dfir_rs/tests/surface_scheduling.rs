@@ -180,6 +180,66 @@ async fn test_nospin_issue_961() {
         .expect("Should not spin.");
 }
 
+#[multiplatform_test(dfir, env_tracing)]
+async fn test_run_until() {
+    let (in_send, in_recv) = dfir_rs::util::unbounded_channel::<usize>();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<usize>();
+    let (stop_send, stop_recv) = tokio::sync::oneshot::channel::<()>();
+
+    let mut df = dfir_syntax! {
+        source_stream(in_recv) -> for_each(|v| out_send.send(v).unwrap());
+    };
+
+    in_send.send(1).unwrap();
+    in_send.send(2).unwrap();
+    stop_send.send(()).unwrap();
+
+    let progress = df.progress();
+
+    // Input which is already available when `stop` completes is still processed.
+    timeout(Duration::from_millis(100), df.run_until(stop_recv))
+        .await
+        .expect("Should stop.");
+    assert_eq!(
+        &[1, 2],
+        &*dfir_rs::util::collect_ready_async::<Vec<_>, _>(&mut out_recv).await
+    );
+    assert!(progress.ticks_completed() > 0);
+    assert!(!progress.in_tick());
+}
+
+#[multiplatform_test(test, wasm, env_tracing)]
+pub fn test_tick_deadline() {
+    let (in_send, in_recv) = dfir_rs::util::unbounded_channel::<usize>();
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<usize>();
+
+    let mut df = dfir_syntax! {
+        source_stream(in_recv) -> for_each(|v| out_send.send(v).unwrap());
+    };
+    // Every tick is past its deadline after reading its first item.
+    df.set_tick_deadline(Some(Duration::ZERO));
+    let progress = df.progress();
+
+    for v in 0..3 {
+        in_send.send(v).unwrap();
+    }
+
+    assert!(df.run_tick_sync());
+    assert_eq!(
+        &[0],
+        &*dfir_rs::util::collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+    assert_eq!(1, progress.ticks_over_deadline());
+
+    // The remaining items are carried into the following ticks.
+    df.run_available_sync();
+    assert_eq!(
+        &[1, 2],
+        &*dfir_rs::util::collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+    assert!(progress.ticks_over_deadline() >= 2);
+}
+
 // TODO(inline): intra-tick cycle (double -> items), not supported
 // #[multiplatform_test(dfir, env_tracing)]
 // async fn test_nospin_issue_961_complicated() {
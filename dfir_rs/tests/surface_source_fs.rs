@@ -0,0 +1,99 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+//! Surface syntax tests of the filesystem sources, `source_file_lines` and `source_dir_watch`.
+
+use std::io::Write;
+
+use dfir_rs::dfir_syntax;
+use dfir_rs::util::{DirEvent, collect_ready_async};
+use multiplatform_test::multiplatform_test;
+use web_time::Duration;
+
+/// Longer than the interval at which the sources check for changes.
+const WAIT: Duration = Duration::from_millis(300);
+
+#[multiplatform_test(dfir)]
+async fn test_source_file_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lines.txt");
+    std::fs::write(&path, "a\nb\r\nc").unwrap();
+
+    let (lines_send, lines_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let mut flow = dfir_syntax! {
+        source_file_lines(&path, false) -> for_each(|line| lines_send.send(line).unwrap());
+    };
+    tokio::time::timeout(WAIT, flow.run())
+        .await
+        .expect_err("Expected time out");
+
+    assert_eq!(
+        vec!["a", "b", "c"],
+        collect_ready_async::<Vec<_>, _>(lines_recv).await
+    );
+}
+
+#[multiplatform_test(dfir)]
+async fn test_source_file_lines_follow() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lines.txt");
+    std::fs::write(&path, "a\nb").unwrap();
+
+    let (lines_send, mut lines_recv) = dfir_rs::util::unbounded_channel::<String>();
+    let mut flow = dfir_syntax! {
+        source_file_lines(&path, true) -> for_each(|line| lines_send.send(line).unwrap());
+    };
+    tokio::time::timeout(WAIT, flow.run())
+        .await
+        .expect_err("Expected time out");
+    // `b` is not complete yet.
+    assert_eq!(
+        vec!["a"],
+        collect_ready_async::<Vec<_>, _>(&mut lines_recv).await
+    );
+
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(b"\nc\n").unwrap();
+    tokio::time::timeout(WAIT, flow.run())
+        .await
+        .expect_err("Expected time out");
+    assert_eq!(
+        vec!["b", "c"],
+        collect_ready_async::<Vec<_>, _>(&mut lines_recv).await
+    );
+}
+
+#[multiplatform_test(dfir)]
+async fn test_source_dir_watch() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("existing"), "").unwrap();
+
+    let (events_send, mut events_recv) = dfir_rs::util::unbounded_channel::<DirEvent>();
+    let mut flow = dfir_syntax! {
+        source_dir_watch(dir.path()) -> for_each(|event| events_send.send(event).unwrap());
+    };
+    tokio::time::timeout(WAIT, flow.run())
+        .await
+        .expect_err("Expected time out");
+    assert!(
+        collect_ready_async::<Vec<_>, _>(&mut events_recv)
+            .await
+            .is_empty()
+    );
+
+    let created = dir.path().join("created");
+    std::fs::write(&created, "").unwrap();
+    std::fs::remove_file(dir.path().join("existing")).unwrap();
+    tokio::time::timeout(WAIT, flow.run())
+        .await
+        .expect_err("Expected time out");
+    assert_eq!(
+        vec![
+            DirEvent::Created(created),
+            DirEvent::Removed(dir.path().join("existing"))
+        ],
+        collect_ready_async::<Vec<_>, _>(&mut events_recv).await
+    );
+}
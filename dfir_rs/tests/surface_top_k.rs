@@ -0,0 +1,101 @@
+use dfir_rs::dfir_syntax;
+use dfir_rs::util::collect_ready;
+use multiplatform_test::multiplatform_test;
+
+#[multiplatform_test]
+pub fn test_top_k_tick() {
+    let (items_send, items_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel::<u32>();
+
+    let mut df = dfir_syntax! {
+        source_stream(items_recv)
+            -> top_k::<'tick>(2, Ord::cmp)
+            -> for_each(|v| result_send.send(v).unwrap());
+    };
+
+    for item in [3, 9, 1, 7] {
+        items_send.send(item).unwrap();
+    }
+    df.run_tick_sync();
+    assert_eq!(&[9, 7], &*collect_ready::<Vec<_>, _>(&mut result_recv));
+
+    for item in [2, 5] {
+        items_send.send(item).unwrap();
+    }
+    df.run_tick_sync();
+    assert_eq!(&[5, 2], &*collect_ready::<Vec<_>, _>(&mut result_recv));
+}
+
+#[multiplatform_test]
+pub fn test_top_k_static() {
+    let (items_send, items_recv) = dfir_rs::util::unbounded_channel::<(char, u32)>();
+    let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel::<(char, u32)>();
+
+    let mut df = dfir_syntax! {
+        source_stream(items_recv)
+            -> top_k::<'static>(2, |(_, a): &(char, u32), (_, b): &(char, u32)| a.cmp(b))
+            -> for_each(|v| result_send.send(v).unwrap());
+    };
+
+    for item in [('a', 3), ('b', 9), ('c', 1)] {
+        items_send.send(item).unwrap();
+    }
+    df.run_tick_sync();
+    assert_eq!(
+        &[('b', 9), ('a', 3)],
+        &*collect_ready::<Vec<_>, _>(&mut result_recv)
+    );
+
+    for item in [('d', 5), ('e', 2)] {
+        items_send.send(item).unwrap();
+    }
+    df.run_tick_sync();
+    assert_eq!(
+        &[('b', 9), ('d', 5)],
+        &*collect_ready::<Vec<_>, _>(&mut result_recv)
+    );
+}
+
+/// top_k on push side: source -> tee -> top_k -> for_each
+#[multiplatform_test]
+pub fn test_top_k_push() {
+    let (out_send, mut out_recv) = dfir_rs::util::unbounded_channel::<u32>();
+    let mut df = dfir_syntax! {
+        my_tee = source_iter([4, 8, 2, 6]) -> tee();
+        my_tee -> top_k(3, Ord::cmp) -> for_each(|v| out_send.send(v).unwrap());
+        my_tee -> for_each(|_| {});
+    };
+    df.run_available_sync();
+    assert_eq!(&[8, 6, 4], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+#[multiplatform_test]
+pub fn test_heavy_hitters_static() {
+    let (items_send, items_recv) = dfir_rs::util::unbounded_channel::<&'static str>();
+    let (result_send, mut result_recv) = dfir_rs::util::unbounded_channel::<(&'static str, u64)>();
+
+    let mut df = dfir_syntax! {
+        source_stream(items_recv)
+            -> heavy_hitters::<'static>(2, 0.25)
+            -> for_each(|v| result_send.send(v).unwrap());
+    };
+
+    for item in ["a", "b", "a", "c", "a"] {
+        items_send.send(item).unwrap();
+    }
+    df.run_tick_sync();
+    assert_eq!(
+        &[("a", 3), ("b", 1)],
+        &*collect_ready::<Vec<_>, _>(&mut result_recv)
+    );
+
+    // fills the remaining counter, then evicts the least frequent item
+    for item in ["d", "b", "e", "a", "b"] {
+        items_send.send(item).unwrap();
+    }
+    df.run_tick_sync();
+    assert_eq!(
+        &[("a", 4), ("b", 3)],
+        &*collect_ready::<Vec<_>, _>(&mut result_recv)
+    );
+}
@@ -1,10 +1,15 @@
 use std::ffi::OsStr;
 use std::io::{Read, Write};
-use std::process::{Child, Stdio};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
 
 #[doc(hidden)]
 pub use ctor;
 
+mod scenario;
+pub use scenario::{Captures, Scenario};
+
 /// Environment variable used to signal that the current test binary should run the example's
 /// `main` instead of the test harness. Set to `"1"` by [`ExampleChild::run_new`].
 ///
@@ -91,26 +96,50 @@ impl<T: ExampleMainReturn, E: std::fmt::Debug> ExampleMainReturn for Result<T, E
 /// Terminates the inner [`Child`] process when dropped.
 pub struct ExampleChild {
     child: Child,
-    output_buffer: Vec<u8>,
-    output_len: usize,
+    /// All output read from the child's stdout so far.
+    output: Vec<u8>,
+    /// Chunks of the child's stdout, read by a background thread so that reads can time out.
+    /// Disconnected once the child's stdout is closed.
+    output_recv: Receiver<Vec<u8>>,
 }
 impl ExampleChild {
     pub fn run_new(args: impl IntoIterator<Item = impl AsRef<OsStr>>) -> Self {
         let current_exe = std::env::current_exe().expect("Failed to get current executable path.");
-        let mut cmd = std::process::Command::new(current_exe);
+        let mut cmd = Command::new(current_exe);
         cmd.args(args).env(RUN_MAIN_ENV_VAR, "1");
 
         log::info!("Re-executing test binary as example: {:?}", cmd);
+        Self::spawn(cmd)
+    }
 
-        let child = cmd
+    /// Spawns `cmd` with piped stdin and stdout, for processes other than the current example.
+    pub fn spawn(mut cmd: Command) -> Self {
+        let mut child = cmd
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .spawn()
             .unwrap();
+
+        let mut stdout = child.stdout.take().unwrap();
+        let (output_send, output_recv) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buffer = [0; 1024];
+            loop {
+                match stdout.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(bytes_read) => {
+                        if output_send.send(buffer[..bytes_read].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
         Self {
             child,
-            output_buffer: vec![0; 1024],
-            output_len: 0,
+            output: Vec::new(),
+            output_recv,
         }
     }
 
@@ -127,52 +156,136 @@ impl ExampleChild {
 
     /// Waits for a specific regex process output before returning.
     pub fn read_regex(&mut self, wait_for_regex: &str) {
-        let stdout = self.child.stdout.as_mut().unwrap();
+        if let Err(err) = self.try_read_regex(wait_for_regex, None) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Waits for a specific regex process output before returning, panicking if it does not
+    /// appear within `timeout`.
+    pub fn read_regex_timeout(&mut self, wait_for_regex: &str, timeout: Duration) {
+        if let Err(err) = self.try_read_regex(wait_for_regex, Some(timeout)) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Waits for a specific regex process output, returning an error message (including the output
+    /// so far) if the child exits or `timeout` elapses before a match is found.
+    ///
+    /// On success, returns the capture groups of the first match.
+    fn try_read_regex(
+        &mut self,
+        wait_for_regex: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Option<String>>, String> {
         let re = regex::Regex::new(wait_for_regex).unwrap();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
 
-        while !re.is_match(&String::from_utf8_lossy(
-            &self.output_buffer[0..self.output_len],
-        )) {
-            eprintln!(
-                "waiting ({}):\n{}",
-                wait_for_regex,
-                String::from_utf8_lossy(&self.output_buffer[0..self.output_len])
-            );
-
-            while self.output_buffer.len() - self.output_len < 1024 {
-                self.output_buffer
-                    .resize(self.output_buffer.len() + 1024, 0);
+        loop {
+            if let Some(captures) = re.captures(&self.output()) {
+                return Ok(captures
+                    .iter()
+                    .map(|group| group.map(|group| group.as_str().to_owned()))
+                    .collect());
             }
 
-            let bytes_read = stdout
-                .read(&mut self.output_buffer[self.output_len..])
-                .unwrap();
-            self.output_len += bytes_read;
+            eprintln!("waiting ({}):\n{}", wait_for_regex, self.output());
 
-            if 0 == bytes_read {
-                panic!("Child process exited before a match was found.");
+            let chunk = match deadline {
+                None => self
+                    .output_recv
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+                Some(deadline) => self
+                    .output_recv
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            };
+            match chunk {
+                Ok(chunk) => self.output.extend_from_slice(&chunk),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(format!(
+                        "Child process exited before a match was found for `{}`, output:\n{}",
+                        wait_for_regex,
+                        self.output()
+                    ));
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!(
+                        "Timed out after {:?} waiting for a match for `{}`, output:\n{}",
+                        timeout.unwrap(),
+                        wait_for_regex,
+                        self.output()
+                    ));
+                }
             }
         }
     }
 
+    /// All output read from the child process's stdout so far.
+    pub fn output(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+
     /// Reads the child process's stdout until the child exits, returning the complete output
     /// (including any output already consumed by [`Self::read_string`] or [`Self::read_regex`]).
     ///
     /// Panics if the child exits with a non-success exit status.
     pub fn read_to_end(&mut self) -> String {
-        let stdout = self.child.stdout.as_mut().unwrap();
-        self.output_buffer.truncate(self.output_len);
-        stdout.read_to_end(&mut self.output_buffer).unwrap();
-        self.output_len = self.output_buffer.len();
-
-        let status = self.child.wait().unwrap();
-        assert!(
-            status.success(),
-            "Child process exited unsuccessfully: {}",
-            status
-        );
-
-        String::from_utf8_lossy(&self.output_buffer[..self.output_len]).into_owned()
+        match self.try_read_to_end(None) {
+            Ok(output) => output,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Reads the child process's stdout until the child exits, returning an error message
+    /// (including the output so far) if it exits unsuccessfully or `timeout` elapses first.
+    fn try_read_to_end(&mut self, timeout: Option<Duration>) -> Result<String, String> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let timed_out = |output: String| {
+            format!(
+                "Timed out after {:?} waiting for the child process to exit, output:\n{}",
+                timeout.unwrap(),
+                output
+            )
+        };
+
+        loop {
+            let chunk = match deadline {
+                None => self
+                    .output_recv
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+                Some(deadline) => self
+                    .output_recv
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            };
+            match chunk {
+                Ok(chunk) => self.output.extend_from_slice(&chunk),
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => return Err(timed_out(self.output())),
+            }
+        }
+
+        // The child may close its stdout before exiting.
+        let status = loop {
+            match self.child.try_wait().unwrap() {
+                Some(status) => break status,
+                None if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                    return Err(timed_out(self.output()));
+                }
+                None if deadline.is_none() => break self.child.wait().unwrap(),
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        if !status.success() {
+            return Err(format!(
+                "Child process exited unsuccessfully: {}, output:\n{}",
+                status,
+                self.output()
+            ));
+        }
+
+        Ok(self.output())
     }
 
     /// Writes a line to the child process stdin. A newline is automatically appended and should not be included in `line`.
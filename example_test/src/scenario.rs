@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::ops::Index;
+use std::time::Duration;
+
+use crate::ExampleChild;
+
+/// Default for [`Scenario::timeout`].
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The values captured by [`Scenario::capture`] so far, indexed by name.
+#[derive(Debug, Default)]
+pub struct Captures(HashMap<String, String>);
+
+impl Index<&str> for Captures {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        self.0
+            .get(name)
+            .unwrap_or_else(|| panic!("Nothing has been captured as `{}`.", name))
+    }
+}
+
+/// A single step of a [`Scenario`].
+enum Step<'a> {
+    Spawn {
+        role: String,
+        spawn: Box<dyn FnOnce(&Captures) -> ExampleChild + 'a>,
+    },
+    WaitFor {
+        role: String,
+        regex: Box<dyn FnOnce(&Captures) -> String + 'a>,
+        capture: Option<String>,
+    },
+    WriteLine {
+        role: String,
+        line: String,
+    },
+    Exit {
+        role: String,
+        check: Box<dyn FnOnce(&str) + 'a>,
+    },
+}
+
+/// A scripted interaction between multiple example processes, each identified by a role name.
+///
+/// Steps are declared up front with the builder methods, and then executed in order by
+/// [`Self::run`]. Every wait for output, including for a process to exit, is bounded by
+/// [`Self::timeout`], and a failing step panics with the name of the role and its output so far.
+/// All the processes are terminated when the scenario finishes.
+///
+/// To run in parallel with other tests, processes should listen on port 0 and print the address
+/// they were given, which later steps can [capture](Self::capture) rather than racing to reserve
+/// a free port up front.
+///
+/// ```rust,ignore
+/// Scenario::new()
+///     .spawn("server", |_| run_current_example!("--role server --address 127.0.0.1:0"))
+///     .capture("server", r"Server is live! Listening on (\S+)", "server")
+///     .spawn("client", |captures| {
+///         run_current_example!(["--role", "client", "--server", &captures["server"]])
+///     })
+///     .wait_for("client", "Client is live!")
+///     .write_line("client", "PUT a,7")
+///     .wait_for("server", r#"Message received PutRequest { key: "a", value: "7" }"#)
+///     .run();
+/// ```
+pub struct Scenario<'a> {
+    steps: Vec<Step<'a>>,
+    timeout: Duration,
+}
+
+impl Default for Scenario<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Scenario<'a> {
+    pub fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets how long each wait may take before the scenario fails. Defaults to 60 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Spawns a process for `role`, typically with
+    /// [`run_current_example!`](crate::run_current_example), given the values captured so far.
+    pub fn spawn(
+        mut self,
+        role: impl Into<String>,
+        spawn: impl FnOnce(&Captures) -> ExampleChild + 'a,
+    ) -> Self {
+        self.steps.push(Step::Spawn {
+            role: role.into(),
+            spawn: Box::new(spawn),
+        });
+        self
+    }
+
+    /// Waits for the output of `role` to contain the string `wait_for`.
+    pub fn wait_for(self, role: impl Into<String>, wait_for: &str) -> Self {
+        self.wait_for_regex(role, &regex::escape(wait_for))
+    }
+
+    /// Waits for the output of `role` to match the regex `wait_for`.
+    pub fn wait_for_regex(mut self, role: impl Into<String>, wait_for: &str) -> Self {
+        let wait_for = wait_for.to_owned();
+        self.steps.push(Step::WaitFor {
+            role: role.into(),
+            regex: Box::new(move |_| wait_for),
+            capture: None,
+        });
+        self
+    }
+
+    /// Waits for the output of `role` to contain the string returned by `wait_for`, given the
+    /// values captured so far.
+    pub fn wait_for_captured(
+        self,
+        role: impl Into<String>,
+        wait_for: impl FnOnce(&Captures) -> String + 'a,
+    ) -> Self {
+        self.wait_for_regex_captured(role, move |captures| regex::escape(&wait_for(captures)))
+    }
+
+    /// Waits for the output of `role` to match the regex returned by `wait_for`, given the values
+    /// captured so far.
+    pub fn wait_for_regex_captured(
+        mut self,
+        role: impl Into<String>,
+        wait_for: impl FnOnce(&Captures) -> String + 'a,
+    ) -> Self {
+        self.steps.push(Step::WaitFor {
+            role: role.into(),
+            regex: Box::new(wait_for),
+            capture: None,
+        });
+        self
+    }
+
+    /// Waits for the output of `role` to match the regex `wait_for`, and captures its first group
+    /// as `name` for later steps.
+    pub fn capture(
+        mut self,
+        role: impl Into<String>,
+        wait_for: &str,
+        name: impl Into<String>,
+    ) -> Self {
+        let wait_for = wait_for.to_owned();
+        self.steps.push(Step::WaitFor {
+            role: role.into(),
+            regex: Box::new(move |_| wait_for),
+            capture: Some(name.into()),
+        });
+        self
+    }
+
+    /// Writes a line to the stdin of `role`. A newline is automatically appended.
+    pub fn write_line(mut self, role: impl Into<String>, line: impl Into<String>) -> Self {
+        self.steps.push(Step::WriteLine {
+            role: role.into(),
+            line: line.into(),
+        });
+        self
+    }
+
+    /// Waits for `role` to exit successfully, then calls `check` with its complete output.
+    pub fn expect_exit(mut self, role: impl Into<String>, check: impl FnOnce(&str) + 'a) -> Self {
+        self.steps.push(Step::Exit {
+            role: role.into(),
+            check: Box::new(check),
+        });
+        self
+    }
+
+    /// Runs the steps in order, panicking if any of them fails.
+    pub fn run(self) {
+        fn child<'c>(
+            children: &'c mut HashMap<String, ExampleChild>,
+            role: &str,
+        ) -> &'c mut ExampleChild {
+            children
+                .get_mut(role)
+                .unwrap_or_else(|| panic!("Role `{}` has not been spawned.", role))
+        }
+
+        let mut children = HashMap::<String, ExampleChild>::new();
+        let mut captures = Captures::default();
+        for step in self.steps {
+            match step {
+                Step::Spawn { role, spawn } => {
+                    let previous = children.insert(role.clone(), spawn(&captures));
+                    assert!(previous.is_none(), "Role `{}` was spawned twice.", role);
+                }
+                Step::WaitFor {
+                    role,
+                    regex,
+                    capture,
+                } => {
+                    let regex = regex(&captures);
+                    match child(&mut children, &role).try_read_regex(&regex, Some(self.timeout)) {
+                        Ok(groups) => {
+                            if let Some(name) = capture {
+                                let value =
+                                    groups.into_iter().nth(1).flatten().unwrap_or_else(|| {
+                                        panic!("`{}` has no capture group for `{}`.", regex, name)
+                                    });
+                                captures.0.insert(name, value);
+                            }
+                        }
+                        Err(err) => panic!("[{}] {}", role, err),
+                    }
+                }
+                Step::WriteLine { role, line } => {
+                    child(&mut children, &role).write_line(&line);
+                }
+                Step::Exit { role, check } => {
+                    let mut exited = children
+                        .remove(&role)
+                        .unwrap_or_else(|| panic!("Role `{}` has not been spawned.", role));
+                    match exited.try_read_to_end(Some(self.timeout)) {
+                        Ok(output) => check(&output),
+                        Err(err) => panic!("[{}] {}", role, err),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use std::process::Command;
+    use std::time::Duration;
+
+    use super::Scenario;
+    use crate::ExampleChild;
+
+    fn sh(script: &str) -> ExampleChild {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script]);
+        ExampleChild::spawn(cmd)
+    }
+
+    #[test]
+    fn captures_and_writes_lines() {
+        Scenario::new()
+            .spawn("server", |_| {
+                sh(r#"echo "Listening on 127.0.0.1:4321"; read line; echo "Got $line""#)
+            })
+            .capture("server", r"Listening on (\S+)", "address")
+            .spawn("client", |captures| {
+                sh(&format!("echo 'Talking to {}'", &captures["address"]))
+            })
+            .wait_for_captured("client", |captures| {
+                format!("Talking to {}", &captures["address"])
+            })
+            .write_line("server", "hello")
+            .wait_for("server", "Got hello")
+            .expect_exit("server", |output| {
+                assert_eq!(output, "Listening on 127.0.0.1:4321\nGot hello\n");
+            })
+            .run();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "[server] Timed out after 200ms waiting for the child process to exit"
+    )]
+    fn expect_exit_times_out() {
+        Scenario::new()
+            .timeout(Duration::from_millis(200))
+            .spawn("server", |_| sh("sleep 30"))
+            .expect_exit("server", |_| {})
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "[server] Child process exited unsuccessfully")]
+    fn expect_exit_checks_status() {
+        Scenario::new()
+            .spawn("server", |_| sh("exit 3"))
+            .expect_exit("server", |_| {})
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "[server] Child process exited before a match was found")]
+    fn wait_for_fails_on_exit() {
+        Scenario::new()
+            .spawn("server", |_| sh("echo starting"))
+            .wait_for("server", "Server is live!")
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "Nothing has been captured as `address`.")]
+    fn missing_capture() {
+        Scenario::new()
+            .spawn("client", |captures| {
+                sh(&format!("echo {}", &captures["address"]))
+            })
+            .run();
+    }
+}
@@ -1,6 +1,7 @@
 use std::any::Any;
 use std::fmt::Debug;
-use std::sync::{Arc, Mutex, OnceLock};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
 
 use anyhow::Result;
 use nanoid::nanoid;
@@ -9,13 +10,16 @@ use serde_json::json;
 use super::terraform::{TERRAFORM_ALPHABET, TerraformOutput, TerraformProvider};
 use super::{ClientStrategy, Host, HostTargetType, LaunchedHost, ResourceBatch, ResourceResult};
 use crate::ssh::LaunchedSshHost;
-use crate::{BaseServerStrategy, HostStrategyGetter, PortNetworkHint};
+use crate::systemd::SystemdOptions;
+use crate::{BaseServerStrategy, HostLocation, HostStrategyGetter, IpStack, PortNetworkHint};
 
 pub struct LaunchedEc2Instance {
     resource_result: Arc<ResourceResult>,
     user: String,
+    systemd: Option<SystemdOptions>,
     pub internal_ip: String,
     pub external_ip: Option<String>,
+    pub external_ipv6: Option<String>,
 }
 
 impl LaunchedSshHost for LaunchedEc2Instance {
@@ -23,6 +27,10 @@ impl LaunchedSshHost for LaunchedEc2Instance {
         self.external_ip.as_deref()
     }
 
+    fn get_external_ipv6(&self) -> Option<&str> {
+        self.external_ipv6.as_deref()
+    }
+
     fn get_internal_ip(&self) -> &str {
         &self.internal_ip
     }
@@ -38,6 +46,10 @@ impl LaunchedSshHost for LaunchedEc2Instance {
     fn ssh_user(&self) -> &str {
         self.user.as_str()
     }
+
+    fn systemd(&self) -> Option<&SystemdOptions> {
+        self.systemd.as_ref()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -52,19 +64,131 @@ pub struct AwsNetwork {
     pub region: String,
     pub existing_network_key: OnceLock<NetworkResources>,
     pub existing_network_id: OnceLock<NetworkResources>,
+    pub ip_stack: IpStack,
+    /// The IPv4 block of a VPC created by Hydro Deploy.
+    cidr_block: String,
+    /// Whether this network is peered with other peering networks, see [`AwsNetwork::peered`].
+    peering: bool,
+    /// The networks this one has been peered with so that their hosts can connect.
+    peers: Mutex<Vec<Weak<AwsNetwork>>>,
     id: String,
 }
 
 impl AwsNetwork {
     pub fn new(region: impl Into<String>, existing_vpc: Option<NetworkResources>) -> Arc<Self> {
+        Self::with_ip_stack(region, existing_vpc, IpStack::V4)
+    }
+
+    /// Like [`AwsNetwork::new`], but with hosts reachable over the IP versions of `ip_stack`.
+    ///
+    /// With [`IpStack::DualStack`], a VPC created by Hydro Deploy is given an IPv6 block, which
+    /// its subnet assigns addresses from. An existing subnet must already assign IPv6 addresses,
+    /// and route IPv6 traffic to an internet gateway.
+    pub fn with_ip_stack(
+        region: impl Into<String>,
+        existing_vpc: Option<NetworkResources>,
+        ip_stack: IpStack,
+    ) -> Arc<Self> {
         Arc::new(Self {
             region: region.into(),
             existing_network_key: OnceLock::new(),
             existing_network_id: existing_vpc.map(From::from).unwrap_or_default(),
+            ip_stack,
+            cidr_block: "10.0.0.0/16".to_owned(),
+            peering: false,
+            peers: Mutex::new(Vec::new()),
             id: nanoid!(8, &TERRAFORM_ALPHABET),
         })
     }
 
+    /// A new VPC with the IPv4 block `cidr_block` (a `/16`, such as `"10.1.0.0/16"`), which is
+    /// peered with other networks created by this method in the same region when their hosts
+    /// need to connect, so that they can use internal IPs instead of public ones.
+    ///
+    /// The blocks of networks that are peered must not overlap.
+    pub fn peered(region: impl Into<String>, cidr_block: impl Into<String>) -> Arc<Self> {
+        Arc::new(Self {
+            region: region.into(),
+            existing_network_key: OnceLock::new(),
+            existing_network_id: OnceLock::new(),
+            ip_stack: IpStack::V4,
+            cidr_block: cidr_block.into(),
+            peering: true,
+            peers: Mutex::new(Vec::new()),
+            id: nanoid!(8, &TERRAFORM_ALPHABET),
+        })
+    }
+
+    /// An identifier of the VPC, which is the same for every [`AwsNetwork`] of an existing VPC.
+    fn vpc_id(&self) -> String {
+        self.existing_network_id
+            .get()
+            .map_or_else(|| self.id.clone(), |existing| existing.vpc.clone())
+    }
+
+    /// Whether hosts in this network and `other` can connect through a peering connection.
+    fn can_peer(&self, other: &AwsNetwork) -> bool {
+        self.peering
+            && other.peering
+            && self.region == other.region
+            && self.existing_network_id.get().is_none()
+            && other.existing_network_id.get().is_none()
+            && self.id != other.id
+    }
+
+    /// Peers this network with `other`, which the next resource batch will set up.
+    fn peer_with(self: &Arc<Self>, other: &Arc<Self>) {
+        for (network, peer) in [(self, other), (other, self)] {
+            let mut peers = network.peers.lock().unwrap();
+            if !peers
+                .iter()
+                .any(|existing| existing.upgrade().is_some_and(|p| p.id == peer.id))
+            {
+                peers.push(Arc::downgrade(peer));
+            }
+        }
+    }
+
+    fn vpc_key(&self, resource_batch: &mut ResourceBatch) -> String {
+        format!(
+            "hydro-vpc-network-{}",
+            resource_batch.resource_id("aws-network", &self.id)
+        )
+    }
+
+    /// Adds the peering connection between this network and `peer` to the batch, returning its
+    /// key, which is the same from either side.
+    fn collect_peering(&self, peer: &AwsNetwork, resource_batch: &mut ResourceBatch) -> String {
+        let (first, second) = if self.id < peer.id {
+            (self, peer)
+        } else {
+            (peer, self)
+        };
+        let first_vpc = first.vpc_key(resource_batch);
+        let second_vpc = second.vpc_key(resource_batch);
+        let key = format!(
+            "hydro-vpc-peering-{}",
+            resource_batch.resource_id("aws-peering", &format!("{}-{}", first.id, second.id))
+        );
+        resource_batch
+            .terraform
+            .resource
+            .entry("aws_vpc_peering_connection".to_owned())
+            .or_default()
+            .insert(
+                key.clone(),
+                json!({
+                    "vpc_id": format!("${{aws_vpc.{first_vpc}.id}}"),
+                    "peer_vpc_id": format!("${{aws_vpc.{second_vpc}.id}}"),
+                    "auto_accept": true,
+                    "tags": {
+                        "Name": key
+                    }
+                }),
+            );
+        key
+    }
+
     fn collect_resources(&self, resource_batch: &mut ResourceBatch) -> NetworkResources {
         resource_batch
             .terraform
@@ -85,7 +209,7 @@ impl AwsNetwork {
             }),
         );
 
-        let vpc_network = format!("hydro-vpc-network-{}", self.id);
+        let vpc_network = self.vpc_key(resource_batch);
         let subnet_key = format!("{vpc_network}-subnet");
         let sg_key = format!("{vpc_network}-default-sg");
 
@@ -112,6 +236,17 @@ impl AwsNetwork {
                 security_group: format!("aws_security_group.{}", existing.security_group),
             }
         } else {
+            let dual_stack = self.ip_stack == IpStack::DualStack;
+            let peers = self
+                .peers
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(Weak::upgrade)
+                .collect::<Vec<_>>();
+            let internal_cidr_blocks = std::iter::once(self.cidr_block.clone())
+                .chain(peers.iter().map(|peer| peer.cidr_block.clone()))
+                .collect::<Vec<_>>();
             resource_batch
                 .terraform
                 .resource
@@ -120,7 +255,8 @@ impl AwsNetwork {
                 .insert(
                     vpc_network.clone(),
                     json!({
-                        "cidr_block": "10.0.0.0/16",
+                        "cidr_block": self.cidr_block,
+                        "assign_generated_ipv6_cidr_block": dual_stack,
                         "enable_dns_hostnames": true,
                         "enable_dns_support": true,
                         "tags": {
@@ -147,23 +283,27 @@ impl AwsNetwork {
                 );
 
             // Create subnet
+            let mut subnet = json!({
+                "vpc_id": format!("${{aws_vpc.{}.id}}", vpc_network),
+                "cidr_block": format!("${{cidrsubnet(aws_vpc.{vpc_network}.cidr_block, 8, 1)}}"),
+                "availability_zone": format!("{}a", self.region),
+                "map_public_ip_on_launch": true,
+                "tags": {
+                    "Name": subnet_key
+                }
+            });
+            if dual_stack {
+                // A /64 out of the /56 block of the VPC.
+                subnet["ipv6_cidr_block"] =
+                    format!("${{cidrsubnet(aws_vpc.{vpc_network}.ipv6_cidr_block, 8, 1)}}").into();
+                subnet["assign_ipv6_address_on_creation"] = true.into();
+            }
             resource_batch
                 .terraform
                 .resource
                 .entry("aws_subnet".to_owned())
                 .or_default()
-                .insert(
-                    subnet_key.clone(),
-                    json!({
-                        "vpc_id": format!("${{aws_vpc.{}.id}}", vpc_network),
-                        "cidr_block": "10.0.1.0/24",
-                        "availability_zone": format!("{}a", self.region),
-                        "map_public_ip_on_launch": true,
-                        "tags": {
-                            "Name": subnet_key
-                        }
-                    }),
-                );
+                .insert(subnet_key.clone(), subnet);
 
             // Create route table
             let rt_key = format!("{vpc_network}-rt");
@@ -196,6 +336,37 @@ impl AwsNetwork {
                         "gateway_id": format!("${{aws_internet_gateway.{}.id}}", igw_key)
                     }),
                 );
+            for peer in &peers {
+                let peering_key = self.collect_peering(peer, resource_batch);
+                resource_batch
+                    .terraform
+                    .resource
+                    .entry("aws_route".to_owned())
+                    .or_default()
+                    .insert(
+                        format!("{vpc_network}-route-{peering_key}"),
+                        json!({
+                            "route_table_id": format!("${{aws_route_table.{}.id}}", rt_key),
+                            "destination_cidr_block": peer.cidr_block,
+                            "vpc_peering_connection_id": format!("${{aws_vpc_peering_connection.{peering_key}.id}}")
+                        }),
+                    );
+            }
+            if dual_stack {
+                resource_batch
+                    .terraform
+                    .resource
+                    .entry("aws_route".to_owned())
+                    .or_default()
+                    .insert(
+                        format!("{vpc_network}-route-ipv6"),
+                        json!({
+                            "route_table_id": format!("${{aws_route_table.{}.id}}", rt_key),
+                            "destination_ipv6_cidr_block": "::/0",
+                            "gateway_id": format!("${{aws_internet_gateway.{}.id}}", igw_key)
+                        }),
+                    );
+            }
 
             resource_batch
                 .terraform
@@ -210,6 +381,8 @@ impl AwsNetwork {
                     }),
                 );
 
+            let ipv6_any = if dual_stack { vec!["::/0"] } else { vec![] };
+
             // Create security group that allows internal communication
             resource_batch
                 .terraform
@@ -227,7 +400,7 @@ impl AwsNetwork {
                                 "from_port": 0,
                                 "to_port": 65535,
                                 "protocol": "tcp",
-                                "cidr_blocks": ["10.0.0.0/16"],
+                                "cidr_blocks": internal_cidr_blocks,
                                 "description": "Allow all TCP traffic within VPC",
                                 "ipv6_cidr_blocks": [],
                                 "prefix_list_ids": [],
@@ -238,7 +411,7 @@ impl AwsNetwork {
                                 "from_port": 0,
                                 "to_port": 65535,
                                 "protocol": "udp",
-                                "cidr_blocks": ["10.0.0.0/16"],
+                                "cidr_blocks": internal_cidr_blocks,
                                 "description": "Allow all UDP traffic within VPC",
                                 "ipv6_cidr_blocks": [],
                                 "prefix_list_ids": [],
@@ -249,7 +422,7 @@ impl AwsNetwork {
                                 "from_port": -1,
                                 "to_port": -1,
                                 "protocol": "icmp",
-                                "cidr_blocks": ["10.0.0.0/16"],
+                                "cidr_blocks": internal_cidr_blocks,
                                 "description": "Allow ICMP within VPC",
                                 "ipv6_cidr_blocks": [],
                                 "prefix_list_ids": [],
@@ -264,7 +437,7 @@ impl AwsNetwork {
                                 "protocol": "-1",
                                 "cidr_blocks": ["0.0.0.0/0"],
                                 "description": "Allow all outbound traffic",
-                                "ipv6_cidr_blocks": [],
+                                "ipv6_cidr_blocks": ipv6_any,
                                 "prefix_list_ids": [],
                                 "security_groups": [],
                                 "self": false
@@ -383,7 +556,8 @@ impl AwsEc2IamInstanceProfile {
             }),
         );
 
-        let instance_profile_key = format!("hydro-instance-profile-{}", self.id);
+        let id = resource_batch.resource_id("aws-instance-profile", &self.id);
+        let instance_profile_key = format!("hydro-instance-profile-{}", id);
 
         if let Some(existing) = self.existing_instance_profile_key_or_name.as_ref() {
             if resource_batch
@@ -421,7 +595,7 @@ impl AwsEc2IamInstanceProfile {
                 .insert(
                     iam_role_key.clone(),
                     json!({
-                        "name": format!("hydro-iam-role-{}", self.id),
+                        "name": format!("hydro-iam-role-{}", id),
                         "assume_role_policy": json!({
                             "Version": "2012-10-17",
                             "Statement": [
@@ -463,7 +637,7 @@ impl AwsEc2IamInstanceProfile {
                 .insert(
                     instance_profile_key.clone(),
                     json!({
-                        "name": format!("hydro-instance-profile-{}", self.id),
+                        "name": format!("hydro-instance-profile-{}", id),
                         "role": format!("${{{RESOURCE_AWS_IAM_ROLE}.{iam_role_key}.name}}"),
                     }),
                 );
@@ -520,7 +694,8 @@ impl AwsCloudwatchLogGroup {
             }),
         );
 
-        let cloudwatch_log_group_key = format!("hydro-cloudwatch-log-group-{}", self.id);
+        let id = resource_batch.resource_id("aws-cloudwatch-log-group", &self.id);
+        let cloudwatch_log_group_key = format!("hydro-cloudwatch-log-group-{}", id);
 
         if let Some(existing) = self.existing_cloudwatch_log_group_key_or_name.as_ref() {
             if resource_batch
@@ -557,7 +732,7 @@ impl AwsCloudwatchLogGroup {
                 .insert(
                     cloudwatch_log_group_key.clone(),
                     json!({
-                        "name": format!("hydro-cloudwatch-log-group-{}", self.id),
+                        "name": format!("hydro-cloudwatch-log-group-{}", id),
                         "retention_in_days": 1,
                     }),
                 );
@@ -584,8 +759,10 @@ pub struct AwsEc2Host {
     cwa_metrics_collected: Option<serde_json::Value>,
     user: Option<String>,
     display_name: Option<String>,
+    systemd: Option<SystemdOptions>,
     pub launched: OnceLock<Arc<LaunchedEc2Instance>>,
     external_ports: Mutex<Vec<u16>>,
+    external_port_ranges: Mutex<Vec<RangeInclusive<u16>>>,
 }
 
 impl Debug for AwsEc2Host {
@@ -611,6 +788,7 @@ impl AwsEc2Host {
         cwa_metrics_collected: Option<serde_json::Value>,
         user: Option<String>,
         display_name: Option<String>,
+        systemd: Option<SystemdOptions>,
     ) -> Self {
         Self {
             id,
@@ -624,10 +802,19 @@ impl AwsEc2Host {
             cwa_metrics_collected,
             user,
             display_name,
+            systemd,
             launched: OnceLock::new(),
             external_ports: Mutex::new(Vec::new()),
+            external_port_ranges: Mutex::new(Vec::new()),
         }
     }
+
+    /// Whether this host and `other` are in the same VPC, which may be shared by several
+    /// [`AwsNetwork`]s of the same existing VPC.
+    fn same_network(&self, other: &AwsEc2Host) -> bool {
+        let (location, other_location) = (self.location().unwrap(), other.location().unwrap());
+        location.same_region(&other_location) && location.same_network(&other_location)
+    }
 }
 
 impl Host for AwsEc2Host {
@@ -651,6 +838,21 @@ impl Host for AwsEc2Host {
         }
     }
 
+    fn request_port_range(&self, ports: RangeInclusive<u16>) -> Result<()> {
+        let mut external_port_ranges = self.external_port_ranges.lock().unwrap();
+        if !external_port_ranges.contains(&ports) {
+            if self.launched.get().is_some() {
+                anyhow::bail!(
+                    "Cannot open ports {}-{} after host has been launched, as the security group cannot be adjusted",
+                    ports.start(),
+                    ports.end(),
+                );
+            }
+            external_port_ranges.push(ports);
+        }
+        Ok(())
+    }
+
     fn request_custom_binary(&self) {
         self.request_port_base(&BaseServerStrategy::ExternalTcpPort(22));
     }
@@ -730,6 +932,7 @@ impl Host for AwsEc2Host {
                 }),
             );
 
+        let key_pair_id = resource_batch.resource_id("aws-key-pair", "");
         resource_batch
             .terraform
             .resource
@@ -738,13 +941,16 @@ impl Host for AwsEc2Host {
             .insert(
                 "ec2_key_pair".to_owned(),
                 json!({
-                    "key_name": format!("hydro-key-{}", nanoid!(8, &TERRAFORM_ALPHABET)),
+                    "key_name": format!("hydro-key-{}", key_pair_id),
                     "public_key": "${tls_private_key.vm_instance_ssh_key.public_key_openssh}"
                 }),
             );
 
         let instance_key = format!("ec2-instance-{}", self.id);
-        let mut instance_name = format!("hydro-ec2-instance-{}", nanoid!(8, &TERRAFORM_ALPHABET));
+        let mut instance_name = format!(
+            "hydro-ec2-instance-{}",
+            resource_batch.resource_id("aws-ec2-instance", &self.id.to_string())
+        );
 
         if let Some(mut display_name) = self.display_name.clone() {
             instance_name.push('-');
@@ -763,9 +969,13 @@ impl Host for AwsEc2Host {
         // Create additional security group for external ports if needed
         let mut security_groups = vec![default_sg_ref];
         let external_ports = self.external_ports.lock().unwrap();
+        let external_port_ranges = self.external_port_ranges.lock().unwrap();
 
-        if !external_ports.is_empty() {
+        let dual_stack = self.network.ip_stack == IpStack::DualStack;
+        let ipv6_any = if dual_stack { vec!["::/0"] } else { vec![] };
+        if !external_ports.is_empty() || !external_port_ranges.is_empty() {
             let sg_key = format!("sg-{}", self.id);
+            let sg_id = resource_batch.resource_id("aws-security-group", &self.id.to_string());
             let mut sg_rules = vec![];
 
             for port in external_ports.iter() {
@@ -775,7 +985,20 @@ impl Host for AwsEc2Host {
                     "protocol": "tcp",
                     "cidr_blocks": ["0.0.0.0/0"],
                     "description": format!("External port {}", port),
-                    "ipv6_cidr_blocks": [],
+                    "ipv6_cidr_blocks": ipv6_any,
+                    "prefix_list_ids": [],
+                    "security_groups": [],
+                    "self": false
+                }));
+            }
+            for range in external_port_ranges.iter() {
+                sg_rules.push(json!({
+                    "from_port": range.start(),
+                    "to_port": range.end(),
+                    "protocol": "tcp",
+                    "cidr_blocks": ["0.0.0.0/0"],
+                    "description": format!("External ports {}-{}", range.start(), range.end()),
+                    "ipv6_cidr_blocks": ipv6_any,
                     "prefix_list_ids": [],
                     "security_groups": [],
                     "self": false
@@ -790,7 +1013,7 @@ impl Host for AwsEc2Host {
                 .insert(
                     sg_key.clone(),
                     json!({
-                        "name": format!("hydro-sg-{}", nanoid!(8, &TERRAFORM_ALPHABET)),
+                        "name": format!("hydro-sg-{}", sg_id),
                         "description": "Hydro external ports security group",
                         "vpc_id": vpc_ref,
                         "ingress": sg_rules,
@@ -800,7 +1023,7 @@ impl Host for AwsEc2Host {
                             "protocol": "-1",
                             "cidr_blocks": ["0.0.0.0/0"],
                             "description": "All outbound traffic",
-                            "ipv6_cidr_blocks": [],
+                            "ipv6_cidr_blocks": ipv6_any,
                             "prefix_list_ids": [],
                             "security_groups": [],
                             "self": false
@@ -811,6 +1034,7 @@ impl Host for AwsEc2Host {
             security_groups.push(format!("${{aws_security_group.{}.id}}", sg_key));
         }
         drop(external_ports);
+        drop(external_port_ranges);
 
         let subnet_ref = format!("${{{}.id}}", network_resources.subnet);
         let iam_instance_profile_ref = iam_instance_profile.map(|key| format!("${{{key}.name}}"));
@@ -908,6 +1132,7 @@ echo -e "{cwa_config_esc}" > /opt/aws/amazon-cloudwatch-agent/etc/amazon-cloudwa
                     "vpc_security_group_ids": security_groups,
                     "subnet_id": subnet_ref,
                     "associate_public_ip_address": true,
+                    "ipv6_address_count": if dual_stack { Some(1) } else { None },
                     "iam_instance_profile": iam_instance_profile_ref, // May be `None`.
                     "user_data": user_data_script, // May be `None`.
                     "tags": {
@@ -929,6 +1154,22 @@ echo -e "{cwa_config_esc}" > /opt/aws/amazon-cloudwatch-agent/etc/amazon-cloudwa
                 value: format!("${{aws_instance.{}.public_ip}}", instance_key),
             },
         );
+
+        if dual_stack {
+            resource_batch.terraform.output.insert(
+                format!("{}-public-ipv6", instance_key),
+                TerraformOutput {
+                    value: format!("${{aws_instance.{}.ipv6_addresses[0]}}", instance_key),
+                },
+            );
+        }
+
+        resource_batch.terraform.output.insert(
+            format!("{}-id", instance_key),
+            TerraformOutput {
+                value: format!("${{aws_instance.{}.id}}", instance_key),
+            },
+        );
     }
 
     fn launched(&self) -> Option<Arc<dyn LaunchedHost>> {
@@ -937,6 +1178,65 @@ echo -e "{cwa_config_esc}" > /opt/aws/amazon-cloudwatch-agent/etc/amazon-cloudwa
             .map(|a| a.clone() as Arc<dyn LaunchedHost>)
     }
 
+    fn collect_image_resources(
+        &self,
+        image_name: &str,
+        resource_batch: &mut ResourceBatch,
+    ) -> Result<String> {
+        let Some(launched) = self.launched.get() else {
+            anyhow::bail!("Cannot bake an image from an EC2 instance which has not been launched");
+        };
+        let instance_id = &launched
+            .resource_result
+            .terraform
+            .outputs
+            .get(&format!("ec2-instance-{}-id", self.id))
+            .unwrap()
+            .value;
+
+        resource_batch
+            .terraform
+            .terraform
+            .required_providers
+            .insert(
+                "aws".to_owned(),
+                TerraformProvider {
+                    source: "hashicorp/aws".to_owned(),
+                    version: "5.0.0".to_owned(),
+                },
+            );
+        resource_batch.terraform.provider.insert(
+            "aws".to_owned(),
+            json!({
+                "region": self.region
+            }),
+        );
+
+        // The instance is rebooted while the AMI is created, so that its file system is consistent.
+        let image_key = format!("ami-{}", self.id);
+        resource_batch
+            .terraform
+            .resource
+            .entry("aws_ami_from_instance".to_owned())
+            .or_default()
+            .insert(
+                image_key.clone(),
+                json!({
+                    "name": image_name,
+                    "source_instance_id": instance_id,
+                }),
+            );
+
+        let output = format!("{image_key}-id");
+        resource_batch.terraform.output.insert(
+            output.clone(),
+            TerraformOutput {
+                value: format!("${{aws_ami_from_instance.{image_key}.id}}"),
+            },
+        );
+        Ok(output)
+    }
+
     fn provision(&self, resource_result: &Arc<ResourceResult>) -> Arc<dyn LaunchedHost> {
         self.launched
             .get_or_init(|| {
@@ -957,16 +1257,39 @@ echo -e "{cwa_config_esc}" > /opt/aws/amazon-cloudwatch-agent/etc/amazon-cloudwa
                     .get(&format!("ec2-instance-{id}-public-ip"))
                     .map(|v| v.value.clone());
 
+                let external_ipv6 = resource_result
+                    .terraform
+                    .outputs
+                    .get(&format!("ec2-instance-{id}-public-ipv6"))
+                    .map(|v| v.value.clone());
+
                 Arc::new(LaunchedEc2Instance {
                     resource_result: resource_result.clone(),
                     user: self.user.clone().unwrap_or_else(|| "ec2-user".to_owned()),
+                    systemd: self.systemd.clone(),
                     internal_ip,
                     external_ip,
+                    external_ipv6,
                 })
             })
             .clone()
     }
 
+    fn location(&self) -> Option<HostLocation> {
+        Some(HostLocation {
+            cloud: "aws",
+            region: self.region.clone(),
+            // The subnet of a network created by Hydro Deploy is in the first zone of the region.
+            zone: self
+                .network
+                .existing_network_id
+                .get()
+                .is_none()
+                .then(|| format!("{}a", self.network.region)),
+            network: self.network.vpc_id(),
+        })
+    }
+
     fn strategy_as_server<'a>(
         &'a self,
         client_host: &dyn Host,
@@ -984,15 +1307,45 @@ echo -e "{cwa_config_esc}" > /opt/aws/amazon-cloudwatch-agent/etc/amazon-cloudwa
             PortNetworkHint::Auto | PortNetworkHint::TcpPort(_)
         ) && client_host.can_connect_to(ClientStrategy::InternalTcpPort(self))
         {
+            // Hosts in different networks that can connect internally do so through a peering
+            // connection, which is set up along with the networks.
+            let peer_network = <dyn Any>::downcast_ref::<AwsEc2Host>(client_host)
+                .filter(|client| !client.same_network(self))
+                .map(|client| client.network.clone());
             Ok((
                 ClientStrategy::InternalTcpPort(self),
-                Box::new(move |_| {
+                Box::new(move |me| {
+                    if let Some(peer_network) = peer_network {
+                        me.downcast_ref::<AwsEc2Host>()
+                            .unwrap()
+                            .network
+                            .peer_with(&peer_network);
+                    }
                     BaseServerStrategy::InternalTcpPort(match network_hint {
                         PortNetworkHint::Auto => None,
                         PortNetworkHint::TcpPort(port) => port,
                     })
                 }),
             ))
+        } else if matches!(
+            network_hint,
+            PortNetworkHint::Auto | PortNetworkHint::TcpPort(_)
+        ) && client_host.can_connect_to(ClientStrategy::ExternalTcpPort(self))
+        {
+            Ok((
+                ClientStrategy::ExternalTcpPort(self),
+                Box::new(move |me| {
+                    let me = me.downcast_ref::<AwsEc2Host>().unwrap();
+                    let port = match network_hint {
+                        PortNetworkHint::TcpPort(Some(port)) => port,
+                        _ => {
+                            crate::next_external_port(&me.external_ports, &me.external_port_ranges)
+                        }
+                    };
+                    me.request_port_base(&BaseServerStrategy::ExternalTcpPort(port));
+                    BaseServerStrategy::ExternalTcpPort(port)
+                }),
+            ))
         } else if matches!(network_hint, PortNetworkHint::Auto)
             && client_host.can_connect_to(ClientStrategy::ForwardedTcpPort(self))
         {
@@ -1026,13 +1379,155 @@ echo -e "{cwa_config_esc}" > /opt/aws/amazon-cloudwatch-agent/etc/amazon-cloudwa
             }
             ClientStrategy::InternalTcpPort(target_host) => {
                 if let Some(aws_target) = <dyn Any>::downcast_ref::<AwsEc2Host>(target_host) {
-                    self.region == aws_target.region
-                        && Arc::ptr_eq(&self.network, &aws_target.network)
+                    self.same_network(aws_target)
+                        || (self.launched.get().is_none()
+                            && aws_target.launched.get().is_none()
+                            && self.network.can_peer(&aws_target.network))
                 } else {
                     false
                 }
             }
             ClientStrategy::ForwardedTcpPort(_) => false,
+            // Any cloud host can reach the public IP of another.
+            ClientStrategy::ExternalTcpPort(target_host) => target_host.location().is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LinuxCompileType;
+
+    fn host(id: usize, region: &str, network: &Arc<AwsNetwork>) -> AwsEc2Host {
+        AwsEc2Host::new(
+            id,
+            region,
+            "t3.micro",
+            HostTargetType::Linux(LinuxCompileType::Glibc),
+            "ami-0",
+            network.clone(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn existing_vpc() -> NetworkResources {
+        NetworkResources {
+            vpc: "vpc-0".to_owned(),
+            subnet: "subnet-0".to_owned(),
+            security_group: "sg-0".to_owned(),
         }
     }
+
+    /// The resources of `resource_type` in the batch, as a JSON object keyed by name.
+    fn resources(resource_batch: &ResourceBatch, resource_type: &str) -> serde_json::Value {
+        serde_json::to_value(&resource_batch.terraform.resource[resource_type]).unwrap()
+    }
+
+    #[test]
+    fn networks_of_the_same_existing_vpc_connect_internally() {
+        let a = host(
+            0,
+            "us-east-1",
+            &AwsNetwork::new("us-east-1", Some(existing_vpc())),
+        );
+        let b = host(
+            1,
+            "us-east-1",
+            &AwsNetwork::new("us-east-1", Some(existing_vpc())),
+        );
+
+        let (strategy, _) = b.strategy_as_server(&a, PortNetworkHint::Auto).unwrap();
+        assert!(matches!(strategy, ClientStrategy::InternalTcpPort(_)));
+        // The zone of the subnet of an existing VPC is not known.
+        assert_eq!(a.location().unwrap().zone, None);
+    }
+
+    #[test]
+    fn different_networks_connect_through_public_ips() {
+        let a = host(0, "us-east-1", &AwsNetwork::new("us-east-1", None));
+        let b = host(1, "us-east-1", &AwsNetwork::new("us-east-1", None));
+
+        let (strategy, getter) = b.strategy_as_server(&a, PortNetworkHint::Auto).unwrap();
+        assert!(matches!(strategy, ClientStrategy::ExternalTcpPort(_)));
+        assert!(matches!(
+            getter(&b),
+            BaseServerStrategy::ExternalTcpPort(port) if b.external_ports.lock().unwrap().contains(&port)
+        ));
+    }
+
+    #[test]
+    fn peered_networks_connect_internally() {
+        let a = host(
+            0,
+            "us-east-1",
+            &AwsNetwork::peered("us-east-1", "10.1.0.0/16"),
+        );
+        let b = host(
+            1,
+            "us-east-1",
+            &AwsNetwork::peered("us-east-1", "10.2.0.0/16"),
+        );
+        assert!(a.location().unwrap().same_zone(&b.location().unwrap()));
+
+        let (strategy, getter) = b.strategy_as_server(&a, PortNetworkHint::Auto).unwrap();
+        assert!(matches!(strategy, ClientStrategy::InternalTcpPort(_)));
+        getter(&b);
+
+        let mut resource_batch = ResourceBatch::new();
+        a.network.collect_resources(&mut resource_batch);
+        b.network.collect_resources(&mut resource_batch);
+
+        let peerings = resources(&resource_batch, "aws_vpc_peering_connection");
+        assert_eq!(peerings.as_object().unwrap().len(), 1);
+        let peering_key = peerings.as_object().unwrap().keys().next().unwrap();
+
+        let peering_routes = resources(&resource_batch, "aws_route")
+            .as_object()
+            .unwrap()
+            .values()
+            .filter_map(|route| route.get("vpc_peering_connection_id"))
+            .map(|id| id.as_str().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            peering_routes,
+            vec![format!("${{aws_vpc_peering_connection.{peering_key}.id}}"); 2]
+        );
+
+        let security_groups = resources(&resource_batch, "aws_security_group");
+        for security_group in security_groups.as_object().unwrap().values() {
+            let mut cidr_blocks = security_group["ingress"][0]["cidr_blocks"]
+                .as_array()
+                .unwrap()
+                .clone();
+            cidr_blocks.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+            assert_eq!(
+                cidr_blocks,
+                vec![json!("10.1.0.0/16"), json!("10.2.0.0/16")]
+            );
+        }
+    }
+
+    #[test]
+    fn networks_in_different_regions_are_not_peered() {
+        let a = host(
+            0,
+            "us-east-1",
+            &AwsNetwork::peered("us-east-1", "10.1.0.0/16"),
+        );
+        let b = host(
+            1,
+            "us-west-2",
+            &AwsNetwork::peered("us-west-2", "10.2.0.0/16"),
+        );
+        assert!(!a.location().unwrap().same_region(&b.location().unwrap()));
+
+        let (strategy, _) = b.strategy_as_server(&a, PortNetworkHint::Auto).unwrap();
+        assert!(matches!(strategy, ClientStrategy::ExternalTcpPort(_)));
+    }
 }
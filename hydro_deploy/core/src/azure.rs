@@ -1,22 +1,25 @@
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::ops::RangeInclusive;
 use std::sync::{Arc, Mutex, OnceLock};
 
 use anyhow::Result;
-use nanoid::nanoid;
 use serde_json::json;
 
-use super::terraform::{TERRAFORM_ALPHABET, TerraformOutput, TerraformProvider};
+use super::terraform::{TerraformOutput, TerraformProvider};
 use super::{ClientStrategy, Host, HostTargetType, LaunchedHost, ResourceBatch, ResourceResult};
 use crate::ssh::LaunchedSshHost;
-use crate::{BaseServerStrategy, HostStrategyGetter, PortNetworkHint};
+use crate::systemd::SystemdOptions;
+use crate::{BaseServerStrategy, HostLocation, HostStrategyGetter, IpStack, PortNetworkHint};
 
 pub struct LaunchedVirtualMachine {
     resource_result: Arc<ResourceResult>,
     user: String,
+    systemd: Option<SystemdOptions>,
     pub internal_ip: String,
     pub external_ip: Option<String>,
+    pub external_ipv6: Option<String>,
 }
 
 impl LaunchedSshHost for LaunchedVirtualMachine {
@@ -24,6 +27,10 @@ impl LaunchedSshHost for LaunchedVirtualMachine {
         self.external_ip.as_deref()
     }
 
+    fn get_external_ipv6(&self) -> Option<&str> {
+        self.external_ipv6.as_deref()
+    }
+
     fn get_internal_ip(&self) -> &str {
         &self.internal_ip
     }
@@ -39,6 +46,10 @@ impl LaunchedSshHost for LaunchedVirtualMachine {
     fn ssh_user(&self) -> &str {
         self.user.as_str()
     }
+
+    fn systemd(&self) -> Option<&SystemdOptions> {
+        self.systemd.as_ref()
+    }
 }
 
 pub struct AzureHost {
@@ -52,8 +63,12 @@ pub struct AzureHost {
     target_type: HostTargetType,
     region: String,
     user: Option<String>,
+    display_name: Option<String>,
+    systemd: Option<SystemdOptions>,
+    ip_stack: IpStack,
     pub launched: OnceLock<Arc<LaunchedVirtualMachine>>, // TODO(mingwei): fix pub
     external_ports: Mutex<Vec<u16>>,
+    external_port_ranges: Mutex<Vec<RangeInclusive<u16>>>,
 }
 
 impl AzureHost {
@@ -67,6 +82,9 @@ impl AzureHost {
         target_type: HostTargetType,
         region: String,
         user: Option<String>,
+        display_name: Option<String>,
+        systemd: Option<SystemdOptions>,
+        ip_stack: IpStack,
     ) -> Self {
         Self {
             id,
@@ -77,15 +95,22 @@ impl AzureHost {
             target_type,
             region,
             user,
+            display_name,
+            systemd,
+            ip_stack,
             launched: OnceLock::new(),
             external_ports: Mutex::new(Vec::new()),
+            external_port_ranges: Mutex::new(Vec::new()),
         }
     }
 }
 
 impl Debug for AzureHost {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("AzureHost({})", self.id))
+        f.write_fmt(format_args!(
+            "AzureHost({} ({:?}))",
+            self.id, &self.display_name
+        ))
     }
 }
 
@@ -110,6 +135,21 @@ impl Host for AzureHost {
         }
     }
 
+    fn request_port_range(&self, ports: RangeInclusive<u16>) -> Result<()> {
+        let mut external_port_ranges = self.external_port_ranges.lock().unwrap();
+        if !external_port_ranges.contains(&ports) {
+            if self.launched.get().is_some() {
+                anyhow::bail!(
+                    "Cannot open ports {}-{} after host has been launched, as the firewall cannot be adjusted",
+                    ports.start(),
+                    ports.end(),
+                );
+            }
+            external_port_ranges.push(ports);
+        }
+        Ok(())
+    }
+
     fn request_custom_binary(&self) {
         self.request_port_base(&BaseServerStrategy::ExternalTcpPort(22));
     }
@@ -191,7 +231,22 @@ impl Host for AzureHost {
             );
 
         let vm_key = format!("vm-instance-{}", self.id);
-        let vm_name = format!("hydro-vm-instance-{}", nanoid!(8, &TERRAFORM_ALPHABET));
+        let mut vm_name = format!(
+            "hydro-vm-instance-{}",
+            resource_batch.resource_id("azure-vm-instance", &self.id.to_string())
+        );
+        // Linux VM names can have at most 64 characters, keep the latter half of display_name
+        if let Some(mut display_name) = self.display_name.clone() {
+            vm_name.push('-');
+            display_name = display_name
+                .replace("_", "-")
+                .replace(":", "-")
+                .to_lowercase();
+
+            let num_chars_to_cut = (vm_name.len() + display_name.len()).saturating_sub(63);
+            display_name.drain(0..num_chars_to_cut);
+            vm_name.push_str(&display_name);
+        }
 
         // Handle provider configuration
         resource_batch.terraform.provider.insert(
@@ -216,6 +271,15 @@ impl Host for AzureHost {
                 }),
             );
 
+        // Dual-stack networks are given a private (unique local) IPv6 range as well.
+        let dual_stack = self.ip_stack == IpStack::DualStack;
+        let mut address_space = vec!["10.0.0.0/16"];
+        let mut address_prefixes = vec!["10.0.2.0/24"];
+        if dual_stack {
+            address_space.push("fd00:db8:deca::/48");
+            address_prefixes.push("fd00:db8:deca:daed::/64");
+        }
+
         resource_batch
             .terraform
             .resource
@@ -225,7 +289,7 @@ impl Host for AzureHost {
                 vm_key.to_string(),
                 json!({
                     "name": format!("{vm_key}-network"),
-                    "address_space": ["10.0.0.0/16"],
+                    "address_space": address_space,
                     "location": self.region.clone(),
                     "resource_group_name": format!("${{azurerm_resource_group.{vm_key}.name}}")
                 }),
@@ -242,7 +306,7 @@ impl Host for AzureHost {
                     "name": "internal",
                     "resource_group_name": format!("${{azurerm_resource_group.{vm_key}.name}}"),
                     "virtual_network_name": format!("${{azurerm_virtual_network.{vm_key}.name}}"),
-                    "address_prefixes": ["10.0.2.0/24"]
+                    "address_prefixes": address_prefixes
                 }),
             );
 
@@ -258,9 +322,52 @@ impl Host for AzureHost {
                     "resource_group_name": format!("${{azurerm_resource_group.{vm_key}.name}}"),
                     "location": format!("${{azurerm_resource_group.{vm_key}.location}}"),
                     "allocation_method": "Static",
+                    // Basic SKU public IPs are retired, and IPv6 public IPs must be standard.
+                    "sku": "Standard",
                 }),
             );
 
+        let mut ip_configurations = vec![json!({
+            "name": "internal",
+            "subnet_id": format!("${{azurerm_subnet.{vm_key}.id}}"),
+            "private_ip_address_allocation": "Dynamic",
+            "public_ip_address_id": format!("${{azurerm_public_ip.{vm_key}.id}}"),
+            "primary": true,
+        })];
+        if dual_stack {
+            let ipv6_key = format!("{vm_key}-ipv6");
+            resource_batch
+                .terraform
+                .resource
+                .entry("azurerm_public_ip".to_owned())
+                .or_default()
+                .insert(
+                    ipv6_key.clone(),
+                    json!({
+                        "name": "hydropubipv6",
+                        "resource_group_name": format!("${{azurerm_resource_group.{vm_key}.name}}"),
+                        "location": format!("${{azurerm_resource_group.{vm_key}.location}}"),
+                        "allocation_method": "Static",
+                        "sku": "Standard",
+                        "ip_version": "IPv6",
+                    }),
+                );
+            ip_configurations.push(json!({
+                "name": "internal-ipv6",
+                "subnet_id": format!("${{azurerm_subnet.{vm_key}.id}}"),
+                "private_ip_address_allocation": "Dynamic",
+                "private_ip_address_version": "IPv6",
+                "public_ip_address_id": format!("${{azurerm_public_ip.{ipv6_key}.id}}"),
+            }));
+
+            resource_batch.terraform.output.insert(
+                format!("{vm_key}-public-ipv6"),
+                TerraformOutput {
+                    value: format!("${{azurerm_public_ip.{ipv6_key}.ip_address}}"),
+                },
+            );
+        }
+
         resource_batch
             .terraform
             .resource
@@ -272,12 +379,7 @@ impl Host for AzureHost {
                     "name": format!("{vm_key}-nic"),
                     "location": format!("${{azurerm_resource_group.{vm_key}.location}}"),
                     "resource_group_name": format!("${{azurerm_resource_group.{vm_key}.name}}"),
-                    "ip_configuration": {
-                        "name": "internal",
-                        "subnet_id": format!("${{azurerm_subnet.{vm_key}.id}}"),
-                        "private_ip_address_allocation": "Dynamic",
-                        "public_ip_address_id": format!("${{azurerm_public_ip.{vm_key}.id}}"),
-                    }
+                    "ip_configuration": ip_configurations
                 }),
             );
 
@@ -409,16 +511,33 @@ impl Host for AzureHost {
                     .get(&format!("vm-instance-{id}-public-ip"))
                     .map(|v| v.value.clone());
 
+                let external_ipv6 = resource_result
+                    .terraform
+                    .outputs
+                    .get(&format!("vm-instance-{id}-public-ipv6"))
+                    .map(|v| v.value.clone());
+
                 Arc::new(LaunchedVirtualMachine {
                     resource_result: resource_result.clone(),
                     user: self.user.as_ref().cloned().unwrap_or("hydro".to_owned()),
+                    systemd: self.systemd.clone(),
                     internal_ip,
                     external_ip,
+                    external_ipv6,
                 })
             })
             .clone()
     }
 
+    fn location(&self) -> Option<HostLocation> {
+        Some(HostLocation {
+            cloud: "azure",
+            region: self.region.clone(),
+            zone: None,
+            network: self.project.clone(),
+        })
+    }
+
     fn strategy_as_server<'a>(
         &'a self,
         client_host: &dyn Host,
@@ -445,6 +564,25 @@ impl Host for AzureHost {
                     })
                 }),
             ))
+        } else if matches!(
+            network_hint,
+            PortNetworkHint::Auto | PortNetworkHint::TcpPort(_)
+        ) && client_host.can_connect_to(ClientStrategy::ExternalTcpPort(self))
+        {
+            Ok((
+                ClientStrategy::ExternalTcpPort(self),
+                Box::new(move |me| {
+                    let me = me.downcast_ref::<AzureHost>().unwrap();
+                    let port = match network_hint {
+                        PortNetworkHint::TcpPort(Some(port)) => port,
+                        _ => {
+                            crate::next_external_port(&me.external_ports, &me.external_port_ranges)
+                        }
+                    };
+                    me.request_port_base(&BaseServerStrategy::ExternalTcpPort(port));
+                    BaseServerStrategy::ExternalTcpPort(port)
+                }),
+            ))
         } else if matches!(network_hint, PortNetworkHint::Auto)
             && client_host.can_connect_to(ClientStrategy::ForwardedTcpPort(self))
         {
@@ -484,6 +622,8 @@ impl Host for AzureHost {
                 }
             }
             ClientStrategy::ForwardedTcpPort(_) => false,
+            // Any cloud host can reach the public IP of another.
+            ClientStrategy::ExternalTcpPort(target_host) => target_host.location().is_some(),
         }
     }
 }
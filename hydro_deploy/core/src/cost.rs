@@ -0,0 +1,279 @@
+//! Estimating the cost of the cloud instances in a deployment before they are provisioned.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::terraform::TerraformBatch;
+
+/// Hours in an average month, used to derive monthly costs from hourly prices.
+const HOURS_PER_MONTH: f64 = 730.0;
+
+/// Approximate on-demand prices (USD per hour) of common instance types, in `us-east` regions.
+const BUNDLED_PRICES: &[(CloudProvider, &str, f64)] = &[
+    (CloudProvider::Aws, "t3.nano", 0.0052),
+    (CloudProvider::Aws, "t3.micro", 0.0104),
+    (CloudProvider::Aws, "t3.small", 0.0208),
+    (CloudProvider::Aws, "t3.medium", 0.0416),
+    (CloudProvider::Aws, "t3.large", 0.0832),
+    (CloudProvider::Aws, "t3.xlarge", 0.1664),
+    (CloudProvider::Aws, "m5.large", 0.096),
+    (CloudProvider::Aws, "m5.xlarge", 0.192),
+    (CloudProvider::Aws, "m5.2xlarge", 0.384),
+    (CloudProvider::Aws, "c5.large", 0.085),
+    (CloudProvider::Aws, "c5.xlarge", 0.17),
+    (CloudProvider::Aws, "c5.2xlarge", 0.34),
+    (CloudProvider::Aws, "c5.4xlarge", 0.68),
+    (CloudProvider::Gcp, "e2-micro", 0.0084),
+    (CloudProvider::Gcp, "e2-small", 0.0168),
+    (CloudProvider::Gcp, "e2-medium", 0.0335),
+    (CloudProvider::Gcp, "e2-standard-2", 0.067),
+    (CloudProvider::Gcp, "e2-standard-4", 0.134),
+    (CloudProvider::Gcp, "n2-standard-2", 0.0971),
+    (CloudProvider::Gcp, "n2-standard-4", 0.1942),
+    (CloudProvider::Gcp, "n2-standard-8", 0.3885),
+    (CloudProvider::Gcp, "c2-standard-4", 0.2088),
+    (CloudProvider::Gcp, "c2-standard-8", 0.4176),
+    (CloudProvider::Azure, "Standard_B1s", 0.0104),
+    (CloudProvider::Azure, "Standard_B2s", 0.0416),
+    (CloudProvider::Azure, "Standard_D2s_v3", 0.096),
+    (CloudProvider::Azure, "Standard_D4s_v3", 0.192),
+    (CloudProvider::Azure, "Standard_D8s_v3", 0.384),
+    (CloudProvider::Azure, "Standard_F2s_v2", 0.0846),
+    (CloudProvider::Azure, "Standard_F4s_v2", 0.169),
+];
+
+/// A cloud provider whose instances are priced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudProvider {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+impl CloudProvider {
+    /// The provider and instance type of a Terraform resource, if it is a priced instance.
+    fn instance_type<'a>(
+        resource_type: &str,
+        resource: &'a serde_json::Value,
+    ) -> Option<(Self, &'a str)> {
+        let (provider, field) = match resource_type {
+            "aws_instance" => (CloudProvider::Aws, "instance_type"),
+            "google_compute_instance" => (CloudProvider::Gcp, "machine_type"),
+            "azurerm_linux_virtual_machine" | "azurerm_windows_virtual_machine" => {
+                (CloudProvider::Azure, "size")
+            }
+            _ => return None,
+        };
+        Some((provider, resource.get(field)?.as_str()?))
+    }
+}
+
+/// Hourly prices (in USD) of instance types, by provider.
+///
+/// Starts from a bundled table of approximate on-demand prices for common instance types, which
+/// can be extended or corrected with [`Self::load_overrides`].
+#[derive(Clone, Debug)]
+pub struct PriceTable {
+    prices: HashMap<(CloudProvider, String), f64>,
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        Self {
+            prices: BUNDLED_PRICES
+                .iter()
+                .map(|&(provider, instance_type, price)| {
+                    ((provider, instance_type.to_owned()), price)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl PriceTable {
+    /// Sets the hourly price of an instance type, replacing any existing price.
+    pub fn set_price(
+        &mut self,
+        provider: CloudProvider,
+        instance_type: impl Into<String>,
+        hourly_usd: f64,
+    ) {
+        self.prices
+            .insert((provider, instance_type.into()), hourly_usd);
+    }
+
+    /// Reads prices from a JSON file, which replace the existing prices. The file maps each
+    /// provider (`aws`, `gcp`, or `azure`) to the hourly prices of instance types, for example
+    /// `{ "aws": { "t3.micro": 0.0104 } }`.
+    pub fn load_overrides(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read price overrides `{}`.", path.display()))?;
+        let overrides: HashMap<CloudProvider, HashMap<String, f64>> =
+            serde_json::from_str(&contents).with_context(|| {
+                format!("Failed to parse price overrides `{}`.", path.display())
+            })?;
+        for (provider, prices) in overrides {
+            for (instance_type, price) in prices {
+                self.set_price(provider, instance_type, price);
+            }
+        }
+        Ok(())
+    }
+
+    /// The hourly price of an instance type, if known.
+    pub fn price(&self, provider: CloudProvider, instance_type: &str) -> Option<f64> {
+        self.prices
+            .get(&(provider, instance_type.to_owned()))
+            .copied()
+    }
+
+    /// Estimates the cost of the instances planned in a Terraform batch.
+    #[expect(
+        clippy::disallowed_methods,
+        reason = "nondeterministic iteration order, will be sorted"
+    )]
+    pub(crate) fn estimate(&self, batch: &TerraformBatch) -> CostEstimate {
+        let mut instances = batch
+            .resource
+            .iter()
+            .flat_map(|(resource_type, resources)| {
+                resources.iter().filter_map(move |(name, resource)| {
+                    let (provider, instance_type) =
+                        CloudProvider::instance_type(resource_type, resource)?;
+                    Some(InstanceCost {
+                        address: format!("{resource_type}.{name}"),
+                        provider,
+                        instance_type: instance_type.to_owned(),
+                        hourly_usd: self.price(provider, instance_type),
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        instances.sort_by(|a, b| a.address.cmp(&b.address));
+        CostEstimate { instances }
+    }
+}
+
+/// The estimated cost of a single planned instance.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct InstanceCost {
+    /// The Terraform address of the instance, e.g. `aws_instance.vm-instance-abc123`.
+    pub address: String,
+    pub provider: CloudProvider,
+    pub instance_type: String,
+    /// The hourly price of the instance, or `None` if its instance type is not in the
+    /// [`PriceTable`].
+    pub hourly_usd: Option<f64>,
+}
+
+/// The estimated cost of the cloud instances planned for a deployment, returned by
+/// [`Deployment::plan`](crate::Deployment::plan).
+///
+/// Only compute instances are priced; networking, storage, and data transfer are not included.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CostEstimate {
+    /// The planned instances, sorted by address.
+    pub instances: Vec<InstanceCost>,
+}
+
+impl CostEstimate {
+    /// The total hourly cost (in USD) of the instances with known prices.
+    pub fn hourly_usd(&self) -> f64 {
+        self.instances
+            .iter()
+            .filter_map(|instance| instance.hourly_usd)
+            .sum()
+    }
+
+    /// The total monthly cost (in USD) of the instances with known prices, assuming they run for
+    /// the entire month.
+    pub fn monthly_usd(&self) -> f64 {
+        self.hourly_usd() * HOURS_PER_MONTH
+    }
+
+    /// The instance types of the planned instances which are missing from the [`PriceTable`].
+    pub fn unpriced(&self) -> impl Iterator<Item = &InstanceCost> {
+        self.instances
+            .iter()
+            .filter(|instance| instance.hourly_usd.is_none())
+    }
+}
+
+impl std::fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Estimated cost of {} instance(s): ${:.2}/hour, ${:.2}/month",
+            self.instances.len(),
+            self.hourly_usd(),
+            self.monthly_usd(),
+        )?;
+        let mut unpriced = self
+            .unpriced()
+            .map(|instance| instance.instance_type.as_str())
+            .collect::<Vec<_>>();
+        unpriced.sort_unstable();
+        unpriced.dedup();
+        if !unpriced.is_empty() {
+            write!(f, " (excluding unpriced {})", unpriced.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn estimates_planned_instances() {
+        let mut batch = TerraformBatch::default();
+        batch.resource.insert(
+            "aws_instance".to_owned(),
+            HashMap::from([
+                ("a".to_owned(), json!({ "instance_type": "t3.micro" })),
+                ("b".to_owned(), json!({ "instance_type": "x9.huge" })),
+            ]),
+        );
+        batch.resource.insert(
+            "google_compute_instance".to_owned(),
+            HashMap::from([("c".to_owned(), json!({ "machine_type": "e2-micro" }))]),
+        );
+        batch.resource.insert(
+            "aws_vpc".to_owned(),
+            HashMap::from([("d".to_owned(), json!({}))]),
+        );
+
+        let mut prices = PriceTable::default();
+        prices.set_price(CloudProvider::Gcp, "e2-micro", 0.01);
+        let estimate = prices.estimate(&batch);
+
+        assert_eq!(
+            vec![
+                "aws_instance.a",
+                "aws_instance.b",
+                "google_compute_instance.c"
+            ],
+            estimate
+                .instances
+                .iter()
+                .map(|instance| instance.address.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!((estimate.hourly_usd() - 0.0204).abs() < 1e-9);
+        assert_eq!(
+            vec!["x9.huge"],
+            estimate
+                .unpriced()
+                .map(|instance| instance.instance_type.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+}
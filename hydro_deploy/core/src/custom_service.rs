@@ -17,7 +17,7 @@ use crate::{
 
 /// Represents an unknown, third-party service that is not part of the Hydroflow ecosystem.
 pub struct CustomService {
-    _id: usize,
+    id: usize,
     on: Arc<dyn Host>,
 
     /// The ports that the service wishes to expose to the public internet.
@@ -29,7 +29,7 @@ pub struct CustomService {
 impl CustomService {
     pub fn new(id: usize, on: Arc<dyn Host>, external_ports: Vec<u16>) -> Self {
         Self {
-            _id: id,
+            id,
             on,
             external_ports,
             launched_host: OnceLock::new(),
@@ -47,6 +47,14 @@ impl CustomService {
 
 #[async_trait]
 impl Service for CustomService {
+    fn display_id(&self) -> String {
+        format!("custom/{}", self.id)
+    }
+
+    fn host(&self) -> Option<Arc<dyn Host>> {
+        Some(self.on.clone())
+    }
+
     fn collect_resources(&self, _resource_batch: &mut ResourceBatch) {
         if self.launched_host.get().is_some() {
             return;
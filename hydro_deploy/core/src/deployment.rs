@@ -3,18 +3,31 @@
     reason = "https://github.com/BrynCooke/buildstructor/issues/200"
 )]
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, Weak};
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use futures::{FutureExt, StreamExt, TryStreamExt};
+use hydro_deploy_integration::integrity::FrameConfig;
 
 use crate::aws::{AwsCloudwatchLogGroup, AwsEc2IamInstanceProfile, AwsNetwork};
+use crate::cost::{CostEstimate, PriceTable};
 use crate::gcp::GcpNetwork;
+use crate::hooks::{self, HookContext, HookEvent, Hooks, HostInfo};
+use crate::image::{self, ImageRecipe};
+use crate::logs::{self, LogDestination, ServiceLogs};
+use crate::profile::{Profile, ProfileHosts};
+use crate::progress::{ProgressTracker, ServicePhase};
+use crate::state::StateFile;
+use crate::systemd::SystemdOptions;
+use crate::terraform::{self, TerraformBackend};
 use crate::{
-    AwsEc2Host, AzureHost, CustomService, GcpComputeEngineHost, Host, HostTargetType,
-    LocalhostHost, ResourcePool, ResourceResult, Service, ServiceBuilder, progress,
+    AwsEc2Host, AzureHost, CustomService, GcpComputeEngineHost, Host, HostTargetType, IpStack,
+    LaunchedBinary, LocalhostHost, LocalhostIsolation, ResourceBatch, ResourcePool, ResourceResult,
+    Service, ServiceBuilder, progress,
 };
 
 pub struct Deployment {
@@ -23,6 +36,26 @@ pub struct Deployment {
     pub resource_pool: ResourcePool,
     localhost_host: Option<Arc<LocalhostHost>>,
     last_resource_result: Option<Arc<ResourceResult>>,
+    state: Option<Arc<StateFile>>,
+    /// Prices used to estimate the cost of planned instances, see [`Self::plan`].
+    price_table: PriceTable,
+    /// Where the output of services is archived after they are stopped, see
+    /// [`Self::retain_logs`].
+    log_destination: Option<LogDestination>,
+    /// The [`Service::fingerprint`] of each started service as of when it was started, by display
+    /// ID, see [`Self::deploy_incremental`].
+    fingerprints: BTreeMap<String, String>,
+    /// The hooks registered for every service, see [`crate::hooks`].
+    hooks: Hooks,
+    /// The display IDs of the services whose [`HookEvent::BuildComplete`] hooks have been run.
+    built: BTreeSet<String>,
+    /// The binary watched for the [`HookEvent::Exit`] hooks of each service, by display ID, with a
+    /// flag set once the deployment stops it, so that its exit is not reported as a crash.
+    watched: BTreeMap<String, (Weak<dyn LaunchedBinary>, Arc<AtomicBool>)>,
+    /// How the frames sent between services are encoded, see [`Self::frame_checksums`].
+    frame: FrameConfig,
+    /// The profile this deployment was created from, see [`Self::from_profile`].
+    profile: Option<ProfileHosts>,
     next_host_id: usize,
     next_service_id: usize,
 }
@@ -41,6 +74,15 @@ impl Deployment {
             resource_pool: ResourcePool::default(),
             localhost_host: None,
             last_resource_result: None,
+            state: None,
+            price_table: PriceTable::default(),
+            log_destination: None,
+            fingerprints: BTreeMap::new(),
+            hooks: Hooks::default(),
+            built: BTreeSet::new(),
+            watched: BTreeMap::new(),
+            frame: FrameConfig::default(),
+            profile: None,
             next_host_id: 0,
             next_service_id: 0,
         };
@@ -49,11 +91,369 @@ impl Deployment {
         ret
     }
 
+    /// Creates a deployment which records its state to the file at `path`, see
+    /// [`crate::state`]. If the file exists, the deployment resumes from it: resources which were
+    /// provisioned by a previous run of the same deployment (that was not shut down cleanly) are
+    /// reused, binaries are not uploaded again, services are bound to the same ports, and binaries
+    /// which are still running (such as systemd units) are reattached to.
+    ///
+    /// Hosts and services are matched with those of the previous run by the order in which they
+    /// are added, so the deployment must be set up in the same way as before.
+    ///
+    /// The data directories of the services on [`Self::Localhost`] are kept next to the file, in
+    /// a directory with the extension `data`, see [`Self::set_localhost_data_root`].
+    pub fn resume(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let mut ret = Self::new();
+        ret.set_localhost_data_root(path.with_extension("data"));
+        ret.state = Some(Arc::new(StateFile::open(path)?));
+        Ok(ret)
+    }
+
+    /// Creates a deployment whose hosts are described by the profile named `name`, in the
+    /// `hydro.toml` file of the current directory or its closest ancestor which has one. See
+    /// [`crate::profile`].
+    pub fn from_profile(name: &str) -> Result<Self> {
+        let path = Profile::find(std::env::current_dir()?)?;
+        Self::from_profile_file(path, name)
+    }
+
+    /// Like [`Self::from_profile`], but reads the profile from the file at `path`.
+    pub fn from_profile_file(path: impl AsRef<Path>, name: &str) -> Result<Self> {
+        let mut ret = Self::new();
+        ret.profile = Some(ProfileHosts::new(name, Profile::load(path, name)?));
+        Ok(ret)
+    }
+
+    /// Returns the host named `name` in the profile this deployment was created from, creating it
+    /// the first time it is requested. Hosts which are not listed in the profile use its
+    /// default settings.
+    pub fn profile_host(&mut self, name: &str) -> Result<Arc<dyn Host>> {
+        let mut profile = self
+            .profile
+            .take()
+            .context("Deployment was not created from a profile.")?;
+        let host = profile.host(self, name);
+        self.profile = Some(profile);
+        host
+    }
+
+    /// Returns a host for each member of the cluster named `name` in the profile this deployment
+    /// was created from, creating them the first time they are requested.
+    pub fn profile_cluster(&mut self, name: &str) -> Result<Vec<Arc<dyn Host>>> {
+        let mut profile = self
+            .profile
+            .take()
+            .context("Deployment was not created from a profile.")?;
+        let hosts = profile.cluster(self, name);
+        self.profile = Some(profile);
+        hosts
+    }
+
+    /// The environment variables set by the profile this deployment was created from, which
+    /// should be passed on to its services (e.g. with [`crate::RustCrate::env`]).
+    pub fn profile_env(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.profile
+            .iter()
+            .flat_map(|profile| &profile.profile.env)
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
     #[expect(non_snake_case, reason = "constructor-esque")]
     pub fn Localhost(&self) -> Arc<LocalhostHost> {
         self.localhost_host.clone().unwrap()
     }
 
+    /// Isolates the ports of the services on [`Self::Localhost`] from each other, see
+    /// [`LocalhostIsolation`]. Must be called before any services are added to the localhost
+    /// host, since it is replaced with a new one.
+    pub fn set_localhost_isolation(&mut self, isolation: LocalhostIsolation) {
+        let current = self.localhost_host.as_ref().unwrap();
+        let mut host = LocalhostHost::new(current.id).with_isolation(isolation);
+        if let Some(data_root) = current.data_root() {
+            host = host.with_data_root(data_root);
+        }
+        let host = Arc::new(host);
+        self.hosts.push(Arc::downgrade(&host) as Weak<dyn Host>);
+        self.localhost_host = Some(host);
+    }
+
+    /// Keeps the data directories of the services on [`Self::Localhost`] in `data_root`, so that
+    /// their persistent data is kept across runs of the deployment, see
+    /// [`LocalhostHost::with_data_root`]. Must be called before any services are added to the
+    /// localhost host, since it is replaced with a new one.
+    pub fn set_localhost_data_root(&mut self, data_root: impl Into<PathBuf>) {
+        let current = self.localhost_host.as_ref().unwrap();
+        let host = Arc::new(
+            LocalhostHost::new(current.id)
+                .with_isolation(current.isolation())
+                .with_data_root(data_root),
+        );
+        self.hosts.push(Arc::downgrade(&host) as Weak<dyn Host>);
+        self.localhost_host = Some(host);
+    }
+
+    /// Stores the terraform state of this deployment in a remote `backend`, under
+    /// `deployment_name`, instead of locally. Deployments with the same name on other machines
+    /// then manage the same resources, and the state is locked while any of them modifies it.
+    /// Must be called before the deployment is deployed.
+    ///
+    /// Resources are named after `deployment_name` rather than randomly, so every machine
+    /// provisions the same resources. Because they are shared, they are not destroyed when the
+    /// deployment is dropped; destroy them with `terraform destroy` in the folder returned by
+    /// [`crate::terraform::TerraformResult::detach`], or from the backend directly.
+    pub fn set_terraform_backend(
+        &mut self,
+        deployment_name: impl Into<String>,
+        backend: TerraformBackend,
+    ) {
+        self.resource_pool
+            .terraform
+            .set_backend(deployment_name, backend);
+    }
+
+    /// Reads instance prices from a JSON file, which replace the bundled prices used to estimate
+    /// the cost of the deployment. See [`PriceTable::load_overrides`].
+    pub fn load_price_overrides(&mut self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        self.price_table.load_overrides(path)
+    }
+
+    /// Retains the stdout and stderr (and profiling data, if traced) of each service while it
+    /// runs, and archives them to `destination` when the services are stopped with
+    /// [`Self::stop`], so that they are not lost when the hosts are torn down. See [`crate::logs`].
+    /// Must be called before the deployment is deployed.
+    pub fn retain_logs(&mut self, destination: LogDestination) {
+        self.log_destination = Some(destination);
+    }
+
+    /// Enables CRC32 checksums on the frames sent between services, so that frames corrupted in
+    /// transit are reported as a [`CorruptFrame`](hydro_deploy_integration::integrity::CorruptFrame)
+    /// instead of being passed on. This also applies to the connections this process makes to
+    /// the services. Must be called before the deployment is deployed.
+    pub fn frame_checksums(&mut self, enabled: bool) {
+        self.frame.checksums = enabled;
+        self.frame.apply();
+    }
+
+    /// Registers `hook` to be run once each service is built and its binary copied to its host.
+    /// See [`crate::hooks`].
+    pub fn on_build_complete<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.add(HookEvent::BuildComplete, hook);
+    }
+
+    /// Registers `hook` to be run each time a service is launched and ready to be started.
+    pub fn on_ready<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.add(HookEvent::Ready, hook);
+    }
+
+    /// Registers `hook` to be run each time a service exits after being started, with its exit
+    /// code and the tail of its output.
+    pub fn on_exit<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.add(HookEvent::Exit, hook);
+    }
+
+    /// Registers `hook` to be run each time a service exits with a non-zero exit code without
+    /// being stopped by [`Self::stop`] or [`Self::drain`].
+    pub fn on_crash<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.add(HookEvent::Crash, hook);
+    }
+
+    /// The deployment hooks followed by the hooks of `service`.
+    fn hooks_of(&self, service: &dyn Service) -> Hooks {
+        let mut hooks = self.hooks.clone();
+        hooks.extend(&service.hooks());
+        hooks
+    }
+
+    /// Runs the `event` hooks of each of `services`, concurrently across services.
+    async fn run_hooks(&self, services: &[Arc<dyn Service>], event: HookEvent) {
+        futures::future::join_all(services.iter().map(|service| {
+            self.hooks_of(&**service).run(HookContext {
+                event,
+                display_id: service.display_id(),
+                host: service.host().map(|host| HostInfo::new(&*host)),
+                exit_code: None,
+                log_tail: Vec::new(),
+            })
+        }))
+        .await;
+    }
+
+    /// Starts watching the binary of each service which was launched since it was last watched,
+    /// to run its [`HookEvent::Exit`] and [`HookEvent::Crash`] hooks once it exits.
+    fn watch_exits(&mut self) {
+        for service in self.services.iter().filter_map(Weak::upgrade) {
+            let Some(binary) = service.current_binary() else {
+                continue;
+            };
+            let display_id = service.display_id();
+            if self.watched.get(&display_id).is_some_and(|(watched, _)| {
+                std::ptr::addr_eq(watched.as_ptr(), Arc::as_ptr(&binary))
+            }) {
+                continue;
+            }
+            let binary = Arc::downgrade(&binary);
+            let stopping = Arc::new(AtomicBool::new(false));
+            self.watched
+                .insert(display_id.clone(), (binary.clone(), stopping.clone()));
+            let mut hooks = self.hooks_of(&*service);
+            if let Some(destination) = self.log_destination.clone() {
+                // Archive the logs as soon as a service crashes, since the deployment may not be
+                // stopped cleanly afterwards.
+                let services = self.services.clone();
+                hooks.add(HookEvent::Crash, move |_| {
+                    let destination = destination.clone();
+                    let logs = collect_logs(&services);
+                    async move {
+                        match logs::archive(&destination, logs, Some("crash")).await {
+                            Ok(location) => {
+                                ProgressTracker::println(format!("Archived logs to {location}"))
+                            }
+                            Err(e) => {
+                                ProgressTracker::eprintln(format!("Failed to archive logs: {e:?}"))
+                            }
+                        }
+                    }
+                });
+            }
+            tokio::spawn(hooks::watch_exit(
+                binary,
+                display_id,
+                service.host().map(|host| HostInfo::new(&*host)),
+                hooks,
+                stopping,
+            ));
+        }
+    }
+
+    /// Marks the currently watched binaries of `services` as stopped by the deployment, so that
+    /// their exits do not run [`HookEvent::Crash`] hooks.
+    fn mark_stopping<'a>(&self, services: impl IntoIterator<Item = &'a Arc<dyn Service>>) {
+        for service in services {
+            if let Some((_, stopping)) = self.watched.get(&service.display_id()) {
+                stopping.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Archives the output retained by the services to the destination configured with
+    /// [`Self::retain_logs`], returning the location of the archived run. This is done
+    /// automatically by [`Self::stop`], and when a service crashes.
+    pub async fn archive_logs(&mut self) -> Result<String> {
+        let Some(destination) = self.log_destination.clone() else {
+            bail!("Logs are not retained, call `retain_logs` before deploying.");
+        };
+        self.services.retain(|weak| weak.strong_count() > 0);
+
+        let logs = collect_logs(&self.services);
+        let location = logs::archive(&destination, logs, None).await?;
+        ProgressTracker::println(format!("Archived logs to {location}"));
+        Ok(location)
+    }
+
+    /// Leaves the deployment running instead of stopping its services and destroying its cloud
+    /// resources, and prints how to reattach to each service later.
+    ///
+    /// Only services launched as systemd units (see [`crate::systemd`]) outlive the deploying
+    /// process, other services still stop when it exits.
+    pub fn detach(self) {
+        for service in self.services.iter().filter_map(Weak::upgrade) {
+            match service.detach() {
+                Some(reattach) => {
+                    ProgressTracker::println(format!("[{}] {reattach}", service.display_id()))
+                }
+                None => ProgressTracker::println(format!(
+                    "[{}] cannot be detached, and will stop when this process exits",
+                    service.display_id()
+                )),
+            }
+        }
+
+        if let Some(resource_result) = &self.last_resource_result {
+            for folder in resource_result.detach() {
+                ProgressTracker::println(format!(
+                    "Left cloud resources running, destroy them with `{} destroy` in {}",
+                    terraform::terraform_name(),
+                    folder.display()
+                ));
+            }
+        }
+    }
+
+    /// Bakes a machine image named `image_name`, by launching `host` (a GCP or AWS host, with the
+    /// base image to start from), running the commands of `recipe` on it, and snapshotting its
+    /// boot disk. Returns the reference to the image, which can be used as the `image` (GCP) or
+    /// `ami` (AWS) of hosts added afterwards. See [`crate::image`].
+    ///
+    /// The host should not be used by any services. It is torn down once it is dropped, while the
+    /// image is kept until it is destroyed manually.
+    pub async fn bake_image(
+        &mut self,
+        host: Arc<dyn Host>,
+        image_name: impl AsRef<str>,
+        recipe: &ImageRecipe,
+    ) -> Result<String> {
+        progress::ProgressTracker::with_group("bake", None, || {
+            image::bake(host, image_name.as_ref(), recipe)
+        })
+        .await
+    }
+
+    /// Estimates the cost of the cloud instances which [`Self::deploy`] would provision, without
+    /// provisioning anything. Hosts which were already provisioned are not included.
+    pub fn plan(&mut self) -> CostEstimate {
+        self.services.retain(|weak| weak.strong_count() > 0);
+        let (resource_batch, _) = self.collect_resources();
+        self.price_table.estimate(&resource_batch.terraform)
+    }
+
+    /// Collects the resources of all services and hosts, along with the addresses of the
+    /// resources added by each host.
+    fn collect_resources(&self) -> (ResourceBatch, BTreeMap<usize, BTreeSet<String>>) {
+        let mut resource_batch = ResourceBatch::resuming(
+            self.state
+                .as_ref()
+                .map(|state| state.previous_resource_ids())
+                .unwrap_or_default(),
+            self.resource_pool
+                .terraform
+                .deployment_name()
+                .map(str::to_owned),
+        );
+
+        for service in self.services.iter().filter_map(Weak::upgrade) {
+            service.collect_resources(&mut resource_batch);
+        }
+
+        let mut host_resources = BTreeMap::new();
+        for host in self.hosts.iter().filter_map(Weak::upgrade) {
+            let before = resource_addresses(&resource_batch);
+            host.collect_resources(&mut resource_batch);
+            let added = &resource_addresses(&resource_batch) - &before;
+            if !added.is_empty() {
+                host_resources.insert(host.id(), added);
+            }
+        }
+
+        (resource_batch, host_resources)
+    }
+
     #[expect(non_snake_case, reason = "constructor-esque")]
     pub fn CustomService(
         &mut self,
@@ -73,6 +473,49 @@ impl Deployment {
         Ok(())
     }
 
+    /// Runs `deploy()` and `start()` while showing a full-screen terminal UI, with the phase of
+    /// each service, their logs, and the ports connecting them. Services can be restarted from the
+    /// TUI, and `stop()` is run once stopping the deployment is requested from the TUI.
+    #[cfg(feature = "tui")]
+    pub async fn run_tui(&mut self) -> Result<()> {
+        use crate::tui::{DeploymentTui, TuiCommand};
+
+        let mut tui = DeploymentTui::start()?;
+        self.deploy().await?;
+        self.start().await?;
+        while let Some(command) = tui.next_command().await {
+            match command {
+                TuiCommand::StopDeployment => break,
+                TuiCommand::RestartService(display_id) => {
+                    let Some(service) = self
+                        .services
+                        .iter()
+                        .filter_map(Weak::upgrade)
+                        .find(|service| service.display_id() == display_id)
+                    else {
+                        continue;
+                    };
+                    self.mark_stopping([&service]);
+                    // Errors are shown in the TUI, the rest of the deployment keeps running.
+                    if with_phase(
+                        &*service,
+                        ServicePhase::Restarting,
+                        Some(ServicePhase::Running),
+                        service.restart(),
+                    )
+                    .await
+                    .is_ok()
+                    {
+                        self.run_hooks(&[service], HookEvent::Ready).await;
+                        self.watch_exits();
+                    }
+                }
+            }
+        }
+        self.stop().await?;
+        Ok(())
+    }
+
     /// Runs `start()`, waits for the trigger future, then runs `stop()`.
     /// This is useful if you need to initiate external network connections between
     /// `deploy()` and `start()`.
@@ -100,26 +543,35 @@ impl Deployment {
         self.services.retain(|weak| weak.strong_count() > 0);
 
         progress::ProgressTracker::with_group("deploy", Some(3), || async {
-            let mut resource_batch = super::ResourceBatch::new();
+            let (resource_batch, host_resources) = self.collect_resources();
 
-            for service in self.services.iter().filter_map(Weak::upgrade) {
-                service.collect_resources(&mut resource_batch);
-            }
-
-            for host in self.hosts.iter().filter_map(Weak::upgrade) {
-                host.collect_resources(&mut resource_batch);
+            let resource_ids = resource_batch.resource_ids().clone();
+            let estimate = self.price_table.estimate(&resource_batch.terraform);
+            if !estimate.instances.is_empty() {
+                ProgressTracker::println(estimate.to_string());
             }
 
             let resource_result = Arc::new(
                 progress::ProgressTracker::with_group("provision", Some(1), || async {
                     resource_batch
-                        .provision(&mut self.resource_pool, self.last_resource_result.clone())
+                        .provision(
+                            &mut self.resource_pool,
+                            self.last_resource_result.clone(),
+                            self.state.clone(),
+                            self.frame,
+                        )
                         .await
                 })
                 .await?,
             );
             self.last_resource_result = Some(resource_result.clone());
 
+            if let Some(state) = &self.state
+                && let Some(folder) = &resource_result.terraform.deployment_folder
+            {
+                state.record_terraform(folder.path().to_owned(), host_resources, &resource_ids)?;
+            }
+
             for host in self.hosts.iter().filter_map(Weak::upgrade) {
                 host.provision(&resource_result);
             }
@@ -129,13 +581,39 @@ impl Deployment {
                 .iter()
                 .filter_map(Weak::upgrade)
                 .collect::<Vec<_>>();
+            // Services which were already deployed are not built or launched again.
+            let new_services = upgraded_services
+                .iter()
+                .filter(|service| !self.built.contains(&service.display_id()))
+                .cloned()
+                .collect::<Vec<_>>();
 
+            let this = &*self;
             progress::ProgressTracker::with_group("prepare", Some(upgraded_services.len()), || {
                 let services_future = upgraded_services
                     .iter()
                     .map(|service: &Arc<dyn Service>| {
                         let resource_result = &resource_result;
-                        async move { service.deploy(resource_result).await }
+                        let is_new = !this.built.contains(&service.display_id());
+                        async move {
+                            with_phase(
+                                &**service,
+                                ServicePhase::Deploying,
+                                None,
+                                service.deploy(resource_result),
+                            )
+                            .await?;
+                            // Run as soon as this service is built, without waiting for the
+                            // others.
+                            if is_new {
+                                this.run_hooks(
+                                    std::slice::from_ref(service),
+                                    HookEvent::BuildComplete,
+                                )
+                                .await;
+                            }
+                            Ok(())
+                        }
                     })
                     .collect::<Vec<_>>();
 
@@ -144,19 +622,27 @@ impl Deployment {
                     .try_fold((), |_, _| async { Ok(()) })
             })
             .await?;
+            self.built
+                .extend(new_services.iter().map(|service| service.display_id()));
 
             progress::ProgressTracker::with_group("ready", Some(upgraded_services.len()), || {
                 let all_services_ready =
-                    upgraded_services
-                        .iter()
-                        .map(|service: &Arc<dyn Service>| async move {
-                            service.ready().await?;
-                            Ok(()) as Result<()>
-                        });
+                    upgraded_services.iter().map(|service: &Arc<dyn Service>| {
+                        if self.log_destination.is_some() {
+                            service.retain_logs();
+                        }
+                        with_phase(
+                            &**service,
+                            ServicePhase::Launching,
+                            Some(ServicePhase::Ready),
+                            service.ready(),
+                        )
+                    });
 
                 futures::future::try_join_all(all_services_ready)
             })
             .await?;
+            self.run_hooks(&new_services, HookEvent::Ready).await;
 
             Ok(())
         })
@@ -169,33 +655,235 @@ impl Deployment {
         progress::ProgressTracker::with_group("start", None, || {
             let all_services_start = self.services.iter().filter_map(Weak::upgrade).map(
                 |service: Arc<dyn Service>| async move {
-                    service.start().await?;
-                    Ok(()) as Result<()>
+                    with_phase(
+                        &*service,
+                        ServicePhase::Starting,
+                        Some(ServicePhase::Running),
+                        service.start(),
+                    )
+                    .await
                 },
             );
 
             futures::future::try_join_all(all_services_start)
         })
         .await?;
+
+        // Services which were already running keep the fingerprint they were started with.
+        let newly_started = self
+            .services
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|service| !self.fingerprints.contains_key(&service.display_id()))
+            .collect::<Vec<_>>();
+        let fingerprints = futures::future::try_join_all(
+            newly_started.iter().map(|service| service.fingerprint()),
+        )
+        .await?;
+        for (service, fingerprint) in newly_started.iter().zip(fingerprints) {
+            if let Some(fingerprint) = fingerprint {
+                self.fingerprints.insert(service.display_id(), fingerprint);
+            }
+        }
+        self.watch_exits();
         Ok(())
     }
 
+    /// Like [`Self::deploy`] followed by [`Self::start`], but for a deployment which is already
+    /// running: deploys and starts the services added since, and redeploys only the started
+    /// services whose code or wiring changed since they were started (see
+    /// [`Service::fingerprint`]), keeping the rest running.
+    ///
+    /// A changed service is rebuilt and its binary uploaded again only if its code changed, and
+    /// is then restarted. Services connected to the ports of a restarted service are restarted
+    /// along with it, since it may be bound to different ports after restarting, so a cycle of
+    /// connected services is always restarted together. All restarted services are launched
+    /// before any of them is started again.
+    pub async fn deploy_incremental(&mut self) -> Result<()> {
+        // Services which were already deployed are left as they are.
+        self.deploy().await?;
+
+        let started = self
+            .services
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|service| self.fingerprints.contains_key(&service.display_id()))
+            .collect::<Vec<_>>();
+        let fingerprints =
+            futures::future::try_join_all(started.iter().map(|service| service.fingerprint()))
+                .await?;
+        let changed = started
+            .iter()
+            .zip(fingerprints)
+            .filter(|(service, fingerprint)| {
+                fingerprint.as_ref() != self.fingerprints.get(&service.display_id())
+            })
+            .map(|(service, _)| service.display_id())
+            .collect::<BTreeSet<_>>();
+        let dependencies = started
+            .iter()
+            .map(|service| (service.display_id(), service.server_dependencies()))
+            .collect::<BTreeMap<_, _>>();
+        let restarted = restart_closure(changed, &dependencies);
+
+        let to_redeploy = started
+            .into_iter()
+            .filter(|service| restarted.contains(&service.display_id()))
+            .collect::<Vec<_>>();
+        if !to_redeploy.is_empty() {
+            self.mark_stopping(&to_redeploy);
+            progress::ProgressTracker::with_group("redeploy", Some(to_redeploy.len()), || {
+                let all_services_redeploy = to_redeploy.iter().map(|service: &Arc<dyn Service>| {
+                    with_phase(
+                        &**service,
+                        ServicePhase::Restarting,
+                        Some(ServicePhase::Ready),
+                        service.redeploy(),
+                    )
+                });
+
+                futures::future::try_join_all(all_services_redeploy)
+            })
+            .await?;
+            self.run_hooks(&to_redeploy, HookEvent::Ready).await;
+            // Recorded again by `start`, with the code and wiring they were restarted with.
+            for display_id in &restarted {
+                self.fingerprints.remove(display_id);
+            }
+        }
+
+        self.start().await
+    }
+
     pub async fn stop(&mut self) -> Result<()> {
         self.services.retain(|weak| weak.strong_count() > 0);
+        for (_, stopping) in self.watched.values() {
+            stopping.store(true, Ordering::SeqCst);
+        }
 
         progress::ProgressTracker::with_group("stop", None, || {
             let all_services_stop = self.services.iter().filter_map(Weak::upgrade).map(
                 |service: Arc<dyn Service>| async move {
-                    service.stop().await?;
-                    Ok(()) as Result<()>
+                    with_phase(
+                        &*service,
+                        ServicePhase::Stopping,
+                        Some(ServicePhase::Stopped),
+                        service.stop(),
+                    )
+                    .await
                 },
             );
 
             futures::future::try_join_all(all_services_stop)
         })
         .await?;
+
+        if self.log_destination.is_some() {
+            self.archive_logs().await?;
+        }
         Ok(())
     }
+
+    /// Drains all services, waiting for each of them to finish sending outstanding data and exit.
+    /// See [`Service::drain`].
+    pub async fn drain(&mut self) -> Result<()> {
+        self.services.retain(|weak| weak.strong_count() > 0);
+        for (_, stopping) in self.watched.values() {
+            stopping.store(true, Ordering::SeqCst);
+        }
+
+        progress::ProgressTracker::with_group("drain", None, || {
+            let all_services_drain = self.services.iter().filter_map(Weak::upgrade).map(
+                |service: Arc<dyn Service>| async move {
+                    with_phase(
+                        &*service,
+                        ServicePhase::Draining,
+                        Some(ServicePhase::Stopped),
+                        service.drain(),
+                    )
+                    .await
+                },
+            );
+
+            futures::future::try_join_all(all_services_drain)
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+/// The logs retained by each of the (still alive) `services`.
+fn collect_logs(services: &[Weak<dyn Service>]) -> Vec<(String, ServiceLogs)> {
+    services
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter_map(|service| Some((service.display_id(), service.logs()?)))
+        .collect()
+}
+
+/// The addresses (`type.name`) of the terraform resources in `resource_batch`.
+#[expect(
+    clippy::disallowed_methods,
+    reason = "nondeterministic iteration order, collected into a sorted set"
+)]
+fn resource_addresses(resource_batch: &ResourceBatch) -> BTreeSet<String> {
+    resource_batch
+        .terraform
+        .resource
+        .iter()
+        .flat_map(|(resource_type, resources)| {
+            resources
+                .keys()
+                .map(move |name| format!("{resource_type}.{name}"))
+        })
+        .collect()
+}
+
+/// The services which must be restarted when the `changed` services are: those along with every
+/// service (transitively) connected to the servers of a restarted service, given the
+/// [`Service::server_dependencies`] of each service.
+fn restart_closure(
+    changed: BTreeSet<String>,
+    dependencies: &BTreeMap<String, BTreeSet<String>>,
+) -> BTreeSet<String> {
+    let mut restarted = changed;
+    loop {
+        let dependents = dependencies
+            .iter()
+            .filter(|(display_id, servers)| {
+                !restarted.contains(*display_id) && !servers.is_disjoint(&restarted)
+            })
+            .map(|(display_id, _)| display_id.clone())
+            .collect::<Vec<_>>();
+        if dependents.is_empty() {
+            return restarted;
+        }
+        restarted.extend(dependents);
+    }
+}
+
+/// Runs `f` for `service`, reporting `phase` while it runs, then `done` (if any) when it succeeds,
+/// or the error when it fails.
+async fn with_phase(
+    service: &dyn Service,
+    phase: ServicePhase,
+    done: Option<ServicePhase>,
+    f: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    let display_id = service.display_id();
+    ProgressTracker::service_phase(&display_id, phase);
+    match f.await {
+        Ok(()) => {
+            if let Some(done) = done {
+                ProgressTracker::service_phase(&display_id, done);
+            }
+            Ok(())
+        }
+        Err(err) => {
+            ProgressTracker::service_phase(&display_id, ServicePhase::Failed(format!("{:#}", err)));
+            Err(err)
+        }
+    }
 }
 
 impl Deployment {
@@ -235,6 +923,7 @@ impl Deployment {
         network: Arc<GcpNetwork>,
         user: Option<String>,
         display_name: Option<String>,
+        systemd: Option<SystemdOptions>,
     ) -> Arc<GcpComputeEngineHost> {
         self.add_host(|id| {
             GcpComputeEngineHost::new(
@@ -247,6 +936,7 @@ impl Deployment {
                 network,
                 user,
                 display_name,
+                systemd,
             )
         })
     }
@@ -261,6 +951,9 @@ impl Deployment {
         target_type: Option<HostTargetType>,
         region: String,
         user: Option<String>,
+        display_name: Option<String>,
+        systemd: Option<SystemdOptions>,
+        ip_stack: Option<IpStack>,
     ) -> Arc<AzureHost> {
         self.add_host(|id| {
             AzureHost::new(
@@ -272,6 +965,9 @@ impl Deployment {
                 target_type.unwrap_or(HostTargetType::Linux(crate::LinuxCompileType::Musl)),
                 region,
                 user,
+                display_name,
+                systemd,
+                ip_stack.unwrap_or_default(),
             )
         })
     }
@@ -290,6 +986,7 @@ impl Deployment {
         cwa_metrics_collected: Option<serde_json::Value>,
         user: Option<String>,
         display_name: Option<String>,
+        systemd: Option<SystemdOptions>,
     ) -> Arc<AwsEc2Host> {
         self.add_host(|id| {
             AwsEc2Host::new(
@@ -304,7 +1001,51 @@ impl Deployment {
                 cwa_metrics_collected,
                 user,
                 display_name,
+                systemd,
             )
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::restart_closure;
+
+    fn set(ids: &[&str]) -> BTreeSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn restart_closure_includes_dependents() {
+        // `client` connects to `server`, which connects to `store`.
+        let dependencies = BTreeMap::from([
+            ("client".to_owned(), set(&["server"])),
+            ("server".to_owned(), set(&["store"])),
+            ("store".to_owned(), set(&[])),
+        ]);
+        assert_eq!(
+            set(&["client", "server", "store"]),
+            restart_closure(set(&["store"]), &dependencies)
+        );
+        // Servers keep running when only their clients are restarted.
+        assert_eq!(
+            set(&["client"]),
+            restart_closure(set(&["client"]), &dependencies)
+        );
+    }
+
+    #[test]
+    fn restart_closure_restarts_cycles_together() {
+        let dependencies = BTreeMap::from([
+            ("leader".to_owned(), set(&["follower"])),
+            ("follower".to_owned(), set(&["leader"])),
+            ("observer".to_owned(), set(&[])),
+        ]);
+        assert_eq!(
+            set(&["follower", "leader"]),
+            restart_closure(set(&["leader"]), &dependencies)
+        );
+    }
+}
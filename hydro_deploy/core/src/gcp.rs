@@ -1,6 +1,7 @@
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::ops::RangeInclusive;
 use std::sync::{Arc, Mutex, OnceLock};
 
 use anyhow::Result;
@@ -10,13 +11,16 @@ use serde_json::json;
 use super::terraform::{TERRAFORM_ALPHABET, TerraformOutput, TerraformProvider};
 use super::{ClientStrategy, Host, HostTargetType, LaunchedHost, ResourceBatch, ResourceResult};
 use crate::ssh::LaunchedSshHost;
-use crate::{BaseServerStrategy, HostStrategyGetter, PortNetworkHint};
+use crate::systemd::SystemdOptions;
+use crate::{BaseServerStrategy, HostLocation, HostStrategyGetter, IpStack, PortNetworkHint};
 
 pub struct LaunchedComputeEngine {
     resource_result: Arc<ResourceResult>,
     user: String,
+    systemd: Option<SystemdOptions>,
     pub internal_ip: String,
     pub external_ip: Option<String>,
+    pub external_ipv6: Option<String>,
 }
 
 impl LaunchedSshHost for LaunchedComputeEngine {
@@ -24,6 +28,10 @@ impl LaunchedSshHost for LaunchedComputeEngine {
         self.external_ip.as_deref()
     }
 
+    fn get_external_ipv6(&self) -> Option<&str> {
+        self.external_ipv6.as_deref()
+    }
+
     fn get_internal_ip(&self) -> &str {
         &self.internal_ip
     }
@@ -39,24 +47,53 @@ impl LaunchedSshHost for LaunchedComputeEngine {
     fn ssh_user(&self) -> &str {
         self.user.as_str()
     }
+
+    fn systemd(&self) -> Option<&SystemdOptions> {
+        self.systemd.as_ref()
+    }
 }
 
 #[derive(Debug)]
 pub struct GcpNetwork {
     pub project: String,
     pub existing_vpc: OnceLock<String>,
+    pub ip_stack: IpStack,
     id: String,
 }
 
 impl GcpNetwork {
     pub fn new(project: impl Into<String>, existing_vpc: Option<String>) -> Arc<Self> {
+        Self::with_ip_stack(project, existing_vpc, IpStack::V4)
+    }
+
+    /// Like [`GcpNetwork::new`], but with hosts reachable over the IP versions of `ip_stack`.
+    ///
+    /// With [`IpStack::DualStack`], a network created by Hydro Deploy has a dual-stack subnetwork
+    /// in each region hosts are placed in. An existing network must be in custom mode, with a
+    /// dual-stack subnetwork with external IPv6 access named after the network in each region.
+    pub fn with_ip_stack(
+        project: impl Into<String>,
+        existing_vpc: Option<String>,
+        ip_stack: IpStack,
+    ) -> Arc<Self> {
         Arc::new(Self {
             project: project.into(),
             existing_vpc: existing_vpc.map(From::from).unwrap_or_default(),
+            ip_stack,
             id: nanoid!(8, &TERRAFORM_ALPHABET),
         })
     }
 
+    /// An identifier of the VPC network, which is the same for every [`GcpNetwork`] of an
+    /// existing network in the same project.
+    fn vpc_id(&self) -> String {
+        format!(
+            "{}/{}",
+            self.project,
+            self.existing_vpc.get().unwrap_or(&self.id)
+        )
+    }
+
     fn collect_resources(&self, resource_batch: &mut ResourceBatch) -> String {
         resource_batch
             .terraform
@@ -70,7 +107,10 @@ impl GcpNetwork {
                 },
             );
 
-        let vpc_network = format!("hydro-vpc-network-{}", self.id);
+        let vpc_network = format!(
+            "hydro-vpc-network-{}",
+            resource_batch.resource_id("gcp-network", &self.id)
+        );
 
         if let Some(existing) = self.existing_vpc.get() {
             if resource_batch
@@ -108,7 +148,10 @@ impl GcpNetwork {
                     json!({
                         "name": vpc_network,
                         "project": self.project,
-                        "auto_create_subnetworks": true
+                        // Auto-created subnetworks only have IPv4 addresses, so dual-stack
+                        // subnetworks are created per region instead, see
+                        // `collect_dual_stack_subnetwork`.
+                        "auto_create_subnetworks": self.ip_stack == IpStack::V4
                     }),
                 );
 
@@ -163,6 +206,52 @@ impl GcpNetwork {
             out
         }
     }
+
+    /// The self link of the dual-stack subnetwork of the network at `vpc_path` in `region`,
+    /// creating it if the network was created by Hydro Deploy.
+    fn collect_dual_stack_subnetwork(
+        &self,
+        resource_batch: &mut ResourceBatch,
+        vpc_path: &str,
+        region: &str,
+    ) -> String {
+        if vpc_path.starts_with("data.") {
+            return format!(
+                "projects/{}/regions/{region}/subnetworks/{}",
+                self.project,
+                self.existing_vpc.get().unwrap()
+            );
+        }
+
+        let network_name = vpc_path.trim_start_matches("google_compute_network.");
+        let subnetworks = resource_batch
+            .terraform
+            .resource
+            .entry("google_compute_subnetwork".to_owned())
+            .or_default();
+        let subnetwork_key = format!("{network_name}-{region}");
+        if !subnetworks.contains_key(&subnetwork_key) {
+            // Each region gets its own /16 within the range the internal firewall rule allows.
+            let index = subnetworks
+                .keys()
+                .filter(|key| key.starts_with(network_name))
+                .count();
+            assert!(index < 128, "too many regions in one GCP network");
+            subnetworks.insert(
+                subnetwork_key.clone(),
+                json!({
+                    "name": subnetwork_key,
+                    "project": self.project,
+                    "region": region,
+                    "network": format!("${{{vpc_path}.self_link}}"),
+                    "ip_cidr_range": format!("10.{}.0.0/16", 128 + index),
+                    "stack_type": "IPV4_IPV6",
+                    "ipv6_access_type": "EXTERNAL"
+                }),
+            );
+        }
+        format!("${{google_compute_subnetwork.{subnetwork_key}.self_link}}")
+    }
 }
 
 pub struct GcpComputeEngineHost {
@@ -177,8 +266,10 @@ pub struct GcpComputeEngineHost {
     network: Arc<GcpNetwork>,
     user: Option<String>,
     display_name: Option<String>,
+    systemd: Option<SystemdOptions>,
     pub launched: OnceLock<Arc<LaunchedComputeEngine>>, // TODO(mingwei): fix pub
     external_ports: Mutex<Vec<u16>>,
+    external_port_ranges: Mutex<Vec<RangeInclusive<u16>>>,
 }
 
 impl Debug for GcpComputeEngineHost {
@@ -202,6 +293,7 @@ impl GcpComputeEngineHost {
         network: Arc<GcpNetwork>,
         user: Option<String>,
         display_name: Option<String>,
+        systemd: Option<SystemdOptions>,
     ) -> Self {
         Self {
             id,
@@ -213,10 +305,20 @@ impl GcpComputeEngineHost {
             network,
             user,
             display_name,
+            systemd,
             launched: OnceLock::new(),
             external_ports: Mutex::new(Vec::new()),
+            external_port_ranges: Mutex::new(Vec::new()),
         }
     }
+
+    /// The region of the zone the host is placed in, which is the zone without its trailing
+    /// `-a`.
+    fn region_of_zone(&self) -> &str {
+        self.region
+            .rsplit_once('-')
+            .map_or(self.region.as_str(), |(region, _)| region)
+    }
 }
 
 impl Host for GcpComputeEngineHost {
@@ -240,6 +342,21 @@ impl Host for GcpComputeEngineHost {
         }
     }
 
+    fn request_port_range(&self, ports: RangeInclusive<u16>) -> Result<()> {
+        let mut external_port_ranges = self.external_port_ranges.lock().unwrap();
+        if !external_port_ranges.contains(&ports) {
+            if self.launched.get().is_some() {
+                anyhow::bail!(
+                    "Cannot open ports {}-{} after host has been launched, as the firewall cannot be adjusted",
+                    ports.start(),
+                    ports.end(),
+                );
+            }
+            external_port_ranges.push(ports);
+        }
+        Ok(())
+    }
+
     fn request_custom_binary(&self) {
         self.request_port_base(&BaseServerStrategy::ExternalTcpPort(22));
     }
@@ -323,7 +440,10 @@ impl Host for GcpComputeEngineHost {
             );
 
         let vm_key = format!("vm-instance-{}", self.id);
-        let mut vm_name = format!("hydro-vm-instance-{}", nanoid!(8, &TERRAFORM_ALPHABET),);
+        let mut vm_name = format!(
+            "hydro-vm-instance-{}",
+            resource_batch.resource_id("gcp-vm-instance", &self.id.to_string())
+        );
         // Name must match regex: (?:[a-z](?:[-a-z0-9]{0,61}[a-z0-9])?), max length = 63 (61 + 1 a-z before and after)
         if let Some(mut display_name) = self.display_name.clone() {
             vm_name.push('-');
@@ -343,27 +463,50 @@ impl Host for GcpComputeEngineHost {
         let mut tags = vec![];
         let mut external_interfaces = vec![];
 
+        let dual_stack = self.network.ip_stack == IpStack::DualStack;
+        let mut interface = json!({ "network": format!("${{{vpc_path}.self_link}}") });
+        if dual_stack {
+            interface["subnetwork"] = self
+                .network
+                .collect_dual_stack_subnetwork(resource_batch, &vpc_path, self.region_of_zone())
+                .into();
+            interface["stack_type"] = "IPV4_IPV6".into();
+        }
+
         let external_ports = self.external_ports.lock().unwrap();
-        if external_ports.is_empty() {
-            external_interfaces.push(json!({ "network": format!("${{{vpc_path}.self_link}}") }));
+        let external_port_ranges = self.external_port_ranges.lock().unwrap();
+        if external_ports.is_empty() && external_port_ranges.is_empty() {
+            external_interfaces.push(interface);
         } else {
-            external_interfaces.push(json!({
-                "network": format!("${{{vpc_path}.self_link}}"),
-                "access_config": [
+            interface["access_config"] = json!([
+                {
+                    "network_tier": "STANDARD"
+                }
+            ]);
+            if dual_stack {
+                // External IPv6 addresses are only offered in the premium tier.
+                interface["ipv6_access_config"] = json!([
                     {
-                        "network_tier": "STANDARD"
+                        "network_tier": "PREMIUM"
                     }
-                ]
-            }));
+                ]);
+            }
+            external_interfaces.push(interface);
 
-            // open the external ports that were requested
-            let my_external_tags = external_ports.iter().map(|port| {
-                let rule_id = nanoid!(8, &TERRAFORM_ALPHABET);
-                let firewall_rule = resource_batch
+            // open the external ports that were requested, with one rule per port or range
+            let port_specs = external_ports.iter().map(|port| port.to_string()).chain(
+                external_port_ranges
+                    .iter()
+                    .map(|range| format!("{}-{}", range.start(), range.end())),
+            );
+            let my_external_tags = port_specs.map(|port| {
+                let rule_id = resource_batch.resource_id("gcp-firewall-rule", &port);
+                let firewall_rules = resource_batch
                     .terraform
                     .resource
                     .entry("google_compute_firewall".to_owned())
-                    .or_default()
+                    .or_default();
+                let firewall_rule = firewall_rules
                     .entry(format!("open-external-port-{}", port))
                     .or_insert(json!({
                         "name": format!("open-external-port-{}-{}", port, rule_id),
@@ -374,10 +517,24 @@ impl Host for GcpComputeEngineHost {
                         "allow": [
                             {
                                 "protocol": "tcp",
-                                "ports": vec![port.to_string()]
+                                "ports": vec![port.clone()]
                             }
                         ]
-                    }));
+                    }))
+                    .clone();
+
+                // A firewall rule cannot mix IPv4 and IPv6 source ranges.
+                if dual_stack {
+                    firewall_rules
+                        .entry(format!("open-external-port-{}-ipv6", port))
+                        .or_insert_with(|| {
+                            let mut ipv6_rule = firewall_rule.clone();
+                            ipv6_rule["name"] =
+                                format!("{}-ipv6", firewall_rule["name"].as_str().unwrap()).into();
+                            ipv6_rule["source_ranges"] = json!(["::/0"]);
+                            ipv6_rule
+                        });
+                }
 
                 firewall_rule["target_tags"].as_array().unwrap()[0].clone()
             });
@@ -390,8 +547,19 @@ impl Host for GcpComputeEngineHost {
                     value: format!("${{google_compute_instance.{vm_key}.network_interface[0].access_config[0].nat_ip}}")
                 }
             );
+
+            if dual_stack {
+                resource_batch.terraform.output.insert(
+                    format!("{vm_key}-public-ipv6"),
+                    TerraformOutput {
+                        value: format!("${{google_compute_instance.{vm_key}.network_interface[0].ipv6_access_config[0].external_ipv6}}")
+                    }
+                );
+            }
         }
-        drop(external_ports); // Drop the lock as soon as possible.
+        // Drop the locks as soon as possible.
+        drop(external_ports);
+        drop(external_port_ranges);
 
         let user = self.user.as_deref().unwrap_or("hydro");
         resource_batch
@@ -431,6 +599,13 @@ impl Host for GcpComputeEngineHost {
                 ),
             },
         );
+
+        resource_batch.terraform.output.insert(
+            format!("{vm_key}-boot-disk"),
+            TerraformOutput {
+                value: format!("${{google_compute_instance.{vm_key}.boot_disk[0].source}}"),
+            },
+        );
     }
 
     fn launched(&self) -> Option<Arc<dyn LaunchedHost>> {
@@ -439,6 +614,75 @@ impl Host for GcpComputeEngineHost {
             .map(|a| a.clone() as Arc<dyn LaunchedHost>)
     }
 
+    fn collect_image_resources(
+        &self,
+        image_name: &str,
+        resource_batch: &mut ResourceBatch,
+    ) -> Result<String> {
+        let Some(launched) = self.launched.get() else {
+            anyhow::bail!("Cannot bake an image from a GCP instance which has not been launched");
+        };
+        let boot_disk = &launched
+            .resource_result
+            .terraform
+            .outputs
+            .get(&format!("vm-instance-{}-boot-disk", self.id))
+            .unwrap()
+            .value;
+
+        resource_batch
+            .terraform
+            .terraform
+            .required_providers
+            .insert(
+                "google".to_owned(),
+                TerraformProvider {
+                    source: "hashicorp/google".to_owned(),
+                    version: "4.53.1".to_owned(),
+                },
+            );
+
+        // Images cannot be created directly from the disk of a running instance, so the disk is
+        // snapshotted first.
+        let image_key = format!("image-{}", self.id);
+        resource_batch
+            .terraform
+            .resource
+            .entry("google_compute_snapshot".to_owned())
+            .or_default()
+            .insert(
+                image_key.clone(),
+                json!({
+                    "name": format!("{image_name}-snapshot"),
+                    "project": self.project,
+                    "zone": self.region,
+                    "source_disk": boot_disk,
+                }),
+            );
+        resource_batch
+            .terraform
+            .resource
+            .entry("google_compute_image".to_owned())
+            .or_default()
+            .insert(
+                image_key.clone(),
+                json!({
+                    "name": image_name,
+                    "project": self.project,
+                    "source_snapshot": format!("${{google_compute_snapshot.{image_key}.self_link}}"),
+                }),
+            );
+
+        let output = format!("{image_key}-self-link");
+        resource_batch.terraform.output.insert(
+            output.clone(),
+            TerraformOutput {
+                value: format!("${{google_compute_image.{image_key}.self_link}}"),
+            },
+        );
+        Ok(output)
+    }
+
     fn provision(&self, resource_result: &Arc<ResourceResult>) -> Arc<dyn LaunchedHost> {
         self.launched
             .get_or_init(|| {
@@ -458,16 +702,34 @@ impl Host for GcpComputeEngineHost {
                     .get(&format!("vm-instance-{id}-public-ip"))
                     .map(|v| v.value.clone());
 
+                let external_ipv6 = resource_result
+                    .terraform
+                    .outputs
+                    .get(&format!("vm-instance-{id}-public-ipv6"))
+                    .map(|v| v.value.clone());
+
                 Arc::new(LaunchedComputeEngine {
                     resource_result: resource_result.clone(),
                     user: self.user.as_ref().cloned().unwrap_or("hydro".to_owned()),
+                    systemd: self.systemd.clone(),
                     internal_ip,
                     external_ip,
+                    external_ipv6,
                 })
             })
             .clone()
     }
 
+    fn location(&self) -> Option<HostLocation> {
+        // The `region` of a GCP host is the zone it is placed in.
+        Some(HostLocation {
+            cloud: "gcp",
+            region: self.region_of_zone().to_owned(),
+            zone: Some(self.region.clone()),
+            network: self.network.vpc_id(),
+        })
+    }
+
     fn strategy_as_server<'a>(
         &'a self,
         client_host: &dyn Host,
@@ -494,6 +756,25 @@ impl Host for GcpComputeEngineHost {
                     })
                 }),
             ))
+        } else if matches!(
+            network_hint,
+            PortNetworkHint::Auto | PortNetworkHint::TcpPort(_)
+        ) && client_host.can_connect_to(ClientStrategy::ExternalTcpPort(self))
+        {
+            Ok((
+                ClientStrategy::ExternalTcpPort(self),
+                Box::new(move |me| {
+                    let me = me.downcast_ref::<GcpComputeEngineHost>().unwrap();
+                    let port = match network_hint {
+                        PortNetworkHint::TcpPort(Some(port)) => port,
+                        _ => {
+                            crate::next_external_port(&me.external_ports, &me.external_port_ranges)
+                        }
+                    };
+                    me.request_port_base(&BaseServerStrategy::ExternalTcpPort(port));
+                    BaseServerStrategy::ExternalTcpPort(port)
+                }),
+            ))
         } else if matches!(network_hint, PortNetworkHint::Auto)
             && client_host.can_connect_to(ClientStrategy::ForwardedTcpPort(self))
         {
@@ -529,12 +810,87 @@ impl Host for GcpComputeEngineHost {
                 if let Some(gcp_target) =
                     <dyn Any>::downcast_ref::<GcpComputeEngineHost>(target_host)
                 {
-                    self.project == gcp_target.project
+                    // VPC networks span all regions, so only the network has to match.
+                    self.location()
+                        .unwrap()
+                        .same_network(&gcp_target.location().unwrap())
                 } else {
                     false
                 }
             }
             ClientStrategy::ForwardedTcpPort(_) => false,
+            // Any cloud host can reach the public IP of another.
+            ClientStrategy::ExternalTcpPort(target_host) => target_host.location().is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LinuxCompileType;
+
+    fn host(id: usize, zone: &str, network: &Arc<GcpNetwork>) -> GcpComputeEngineHost {
+        GcpComputeEngineHost::new(
+            id,
+            "project",
+            "e2-micro",
+            "debian-cloud/debian-12",
+            HostTargetType::Linux(LinuxCompileType::Glibc),
+            zone,
+            network.clone(),
+            None,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn location_splits_zone_and_region() {
+        let location = host(0, "us-west1-a", &GcpNetwork::new("project", None))
+            .location()
+            .unwrap();
+        assert_eq!(location.region, "us-west1");
+        assert_eq!(location.zone.as_deref(), Some("us-west1-a"));
+    }
+
+    #[test]
+    fn networks_of_the_same_existing_vpc_connect_internally() {
+        let a = host(
+            0,
+            "us-west1-a",
+            &GcpNetwork::new("project", Some("shared".to_owned())),
+        );
+        let b = host(
+            1,
+            "us-east1-b",
+            &GcpNetwork::new("project", Some("shared".to_owned())),
+        );
+
+        let (strategy, _) = b.strategy_as_server(&a, PortNetworkHint::Auto).unwrap();
+        assert!(matches!(strategy, ClientStrategy::InternalTcpPort(_)));
+    }
+
+    #[test]
+    fn different_networks_connect_through_public_ips() {
+        let a = host(0, "us-west1-a", &GcpNetwork::new("project", None));
+        let b = host(1, "us-west1-a", &GcpNetwork::new("project", None));
+        let c = host(
+            2,
+            "us-west1-a",
+            &GcpNetwork::new("other-project", Some("shared".to_owned())),
+        );
+        let d = host(
+            3,
+            "us-west1-a",
+            &GcpNetwork::new("project", Some("shared".to_owned())),
+        );
+
+        for (client, server) in [(&a, &b), (&c, &d)] {
+            let (strategy, _) = server
+                .strategy_as_server(client, PortNetworkHint::Auto)
+                .unwrap();
+            assert!(matches!(strategy, ClientStrategy::ExternalTcpPort(_)));
         }
     }
 }
@@ -0,0 +1,301 @@
+//! Callbacks which are run when services reach points in their lifecycle, for example to send
+//! notifications or to react to a crashed service, without changing the deploy loop.
+//!
+//! Hooks are registered either for every service of a [`Deployment`](crate::Deployment), with
+//! [`Deployment::on_ready`](crate::Deployment::on_ready) and friends, or for a single service,
+//! e.g. with [`RustCrateService::on_ready`](crate::rust_crate::service::RustCrateService::on_ready).
+//! Each hook receives a [`HookContext`] describing the service, and hooks of the same event are
+//! run one after another, in the order they were registered (deployment hooks first).
+//!
+//! [`HookEvent::BuildComplete`] and [`HookEvent::Ready`] hooks are run by
+//! [`Deployment::deploy`](crate::Deployment::deploy), which waits for them to finish.
+//! [`HookEvent::Exit`] and [`HookEvent::Crash`] hooks are run in the background once a started
+//! service exits.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use futures::FutureExt;
+use futures::future::BoxFuture;
+
+use crate::{Host, HostLocation, HostTargetType, LaunchedBinary};
+
+/// The number of lines of output kept for [`HookContext::log_tail`].
+pub const LOG_TAIL_LINES: usize = 100;
+
+/// A point in the lifecycle of a service at which hooks are run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HookEvent {
+    /// The service was built and its binary copied to its host.
+    BuildComplete,
+    /// The service was launched, and is listening on its ports.
+    Ready,
+    /// The service exited, for any reason.
+    Exit,
+    /// The service exited with a non-zero exit code without being stopped by the deployment.
+    /// [`HookEvent::Exit`] hooks are run as well.
+    Crash,
+}
+
+/// The host a service runs on, see [`HookContext::host`].
+#[derive(Clone, Debug)]
+pub struct HostInfo {
+    /// The [`Host::id`] of the host.
+    pub id: usize,
+    pub target_type: HostTargetType,
+    /// Where the host is located, or `None` if it is not in a cloud (such as localhost).
+    pub location: Option<HostLocation>,
+}
+
+impl HostInfo {
+    pub(crate) fn new(host: &dyn Host) -> Self {
+        Self {
+            id: host.id(),
+            target_type: host.target_type(),
+            location: host.location(),
+        }
+    }
+}
+
+/// Passed to each hook, describing the service it is run for.
+#[derive(Clone, Debug)]
+pub struct HookContext {
+    pub event: HookEvent,
+    /// The [`Service::display_id`](crate::Service::display_id) of the service.
+    pub display_id: String,
+    /// The host of the service, if known.
+    pub host: Option<HostInfo>,
+    /// The exit code of the service, for [`HookEvent::Exit`] and [`HookEvent::Crash`].
+    pub exit_code: Option<i32>,
+    /// The last [`LOG_TAIL_LINES`] lines of stdout and stderr of the service since it was
+    /// started, for [`HookEvent::Exit`] and [`HookEvent::Crash`]. Empty for other events.
+    pub log_tail: Vec<String>,
+}
+
+type Hook = Arc<dyn Fn(HookContext) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// A set of registered hooks.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    hooks: Vec<(HookEvent, Hook)>,
+}
+
+impl Hooks {
+    /// Registers `hook` to be run on `event`.
+    pub fn add<F, Fut>(&mut self, event: HookEvent, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks
+            .push((event, Arc::new(move |context| hook(context).boxed())));
+    }
+
+    /// Appends the hooks of `other`, which are run after the hooks of `self`.
+    pub(crate) fn extend(&mut self, other: &Hooks) {
+        self.hooks.extend(other.hooks.iter().cloned());
+    }
+
+    /// Runs the hooks registered for `context.event`.
+    pub(crate) async fn run(&self, context: HookContext) {
+        for (_, hook) in self
+            .hooks
+            .iter()
+            .filter(|(event, _)| *event == context.event)
+        {
+            hook(context.clone()).await;
+        }
+    }
+}
+
+/// Watches `binary` until it exits, keeping the tail of its output, then runs the
+/// [`HookEvent::Exit`] (and, if it crashed, [`HookEvent::Crash`]) hooks of `hooks`. The binary is
+/// considered to have crashed unless `stopping` is set by the time it exits.
+///
+/// Gives up silently if the binary is dropped before it is seen to exit.
+pub(crate) async fn watch_exit(
+    binary: Weak<dyn LaunchedBinary>,
+    display_id: String,
+    host: Option<HostInfo>,
+    hooks: Hooks,
+    stopping: Arc<AtomicBool>,
+) {
+    let Some((mut stdout, mut stderr)) = binary
+        .upgrade()
+        .map(|binary| (binary.stdout(), binary.stderr()))
+    else {
+        return;
+    };
+
+    // `LaunchedBinary::wait` may hold the process while waiting, which would prevent it from
+    // being stopped, so the exit code is polled instead.
+    let mut poll = tokio::time::interval(Duration::from_millis(500));
+    let mut log_tail = VecDeque::with_capacity(LOG_TAIL_LINES);
+    let mut push_line = |line: String| {
+        if log_tail.len() == LOG_TAIL_LINES {
+            log_tail.pop_front();
+        }
+        log_tail.push_back(line);
+    };
+    let exit_code = loop {
+        tokio::select! {
+            Some(line) = stdout.recv() => push_line(line),
+            Some(line) = stderr.recv() => push_line(line),
+            _ = poll.tick() => {
+                let Some(binary) = binary.upgrade() else {
+                    return;
+                };
+                if let Some(exit_code) = binary.exit_code() {
+                    break exit_code;
+                }
+            }
+        }
+    };
+    // The last lines may still be in flight once the exit is seen, so wait a little for the
+    // output to end (it may not, if the binary left behind children holding it open).
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        while let Some(line) = stdout.recv().await {
+            push_line(line);
+        }
+        while let Some(line) = stderr.recv().await {
+            push_line(line);
+        }
+    })
+    .await;
+
+    let crashed = exit_code != 0 && !stopping.load(Ordering::SeqCst);
+    let mut context = HookContext {
+        event: HookEvent::Exit,
+        display_id,
+        host,
+        exit_code: Some(exit_code),
+        log_tail: log_tail.into(),
+    };
+    hooks.run(context.clone()).await;
+    if crashed {
+        context.event = HookEvent::Crash;
+        hooks.run(context).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Runs a localhost binary which exits with code 3, and returns the contexts of the
+    /// [`HookEvent::Exit`] and [`HookEvent::Crash`] hooks run for it.
+    #[cfg(unix)]
+    async fn watch_localhost_exit(stopped: bool) -> Vec<HookContext> {
+        use std::process::Stdio;
+
+        use crate::localhost::launched_binary::LaunchedLocalhostBinary;
+
+        let child = async_process::Command::new("sh")
+            .args(["-c", "read line; echo out; echo err >&2; exit 3"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let binary: Arc<dyn LaunchedBinary> = Arc::new(LaunchedLocalhostBinary::new(
+            child,
+            "service/0".to_owned(),
+            None,
+            None,
+        ));
+
+        let contexts = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = Hooks::default();
+        for event in [HookEvent::Exit, HookEvent::Crash] {
+            let contexts = contexts.clone();
+            hooks.add(event, move |context| {
+                let contexts = contexts.clone();
+                async move { contexts.lock().unwrap().push(context) }
+            });
+        }
+
+        tokio::join!(
+            watch_exit(
+                Arc::downgrade(&binary),
+                "service/0".to_owned(),
+                None,
+                hooks,
+                Arc::new(AtomicBool::new(stopped)),
+            ),
+            // Only sent once `watch_exit` subscribed to the output, so that none of it is missed.
+            async { binary.stdin().send("go\n".to_owned()).unwrap() },
+        );
+        Arc::try_unwrap(contexts).unwrap().into_inner().unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_watch_exit_localhost_crash() {
+        let contexts = watch_localhost_exit(false).await;
+        assert_eq!(
+            vec![HookEvent::Exit, HookEvent::Crash],
+            contexts.iter().map(|c| c.event).collect::<Vec<_>>()
+        );
+        for context in contexts {
+            assert_eq!(Some(3), context.exit_code);
+            assert_eq!("service/0", context.display_id);
+            let mut log_tail = context.log_tail;
+            log_tail.sort();
+            assert_eq!(vec!["err", "out"], log_tail);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_watch_exit_localhost_stopped_is_not_crash() {
+        let contexts = watch_localhost_exit(true).await;
+        assert_eq!(
+            vec![HookEvent::Exit],
+            contexts.iter().map(|c| c.event).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(3), contexts[0].exit_code);
+    }
+
+    #[tokio::test]
+    async fn test_run_hooks_of_event_in_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut deployment_hooks = Hooks::default();
+        let mut service_hooks = Hooks::default();
+        for (hooks, name, event) in [
+            (&mut service_hooks, "service exit", HookEvent::Exit),
+            (&mut deployment_hooks, "deployment exit", HookEvent::Exit),
+            (&mut deployment_hooks, "deployment ready", HookEvent::Ready),
+        ] {
+            let calls = calls.clone();
+            hooks.add(event, move |context| {
+                let calls = calls.clone();
+                async move {
+                    calls
+                        .lock()
+                        .unwrap()
+                        .push(format!("{name} {:?}", context.exit_code));
+                }
+            });
+        }
+        deployment_hooks.extend(&service_hooks);
+
+        deployment_hooks
+            .run(HookContext {
+                event: HookEvent::Exit,
+                display_id: "service/0".to_owned(),
+                host: None,
+                exit_code: Some(1),
+                log_tail: Vec::new(),
+            })
+            .await;
+        assert_eq!(
+            vec!["deployment exit Some(1)", "service exit Some(1)"],
+            *calls.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Baking machine images, see [`Deployment::bake_image`](crate::Deployment::bake_image).
+//!
+//! Setting up a fresh cloud instance (for example installing `perf` with the `setup_command` of
+//! [`TracingOptions`](crate::rust_crate::tracing_options::TracingOptions)) is repeated every time
+//! a deployment is brought up. Instead, the setup can be baked once into a machine image by
+//! launching a builder instance from a base image, running the commands of an [`ImageRecipe`] on
+//! it, and snapshotting its boot disk. The resulting image is then passed as the `image` (GCP) or
+//! `ami` (AWS) of the hosts of later deployments, which boot with everything already installed.
+//!
+//! Baked images are not destroyed with the deployment which baked them, since they are meant to
+//! be reused. They are managed by a separate terraform folder, which is printed after baking and
+//! can be destroyed once the image is no longer needed.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+
+use crate::progress::ProgressTracker;
+use crate::{Host, ResourceBatch, ResourcePool, terraform};
+
+/// Installs `perf`, with the package manager of either Debian-based or RPM-based distributions.
+const INSTALL_PERF: &str = "if command -v apt-get >/dev/null; then \
+    sudo apt-get update && sudo DEBIAN_FRONTEND=noninteractive apt-get install -y linux-perf \
+    || sudo DEBIAN_FRONTEND=noninteractive apt-get install -y linux-tools-common linux-tools-generic; \
+    else sudo yum install -y perf; fi";
+
+/// Installs the CA certificates used by binaries which connect to TLS endpoints.
+const INSTALL_CA_CERTIFICATES: &str = "if command -v apt-get >/dev/null; then \
+    sudo apt-get update && sudo DEBIAN_FRONTEND=noninteractive apt-get install -y ca-certificates; \
+    else sudo yum install -y ca-certificates; fi";
+
+/// The setup which is baked into a machine image by
+/// [`Deployment::bake_image`](crate::Deployment::bake_image).
+#[derive(Clone, Debug, Default)]
+pub struct ImageRecipe {
+    commands: Vec<String>,
+}
+
+impl ImageRecipe {
+    /// A recipe which does not run any commands.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A recipe which installs the runtime dependencies of services deployed by Hydro Deploy: CA
+    /// certificates, and `perf` for profiling with
+    /// [`TracingOptions`](crate::rust_crate::tracing_options::TracingOptions), whose
+    /// `setup_command` can then be omitted.
+    pub fn runtime() -> Self {
+        Self::new()
+            .command(INSTALL_CA_CERTIFICATES)
+            .command(INSTALL_PERF)
+    }
+
+    /// Adds a shell command, which is run with `bash` as the SSH user of the host (so commands
+    /// which need root should use `sudo`). Commands run in the order they are added, and baking
+    /// fails if any of them exits with a non-zero exit code.
+    pub fn command(mut self, command: impl Into<String>) -> Self {
+        self.commands.push(command.into());
+        self
+    }
+}
+
+/// Launches `host`, runs the commands of `recipe` on it, and bakes its boot disk into an image
+/// named `image_name`, returning the reference to the image.
+pub(crate) async fn bake(
+    host: Arc<dyn Host>,
+    image_name: &str,
+    recipe: &ImageRecipe,
+) -> Result<String> {
+    // Baking is provisioned separately from the deployment, so that it does not change the
+    // numbering of the deployment's provisioning steps.
+    let mut pool = ResourcePool::default();
+
+    host.request_custom_binary();
+    let mut host_batch = ResourceBatch::new();
+    host.collect_resources(&mut host_batch);
+    let host_result = Arc::new(
+        ProgressTracker::with_group("provision builder", Some(1), || async {
+            host_batch.provision(&mut pool, None, None).await
+        })
+        .await?,
+    );
+    let launched = host.provision(&host_result);
+
+    let commands = recipe
+        .commands
+        .iter()
+        .map(String::as_str)
+        // Flush all writes to the disk before it is snapshotted.
+        .chain(["sync"]);
+    for (i, command) in commands.enumerate() {
+        let binary = launched
+            .launch_command(
+                format!("{image_name} setup {i}"),
+                "bash",
+                &["-c".to_owned(), command.to_owned()],
+                &HashMap::new(),
+            )
+            .await?;
+        let exit_code = binary.wait().await?;
+        if exit_code != 0 {
+            bail!("Image setup command `{command}` failed with exit code {exit_code}");
+        }
+    }
+
+    let mut image_batch = ResourceBatch::new();
+    let image_output = host.collect_image_resources(image_name, &mut image_batch)?;
+    let image_result = ProgressTracker::with_group("bake image", Some(1), || async {
+        image_batch.provision(&mut pool, None, None).await
+    })
+    .await?;
+    let image = image_result
+        .terraform
+        .outputs
+        .get(&image_output)
+        .with_context(|| format!("Missing image output `{image_output}`"))?
+        .value
+        .clone();
+
+    // Keep the image after the builder host is torn down.
+    for folder in image_result.detach() {
+        ProgressTracker::println(format!(
+            "Baked image {image}, destroy it with `{} destroy` in {}",
+            terraform::terraform_name(),
+            folder.display()
+        ));
+    }
+    Ok(image)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::localhost::LocalhostHost;
+
+    /// Bakes `recipe` on localhost, which runs its commands but cannot be snapshotted.
+    async fn bake_locally(recipe: &ImageRecipe) -> anyhow::Error {
+        bake(Arc::new(LocalhostHost::new(0)), "test-image", recipe)
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn runs_commands_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let recipe = ImageRecipe::new()
+            .command(format!("echo first >> {}", log.display()))
+            .command(format!("echo second >> {}", log.display()));
+
+        let error = bake_locally(&recipe).await;
+        assert!(
+            error.to_string().contains("cannot be baked"),
+            "unexpected error: {error:?}"
+        );
+        assert_eq!("first\nsecond\n", std::fs::read_to_string(&log).unwrap());
+    }
+
+    #[tokio::test]
+    async fn stops_at_failing_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let recipe = ImageRecipe::new()
+            .command("exit 3")
+            .command(format!("echo unreachable >> {}", log.display()));
+
+        let error = bake_locally(&recipe).await;
+        assert!(
+            error
+                .to_string()
+                .contains("`exit 3` failed with exit code 3"),
+            "unexpected error: {error:?}"
+        );
+        assert!(!log.exists());
+    }
+}
@@ -1,27 +1,50 @@
 use std::any::Any;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt::Debug;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use append_only_vec::AppendOnlyVec;
 use async_trait::async_trait;
 use hydro_deploy_integration::ServerBindConfig;
+use hydro_deploy_integration::integrity::FrameConfig;
+use rust_crate::assets::Asset;
 use rust_crate::build::BuildOutput;
+use rust_crate::resource_limits::ResourceLimits;
 use rust_crate::tracing_options::TracingOptions;
 use tokio::sync::{mpsc, oneshot};
 
+pub mod cost;
+pub use cost::{CostEstimate, PriceTable};
+
 pub mod deployment;
 pub use deployment::Deployment;
 
+pub mod hooks;
+pub use hooks::{HookContext, HookEvent};
+
+pub mod logs;
+pub use logs::LogDestination;
+
+pub mod profile;
+pub use profile::Profile;
+
 pub mod progress;
 
+#[cfg(feature = "tui")]
+pub mod tui;
+
 pub mod localhost;
-pub use localhost::LocalhostHost;
+pub use localhost::{LocalhostHost, LocalhostIsolation};
 
 pub mod ssh;
 
+pub mod systemd;
+pub use systemd::{SystemdOptions, SystemdRestart};
+
 pub mod gcp;
 pub use gcp::GcpComputeEngineHost;
 
@@ -32,12 +55,19 @@ pub mod aws;
 pub use aws::{AwsEc2Host, AwsNetwork};
 
 pub mod rust_crate;
-pub use rust_crate::RustCrate;
+pub use rust_crate::{PrebuiltCrate, RustCrate};
 
 pub mod custom_service;
 pub use custom_service::CustomService;
 
+pub mod image;
+pub use image::ImageRecipe;
+
+pub mod state;
+pub use state::{DeploymentState, LaunchedState};
+
 pub mod terraform;
+pub use terraform::TerraformBackend;
 
 pub mod util;
 
@@ -48,22 +78,67 @@ pub struct ResourcePool {
 
 pub struct ResourceBatch {
     pub terraform: terraform::TerraformBatch,
+    /// The IDs used in the names of the resources, see [`ResourceBatch::resource_id`].
+    ids: state::ResourceIds,
+    /// The name of the deployment, if its terraform state is stored in a shared backend.
+    deployment_name: Option<String>,
 }
 
 impl ResourceBatch {
     fn new() -> ResourceBatch {
+        Self::resuming(BTreeMap::new(), None)
+    }
+
+    /// A batch whose resources are named with the IDs assigned by a previous run of the
+    /// deployment, see [`state::ResourceIds`]. If the deployment is named (because its state is
+    /// stored in a [`TerraformBackend`]), new IDs are derived from `deployment_name`.
+    fn resuming(
+        previous_ids: BTreeMap<String, String>,
+        deployment_name: Option<String>,
+    ) -> ResourceBatch {
         ResourceBatch {
             terraform: terraform::TerraformBatch::default(),
+            ids: state::ResourceIds::new(previous_ids),
+            deployment_name,
         }
     }
 
+    /// A short ID to name the resource of the given `kind` requested by `instance` with, which
+    /// stays the same when the deployment is resumed (see [`crate::state`]).
+    ///
+    /// When the state of the deployment is stored in a [`TerraformBackend`], the ID is derived
+    /// from the deployment name instead of being random, so that every machine managing the
+    /// deployment names its resources the same way.
+    pub fn resource_id(&mut self, kind: &str, instance: &str) -> String {
+        let deployment_name = self.deployment_name.as_deref();
+        self.ids.get(kind, instance, |key| match deployment_name {
+            Some(deployment_name) => terraform::deterministic_resource_id(deployment_name, key),
+            None => nanoid::nanoid!(8, &terraform::TERRAFORM_ALPHABET),
+        })
+    }
+
+    /// The IDs assigned with [`ResourceBatch::resource_id`].
+    pub(crate) fn resource_ids(&self) -> &BTreeMap<String, String> {
+        self.ids.assigned()
+    }
+
     async fn provision(
         self,
         pool: &mut ResourcePool,
         last_result: Option<Arc<ResourceResult>>,
+        state: Option<Arc<state::StateFile>>,
+        frame: FrameConfig,
     ) -> Result<ResourceResult> {
+        let existing = state
+            .as_ref()
+            .and_then(|state| state.previous_terraform_folder());
         Ok(ResourceResult {
-            terraform: self.terraform.provision(&mut pool.terraform).await?,
+            terraform: self
+                .terraform
+                .provision_in(&mut pool.terraform, existing)
+                .await?,
+            state,
+            frame,
             _last_result: last_result,
         })
     }
@@ -72,9 +147,27 @@ impl ResourceBatch {
 #[derive(Debug)]
 pub struct ResourceResult {
     pub terraform: terraform::TerraformResult,
+    /// The state file the deployment is recorded to, if it is resumable.
+    pub(crate) state: Option<Arc<state::StateFile>>,
+    /// How the frames sent between services are encoded.
+    pub(crate) frame: FrameConfig,
     _last_result: Option<Arc<ResourceResult>>,
 }
 
+impl ResourceResult {
+    /// Leaves the resources of this and all previous provisioning steps running when they are
+    /// dropped, returning the folders from which they can be destroyed later.
+    fn detach(&self) -> Vec<std::path::PathBuf> {
+        let mut folders = self
+            ._last_result
+            .as_ref()
+            .map(|last| last.detach())
+            .unwrap_or_default();
+        folders.extend(self.terraform.detach().map(std::path::Path::to_owned));
+        folders
+    }
+}
+
 #[cfg(feature = "profile-folding")]
 #[derive(Clone, Debug)]
 pub struct TracingResults {
@@ -96,6 +189,22 @@ pub trait LaunchedBinary: Send + Sync {
     fn stdout_filter(&self, prefix: String) -> mpsc::UnboundedReceiver<String>;
     fn stderr_filter(&self, prefix: String) -> mpsc::UnboundedReceiver<String>;
 
+    /// Appends every subsequent line of stdout and stderr to `stdout` and `stderr` respectively.
+    /// Used to retain logs, see [`logs`].
+    ///
+    /// By default, this takes the lines through [`Self::stdout`] and [`Self::stderr`], so they no
+    /// longer reach the fallback receiver. Implementations should override it to retain the lines
+    /// without affecting the other receivers.
+    fn retain_output(&self, stdout: logs::RetainedLines, stderr: logs::RetainedLines) {
+        for (mut receiver, lines) in [(self.stdout(), stdout), (self.stderr(), stderr)] {
+            tokio::spawn(async move {
+                while let Some(line) = receiver.recv().await {
+                    lines.push(line);
+                }
+            });
+        }
+    }
+
     #[cfg(feature = "profile-folding")]
     fn tracing_results(&self) -> Option<&TracingResults>;
 
@@ -103,8 +212,37 @@ pub trait LaunchedBinary: Send + Sync {
 
     /// Wait for the process to stop on its own. Returns the exit code.
     async fn wait(&self) -> Result<i32>;
+
+    /// Suspends the process, so that it makes no progress (and neither sends nor receives
+    /// messages) until [`LaunchedBinary::resume`] is called. Used to inject faults in tests.
+    ///
+    /// This stops the whole process (with `SIGSTOP` on localhost), so every location compiled
+    /// into the binary and all of its connections are suspended together. Processes it spawned
+    /// keep running.
+    async fn suspend(&self) -> Result<()> {
+        bail!("Suspending is not supported for this binary.")
+    }
+
+    /// Resumes a process suspended with [`LaunchedBinary::suspend`].
+    async fn resume(&self) -> Result<()> {
+        bail!("Resuming is not supported for this binary.")
+    }
+
     /// If the process is still running, force stop it. Then run post-run tasks.
     async fn stop(&self) -> Result<()>;
+
+    /// Leaves the process running after the deployment is dropped, returning instructions for
+    /// reattaching to it, or `None` if the process cannot outlive the deployment.
+    fn detach(&self) -> Option<String> {
+        None
+    }
+
+    /// Identifies the process to [`LaunchedHost::reattach_binary`], so that a resumed deployment
+    /// can reattach to it (see [`crate::state`]), or `None` if the process cannot outlive the
+    /// deployment.
+    fn resume_handle(&self) -> Option<String> {
+        None
+    }
 }
 
 #[async_trait]
@@ -113,6 +251,17 @@ pub trait LaunchedHost: Send + Sync {
     /// to listen to network connections (such as the IP address to bind to).
     fn base_server_config(&self, strategy: &BaseServerStrategy) -> ServerBindConfig;
 
+    /// The public IP of this host, which hosts in other networks can connect to, if it has one.
+    fn external_ip(&self) -> Option<String> {
+        None
+    }
+
+    /// The public IPv6 address of this host, if it has one (see [`IpStack::DualStack`]). Hosts
+    /// in other networks connect to it when the host has no public IPv4 address.
+    fn external_ipv6(&self) -> Option<String> {
+        None
+    }
+
     fn server_config(&self, strategy: &ServerStrategy) -> ServerBindConfig {
         match strategy {
             ServerStrategy::Direct(b) => self.base_server_config(b),
@@ -138,8 +287,34 @@ pub trait LaunchedHost: Send + Sync {
         }
     }
 
+    /// Computes the configuration of every port that the service with the given `service_id`
+    /// listens on, by port name. Hosts which isolate their services from each other override
+    /// this to give each service its own addresses.
+    fn service_server_configs(
+        &self,
+        service_id: usize,
+        strategies: Vec<(&str, &ServerStrategy)>,
+    ) -> HashMap<String, ServerBindConfig> {
+        let _ = service_id;
+        strategies
+            .into_iter()
+            .map(|(name, strategy)| (name.to_owned(), self.server_config(strategy)))
+            .collect()
+    }
+
     async fn copy_binary(&self, binary: &BuildOutput) -> Result<()>;
 
+    /// Copies `assets` into the asset directory of the service with the given `service_id`,
+    /// replacing any previous copies, and returns the path of the directory on the host.
+    async fn copy_assets(&self, service_id: usize, assets: &[Asset]) -> Result<PathBuf>;
+
+    /// The directory on the host in which the service with the given `service_id` keeps its
+    /// persistent data. The directory is kept when the service is restarted, and is created by
+    /// the service itself when it is first used. Remote hosts also keep it across runs of the
+    /// deployment, while localhost only does so with
+    /// [`LocalhostHost::with_data_root`](localhost::LocalhostHost::with_data_root).
+    fn data_dir(&self, service_id: usize) -> PathBuf;
+
     async fn launch_binary(
         &self,
         id: String,
@@ -150,6 +325,50 @@ pub trait LaunchedHost: Send + Sync {
         pin_to_core: Option<usize>,
     ) -> Result<Box<dyn LaunchedBinary>>;
 
+    /// Like [`LaunchedHost::launch_binary`], but with the controls in `resources` applied to the
+    /// binary. By default, only pinning the binary to a single core is supported.
+    async fn launch_binary_with_limits(
+        &self,
+        id: String,
+        binary: &BuildOutput,
+        args: &[String],
+        perf: Option<TracingOptions>,
+        env: &HashMap<String, String>,
+        resources: &ResourceLimits,
+    ) -> Result<Box<dyn LaunchedBinary>> {
+        let Ok(pin_to_core) = resources.as_pin_to_core() else {
+            bail!("[{id}] this host does not support the resource limits {resources:?}");
+        };
+        self.launch_binary(id, binary, args, perf, env, pin_to_core)
+            .await
+    }
+
+    /// Reattaches to the binary identified by `handle` (see [`LaunchedBinary::resume_handle`]),
+    /// which was launched by a previous run of the deployment. Returns `None` if it is no longer
+    /// running, or if this host cannot reattach to binaries.
+    async fn reattach_binary(
+        &self,
+        id: String,
+        handle: &str,
+    ) -> Result<Option<Box<dyn LaunchedBinary>>> {
+        let _ = (id, handle);
+        Ok(None)
+    }
+
+    /// Launches an arbitrary program which is already installed on the host, such as a sidecar
+    /// process attached to a service. By default, hosts cannot launch programs other than
+    /// service binaries.
+    async fn launch_command(
+        &self,
+        id: String,
+        program: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<Box<dyn LaunchedBinary>> {
+        let _ = (args, env);
+        bail!("[{id}] this host does not support launching `{program}`");
+    }
+
     async fn forward_port(&self, addr: &SocketAddr) -> Result<SocketAddr>;
 }
 
@@ -187,6 +406,57 @@ pub enum ClientStrategy<'a> {
         /// The host that this port is available on.
         &'a dyn Host,
     ),
+    /// A TCP port reached through the public IP of its host, for hosts in different networks or
+    /// clouds.
+    ExternalTcpPort(
+        /// The host that this port is available on.
+        &'a dyn Host,
+    ),
+}
+
+/// Where a host is located, used to choose how other hosts connect to it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HostLocation {
+    /// The cloud provider, such as `"aws"`, `"gcp"`, or `"azure"`.
+    pub cloud: &'static str,
+    /// The region of the cloud provider the host is in.
+    pub region: String,
+    /// The availability zone within the region, if known.
+    pub zone: Option<String>,
+    /// An identifier of the private network (such as a VPC) the host is in. Hosts in the same
+    /// network can reach each other through their internal IPs.
+    pub network: String,
+}
+
+impl HostLocation {
+    /// Whether both locations are in the same private network of the same cloud.
+    pub fn same_network(&self, other: &HostLocation) -> bool {
+        self.cloud == other.cloud && self.network == other.network
+    }
+
+    /// Whether both locations are in the same region of the same cloud.
+    pub fn same_region(&self, other: &HostLocation) -> bool {
+        self.cloud == other.cloud && self.region == other.region
+    }
+
+    /// Whether both locations are in the same availability zone, which is only known if both
+    /// have a zone.
+    pub fn same_zone(&self, other: &HostLocation) -> bool {
+        self.same_region(other) && self.zone.is_some() && self.zone == other.zone
+    }
+}
+
+/// The IP versions a cloud host can be reached over from outside its network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum IpStack {
+    /// Only IPv4.
+    #[default]
+    V4,
+    /// Both IPv4 and IPv6. The host is given a public IPv6 address in addition to its public IPv4
+    /// address, and its external ports are bound on both (see
+    /// [`ServerBindConfig::DualStackTcpPort`]). Hosts within the network still connect to each
+    /// other over IPv4.
+    DualStack,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -209,11 +479,44 @@ pub enum PortNetworkHint {
 
 pub type HostStrategyGetter = Box<dyn FnOnce(&dyn Any) -> BaseServerStrategy>;
 
+/// The first port handed out by [`next_external_port`].
+const EXTERNAL_PORT_START: u16 = 30000;
+
+/// Chooses a port to serve on through the public IP of a cloud host, given the ports and port
+/// ranges already opened in its firewall. The caller should then request it with
+/// [`BaseServerStrategy::ExternalTcpPort`].
+pub(crate) fn next_external_port(
+    external_ports: &Mutex<Vec<u16>>,
+    external_port_ranges: &Mutex<Vec<RangeInclusive<u16>>>,
+) -> u16 {
+    let external_port_ranges = external_port_ranges.lock().unwrap();
+    external_ports
+        .lock()
+        .unwrap()
+        .iter()
+        .copied()
+        .chain(external_port_ranges.iter().map(|range| *range.end()))
+        .filter(|port| EXTERNAL_PORT_START <= *port)
+        .max()
+        .map_or(EXTERNAL_PORT_START, |port| port + 1)
+}
+
 pub trait Host: Any + Send + Sync + Debug {
     fn target_type(&self) -> HostTargetType;
 
     fn request_port_base(&self, bind_type: &BaseServerStrategy);
 
+    /// Requests every port in `ports` as a [`BaseServerStrategy::ExternalTcpPort`], for a service
+    /// which binds one of them that is only known once it is launched. Cloud hosts open the range
+    /// with a single firewall rule instead of one per port, so they return an error if the range
+    /// was not already requested before the host was launched.
+    fn request_port_range(&self, ports: RangeInclusive<u16>) -> Result<()> {
+        for port in ports {
+            self.request_port_base(&BaseServerStrategy::ExternalTcpPort(port));
+        }
+        Ok(())
+    }
+
     fn request_port(&self, bind_type: &ServerStrategy) {
         match bind_type {
             ServerStrategy::Direct(base) => self.request_port_base(base),
@@ -253,6 +556,23 @@ pub trait Host: Any + Send + Sync + Debug {
 
     fn launched(&self) -> Option<Arc<dyn LaunchedHost>>;
 
+    /// Adds the resources which bake the boot disk of this (launched) host into a machine image
+    /// named `image_name` to `resource_batch`, returning the name of the terraform output which
+    /// holds the reference to the image. See [`crate::image`].
+    fn collect_image_resources(
+        &self,
+        image_name: &str,
+        resource_batch: &mut ResourceBatch,
+    ) -> Result<String> {
+        let _ = (image_name, resource_batch);
+        bail!("{:?} cannot be baked into a machine image", self)
+    }
+
+    /// Where this host is located, or `None` if it is not in a cloud (such as localhost).
+    fn location(&self) -> Option<HostLocation> {
+        None
+    }
+
     /// Identifies a network type that this host can use for connections if it is the server.
     /// The host will be `None` if the connection is from the same host as the target.
     fn strategy_as_server<'a>(
@@ -267,6 +587,14 @@ pub trait Host: Any + Send + Sync + Debug {
 
 #[async_trait]
 pub trait Service: Send + Sync {
+    /// An identifier for this service, which is unique within a deployment. Used to report the
+    /// progress of the service, and to identify it in the deployment TUI.
+    ///
+    /// By default, this is derived from the address of the service.
+    fn display_id(&self) -> String {
+        format!("service@{:p}", self as *const Self as *const ())
+    }
+
     /// Makes requests for physical resources server ports that this service needs to run.
     /// This should **not** recursively call `collect_resources` on the host, since
     /// we guarantee that `collect_resources` is only called once per host.
@@ -287,6 +615,82 @@ pub trait Service: Send + Sync {
 
     /// Stops the service by having it disconnect from other services and stop computations.
     async fn stop(&self) -> Result<()>;
+
+    /// Drains the service by having it stop accepting input, finish sending any outstanding
+    /// data, and then exit. Services which do not support draining are stopped instead.
+    async fn drain(&self) -> Result<()> {
+        self.stop().await
+    }
+
+    /// Starts retaining the output of the service from its next launch, so that it can be
+    /// archived with [`Deployment::archive_logs`]. Services which do not support this ignore it.
+    fn retain_logs(&self) {}
+
+    /// The output retained since [`Service::retain_logs`] was called, if any.
+    fn logs(&self) -> Option<logs::ServiceLogs> {
+        None
+    }
+
+    /// Leaves the service running after the deployment is dropped, returning instructions for
+    /// reattaching to it, or `None` if the service cannot outlive the deployment.
+    fn detach(&self) -> Option<String> {
+        None
+    }
+
+    /// The host the service runs on, if it runs on a single host.
+    fn host(&self) -> Option<Arc<dyn Host>> {
+        None
+    }
+
+    /// The running binary of the service, if it has been launched and runs a single binary. Used
+    /// to run the [`HookEvent::Exit`] and [`HookEvent::Crash`] hooks once it exits.
+    fn current_binary(&self) -> Option<Arc<dyn LaunchedBinary>> {
+        None
+    }
+
+    /// The hooks registered for this service, which are run after those registered for the
+    /// whole deployment. See [`hooks`].
+    fn hooks(&self) -> hooks::Hooks {
+        hooks::Hooks::default()
+    }
+
+    /// Restarts a started service, by stopping it and then launching and starting it again.
+    ///
+    /// Ports which were chosen automatically are bound again on the same port, so that the
+    /// addresses other services were given to connect to it stay valid.
+    async fn restart(&self) -> Result<()> {
+        bail!(
+            "Service `{}` does not support restarting.",
+            self.display_id()
+        )
+    }
+
+    /// A hash of the code of the service and its deployment parameters, such as its arguments
+    /// and the servers it connects to. Used by [`Deployment::deploy_incremental`] to find the
+    /// services which changed since they were started. Services which return `None` are never
+    /// redeployed incrementally.
+    ///
+    /// The fingerprint must only depend on the code and configuration of the service, and not on
+    /// its runtime state (such as the ports its servers are currently bound to).
+    async fn fingerprint(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// The display IDs of the services whose servers this service connects to, which must be
+    /// restarted along with it when they are redeployed.
+    fn server_dependencies(&self) -> BTreeSet<String> {
+        BTreeSet::new()
+    }
+
+    /// Redeploys a started service whose [`Service::fingerprint`] changed, or which connects to
+    /// a redeployed service: rebuilds and uploads its code if that changed, then stops and
+    /// launches it again. The service is started again by the next call to [`Service::start`].
+    async fn redeploy(&self) -> Result<()> {
+        bail!(
+            "Service `{}` does not support redeploying.",
+            self.display_id()
+        )
+    }
 }
 
 pub trait ServiceBuilder {
@@ -33,6 +33,7 @@ use super::samply::samply_to_folded;
 use crate::LaunchedBinary;
 #[cfg(feature = "profile-folding")]
 use crate::TracingResults;
+use crate::logs::RetainedLines;
 use crate::progress::ProgressTracker;
 #[cfg(feature = "profile-folding")]
 use crate::rust_crate::flamegraph::handle_fold_data;
@@ -46,6 +47,9 @@ pub(super) struct TracingDataLocal {
 pub struct LaunchedLocalhostBinary {
     /// Must use async mutex -- we will .await methods within the child (while holding lock).
     child: tokio::sync::Mutex<async_process::Child>,
+    /// Stored separately, since the `child` lock is held while waiting for the process to exit.
+    #[cfg(unix)]
+    pid: u32,
     tracing_config: Option<TracingOptions>,
     tracing_data_local: std::sync::Mutex<Option<TracingDataLocal>>,
     #[cfg(feature = "profile-folding")]
@@ -75,7 +79,7 @@ impl Drop for LaunchedLocalhostBinary {
 }
 
 impl LaunchedLocalhostBinary {
-    pub(super) fn new(
+    pub(crate) fn new(
         mut child: async_process::Child,
         id: String,
         tracing_config: Option<TracingOptions>,
@@ -104,6 +108,8 @@ impl LaunchedLocalhostBinary {
         );
 
         Self {
+            #[cfg(unix)]
+            pid: child.id(),
             child: tokio::sync::Mutex::new(child),
             tracing_config,
             tracing_data_local: std::sync::Mutex::new(tracing_data_local),
@@ -142,6 +148,11 @@ impl LaunchedBinary for LaunchedLocalhostBinary {
         self.stderr_broadcast.receive(Some(prefix))
     }
 
+    fn retain_output(&self, stdout: RetainedLines, stderr: RetainedLines) {
+        self.stdout_broadcast.retain(stdout);
+        self.stderr_broadcast.retain(stderr);
+    }
+
     #[cfg(feature = "profile-folding")]
     fn tracing_results(&self) -> Option<&TracingResults> {
         self.tracing_results.get()
@@ -160,6 +171,24 @@ impl LaunchedBinary for LaunchedLocalhostBinary {
         Ok(exit_code(self.child.lock().await.status().await?))
     }
 
+    #[cfg(unix)]
+    async fn suspend(&self) -> Result<()> {
+        nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(self.pid as i32),
+            nix::sys::signal::SIGSTOP,
+        )?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    async fn resume(&self) -> Result<()> {
+        nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(self.pid as i32),
+            nix::sys::signal::SIGCONT,
+        )?;
+        Ok(())
+    }
+
     async fn stop(&self) -> Result<()> {
         if let Err(err) = { self.child.lock().await.kill() }
             && !matches!(err.kind(), std::io::ErrorKind::InvalidInput)
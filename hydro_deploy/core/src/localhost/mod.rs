@@ -1,18 +1,22 @@
 use std::collections::HashMap;
-use std::net::SocketAddr;
-use std::sync::{Arc, OnceLock};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 use async_process::{Command, Stdio};
 use async_trait::async_trait;
 use hydro_deploy_integration::ServerBindConfig;
 
 use crate::progress::ProgressTracker;
+use crate::rust_crate::assets::Asset;
 use crate::rust_crate::build::BuildOutput;
+use crate::rust_crate::resource_limits::ResourceLimits;
 use crate::rust_crate::tracing_options::TracingOptions;
 use crate::{
     BaseServerStrategy, ClientStrategy, Host, HostStrategyGetter, HostTargetType, LaunchedBinary,
-    LaunchedHost, PortNetworkHint, ResourceBatch, ResourceResult,
+    LaunchedHost, PortNetworkHint, ResourceBatch, ResourceResult, ServerStrategy,
 };
 
 pub mod launched_binary;
@@ -24,10 +28,37 @@ mod samply;
 
 static LOCAL_LIBDIR: OnceLock<String> = OnceLock::new();
 
+/// How the services on a [`LocalhostHost`] are isolated from each other's network ports.
+///
+/// By default, all services on localhost listen on `127.0.0.1` (or on Unix sockets) with
+/// OS-assigned ports. With isolation enabled, services always communicate over TCP, and each
+/// service listens on its own addresses, so that large deployments on one machine behave closer
+/// to deployments across many hosts, and a stray connection cannot reach the wrong service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LocalhostIsolation {
+    /// No isolation.
+    #[default]
+    None,
+    /// Each service listens on its own loopback address (`127.1.0.1`, `127.1.0.2`, ...), which
+    /// gives it a separate port space, like a separate network namespace. Only supported on
+    /// Linux, which routes all of `127.0.0.0/8` to the loopback interface.
+    LoopbackAddress,
+    /// Each service listens on `127.0.0.1`, on ports from its own block: the `n`-th service
+    /// launched uses ports starting at `base + n * block_size`. Ports explicitly requested with
+    /// a network hint are not changed.
+    PortOffset {
+        /// The first port of the first service's block.
+        base: u16,
+        /// The number of ports in each service's block.
+        block_size: u16,
+    },
+}
+
 #[derive(Debug)]
 pub struct LocalhostHost {
     pub id: usize,
     client_only: bool,
+    launched: Arc<LaunchedLocalhost>,
 }
 
 impl LocalhostHost {
@@ -35,6 +66,7 @@ impl LocalhostHost {
         LocalhostHost {
             id,
             client_only: false,
+            launched: Arc::new(LaunchedLocalhost::default()),
         }
     }
 
@@ -42,8 +74,55 @@ impl LocalhostHost {
         LocalhostHost {
             id: self.id,
             client_only: true,
+            launched: self.launched.clone(),
         }
     }
+
+    /// Isolates the ports of the services on this host from each other, see
+    /// [`LocalhostIsolation`].
+    pub fn with_isolation(self, isolation: LocalhostIsolation) -> LocalhostHost {
+        if isolation == LocalhostIsolation::LoopbackAddress && !cfg!(target_os = "linux") {
+            panic!("`LocalhostIsolation::LoopbackAddress` is only supported on Linux");
+        }
+
+        LocalhostHost {
+            id: self.id,
+            client_only: self.client_only,
+            launched: Arc::new(LaunchedLocalhost {
+                isolation,
+                service_indices: Mutex::default(),
+                data_root: self.launched.data_root.clone(),
+            }),
+        }
+    }
+
+    /// Keeps the data directory of each service (see
+    /// [`LaunchedHost::data_dir`](crate::LaunchedHost::data_dir)) in `data_root`, so that it is
+    /// kept across runs of the deployment. By default, data directories are only kept while the
+    /// deploying process runs, since concurrent deployments on the same machine must not share
+    /// them.
+    pub fn with_data_root(self, data_root: impl Into<PathBuf>) -> LocalhostHost {
+        LocalhostHost {
+            id: self.id,
+            client_only: self.client_only,
+            launched: Arc::new(LaunchedLocalhost {
+                isolation: self.launched.isolation,
+                service_indices: Mutex::default(),
+                data_root: Some(data_root.into()),
+            }),
+        }
+    }
+
+    /// The isolation of the services on this host.
+    pub fn isolation(&self) -> LocalhostIsolation {
+        self.launched.isolation
+    }
+
+    /// The directory holding the data directories of the services, if set with
+    /// [`Self::with_data_root`].
+    pub fn data_root(&self) -> Option<&Path> {
+        self.launched.data_root.as_deref()
+    }
 }
 
 impl Host for LocalhostHost {
@@ -52,6 +131,9 @@ impl Host for LocalhostHost {
     }
 
     fn request_port_base(&self, _bind_type: &BaseServerStrategy) {}
+    fn request_port_range(&self, _ports: RangeInclusive<u16>) -> Result<()> {
+        Ok(())
+    }
     fn collect_resources(&self, _resource_batch: &mut ResourceBatch) {}
     fn request_custom_binary(&self) {}
 
@@ -60,11 +142,11 @@ impl Host for LocalhostHost {
     }
 
     fn launched(&self) -> Option<Arc<dyn LaunchedHost>> {
-        Some(Arc::new(LaunchedLocalhost))
+        Some(self.launched.clone())
     }
 
     fn provision(&self, _resource_result: &Arc<ResourceResult>) -> Arc<dyn LaunchedHost> {
-        Arc::new(LaunchedLocalhost)
+        self.launched.clone()
     }
 
     fn strategy_as_server<'a>(
@@ -76,7 +158,9 @@ impl Host for LocalhostHost {
             anyhow::bail!("Localhost cannot be a server if it is client only")
         }
 
+        // Unix sockets bypass the network entirely, so isolated services always use TCP.
         if matches!(network_hint, PortNetworkHint::Auto)
+            && self.isolation() == LocalhostIsolation::None
             && connection_from.can_connect_to(ClientStrategy::UnixSocket(self.id))
         {
             Ok((
@@ -104,13 +188,14 @@ impl Host for LocalhostHost {
 
     fn can_connect_to(&self, typ: ClientStrategy) -> bool {
         match typ {
+            // Named pipes stand in for Unix sockets on Windows.
             ClientStrategy::UnixSocket(id) => {
-                #[cfg(unix)]
+                #[cfg(any(unix, windows))]
                 {
                     self.id == id
                 }
 
-                #[cfg(not(unix))]
+                #[cfg(not(any(unix, windows)))]
                 {
                     let _ = id;
                     false
@@ -118,11 +203,89 @@ impl Host for LocalhostHost {
             }
             ClientStrategy::InternalTcpPort(target_host) => self.id == target_host.id(),
             ClientStrategy::ForwardedTcpPort(_) => true,
+            // Ports forwarded over SSH are preferred, as they do not need to be publicly exposed.
+            ClientStrategy::ExternalTcpPort(_) => false,
         }
     }
 }
 
-struct LaunchedLocalhost;
+#[derive(Debug, Default)]
+struct LaunchedLocalhost {
+    isolation: LocalhostIsolation,
+    /// The order in which services were first launched, used to assign their isolated addresses.
+    service_indices: Mutex<HashMap<usize, u32>>,
+    /// The directory holding the data directories of the services, see
+    /// [`LocalhostHost::with_data_root`].
+    data_root: Option<PathBuf>,
+}
+
+impl LaunchedLocalhost {
+    fn service_index(&self, service_id: usize) -> u32 {
+        let mut service_indices = self.service_indices.lock().unwrap();
+        let next_index = service_indices.len() as u32;
+        *service_indices.entry(service_id).or_insert(next_index)
+    }
+
+    /// Moves every TCP port in `config` to the addresses reserved for the service, assigning
+    /// ports from its block in order for [`LocalhostIsolation::PortOffset`].
+    fn isolate(
+        &self,
+        service_index: u32,
+        config: ServerBindConfig,
+        next_offset: &mut u16,
+    ) -> ServerBindConfig {
+        match config {
+            ServerBindConfig::TcpPort(host, port) => match self.isolation {
+                LocalhostIsolation::None => ServerBindConfig::TcpPort(host, port),
+                LocalhostIsolation::LoopbackAddress => {
+                    let address =
+                        Ipv4Addr::from(u32::from(Ipv4Addr::new(127, 1, 0, 1)) + service_index);
+                    ServerBindConfig::TcpPort(address.to_string(), port)
+                }
+                LocalhostIsolation::PortOffset { base, block_size } => {
+                    let port = port.or_else(|| {
+                        assert!(
+                            *next_offset < block_size,
+                            "service uses more than {block_size} ports, increase the `block_size` of `LocalhostIsolation::PortOffset`"
+                        );
+                        let port = u32::from(base)
+                            + service_index * u32::from(block_size)
+                            + u32::from(*next_offset);
+                        *next_offset += 1;
+                        Some(u16::try_from(port).expect(
+                            "ran out of ports for `LocalhostIsolation::PortOffset`, use a lower `base` or `block_size`",
+                        ))
+                    });
+                    ServerBindConfig::TcpPort(host, port)
+                }
+            },
+            ServerBindConfig::Demux(demux) => ServerBindConfig::Demux(
+                demux
+                    .into_iter()
+                    .map(|(key, underlying)| {
+                        (key, self.isolate(service_index, underlying, next_offset))
+                    })
+                    .collect(),
+            ),
+            ServerBindConfig::Merge(merge) => ServerBindConfig::Merge(
+                merge
+                    .into_iter()
+                    .map(|underlying| self.isolate(service_index, underlying, next_offset))
+                    .collect(),
+            ),
+            ServerBindConfig::Tagged(underlying, id) => ServerBindConfig::Tagged(
+                Box::new(self.isolate(service_index, *underlying, next_offset)),
+                id,
+            ),
+            ServerBindConfig::MultiConnection(underlying) => ServerBindConfig::MultiConnection(
+                Box::new(self.isolate(service_index, *underlying, next_offset)),
+            ),
+            other @ (ServerBindConfig::UnixSocket
+            | ServerBindConfig::DualStackTcpPort(..)
+            | ServerBindConfig::Null) => other,
+        }
+    }
+}
 
 #[async_trait]
 impl LaunchedHost for LaunchedLocalhost {
@@ -136,10 +299,66 @@ impl LaunchedHost for LaunchedLocalhost {
         }
     }
 
+    fn service_server_configs(
+        &self,
+        service_id: usize,
+        strategies: Vec<(&str, &ServerStrategy)>,
+    ) -> HashMap<String, ServerBindConfig> {
+        if self.isolation == LocalhostIsolation::None {
+            return strategies
+                .into_iter()
+                .map(|(name, strategy)| (name.to_owned(), self.server_config(strategy)))
+                .collect();
+        }
+
+        let service_index = self.service_index(service_id);
+        let mut next_offset = 0;
+        strategies
+            .into_iter()
+            .map(|(name, strategy)| {
+                let config = self.server_config(strategy);
+                (
+                    name.to_owned(),
+                    self.isolate(service_index, config, &mut next_offset),
+                )
+            })
+            .collect()
+    }
+
     async fn copy_binary(&self, _binary: &BuildOutput) -> Result<()> {
         Ok(())
     }
 
+    fn data_dir(&self, service_id: usize) -> PathBuf {
+        match &self.data_root {
+            Some(data_root) => data_root.join(format!("service-{service_id}")),
+            // Services of concurrent deployments must not share data directories.
+            None => {
+                std::env::temp_dir().join(format!("hydro-data-{}-{service_id}", std::process::id()))
+            }
+        }
+    }
+
+    async fn copy_assets(&self, service_id: usize, assets: &[Asset]) -> Result<PathBuf> {
+        // Services of concurrent deployments must not share asset directories.
+        let asset_dir =
+            std::env::temp_dir().join(format!("hydro-assets-{}-{service_id}", std::process::id()));
+        if tokio::fs::try_exists(&asset_dir).await? {
+            tokio::fs::remove_dir_all(&asset_dir).await?;
+        }
+
+        for asset in assets {
+            let remote_path = asset.remote_path_in(&asset_dir);
+            if let Some(parent) = remote_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::copy(&asset.local_path, &remote_path)
+                .await
+                .with_context(|| format!("copying asset {}", asset.local_path.display()))?;
+        }
+        Ok(asset_dir)
+    }
+
     async fn launch_binary(
         &self,
         id: String,
@@ -149,11 +368,48 @@ impl LaunchedHost for LaunchedLocalhost {
         env: &HashMap<String, String>,
         pin_to_core: Option<usize>,
     ) -> Result<Box<dyn LaunchedBinary>> {
-        if pin_to_core.is_some() {
-            ProgressTracker::println(format!(
-                "[{id}] pin_to_core is not supported on localhost, ignoring"
-            ));
-        }
+        self.launch_binary_with_limits(
+            id,
+            binary,
+            args,
+            tracing,
+            env,
+            &ResourceLimits::pinned_to(pin_to_core),
+        )
+        .await
+    }
+
+    async fn launch_binary_with_limits(
+        &self,
+        id: String,
+        binary: &BuildOutput,
+        args: &[String],
+        tracing: Option<TracingOptions>,
+        env: &HashMap<String, String>,
+        resources: &ResourceLimits,
+    ) -> Result<Box<dyn LaunchedBinary>> {
+        let prefix = if cfg!(target_os = "linux") {
+            resources.command_prefix(None)
+        } else if resources.memory_limit.is_some() || resources.max_open_files.is_some() {
+            bail!("[{id}] memory and open file limits are only supported on Linux");
+        } else {
+            if resources.cpu_affinity.is_some() {
+                ProgressTracker::println(format!(
+                    "[{id}] CPU affinity is only supported on Linux, ignoring"
+                ));
+            }
+            vec![]
+        };
+        // Runs `program` under the programs which apply the resource limits.
+        let limited_command = |program: &std::ffi::OsStr| {
+            if let Some((limiter, limiter_args)) = prefix.split_first() {
+                let mut command = Command::new(limiter);
+                command.args(limiter_args).arg(program);
+                command
+            } else {
+                Command::new(program)
+            }
+        };
 
         let (maybe_perf_outfile, mut command) = if let Some(tracing) = tracing.as_ref() {
             if cfg!(any(target_os = "macos", target_family = "windows")) {
@@ -163,7 +419,7 @@ impl LaunchedHost for LaunchedLocalhost {
                 );
                 let samply_outfile = tempfile::NamedTempFile::new()?;
 
-                let mut command = Command::new("samply");
+                let mut command = limited_command("samply".as_ref());
                 command
                     .arg("record")
                     .arg("--save-only")
@@ -177,7 +433,7 @@ impl LaunchedHost for LaunchedLocalhost {
                 ProgressTracker::println(format!("[{} tracing] Tracing binary with `perf`.", id));
                 let perf_outfile = tempfile::NamedTempFile::new()?;
 
-                let mut command = Command::new("perf");
+                let mut command = limited_command("perf".as_ref());
                 command
                     .args([
                         "record",
@@ -201,7 +457,7 @@ impl LaunchedHost for LaunchedLocalhost {
                 );
             }
         } else {
-            let mut command = Command::new(&binary.bin_path);
+            let mut command = limited_command(binary.bin_path.as_os_str());
             command.args(args);
             (None, command)
         };
@@ -280,7 +536,158 @@ impl LaunchedHost for LaunchedLocalhost {
         )))
     }
 
+    async fn launch_command(
+        &self,
+        id: String,
+        program: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<Box<dyn LaunchedBinary>> {
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        #[cfg(not(target_family = "unix"))]
+        command.kill_on_drop(true);
+
+        ProgressTracker::println(format!("[{}] running command: `{:?}`", id, command));
+
+        let child = command.spawn().map_err(|e| {
+            anyhow::Error::new(e).context(format!("Failed to execute command: {:?}", command))
+        })?;
+
+        Ok(Box::new(LaunchedLocalhostBinary::new(
+            child, id, None, None,
+        )))
+    }
+
     async fn forward_port(&self, addr: &SocketAddr) -> Result<SocketAddr> {
         Ok(*addr)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn isolated_configs(isolation: LocalhostIsolation, service_id: usize) -> Vec<ServerBindConfig> {
+        let launched = LaunchedLocalhost {
+            isolation,
+            service_indices: Mutex::default(),
+            data_root: None,
+        };
+        // Launch another service first, so that `service_id` is assigned the second slot.
+        launched.service_index(usize::MAX);
+        let mut next_offset = 0;
+        let service_index = launched.service_index(service_id);
+        [None, Some(9000), None]
+            .into_iter()
+            .map(|port| {
+                launched.isolate(
+                    service_index,
+                    ServerBindConfig::TcpPort("127.0.0.1".to_owned(), port),
+                    &mut next_offset,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn port_offset_assigns_ports_from_service_block() {
+        let configs = isolated_configs(
+            LocalhostIsolation::PortOffset {
+                base: 20000,
+                block_size: 100,
+            },
+            7,
+        );
+        let ports = configs
+            .into_iter()
+            .map(|config| match config {
+                ServerBindConfig::TcpPort(_, port) => port.unwrap(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![20100, 9000, 20101], ports);
+    }
+
+    #[test]
+    fn loopback_address_assigns_address_per_service() {
+        let configs = isolated_configs(LocalhostIsolation::LoopbackAddress, 7);
+        for config in configs {
+            let ServerBindConfig::TcpPort(host, _) = config else {
+                unreachable!()
+            };
+            assert_eq!("127.1.0.2", host);
+        }
+    }
+
+    #[test]
+    fn data_dirs_are_kept_in_data_root() {
+        let host = LocalhostHost::new(0);
+        let default_dir = host.launched.data_dir(3);
+        assert_ne!(default_dir, LocalhostHost::new(1).launched.data_dir(4));
+        assert!(
+            default_dir
+                .to_str()
+                .unwrap()
+                .contains(&std::process::id().to_string())
+        );
+
+        let host =
+            host.with_data_root("/var/lib/hydro")
+                .with_isolation(LocalhostIsolation::PortOffset {
+                    base: 20000,
+                    block_size: 10,
+                });
+        assert_eq!(
+            PathBuf::from("/var/lib/hydro/service-3"),
+            host.launched.data_dir(3)
+        );
+    }
+
+    #[tokio::test]
+    async fn copy_assets_replaces_previous_copies() {
+        let local = tempfile::tempdir().unwrap();
+        std::fs::write(local.path().join("config.toml"), "port = 1").unwrap();
+        std::fs::write(local.path().join("server.pem"), "certificate").unwrap();
+
+        let host = LocalhostHost::new(0);
+        let asset_dir = host
+            .launched
+            .copy_assets(
+                usize::MAX,
+                &[
+                    Asset::new(local.path().join("config.toml"), "config.toml"),
+                    Asset::new(local.path().join("server.pem"), "tls/server.pem"),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            "certificate",
+            std::fs::read_to_string(asset_dir.join("tls/server.pem")).unwrap()
+        );
+
+        std::fs::write(local.path().join("config.toml"), "port = 2").unwrap();
+        let asset_dir = host
+            .launched
+            .copy_assets(
+                usize::MAX,
+                &[Asset::new(local.path().join("config.toml"), "config.toml")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            "port = 2",
+            std::fs::read_to_string(asset_dir.join("config.toml")).unwrap()
+        );
+        assert!(!asset_dir.join("tls").exists());
+
+        std::fs::remove_dir_all(asset_dir).unwrap();
+    }
+}
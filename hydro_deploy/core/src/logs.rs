@@ -0,0 +1,353 @@
+//! Retaining the output of services, so that the results of an experiment are not lost when its
+//! machines are torn down.
+//!
+//! When a [`Deployment`](crate::Deployment) is configured with
+//! [`Deployment::retain_logs`](crate::Deployment::retain_logs), the stdout and stderr of each
+//! service (and its profiling data, if it was traced) are kept in memory while it runs. After the
+//! run, [`Deployment::archive_logs`](crate::Deployment::archive_logs) writes them into a new
+//! directory for the run, along with a [`LogManifest`] (`manifest.json`) listing the files of
+//! each service, and uploads the directory to the [`LogDestination`]. The logs are also archived
+//! when a service crashes, so that they are kept even if the deployment is not stopped cleanly.
+//!
+//! At most [`MAX_RETAINED_LINES`] of each of stdout and stderr are kept per service, discarding
+//! the oldest lines first.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+
+use crate::LaunchedBinary;
+use crate::terraform::TERRAFORM_ALPHABET;
+
+/// The maximum number of lines of each of stdout and stderr retained for a service.
+pub const MAX_RETAINED_LINES: usize = 100_000;
+
+/// Where the logs of a run are archived.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogDestination {
+    /// A local directory, in which a subdirectory is created for each run.
+    Directory(PathBuf),
+    /// An S3 location (`s3://bucket/prefix`), uploaded to with the `aws` CLI.
+    S3(String),
+    /// A GCS location (`gs://bucket/prefix`), uploaded to with the `gcloud` CLI.
+    Gcs(String),
+}
+
+impl LogDestination {
+    /// Parses an `s3://` or `gs://` URL, or otherwise a local directory path.
+    pub fn parse(destination: &str) -> Self {
+        if destination.starts_with("s3://") {
+            LogDestination::S3(destination.to_owned())
+        } else if destination.starts_with("gs://") {
+            LogDestination::Gcs(destination.to_owned())
+        } else {
+            LogDestination::Directory(PathBuf::from(destination))
+        }
+    }
+}
+
+/// Lines of output retained with [`LaunchedBinary::retain_output`], of which only the most recent
+/// [`MAX_RETAINED_LINES`] are kept.
+#[derive(Clone)]
+pub struct RetainedLines(Arc<Mutex<RetainedLinesInner>>);
+
+struct RetainedLinesInner {
+    lines: VecDeque<String>,
+    /// The number of lines discarded to stay within the limit.
+    discarded: usize,
+    limit: usize,
+}
+
+impl Default for RetainedLines {
+    fn default() -> Self {
+        Self::with_limit(MAX_RETAINED_LINES)
+    }
+}
+
+impl RetainedLines {
+    /// Keeps at most `limit` lines, instead of [`MAX_RETAINED_LINES`].
+    pub fn with_limit(limit: usize) -> Self {
+        Self(Arc::new(Mutex::new(RetainedLinesInner {
+            lines: VecDeque::new(),
+            discarded: 0,
+            limit,
+        })))
+    }
+
+    /// Appends a line, discarding the oldest line if the limit is reached.
+    pub fn push(&self, line: String) {
+        let mut inner = self.0.lock().unwrap();
+        inner.lines.push_back(line);
+        if inner.lines.len() > inner.limit {
+            inner.lines.pop_front();
+            inner.discarded += 1;
+        }
+    }
+
+    /// The retained lines, and the number of earlier lines which were discarded.
+    pub fn lines(&self) -> (Vec<String>, usize) {
+        let inner = self.0.lock().unwrap();
+        (inner.lines.iter().cloned().collect(), inner.discarded)
+    }
+}
+
+/// The output of a service, as retained since [`Service::retain_logs`](crate::Service::retain_logs)
+/// was called. If the service was restarted, includes the output of every launch.
+#[derive(Clone, Debug, Default)]
+pub struct ServiceLogs {
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+    /// The number of lines of stdout discarded before `stdout`, see [`MAX_RETAINED_LINES`].
+    pub stdout_discarded: usize,
+    /// The number of lines of stderr discarded before `stderr`, see [`MAX_RETAINED_LINES`].
+    pub stderr_discarded: usize,
+    /// The exit code of the last launch, if it has exited.
+    pub exit_code: Option<i32>,
+    /// Folded stack traces, if the service was traced.
+    pub perf: Option<Vec<u8>>,
+}
+
+/// Accumulates the output of each launch of a service.
+#[derive(Default)]
+pub(crate) struct LogCapture {
+    stdout: RetainedLines,
+    stderr: RetainedLines,
+}
+
+impl LogCapture {
+    /// Retains all subsequent output of `binary`.
+    pub(crate) fn attach(&self, binary: &dyn LaunchedBinary) {
+        binary.retain_output(self.stdout.clone(), self.stderr.clone());
+    }
+
+    /// The output retained so far.
+    pub(crate) fn logs(&self) -> ServiceLogs {
+        let (stdout, stdout_discarded) = self.stdout.lines();
+        let (stderr, stderr_discarded) = self.stderr.lines();
+        ServiceLogs {
+            stdout,
+            stderr,
+            stdout_discarded,
+            stderr_discarded,
+            ..ServiceLogs::default()
+        }
+    }
+}
+
+/// Lists the files of each service in an archived run, written as `manifest.json`. Paths are
+/// relative to the directory of the run.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogManifest {
+    /// The name of the directory of the run, e.g. `run-1700000000000-a1b2c3`, or
+    /// `run-1700000000000-a1b2c3-crash` if it was archived because a service crashed.
+    pub run_id: String,
+    pub services: Vec<ServiceLogEntry>,
+}
+
+/// The archived files of a single service, see [`LogManifest`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceLogEntry {
+    pub display_id: String,
+    pub stdout: PathBuf,
+    pub stderr: PathBuf,
+    pub perf: Option<PathBuf>,
+    pub exit_code: Option<i32>,
+}
+
+/// Writes the logs of each service into a new directory for the run, and uploads it to
+/// `destination`. Returns the location of the archived run.
+///
+/// The name of the directory is unique to the archive, with `suffix` (if any) appended.
+pub(crate) async fn archive(
+    destination: &LogDestination,
+    logs: Vec<(String, ServiceLogs)>,
+    suffix: Option<&str>,
+) -> Result<String> {
+    let mut run_id = format!(
+        "run-{}-{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis(),
+        nanoid!(6, &TERRAFORM_ALPHABET)
+    );
+    if let Some(suffix) = suffix {
+        run_id.push('-');
+        run_id.push_str(suffix);
+    }
+
+    match destination {
+        LogDestination::Directory(root) => {
+            std::fs::create_dir_all(root)?;
+            let run_dir = root.join(&run_id);
+            write_run(&run_dir, &run_id, &logs)?;
+            Ok(run_dir.display().to_string())
+        }
+        LogDestination::S3(url) | LogDestination::Gcs(url) => {
+            let staging = tempfile::tempdir()?;
+            let run_dir = staging.path().join(&run_id);
+            write_run(&run_dir, &run_id, &logs)?;
+
+            let target = format!("{}/{}", url.trim_end_matches('/'), run_id);
+            let mut command = if matches!(destination, LogDestination::S3(_)) {
+                let mut command = tokio::process::Command::new("aws");
+                command.args(["s3", "cp", "--recursive"]);
+                command
+            } else {
+                let mut command = tokio::process::Command::new("gcloud");
+                command.args(["storage", "cp", "--recursive"]);
+                command
+            };
+            let output = command
+                .arg(&run_dir)
+                .arg(&target)
+                .stdin(Stdio::null())
+                .output()
+                .await
+                .with_context(|| format!("Failed to run the upload of logs to `{target}`."))?;
+            if !output.status.success() {
+                bail!(
+                    "Failed to upload logs to `{}`: {}",
+                    target,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+            Ok(target)
+        }
+    }
+}
+
+/// Writes the logs of each service and the [`LogManifest`] into `run_dir`, which must not exist
+/// yet.
+fn write_run(run_dir: &Path, run_id: &str, logs: &[(String, ServiceLogs)]) -> Result<()> {
+    std::fs::create_dir(run_dir)
+        .with_context(|| format!("Failed to create `{}`.", run_dir.display()))?;
+    let mut services = Vec::new();
+    for (display_id, logs) in logs {
+        // Display IDs may contain characters which are not valid in file names, e.g. `/`.
+        let service_dir = PathBuf::from(
+            display_id
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || "-_.".contains(c) {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>(),
+        );
+        std::fs::create_dir_all(run_dir.join(&service_dir))?;
+
+        let write = |name: &str, contents: &[u8]| -> Result<PathBuf> {
+            let path = service_dir.join(name);
+            std::fs::write(run_dir.join(&path), contents)
+                .with_context(|| format!("Failed to write `{}`.", path.display()))?;
+            Ok(path)
+        };
+        services.push(ServiceLogEntry {
+            display_id: display_id.clone(),
+            stdout: write(
+                "stdout.log",
+                join_lines(&logs.stdout, logs.stdout_discarded).as_bytes(),
+            )?,
+            stderr: write(
+                "stderr.log",
+                join_lines(&logs.stderr, logs.stderr_discarded).as_bytes(),
+            )?,
+            perf: logs
+                .perf
+                .as_deref()
+                .map(|perf| write("perf.folded", perf))
+                .transpose()?,
+            exit_code: logs.exit_code,
+        });
+    }
+
+    let manifest = LogManifest {
+        run_id: run_id.to_owned(),
+        services,
+    };
+    std::fs::write(
+        run_dir.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    Ok(())
+}
+
+/// The contents of a log file, starting with a note of how many earlier lines were discarded.
+fn join_lines(lines: &[String], discarded: usize) -> String {
+    let note = (discarded > 0).then(|| format!("[{discarded} earlier lines were not retained]\n"));
+    note.into_iter()
+        .chain(lines.iter().map(|line| format!("{line}\n")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_manifest() {
+        let root = tempfile::tempdir().unwrap();
+        let run_dir = root.path().join("run-0");
+        let logs = vec![(
+            "service/0".to_owned(),
+            ServiceLogs {
+                stdout: vec!["hello".to_owned(), "world".to_owned()],
+                stderr: vec!["last".to_owned()],
+                stdout_discarded: 0,
+                stderr_discarded: 2,
+                exit_code: Some(0),
+                perf: None,
+            },
+        )];
+        write_run(&run_dir, "run-0", &logs).unwrap();
+        // A run is never written into an existing directory.
+        assert!(write_run(&run_dir, "run-0", &logs).is_err());
+        let root = run_dir;
+
+        let manifest: LogManifest =
+            serde_json::from_str(&std::fs::read_to_string(root.join("manifest.json")).unwrap())
+                .unwrap();
+        assert_eq!("run-0", manifest.run_id);
+        assert_eq!(1, manifest.services.len());
+        let service = &manifest.services[0];
+        assert_eq!(Path::new("service_0/stdout.log"), service.stdout);
+        assert_eq!(None, service.perf);
+        assert_eq!(
+            "hello\nworld\n",
+            std::fs::read_to_string(root.join(&service.stdout)).unwrap()
+        );
+        assert_eq!(
+            "[2 earlier lines were not retained]\nlast\n",
+            std::fs::read_to_string(root.join(&service.stderr)).unwrap()
+        );
+    }
+
+    #[test]
+    fn retains_most_recent_lines() {
+        let retained = RetainedLines::with_limit(2);
+        for line in ["a", "b", "c"] {
+            retained.push(line.to_owned());
+        }
+        assert_eq!(retained.lines(), (vec!["b".to_owned(), "c".to_owned()], 1));
+    }
+
+    #[tokio::test]
+    async fn archives_are_not_merged() {
+        let root = tempfile::tempdir().unwrap();
+        let destination = LogDestination::Directory(root.path().to_owned());
+        let first = archive(&destination, vec![], None).await.unwrap();
+        let second = archive(&destination, vec![], Some("crash")).await.unwrap();
+        assert_ne!(first, second);
+        assert!(second.ends_with("-crash"));
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 2);
+    }
+}
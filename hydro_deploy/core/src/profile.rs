@@ -0,0 +1,415 @@
+//! Named deployment profiles, which describe the hosts of a deployment in a `hydro.toml` file so
+//! that the same deployment code can target different environments.
+//!
+//! Each profile has default host settings, optional settings for individual hosts and clusters
+//! (which override the defaults), and environment variables for the services:
+//!
+//! ```toml
+//! # hosts are on localhost unless a provider is set
+//! [profiles.local]
+//! clusters.workers.size = 2
+//!
+//! [profiles.staging]
+//! env = { RUST_LOG = "info" }
+//!
+//! [profiles.staging.defaults]
+//! provider = "gcp"
+//! project = "my-project"
+//! region = "us-west1-a"
+//! instance_type = "e2-micro"
+//! image = "debian-cloud/debian-11"
+//!
+//! [profiles.staging.hosts.leader]
+//! instance_type = "e2-standard-4"
+//!
+//! [profiles.staging.clusters.workers]
+//! size = 8
+//! host = { region = "us-east1-b" }
+//! ```
+//!
+//! A deployment is created from a profile with [`Deployment::from_profile`], and its hosts are
+//! then created on demand with [`Deployment::profile_host`] and [`Deployment::profile_cluster`].
+//! Hosts which are not listed in the profile use the default settings.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::gcp::GcpNetwork;
+use crate::{AwsNetwork, Deployment, Host};
+
+/// Where the hosts of a profile are created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostProvider {
+    #[default]
+    Localhost,
+    Gcp,
+    Aws,
+    Azure,
+}
+
+/// The settings of a host in a [`Profile`]. Settings which are not set are taken from the
+/// defaults of the profile.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostProfile {
+    pub provider: Option<HostProvider>,
+    /// The GCP or Azure project.
+    pub project: Option<String>,
+    /// The GCP zone, AWS region, or Azure region.
+    pub region: Option<String>,
+    /// The GCP machine type, AWS instance type, or Azure machine size.
+    pub instance_type: Option<String>,
+    /// The GCP image or AWS AMI. Azure hosts always use the default image.
+    pub image: Option<String>,
+    /// The user to connect to the host as.
+    pub user: Option<String>,
+}
+
+impl HostProfile {
+    /// Returns these settings, with any that are not set taken from `defaults`.
+    pub fn or(&self, defaults: &HostProfile) -> HostProfile {
+        HostProfile {
+            provider: self.provider.or(defaults.provider),
+            project: self.project.clone().or_else(|| defaults.project.clone()),
+            region: self.region.clone().or_else(|| defaults.region.clone()),
+            instance_type: self
+                .instance_type
+                .clone()
+                .or_else(|| defaults.instance_type.clone()),
+            image: self.image.clone().or_else(|| defaults.image.clone()),
+            user: self.user.clone().or_else(|| defaults.user.clone()),
+        }
+    }
+}
+
+/// The settings of a cluster in a [`Profile`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusterProfile {
+    /// The number of members in the cluster, each on its own host.
+    pub size: usize,
+    /// The settings of each member's host.
+    #[serde(default)]
+    pub host: HostProfile,
+}
+
+/// A named set of hosts and environment variables, read from a `hydro.toml` file. See
+/// [`crate::profile`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// The settings of hosts which are not set in [`Self::hosts`] or [`Self::clusters`].
+    #[serde(default)]
+    pub defaults: HostProfile,
+    #[serde(default)]
+    pub hosts: BTreeMap<String, HostProfile>,
+    #[serde(default)]
+    pub clusters: BTreeMap<String, ClusterProfile>,
+    /// Environment variables to set for the services of the deployment.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfilesFile {
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Profile {
+    /// The name of the file that profiles are read from.
+    pub const FILE_NAME: &str = "hydro.toml";
+
+    /// Finds the `hydro.toml` file in `dir` or its closest ancestor which has one.
+    pub fn find(dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dir = dir.as_ref();
+        dir.ancestors()
+            .map(|ancestor| ancestor.join(Self::FILE_NAME))
+            .find(|path| path.is_file())
+            .with_context(|| {
+                format!(
+                    "Failed to find `{}` in `{}` or its parents.",
+                    Self::FILE_NAME,
+                    dir.display()
+                )
+            })
+    }
+
+    /// Reads the profile named `name` from the file at `path`.
+    pub fn load(path: impl AsRef<Path>, name: &str) -> Result<Profile> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read profiles `{}`.", path.display()))?;
+        Self::parse(&contents, name)
+            .with_context(|| format!("Failed to load profile from `{}`.", path.display()))
+    }
+
+    /// Parses the profile named `name` from the contents of a `hydro.toml` file.
+    pub fn parse(contents: &str, name: &str) -> Result<Profile> {
+        let mut file: ProfilesFile = toml::from_str(contents)?;
+        match file.profiles.remove(name) {
+            Some(profile) => Ok(profile),
+            None => bail!(
+                "No profile named `{}`, expected one of: {}",
+                name,
+                file.profiles
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// The hosts created from the [`Profile`] of a deployment so far, see
+/// [`Deployment::profile_host`].
+pub(crate) struct ProfileHosts {
+    name: String,
+    pub(crate) profile: Profile,
+    hosts: BTreeMap<String, Arc<dyn Host>>,
+    clusters: BTreeMap<String, Vec<Arc<dyn Host>>>,
+    /// The network shared by the GCP hosts of each project.
+    gcp_networks: BTreeMap<String, Arc<GcpNetwork>>,
+    /// The network shared by the AWS hosts of each region.
+    aws_networks: BTreeMap<String, Arc<AwsNetwork>>,
+}
+
+impl ProfileHosts {
+    pub(crate) fn new(name: impl Into<String>, profile: Profile) -> Self {
+        Self {
+            name: name.into(),
+            profile,
+            hosts: BTreeMap::new(),
+            clusters: BTreeMap::new(),
+            gcp_networks: BTreeMap::new(),
+            aws_networks: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn host(
+        &mut self,
+        deployment: &mut Deployment,
+        name: &str,
+    ) -> Result<Arc<dyn Host>> {
+        if let Some(host) = self.hosts.get(name) {
+            return Ok(host.clone());
+        }
+
+        let settings = self
+            .profile
+            .hosts
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+            .or(&self.profile.defaults);
+        let host = self.add_host(deployment, &settings, name.to_owned())?;
+        self.hosts.insert(name.to_owned(), host.clone());
+        Ok(host)
+    }
+
+    pub(crate) fn cluster(
+        &mut self,
+        deployment: &mut Deployment,
+        name: &str,
+    ) -> Result<Vec<Arc<dyn Host>>> {
+        if let Some(hosts) = self.clusters.get(name) {
+            return Ok(hosts.clone());
+        }
+
+        let Some(cluster) = self.profile.clusters.get(name) else {
+            bail!(
+                "Profile `{}` has no cluster named `{}`, which is needed for its size.",
+                self.name,
+                name
+            );
+        };
+        let settings = cluster.host.or(&self.profile.defaults);
+        let hosts = (0..cluster.size)
+            .map(|i| self.add_host(deployment, &settings, format!("{}-{}", name, i)))
+            .collect::<Result<Vec<_>>>()?;
+        self.clusters.insert(name.to_owned(), hosts.clone());
+        Ok(hosts)
+    }
+
+    fn add_host(
+        &mut self,
+        deployment: &mut Deployment,
+        settings: &HostProfile,
+        display_name: String,
+    ) -> Result<Arc<dyn Host>> {
+        let required = |value: &Option<String>, field: &str| {
+            value.clone().with_context(|| {
+                format!(
+                    "Host `{}` of profile `{}` needs a `{}`.",
+                    display_name, self.name, field
+                )
+            })
+        };
+
+        let host: Arc<dyn Host> = match settings.provider.unwrap_or_default() {
+            HostProvider::Localhost => deployment.Localhost(),
+            HostProvider::Gcp => {
+                let project = required(&settings.project, "project")?;
+                let machine_type = required(&settings.instance_type, "instance_type")?;
+                let image = required(&settings.image, "image")?;
+                let region = required(&settings.region, "region")?;
+                let network = self
+                    .gcp_networks
+                    .entry(project.clone())
+                    .or_insert_with(|| GcpNetwork::new(&project, None))
+                    .clone();
+                deployment
+                    .GcpComputeEngineHost()
+                    .project(project)
+                    .machine_type(machine_type)
+                    .image(image)
+                    .region(region)
+                    .network(network)
+                    .and_user(settings.user.clone())
+                    .display_name(display_name)
+                    .add()
+            }
+            HostProvider::Aws => {
+                let region = required(&settings.region, "region")?;
+                let instance_type = required(&settings.instance_type, "instance_type")?;
+                let ami = required(&settings.image, "image")?;
+                let network = self
+                    .aws_networks
+                    .entry(region.clone())
+                    .or_insert_with(|| AwsNetwork::new(&region, None))
+                    .clone();
+                deployment
+                    .AwsEc2Host()
+                    .region(region)
+                    .instance_type(instance_type)
+                    .ami(ami)
+                    .network(network)
+                    .and_user(settings.user.clone())
+                    .display_name(display_name)
+                    .add()
+            }
+            HostProvider::Azure => {
+                if settings.image.is_some() {
+                    bail!(
+                        "Host `{}` of profile `{}` sets an `image`, which is not supported for Azure hosts.",
+                        display_name,
+                        self.name
+                    );
+                }
+                deployment
+                    .AzureHost()
+                    .project(required(&settings.project, "project")?)
+                    .os_type("linux")
+                    .machine_size(required(&settings.instance_type, "instance_type")?)
+                    .region(required(&settings.region, "region")?)
+                    .and_user(settings.user.clone())
+                    .display_name(display_name)
+                    .add()
+            }
+        };
+        Ok(host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: &str = r#"
+        [profiles.local]
+        clusters.workers.size = 2
+        env = { RUST_LOG = "debug" }
+
+        [profiles.staging.defaults]
+        provider = "aws"
+        region = "us-east-1"
+        instance_type = "t3.micro"
+        image = "ami-0e95a5e2743ec9ec9"
+
+        [profiles.staging.hosts.leader]
+        instance_type = "t3.large"
+
+        [profiles.staging.hosts.gcp]
+        provider = "gcp"
+
+        [profiles.staging.hosts.analytics]
+        provider = "gcp"
+        project = "hydro-staging"
+        region = "us-west1-a"
+        instance_type = "e2-micro"
+        image = "debian-cloud/debian-11"
+    "#;
+
+    #[test]
+    fn parses_profiles_with_defaults() {
+        let staging = Profile::parse(PROFILES, "staging").unwrap();
+        assert_eq!(
+            HostProfile {
+                provider: Some(HostProvider::Aws),
+                region: Some("us-east-1".to_owned()),
+                instance_type: Some("t3.large".to_owned()),
+                image: Some("ami-0e95a5e2743ec9ec9".to_owned()),
+                ..HostProfile::default()
+            },
+            staging.hosts["leader"].or(&staging.defaults)
+        );
+
+        let err = Profile::parse(PROFILES, "prod").unwrap_err();
+        assert_eq!(
+            "No profile named `prod`, expected one of: local, staging",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn creates_hosts_from_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(Profile::FILE_NAME);
+        std::fs::write(&path, PROFILES).unwrap();
+        assert_eq!(path, Profile::find(dir.path()).unwrap());
+
+        let mut deployment = Deployment::from_profile_file(&path, "local").unwrap();
+        let workers = deployment.profile_cluster("workers").unwrap();
+        assert_eq!(2, workers.len());
+        assert_eq!(
+            vec![("RUST_LOG", "debug")],
+            deployment.profile_env().collect::<Vec<_>>()
+        );
+        assert!(deployment.profile_cluster("clients").is_err());
+
+        let mut deployment = Deployment::from_profile_file(&path, "staging").unwrap();
+        let leader = deployment.profile_host("leader").unwrap();
+        assert!(Arc::ptr_eq(
+            &leader,
+            &deployment.profile_host("leader").unwrap()
+        ));
+        let leader = format!("{:?}", leader);
+        assert!(leader.starts_with("AwsEc2Host("), "{}", leader);
+        assert!(leader.ends_with("(Some(\"leader\")))"), "{}", leader);
+
+        let analytics = format!("{:?}", deployment.profile_host("analytics").unwrap());
+        assert!(
+            analytics.starts_with("GcpComputeEngineHost("),
+            "{}",
+            analytics
+        );
+        assert!(
+            analytics.ends_with("(Some(\"analytics\")))"),
+            "{}",
+            analytics
+        );
+
+        let err = deployment.profile_host("gcp").unwrap_err();
+        assert_eq!(
+            "Host `gcp` of profile `staging` needs a `project`.",
+            err.to_string()
+        );
+    }
+}
@@ -1,7 +1,9 @@
+use std::fmt::Display;
 use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
 use futures::Future;
+use hydro_deploy_integration::ServerPort;
 use indicatif::MultiProgress;
 
 static PROGRESS_TRACKER: OnceLock<Mutex<ProgressTracker>> = OnceLock::new();
@@ -10,6 +12,62 @@ tokio::task_local! {
     static CURRENT_GROUP: Vec<usize>;
 }
 
+/// The lifecycle phase of a service within a deployment, reported with
+/// [`ProgressTracker::service_phase`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ServicePhase {
+    /// Compiling the service's binary.
+    Building,
+    /// Provisioning the host and copying the binary to it.
+    Deploying,
+    /// Launching the binary and waiting for it to bind its ports.
+    Launching,
+    /// Launched and listening, but not yet connected to other services.
+    Ready,
+    /// Connecting to other services.
+    Starting,
+    /// Running.
+    Running,
+    /// Stopping and relaunching the binary.
+    Restarting,
+    /// Waiting for the binary to finish sending outstanding data and exit.
+    Draining,
+    /// Waiting for the binary to exit.
+    Stopping,
+    /// Stopped.
+    Stopped,
+    /// A phase failed, with the given error message.
+    Failed(String),
+}
+
+impl Display for ServicePhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServicePhase::Building => write!(f, "building"),
+            ServicePhase::Deploying => write!(f, "deploying"),
+            ServicePhase::Launching => write!(f, "launching"),
+            ServicePhase::Ready => write!(f, "ready"),
+            ServicePhase::Starting => write!(f, "starting"),
+            ServicePhase::Running => write!(f, "running"),
+            ServicePhase::Restarting => write!(f, "restarting"),
+            ServicePhase::Draining => write!(f, "draining"),
+            ServicePhase::Stopping => write!(f, "stopping"),
+            ServicePhase::Stopped => write!(f, "stopped"),
+            ServicePhase::Failed(err) => write!(f, "failed: {}", err),
+        }
+    }
+}
+
+/// Whether a service listens on a port, or connects to a port of another service, reported with
+/// [`ProgressTracker::service_port`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PortDirection {
+    /// The service listens on the port.
+    Bind,
+    /// The service connects to the port.
+    Connect,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum LeafStatus {
     Started,
@@ -123,6 +181,43 @@ impl BarTree {
         }
     }
 
+    #[cfg(feature = "tui")]
+    fn collect_active(&self, out: &mut Vec<String>) {
+        match self {
+            BarTree::Root(children) => {
+                for child in children {
+                    child.collect_active(out);
+                }
+            }
+            BarTree::Group(_, pb, children, _) => {
+                if children
+                    .iter()
+                    .any(|child| child.status() == LeafStatus::Started)
+                {
+                    for child in children {
+                        child.collect_active(out);
+                    }
+                } else {
+                    out.push(format!("{} ({:.1?})", pb.prefix(), pb.elapsed()));
+                }
+            }
+            BarTree::Leaf(_, pb, LeafStatus::Started) => {
+                let message = pb.message();
+                if message.is_empty() {
+                    out.push(format!("{} ({:.1?})", pb.prefix(), pb.elapsed()));
+                } else {
+                    out.push(format!(
+                        "{} {} ({:.1?})",
+                        pb.prefix(),
+                        message,
+                        pb.elapsed()
+                    ));
+                }
+            }
+            BarTree::Leaf(..) | BarTree::Finished => {}
+        }
+    }
+
     fn find_node(&self, path: &[usize]) -> &BarTree {
         if path.is_empty() {
             return self;
@@ -360,6 +455,11 @@ impl ProgressTracker {
 
 impl ProgressTracker {
     pub fn println(msg: impl AsRef<str>) {
+        #[cfg(feature = "tui")]
+        if crate::tui::record_log(msg.as_ref(), false) {
+            return;
+        }
+
         let progress_bar = PROGRESS_TRACKER
             .get_or_init(|| Mutex::new(ProgressTracker::new()))
             .lock()
@@ -371,6 +471,11 @@ impl ProgressTracker {
     }
 
     pub fn eprintln(msg: impl AsRef<str>) {
+        #[cfg(feature = "tui")]
+        if crate::tui::record_log(msg.as_ref(), true) {
+            return;
+        }
+
         let progress_bar = PROGRESS_TRACKER
             .get_or_init(|| Mutex::new(ProgressTracker::new()))
             .lock()
@@ -381,6 +486,55 @@ impl ProgressTracker {
         });
     }
 
+    /// Reports that the service `service` (identified by [`crate::Service::display_id`]) entered
+    /// the given phase. Shown by the deployment TUI, if it is running.
+    pub fn service_phase(service: &str, phase: ServicePhase) {
+        #[cfg(feature = "tui")]
+        crate::tui::record_phase(service, phase);
+        #[cfg(not(feature = "tui"))]
+        let _ = (service, phase);
+    }
+
+    /// Reports that the service `service` binds or connects to `port`, resolved to `server_port`.
+    /// Shown by the deployment TUI, if it is running.
+    pub fn service_port(
+        service: &str,
+        port: &str,
+        direction: PortDirection,
+        server_port: &ServerPort,
+    ) {
+        #[cfg(feature = "tui")]
+        crate::tui::record_port(service, port, direction, server_port);
+        #[cfg(not(feature = "tui"))]
+        let _ = (service, port, direction, server_port);
+    }
+
+    /// Hides (or re-shows) the progress bars, while the deployment TUI draws its own display.
+    #[cfg(feature = "tui")]
+    pub(crate) fn set_hidden(hidden: bool) {
+        let progress_bar = PROGRESS_TRACKER
+            .get_or_init(|| Mutex::new(ProgressTracker::new()))
+            .lock()
+            .unwrap();
+        progress_bar.multi_progress.set_draw_target(if hidden {
+            indicatif::ProgressDrawTarget::hidden()
+        } else {
+            indicatif::ProgressDrawTarget::stderr()
+        });
+    }
+
+    /// Descriptions of the tasks currently in progress, for the deployment TUI.
+    #[cfg(feature = "tui")]
+    pub(crate) fn active_tasks() -> Vec<String> {
+        let progress_bar = PROGRESS_TRACKER
+            .get_or_init(|| Mutex::new(ProgressTracker::new()))
+            .lock()
+            .unwrap();
+        let mut out = Vec::new();
+        progress_bar.tree.collect_active(&mut out);
+        out
+    }
+
     pub fn with_group<'a, T, F: Future<Output = T>>(
         name: impl Into<String>,
         anticipated_total: Option<usize>,
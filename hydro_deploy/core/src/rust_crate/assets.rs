@@ -0,0 +1,66 @@
+//! Files shipped alongside the binary of a [`RustCrateService`](super::RustCrateService).
+//!
+//! Each [`Asset`] (for example a config file, a TLS certificate, or a dataset) is copied into an
+//! asset directory on the host before the binary is launched. The binary finds the directory
+//! through the [`ASSET_DIR_ENV`] environment variable, or with
+//! [`hydro_deploy_integration::asset_dir`].
+
+use std::path::{Component, Path, PathBuf};
+
+pub use hydro_deploy_integration::ASSET_DIR_ENV;
+
+/// A local file to be copied into the asset directory of a service.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Asset {
+    /// The path of the file on the deploying machine.
+    pub local_path: PathBuf,
+    /// The path of the file on the host, relative to the asset directory.
+    pub remote_path: PathBuf,
+}
+
+impl Asset {
+    /// Creates an asset which copies `local_path` to `remote_path` within the asset directory.
+    ///
+    /// # Panics
+    /// If `remote_path` is not a relative path within the asset directory, i.e. if it is absolute
+    /// or contains `..`.
+    pub fn new(local_path: impl Into<PathBuf>, remote_path: impl Into<PathBuf>) -> Self {
+        let remote_path = remote_path.into();
+        assert!(
+            remote_path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir)),
+            "asset path `{}` must be relative to the asset directory",
+            remote_path.display()
+        );
+        Self {
+            local_path: local_path.into(),
+            remote_path,
+        }
+    }
+
+    /// The path of the file on the host, within `asset_dir`.
+    pub fn remote_path_in(&self, asset_dir: &Path) -> PathBuf {
+        asset_dir.join(&self.remote_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_path_in() {
+        let asset = Asset::new("certs/server.pem", "tls/server.pem");
+        assert_eq!(
+            PathBuf::from("/home/hydro/assets/tls/server.pem"),
+            asset.remote_path_in(Path::new("/home/hydro/assets"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must be relative to the asset directory")]
+    fn test_rejects_escaping_path() {
+        Asset::new("config.toml", "../config.toml");
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::error::Error;
 use std::fmt::Display;
 use std::io::BufRead;
@@ -95,6 +96,86 @@ impl BuildParams {
             config,
         }
     }
+
+    /// A hash of the source files of the crate and of its local (path) dependencies, which changes
+    /// whenever their code (or the code staged into them) changes. Build outputs in `target`
+    /// directories and hidden directories are not included.
+    ///
+    /// Runs `cargo metadata` to find the path dependencies, and reads the files on a blocking
+    /// thread.
+    pub async fn source_hash(&self) -> anyhow::Result<String> {
+        let src = self.src.clone();
+        tokio::task::spawn_blocking(move || Self::source_hash_blocking(&src)).await?
+    }
+
+    fn source_hash_blocking(src: &Path) -> anyhow::Result<String> {
+        fn hash_dir(hasher: &mut blake3::Hasher, root: &Path, dir: &Path) {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                return;
+            };
+            let mut paths = entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .collect::<Vec<_>>();
+            paths.sort();
+            for path in paths {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if path.is_dir() {
+                    if name != "target" && !name.starts_with('.') {
+                        hash_dir(hasher, root, &path);
+                    }
+                } else if let Ok(contents) = std::fs::read(&path) {
+                    let relative = path.strip_prefix(root).unwrap_or(&path);
+                    hasher.update(relative.to_string_lossy().as_bytes());
+                    hasher.update(&contents);
+                }
+            }
+        }
+
+        let metadata = cargo_metadata::MetadataCommand::new()
+            .manifest_path(src.join("Cargo.toml"))
+            .exec()?;
+        let root = metadata
+            .resolve
+            .as_ref()
+            .and_then(|resolve| resolve.root.clone())
+            .or_else(|| {
+                metadata
+                    .packages
+                    .iter()
+                    .find(|package| {
+                        package.manifest_path.parent().map(|dir| dir.as_std_path()) == Some(src)
+                    })
+                    .map(|package| package.id.clone())
+            });
+
+        // The local packages the crate (transitively) depends on, including itself.
+        let mut local_dirs = BTreeSet::from([src.to_owned()]);
+        if let (Some(resolve), Some(root)) = (&metadata.resolve, root) {
+            let mut visited = BTreeSet::new();
+            let mut to_visit = vec![root];
+            while let Some(id) = to_visit.pop() {
+                if !visited.insert(id.clone()) {
+                    continue;
+                }
+                if let Some(package) = metadata.packages.iter().find(|package| package.id == id)
+                    && package.source.is_none()
+                    && let Some(dir) = package.manifest_path.parent()
+                {
+                    local_dirs.insert(dir.as_std_path().to_owned());
+                }
+                if let Some(node) = resolve.nodes.iter().find(|node| node.id == id) {
+                    to_visit.extend(node.dependencies.iter().cloned());
+                }
+            }
+        }
+
+        let mut hasher = blake3::Hasher::new();
+        for dir in &local_dirs {
+            hasher.update(dir.to_string_lossy().as_bytes());
+            hash_dir(&mut hasher, dir, dir);
+        }
+        Ok(hasher.finalize().to_hex().to_string())
+    }
 }
 
 /// Information about a built crate. See [`build_crate_memoized`].
@@ -113,13 +194,23 @@ impl BuildOutput {
     }
 }
 
-/// Build memoization cache.
-static BUILDS: OnceLock<MemoMap<BuildParams, OnceCell<BuildOutput>>> = OnceLock::new();
+/// Build memoization cache, keyed by the build parameters and the revision of the source.
+static BUILDS: OnceLock<MemoMap<(BuildParams, Option<String>), OnceCell<BuildOutput>>> =
+    OnceLock::new();
 
 pub async fn build_crate_memoized(params: BuildParams) -> Result<&'static BuildOutput, BuildError> {
+    build_crate_memoized_at(params, None).await
+}
+
+/// Like [`build_crate_memoized`], but memoized separately for each `revision` of the source (see
+/// [`BuildParams::source_hash`]), so that a crate is built again after its source changes.
+pub async fn build_crate_memoized_at(
+    params: BuildParams,
+    revision: Option<String>,
+) -> Result<&'static BuildOutput, BuildError> {
     BUILDS
         .get_or_init(MemoMap::new)
-        .get_or_insert(&params, Default::default)
+        .get_or_insert(&(params.clone(), revision), Default::default)
         .get_or_try_init(move || {
             ProgressTracker::rich_leaf("build", move |set_msg| async move {
                 tokio::task::spawn_blocking(move || {
@@ -452,3 +543,35 @@ impl Display for BuildError {
 }
 
 impl Error for BuildError {}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildParams;
+
+    #[test]
+    fn source_hash_includes_path_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write(
+            "app/Cargo.toml",
+            "[package]\nname = \"app\"\nversion = \"0.0.0\"\nedition = \"2024\"\n\n[dependencies]\nlib = { path = \"../lib\" }\n",
+        );
+        write("app/src/main.rs", "fn main() {}\n");
+        write(
+            "lib/Cargo.toml",
+            "[package]\nname = \"lib\"\nversion = \"0.0.0\"\nedition = \"2024\"\n",
+        );
+        write("lib/src/lib.rs", "pub fn f() {}\n");
+
+        let app = dunce::canonicalize(dir.path().join("app")).unwrap();
+        let before = BuildParams::source_hash_blocking(&app).unwrap();
+        assert_eq!(before, BuildParams::source_hash_blocking(&app).unwrap());
+
+        write("lib/src/lib.rs", "pub fn f() { println!(); }\n");
+        assert_ne!(before, BuildParams::source_hash_blocking(&app).unwrap());
+    }
+}
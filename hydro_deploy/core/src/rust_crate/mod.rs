@@ -1,20 +1,35 @@
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use nameof::name_of;
+use resource_limits::ResourceLimits;
 use tracing_options::TracingOptions;
 
 use super::Host;
+use crate::rust_crate::assets::Asset;
 use crate::rust_crate::build::BuildParams;
 use crate::{HostTargetType, ServiceBuilder};
 
+pub mod assets;
 pub mod build;
 pub mod ports;
+pub mod resource_limits;
+
+pub mod prebuilt;
+pub use prebuilt::PrebuiltCrate;
 
 pub mod service;
 pub use service::*;
 
+pub mod sidecar;
+/// Environment variable naming the directory in which a service keeps its persistent data, which
+/// is set to a directory chosen by the host (see [`LaunchedHost::data_dir`](crate::LaunchedHost::data_dir))
+/// unless the environment of the service already sets it.
+pub use hydro_deploy_integration::DATA_DIR_ENV;
+pub use sidecar::{HealthCheck, Sidecar};
+
 #[cfg(feature = "profile-folding")]
 pub(crate) mod flamegraph;
 pub mod tracing_options;
@@ -48,8 +63,11 @@ pub struct RustCrate {
     tracing: Option<TracingOptions>,
     args: Vec<String>,
     display_name: Option<String>,
+    port_fallback: Option<RangeInclusive<u16>>,
     env: HashMap<String, String>,
-    pin_to_core: Option<usize>,
+    resources: ResourceLimits,
+    sidecars: Vec<Sidecar>,
+    assets: Vec<Asset>,
 }
 
 impl RustCrate {
@@ -74,8 +92,11 @@ impl RustCrate {
             tracing: None,
             args: vec![],
             display_name: None,
+            port_fallback: None,
             env: HashMap::new(),
-            pin_to_core: None,
+            resources: ResourceLimits::default(),
+            sidecars: vec![],
+            assets: vec![],
         }
     }
 
@@ -188,14 +209,88 @@ impl RustCrate {
         self
     }
 
+    /// Sets the ports to retry with when a port explicitly requested by this service (such as
+    /// with [`PortNetworkHint::TcpPort`](crate::PortNetworkHint::TcpPort)) is already in use on
+    /// its host. Each conflicting port is replaced with the next port from `ports`, and the
+    /// services connecting to this one are given the port that was actually bound.
+    ///
+    /// If the service binds any ports through the public IP of a cloud host, every port in
+    /// `ports` is also opened in the host's firewall, with a single rule for the whole range.
+    /// Without a fallback, deploying the service fails with an error naming the conflicting port.
+    pub fn port_fallback(mut self, ports: RangeInclusive<u16>) -> Self {
+        if self.port_fallback.is_some() {
+            panic!("{} already set", name_of!(port_fallback in Self));
+        }
+
+        self.port_fallback = Some(ports);
+        self
+    }
+
     /// Sets an environment variable to be written to a .env file on the launched instance.
     pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.env.insert(key.into(), value.into());
         self
     }
 
-    pub fn pin_to_core(mut self, core: usize) -> Self {
-        self.pin_to_core = Some(core);
+    /// Pins the binary to a single CPU core, equivalent to `cpu_affinity([core])`.
+    pub fn pin_to_core(self, core: usize) -> Self {
+        self.cpu_affinity([core])
+    }
+
+    /// Pins the binary to the given CPU cores.
+    pub fn cpu_affinity(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        if self.resources.cpu_affinity.is_some() {
+            panic!("{} already set", name_of!(cpu_affinity in ResourceLimits));
+        }
+
+        self.resources.cpu_affinity = Some(cores.into_iter().collect());
+        self
+    }
+
+    /// Sets the maximum number of files the binary may have open at once, which may be at most
+    /// the hard limit of the host. Launching the binary fails on platforms other than Linux.
+    pub fn max_open_files(mut self, files: u64) -> Self {
+        if self.resources.max_open_files.is_some() {
+            panic!("{} already set", name_of!(max_open_files in ResourceLimits));
+        }
+
+        self.resources.max_open_files = Some(files);
+        self
+    }
+
+    /// Sets the maximum memory (in bytes) the binary may use, enforced with cgroups v2 on Linux
+    /// hosts. The binary is killed if it exceeds the limit.
+    ///
+    /// Cloud hosts need passwordless `sudo` to create the cgroup, and localhost needs a running
+    /// systemd user manager. Launching the binary fails on other platforms.
+    pub fn memory_limit(mut self, bytes: u64) -> Self {
+        if self.resources.memory_limit.is_some() {
+            panic!("{} already set", name_of!(memory_limit in ResourceLimits));
+        }
+
+        self.resources.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Adds a helper process which is launched (and must pass its health check) before the
+    /// binary, and is stopped along with it.
+    pub fn sidecar(mut self, sidecar: Sidecar) -> Self {
+        self.sidecars.push(sidecar);
+        self
+    }
+
+    /// Ships the local file at `local_path` alongside the binary, at `remote_path` relative to
+    /// the service's asset directory. The binary can find the directory through the
+    /// [`assets::ASSET_DIR_ENV`] environment variable.
+    ///
+    /// # Panics
+    /// If `remote_path` is absolute or contains `..`.
+    pub fn with_asset(
+        mut self,
+        local_path: impl Into<PathBuf>,
+        remote_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.assets.push(Asset::new(local_path, remote_path));
         self
     }
 
@@ -232,13 +327,16 @@ impl ServiceBuilder for RustCrate {
         RustCrateService::new(
             id,
             on,
-            build_params,
+            BinarySource::Build(build_params),
             self.tracing,
             Some(self.args),
             self.display_name,
             vec![],
+            self.port_fallback,
             self.env,
-            self.pin_to_core,
+            self.resources,
+            self.sidecars,
+            self.assets,
         )
     }
 }
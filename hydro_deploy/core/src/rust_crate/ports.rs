@@ -1,6 +1,7 @@
 use std::any::Any;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt::Debug;
+use std::net::{IpAddr, SocketAddr};
 use std::ops::Deref;
 use std::sync::{Arc, Weak};
 
@@ -44,6 +45,9 @@ pub trait RustCrateSource: Send + Sync {
 pub trait RustCrateServer: Debug + Send + Sync {
     fn get_port(&self) -> ServerPort;
     fn launched_host(&self) -> Arc<dyn LaunchedHost>;
+    /// The display ID of the service which binds this server and the name of its port, which
+    /// identify the server regardless of the port it is currently bound to.
+    fn server_key(&self) -> (String, String);
 }
 
 pub type ReverseSinkInstantiator = Box<dyn FnOnce(&dyn Any) -> ServerStrategy>;
@@ -247,6 +251,15 @@ impl RustCrateServer for RustCratePortConfig {
     fn launched_host(&self) -> Arc<dyn LaunchedHost> {
         self.service_host.launched().unwrap()
     }
+
+    fn server_key(&self) -> (String, String) {
+        let display_id = self
+            .service
+            .upgrade()
+            .map(|service| service.display_id())
+            .unwrap_or_default();
+        (display_id, self.port.clone())
+    }
 }
 
 pub enum SourcePath {
@@ -383,6 +396,8 @@ impl RustCrateSink for RustCratePortConfig {
 pub enum ServerConfig {
     Direct(Arc<dyn RustCrateServer>),
     Forwarded(Arc<dyn RustCrateServer>),
+    /// A server reached through the public IP of its host.
+    External(Arc<dyn RustCrateServer>),
     /// A demux that will be used at runtime to listen to many connections.
     Demux(HashMap<u32, ServerConfig>),
     /// The other side of a demux, with a port to extract the appropriate connection.
@@ -407,6 +422,7 @@ impl ServerConfig {
                 ServerConfig::Direct(server)
             }
             ClientStrategy::ForwardedTcpPort(_) => ServerConfig::Forwarded(server),
+            ClientStrategy::ExternalTcpPort(_) => ServerConfig::External(server),
         }
     }
 }
@@ -437,6 +453,31 @@ async fn forward_connection(conn: &ServerPort, target: &dyn LaunchedHost) -> Ser
     }
 }
 
+/// Replaces the IP of every TCP port in `conn` with the public IP of its host.
+fn externalize_connection(conn: ServerPort, external_ip: IpAddr) -> ServerPort {
+    match conn {
+        ServerPort::UnixSocket(_) => panic!("Expected a TCP port to be reached externally"),
+        ServerPort::TcpPort(addr) => ServerPort::TcpPort(SocketAddr::new(external_ip, addr.port())),
+        ServerPort::Demux(demux) => ServerPort::Demux(
+            demux
+                .into_iter()
+                .map(|(key, conn)| (key, externalize_connection(conn, external_ip)))
+                .collect(),
+        ),
+        ServerPort::Merge(merge) => ServerPort::Merge(
+            merge
+                .into_iter()
+                .map(|conn| externalize_connection(conn, external_ip))
+                .collect(),
+        ),
+        ServerPort::Tagged(underlying, id) => ServerPort::Tagged(
+            Box::new(externalize_connection(*underlying, external_ip)),
+            id,
+        ),
+        ServerPort::Null => ServerPort::Null,
+    }
+}
+
 impl ServerConfig {
     #[async_recursion]
     pub async fn load_instantiated(
@@ -451,6 +492,18 @@ impl ServerConfig {
                 forward_connection(&selected, server.launched_host().as_ref()).await
             }
 
+            ServerConfig::External(server) => {
+                let selected = select(server.get_port());
+                let host = server.launched_host();
+                let external_ip = host
+                    .external_ip()
+                    .or_else(|| host.external_ipv6())
+                    .expect("Host connected to through its public IP has no public IP")
+                    .parse()
+                    .expect("Invalid public IP");
+                externalize_connection(selected, external_ip)
+            }
+
             ServerConfig::Demux(demux) => {
                 let mut demux_map = BTreeMap::new();
                 for (key, conn) in demux {
@@ -513,4 +566,73 @@ impl ServerConfig {
             ServerConfig::Null => ServerPort::Null,
         }
     }
+
+    /// Describes the servers connected to and how, identifying each server by its service and
+    /// port name (see [`RustCrateServer::server_key`]), so that the description does not change
+    /// when a server is relaunched and bound to a different port.
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            ServerConfig::Direct(server) => format!("direct({:?})", server.server_key()),
+            ServerConfig::Forwarded(server) => format!("forwarded({:?})", server.server_key()),
+            ServerConfig::External(server) => format!("external({:?})", server.server_key()),
+            ServerConfig::Demux(demux) => {
+                #[expect(
+                    clippy::disallowed_methods,
+                    reason = "nondeterministic iteration order, collected into a sorted map"
+                )]
+                let demux = demux
+                    .iter()
+                    .map(|(key, conn)| (*key, conn.describe()))
+                    .collect::<BTreeMap<_, _>>();
+                format!("demux({:?})", demux)
+            }
+            ServerConfig::DemuxSelect(underlying, key) => {
+                format!("{}[{}]", underlying.describe(), key)
+            }
+            ServerConfig::Merge(merge) => format!(
+                "merge({:?})",
+                merge.iter().map(|conn| conn.describe()).collect::<Vec<_>>()
+            ),
+            ServerConfig::MergeSelect(underlying, key) => {
+                format!("{}[{}]", underlying.describe(), key)
+            }
+            ServerConfig::Tagged(underlying, id) => {
+                format!("tagged({}, {})", underlying.describe(), id)
+            }
+            ServerConfig::TaggedUnwrap(underlying) => format!("untag({})", underlying.describe()),
+            ServerConfig::Null => "null".to_owned(),
+        }
+    }
+
+    /// Adds the display IDs of the services whose servers this connects to to `services`.
+    pub(crate) fn collect_services(&self, services: &mut BTreeSet<String>) {
+        match self {
+            ServerConfig::Direct(server)
+            | ServerConfig::Forwarded(server)
+            | ServerConfig::External(server) => {
+                services.insert(server.server_key().0);
+            }
+            ServerConfig::Demux(demux) =>
+            {
+                #[expect(
+                    clippy::disallowed_methods,
+                    reason = "nondeterministic iteration order, collected into a set"
+                )]
+                let conns = demux.values();
+                for conn in conns {
+                    conn.collect_services(services);
+                }
+            }
+            ServerConfig::Merge(merge) => {
+                for conn in merge.iter() {
+                    conn.collect_services(services);
+                }
+            }
+            ServerConfig::DemuxSelect(underlying, _)
+            | ServerConfig::MergeSelect(underlying, _)
+            | ServerConfig::Tagged(underlying, _)
+            | ServerConfig::TaggedUnwrap(underlying) => underlying.collect_services(services),
+            ServerConfig::Null => {}
+        }
+    }
 }
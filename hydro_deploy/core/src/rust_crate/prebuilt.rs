@@ -0,0 +1,378 @@
+//! Deploying binaries which were built ahead of time (for example by a CI pipeline), instead of
+//! running `cargo build` for every deployment.
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result, bail};
+use memo_map::MemoMap;
+use nameof::name_of;
+use tokio::process::Command;
+use tokio::sync::OnceCell;
+
+use super::build::BuildOutput;
+use super::resource_limits::ResourceLimits;
+use super::service::{BinarySource, RustCrateService};
+use crate::progress::ProgressTracker;
+use crate::{Host, HostTargetType, LinuxCompileType, ServiceBuilder};
+
+/// Where a prebuilt binary is fetched from.
+///
+/// Both variants are templates, in which `{target}` is replaced with the target triple of the
+/// host being deployed to (e.g. `x86_64-unknown-linux-musl`) and `{git_hash}` with the git commit
+/// the binary was built from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ArtifactLocation {
+    /// Downloaded over HTTP(S) with `curl`.
+    Url(String),
+    /// Read from the local filesystem.
+    Path(PathBuf),
+}
+
+/// Specifies a binary that uses `hydro_deploy_integration`, built ahead of time, to be deployed
+/// as a service.
+///
+/// Unlike [`RustCrate`](super::RustCrate), no `cargo build` is run. Instead the binary for the
+/// host's target is fetched from an [`ArtifactLocation`] and verified against a BLAKE3 checksum
+/// (as printed by `b3sum`) before it is deployed. This lets CI pipelines build once and deploy
+/// many times.
+#[derive(Clone)]
+pub struct PrebuiltCrate {
+    location: ArtifactLocation,
+    git_hash: Option<String>,
+    checksums: HashMap<String, String>,
+    args: Vec<String>,
+    display_name: Option<String>,
+    port_fallback: Option<RangeInclusive<u16>>,
+    env: HashMap<String, String>,
+    resources: ResourceLimits,
+}
+
+impl PrebuiltCrate {
+    /// Creates a `PrebuiltCrate` which downloads its binary from a URL template, see
+    /// [`ArtifactLocation`].
+    pub fn from_url(url: impl Into<String>) -> Self {
+        Self::new(ArtifactLocation::Url(url.into()))
+    }
+
+    /// Creates a `PrebuiltCrate` which reads its binary from a local path template, see
+    /// [`ArtifactLocation`].
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        Self::new(ArtifactLocation::Path(path.into()))
+    }
+
+    fn new(location: ArtifactLocation) -> Self {
+        Self {
+            location,
+            git_hash: None,
+            checksums: HashMap::new(),
+            args: vec![],
+            display_name: None,
+            port_fallback: None,
+            env: HashMap::new(),
+            resources: ResourceLimits::default(),
+        }
+    }
+
+    /// Sets the git commit the binary was built from, substituted for `{git_hash}` in the
+    /// location. Defaults to the output of `git rev-parse HEAD` in the current directory.
+    pub fn git_hash(mut self, git_hash: impl Into<String>) -> Self {
+        if self.git_hash.is_some() {
+            panic!("{} already set", name_of!(git_hash in Self));
+        }
+
+        self.git_hash = Some(git_hash.into());
+        self
+    }
+
+    /// Sets the expected BLAKE3 checksum (in hex) of the binary for the given target triple.
+    ///
+    /// Deploying to a host whose target has no checksum fails.
+    pub fn checksum(mut self, target: impl Into<String>, checksum: impl Into<String>) -> Self {
+        let target = target.into();
+        if self.checksums.contains_key(&target) {
+            panic!(
+                "{} already set for target `{}`",
+                name_of!(checksums in Self),
+                target
+            );
+        }
+
+        self.checksums.insert(target, checksum.into());
+        self
+    }
+
+    /// Sets the arguments to be passed to the binary when it is launched.
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(|s| s.into()));
+        self
+    }
+
+    /// Sets the display name for this service, which will be used in logging.
+    pub fn display_name(mut self, display_name: impl Into<String>) -> Self {
+        if self.display_name.is_some() {
+            panic!("{} already set", name_of!(display_name in Self));
+        }
+
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    /// Sets the ports to retry with when a port explicitly requested by this service (such as
+    /// with [`PortNetworkHint::TcpPort`](crate::PortNetworkHint::TcpPort)) is already in use on
+    /// its host. Each conflicting port is replaced with the next port from `ports`, and the
+    /// services connecting to this one are given the port that was actually bound.
+    ///
+    /// If the service binds any ports through the public IP of a cloud host, every port in
+    /// `ports` is also opened in the host's firewall. Without a fallback, deploying the service
+    /// fails with an error naming the conflicting port.
+    pub fn port_fallback(mut self, ports: RangeInclusive<u16>) -> Self {
+        if self.port_fallback.is_some() {
+            panic!("{} already set", name_of!(port_fallback in Self));
+        }
+
+        self.port_fallback = Some(ports);
+        self
+    }
+
+    /// Sets an environment variable to be written to a .env file on the launched instance.
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the controls over the resources available to the binary.
+    pub fn resources(mut self, resources: ResourceLimits) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    pub fn get_prebuilt_params(&self, target: HostTargetType) -> PrebuiltParams {
+        let target_triple = target_triple(target);
+        PrebuiltParams {
+            location: self.location.clone(),
+            git_hash: self.git_hash.clone(),
+            checksum: self.checksums.get(&target_triple).cloned(),
+            target_triple,
+        }
+    }
+}
+
+impl ServiceBuilder for PrebuiltCrate {
+    type Service = RustCrateService;
+    fn build(self, id: usize, on: Arc<dyn Host>) -> Self::Service {
+        let prebuilt_params = self.get_prebuilt_params(on.target_type());
+
+        RustCrateService::new(
+            id,
+            on,
+            BinarySource::Prebuilt(prebuilt_params),
+            None,
+            Some(self.args),
+            self.display_name,
+            vec![],
+            self.port_fallback,
+            self.env,
+            self.resources,
+            vec![],
+            vec![],
+        )
+    }
+}
+
+/// Fetch parameters for [`fetch_prebuilt_memoized`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PrebuiltParams {
+    /// The location template.
+    location: ArtifactLocation,
+    /// Substituted for `{git_hash}`, or `None` to use the current commit.
+    git_hash: Option<String>,
+    /// Substituted for `{target}`.
+    target_triple: String,
+    /// The expected BLAKE3 checksum, in hex.
+    checksum: Option<String>,
+}
+
+/// Fetch memoization cache.
+static PREBUILTS: OnceLock<MemoMap<PrebuiltParams, OnceCell<BuildOutput>>> = OnceLock::new();
+
+/// Fetches and verifies a prebuilt binary, at most once for each set of `params`.
+pub async fn fetch_prebuilt_memoized(params: PrebuiltParams) -> Result<&'static BuildOutput> {
+    PREBUILTS
+        .get_or_init(MemoMap::new)
+        .get_or_insert(&params, Default::default)
+        .get_or_try_init(move || ProgressTracker::leaf("fetch prebuilt", fetch_prebuilt(params)))
+        .await
+}
+
+async fn fetch_prebuilt(params: PrebuiltParams) -> Result<BuildOutput> {
+    let checksum = params
+        .checksum
+        .as_deref()
+        .with_context(|| {
+            format!(
+                "No checksum was given for the prebuilt binary for target `{}`.",
+                params.target_triple
+            )
+        })?
+        .to_ascii_lowercase();
+    let git_hash = match params.git_hash {
+        Some(git_hash) => git_hash,
+        None => current_git_hash().await?,
+    };
+
+    let url = match &params.location {
+        ArtifactLocation::Path(template) => {
+            let path = expand_template(
+                &template.to_string_lossy(),
+                &params.target_triple,
+                &git_hash,
+            );
+            let bin_path = PathBuf::from(path);
+            let bin_data = tokio::fs::read(&bin_path).await.with_context(|| {
+                format!("Failed to read prebuilt binary `{}`.", bin_path.display())
+            })?;
+            verify_checksum(&bin_data, &checksum, &bin_path.display().to_string())?;
+            return Ok(BuildOutput {
+                bin_data,
+                bin_path,
+                shared_library_path: None,
+            });
+        }
+        ArtifactLocation::Url(template) => {
+            expand_template(template, &params.target_triple, &git_hash)
+        }
+    };
+
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", &url])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Failed to run `curl`.")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to download prebuilt binary `{}`: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        );
+    }
+    let bin_data = output.stdout;
+    verify_checksum(&bin_data, &checksum, &url)?;
+
+    // Cached by checksum, so a binary which was already downloaded is simply overwritten.
+    let cache_dir = std::env::temp_dir().join("hydro_deploy_prebuilt");
+    tokio::fs::create_dir_all(&cache_dir).await?;
+    let bin_path = cache_dir.join(&checksum);
+    tokio::fs::write(&bin_path, &bin_data).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+
+    Ok(BuildOutput {
+        bin_data,
+        bin_path,
+        shared_library_path: None,
+    })
+}
+
+/// The commit checked out in the current directory.
+async fn current_git_hash() -> Result<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Failed to run `git rev-parse HEAD`.")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to determine the current git commit, set it with `PrebuiltCrate::git_hash`: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_owned())
+}
+
+fn expand_template(template: &str, target_triple: &str, git_hash: &str) -> String {
+    template
+        .replace("{target}", target_triple)
+        .replace("{git_hash}", git_hash)
+}
+
+fn verify_checksum(bin_data: &[u8], expected: &str, source: &str) -> Result<()> {
+    let actual = blake3::hash(bin_data).to_hex();
+    if actual.as_str() != expected {
+        bail!(
+            "Checksum mismatch for prebuilt binary `{}`: expected {}, got {}.",
+            source,
+            expected,
+            actual,
+        );
+    }
+    Ok(())
+}
+
+/// The target triple binaries are built for, matching the `--target` used by
+/// [`build_crate_memoized`](super::build::build_crate_memoized).
+fn target_triple(target: HostTargetType) -> String {
+    match target {
+        HostTargetType::Linux(LinuxCompileType::Glibc) => "x86_64-unknown-linux-gnu".to_owned(),
+        HostTargetType::Linux(LinuxCompileType::Musl) => "x86_64-unknown-linux-musl".to_owned(),
+        HostTargetType::Local => match (std::env::consts::ARCH, std::env::consts::OS) {
+            (arch, "linux") => format!("{arch}-unknown-linux-gnu"),
+            (arch, "macos") => format!("{arch}-apple-darwin"),
+            (arch, "windows") => format!("{arch}-pc-windows-msvc"),
+            (arch, os) => format!("{arch}-{os}"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_location_template() {
+        assert_eq!(
+            "https://artifacts.example.com/abc123/x86_64-unknown-linux-musl/server",
+            expand_template(
+                "https://artifacts.example.com/{git_hash}/{target}/server",
+                &target_triple(HostTargetType::Linux(LinuxCompileType::Musl)),
+                "abc123",
+            ),
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_verifies_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("abc123-server"), b"binary").unwrap();
+        let prebuilt = PrebuiltCrate::from_path(dir.path().join("{git_hash}-server"))
+            .git_hash("abc123")
+            .checksum(
+                target_triple(HostTargetType::Local),
+                blake3::hash(b"binary").to_hex().as_str(),
+            );
+
+        let fetched = fetch_prebuilt(prebuilt.get_prebuilt_params(HostTargetType::Local))
+            .await
+            .unwrap();
+        assert_eq!(b"binary", &*fetched.bin_data);
+
+        let mismatched = prebuilt.checksum(
+            target_triple(HostTargetType::Linux(LinuxCompileType::Musl)),
+            "0000",
+        );
+        let err = fetch_prebuilt(
+            mismatched.get_prebuilt_params(HostTargetType::Linux(LinuxCompileType::Musl)),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{err}");
+    }
+}
@@ -0,0 +1,188 @@
+/// Controls over the resources available to a launched service, applied by
+/// [`LaunchedHost::launch_binary_with_limits`](crate::LaunchedHost::launch_binary_with_limits).
+///
+/// The controls are enforced on Linux hosts. Hosts which cannot enforce a control fail to launch
+/// the binary, except for the CPU affinity, which is only a performance hint.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The maximum number of files the process may have open at once (`ulimit -n`), which must
+    /// be at most the hard limit of the host unless the binary is launched as a systemd unit.
+    pub max_open_files: Option<u64>,
+    /// The maximum memory (in bytes) the process may use, enforced with a cgroups v2 scope on
+    /// Linux hosts. The process is killed if it exceeds the limit.
+    pub memory_limit: Option<u64>,
+    /// The CPU cores the process is pinned to.
+    pub cpu_affinity: Option<Vec<usize>>,
+}
+
+impl ResourceLimits {
+    /// Returns `true` if no controls are set.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// The single core the process is pinned to, if that is the only control set, which hosts
+    /// that only implement [`LaunchedHost::launch_binary`](crate::LaunchedHost::launch_binary)
+    /// can apply. Returns `Err` if other controls are set.
+    pub(crate) fn as_pin_to_core(&self) -> Result<Option<usize>, ()> {
+        match self {
+            ResourceLimits {
+                max_open_files: None,
+                memory_limit: None,
+                cpu_affinity: None,
+            } => Ok(None),
+            ResourceLimits {
+                max_open_files: None,
+                memory_limit: None,
+                cpu_affinity: Some(cores),
+            } if cores.len() == 1 => Ok(Some(cores[0])),
+            _ => Err(()),
+        }
+    }
+
+    /// The limits for a binary launched with only `pin_to_core` set.
+    pub(crate) fn pinned_to(pin_to_core: Option<usize>) -> Self {
+        ResourceLimits {
+            cpu_affinity: pin_to_core.map(|core| vec![core]),
+            ..Default::default()
+        }
+    }
+
+    /// The program and arguments to run a command under on a Linux host, which apply the
+    /// controls before running it.
+    ///
+    /// The memory limit needs a transient systemd scope. With `scope_user`, the scope is created
+    /// by the system manager (through `sudo`) and runs the command as that user, which works in
+    /// non-interactive SSH sessions. Otherwise, it is created by the service manager of the
+    /// current user, which is only running while the user is logged in.
+    pub(crate) fn command_prefix(&self, scope_user: Option<&str>) -> Vec<String> {
+        let mut prefix = vec![];
+        if let Some(bytes) = self.memory_limit {
+            if let Some(user) = scope_user {
+                prefix.extend(["sudo".to_owned(), "-n".to_owned()]);
+                prefix.extend(["systemd-run".to_owned(), format!("--uid={user}")]);
+            } else {
+                prefix.extend(["systemd-run".to_owned(), "--user".to_owned()]);
+            }
+            prefix.extend([
+                "--scope".to_owned(),
+                "--quiet".to_owned(),
+                format!("--property=MemoryMax={bytes}"),
+                "--property=MemorySwapMax=0".to_owned(),
+                "--".to_owned(),
+            ]);
+        }
+        if let Some(files) = self.max_open_files {
+            prefix.extend([
+                "prlimit".to_owned(),
+                format!("--nofile={files}"),
+                "--".to_owned(),
+            ]);
+        }
+        if let Some(cores) = &self.cpu_affinity {
+            prefix.extend(["taskset".to_owned(), "-c".to_owned(), core_list(cores, ",")]);
+        }
+        prefix
+    }
+
+    /// The properties of a systemd unit which apply the controls to its processes.
+    pub(crate) fn systemd_properties(&self) -> Vec<String> {
+        let mut properties = vec![];
+        if let Some(bytes) = self.memory_limit {
+            properties.push(format!("MemoryMax={bytes}"));
+            properties.push("MemorySwapMax=0".to_owned());
+        }
+        if let Some(files) = self.max_open_files {
+            properties.push(format!("LimitNOFILE={files}"));
+        }
+        if let Some(cores) = &self.cpu_affinity {
+            properties.push(format!("CPUAffinity={}", core_list(cores, " ")));
+        }
+        properties
+    }
+}
+
+fn core_list(cores: &[usize], separator: &str) -> String {
+    cores
+        .iter()
+        .map(|core| core.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_limits() -> ResourceLimits {
+        ResourceLimits {
+            max_open_files: Some(4096),
+            memory_limit: Some(1 << 30),
+            cpu_affinity: Some(vec![0, 2]),
+        }
+    }
+
+    #[test]
+    fn pin_to_core_round_trips() {
+        assert_eq!(ResourceLimits::pinned_to(None).as_pin_to_core(), Ok(None));
+        assert_eq!(
+            ResourceLimits::pinned_to(Some(3)).as_pin_to_core(),
+            Ok(Some(3))
+        );
+        assert!(ResourceLimits::pinned_to(None).is_empty());
+        assert_eq!(all_limits().as_pin_to_core(), Err(()));
+    }
+
+    #[test]
+    fn ssh_prefix_uses_system_scope() {
+        assert_eq!(
+            all_limits().command_prefix(Some("hydro")).join(" "),
+            "sudo -n systemd-run --uid=hydro --scope --quiet --property=MemoryMax=1073741824 \
+             --property=MemorySwapMax=0 -- prlimit --nofile=4096 -- taskset -c 0,2"
+        );
+    }
+
+    #[test]
+    fn local_prefix_uses_user_scope() {
+        assert_eq!(
+            all_limits().command_prefix(None).join(" "),
+            "systemd-run --user --scope --quiet --property=MemoryMax=1073741824 \
+             --property=MemorySwapMax=0 -- prlimit --nofile=4096 -- taskset -c 0,2"
+        );
+        assert!(ResourceLimits::default().command_prefix(None).is_empty());
+    }
+
+    #[test]
+    fn systemd_properties() {
+        assert_eq!(
+            all_limits().systemd_properties(),
+            vec![
+                "MemoryMax=1073741824",
+                "MemorySwapMax=0",
+                "LimitNOFILE=4096",
+                "CPUAffinity=0 2"
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn prefix_applies_limits() {
+        let prefix = ResourceLimits {
+            max_open_files: Some(64),
+            memory_limit: None,
+            cpu_affinity: Some(vec![0]),
+        }
+        .command_prefix(None);
+        let output = std::process::Command::new(&prefix[0])
+            .args(&prefix[1..])
+            .args(["sh", "-c", "ulimit -n && taskset -cp $$"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut lines = stdout.lines();
+        assert_eq!(lines.next(), Some("64"));
+        assert!(lines.next().unwrap().ends_with(": 0"));
+    }
+}
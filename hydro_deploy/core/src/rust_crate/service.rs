@@ -1,36 +1,60 @@
-use std::collections::HashMap;
-use std::sync::{Arc, OnceLock};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use std::time::Duration;
 
 use anyhow::{Context, Result, bail};
 use async_trait::async_trait;
 use futures::Future;
-use hydro_deploy_integration::{InitConfig, ServerPort};
+use hydro_deploy_integration::integrity::FrameConfig;
+use hydro_deploy_integration::{BindError, ControlMessage, InitConfig, ServerPort};
 use memo_map::MemoMap;
 use serde::Serialize;
-use tokio::sync::{OnceCell, RwLock, mpsc};
+use tokio::sync::{OnceCell, mpsc};
 
-use super::build::{BuildError, BuildOutput, BuildParams, build_crate_memoized};
+use super::DATA_DIR_ENV;
+use super::assets::{ASSET_DIR_ENV, Asset};
+use super::build::{BuildOutput, BuildParams, build_crate_memoized_at};
 use super::ports::{self, RustCratePortConfig};
+use super::prebuilt::{PrebuiltParams, fetch_prebuilt_memoized};
+use super::resource_limits::ResourceLimits;
+use super::sidecar::Sidecar;
 use super::tracing_options::TracingOptions;
 #[cfg(feature = "profile-folding")]
 use crate::TracingResults;
-use crate::progress::ProgressTracker;
+use crate::hooks::{HookContext, HookEvent, Hooks};
+use crate::logs::{LogCapture, ServiceLogs};
+use crate::progress::{PortDirection, ProgressTracker, ServicePhase};
+use crate::state::{LaunchedState, StateFile, pin_port};
 use crate::{
     BaseServerStrategy, Host, LaunchedBinary, LaunchedHost, PortNetworkHint, ResourceBatch,
     ResourceResult, ServerStrategy, Service,
 };
 
+/// Where a [`RustCrateService`] gets its binary from.
+#[derive(Clone)]
+pub enum BinarySource {
+    /// Built with `cargo build`, see [`RustCrate`](super::RustCrate).
+    Build(BuildParams),
+    /// Built ahead of time and fetched, see [`PrebuiltCrate`](super::PrebuiltCrate).
+    Prebuilt(PrebuiltParams),
+}
+
 pub struct RustCrateService {
     id: usize,
     pub(super) on: Arc<dyn Host>,
-    build_params: BuildParams,
+    source: BinarySource,
     tracing: Option<TracingOptions>,
     args: Option<Vec<String>>,
     display_id: Option<String>,
     external_ports: Vec<u16>,
+    /// The ports to bind instead of an explicitly requested port which is already in use.
+    port_fallback: Option<RangeInclusive<u16>>,
     env: HashMap<String, String>,
-    pin_to_core: Option<usize>,
+    resources: ResourceLimits,
+    sidecars: Vec<Sidecar>,
+    assets: Vec<Asset>,
 
     meta: OnceLock<String>,
 
@@ -40,14 +64,35 @@ pub struct RustCrateService {
     pub(super) port_to_bind: MemoMap<String, ServerStrategy>,
 
     launched_host: OnceCell<Arc<dyn LaunchedHost>>,
+    /// The directory on the host which the assets were copied into, if there are any.
+    asset_dir: OnceLock<PathBuf>,
+    /// The state file of the deployment, if it is resumable.
+    state: OnceLock<Arc<StateFile>>,
+    /// How the frames sent between services are encoded, set by the deployment.
+    frame: OnceLock<FrameConfig>,
 
     /// A map of port names to config for how other services can connect to this one.
     /// Only valid after `ready` has been called, only contains ports that are configured
     /// in `server_ports`.
-    pub(super) server_defns: Arc<RwLock<HashMap<String, ServerPort>>>,
-
-    launched_binary: OnceCell<Box<dyn LaunchedBinary>>,
-    started: OnceCell<()>,
+    pub(super) server_defns: Arc<tokio::sync::RwLock<HashMap<String, ServerPort>>>,
+
+    /// The running binary, replaced when the service is restarted.
+    launched_binary: RwLock<Option<Arc<dyn LaunchedBinary>>>,
+    /// Held while the binary is being launched or restarted, so that it is only launched once.
+    launch_lock: tokio::sync::Mutex<()>,
+    /// The running sidecars, launched before the binary and stopped after it.
+    launched_sidecars: tokio::sync::Mutex<Vec<Box<dyn LaunchedBinary>>>,
+    /// Whether the binary has been started, reset when it is redeployed.
+    started: tokio::sync::Mutex<bool>,
+    /// The revision of the source the binary is built from, set when it is redeployed. See
+    /// [`build_crate_memoized_at`].
+    build_revision: Mutex<Option<String>>,
+    /// The unique ID of the binary copied to the host, see [`BuildOutput::unique_id`].
+    deployed_binary_id: Mutex<Option<String>>,
+    /// The retained output of the binary, if enabled with [`Service::retain_logs`].
+    log_capture: OnceLock<LogCapture>,
+    /// The hooks registered for this service, see [`crate::hooks`].
+    hooks: Mutex<Hooks>,
 }
 
 impl RustCrateService {
@@ -55,36 +100,51 @@ impl RustCrateService {
     pub fn new(
         id: usize,
         on: Arc<dyn Host>,
-        build_params: BuildParams,
+        source: BinarySource,
         tracing: Option<TracingOptions>,
         args: Option<Vec<String>>,
         display_id: Option<String>,
         external_ports: Vec<u16>,
+        port_fallback: Option<RangeInclusive<u16>>,
         env: HashMap<String, String>,
-        pin_to_core: Option<usize>,
+        resources: ResourceLimits,
+        sidecars: Vec<Sidecar>,
+        assets: Vec<Asset>,
     ) -> Self {
         Self {
             id,
             on,
-            build_params,
+            source,
             tracing,
             args,
             display_id,
             external_ports,
+            port_fallback,
             env,
-            pin_to_core,
+            resources,
+            sidecars,
+            assets,
             meta: OnceLock::new(),
             port_to_server: MemoMap::new(),
             port_to_bind: MemoMap::new(),
             launched_host: OnceCell::new(),
-            server_defns: Arc::new(RwLock::new(HashMap::new())),
-            launched_binary: OnceCell::new(),
-            started: OnceCell::new(),
+            asset_dir: OnceLock::new(),
+            state: OnceLock::new(),
+            frame: OnceLock::new(),
+            server_defns: Arc::new(tokio::sync::RwLock::new(HashMap::new())),
+            launched_binary: RwLock::new(None),
+            launch_lock: tokio::sync::Mutex::new(()),
+            launched_sidecars: tokio::sync::Mutex::new(vec![]),
+            started: tokio::sync::Mutex::new(false),
+            build_revision: Mutex::new(None),
+            deployed_binary_id: Mutex::new(None),
+            log_capture: OnceLock::new(),
+            hooks: Mutex::new(Hooks::default()),
         }
     }
 
     pub fn update_meta<T: Serialize>(&self, meta: T) {
-        if self.launched_binary.get().is_some() {
+        if self.launched_binary.read().unwrap().is_some() {
             panic!("Cannot update meta after binary has been launched")
         }
         self.meta
@@ -118,41 +178,506 @@ impl RustCrateService {
         }
     }
 
+    /// Registers `hook` to be run once this service is built and its binary copied to its host.
+    /// See [`crate::hooks`].
+    pub fn on_build_complete<F, Fut>(&self, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks
+            .lock()
+            .unwrap()
+            .add(HookEvent::BuildComplete, hook);
+    }
+
+    /// Registers `hook` to be run each time this service is launched and ready to be started.
+    pub fn on_ready<F, Fut>(&self, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.lock().unwrap().add(HookEvent::Ready, hook);
+    }
+
+    /// Registers `hook` to be run each time this service exits after being started, with its
+    /// exit code and the tail of its output.
+    pub fn on_exit<F, Fut>(&self, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.lock().unwrap().add(HookEvent::Exit, hook);
+    }
+
+    /// Registers `hook` to be run each time this service exits with a non-zero exit code
+    /// without being stopped by the deployment.
+    pub fn on_crash<F, Fut>(&self, hook: F)
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.lock().unwrap().add(HookEvent::Crash, hook);
+    }
+
+    /// The running binary.
+    ///
+    /// # Panics
+    /// If the service has not been launched with [`Service::ready`].
+    fn launched_binary(&self) -> Arc<dyn LaunchedBinary> {
+        self.launched_binary
+            .read()
+            .unwrap()
+            .clone()
+            .expect("Service has not been launched.")
+    }
+
     pub fn stdout(&self) -> mpsc::UnboundedReceiver<String> {
-        self.launched_binary.get().unwrap().stdout()
+        self.launched_binary().stdout()
     }
 
     pub fn stderr(&self) -> mpsc::UnboundedReceiver<String> {
-        self.launched_binary.get().unwrap().stderr()
+        self.launched_binary().stderr()
     }
 
     pub fn stdout_filter(&self, prefix: String) -> mpsc::UnboundedReceiver<String> {
-        self.launched_binary.get().unwrap().stdout_filter(prefix)
+        self.launched_binary().stdout_filter(prefix)
     }
 
     pub fn stderr_filter(&self, prefix: String) -> mpsc::UnboundedReceiver<String> {
-        self.launched_binary.get().unwrap().stderr_filter(prefix)
+        self.launched_binary().stderr_filter(prefix)
     }
 
     #[cfg(feature = "profile-folding")]
-    pub fn tracing_results(&self) -> Option<&TracingResults> {
-        self.launched_binary.get().unwrap().tracing_results()
+    pub fn tracing_results(&self) -> Option<TracingResults> {
+        self.launched_binary().tracing_results().cloned()
     }
 
     pub fn exit_code(&self) -> Option<i32> {
-        self.launched_binary.get().unwrap().exit_code()
+        self.launched_binary().exit_code()
     }
 
-    fn build(
-        &self,
-    ) -> impl use<> + 'static + Future<Output = Result<&'static BuildOutput, BuildError>> {
+    /// Suspends the whole running binary, see [`LaunchedBinary::suspend`].
+    pub async fn suspend(&self) -> Result<()> {
+        self.launched_binary().suspend().await
+    }
+
+    /// Resumes the binary after [`Self::suspend`].
+    pub async fn resume(&self) -> Result<()> {
+        self.launched_binary().resume().await
+    }
+
+    fn build(&self) -> impl use<> + 'static + Future<Output = Result<&'static BuildOutput>> {
         // Memoized, so no caching in `self` is needed.
-        build_crate_memoized(self.build_params.clone())
+        let source = self.source.clone();
+        let revision = self.build_revision.lock().unwrap().clone();
+        async move {
+            match source {
+                BinarySource::Build(build_params) => {
+                    Ok(build_crate_memoized_at(build_params, revision).await?)
+                }
+                BinarySource::Prebuilt(prebuilt_params) => {
+                    fetch_prebuilt_memoized(prebuilt_params).await
+                }
+            }
+        }
+    }
+
+    /// Launches the sidecars and waits for them to become healthy, stopping any which were
+    /// launched if one fails.
+    async fn launch_sidecars(&self) -> Result<()> {
+        let launched_host = self.launched_host.get().unwrap();
+        let display_id = self.display_id();
+        let launched = futures::future::join_all(
+            self.sidecars
+                .iter()
+                .map(|sidecar| sidecar.launch(&display_id, launched_host)),
+        )
+        .await;
+
+        let mut launched_sidecars = self.launched_sidecars.lock().await;
+        let mut first_error = None;
+        for result in launched {
+            match result {
+                Ok(sidecar) => launched_sidecars.push(sidecar),
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        drop(launched_sidecars);
+
+        if let Some(err) = first_error {
+            self.stop_sidecars().await?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Stops the running sidecars.
+    async fn stop_sidecars(&self) -> Result<()> {
+        let sidecars = std::mem::take(&mut *self.launched_sidecars.lock().await);
+        futures::future::join_all(sidecars.iter().map(|sidecar| sidecar.stop()))
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    /// Requests the fallback ports from the host if this service binds an external port, since
+    /// they must also be opened in the firewall. Fails if the host was launched without them.
+    fn request_port_fallback(&self) -> Result<()> {
+        if let Some(fallback) = &self.port_fallback
+            && self
+                .port_to_bind
+                .iter()
+                .any(|(_, bind_type)| has_external_port(bind_type))
+        {
+            self.on.request_port_range(fallback.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Copies the assets to the host, if there are any.
+    async fn copy_assets(&self, launched_host: &dyn LaunchedHost) -> Result<()> {
+        if self.assets.is_empty() {
+            return Ok(());
+        }
+        let asset_dir = launched_host.copy_assets(self.id, &self.assets).await?;
+        let _ = self.asset_dir.set(asset_dir);
+        Ok(())
+    }
+
+    /// Launches the sidecars and the binary, stopping the sidecars if the binary fails to become
+    /// ready. If the deployment is being resumed and the binary launched by the previous run is
+    /// still running, reattaches to it instead.
+    async fn launch(&self) -> Result<Arc<dyn LaunchedBinary>> {
+        ProgressTracker::with_group(self.display_id(), None, || async {
+            if let Some(binary) = self.reattach().await? {
+                return Ok(binary);
+            }
+
+            self.launch_sidecars().await?;
+            let launched = self.launch_binary().await;
+            if launched.is_err() {
+                self.stop_sidecars().await?;
+            }
+            launched
+        })
+        .await
+    }
+
+    /// Reattaches to the binary launched by the previous run of a resumed deployment, if it is
+    /// still running the deployed build. It is bound to the ports recorded in the state, and has
+    /// already been started.
+    ///
+    /// Services with sidecars are always launched again, since the sidecars are launched along
+    /// with the binary.
+    async fn reattach(&self) -> Result<Option<Arc<dyn LaunchedBinary>>> {
+        let Some(state) = self.state.get() else {
+            return Ok(None);
+        };
+        if !self.sidecars.is_empty() {
+            return Ok(None);
+        }
+        let display_id = self.display_id();
+        let (Some(previous), Some(server_defns)) = (
+            state.previous_launch(&display_id),
+            state.previous_ports(&display_id),
+        ) else {
+            return Ok(None);
+        };
+        if self.deployed_binary_id.lock().unwrap().as_ref() != Some(&previous.binary) {
+            return Ok(None);
+        }
+
+        let launched_host = self.launched_host.get().unwrap();
+        let Some(binary) = launched_host
+            .reattach_binary(display_id.clone(), &previous.handle)
+            .await?
+        else {
+            return Ok(None);
+        };
+        if let Some(capture) = self.log_capture.get() {
+            capture.attach(&*binary);
+        }
+        ProgressTracker::println(format!("[{display_id}] reattached to the running binary"));
+
+        for (port_name, server_port) in server_defns.iter() {
+            ProgressTracker::service_port(&display_id, port_name, PortDirection::Bind, server_port);
+        }
+        state.record_ports(display_id.clone(), &server_defns)?;
+        state.record_launch(display_id, previous)?;
+        *self.server_defns.try_write().unwrap() = server_defns;
+        *self.started.lock().await = true;
+        Ok(Some(Arc::from(binary)))
+    }
+
+    /// Launches the binary on the deployed host, sends it the configuration of the ports it binds
+    /// to, and waits for it to report that it is ready.
+    async fn launch_binary(&self) -> Result<Arc<dyn LaunchedBinary>> {
+        let launched_host = self.launched_host.get().unwrap();
+
+        let built = self.build().await?;
+        let args = self.args.as_ref().cloned().unwrap_or_default();
+
+        let mut env = self.env.clone();
+        if let Some(asset_dir) = self.asset_dir.get() {
+            env.insert(
+                ASSET_DIR_ENV.to_owned(),
+                asset_dir.to_str().unwrap().to_owned(),
+            );
+        }
+        env.entry(DATA_DIR_ENV.to_owned()).or_insert_with(|| {
+            let data_dir = launched_host.data_dir(self.id);
+            data_dir.to_str().unwrap().to_owned()
+        });
+
+        let binary = launched_host
+            .launch_binary_with_limits(
+                self.display_id(),
+                built,
+                &args,
+                self.tracing.clone(),
+                &env,
+                &self.resources,
+            )
+            .await?;
+        if let Some(capture) = self.log_capture.get() {
+            capture.attach(&*binary);
+        }
+
+        let mut bind_config = launched_host.service_server_configs(
+            self.id,
+            self.port_to_bind
+                .iter()
+                .map(|(port_name, bind_type)| (port_name.as_str(), bind_type))
+                .collect(),
+        );
+        if let Some(state) = self.state.get() {
+            bind_config = state.pin_ports(&self.display_id(), bind_config);
+        }
+        // When restarting, bind the same ports as before, so that the addresses other services
+        // were given to connect to stay valid.
+        {
+            let previous = self.server_defns.read().await;
+            bind_config = bind_config
+                .into_iter()
+                .map(|(port_name, config)| {
+                    let config = match previous.get(&port_name) {
+                        Some(port) => pin_port(config, port),
+                        None => config,
+                    };
+                    (port_name, config)
+                })
+                .collect();
+        }
+
+        let meta = self.meta.get().map(|s| s.as_str().into());
+        let frame = self.frame.get().copied().unwrap_or_default();
+        let mut fallback_ports = self.port_fallback.clone().into_iter().flatten();
+        let server_defns: HashMap<String, ServerPort> = loop {
+            let formatted_bind_config =
+                serde_json::to_string::<InitConfig>(&(bind_config.clone(), meta.clone(), frame))
+                    .unwrap();
+
+            // request stdout before sending config so we don't miss the "ready" response
+            let stdout_receiver = binary.deploy_stdout();
+
+            binary.stdin().send(format!("{formatted_bind_config}\n"))?;
+
+            let ready_line = ProgressTracker::leaf(
+                "waiting for ready",
+                tokio::time::timeout(Duration::from_secs(60), stdout_receiver),
+            )
+            .await
+            .context("Timed out waiting for ready")?
+            .context("Program unexpectedly quit")?;
+            if let Some(line_rest) = ready_line.strip_prefix("ready: ") {
+                break serde_json::from_str(line_rest).unwrap();
+            }
+
+            let Some(line_rest) = ready_line.strip_prefix(BindError::PREFIX) else {
+                bail!("expected ready");
+            };
+            let error: BindError = serde_json::from_str(line_rest).unwrap();
+            let conflict = error.port.filter(|_| error.in_use);
+            let Some((port, candidate)) = conflict.and_then(|port| {
+                fallback_ports
+                    .find(|candidate| *candidate != port)
+                    .map(|candidate| (port, candidate))
+            }) else {
+                binary.stop().await?;
+                if let Some(port) = conflict {
+                    bail!(
+                        "Port {} requested by `{}` is already in use on {}{}",
+                        port,
+                        self.display_id(),
+                        error.host,
+                        if self.port_fallback.is_some() {
+                            ", and no fallback ports are left"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+                bail!(
+                    "`{}` could not bind its ports: {}",
+                    self.display_id(),
+                    error
+                );
+            };
+
+            ProgressTracker::println(format!(
+                "[{}] port {} is already in use on {}, retrying with port {}",
+                self.display_id(),
+                port,
+                error.host,
+                candidate
+            ));
+            bind_config = bind_config
+                .into_iter()
+                .map(|(port_name, mut config)| {
+                    config.replace_port(port, candidate);
+                    (port_name, config)
+                })
+                .collect();
+        };
+
+        for (port_name, server_port) in server_defns.iter() {
+            ProgressTracker::service_port(
+                &self.display_id(),
+                port_name,
+                PortDirection::Bind,
+                server_port,
+            );
+        }
+        if let Some(state) = self.state.get() {
+            state.record_ports(self.display_id(), &server_defns)?;
+            if let (Some(handle), Some(binary_id)) = (
+                binary.resume_handle(),
+                self.deployed_binary_id.lock().unwrap().clone(),
+            ) {
+                state.record_launch(
+                    self.display_id(),
+                    LaunchedState {
+                        handle,
+                        binary: binary_id,
+                    },
+                )?;
+            }
+        }
+        *self.server_defns.write().await = server_defns;
+        Ok(Arc::from(binary))
+    }
+
+    /// Sends the binary the ports of the services it connects to, and waits for it to start.
+    async fn send_start(&self) -> Result<()> {
+        let sink_ports_futures = self
+            .port_to_server
+            .iter()
+            .map(|(port_name, outgoing)| async {
+                (&**port_name, outgoing.load_instantiated(&|p| p).await)
+            });
+        let sink_ports = futures::future::join_all(sink_ports_futures)
+            .await
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        for (port_name, server_port) in sink_ports.iter() {
+            ProgressTracker::service_port(
+                &self.display_id(),
+                port_name,
+                PortDirection::Connect,
+                server_port,
+            );
+        }
+
+        let formatted_defns = serde_json::to_string(&sink_ports).unwrap();
+
+        let launched_binary = self.launched_binary();
+        let stdout_receiver = launched_binary.deploy_stdout();
+
+        launched_binary
+            .stdin()
+            .send(format!("start: {formatted_defns}\n"))
+            .unwrap();
+
+        let start_ack_line = ProgressTracker::leaf(
+            self.display_id() + " / waiting for ack start",
+            tokio::time::timeout(Duration::from_secs(60), stdout_receiver),
+        )
+        .await??;
+        if !start_ack_line.starts_with("ack start") {
+            bail!("expected ack start");
+        }
+
+        Ok(())
+    }
+
+    /// Asks the binary to stop, and waits for it to exit (force stopping it after a timeout).
+    async fn stop_binary(&self) -> Result<()> {
+        let launched_binary = self.launched_binary();
+        launched_binary
+            .stdin()
+            .send(format!("{}\n", ControlMessage::Terminate))?;
+
+        let timeout_result = ProgressTracker::leaf(
+            "waiting for exit",
+            tokio::time::timeout(Duration::from_secs(60), launched_binary.wait()),
+        )
+        .await;
+        match timeout_result {
+            Err(_timeout) => {} // `wait()` timed out, but stop will force quit.
+            Ok(Err(unexpected_error)) => return Err(unexpected_error), // `wait()` errored.
+            Ok(Ok(_exit_status)) => {}
+        }
+        launched_binary.stop().await?;
+        self.stop_sidecars().await?;
+
+        Ok(())
+    }
+
+    /// Asks the binary to drain, waits for it to report completion, then waits for it to exit
+    /// (force stopping it after a timeout).
+    async fn drain_binary(&self) -> Result<()> {
+        let launched_binary = self.launched_binary();
+        let mut drained_receiver =
+            launched_binary.stdout_filter(ControlMessage::DRAINED.to_owned());
+        launched_binary
+            .stdin()
+            .send(format!("{}\n", ControlMessage::Drain))?;
+
+        let drained = ProgressTracker::leaf(
+            "waiting for drain",
+            tokio::time::timeout(Duration::from_secs(60), drained_receiver.recv()),
+        )
+        .await;
+        let drain_result = match drained {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(anyhow::anyhow!("Program quit before it finished draining")),
+            Err(_timeout) => Err(anyhow::anyhow!("Timed out waiting for drain")),
+        };
+
+        // Whether or not draining succeeded, make sure the binary exits.
+        let _ = ProgressTracker::leaf(
+            "waiting for exit",
+            tokio::time::timeout(Duration::from_secs(60), launched_binary.wait()),
+        )
+        .await;
+        launched_binary.stop().await?;
+        self.stop_sidecars().await?;
+
+        drain_result
     }
 }
 
 #[async_trait]
 impl Service for RustCrateService {
+    fn display_id(&self) -> String {
+        self.display_id
+            .clone()
+            .unwrap_or_else(|| format!("service/{}", self.id))
+    }
+
     fn collect_resources(&self, _resource_batch: &mut ResourceBatch) {
         if self.launched_host.get().is_some() {
             return;
@@ -170,167 +695,197 @@ impl Service for RustCrateService {
         for port in self.external_ports.iter() {
             host.request_port_base(&BaseServerStrategy::ExternalTcpPort(*port));
         }
+
+        // if the host was already launched, this fails again in `deploy`, which reports the error
+        let _ = self.request_port_fallback();
     }
 
     async fn deploy(&self, resource_result: &Arc<ResourceResult>) -> Result<()> {
         self.launched_host
             .get_or_try_init::<anyhow::Error, _, _>(|| {
-                ProgressTracker::with_group(
-                    self.display_id
-                        .clone()
-                        .unwrap_or_else(|| format!("service/{}", self.id)),
-                    None,
-                    || async {
-                        let built = self.build().await?;
-
-                        let host = &self.on;
-                        let launched = host.provision(resource_result);
-
+                ProgressTracker::with_group(self.display_id(), None, || async {
+                    ProgressTracker::service_phase(&self.display_id(), ServicePhase::Building);
+                    let built = self.build().await?;
+
+                    ProgressTracker::service_phase(&self.display_id(), ServicePhase::Deploying);
+                    self.request_port_fallback()?;
+                    let host = &self.on;
+                    let launched = host.provision(resource_result);
+                    *self.deployed_binary_id.lock().unwrap() = Some(built.unique_id().to_string());
+
+                    let _ = self.frame.set(resource_result.frame);
+                    if let Some(state) = &resource_result.state {
+                        let _ = self.state.set(state.clone());
+                        let hash = built.unique_id().to_string();
+                        if !state.has_binary(host.id(), &hash) {
+                            launched.copy_binary(built).await?;
+                            state.record_binary(host.id(), hash)?;
+                        }
+                    } else {
                         launched.copy_binary(built).await?;
-                        Ok(launched)
-                    },
-                )
+                    }
+                    self.copy_assets(&*launched).await?;
+                    Ok(launched)
+                })
             })
             .await?;
         Ok(())
     }
 
     async fn ready(&self) -> Result<()> {
-        self.launched_binary
-            .get_or_try_init(|| {
-                ProgressTracker::with_group(
-                    self.display_id
-                        .clone()
-                        .unwrap_or_else(|| format!("service/{}", self.id)),
-                    None,
-                    || async {
-                        let launched_host = self.launched_host.get().unwrap();
-
-                        let built = self.build().await?;
-                        let args = self.args.as_ref().cloned().unwrap_or_default();
-
-                        let binary = launched_host
-                            .launch_binary(
-                                self.display_id
-                                    .clone()
-                                    .unwrap_or_else(|| format!("service/{}", self.id)),
-                                built,
-                                &args,
-                                self.tracing.clone(),
-                                &self.env,
-                                self.pin_to_core,
-                            )
-                            .await?;
-
-                        let bind_config = self
-                            .port_to_bind
-                            .iter()
-                            .map(|(port_name, bind_type)| {
-                                (port_name.clone(), launched_host.server_config(bind_type))
-                            })
-                            .collect::<HashMap<_, _>>();
-
-                        let formatted_bind_config = serde_json::to_string::<InitConfig>(&(
-                            bind_config,
-                            self.meta.get().map(|s| s.as_str().into()),
-                        ))
-                        .unwrap();
-
-                        // request stdout before sending config so we don't miss the "ready" response
-                        let stdout_receiver = binary.deploy_stdout();
-
-                        binary.stdin().send(format!("{formatted_bind_config}\n"))?;
-
-                        let ready_line = ProgressTracker::leaf(
-                            "waiting for ready",
-                            tokio::time::timeout(Duration::from_secs(60), stdout_receiver),
-                        )
-                        .await
-                        .context("Timed out waiting for ready")?
-                        .context("Program unexpectedly quit")?;
-                        if let Some(line_rest) = ready_line.strip_prefix("ready: ") {
-                            *self.server_defns.try_write().unwrap() =
-                                serde_json::from_str(line_rest).unwrap();
-                        } else {
-                            bail!("expected ready");
-                        }
-                        Ok(binary)
-                    },
-                )
-            })
-            .await?;
+        let _launching = self.launch_lock.lock().await;
+        if self.launched_binary.read().unwrap().is_some() {
+            return Ok(());
+        }
+        let binary = self.launch().await?;
+        *self.launched_binary.write().unwrap() = Some(binary);
         Ok(())
     }
 
     async fn start(&self) -> Result<()> {
-        self.started
-            .get_or_try_init(|| async {
-                let sink_ports_futures =
-                    self.port_to_server
-                        .iter()
-                        .map(|(port_name, outgoing)| async {
-                            (&**port_name, outgoing.load_instantiated(&|p| p).await)
-                        });
-                let sink_ports = futures::future::join_all(sink_ports_futures)
-                    .await
-                    .into_iter()
-                    .collect::<HashMap<_, _>>();
-
-                let formatted_defns = serde_json::to_string(&sink_ports).unwrap();
-
-                let stdout_receiver = self.launched_binary.get().unwrap().deploy_stdout();
-
-                self.launched_binary
-                    .get()
-                    .unwrap()
-                    .stdin()
-                    .send(format!("start: {formatted_defns}\n"))
-                    .unwrap();
-
-                let start_ack_line = ProgressTracker::leaf(
-                    self.display_id
-                        .clone()
-                        .unwrap_or_else(|| format!("service/{}", self.id))
-                        + " / waiting for ack start",
-                    tokio::time::timeout(Duration::from_secs(60), stdout_receiver),
-                )
-                .await??;
-                if !start_ack_line.starts_with("ack start") {
-                    bail!("expected ack start");
-                }
-
-                Ok(())
-            })
-            .await?;
+        let mut started = self.started.lock().await;
+        if !*started {
+            self.send_start().await?;
+            *started = true;
+        }
 
         Ok(())
     }
 
     async fn stop(&self) -> Result<()> {
-        ProgressTracker::with_group(
-            self.display_id
-                .clone()
-                .unwrap_or_else(|| format!("service/{}", self.id)),
-            None,
-            || async {
-                let launched_binary = self.launched_binary.get().unwrap();
-                launched_binary.stdin().send("stop\n".to_owned())?;
-
-                let timeout_result = ProgressTracker::leaf(
-                    "waiting for exit",
-                    tokio::time::timeout(Duration::from_secs(60), launched_binary.wait()),
-                )
-                .await;
-                match timeout_result {
-                    Err(_timeout) => {} // `wait()` timed out, but stop will force quit.
-                    Ok(Err(unexpected_error)) => return Err(unexpected_error), // `wait()` errored.
-                    Ok(Ok(_exit_status)) => {}
-                }
-                launched_binary.stop().await?;
+        ProgressTracker::with_group(self.display_id(), None, || self.stop_binary()).await
+    }
 
-                Ok(())
-            },
-        )
+    async fn drain(&self) -> Result<()> {
+        ProgressTracker::with_group(self.display_id(), None, || self.drain_binary()).await
+    }
+
+    fn retain_logs(&self) {
+        let _ = self.log_capture.set(LogCapture::default());
+    }
+
+    fn logs(&self) -> Option<ServiceLogs> {
+        let mut logs = self.log_capture.get()?.logs();
+        if let Some(binary) = self.launched_binary.read().unwrap().as_ref() {
+            logs.exit_code = binary.exit_code();
+            #[cfg(feature = "profile-folding")]
+            {
+                logs.perf = binary
+                    .tracing_results()
+                    .map(|results| results.folded_data.clone());
+            }
+        }
+        Some(logs)
+    }
+
+    fn detach(&self) -> Option<String> {
+        self.launched_binary.read().unwrap().as_ref()?.detach()
+    }
+
+    fn host(&self) -> Option<Arc<dyn Host>> {
+        Some(self.on.clone())
+    }
+
+    fn current_binary(&self) -> Option<Arc<dyn LaunchedBinary>> {
+        self.launched_binary.read().unwrap().clone()
+    }
+
+    fn hooks(&self) -> Hooks {
+        self.hooks.lock().unwrap().clone()
+    }
+
+    async fn restart(&self) -> Result<()> {
+        let _launching = self.launch_lock.lock().await;
+        ProgressTracker::with_group(self.display_id(), None, || async {
+            self.stop_binary().await?;
+            let binary = self.launch().await?;
+            *self.launched_binary.write().unwrap() = Some(binary);
+            self.send_start().await
+        })
+        .await
+    }
+
+    async fn fingerprint(&self) -> Result<Option<String>> {
+        let mut hasher = blake3::Hasher::new();
+        match &self.source {
+            BinarySource::Build(build_params) => {
+                hasher.update(build_params.source_hash().await?.as_bytes());
+            }
+            BinarySource::Prebuilt(prebuilt_params) => {
+                hasher.update(format!("{:?}", prebuilt_params).as_bytes());
+            }
+        }
+
+        #[expect(
+            clippy::disallowed_methods,
+            reason = "nondeterministic iteration order, collected into a sorted map"
+        )]
+        let env = self.env.iter().collect::<BTreeMap<_, _>>();
+        hasher.update(format!("{:?} {:?} {:?}", self.args, env, self.assets).as_bytes());
+
+        let connections = self
+            .port_to_server
+            .iter()
+            .map(|(port_name, server)| (port_name.clone(), server.describe()))
+            .collect::<BTreeMap<_, _>>();
+        let bound = self
+            .port_to_bind
+            .iter()
+            .map(|(port_name, _)| port_name.clone())
+            .collect::<BTreeSet<_>>();
+        hasher.update(format!("{:?} {:?}", connections, bound).as_bytes());
+
+        Ok(Some(hasher.finalize().to_hex().to_string()))
+    }
+
+    fn server_dependencies(&self) -> BTreeSet<String> {
+        let mut services = BTreeSet::new();
+        for (_, server) in self.port_to_server.iter() {
+            server.collect_services(&mut services);
+        }
+        services
+    }
+
+    async fn redeploy(&self) -> Result<()> {
+        ProgressTracker::with_group(self.display_id(), None, || async {
+            if let BinarySource::Build(build_params) = &self.source {
+                // Built again for the current revision of the source, which is a no-op for cargo
+                // (and the binary is not uploaded again) if the code did not change.
+                *self.build_revision.lock().unwrap() = Some(build_params.source_hash().await?);
+                let built = self.build().await?;
+                let binary_id = built.unique_id().to_string();
+                if self.deployed_binary_id.lock().unwrap().as_ref() != Some(&binary_id) {
+                    let launched_host = self.launched_host.get().unwrap();
+                    launched_host.copy_binary(built).await?;
+                    if let Some(state) = self.state.get() {
+                        state.record_binary(self.on.id(), binary_id.clone())?;
+                    }
+                    *self.deployed_binary_id.lock().unwrap() = Some(binary_id);
+                }
+            }
+            // Assets are copied again, as they may have changed.
+            self.copy_assets(&**self.launched_host.get().unwrap())
+                .await?;
+
+            self.stop_binary().await?;
+            let binary = self.launch().await?;
+            *self.launched_binary.write().unwrap() = Some(binary);
+            *self.started.lock().await = false;
+            Ok(())
+        })
         .await
     }
 }
+
+/// Whether `strategy` binds any port through the public IP of its host.
+fn has_external_port(strategy: &ServerStrategy) -> bool {
+    match strategy {
+        ServerStrategy::Direct(base) | ServerStrategy::Many(base) => {
+            matches!(base, BaseServerStrategy::ExternalTcpPort(_))
+        }
+        ServerStrategy::Demux(demux) => demux.values().any(has_external_port),
+        ServerStrategy::Merge(merge) => merge.iter().any(has_external_port),
+        ServerStrategy::Tagged(underlying, _) => has_external_port(underlying),
+        ServerStrategy::Null => false,
+    }
+}
@@ -0,0 +1,236 @@
+//! Helper processes which run alongside a [`RustCrateService`](super::RustCrateService).
+//!
+//! A [`Sidecar`] is a program already installed on the host (for example a local Redis, or a
+//! proxy) which the service depends on. Sidecars are launched, and must pass their
+//! [`HealthCheck`], before the service's binary is launched. They are stopped after the binary
+//! when the service is stopped or drained.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+
+use crate::progress::ProgressTracker;
+use crate::{LaunchedBinary, LaunchedHost};
+
+/// Default time allowed for a [`Sidecar`] to become healthy.
+pub const DEFAULT_HEALTH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a [`HealthCheck`] is retried until it passes.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How to check that a [`Sidecar`] is ready to be used.
+#[derive(Clone, Debug)]
+pub enum HealthCheck {
+    /// The sidecar is considered healthy as soon as it has been launched.
+    None,
+    /// The sidecar is healthy once a TCP connection to this port on the host succeeds.
+    ///
+    /// The connection is made from the deploying machine through [`LaunchedHost::forward_port`],
+    /// so on remote hosts (where the forwarded port accepts connections before reaching the
+    /// sidecar) prefer [`HealthCheck::Command`].
+    TcpPort(u16),
+    /// The sidecar is healthy once this command, run on the host, exits successfully.
+    Command {
+        /// The program to run, e.g. `redis-cli`.
+        program: String,
+        /// The arguments to the program, e.g. `["ping"]`.
+        args: Vec<String>,
+    },
+}
+
+/// A helper process which is launched and health-checked before a service's binary, and stopped
+/// along with it.
+#[derive(Clone, Debug)]
+pub struct Sidecar {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    health_check: HealthCheck,
+    timeout: Duration,
+}
+
+impl Sidecar {
+    /// Creates a sidecar named `name` (used in logs) which runs `program`, which must already be
+    /// installed on the host.
+    pub fn new(name: impl Into<String>, program: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            program: program.into(),
+            args: vec![],
+            env: HashMap::new(),
+            health_check: HealthCheck::None,
+            timeout: DEFAULT_HEALTH_TIMEOUT,
+        }
+    }
+
+    /// Sets the arguments to be passed to the program when it is launched.
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(|s| s.into()));
+        self
+    }
+
+    /// Sets an environment variable for the program.
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets how to check that the sidecar is ready, [`HealthCheck::None`] by default.
+    pub fn health_check(mut self, health_check: HealthCheck) -> Self {
+        self.health_check = health_check;
+        self
+    }
+
+    /// Sets how long the sidecar has to pass its health check, [`DEFAULT_HEALTH_TIMEOUT`] by
+    /// default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Launches the sidecar on `host` and waits for it to become healthy. The sidecar is stopped
+    /// if it does not.
+    pub(super) async fn launch(
+        &self,
+        service_id: &str,
+        host: &Arc<dyn LaunchedHost>,
+    ) -> Result<Box<dyn LaunchedBinary>> {
+        let id = format!("{}/{}", service_id, self.name);
+        let binary = host
+            .launch_command(id.clone(), &self.program, &self.args, &self.env)
+            .await?;
+
+        let healthy = ProgressTracker::leaf(
+            format!("waiting for sidecar {}", self.name),
+            tokio::time::timeout(self.timeout, self.wait_healthy(&id, host, &*binary)),
+        )
+        .await;
+        let result = match healthy {
+            Ok(Ok(())) => return Ok(binary),
+            Ok(Err(err)) => Err(err),
+            Err(_timeout) => Err(anyhow::anyhow!(
+                "Timed out waiting for sidecar `{}` to become healthy",
+                self.name
+            )),
+        };
+
+        binary.stop().await?;
+        result
+    }
+
+    /// Retries the health check until it passes, or fails if the sidecar exits.
+    async fn wait_healthy(
+        &self,
+        id: &str,
+        host: &Arc<dyn LaunchedHost>,
+        binary: &dyn LaunchedBinary,
+    ) -> Result<()> {
+        let forwarded = match &self.health_check {
+            HealthCheck::None => return Ok(()),
+            HealthCheck::TcpPort(port) => Some(
+                host.forward_port(&SocketAddr::from((Ipv4Addr::LOCALHOST, *port)))
+                    .await?,
+            ),
+            HealthCheck::Command { .. } => None,
+        };
+
+        loop {
+            if let Some(code) = binary.exit_code() {
+                bail!(
+                    "Sidecar `{}` exited with code {} before becoming healthy",
+                    self.name,
+                    code
+                );
+            }
+
+            let passed = match &self.health_check {
+                HealthCheck::None => true,
+                HealthCheck::TcpPort(_) => tokio::net::TcpStream::connect(forwarded.unwrap())
+                    .await
+                    .is_ok(),
+                HealthCheck::Command { program, args } => {
+                    let probe = host
+                        .launch_command(format!("{id} health"), program, args, &HashMap::new())
+                        .await?;
+                    let code = probe.wait().await?;
+                    probe.stop().await?;
+                    code == 0
+                }
+            };
+            if passed {
+                return Ok(());
+            }
+
+            tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::Host;
+    use crate::localhost::LocalhostHost;
+
+    fn localhost() -> Arc<dyn LaunchedHost> {
+        LocalhostHost::new(0).launched().unwrap()
+    }
+
+    fn probe(program: &str) -> HealthCheck {
+        HealthCheck::Command {
+            program: program.to_owned(),
+            args: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn launches_once_healthy() {
+        let host = localhost();
+        let binary = Sidecar::new("sleeper", "sleep")
+            .args(["30"])
+            .health_check(probe("true"))
+            .launch("service", &host)
+            .await
+            .unwrap();
+        assert_eq!(None, binary.exit_code());
+        binary.stop().await.unwrap();
+
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let binary = Sidecar::new("sleeper", "sleep")
+            .args(["30"])
+            .health_check(HealthCheck::TcpPort(port))
+            .launch("service", &host)
+            .await
+            .unwrap();
+        binary.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_if_never_healthy() {
+        let err = Sidecar::new("sleeper", "sleep")
+            .args(["30"])
+            .health_check(probe("false"))
+            .timeout(Duration::from_millis(500))
+            .launch("service", &localhost())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Timed out"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn fails_if_exited() {
+        let err = Sidecar::new("quitter", "sh")
+            .args(["-c", "exit 3"])
+            .health_check(probe("false"))
+            .timeout(Duration::from_secs(10))
+            .launch("service", &localhost())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exited with code 3"), "{err}");
+    }
+}
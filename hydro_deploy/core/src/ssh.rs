@@ -32,11 +32,15 @@ use tokio_util::io::SyncIoBridge;
 
 #[cfg(feature = "profile-folding")]
 use crate::TracingResults;
+use crate::logs::RetainedLines;
 use crate::progress::ProgressTracker;
+use crate::rust_crate::assets::Asset;
 use crate::rust_crate::build::BuildOutput;
 #[cfg(feature = "profile-folding")]
 use crate::rust_crate::flamegraph::handle_fold_data;
+use crate::rust_crate::resource_limits::ResourceLimits;
 use crate::rust_crate::tracing_options::TracingOptions;
+use crate::systemd::{self, SystemdOptions};
 use crate::util::{PriorityBroadcast, async_retry, prioritized_broadcast};
 use crate::{BaseServerStrategy, LaunchedBinary, LaunchedHost, ResourceResult};
 
@@ -83,6 +87,11 @@ impl LaunchedBinary for LaunchedSshBinary {
         self.stderr_broadcast.receive(Some(prefix))
     }
 
+    fn retain_output(&self, stdout: RetainedLines, stderr: RetainedLines) {
+        self.stdout_broadcast.retain(stdout);
+        self.stderr_broadcast.retain(stderr);
+    }
+
     #[cfg(feature = "profile-folding")]
     fn tracing_results(&self) -> Option<&TracingResults> {
         self.tracing_results.get()
@@ -228,10 +237,23 @@ impl Drop for LaunchedSshBinary {
 pub trait LaunchedSshHost: Send + Sync {
     fn get_internal_ip(&self) -> &str;
     fn get_external_ip(&self) -> Option<&str>;
+
+    /// The public IPv6 address of the host, if it was provisioned with
+    /// [`IpStack::DualStack`](crate::IpStack::DualStack).
+    fn get_external_ipv6(&self) -> Option<&str> {
+        None
+    }
+
     fn get_cloud_provider(&self) -> &'static str;
     fn resource_result(&self) -> &Arc<ResourceResult>;
     fn ssh_user(&self) -> &str;
 
+    /// If set, binaries are launched as systemd units rather than as children of the SSH
+    /// session, see [`crate::systemd`].
+    fn systemd(&self) -> Option<&SystemdOptions> {
+        None
+    }
+
     fn ssh_key_path(&self) -> PathBuf {
         self.resource_result()
             .terraform
@@ -284,7 +306,58 @@ pub trait LaunchedSshHost: Send + Sync {
     }
 }
 
-async fn create_channel<H>(session: &AsyncSession<H>) -> Result<AsyncChannel>
+/// Runs `command` in a new channel of `session`, forwarding its stdin and output.
+async fn exec_command<T: LaunchedSshHost>(
+    host: &T,
+    session: AsyncSession<NoCheckHandler>,
+    id: String,
+    label: String,
+    command: String,
+    tracing: Option<TracingOptions>,
+) -> Result<Box<dyn LaunchedBinary>> {
+    let (channel, stdout, stderr) = ProgressTracker::leaf(label, async {
+        let channel = create_channel(&session).await?;
+        // Make sure to begin reading stdout/stderr before running the command.
+        let (stdout, stderr) = (channel.stdout(), channel.stderr());
+        channel.exec(false, command).await?;
+        anyhow::Ok((channel, stdout, stderr))
+    })
+    .await?;
+
+    let (stdin_sender, mut stdin_receiver) = mpsc::unbounded_channel::<String>();
+    let mut stdin = channel.stdin();
+
+    tokio::spawn(async move {
+        while let Some(line) = stdin_receiver.recv().await {
+            if stdin.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+            stdin.flush().await.unwrap();
+        }
+    });
+
+    let id_clone = id.clone();
+    let stdout_broadcast = prioritized_broadcast(LinesStream::new(stdout.lines()), move |s| {
+        ProgressTracker::println(format!("[{id_clone}] {s}"));
+    });
+    let stderr_broadcast = prioritized_broadcast(LinesStream::new(stderr.lines()), move |s| {
+        ProgressTracker::println(format!("[{id} stderr] {s}"));
+    });
+
+    Ok(Box::new(LaunchedSshBinary {
+        _resource_result: host.resource_result().clone(),
+        session: Some(session),
+        channel,
+        stdin_sender,
+        stdout_broadcast,
+        stderr_broadcast,
+        tracing,
+        #[cfg(feature = "profile-folding")]
+        tracing_results: OnceLock::new(),
+    }))
+}
+
+pub(crate) async fn create_channel<H>(session: &AsyncSession<H>) -> Result<AsyncChannel>
 where
     H: 'static + Handler,
 {
@@ -306,10 +379,30 @@ impl<T: LaunchedSshHost> LaunchedHost for T {
             BaseServerStrategy::InternalTcpPort(hint) => {
                 ServerBindConfig::TcpPort(self.get_internal_ip().to_owned(), *hint)
             }
-            BaseServerStrategy::ExternalTcpPort(_) => todo!(),
+            // Cloud providers route traffic to the public IPv4 address to the internal one, but
+            // traffic to the public IPv6 address is delivered to it directly, so dual-stack
+            // hosts listen on all interfaces.
+            BaseServerStrategy::ExternalTcpPort(port) => {
+                if self.get_external_ipv6().is_some() {
+                    ServerBindConfig::DualStackTcpPort(
+                        self.get_internal_ip().to_owned(),
+                        Some(*port),
+                    )
+                } else {
+                    ServerBindConfig::TcpPort(self.get_internal_ip().to_owned(), Some(*port))
+                }
+            }
         }
     }
 
+    fn external_ip(&self) -> Option<String> {
+        self.get_external_ip().map(str::to_owned)
+    }
+
+    fn external_ipv6(&self) -> Option<String> {
+        self.get_external_ipv6().map(str::to_owned)
+    }
+
     async fn copy_binary(&self, binary: &BuildOutput) -> Result<()> {
         let session = self.open_ssh_session().await?;
 
@@ -372,6 +465,84 @@ impl<T: LaunchedSshHost> LaunchedHost for T {
         Ok(())
     }
 
+    fn data_dir(&self, service_id: usize) -> PathBuf {
+        PathBuf::from(format!("/home/{}/hydro-data-{service_id}", self.ssh_user()))
+    }
+
+    async fn copy_assets(&self, service_id: usize, assets: &[Asset]) -> Result<PathBuf> {
+        let session = self.open_ssh_session().await?;
+
+        let sftp = async_retry(&|| session.open_sftp(), 10, Duration::from_secs(1)).await?;
+
+        let user = self.ssh_user();
+        let asset_dir = PathBuf::from(format!("/home/{user}/hydro-assets-{service_id}"));
+
+        // Remove the assets of a previous run, which may no longer be wanted.
+        let (status, output) = systemd::run_command(
+            &session,
+            format!(
+                "rm -rf {}",
+                shell_escape::unix::escape(asset_dir.to_str().unwrap().into())
+            ),
+        )
+        .await?;
+        if status != 0 {
+            anyhow::bail!(
+                "Failed to clear asset directory {}: {output}",
+                asset_dir.display()
+            );
+        }
+
+        for asset in assets {
+            let remote_path = asset.remote_path_in(&asset_dir);
+            let mut local_file = File::open(&asset.local_path)
+                .await
+                .with_context(|| format!("reading asset {}", asset.local_path.display()))?;
+            let sftp = &sftp;
+            let asset_dir = &asset_dir;
+
+            ProgressTracker::leaf(
+                format!("uploading asset to {}", remote_path.display()),
+                async move {
+                    // Create the directories leading to the asset, outermost first.
+                    let mut dirs = remote_path
+                        .ancestors()
+                        .skip(1)
+                        .take_while(|dir| dir.starts_with(asset_dir))
+                        .collect::<Vec<_>>();
+                    dirs.reverse();
+                    for dir in dirs {
+                        let dir = dir.to_str().unwrap();
+                        if sftp.metadata(dir).await.is_err() {
+                            sftp.create_dir(dir).await?;
+                        }
+                    }
+
+                    let mut created_file = sftp.create(remote_path.to_str().unwrap()).await?;
+                    tokio::io::copy(&mut local_file, &mut created_file).await?;
+                    created_file.sync_all().await?;
+                    anyhow::Ok(())
+                },
+            )
+            .await?;
+        }
+        sftp.close().await?;
+
+        Ok(asset_dir)
+    }
+
+    async fn reattach_binary(
+        &self,
+        id: String,
+        handle: &str,
+    ) -> Result<Option<Box<dyn LaunchedBinary>>> {
+        if self.systemd().is_none() {
+            return Ok(None);
+        }
+        let session = self.open_ssh_session().await?;
+        systemd::reattach_unit(self, session, id, handle).await
+    }
+
     async fn launch_binary(
         &self,
         id: String,
@@ -380,21 +551,56 @@ impl<T: LaunchedSshHost> LaunchedHost for T {
         tracing: Option<TracingOptions>,
         env: &HashMap<String, String>,
         pin_to_core: Option<usize>,
+    ) -> Result<Box<dyn LaunchedBinary>> {
+        self.launch_binary_with_limits(
+            id,
+            binary,
+            args,
+            tracing,
+            env,
+            &ResourceLimits::pinned_to(pin_to_core),
+        )
+        .await
+    }
+
+    async fn launch_binary_with_limits(
+        &self,
+        id: String,
+        binary: &BuildOutput,
+        args: &[String],
+        tracing: Option<TracingOptions>,
+        env: &HashMap<String, String>,
+        resources: &ResourceLimits,
     ) -> Result<Box<dyn LaunchedBinary>> {
         let session = self.open_ssh_session().await?;
 
         let user = self.ssh_user();
         let binary_path = PathBuf::from(format!("/home/{user}/hydro-{}", binary.unique_id()));
 
+        if let Some(options) = self.systemd() {
+            if tracing.is_some() {
+                anyhow::bail!("Tracing is not supported for binaries launched as systemd units.");
+            }
+
+            let mut command = binary_path.to_str().unwrap().to_owned();
+            for arg in args {
+                command.push(' ');
+                command.push_str(&shell_escape::unix::escape(arg.into()))
+            }
+
+            return systemd::launch_unit(self, session, id, options, command, env, resources).await;
+        }
+
         let mut command = String::new();
-        // Prepend env variables
+        // Prepend env variables, with `env` so that they are passed through the programs which
+        // apply the resource limits.
+        if !env.is_empty() {
+            command.push_str("env ");
+        }
         for (k, v) in env {
             command.push_str(&format!("{}={} ", k, shell_escape::unix::escape(v.into())));
         }
 
-        if let Some(core) = pin_to_core {
-            command.push_str(&format!("taskset -c {core} "));
-        }
         command.push_str(binary_path.to_str().unwrap());
         for arg in args {
             command.push(' ');
@@ -445,49 +651,55 @@ impl<T: LaunchedSshHost> LaunchedHost for T {
             );
         }
 
-        let (channel, stdout, stderr) = ProgressTracker::leaf(
+        let prefix = resources.command_prefix(Some(user));
+        if !prefix.is_empty() {
+            let prefix = prefix
+                .into_iter()
+                .map(|arg| shell_escape::unix::escape(arg.into()).into_owned())
+                .collect::<Vec<_>>()
+                .join(" ");
+            command = format!("{prefix} {command}");
+        }
+
+        exec_command(
+            self,
+            session,
+            id,
             format!("launching binary {}", binary_path.display()),
-            async {
-                let channel = create_channel(&session).await?;
-                // Make sure to begin reading stdout/stderr before running the command.
-                let (stdout, stderr) = (channel.stdout(), channel.stderr());
-                channel.exec(false, command).await?;
-                anyhow::Ok((channel, stdout, stderr))
-            },
+            command,
+            tracing,
         )
-        .await?;
-
-        let (stdin_sender, mut stdin_receiver) = mpsc::unbounded_channel::<String>();
-        let mut stdin = channel.stdin();
+        .await
+    }
 
-        tokio::spawn(async move {
-            while let Some(line) = stdin_receiver.recv().await {
-                if stdin.write_all(line.as_bytes()).await.is_err() {
-                    break;
-                }
-                stdin.flush().await.unwrap();
-            }
-        });
+    async fn launch_command(
+        &self,
+        id: String,
+        program: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<Box<dyn LaunchedBinary>> {
+        let session = self.open_ssh_session().await?;
 
-        let id_clone = id.clone();
-        let stdout_broadcast = prioritized_broadcast(LinesStream::new(stdout.lines()), move |s| {
-            ProgressTracker::println(format!("[{id_clone}] {s}"));
-        });
-        let stderr_broadcast = prioritized_broadcast(LinesStream::new(stderr.lines()), move |s| {
-            ProgressTracker::println(format!("[{id} stderr] {s}"));
-        });
+        let mut command = String::new();
+        for (k, v) in env {
+            command.push_str(&format!("{}={} ", k, shell_escape::unix::escape(v.into())));
+        }
+        command.push_str(&shell_escape::unix::escape(program.into()));
+        for arg in args {
+            command.push(' ');
+            command.push_str(&shell_escape::unix::escape(arg.into()))
+        }
 
-        Ok(Box::new(LaunchedSshBinary {
-            _resource_result: self.resource_result().clone(),
-            session: Some(session),
-            channel,
-            stdin_sender,
-            stdout_broadcast,
-            stderr_broadcast,
-            tracing,
-            #[cfg(feature = "profile-folding")]
-            tracing_results: OnceLock::new(),
-        }))
+        exec_command(
+            self,
+            session,
+            id,
+            format!("launching command {program}"),
+            command,
+            None,
+        )
+        .await
     }
 
     async fn forward_port(&self, addr: &SocketAddr) -> Result<SocketAddr> {
@@ -0,0 +1,456 @@
+//! Persisted state of a deployment, used to resume it after the deploying process crashes.
+//!
+//! A [`Deployment`](crate::Deployment) created with
+//! [`Deployment::resume`](crate::Deployment::resume) records a [`DeploymentState`] in a JSON
+//! file as it is deployed: the terraform folders holding the provisioned resources (and which
+//! resources were provisioned for each host), the names given to those resources, the binaries
+//! uploaded to each host, the ports each service was bound to, and the binaries which were
+//! launched. When the same deployment is resumed from that file, the existing resources are
+//! reused instead of being provisioned from scratch (since they are given the same names),
+//! binaries which were already uploaded are not uploaded again, and services are bound to the
+//! same ports as before, so that external clients can reconnect to them.
+//!
+//! Binaries which outlive the process that launched them, such as those launched as systemd
+//! units (see [`crate::systemd`]), are reattached to if they are still running the same build,
+//! instead of being launched again. Other binaries were stopped along with the process which
+//! crashed, so they are launched again.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use hydro_deploy_integration::ServerBindConfig;
+pub use hydro_deploy_integration::ServerPort;
+use serde::{Deserialize, Serialize};
+
+use crate::terraform::has_terraform_state;
+
+/// The state of a deployment, as persisted in its state file.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DeploymentState {
+    /// The terraform folders holding the provisioned resources, in the order they were
+    /// provisioned (one per call to [`Deployment::deploy`](crate::Deployment::deploy) which
+    /// provisioned resources).
+    pub terraform_folders: Vec<PathBuf>,
+    /// The terraform resources (as `type.name` addresses) provisioned for each host, by host ID.
+    /// Resources shared between hosts, such as networks, are listed for the first host using them.
+    pub hosts: BTreeMap<usize, BTreeSet<String>>,
+    /// The hashes of the binaries uploaded to each host, by host ID.
+    pub binaries: BTreeMap<usize, BTreeSet<String>>,
+    /// The ports each service was bound to, by service display ID and port name.
+    pub ports: BTreeMap<String, BTreeMap<String, ServerPort>>,
+    /// The IDs used in the names of provisioned resources, by resource kind and ordinal.
+    #[serde(default)]
+    pub resource_ids: BTreeMap<String, String>,
+    /// The binary launched for each service which can be reattached to, by service display ID.
+    #[serde(default)]
+    pub launched: BTreeMap<String, LaunchedState>,
+}
+
+/// A binary launched for a service, which can be reattached to while it keeps running.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LaunchedState {
+    /// Identifies the running binary to its host, see
+    /// [`LaunchedBinary::resume_handle`](crate::LaunchedBinary::resume_handle).
+    pub handle: String,
+    /// The unique ID of the build the binary runs.
+    pub binary: String,
+}
+
+/// Assigns the IDs used in the names of provisioned resources, so that resuming a deployment
+/// gives its resources the same names (and so reuses them) instead of fresh random ones.
+///
+/// Each resource is identified by its kind and the ordinal of the instance (of that kind) which
+/// requested it, which is stable as long as the deployment is set up in the same way.
+#[derive(Debug, Default)]
+pub(crate) struct ResourceIds {
+    /// The IDs assigned by the previous run of the deployment, by `kind/ordinal`.
+    previous: BTreeMap<String, String>,
+    /// The IDs assigned so far, by `kind/ordinal`.
+    assigned: BTreeMap<String, String>,
+    /// The `kind/ordinal` key of each instance which has been assigned an ID, by kind and
+    /// instance.
+    instances: HashMap<(String, String), String>,
+}
+
+impl ResourceIds {
+    pub(crate) fn new(previous: BTreeMap<String, String>) -> Self {
+        Self {
+            previous,
+            ..Self::default()
+        }
+    }
+
+    /// The ID of the resource of the given `kind` requested by `instance`, generating a fresh
+    /// one from its `kind/ordinal` key if it was not assigned before.
+    pub(crate) fn get(
+        &mut self,
+        kind: &str,
+        instance: &str,
+        fresh: impl FnOnce(&str) -> String,
+    ) -> String {
+        let instance_key = (kind.to_owned(), instance.to_owned());
+        if let Some(key) = self.instances.get(&instance_key) {
+            return self.assigned[key].clone();
+        }
+
+        let ordinal = self
+            .assigned
+            .range(format!("{kind}/")..format!("{kind}0"))
+            .count();
+        let key = format!("{kind}/{ordinal}");
+        let id = self
+            .previous
+            .get(&key)
+            .cloned()
+            .unwrap_or_else(|| fresh(&key));
+        self.assigned.insert(key.clone(), id.clone());
+        self.instances.insert(instance_key, key);
+        id
+    }
+
+    /// The IDs assigned so far, to be recorded in the state.
+    pub(crate) fn assigned(&self) -> &BTreeMap<String, String> {
+        &self.assigned
+    }
+}
+
+impl DeploymentState {
+    /// Reads the state from `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)
+            .with_context(|| format!("Failed to read deployment state {}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("Invalid deployment state {}", path.display()))
+    }
+
+    /// Writes the state to `path`, replacing the previous state atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_vec_pretty(self).unwrap())
+            .with_context(|| format!("Failed to write deployment state {}", path.display()))?;
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("Failed to write deployment state {}", path.display()))
+    }
+}
+
+/// A [`DeploymentState`] being recorded to (and resumed from) a state file.
+#[derive(Debug)]
+pub(crate) struct StateFile {
+    path: PathBuf,
+    /// The state as of the start of the deployment, which is being resumed.
+    previous: DeploymentState,
+    current: Mutex<DeploymentState>,
+}
+
+impl StateFile {
+    /// Opens the state file at `path`, resuming from its contents if it exists.
+    pub(crate) fn open(path: PathBuf) -> Result<Self> {
+        let previous = if path.exists() {
+            DeploymentState::load(&path)?
+        } else {
+            DeploymentState::default()
+        };
+
+        // Binaries are content-addressed, so they remain valid as long as the hosts do.
+        let hosts_remain = previous
+            .terraform_folders
+            .iter()
+            .all(|folder| has_terraform_state(folder));
+        let current = DeploymentState {
+            binaries: if hosts_remain {
+                previous.binaries.clone()
+            } else {
+                BTreeMap::new()
+            },
+            ..DeploymentState::default()
+        };
+
+        Ok(Self {
+            path,
+            previous,
+            current: Mutex::new(current),
+        })
+    }
+
+    /// The IDs used in the names of resources provisioned by the previous run, see
+    /// [`ResourceIds`].
+    pub(crate) fn previous_resource_ids(&self) -> BTreeMap<String, String> {
+        self.previous.resource_ids.clone()
+    }
+
+    /// The terraform folder of the next provisioning step, if it was provisioned before and
+    /// has not since been destroyed.
+    pub(crate) fn previous_terraform_folder(&self) -> Option<PathBuf> {
+        let index = self.current.lock().unwrap().terraform_folders.len();
+        self.previous
+            .terraform_folders
+            .get(index)
+            .filter(|folder| has_terraform_state(folder))
+            .cloned()
+    }
+
+    /// Records the folder of a terraform provisioning step, along with the resources of each host
+    /// and the IDs used in their names.
+    pub(crate) fn record_terraform(
+        &self,
+        folder: PathBuf,
+        hosts: BTreeMap<usize, BTreeSet<String>>,
+        resource_ids: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        current.terraform_folders.push(folder);
+        current.hosts.extend(hosts);
+        current
+            .resource_ids
+            .extend(resource_ids.iter().map(|(k, v)| (k.clone(), v.clone())));
+        current.save(&self.path)
+    }
+
+    /// Whether the binary with the given hash was already uploaded to the host.
+    pub(crate) fn has_binary(&self, host_id: usize, hash: &str) -> bool {
+        self.current
+            .lock()
+            .unwrap()
+            .binaries
+            .get(&host_id)
+            .is_some_and(|hashes| hashes.contains(hash))
+    }
+
+    /// Records that the binary with the given hash was uploaded to the host.
+    pub(crate) fn record_binary(&self, host_id: usize, hash: String) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        current.binaries.entry(host_id).or_default().insert(hash);
+        current.save(&self.path)
+    }
+
+    /// Binds the ports of a service to those it was bound to before, if any, where the port
+    /// would otherwise be chosen automatically.
+    pub(crate) fn pin_ports(
+        &self,
+        service: &str,
+        bind_config: HashMap<String, ServerBindConfig>,
+    ) -> HashMap<String, ServerBindConfig> {
+        let Some(previous) = self.previous.ports.get(service) else {
+            return bind_config;
+        };
+
+        bind_config
+            .into_iter()
+            .map(|(name, config)| {
+                let config = match previous.get(&name) {
+                    Some(port) => pin_port(config, port),
+                    None => config,
+                };
+                (name, config)
+            })
+            .collect()
+    }
+
+    /// The ports a service was bound to by the previous run, if any.
+    pub(crate) fn previous_ports(&self, service: &str) -> Option<HashMap<String, ServerPort>> {
+        self.previous.ports.get(service).map(|ports| {
+            ports
+                .iter()
+                .map(|(name, port)| (name.clone(), port.clone()))
+                .collect()
+        })
+    }
+
+    /// The binary launched for a service by the previous run, if it can be reattached to.
+    pub(crate) fn previous_launch(&self, service: &str) -> Option<LaunchedState> {
+        self.previous.launched.get(service).cloned()
+    }
+
+    /// Records the binary launched for a service, which can be reattached to.
+    pub(crate) fn record_launch(&self, service: String, launched: LaunchedState) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        current.launched.insert(service, launched);
+        current.save(&self.path)
+    }
+
+    /// Records the ports a service was bound to.
+    #[expect(
+        clippy::disallowed_methods,
+        reason = "nondeterministic iteration order, collected into a sorted map"
+    )]
+    pub(crate) fn record_ports(
+        &self,
+        service: String,
+        ports: &HashMap<String, ServerPort>,
+    ) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        current.ports.insert(
+            service,
+            ports
+                .iter()
+                .map(|(name, port)| (name.clone(), port.clone()))
+                .collect(),
+        );
+        current.save(&self.path)
+    }
+}
+
+/// Fills in the automatically chosen TCP ports of `config` with those of `previous`.
+pub(crate) fn pin_port(config: ServerBindConfig, previous: &ServerPort) -> ServerBindConfig {
+    match (config, previous) {
+        (ServerBindConfig::TcpPort(host, None), ServerPort::TcpPort(addr)) => {
+            ServerBindConfig::TcpPort(host, Some(addr.port()))
+        }
+        (ServerBindConfig::DualStackTcpPort(host, None), ServerPort::TcpPort(addr)) => {
+            ServerBindConfig::DualStackTcpPort(host, Some(addr.port()))
+        }
+        (ServerBindConfig::Demux(demux), ServerPort::Demux(previous)) => ServerBindConfig::Demux(
+            demux
+                .into_iter()
+                .map(|(key, config)| {
+                    let config = match previous.get(&key) {
+                        Some(port) => pin_port(config, port),
+                        None => config,
+                    };
+                    (key, config)
+                })
+                .collect(),
+        ),
+        (ServerBindConfig::Merge(merge), ServerPort::Merge(previous))
+            if merge.len() == previous.len() =>
+        {
+            ServerBindConfig::Merge(
+                merge
+                    .into_iter()
+                    .zip(previous)
+                    .map(|(config, port)| pin_port(config, port))
+                    .collect(),
+            )
+        }
+        (ServerBindConfig::Tagged(config, tag), ServerPort::Tagged(port, previous_tag))
+            if tag == *previous_tag =>
+        {
+            ServerBindConfig::Tagged(Box::new(pin_port(*config, port)), tag)
+        }
+        (ServerBindConfig::MultiConnection(config), port) => {
+            ServerBindConfig::MultiConnection(Box::new(pin_port(*config, port)))
+        }
+        (config, _) => config,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    #[test]
+    fn pin_port_fills_in_automatic_ports() {
+        let previous = ServerPort::Demux(BTreeMap::from([
+            (
+                0,
+                ServerPort::TcpPort("10.0.0.1:4000".parse::<SocketAddr>().unwrap()),
+            ),
+            (
+                1,
+                ServerPort::TcpPort("10.0.0.1:4001".parse::<SocketAddr>().unwrap()),
+            ),
+        ]));
+        let config = ServerBindConfig::Demux(BTreeMap::from([
+            (0, ServerBindConfig::TcpPort("10.0.0.1".to_owned(), None)),
+            (
+                1,
+                ServerBindConfig::TcpPort("10.0.0.1".to_owned(), Some(5000)),
+            ),
+        ]));
+
+        let ServerBindConfig::Demux(pinned) = pin_port(config, &previous) else {
+            panic!("expected demux");
+        };
+        assert!(matches!(
+            &pinned[&0],
+            ServerBindConfig::TcpPort(_, Some(4000))
+        ));
+        // Explicitly requested ports are kept.
+        assert!(matches!(
+            &pinned[&1],
+            ServerBindConfig::TcpPort(_, Some(5000))
+        ));
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let state = StateFile::open(path.clone()).unwrap();
+        state.record_binary(0, "abc".to_owned()).unwrap();
+        state
+            .record_ports(
+                "service/0".to_owned(),
+                &HashMap::from([(
+                    "out".to_owned(),
+                    ServerPort::TcpPort("127.0.0.1:4000".parse().unwrap()),
+                )]),
+            )
+            .unwrap();
+
+        let resumed = StateFile::open(path).unwrap();
+        assert!(resumed.has_binary(0, "abc"));
+        assert!(!resumed.has_binary(1, "abc"));
+        let pinned = resumed.pin_ports(
+            "service/0",
+            HashMap::from([(
+                "out".to_owned(),
+                ServerBindConfig::TcpPort("127.0.0.1".to_owned(), None),
+            )]),
+        );
+        assert!(matches!(
+            &pinned["out"],
+            ServerBindConfig::TcpPort(_, Some(4000))
+        ));
+    }
+
+    #[test]
+    fn launches_and_resource_ids_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let launched = LaunchedState {
+            handle: "hydro-service-0-abc".to_owned(),
+            binary: "hash".to_owned(),
+        };
+
+        let state = StateFile::open(path.clone()).unwrap();
+        let mut ids = ResourceIds::new(state.previous_resource_ids());
+        let network_id = ids.get("network", "random-1", |_| "first".to_owned());
+        state
+            .record_terraform(
+                dir.path().join("terraform"),
+                BTreeMap::new(),
+                ids.assigned(),
+            )
+            .unwrap();
+        state
+            .record_launch("service/0".to_owned(), launched.clone())
+            .unwrap();
+
+        let resumed = StateFile::open(path).unwrap();
+        assert_eq!(Some(launched), resumed.previous_launch("service/0"));
+        assert_eq!(None, resumed.previous_launch("service/1"));
+
+        // The same network (with a new random instance ID) gets the same resource ID.
+        let mut ids = ResourceIds::new(resumed.previous_resource_ids());
+        assert_eq!(
+            network_id,
+            ids.get("network", "random-2", |_| "second".to_owned())
+        );
+        assert_eq!(
+            network_id,
+            ids.get("network", "random-2", |_| unreachable!())
+        );
+        assert_eq!(
+            "second",
+            ids.get("network", "random-3", |_| "second".to_owned())
+        );
+    }
+}
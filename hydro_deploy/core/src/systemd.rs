@@ -0,0 +1,592 @@
+//! Running binaries on SSH hosts as systemd units, see [`SystemdOptions`].
+//!
+//! By default, a binary launched on an SSH host is a foreground child of the SSH session, so it
+//! is stopped as soon as the session is closed (for example when the deploying process exits). A
+//! host configured with [`SystemdOptions`] instead launches each binary as a transient systemd
+//! unit (with `systemd-run`), which runs independently of the session, is restarted according to
+//! its [`SystemdRestart`] policy, and logs its stdout to the journal (and its stderr to a file
+//! next to it, since the journal does not keep the two apart). Together with
+//! [`Deployment::detach`](crate::Deployment::detach), this lets long-running services outlive the
+//! process which deployed them.
+//!
+//! The standard input of the binary is appended to a file on the host, which the unit follows.
+//! The port configuration sent by Hydro Deploy when the binary was launched is also recorded, and
+//! an instance restarted by systemd receives it again, followed by the input sent after it was
+//! restarted. Other input (such as a request to drain) is not replayed.
+//!
+//! A resumed deployment (see [`crate::state`]) reattaches to units which are still running with
+//! [`reattach_unit`], following their output from that point on.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use async_ssh2_russh::russh::Disconnect;
+use async_ssh2_russh::{AsyncChannel, AsyncSession, NoCheckHandler};
+use async_trait::async_trait;
+use nanoid::nanoid;
+use shell_escape::unix::escape;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::LinesStream;
+
+#[cfg(feature = "profile-folding")]
+use crate::TracingResults;
+use crate::logs::RetainedLines;
+use crate::progress::ProgressTracker;
+use crate::rust_crate::resource_limits::ResourceLimits;
+use crate::ssh::{LaunchedSshHost, create_channel};
+use crate::terraform::TERRAFORM_ALPHABET;
+use crate::util::{PriorityBroadcast, prioritized_broadcast};
+use crate::{LaunchedBinary, ResourceResult};
+
+/// When systemd restarts a binary after it exits, see the `Restart=` option of `systemd.service`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SystemdRestart {
+    /// Never restart the binary.
+    No,
+    /// Restart the binary if it exits with a non-zero exit code or is killed by a signal.
+    #[default]
+    OnFailure,
+    /// Always restart the binary, even if it exits cleanly.
+    Always,
+}
+
+impl SystemdRestart {
+    fn as_str(self) -> &'static str {
+        match self {
+            SystemdRestart::No => "no",
+            SystemdRestart::OnFailure => "on-failure",
+            SystemdRestart::Always => "always",
+        }
+    }
+}
+
+/// Configures an SSH host to launch binaries as systemd units, see [`crate::systemd`].
+///
+/// The host must run systemd, and its SSH user must be able to use `sudo` without a password.
+#[derive(Clone, Debug, Default)]
+pub struct SystemdOptions {
+    /// When systemd restarts the binary after it exits.
+    pub restart: SystemdRestart,
+    /// How long systemd waits before restarting the binary, or systemd's default if `None`.
+    pub restart_delay: Option<Duration>,
+}
+
+/// A binary running as a systemd unit, launched by [`launch_unit`].
+struct LaunchedSystemdBinary {
+    _resource_result: Arc<ResourceResult>,
+    session: Option<AsyncSession<NoCheckHandler>>,
+    unit: String,
+    /// Instructions for reattaching to the unit, see [`LaunchedBinary::detach`].
+    reattach: String,
+    /// Runs `journalctl`, whose output is the stdout of the unit.
+    _journal: AsyncChannel,
+    /// Follows the file with the stderr of the unit.
+    _stderr_tail: AsyncChannel,
+    stdin_sender: mpsc::UnboundedSender<String>,
+    stdout_broadcast: PriorityBroadcast,
+    stderr_broadcast: PriorityBroadcast,
+    /// The exit code of the unit, once it has stopped (and will not be restarted).
+    exit_code: watch::Receiver<Option<i32>>,
+}
+
+#[async_trait]
+impl LaunchedBinary for LaunchedSystemdBinary {
+    fn stdin(&self) -> mpsc::UnboundedSender<String> {
+        self.stdin_sender.clone()
+    }
+
+    fn deploy_stdout(&self) -> oneshot::Receiver<String> {
+        self.stdout_broadcast.receive_priority()
+    }
+
+    fn stdout(&self) -> mpsc::UnboundedReceiver<String> {
+        self.stdout_broadcast.receive(None)
+    }
+
+    fn stderr(&self) -> mpsc::UnboundedReceiver<String> {
+        self.stderr_broadcast.receive(None)
+    }
+
+    fn stdout_filter(&self, prefix: String) -> mpsc::UnboundedReceiver<String> {
+        self.stdout_broadcast.receive(Some(prefix))
+    }
+
+    fn stderr_filter(&self, prefix: String) -> mpsc::UnboundedReceiver<String> {
+        self.stderr_broadcast.receive(Some(prefix))
+    }
+
+    fn retain_output(&self, stdout: RetainedLines, stderr: RetainedLines) {
+        self.stdout_broadcast.retain(stdout);
+        self.stderr_broadcast.retain(stderr);
+    }
+
+    #[cfg(feature = "profile-folding")]
+    fn tracing_results(&self) -> Option<&TracingResults> {
+        None
+    }
+
+    fn exit_code(&self) -> Option<i32> {
+        *self.exit_code.borrow()
+    }
+
+    async fn wait(&self) -> Result<i32> {
+        let exit_code = *self
+            .exit_code
+            .clone()
+            .wait_for(Option::is_some)
+            .await?
+            .as_ref()
+            .unwrap();
+        Ok(exit_code)
+    }
+
+    async fn stop(&self) -> Result<()> {
+        let session = self.session.as_ref().unwrap();
+        ProgressTracker::leaf(format!("stopping unit {}", self.unit), async {
+            let (status, output) =
+                run_command(session, format!("sudo systemctl stop {}", self.unit)).await?;
+            if status != 0 {
+                bail!("Failed to stop unit {}: {}", self.unit, output);
+            }
+            // Unload the unit if it failed, and clean up its input. Errors are ignored since the
+            // unit has already stopped.
+            run_command(
+                session,
+                format!(
+                    "sudo systemctl reset-failed {} ; sudo rm -f {} {} {}",
+                    self.unit,
+                    unit_file(&self.unit, "stdin"),
+                    unit_file(&self.unit, "init"),
+                    unit_file(&self.unit, "stderr"),
+                ),
+            )
+            .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    fn detach(&self) -> Option<String> {
+        Some(self.reattach.clone())
+    }
+
+    fn resume_handle(&self) -> Option<String> {
+        Some(self.unit.clone())
+    }
+}
+
+impl Drop for LaunchedSystemdBinary {
+    fn drop(&mut self) {
+        // Only closes the session, the unit keeps running until it is stopped.
+        if let Some(session) = self.session.take() {
+            tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(session.disconnect(
+                    Disconnect::ByApplication,
+                    "",
+                    "",
+                ))
+            })
+            .unwrap();
+        }
+    }
+}
+
+/// Runs `command` in a new channel of `session` until it exits, returning its exit code and
+/// combined output.
+pub(crate) async fn run_command(
+    session: &AsyncSession<NoCheckHandler>,
+    command: String,
+) -> Result<(u32, String)> {
+    let channel = create_channel(session).await?;
+    let (stdout, stderr) = (channel.stdout(), channel.stderr());
+    channel.exec(false, command).await?;
+
+    let mut output_lines = LinesStream::new(stdout.lines()).merge(LinesStream::new(stderr.lines()));
+    let mut output = String::new();
+    while let Some(line) = output_lines.next().await {
+        output.push_str(&line?);
+        output.push('\n');
+    }
+
+    channel.closed().wait().await;
+    Ok((*channel.recv_exit_status().try_get()?, output))
+}
+
+/// The (shell-escaped) path of the file with the given `extension` kept for the unit `unit`:
+/// `stdin` for its input, `init` for the input replayed when it is restarted, and `stderr` for its
+/// stderr.
+fn unit_file(unit: &str, extension: &str) -> String {
+    escape(format!("/tmp/{unit}.{extension}").into()).into_owned()
+}
+
+/// Launches `command` on `host` as a transient systemd unit, configured by `options`.
+pub(crate) async fn launch_unit<T: LaunchedSshHost>(
+    host: &T,
+    session: AsyncSession<NoCheckHandler>,
+    id: String,
+    options: &SystemdOptions,
+    command: String,
+    env: &HashMap<String, String>,
+    resources: &ResourceLimits,
+) -> Result<Box<dyn LaunchedBinary>> {
+    let sanitized_id = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    let unit = format!("hydro-{sanitized_id}-{}", nanoid!(8, &TERRAFORM_ALPHABET));
+    let systemd_run =
+        systemd_run_command(host.ssh_user(), &unit, options, &command, env, resources);
+
+    attach_unit(host, session, id, unit, Some(systemd_run)).await
+}
+
+/// The command which launches `command` as the unit `unit`, see [`launch_unit`].
+fn systemd_run_command(
+    user: &str,
+    unit: &str,
+    options: &SystemdOptions,
+    command: &str,
+    env: &HashMap<String, String>,
+    resources: &ResourceLimits,
+) -> String {
+    let stdin_path = unit_file(unit, "stdin");
+    let mut systemd_run = format!(
+        "touch {stdin_path} && sudo systemd-run --quiet --unit={unit} --uid={} --working-directory={} --property=Restart={} --property=StandardOutput=journal --property=StandardError=append:{}",
+        escape(user.into()),
+        escape(format!("/home/{user}").into()),
+        options.restart.as_str(),
+        unit_file(unit, "stderr"),
+    );
+    if let Some(restart_delay) = options.restart_delay {
+        systemd_run.push_str(&format!(
+            " --property=RestartSec={}ms",
+            restart_delay.as_millis()
+        ));
+    }
+    for property in resources.systemd_properties() {
+        systemd_run.push_str(&format!(" --property={}", escape(property.into())));
+    }
+    for (k, v) in env {
+        systemd_run.push_str(&format!(" --setenv={}", escape(format!("{k}={v}").into())));
+    }
+    systemd_run.push_str(&format!(
+        " -- bash -c {}",
+        escape(unit_script(command, unit).into())
+    ));
+    systemd_run
+}
+
+/// The script run by the unit `unit`, which runs `command` with the input of the unit.
+///
+/// The first instance follows the input from the start. Once the port configuration has been
+/// recorded (see [`stdin_script`]), restarted instances receive it first and then only follow
+/// input appended from then on.
+fn unit_script(command: &str, unit: &str) -> String {
+    let stdin_path = unit_file(unit, "stdin");
+    let init_path = unit_file(unit, "init");
+    format!(
+        "{command} < <(if [ -e {init_path} ]; then cat {init_path}; exec tail -n 0 -f {stdin_path}; else exec tail -n +1 -f {stdin_path}; fi)"
+    )
+}
+
+/// The script which appends its input to the input file of the unit `unit`, and records the
+/// input which configures the ports of the binary: the last line before the `start: ` line, and
+/// that line itself. Earlier lines are bind configurations which the binary failed to bind.
+fn stdin_script(unit: &str) -> String {
+    let stdin_path = unit_file(unit, "stdin");
+    let init_path = unit_file(unit, "init");
+    format!(
+        "last= ; while IFS= read -r line; do printf '%s\\n' \"$line\" >> {stdin_path}; if [ ! -e {init_path} ]; then case \"$line\" in 'start: '*) printf '%s\\n%s\\n' \"$last\" \"$line\" > {init_path}.partial && mv {init_path}.partial {init_path} ;; *) last=$line ;; esac; fi; done"
+    )
+}
+
+/// Reattaches to the unit `unit` launched on `host` by a previous run of the deployment, if it is
+/// still running. Only output written from now on is received.
+pub(crate) async fn reattach_unit<T: LaunchedSshHost>(
+    host: &T,
+    session: AsyncSession<NoCheckHandler>,
+    id: String,
+    unit: &str,
+) -> Result<Option<Box<dyn LaunchedBinary>>> {
+    let (_, state) = run_command(&session, format!("systemctl is-active {unit}")).await?;
+    if !matches!(state.trim(), "active" | "activating" | "reloading") {
+        return Ok(None);
+    }
+
+    attach_unit(host, session, id, unit.to_owned(), None)
+        .await
+        .map(Some)
+}
+
+/// Follows the output of the unit `unit` and forwards input to it, after launching it with
+/// `systemd_run` if it is not already running.
+async fn attach_unit<T: LaunchedSshHost>(
+    host: &T,
+    session: AsyncSession<NoCheckHandler>,
+    id: String,
+    unit: String,
+    systemd_run: Option<String>,
+) -> Result<Box<dyn LaunchedBinary>> {
+    let user = host.ssh_user();
+    let stderr_path = unit_file(&unit, "stderr");
+
+    let (journal, stdout, stderr_tail, stderr) =
+        ProgressTracker::leaf(format!("attaching to unit {unit}"), async {
+            // Follow the journal and the stderr file before launching the unit, so that no
+            // output is missed. A unit which is already running is only followed from now on.
+            let journal = create_channel(&session).await?;
+            let stdout = journal.stdout();
+            journal
+                .exec(
+                    false,
+                    format!(
+                        "sudo journalctl --unit={unit} --follow --output=cat --no-pager{}",
+                        if systemd_run.is_none() {
+                            " --lines=0"
+                        } else {
+                            ""
+                        }
+                    ),
+                )
+                .await?;
+            let stderr_tail = create_channel(&session).await?;
+            let stderr = stderr_tail.stdout();
+            stderr_tail
+                .exec(
+                    false,
+                    format!(
+                        "sudo tail --lines={} --follow=name --retry {stderr_path} 2>/dev/null",
+                        if systemd_run.is_none() { "0" } else { "+1" }
+                    ),
+                )
+                .await?;
+
+            if let Some(systemd_run) = systemd_run {
+                let (status, output) = run_command(&session, systemd_run).await?;
+                if status != 0 {
+                    bail!("Failed to launch unit {unit}: {output}");
+                }
+            }
+            anyhow::Ok((journal, stdout, stderr_tail, stderr))
+        })
+        .await?;
+
+    let (stdin_sender, mut stdin_receiver) = mpsc::unbounded_channel::<String>();
+    let stdin_channel = create_channel(&session).await?;
+    let mut stdin = stdin_channel.stdin();
+    stdin_channel
+        .exec(
+            false,
+            format!("bash -c {}", escape(stdin_script(&unit).into())),
+        )
+        .await?;
+    tokio::spawn(async move {
+        // Keep the channel open as long as input is being sent.
+        let _stdin_channel = stdin_channel;
+        while let Some(line) = stdin_receiver.recv().await {
+            if stdin.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+            stdin.flush().await.unwrap();
+        }
+    });
+
+    // Waits for the unit to stop without being restarted, then prints its exit code.
+    let exit_channel = create_channel(&session).await?;
+    let exit_stdout = exit_channel.stdout();
+    exit_channel
+        .exec(
+            false,
+            format!(
+                "while case \"$(systemctl is-active {unit})\" in active|activating|deactivating|reloading) true ;; *) false ;; esac; do sleep 1; done; systemctl show --property=ExecMainStatus --value {unit}"
+            ),
+        )
+        .await?;
+    let (exit_sender, exit_code) = watch::channel(None);
+    tokio::spawn(async move {
+        let _exit_channel = exit_channel;
+        let mut lines = exit_stdout.lines();
+        if let Ok(Some(line)) = lines.next_line().await
+            && let Ok(code) = line.trim().parse()
+        {
+            let _ = exit_sender.send(Some(code));
+        }
+    });
+
+    let id_clone = id.clone();
+    let stdout_broadcast = prioritized_broadcast(LinesStream::new(stdout.lines()), move |s| {
+        ProgressTracker::println(format!("[{id_clone}] {s}"));
+    });
+    let stderr_broadcast = prioritized_broadcast(LinesStream::new(stderr.lines()), move |s| {
+        ProgressTracker::println(format!("[{id} stderr] {s}"));
+    });
+
+    // Hosts without a public IP are only reachable from within their network.
+    let ssh = format!(
+        "ssh -i {} {user}@{}",
+        host.ssh_key_path().display(),
+        host.get_external_ip()
+            .unwrap_or_else(|| host.get_internal_ip())
+    );
+    let reattach = reattach_instructions(&ssh, &unit);
+
+    Ok(Box::new(LaunchedSystemdBinary {
+        _resource_result: host.resource_result().clone(),
+        session: Some(session),
+        unit,
+        reattach,
+        _journal: journal,
+        _stderr_tail: stderr_tail,
+        stdin_sender,
+        stdout_broadcast,
+        stderr_broadcast,
+        exit_code,
+    }))
+}
+
+/// Instructions for reattaching to the unit `unit` with the SSH command `ssh`.
+fn reattach_instructions(ssh: &str, unit: &str) -> String {
+    format!(
+        "running as systemd unit `{unit}`\n  logs: {ssh} sudo journalctl --unit={unit} --follow\n  stderr: {ssh} sudo tail --follow {stderr_path}\n  input: {ssh} 'cat >> {stdin_path}'\n  stop: {ssh} sudo systemctl stop {unit}",
+        stderr_path = unit_file(unit, "stderr"),
+        stdin_path = unit_file(unit, "stdin"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    use super::*;
+
+    /// A unit name whose files in `/tmp` are removed when it is dropped.
+    struct TestUnit(String);
+
+    impl TestUnit {
+        fn new() -> Self {
+            TestUnit(format!("hydro-test-{}", nanoid!(8, &TERRAFORM_ALPHABET)))
+        }
+
+        fn path(&self, extension: &str) -> String {
+            format!("/tmp/{}.{extension}", self.0)
+        }
+    }
+
+    impl Drop for TestUnit {
+        fn drop(&mut self) {
+            for extension in ["stdin", "init", "stderr"] {
+                let _ = std::fs::remove_file(self.path(extension));
+            }
+        }
+    }
+
+    #[test]
+    fn systemd_run_command_applies_options() {
+        let options = SystemdOptions {
+            restart: SystemdRestart::Always,
+            restart_delay: Some(Duration::from_secs(2)),
+        };
+        let resources = ResourceLimits {
+            memory_limit: Some(1024),
+            ..Default::default()
+        };
+        let env = HashMap::from([("KEY".to_owned(), "a b".to_owned())]);
+        let command = systemd_run_command("hydro", "unit", &options, "./bin", &env, &resources);
+        for expected in [
+            "touch /tmp/unit.stdin && sudo systemd-run --quiet --unit=unit --uid=hydro ",
+            " --property=Restart=always ",
+            " --property=StandardOutput=journal --property=StandardError=append:/tmp/unit.stderr ",
+            " --property=RestartSec=2000ms ",
+            " --property=MemoryMax=1024 ",
+            " --setenv='KEY=a b' ",
+            " -- bash -c ",
+        ] {
+            assert!(
+                command.contains(expected),
+                "{expected:?} not in {command:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn reattach_instructions_include_stderr() {
+        let instructions = reattach_instructions("ssh host", "unit");
+        assert!(instructions.contains("ssh host sudo tail --follow /tmp/unit.stderr"));
+        assert!(instructions.contains("ssh host 'cat >> /tmp/unit.stdin'"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn stdin_script_records_port_configuration() {
+        let unit = TestUnit::new();
+        let mut child = Command::new("bash")
+            .args(["-c", &stdin_script(&unit.0)])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"unbound\nbound\nstart: {}\ndrain\n")
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+
+        assert_eq!(
+            "unbound\nbound\nstart: {}\ndrain\n",
+            std::fs::read_to_string(unit.path("stdin")).unwrap()
+        );
+        assert_eq!(
+            "bound\nstart: {}\n",
+            std::fs::read_to_string(unit.path("init")).unwrap()
+        );
+    }
+
+    /// Runs the script of `unit` with a command which prints the first 3 lines of its input,
+    /// appending `appended` to the input file until it exits.
+    #[cfg(target_os = "linux")]
+    fn run_unit_script(unit: &TestUnit, appended: &str) -> String {
+        let mut child = Command::new("bash")
+            .args(["-c", &unit_script("head -n 3", &unit.0)])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let append = || {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(unit.path("stdin"))
+                .unwrap();
+            writeln!(file, "{appended}").unwrap();
+        };
+        while child.try_wait().unwrap().is_none() {
+            append();
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        // Stops `tail`, which exits once it fails to write to the closed pipe.
+        append();
+        let output = child.wait_with_output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn first_instance_follows_input_from_start() {
+        let unit = TestUnit::new();
+        std::fs::write(unit.path("stdin"), "bound\nstart: {}\n").unwrap();
+        assert_eq!("bound\nstart: {}\nnew\n", run_unit_script(&unit, "new"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn restarted_instance_replays_only_port_configuration() {
+        let unit = TestUnit::new();
+        std::fs::write(unit.path("stdin"), "unbound\nbound\nstart: {}\ndrain\n").unwrap();
+        std::fs::write(unit.path("init"), "bound\nstart: {}\n").unwrap();
+        assert_eq!("bound\nstart: {}\nnew\n", run_unit_script(&unit, "new"));
+    }
+}
@@ -3,8 +3,9 @@ use std::ffi::OsStr;
 use std::io::{BufRead, BufReader};
 #[cfg(unix)]
 use std::os::unix::process::CommandExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStdout, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, OnceLock, RwLock};
 
 use anyhow::{Context, Result, bail};
@@ -43,14 +44,125 @@ pub fn terraform_name() -> &'static str {
         .unwrap_or("terraform")
 }
 
+/// A remote backend which stores the terraform state of a deployment, instead of the default
+/// local state file.
+///
+/// Storing the state remotely lets several machines manage the same long-lived deployment. Each
+/// backend locks the state while it is being modified, so concurrent deployments with the same
+/// name wait for each other instead of provisioning conflicting resources.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TerraformBackend {
+    /// A Google Cloud Storage bucket, locked natively by GCS.
+    Gcs {
+        bucket: String,
+        /// A prefix for the state objects within the bucket.
+        prefix: Option<String>,
+    },
+    /// An Amazon S3 bucket, locked with a lock file in the bucket.
+    S3 {
+        bucket: String,
+        region: String,
+        /// A prefix for the state objects within the bucket.
+        prefix: Option<String>,
+        /// A DynamoDB table to lock the state with, for versions of terraform which do not
+        /// support lock files in S3.
+        dynamodb_table: Option<String>,
+    },
+    /// An Azure Blob Storage container, locked with blob leases.
+    AzureRm {
+        resource_group_name: String,
+        storage_account_name: String,
+        container_name: String,
+    },
+}
+
+impl TerraformBackend {
+    /// The name of the backend and its configuration, storing the state under `key`.
+    fn config(&self, key: &str) -> (&'static str, serde_json::Value) {
+        let prefixed = |prefix: &Option<String>| match prefix {
+            Some(prefix) => format!("{}/{}", prefix.trim_end_matches('/'), key),
+            None => key.to_owned(),
+        };
+
+        match self {
+            TerraformBackend::Gcs { bucket, prefix } => (
+                "gcs",
+                serde_json::json!({
+                    "bucket": bucket,
+                    "prefix": prefixed(prefix),
+                }),
+            ),
+            TerraformBackend::S3 {
+                bucket,
+                region,
+                prefix,
+                dynamodb_table,
+            } => {
+                let mut config = serde_json::json!({
+                    "bucket": bucket,
+                    "region": region,
+                    "key": format!("{}/terraform.tfstate", prefixed(prefix)),
+                });
+                if let Some(table) = dynamodb_table {
+                    config["dynamodb_table"] = table.clone().into();
+                } else {
+                    config["use_lockfile"] = true.into();
+                }
+                ("s3", config)
+            }
+            TerraformBackend::AzureRm {
+                resource_group_name,
+                storage_account_name,
+                container_name,
+            } => (
+                "azurerm",
+                serde_json::json!({
+                    "resource_group_name": resource_group_name,
+                    "storage_account_name": storage_account_name,
+                    "container_name": container_name,
+                    "key": format!("{}.terraform.tfstate", key),
+                }),
+            ),
+        }
+    }
+}
+
+/// A short ID for the resource with the `kind/ordinal` key `key` in the deployment named
+/// `deployment_name`, which is the same on every machine managing the deployment.
+pub(crate) fn deterministic_resource_id(deployment_name: &str, key: &str) -> String {
+    let hash = blake3::hash(format!("{}/{}", deployment_name, key).as_bytes());
+    hash.to_hex()[..8].to_owned()
+}
+
+/// Whether `folder` holds the terraform state of a deployment, either directly or as the
+/// configuration of a remote backend.
+pub(crate) fn has_terraform_state(folder: &Path) -> bool {
+    folder.join("terraform.tfstate").exists()
+        || folder.join(".terraform").join("terraform.tfstate").exists()
+}
+
 /// Keeps track of resources which may need to be cleaned up.
 #[derive(Default)]
 pub struct TerraformPool {
     counter: u32,
     active_applies: HashMap<u32, Arc<tokio::sync::RwLock<TerraformApply>>>,
+    /// The name of the deployment and the backend its state is stored in, if it is remote.
+    backend: Option<(String, TerraformBackend)>,
 }
 
 impl TerraformPool {
+    /// Stores the state of the deployment in `backend`, under `deployment_name`. Each
+    /// provisioning step of the deployment is stored separately, numbered in the order they are
+    /// provisioned, so deployments must provision resources in the same order on every machine.
+    pub fn set_backend(&mut self, deployment_name: impl Into<String>, backend: TerraformBackend) {
+        self.backend = Some((deployment_name.into(), backend));
+    }
+
+    /// The name of the deployment, if its state is stored in a backend, see [`Self::set_backend`].
+    pub fn deployment_name(&self) -> Option<&str> {
+        self.backend.as_ref().map(|(name, _)| name.as_str())
+    }
+
     fn create_apply(
         &mut self,
         deployment_folder: TempDir,
@@ -83,6 +195,7 @@ impl TerraformPool {
         let deployment = Arc::new(tokio::sync::RwLock::new(TerraformApply {
             child: Some((spawned_id, Arc::new(RwLock::new(spawned_child)))),
             deployment_folder: Some(deployment_folder),
+            shared: self.backend.is_some(),
         }));
 
         self.active_applies.insert(next_counter, deployment.clone());
@@ -123,6 +236,7 @@ impl Default for TerraformBatch {
         TerraformBatch {
             terraform: TerraformConfig {
                 required_providers: HashMap::new(),
+                backend: HashMap::new(),
             },
             provider: HashMap::new(),
             data: HashMap::new(),
@@ -134,6 +248,17 @@ impl Default for TerraformBatch {
 
 impl TerraformBatch {
     pub async fn provision(self, pool: &mut TerraformPool) -> Result<TerraformResult> {
+        self.provision_in(pool, None).await
+    }
+
+    /// Provisions the resources of this batch. If `existing` is the folder of a previous
+    /// deployment (which was not destroyed), it is taken over so that the resources it provisioned
+    /// are reused instead of being recreated.
+    pub async fn provision_in(
+        mut self,
+        pool: &mut TerraformPool,
+        existing: Option<PathBuf>,
+    ) -> Result<TerraformResult> {
         // Hack to quiet false-positive `clippy::needless_pass_by_ref_mut` on latest nightlies.
         // TODO(mingwei): Remove this when it is no longer needed (current date 2023-08-30).
         // https://github.com/rust-lang/rust-clippy/issues/11380
@@ -147,13 +272,26 @@ impl TerraformBatch {
             return Ok(TerraformResult {
                 outputs: HashMap::new(),
                 deployment_folder: None,
+                detached: AtomicBool::new(false),
             });
         }
 
+        if let Some(existing) = &existing {
+            self.pin_previous_resources(existing)?;
+        }
+
+        if let Some((deployment_name, backend)) = &pool.backend {
+            let (name, config) = backend.config(&format!("{}/{}", deployment_name, pool.counter));
+            self.terraform.backend = HashMap::from([(name.to_owned(), config)]);
+        }
+
         ProgressTracker::with_group(terraform_name(), Some(1), || async {
             let dothydro_folder = std::env::current_dir().unwrap().join(".hydro");
             std::fs::create_dir_all(&dothydro_folder).unwrap();
             let deployment_folder = tempfile::tempdir_in(dothydro_folder).unwrap();
+            if let Some(existing) = &existing {
+                take_over_deployment(existing, &deployment_folder)?;
+            }
 
             std::fs::write(
                 deployment_folder.path().join("main.tf.json"),
@@ -193,11 +331,48 @@ impl TerraformBatch {
         })
         .await
     }
+
+    /// Replaces the definitions of the resources already provisioned by the previous deployment in
+    /// `existing` with their previous definitions. Resource names are randomized, so the new
+    /// definitions would otherwise cause every resource to be replaced.
+    fn pin_previous_resources(&mut self, existing: &Path) -> Result<()> {
+        let previous: TerraformBatch = serde_json::from_slice(
+            &std::fs::read(existing.join("main.tf.json"))
+                .with_context(|| format!("Failed to read {} deployment", terraform_name()))?,
+        )?;
+
+        for (resource_type, resources) in previous.resource {
+            if let Some(new_resources) = self.resource.get_mut(&resource_type) {
+                for (name, definition) in resources {
+                    if let Some(new_definition) = new_resources.get_mut(&name) {
+                        *new_definition = definition;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Moves the contents of the folder of a previous deployment (including its terraform state) into
+/// `deployment_folder`, which then owns the previously provisioned resources.
+fn take_over_deployment(existing: &Path, deployment_folder: &TempDir) -> Result<()> {
+    std::fs::remove_dir(deployment_folder.path())?;
+    std::fs::rename(existing, deployment_folder.path()).with_context(|| {
+        format!(
+            "Failed to take over {} deployment at {}",
+            terraform_name(),
+            existing.display()
+        )
+    })
 }
 
 struct TerraformApply {
     child: Option<(u32, Arc<RwLock<Child>>)>,
     deployment_folder: Option<TempDir>,
+    /// Whether the state is stored in a [`TerraformBackend`], where it may be shared with other
+    /// machines, in which case the resources are never destroyed automatically.
+    shared: bool,
 }
 
 async fn display_apply_outputs(stdout: &mut ChildStdout) {
@@ -218,6 +393,14 @@ async fn display_apply_outputs(stdout: &mut ChildStdout) {
                     || line.contains(": Reading...")
                     || line.contains(": Still reading...")
                     || line.contains(": Read complete after")
+                    // when resuming a previous deployment
+                    || line.contains(": Refreshing state...")
+                    || line.contains(": Modifying...")
+                    || line.contains(": Still modifying...")
+                    || line.contains(": Modifications complete after")
+                    || line.contains(": Destroying...")
+                    || line.contains(": Still destroying...")
+                    || line.contains(": Destruction complete after")
                 {
                 } else if line.ends_with(": Creating...") {
                     let id = line.split(':').next().unwrap().trim();
@@ -318,6 +501,7 @@ impl TerraformApply {
         Ok(TerraformResult {
             outputs: serde_json::from_slice(&output.stdout).unwrap(),
             deployment_folder: self.deployment_folder.take(),
+            detached: AtomicBool::new(self.shared),
         })
     }
 }
@@ -380,7 +564,11 @@ impl Drop for TerraformApply {
         }
 
         if let Some(deployment_folder) = self.deployment_folder.take() {
-            destroy_deployment(deployment_folder);
+            if self.shared {
+                let _ = deployment_folder.keep();
+            } else {
+                destroy_deployment(deployment_folder);
+            }
         }
     }
 }
@@ -388,6 +576,9 @@ impl Drop for TerraformApply {
 #[derive(Serialize, Deserialize)]
 pub struct TerraformConfig {
     pub required_providers: HashMap<String, TerraformProvider>,
+    /// The remote backend storing the state, if any, see [`TerraformBackend`].
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub backend: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -406,12 +597,28 @@ pub struct TerraformResult {
     pub outputs: HashMap<String, TerraformOutput>,
     /// `None` if no deployment was performed
     pub deployment_folder: Option<TempDir>,
+    /// Whether the resources are left running when this is dropped, see [`Self::detach`]. Always
+    /// set for state stored in a [`TerraformBackend`].
+    detached: AtomicBool,
+}
+
+impl TerraformResult {
+    /// Leaves the provisioned resources running when this is dropped, instead of destroying them.
+    /// Returns the deployment folder, from which they can be destroyed later.
+    pub fn detach(&self) -> Option<&Path> {
+        self.detached.store(true, Ordering::Relaxed);
+        self.deployment_folder.as_ref().map(TempDir::path)
+    }
 }
 
 impl Drop for TerraformResult {
     fn drop(&mut self) {
         if let Some(deployment_folder) = self.deployment_folder.take() {
-            destroy_deployment(deployment_folder);
+            if self.detached.load(Ordering::Relaxed) {
+                let _ = deployment_folder.keep();
+            } else {
+                destroy_deployment(deployment_folder);
+            }
         }
     }
 }
@@ -420,3 +627,82 @@ impl Drop for TerraformResult {
 pub struct TerraformResultOutput {
     value: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_state_is_keyed_by_deployment() {
+        let (name, config) = TerraformBackend::S3 {
+            bucket: "hydro-state".to_owned(),
+            region: "us-east-1".to_owned(),
+            prefix: Some("team/".to_owned()),
+            dynamodb_table: None,
+        }
+        .config("my-deployment/0");
+        assert_eq!(name, "s3");
+        assert_eq!(config["key"], "team/my-deployment/0/terraform.tfstate");
+        assert_eq!(config["use_lockfile"], true);
+
+        let (name, config) = TerraformBackend::Gcs {
+            bucket: "hydro-state".to_owned(),
+            prefix: None,
+        }
+        .config("my-deployment/1");
+        assert_eq!(name, "gcs");
+        assert_eq!(config["prefix"], "my-deployment/1");
+    }
+
+    #[test]
+    fn deterministic_resource_ids_depend_on_deployment() {
+        let id = deterministic_resource_id("my-deployment", "network/0");
+        assert_eq!(id.len(), 8);
+        assert!(id.chars().all(|c| TERRAFORM_ALPHABET.contains(&c)));
+        assert_eq!(id, deterministic_resource_id("my-deployment", "network/0"));
+        assert_ne!(id, deterministic_resource_id("my-deployment", "network/1"));
+        assert_ne!(
+            id,
+            deterministic_resource_id("other-deployment", "network/0")
+        );
+    }
+
+    #[test]
+    fn shared_state_is_not_destroyed() {
+        let dothydro_folder = tempfile::tempdir().unwrap();
+        let deployment_folder = tempfile::tempdir_in(dothydro_folder.path()).unwrap();
+        let path = deployment_folder.path().to_owned();
+
+        // Dropping an apply of shared state keeps its folder, instead of running `destroy`.
+        drop(TerraformApply {
+            child: None,
+            deployment_folder: Some(deployment_folder),
+            shared: true,
+        });
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn backend_names_resources_after_deployment() {
+        let mut pool = TerraformPool::default();
+        assert_eq!(pool.deployment_name(), None);
+        pool.set_backend(
+            "my-deployment",
+            TerraformBackend::Gcs {
+                bucket: "hydro-state".to_owned(),
+                prefix: None,
+            },
+        );
+        assert_eq!(pool.deployment_name(), Some("my-deployment"));
+
+        let mut batch =
+            crate::ResourceBatch::resuming(Default::default(), Some("my-deployment".to_owned()));
+        let id = batch.resource_id("network", "host-1");
+        assert_eq!(id, deterministic_resource_id("my-deployment", "network/0"));
+        assert_eq!(id, batch.resource_id("network", "host-1"));
+        assert_eq!(
+            batch.resource_id("network", "host-2"),
+            deterministic_resource_id("my-deployment", "network/1")
+        );
+    }
+}
@@ -0,0 +1,602 @@
+//! A full-screen terminal UI for monitoring and controlling a running deployment, see
+//! [`Deployment::run_tui`](crate::Deployment::run_tui).
+//!
+//! The TUI shows the [`ServicePhase`] of each service, tails the logs of all services (with
+//! filtering), and shows the resolved [`ServerPort`] wiring between services. Services can be
+//! restarted, and the deployment stopped, interactively.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use hydro_deploy_integration::ServerPort;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::sync::mpsc;
+
+use crate::progress::{PortDirection, ProgressTracker, ServicePhase};
+
+/// The maximum number of log lines kept for display, older lines are discarded.
+const MAX_LOG_LINES: usize = 10_000;
+
+/// How often the TUI is redrawn, if there is no input.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// State recorded from the deployment, `None` if the TUI is not running.
+static TUI_STATE: Mutex<Option<TuiState>> = Mutex::new(None);
+
+/// The state recorded from the deployment. A panic while the lock was held (such as in the TUI
+/// thread) does not stop the deployment from recording its progress.
+fn tui_state() -> MutexGuard<'static, Option<TuiState>> {
+    TUI_STATE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A command issued interactively from the TUI, received with [`DeploymentTui::next_command`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TuiCommand {
+    /// Restart the service with the given [`crate::Service::display_id`].
+    RestartService(String),
+    /// Stop the deployment.
+    StopDeployment,
+}
+
+struct ServiceEntry {
+    name: String,
+    phase: ServicePhase,
+    since: Instant,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct LogLine {
+    source: Option<String>,
+    stderr: bool,
+    text: String,
+}
+
+struct PortEntry {
+    service: String,
+    port: String,
+    direction: PortDirection,
+    server_port: ServerPort,
+}
+
+#[derive(Default)]
+struct TuiState {
+    services: Vec<ServiceEntry>,
+    logs: VecDeque<LogLine>,
+    ports: Vec<PortEntry>,
+}
+
+pub(crate) fn record_phase(service: &str, phase: ServicePhase) {
+    let mut state = tui_state();
+    let Some(state) = state.as_mut() else {
+        return;
+    };
+    if let Some(entry) = state
+        .services
+        .iter_mut()
+        .find(|entry| entry.name == service)
+    {
+        entry.phase = phase;
+        entry.since = Instant::now();
+    } else {
+        state.services.push(ServiceEntry {
+            name: service.to_owned(),
+            phase,
+            since: Instant::now(),
+        });
+    }
+}
+
+pub(crate) fn record_port(
+    service: &str,
+    port: &str,
+    direction: PortDirection,
+    server_port: &ServerPort,
+) {
+    let mut state = tui_state();
+    let Some(state) = state.as_mut() else {
+        return;
+    };
+    state.ports.retain(|entry| {
+        entry.service != service || entry.port != port || entry.direction != direction
+    });
+    state.ports.push(PortEntry {
+        service: service.to_owned(),
+        port: port.to_owned(),
+        direction,
+        server_port: server_port.clone(),
+    });
+}
+
+/// Records a log line. Returns `false` if the TUI is not running, in which case the line should
+/// be printed as usual.
+pub(crate) fn record_log(line: &str, stderr: bool) -> bool {
+    let mut state = tui_state();
+    let Some(state) = state.as_mut() else {
+        return false;
+    };
+    for line in line.lines() {
+        if state.logs.len() >= MAX_LOG_LINES {
+            state.logs.pop_front();
+        }
+        state.logs.push_back(parse_log_line(line, stderr));
+    }
+    true
+}
+
+/// Splits the service a line of output is from off the line. Lines from services are formatted
+/// as `[service] text` or `[service stderr] text`.
+fn parse_log_line(line: &str, stderr: bool) -> LogLine {
+    match line
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("] "))
+    {
+        Some((source, text)) => {
+            let (source, stderr) = match source.strip_suffix(" stderr") {
+                Some(source) => (source, true),
+                None => (source, stderr),
+            };
+            LogLine {
+                source: Some(source.to_owned()),
+                stderr,
+                text: text.to_owned(),
+            }
+        }
+        None => LogLine {
+            source: None,
+            stderr,
+            text: line.to_owned(),
+        },
+    }
+}
+
+/// A running deployment TUI, which takes over the terminal until dropped.
+///
+/// While running, the output of [`ProgressTracker`] (including service logs) is shown in the TUI
+/// rather than printed.
+pub struct DeploymentTui {
+    commands: mpsc::UnboundedReceiver<TuiCommand>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DeploymentTui {
+    /// Starts the TUI, switching the terminal to the alternate screen.
+    ///
+    /// Fails if a TUI is already running, or if the terminal cannot be set up.
+    pub fn start() -> Result<Self> {
+        {
+            let mut state = tui_state();
+            if state.is_some() {
+                bail!("A deployment TUI is already running.");
+            }
+            *state = Some(TuiState::default());
+        }
+        let terminal = match ratatui::try_init() {
+            Ok(terminal) => terminal,
+            Err(err) => {
+                *tui_state() = None;
+                ratatui::restore();
+                return Err(err.into());
+            }
+        };
+        ProgressTracker::set_hidden(true);
+
+        let (command_send, command_recv) = mpsc::unbounded_channel();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::spawn({
+            let shutdown = shutdown.clone();
+            move || run(terminal, command_send, &shutdown)
+        });
+
+        Ok(Self {
+            commands: command_recv,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    /// Waits for the next command issued from the TUI.
+    pub async fn next_command(&mut self) -> Option<TuiCommand> {
+        self.commands.recv().await
+    }
+}
+
+impl Drop for DeploymentTui {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        *tui_state() = None;
+        ProgressTracker::set_hidden(false);
+    }
+}
+
+/// The TUI thread, draws the TUI and handles input until `shutdown` is set.
+fn run(
+    mut terminal: DefaultTerminal,
+    commands: mpsc::UnboundedSender<TuiCommand>,
+    shutdown: &AtomicBool,
+) {
+    let mut view = View::default();
+    while !shutdown.load(Ordering::Relaxed) {
+        let tasks = ProgressTracker::active_tasks();
+        if let Some(state) = tui_state().as_ref() {
+            let _ = terminal.draw(|frame| view.render(frame, state, &tasks));
+        }
+
+        if event::poll(REDRAW_INTERVAL).unwrap_or(false)
+            && let Ok(Event::Key(key)) = event::read()
+            && KeyEventKind::Press == key.kind
+        {
+            let selected = tui_state().as_ref().and_then(|state| {
+                view.services
+                    .selected()
+                    .and_then(|idx| state.services.get(idx))
+                    .map(|entry| entry.name.clone())
+            });
+            if let Some(command) = view.handle_key(key, selected) {
+                let _ = commands.send(command);
+            }
+        }
+    }
+    ratatui::restore();
+}
+
+/// UI state which is local to the TUI thread.
+#[derive(Default)]
+struct View {
+    /// The selected service.
+    services: TableState,
+    /// Only show logs from the selected service.
+    filter_selected: bool,
+    /// Only show logs containing this text.
+    filter_text: String,
+    /// Whether keystrokes are currently being typed into `filter_text`.
+    editing_filter: bool,
+    /// Number of lines scrolled up from the end of the logs, zero to follow new lines.
+    scroll: usize,
+    /// The name of the selected service, if `filter_selected` is set.
+    filter_service: Option<String>,
+}
+
+impl View {
+    fn handle_key(&mut self, key: KeyEvent, selected: Option<String>) -> Option<TuiCommand> {
+        if self.editing_filter {
+            match key.code {
+                KeyCode::Enter => self.editing_filter = false,
+                KeyCode::Esc => {
+                    self.editing_filter = false;
+                    self.filter_text.clear();
+                }
+                KeyCode::Backspace => {
+                    self.filter_text.pop();
+                }
+                KeyCode::Char(c) => self.filter_text.push(c),
+                _ => {}
+            }
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Some(TuiCommand::StopDeployment);
+            }
+            KeyCode::Char('q') => return Some(TuiCommand::StopDeployment),
+            KeyCode::Char('r') => return selected.map(TuiCommand::RestartService),
+            KeyCode::Up | KeyCode::Char('k') => self.services.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.services.select_next(),
+            KeyCode::Char('f') => {
+                self.filter_selected = !self.filter_selected;
+                self.filter_service = selected;
+            }
+            KeyCode::Char('/') => self.editing_filter = true,
+            KeyCode::PageUp => self.scroll += 10,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::End => self.scroll = 0,
+            _ => {}
+        }
+        None
+    }
+
+    fn render(&mut self, frame: &mut Frame, state: &TuiState, tasks: &[String]) {
+        let [top, tasks_area, logs_area, help_area] = Layout::vertical([
+            Constraint::Length(state.services.len().max(state.ports.len()).clamp(1, 12) as u16 + 3),
+            Constraint::Length(tasks.len().min(5) as u16 + 2),
+            Constraint::Min(5),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [services_area, ports_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(top);
+
+        // Services and their phases.
+        let services = Table::new(
+            state.services.iter().map(|entry| {
+                let color = match entry.phase {
+                    ServicePhase::Running => Color::Green,
+                    ServicePhase::Failed(_) => Color::Red,
+                    ServicePhase::Stopped => Color::DarkGray,
+                    _ => Color::Yellow,
+                };
+                Row::new(vec![
+                    Span::raw(entry.name.clone()),
+                    Span::styled(entry.phase.to_string(), Style::new().fg(color)),
+                    Span::raw(format!("{:.0?}", entry.since.elapsed())),
+                ])
+            }),
+            [
+                Constraint::Fill(2),
+                Constraint::Fill(2),
+                Constraint::Length(8),
+            ],
+        )
+        .header(
+            Row::new(["Service", "Phase", "Since"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title(" Services "));
+        if self.services.selected().is_none() && !state.services.is_empty() {
+            self.services.select_first();
+        }
+        frame.render_stateful_widget(services, services_area, &mut self.services);
+
+        // Port wiring, matching each client port to the server port it connects to.
+        let ports = Table::new(
+            state.ports.iter().map(|entry| {
+                let (arrow, peer) = match entry.direction {
+                    PortDirection::Bind => ("<-", String::new()),
+                    PortDirection::Connect => {
+                        let endpoints = endpoints(&entry.server_port);
+                        let peer = state
+                            .ports
+                            .iter()
+                            .filter(|other| PortDirection::Bind == other.direction)
+                            .filter(|other| {
+                                endpoints(&other.server_port)
+                                    .iter()
+                                    .any(|endpoint| endpoints.contains(endpoint))
+                            })
+                            .map(|other| format!("{}.{}", other.service, other.port))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        ("->", peer)
+                    }
+                };
+                Row::new(vec![
+                    format!("{}.{}", entry.service, entry.port),
+                    arrow.to_owned(),
+                    endpoints(&entry.server_port).join(", "),
+                    peer,
+                ])
+            }),
+            [
+                Constraint::Fill(2),
+                Constraint::Length(2),
+                Constraint::Fill(3),
+                Constraint::Fill(2),
+            ],
+        )
+        .header(
+            Row::new(["Port", "", "Address", "Peer"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::bordered().title(" Ports "));
+        frame.render_widget(ports, ports_area);
+
+        // Tasks in progress.
+        let tasks = Paragraph::new(
+            tasks
+                .iter()
+                .take(5)
+                .map(|task| Line::raw(task.as_str()))
+                .collect::<Vec<_>>(),
+        )
+        .block(Block::bordered().title(" In progress "));
+        frame.render_widget(tasks, tasks_area);
+
+        // Logs, filtered and scrolled.
+        let filter_service = self
+            .filter_selected
+            .then_some(self.filter_service.as_deref())
+            .flatten();
+        let matching = state
+            .logs
+            .iter()
+            .filter(|line| {
+                filter_service.is_none_or(|service| line.source.as_deref() == Some(service))
+            })
+            .filter(|line| {
+                self.filter_text.is_empty()
+                    || line.text.contains(&self.filter_text)
+                    || line
+                        .source
+                        .as_ref()
+                        .is_some_and(|source| source.contains(&self.filter_text))
+            })
+            .collect::<Vec<_>>();
+        let height = logs_area.height.saturating_sub(2) as usize;
+        self.scroll = self.scroll.min(matching.len().saturating_sub(height));
+        let end = matching.len() - self.scroll;
+        let lines = matching[end.saturating_sub(height)..end]
+            .iter()
+            .map(|line| {
+                let mut spans = Vec::new();
+                if let Some(source) = &line.source {
+                    spans.push(Span::styled(
+                        format!("[{}] ", source),
+                        Style::new().fg(Color::Cyan),
+                    ));
+                }
+                let style = if line.stderr {
+                    Style::new().fg(Color::LightRed)
+                } else {
+                    Style::new()
+                };
+                spans.push(Span::styled(line.text.as_str(), style));
+                Line::from(spans)
+            })
+            .collect::<Vec<_>>();
+        let mut title = String::from(" Logs");
+        if let Some(service) = filter_service {
+            title.push_str(&format!(" from {}", service));
+        }
+        if !self.filter_text.is_empty() || self.editing_filter {
+            title.push_str(&format!(" matching \"{}\"", self.filter_text));
+        }
+        if self.scroll > 0 {
+            title.push_str(&format!(" (scrolled up {} lines)", self.scroll));
+        }
+        title.push(' ');
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            logs_area,
+        );
+
+        let help = if self.editing_filter {
+            "type to filter logs, enter: done, esc: clear"
+        } else {
+            "q: stop deployment  r: restart service  up/down: select service  f: filter logs by service  /: search logs  pgup/pgdn/end: scroll"
+        };
+        frame.render_widget(
+            Paragraph::new(help).style(Style::new().fg(Color::DarkGray)),
+            help_area,
+        );
+    }
+}
+
+/// The addresses (or socket paths) that a [`ServerPort`] refers to.
+fn endpoints(server_port: &ServerPort) -> Vec<String> {
+    match server_port {
+        ServerPort::UnixSocket(path) => vec![path.display().to_string()],
+        ServerPort::TcpPort(addr) => vec![addr.to_string()],
+        ServerPort::Demux(demux) => demux.values().flat_map(endpoints).collect(),
+        ServerPort::Merge(merge) => merge.iter().flat_map(endpoints).collect(),
+        ServerPort::Tagged(underlying, _) => endpoints(underlying),
+        ServerPort::Null => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn parses_log_sources() {
+        assert_eq!(
+            parse_log_line("[service/1 stderr] oops", false),
+            LogLine {
+                source: Some("service/1".to_owned()),
+                stderr: true,
+                text: "oops".to_owned(),
+            }
+        );
+        assert_eq!(
+            parse_log_line("[service/1] hello", false),
+            LogLine {
+                source: Some("service/1".to_owned()),
+                stderr: false,
+                text: "hello".to_owned(),
+            }
+        );
+        assert_eq!(
+            parse_log_line("[not closed", true),
+            LogLine {
+                source: None,
+                stderr: true,
+                text: "[not closed".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn keys_issue_commands() {
+        let mut view = View::default();
+        assert_eq!(
+            view.handle_key(key(KeyCode::Char('r')), Some("a".to_owned())),
+            Some(TuiCommand::RestartService("a".to_owned()))
+        );
+        assert_eq!(view.handle_key(key(KeyCode::Char('r')), None), None);
+        assert_eq!(
+            view.handle_key(
+                KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+                None
+            ),
+            Some(TuiCommand::StopDeployment)
+        );
+
+        // While editing the filter, keys are typed into it instead.
+        view.handle_key(key(KeyCode::Char('/')), None);
+        for c in "qr".chars() {
+            assert_eq!(view.handle_key(key(KeyCode::Char(c)), None), None);
+        }
+        assert_eq!(view.filter_text, "qr");
+        view.handle_key(key(KeyCode::Esc), None);
+        assert!(view.filter_text.is_empty());
+        assert_eq!(
+            view.handle_key(key(KeyCode::Char('q')), None),
+            Some(TuiCommand::StopDeployment)
+        );
+    }
+
+    #[test]
+    fn endpoints_of_nested_ports() {
+        let addr = |port| ServerPort::TcpPort(SocketAddr::from(([127, 0, 0, 1], port)));
+        let port = ServerPort::Merge(vec![
+            ServerPort::Tagged(Box::new(addr(1)), 0),
+            ServerPort::Demux([(0, addr(2)), (1, ServerPort::Null)].into()),
+        ]);
+        assert_eq!(endpoints(&port), vec!["127.0.0.1:1", "127.0.0.1:2"]);
+    }
+
+    /// The global state is only touched by this test, since tests run concurrently.
+    #[test]
+    fn records_only_while_running() {
+        assert!(!record_log("[a] before", false));
+        record_phase("a", ServicePhase::Building);
+        assert!(tui_state().is_none());
+
+        *tui_state() = Some(TuiState::default());
+        // A second TUI cannot be started, without disturbing the running one.
+        assert!(DeploymentTui::start().is_err());
+        assert!(tui_state().is_some());
+
+        record_phase("a", ServicePhase::Building);
+        record_phase("a", ServicePhase::Running);
+        assert!(record_log("[a] one\n[b stderr] two", false));
+        {
+            let state = tui_state();
+            let state = state.as_ref().unwrap();
+            assert_eq!(state.services.len(), 1);
+            assert_eq!(state.services[0].phase, ServicePhase::Running);
+            assert_eq!(state.logs.len(), 2);
+            assert!(state.logs[1].stderr);
+        }
+
+        // A panic while the state is locked does not stop later recording.
+        let _ = std::thread::spawn(|| {
+            let _state = tui_state();
+            panic!("poison the state");
+        })
+        .join();
+        assert!(record_log("[a] after", false));
+
+        *tui_state() = None;
+    }
+}
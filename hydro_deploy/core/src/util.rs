@@ -4,6 +4,8 @@ use std::time::Duration;
 use futures::{Future, Stream, StreamExt};
 use tokio::sync::{mpsc, oneshot};
 
+use crate::logs::RetainedLines;
+
 pub async fn async_retry<T, E, F: Future<Output = Result<T, E>>>(
     mut thunk: impl FnMut() -> F,
     count: usize,
@@ -27,6 +29,7 @@ pub struct PriorityBroadcast(Weak<Mutex<PriorityBroadcastInternal>>);
 struct PriorityBroadcastInternal {
     priority_sender: Option<oneshot::Sender<String>>,
     senders: Vec<(Option<String>, mpsc::UnboundedSender<String>)>,
+    retained: Vec<RetainedLines>,
 }
 
 impl PriorityBroadcast {
@@ -54,6 +57,16 @@ impl PriorityBroadcast {
 
         receiver
     }
+
+    /// Appends every subsequent line (other than those taken by [`Self::receive_priority`]) to
+    /// `lines`. Unlike [`Self::receive`], this does not stop lines from going to the fallback
+    /// receiver.
+    pub fn retain(&self, lines: RetainedLines) {
+        if let Some(internal) = self.0.upgrade() {
+            let mut internal = internal.lock().unwrap();
+            internal.retained.push(lines);
+        }
+    }
 }
 
 pub fn prioritized_broadcast<T: Stream<Item = std::io::Result<String>> + Send + Unpin + 'static>(
@@ -63,6 +76,7 @@ pub fn prioritized_broadcast<T: Stream<Item = std::io::Result<String>> + Send +
     let internal = Arc::new(Mutex::new(PriorityBroadcastInternal {
         priority_sender: None,
         senders: Vec::new(),
+        retained: Vec::new(),
     }));
 
     let weak_internal = Arc::downgrade(&internal);
@@ -79,6 +93,10 @@ pub fn prioritized_broadcast<T: Stream<Item = std::io::Result<String>> + Send +
                 continue; // Skip regular receivers if successfully sent to the priority receiver.
             }
 
+            for lines in internal.retained.iter() {
+                lines.push(line.clone());
+            }
+
             // Regular receivers
             internal.senders.retain(|receiver| !receiver.1.is_closed());
 
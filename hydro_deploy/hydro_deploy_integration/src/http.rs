@@ -0,0 +1,165 @@
+//! Serving HTTP endpoints with [`axum`], on ports bound by Hydro Deploy.
+//!
+//! Each request to an endpoint is assigned a unique ID, and its body is emitted by the
+//! [`HttpRequestStream`] along with that ID. The request is answered once a response with the same
+//! ID is sent to the [`HttpResponseSink`].
+
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use bytes::Bytes;
+use futures::Sink;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::{AcceptedServer, BoundServer, Connected, Connection};
+
+/// A response to a request received by an HTTP endpoint, with a JSON body.
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Bytes,
+}
+
+impl HttpResponse {
+    /// A successful response with the given JSON `body`.
+    pub fn ok(body: impl Into<Bytes>) -> Self {
+        Self {
+            status: StatusCode::OK.as_u16(),
+            body: body.into(),
+        }
+    }
+
+    /// A response rejecting a request whose body could not be parsed, with the given JSON `body`
+    /// describing the error.
+    pub fn bad_request(body: impl Into<Bytes>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST.as_u16(),
+            body: body.into(),
+        }
+    }
+}
+
+/// The ID and body of each request received by an HTTP endpoint.
+pub type HttpRequestStream = UnboundedReceiverStream<(u64, Bytes)>;
+
+type PendingResponses = Arc<Mutex<HashMap<u64, oneshot::Sender<HttpResponse>>>>;
+
+/// Answers the requests received by an HTTP endpoint, by request ID. Responses to requests which
+/// were already answered, or whose client disconnected, are dropped.
+pub struct HttpResponseSink {
+    pending: PendingResponses,
+}
+
+impl Sink<(u64, HttpResponse)> for HttpResponseSink {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(
+        self: Pin<&mut Self>,
+        (request_id, response): (u64, HttpResponse),
+    ) -> Result<(), Self::Error> {
+        if let Some(respond) = self.pending.lock().unwrap().remove(&request_id) {
+            let _ = respond.send(response);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+struct EndpointState {
+    next_request_id: AtomicU64,
+    requests: mpsc::UnboundedSender<(u64, Bytes)>,
+    pending: PendingResponses,
+}
+
+async fn handle_request(State(state): State<Arc<EndpointState>>, body: Bytes) -> Response {
+    let request_id = state.next_request_id.fetch_add(1, Ordering::Relaxed);
+    let (respond, response) = oneshot::channel();
+    state.pending.lock().unwrap().insert(request_id, respond);
+
+    if state.requests.send((request_id, body)).is_err() {
+        state.pending.lock().unwrap().remove(&request_id);
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+
+    match response.await {
+        Ok(HttpResponse { status, body }) => (
+            StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            [(header::CONTENT_TYPE, "application/json")],
+            body,
+        )
+            .into_response(),
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+/// A TCP port bound by Hydro Deploy for an HTTP endpoint, which starts accepting requests once
+/// [`ConnectedHttp::serve`] is called.
+pub struct ConnectedHttp {
+    listener: TcpListener,
+}
+
+impl Connected for ConnectedHttp {
+    fn from_defn(pipe: Connection) -> Self {
+        match pipe {
+            Connection::AsServer(AcceptedServer::MultiConnection(bound_server)) => {
+                match *bound_server {
+                    BoundServer::TcpPort(listener, _) => ConnectedHttp {
+                        listener: listener.into_inner(),
+                    },
+                    _ => panic!("HTTP endpoints must be bound to a TCP port"),
+                }
+            }
+            _ => panic!("Cannot connect to a non-multi-connection pipe as an HTTP endpoint"),
+        }
+    }
+}
+
+impl ConnectedHttp {
+    /// Spawns an [`axum`] server which accepts `POST` requests to `route`, returning the stream
+    /// of received requests and the sink to answer them with. Must be called within a Tokio
+    /// runtime.
+    pub fn serve(self, route: &str) -> (HttpRequestStream, HttpResponseSink) {
+        let (requests_send, requests_recv) = mpsc::unbounded_channel();
+        let pending = PendingResponses::default();
+
+        let router = Router::new()
+            .route(route, post(handle_request))
+            .with_state(Arc::new(EndpointState {
+                next_request_id: AtomicU64::new(0),
+                requests: requests_send,
+                pending: pending.clone(),
+            }));
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(self.listener, router).await {
+                eprintln!("HTTP endpoint failed: {e}");
+            }
+        });
+
+        (
+            UnboundedReceiverStream::new(requests_recv),
+            HttpResponseSink { pending },
+        )
+    }
+}
@@ -0,0 +1,190 @@
+//! Optional integrity checking of the frames sent between services.
+//!
+//! By default, frames are only length-delimited, so a frame corrupted in transit (for example by
+//! a faulty NIC) is passed on as-is and typically surfaces later as a confusing deserialization
+//! failure, or worse, as a wrong value. When checksums are enabled by the [`FrameConfig`], each
+//! frame additionally carries a CRC32 checksum of its payload, which is verified on receipt. A
+//! frame whose checksum does not match is reported as an [`io::Error`] wrapping a
+//! [`CorruptFrame`].
+//!
+//! Both ends of a connection must agree on whether checksums are enabled, so the same
+//! [`FrameConfig`] is sent to every service of a deployment, as part of its
+//! [`InitConfig`](crate::InitConfig) (or [`InstanceConfig`](crate::InstanceConfig)).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use tokio::io;
+use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec};
+
+/// Length of the checksum appended to each frame.
+const CHECKSUM_LEN: usize = 4;
+
+/// How the frames sent between services are encoded, which must be the same for every service of
+/// a deployment.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameConfig {
+    /// Whether each frame carries a CRC32 checksum of its payload.
+    pub checksums: bool,
+}
+
+static CHECKSUMS: AtomicBool = AtomicBool::new(false);
+
+impl FrameConfig {
+    /// Applies this config to all connections made by this process from now on.
+    pub fn apply(self) {
+        CHECKSUMS.store(self.checksums, Ordering::Relaxed);
+    }
+
+    /// The config last [applied](Self::apply) in this process, or the default.
+    pub fn current() -> Self {
+        Self {
+            checksums: CHECKSUMS.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A frame whose payload does not match its checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptFrame {
+    /// The length of the received frame, including its checksum.
+    pub len: usize,
+    /// The checksum carried by the frame, or `None` if the frame is too short to hold one.
+    pub expected: Option<u32>,
+    /// The checksum of the received payload.
+    pub actual: u32,
+}
+
+impl CorruptFrame {
+    /// Returns the [`CorruptFrame`] wrapped by an error produced by [`FrameCodec`], if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&CorruptFrame> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl std::fmt::Display for CorruptFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.expected {
+            Some(expected) => write!(
+                f,
+                "corrupt frame of {} bytes: expected checksum {:08x}, got {:08x}",
+                self.len, expected, self.actual
+            ),
+            None => write!(
+                f,
+                "corrupt frame of {} bytes: too short to hold a checksum",
+                self.len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CorruptFrame {}
+
+/// Length-delimited framing, which also appends a CRC32 checksum to each frame if checksums are
+/// enabled.
+///
+/// The [`Default`] codec enables checksums according to [`FrameConfig::current`].
+#[derive(Debug)]
+pub struct FrameCodec {
+    inner: LengthDelimitedCodec,
+    checksums: bool,
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new(FrameConfig::current().checksums)
+    }
+}
+
+impl FrameCodec {
+    /// Creates a codec, with checksums enabled if `checksums` is `true`.
+    pub fn new(checksums: bool) -> Self {
+        Self {
+            inner: LengthDelimitedCodec::new(),
+            checksums,
+        }
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        let Some(mut frame) = self.inner.decode(src)? else {
+            return Ok(None);
+        };
+        if !self.checksums {
+            return Ok(Some(frame));
+        }
+
+        let len = frame.len();
+        if len < CHECKSUM_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                CorruptFrame {
+                    len,
+                    expected: None,
+                    actual: crc32fast::hash(&frame),
+                },
+            ));
+        }
+        let expected = frame.split_off(len - CHECKSUM_LEN).get_u32();
+        let actual = crc32fast::hash(&frame);
+        if expected != actual {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                CorruptFrame {
+                    len,
+                    expected: Some(expected),
+                    actual,
+                },
+            ));
+        }
+        Ok(Some(frame))
+    }
+}
+
+impl Encoder<Bytes> for FrameCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        if !self.checksums {
+            return self.inner.encode(item, dst);
+        }
+
+        let mut frame = BytesMut::with_capacity(item.len() + CHECKSUM_LEN);
+        frame.put_slice(&item);
+        frame.put_u32(crc32fast::hash(&item));
+        self.inner.encode(frame.freeze(), dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_corrupt_frame() {
+        let mut codec = FrameCodec::new(true);
+        let mut buf = BytesMut::new();
+        codec
+            .encode(Bytes::from_static(b"hello"), &mut buf)
+            .unwrap();
+        codec
+            .encode(Bytes::from_static(b"world"), &mut buf)
+            .unwrap();
+
+        // Flip a bit in the payload of the second frame.
+        let last = buf.len() - CHECKSUM_LEN - 1;
+        buf[last] ^= 1;
+
+        assert_eq!(b"hello", &codec.decode(&mut buf).unwrap().unwrap()[..]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        let corrupt = CorruptFrame::from_io_error(&err).unwrap();
+        assert_eq!(5 + CHECKSUM_LEN, corrupt.len);
+        assert_ne!(corrupt.expected, Some(corrupt.actual));
+    }
+}
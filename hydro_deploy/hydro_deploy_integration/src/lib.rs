@@ -1,37 +1,95 @@
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Duration;
 
 use async_recursion::async_recursion;
 use bytes::{Bytes, BytesMut};
+use futures::channel::mpsc;
 use futures::sink::Buffer;
 use futures::stream::{FuturesUnordered, SplitSink, SplitStream};
+use futures::task::AtomicWaker;
 use futures::{Future, Sink, SinkExt, Stream, StreamExt, stream};
 use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
 use tempfile::TempDir;
 use tokio::io;
 use tokio::net::{TcpListener, TcpStream};
 #[cfg(unix)]
 use tokio::net::{UnixListener, UnixStream};
 use tokio_stream::wrappers::TcpListenerStream;
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio_util::codec::Framed;
 
+use crate::integrity::{FrameCodec, FrameConfig};
+#[cfg(windows)]
+use crate::named_pipe::{UnixListener, UnixStream};
+
+#[cfg(feature = "http")]
+pub mod http;
+pub mod integrity;
 pub mod multi_connection;
+#[cfg(windows)]
+mod named_pipe;
 pub mod single_connection;
 
-pub type InitConfig<'a> = (HashMap<String, ServerBindConfig>, Option<Cow<'a, str>>);
+pub type InitConfig<'a> = (
+    HashMap<String, ServerBindConfig>,
+    Option<Cow<'a, str>>,
+    FrameConfig,
+);
+
+/// Environment variable naming a file containing an [`InstanceConfig`]. When set, a program
+/// reads its ports from that file instead of negotiating them with Hydro Deploy over stdin.
+pub const INSTANCE_CONFIG_ENV: &str = "HYDRO_INSTANCE_CONFIG";
+
+/// Environment variable naming the directory which the assets shipped alongside a program are
+/// copied into, if it has any. See [`asset_dir`].
+pub const ASSET_DIR_ENV: &str = "HYDRO_ASSET_DIR";
+
+/// The directory containing the assets shipped alongside this program by Hydro Deploy, or `None`
+/// if it was launched without any.
+pub fn asset_dir() -> Option<PathBuf> {
+    std::env::var_os(ASSET_DIR_ENV).map(PathBuf::from)
+}
+
+/// Environment variable naming the directory in which a program keeps its persistent data, which
+/// is preserved when the program is restarted. See [`data_dir`].
+pub const DATA_DIR_ENV: &str = "HYDRO_DATA_DIR";
+
+/// The directory in which this program keeps its persistent data, or `None` if it was launched
+/// without one. The directory may not exist yet.
+pub fn data_dir() -> Option<PathBuf> {
+    std::env::var_os(DATA_DIR_ENV).map(PathBuf::from)
+}
+
+/// Complete port configuration for a program launched without Hydro Deploy, read from the file
+/// named by [`INSTANCE_CONFIG_ENV`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InstanceConfig {
+    /// Ports the program listens on, by port name.
+    pub bind: HashMap<String, ServerBindConfig>,
+    /// Ports of other programs that the program connects to, by port name.
+    pub connect: HashMap<String, ServerPort>,
+    /// Serialized metadata, in the same format as the second element of [`InitConfig`].
+    pub meta: Option<String>,
+    /// How frames are encoded, which must be the same for every program of the deployment.
+    #[serde(default)]
+    pub frame: FrameConfig,
+}
 
 /// Contains runtime information passed by Hydro Deploy to a program,
 /// describing how to connect to other services and metadata about them.
 pub struct DeployPorts<T = Option<()>> {
     pub ports: RefCell<HashMap<String, Connection>>,
     pub meta: T,
+    /// Triggered when Hydro Deploy asks the program to drain, see [`ControlMessage::Drain`].
+    pub drain: DrainSignal,
 }
 
 impl<T> DeployPorts<T> {
@@ -44,10 +102,81 @@ impl<T> DeployPorts<T> {
     }
 }
 
-#[cfg(not(unix))]
+/// Control messages sent by Hydro Deploy to a started program, one per line on its stdin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlMessage {
+    /// Stop accepting input, finish processing and sending any outstanding data, then print
+    /// [`ControlMessage::DRAINED`] and exit.
+    Drain,
+    /// Exit immediately.
+    Terminate,
+}
+
+impl ControlMessage {
+    /// The line printed by the program once it has finished draining.
+    pub const DRAINED: &'static str = "drained";
+
+    /// Parses a control message from a line of stdin, returning `None` if it is not recognized.
+    pub fn parse(line: &str) -> Option<Self> {
+        match line.trim() {
+            "drain" => Some(ControlMessage::Drain),
+            "stop" => Some(ControlMessage::Terminate),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ControlMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlMessage::Drain => write!(f, "drain"),
+            ControlMessage::Terminate => write!(f, "stop"),
+        }
+    }
+}
+
+/// Shared flag which is set once the program has been asked to drain. Network sources wrapped
+/// with [`DrainSignal::close_on_drain`] end as soon as it is triggered.
+#[derive(Clone, Debug)]
+pub struct DrainSignal(Arc<tokio::sync::watch::Sender<bool>>);
+
+impl Default for DrainSignal {
+    fn default() -> Self {
+        Self(Arc::new(tokio::sync::watch::channel(false).0))
+    }
+}
+
+impl DrainSignal {
+    /// Starts draining, waking up everything waiting on [`Self::drained`].
+    pub fn trigger(&self) {
+        self.0.send_replace(true);
+    }
+
+    /// Whether [`Self::trigger`] has been called.
+    pub fn is_draining(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Completes once [`Self::trigger`] has been called.
+    pub async fn drained(&self) {
+        let mut receiver = self.0.subscribe();
+        while !*receiver.borrow_and_update() {
+            // The sender is kept alive by `self`, so this cannot fail.
+            receiver.changed().await.unwrap();
+        }
+    }
+
+    /// Wraps `stream` so that it ends once [`Self::trigger`] has been called.
+    pub fn close_on_drain<S: Stream>(&self, stream: S) -> impl Stream<Item = S::Item> + use<S> {
+        let signal = self.clone();
+        stream.take_until(Box::pin(async move { signal.drained().await }))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
 type UnixStream = std::convert::Infallible;
 
-#[cfg(not(unix))]
+#[cfg(not(any(unix, windows)))]
 type UnixListener = std::convert::Infallible;
 
 /// Describes how to connect to a service which is listening on some port.
@@ -62,17 +191,35 @@ pub enum ServerPort {
 }
 
 impl ServerPort {
-    #[async_recursion]
+    /// Connects to the port, retrying TCP connections for about 10 seconds while the other side
+    /// is not yet listening.
+    ///
+    /// # Panics
+    /// If the other side is still not listening after that.
     pub async fn connect(&self) -> ClientConnection {
+        self.connect_with_retries(Some(10)).await
+    }
+
+    /// Like [`Self::connect`], but retries TCP connections until the other side is listening,
+    /// with exponential backoff, rather than giving up. Used when the other side may be started
+    /// arbitrarily later, such as by tooling other than Hydro Deploy.
+    pub async fn connect_until_listening(&self) -> ClientConnection {
+        self.connect_with_retries(None).await
+    }
+
+    /// Connects to the port, making up to `retries` attempts per TCP connection, or retrying
+    /// forever if `None`.
+    #[async_recursion]
+    async fn connect_with_retries(&self, retries: Option<usize>) -> ClientConnection {
         match self {
             ServerPort::UnixSocket(path) => {
-                #[cfg(unix)]
+                #[cfg(any(unix, windows))]
                 {
                     let bound = UnixStream::connect(path.clone());
                     ClientConnection::UnixSocket(bound.await.unwrap())
                 }
 
-                #[cfg(not(unix))]
+                #[cfg(not(any(unix, windows)))]
                 {
                     let _ = path;
                     panic!("Unix sockets are not supported on this platform")
@@ -80,19 +227,33 @@ impl ServerPort {
             }
             ServerPort::TcpPort(addr) => {
                 let addr_clone = *addr;
-                let stream = async_retry(
-                    move || TcpStream::connect(addr_clone),
-                    10,
-                    Duration::from_secs(1),
-                )
-                .await
-                .unwrap();
+                let stream = match retries {
+                    Some(retries) => async_retry(
+                        move || TcpStream::connect(addr_clone),
+                        retries,
+                        Duration::from_secs(1),
+                    )
+                    .await
+                    .unwrap_or_else(|e| panic!("Failed to connect to {}: {}", addr_clone, e)),
+                    None => {
+                        let mut delay = Duration::from_millis(100);
+                        loop {
+                            match TcpStream::connect(addr_clone).await {
+                                Ok(stream) => break stream,
+                                Err(_) => {
+                                    tokio::time::sleep(delay).await;
+                                    delay = (delay * 2).min(Duration::from_secs(5));
+                                }
+                            }
+                        }
+                    }
+                };
                 ClientConnection::TcpPort(stream)
             }
             ServerPort::Demux(bindings) => ClientConnection::Demux(
                 bindings
                     .iter()
-                    .map(|(k, v)| async move { (*k, v.connect().await) })
+                    .map(|(k, v)| async move { (*k, v.connect_with_retries(retries).await) })
                     .collect::<FuturesUnordered<_>>()
                     .collect::<BTreeMap<_, _>>()
                     .await,
@@ -100,14 +261,15 @@ impl ServerPort {
             ServerPort::Merge(ports) => ClientConnection::Merge(
                 ports
                     .iter()
-                    .map(|p| p.connect())
+                    .map(|p| p.connect_with_retries(retries))
                     .collect::<FuturesUnordered<_>>()
                     .collect::<Vec<_>>()
                     .await,
             ),
-            ServerPort::Tagged(port, tag) => {
-                ClientConnection::Tagged(Box::new(port.as_ref().connect().await), *tag)
-            }
+            ServerPort::Tagged(port, tag) => ClientConnection::Tagged(
+                Box::new(port.as_ref().connect_with_retries(retries).await),
+                *tag,
+            ),
             ServerPort::Null => ClientConnection::Null,
         }
     }
@@ -131,7 +293,19 @@ pub enum ClientConnection {
 pub enum ServerBindConfig {
     UnixSocket,
     TcpPort(
-        /// The host the port should be bound on.
+        /// The host the port should be bound on, which may be an IPv6 address (optionally in
+        /// brackets).
+        String,
+        /// The port the service should listen on.
+        ///
+        /// If `None`, the port will be chosen automatically.
+        Option<u16>,
+    ),
+    /// A TCP port bound on all IPv4 and IPv6 interfaces (the IPv6 wildcard `::` with
+    /// dual-stack), which accepts connections over both IP versions.
+    DualStackTcpPort(
+        /// The IP address clients are given to connect to the port, such as the internal IP of
+        /// the host.
         String,
         /// The port the service should listen on.
         ///
@@ -146,11 +320,22 @@ pub enum ServerBindConfig {
 }
 
 impl ServerBindConfig {
-    #[async_recursion]
+    /// Binds the ports described by this config.
+    ///
+    /// # Panics
+    /// If a port cannot be bound, see [`ServerBindConfig::try_bind`].
     pub async fn bind(self) -> BoundServer {
-        match self {
+        self.try_bind().await.unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Binds the ports described by this config, returning an error if any of them cannot be
+    /// bound (for example because it is already in use). Ports which were bound before the
+    /// error are released.
+    #[async_recursion]
+    pub async fn try_bind(self) -> Result<BoundServer, BindError> {
+        Ok(match self {
             ServerBindConfig::UnixSocket => {
-                #[cfg(unix)]
+                #[cfg(any(unix, windows))]
                 {
                     let dir = tempfile::tempdir().unwrap();
                     let socket_path = dir.path().join("socket");
@@ -158,43 +343,135 @@ impl ServerBindConfig {
                     BoundServer::UnixSocket(bound, dir)
                 }
 
-                #[cfg(not(unix))]
+                #[cfg(not(any(unix, windows)))]
                 {
                     panic!("Unix sockets are not supported on this platform")
                 }
             }
             ServerBindConfig::TcpPort(host, port) => {
-                let listener = TcpListener::bind((host, port.unwrap_or(0)))
+                let listener = TcpListener::bind((strip_brackets(&host), port.unwrap_or(0)))
                     .await
-                    .unwrap_or_else(|e| panic!("Failed to bind port {:?}: {}", port, e));
+                    .map_err(|e| BindError::new(&host, port, &e))?;
                 let addr = listener.local_addr().unwrap();
                 BoundServer::TcpPort(TcpListenerStream::new(listener), addr)
             }
+            ServerBindConfig::DualStackTcpPort(advertised, port) => {
+                let advertised_ip = strip_brackets(&advertised)
+                    .parse::<IpAddr>()
+                    .unwrap_or_else(|e| panic!("Invalid IP address {:?}: {}", advertised, e));
+                let listener = bind_dual_stack(port.unwrap_or(0))
+                    .map_err(|e| BindError::new(&advertised, port, &e))?;
+                let addr = SocketAddr::new(advertised_ip, listener.local_addr().unwrap().port());
+                BoundServer::TcpPort(TcpListenerStream::new(listener), addr)
+            }
             ServerBindConfig::Demux(bindings) => {
                 let mut demux = BTreeMap::new();
                 for (key, bind) in bindings {
-                    demux.insert(key, bind.bind().await); // TODO(mingwei): Do in parallel.
+                    demux.insert(key, bind.try_bind().await?); // TODO(mingwei): Do in parallel.
                 }
                 BoundServer::Demux(demux)
             }
             ServerBindConfig::Merge(bindings) => {
                 let mut merge = Vec::new();
                 for bind in bindings {
-                    merge.push(bind.bind().await); // TODO(mingwei): Do in parallel.
+                    merge.push(bind.try_bind().await?); // TODO(mingwei): Do in parallel.
                 }
                 BoundServer::Merge(merge)
             }
             ServerBindConfig::Tagged(underlying, id) => {
-                BoundServer::Tagged(Box::new(underlying.bind().await), id)
+                BoundServer::Tagged(Box::new(underlying.try_bind().await?), id)
             }
             ServerBindConfig::MultiConnection(underlying) => {
-                BoundServer::MultiConnection(Box::new(underlying.bind().await))
+                BoundServer::MultiConnection(Box::new(underlying.try_bind().await?))
             }
             ServerBindConfig::Null => BoundServer::Null,
+        })
+    }
+
+    /// Replaces every explicitly requested TCP port `from` in this config with `to`, returning
+    /// whether any port was replaced.
+    pub fn replace_port(&mut self, from: u16, to: u16) -> bool {
+        match self {
+            ServerBindConfig::UnixSocket | ServerBindConfig::Null => false,
+            ServerBindConfig::TcpPort(_, port) | ServerBindConfig::DualStackTcpPort(_, port) => {
+                if *port == Some(from) {
+                    *port = Some(to);
+                    true
+                } else {
+                    false
+                }
+            }
+            // not `any`, which would stop after the first binding with the port
+            ServerBindConfig::Demux(bindings) => {
+                bindings
+                    .values_mut()
+                    .filter(|bind| bind.replace_port(from, to))
+                    .count()
+                    > 0
+            }
+            ServerBindConfig::Merge(bindings) => {
+                bindings
+                    .iter_mut()
+                    .filter(|bind| bind.replace_port(from, to))
+                    .count()
+                    > 0
+            }
+            ServerBindConfig::Tagged(underlying, _)
+            | ServerBindConfig::MultiConnection(underlying) => underlying.replace_port(from, to),
         }
     }
 }
 
+/// Why a [`ServerBindConfig`] could not be bound.
+///
+/// When launched by Hydro Deploy, a program reports this (as JSON) on a line starting with
+/// [`BindError::PREFIX`] instead of panicking, and then waits for a new [`InitConfig`], so that
+/// Hydro Deploy can report the conflict or retry with a different port.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BindError {
+    /// The address the port was bound on.
+    pub host: String,
+    /// The port that could not be bound, or `None` if it was to be chosen automatically.
+    pub port: Option<u16>,
+    /// Whether the port is already in use (`EADDRINUSE`).
+    pub in_use: bool,
+    /// A description of the underlying error.
+    pub message: String,
+}
+
+impl BindError {
+    /// The prefix of the line on which a program reports a [`BindError`].
+    pub const PREFIX: &'static str = "bind error: ";
+
+    fn new(host: &str, port: Option<u16>, error: &io::Error) -> Self {
+        BindError {
+            host: host.to_owned(),
+            port,
+            in_use: error.kind() == io::ErrorKind::AddrInUse,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for BindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.port {
+            Some(port) => write!(
+                f,
+                "Failed to bind port {} on {}: {}",
+                port, self.host, self.message
+            ),
+            None => write!(
+                f,
+                "Failed to bind a port on {}: {}",
+                self.host, self.message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BindError {}
+
 #[derive(Debug)]
 pub enum Connection {
     AsClient(ClientConnection),
@@ -239,6 +516,27 @@ pub trait ConnectedSource {
     fn into_source(self) -> Self::Stream;
 }
 
+/// Strips the brackets around an IPv6 address in a host (as in `[::1]:8080`), which are not
+/// accepted when binding.
+fn strip_brackets(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// Binds a listener on `port` of the IPv6 wildcard address, which also accepts IPv4 connections.
+fn bind_dual_stack(port: u16) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    // Defaults to IPv6 only on some platforms, such as Windows and the BSDs.
+    socket.set_only_v6(false)?;
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
 #[derive(Debug)]
 pub enum BoundServer {
     UnixSocket(UnixListener, TempDir),
@@ -265,13 +563,13 @@ pub enum AcceptedServer {
 pub async fn accept_bound(bound: BoundServer) -> AcceptedServer {
     match bound {
         BoundServer::UnixSocket(listener, dir) => {
-            #[cfg(unix)]
+            #[cfg(any(unix, windows))]
             {
                 let stream = listener.accept().await.unwrap().0;
                 AcceptedServer::UnixSocket(stream, dir)
             }
 
-            #[cfg(not(unix))]
+            #[cfg(not(any(unix, windows)))]
             {
                 let _ = listener;
                 let _ = dir;
@@ -312,12 +610,12 @@ impl BoundServer {
     pub fn server_port(&self) -> ServerPort {
         match self {
             BoundServer::UnixSocket(_, tempdir) => {
-                #[cfg(unix)]
+                #[cfg(any(unix, windows))]
                 {
                     ServerPort::UnixSocket(tempdir.path().join("socket"))
                 }
 
-                #[cfg(not(unix))]
+                #[cfg(not(any(unix, windows)))]
                 {
                     let _ = tempdir;
                     panic!("Unix sockets are not supported on this platform")
@@ -357,7 +655,7 @@ impl BoundServer {
 fn accept(bound: AcceptedServer) -> ConnectedDirect {
     match bound {
         AcceptedServer::UnixSocket(stream, _dir) => {
-            #[cfg(unix)]
+            #[cfg(any(unix, windows))]
             {
                 ConnectedDirect {
                     stream_sink: Some(Box::pin(unix_bytes(stream))),
@@ -366,7 +664,7 @@ fn accept(bound: AcceptedServer) -> ConnectedDirect {
                 }
             }
 
-            #[cfg(not(unix))]
+            #[cfg(not(any(unix, windows)))]
             {
                 let _ = stream;
                 panic!("Unix sockets are not supported on this platform")
@@ -407,12 +705,12 @@ fn accept(bound: AcceptedServer) -> ConnectedDirect {
 }
 
 fn tcp_bytes(stream: TcpStream) -> impl StreamSink {
-    Framed::new(stream, LengthDelimitedCodec::new())
+    Framed::new(stream, FrameCodec::default())
 }
 
-#[cfg(unix)]
+#[cfg(any(unix, windows))]
 fn unix_bytes(stream: UnixStream) -> impl StreamSink {
-    Framed::new(stream, LengthDelimitedCodec::new())
+    Framed::new(stream, FrameCodec::default())
 }
 
 struct IoErrorDrain<T> {
@@ -473,7 +771,7 @@ impl Connected for ConnectedDirect {
     fn from_defn(pipe: Connection) -> Self {
         match pipe {
             Connection::AsClient(ClientConnection::UnixSocket(stream)) => {
-                #[cfg(unix)]
+                #[cfg(any(unix, windows))]
                 {
                     ConnectedDirect {
                         stream_sink: Some(Box::pin(unix_bytes(stream))),
@@ -482,7 +780,7 @@ impl Connected for ConnectedDirect {
                     }
                 }
 
-                #[cfg(not(unix))]
+                #[cfg(not(any(unix, windows)))]
                 {
                     let _ = stream;
                     panic!("Unix sockets are not supported on this platform");
@@ -712,10 +1010,73 @@ impl<T: Unpin, S: Stream<Item = T> + Send + Sync + ?Sized> Stream for MergeSourc
     }
 }
 
+#[derive(Default)]
+struct TaggedControlState {
+    paused: Mutex<HashSet<u32>>,
+    /// Woken when a source is resumed, so that the merged stream polls it again.
+    resume_waker: AtomicWaker,
+    /// The tags of the sources which have disconnected, and the listeners for future disconnects.
+    disconnects: Mutex<(Vec<u32>, Vec<mpsc::UnboundedSender<u32>>)>,
+}
+
+/// A handle for controlling the sources merged by a [`ConnectedTagged`], obtained with
+/// [`ConnectedTagged::control`].
+///
+/// Pausing a source stops reading from it, so that it is slowed down by backpressure without
+/// affecting the other sources. This can be used to ingest fairly from peers of differing
+/// speeds.
+#[derive(Clone, Default)]
+pub struct TaggedControl(Arc<TaggedControlState>);
+
+impl TaggedControl {
+    /// Stops reading from the source with the given tag, until [`Self::resume`] is called.
+    pub fn pause(&self, tag: u32) {
+        self.0.paused.lock().unwrap().insert(tag);
+    }
+
+    /// Resumes reading from the source with the given tag.
+    pub fn resume(&self, tag: u32) {
+        if self.0.paused.lock().unwrap().remove(&tag) {
+            self.0.resume_waker.wake();
+        }
+    }
+
+    /// Whether the source with the given tag is paused.
+    pub fn is_paused(&self, tag: u32) -> bool {
+        self.0.paused.lock().unwrap().contains(&tag)
+    }
+
+    /// The tags of the sources which have disconnected so far.
+    pub fn disconnected(&self) -> Vec<u32> {
+        self.0.disconnects.lock().unwrap().0.clone()
+    }
+
+    /// A stream of the tags of sources as they disconnect, starting with those which have
+    /// already disconnected.
+    pub fn disconnects(&self) -> impl Stream<Item = u32> + use<> {
+        let (sender, receiver) = mpsc::unbounded();
+        let mut disconnects = self.0.disconnects.lock().unwrap();
+        for tag in &disconnects.0 {
+            let _ = sender.unbounded_send(*tag);
+        }
+        disconnects.1.push(sender);
+        receiver
+    }
+
+    fn notify_disconnect(&self, tag: u32) {
+        let mut disconnects = self.0.disconnects.lock().unwrap();
+        disconnects.0.push(tag);
+        disconnects
+            .1
+            .retain(|listener| listener.unbounded_send(tag).is_ok());
+    }
+}
+
 pub struct TaggedSource<T: Unpin, S: Stream<Item = Result<T, io::Error>> + Send + Sync + ?Sized> {
     marker: PhantomData<T>,
     id: u32,
     source: Pin<Box<S>>,
+    control: TaggedControl,
 }
 
 impl<T: Unpin, S: Stream<Item = Result<T, io::Error>> + Send + Sync + ?Sized> Stream
@@ -724,11 +1085,22 @@ impl<T: Unpin, S: Stream<Item = Result<T, io::Error>> + Send + Sync + ?Sized> St
     type Item = Result<(u32, T), io::Error>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let id = self.as_ref().id;
-        let source = &mut self.get_mut().source;
-        match source.as_mut().poll_next(cx) {
+        let me = self.get_mut();
+        let id = me.id;
+        if me.control.is_paused(id) {
+            me.control.0.resume_waker.register(cx.waker());
+            // Check again in case the source was resumed before the waker was registered.
+            if me.control.is_paused(id) {
+                return Poll::Pending;
+            }
+        }
+
+        match me.source.as_mut().poll_next(cx) {
             Poll::Ready(Some(v)) => Poll::Ready(Some(v.map(|d| (id, d)))),
-            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Ready(None) => {
+                me.control.notify_disconnect(id);
+                Poll::Ready(None)
+            }
             Poll::Pending => Poll::Pending,
         }
     }
@@ -744,6 +1116,17 @@ where
     <T as ConnectedSource>::Output: 'static + Sync + Unpin,
 {
     source: MergedMux<T>,
+    control: TaggedControl,
+}
+
+impl<T: ConnectedSource> ConnectedTagged<T>
+where
+    <T as ConnectedSource>::Output: 'static + Sync + Unpin,
+{
+    /// A handle for pausing individual sources and observing when they disconnect.
+    pub fn control(&self) -> TaggedControl {
+        self.control.clone()
+    }
 }
 
 impl<T: Connected + ConnectedSource> Connected for ConnectedTagged<T>
@@ -801,12 +1184,14 @@ where
             _ => panic!("Cannot connect to a non-tagged pipe as a tagged"),
         };
 
+        let control = TaggedControl::default();
         let mut connected_mux = Vec::new();
         for (pipe, id) in sources {
             connected_mux.push(Some(Box::pin(TaggedSource {
                 marker: PhantomData,
                 id,
                 source: pipe,
+                control: control.clone(),
             })));
         }
 
@@ -816,7 +1201,10 @@ where
             poll_cursor: 0,
         };
 
-        ConnectedTagged { source: muxer }
+        ConnectedTagged {
+            source: muxer,
+            control,
+        }
     }
 }
 
@@ -840,6 +1228,62 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_control_message_parse() {
+        for message in [ControlMessage::Drain, ControlMessage::Terminate] {
+            assert_eq!(
+                Some(message),
+                ControlMessage::parse(&format!("{message}\n"))
+            );
+        }
+        assert_eq!(
+            Some(ControlMessage::Drain),
+            ControlMessage::parse("  drain \r\n")
+        );
+        assert_eq!(None, ControlMessage::parse("start: {}"));
+        assert_eq!(None, ControlMessage::parse(ControlMessage::DRAINED));
+        assert_eq!(None, ControlMessage::parse(""));
+    }
+
+    #[test]
+    fn test_drain_signal_wakes_waiters() {
+        let drain = DrainSignal::default();
+        assert!(!drain.is_draining());
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut drained = Box::pin(drain.drained());
+        assert!(drained.as_mut().poll(&mut cx).is_pending());
+
+        drain.clone().trigger();
+        assert!(drain.is_draining());
+        assert!(drained.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_connect_until_listening() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let addr = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap();
+
+            // The other side only starts listening after the first attempts have failed.
+            let listening = tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                let listener = TcpListener::bind(addr).await.unwrap();
+                listener.accept().await.unwrap();
+            });
+
+            let connection = ServerPort::TcpPort(addr).connect_until_listening().await;
+            assert!(matches!(connection, ClientConnection::TcpPort(_)));
+            listening.await.unwrap();
+        });
+    }
+
     #[test]
     fn test_merge_source_fair_polling() {
         // Create test streams that yield values in a predictable pattern
@@ -869,4 +1313,150 @@ mod tests {
         // With fair polling, we should get values in round-robin order: 1, 2, 3, 4, 5, 6, 7, 8, 9
         assert_eq!(results, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
     }
+
+    #[test]
+    fn test_tagged_control_pause_and_disconnect() {
+        let control = TaggedControl::default();
+        let tagged = |id: u32, items: Vec<i32>| {
+            Some(Box::pin(TaggedSource {
+                marker: PhantomData,
+                id,
+                source: Box::pin(stream::iter(items.into_iter().map(Ok::<_, io::Error>))),
+                control: control.clone(),
+            }))
+        };
+
+        let mut merge_source = MergeSource {
+            marker: PhantomData,
+            sources: vec![tagged(0, vec![1, 2]), tagged(1, vec![3])],
+            poll_cursor: 0,
+        };
+        let mut disconnects = control.disconnects();
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut poll = |merge_source: &mut MergeSource<_, _>| match Pin::new(merge_source)
+            .poll_next(&mut cx)
+        {
+            Poll::Ready(Some(Ok(value))) => Some(value),
+            Poll::Ready(Some(Err(e))) => panic!("{e}"),
+            Poll::Ready(None) | Poll::Pending => None,
+        };
+
+        control.pause(0);
+        assert_eq!(poll(&mut merge_source), Some((1, 3)));
+        // Only the paused source is left once the other has disconnected.
+        assert_eq!(poll(&mut merge_source), None);
+        assert_eq!(control.disconnected(), vec![1]);
+
+        control.resume(0);
+        assert_eq!(poll(&mut merge_source), Some((0, 1)));
+        assert_eq!(poll(&mut merge_source), Some((0, 2)));
+        assert_eq!(poll(&mut merge_source), None);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut disconnected = Vec::new();
+        while let Poll::Ready(Some(tag)) = Pin::new(&mut disconnects).poll_next(&mut cx) {
+            disconnected.push(tag);
+        }
+        assert_eq!(disconnected, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_bind_reports_port_in_use() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let bound = ServerBindConfig::TcpPort("127.0.0.1".to_owned(), None)
+                .bind()
+                .await;
+            let ServerPort::TcpPort(addr) = bound.server_port() else {
+                unreachable!()
+            };
+
+            let mut config = ServerBindConfig::Demux(BTreeMap::from([(
+                0,
+                ServerBindConfig::TcpPort("127.0.0.1".to_owned(), Some(addr.port())),
+            )]));
+            let error = config.clone().try_bind().await.unwrap_err();
+            assert_eq!(Some(addr.port()), error.port);
+            assert!(error.in_use);
+
+            assert!(config.replace_port(addr.port(), 0));
+            config.try_bind().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_replace_port_replaces_every_binding() {
+        let mut config = ServerBindConfig::Merge(vec![
+            ServerBindConfig::TcpPort("127.0.0.1".to_owned(), Some(1234)),
+            ServerBindConfig::TcpPort("127.0.0.1".to_owned(), Some(1234)),
+            ServerBindConfig::TcpPort("127.0.0.1".to_owned(), Some(5678)),
+        ]);
+        assert!(config.replace_port(1234, 4321));
+        let ServerBindConfig::Merge(bindings) = &config else {
+            unreachable!()
+        };
+        let ports = bindings
+            .iter()
+            .map(|bind| match bind {
+                ServerBindConfig::TcpPort(_, port) => *port,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![Some(4321), Some(4321), Some(5678)], ports);
+        assert!(!config.replace_port(1234, 4321));
+    }
+
+    #[test]
+    fn test_dual_stack_accepts_ipv4_and_ipv6() {
+        if std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).is_err() {
+            eprintln!("skipping test, IPv6 loopback is not available");
+            return;
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let bound = ServerBindConfig::DualStackTcpPort("[::1]".to_owned(), None)
+                .bind()
+                .await;
+            let ServerPort::TcpPort(addr) = bound.server_port() else {
+                unreachable!()
+            };
+            assert_eq!(IpAddr::from(Ipv6Addr::LOCALHOST), addr.ip());
+
+            TcpStream::connect(addr).await.unwrap();
+            TcpStream::connect(("127.0.0.1", addr.port()))
+                .await
+                .unwrap();
+        });
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_unix_socket_round_trip() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let bound = ServerBindConfig::UnixSocket.bind().await;
+            let port = bound.server_port();
+            let (accepted, client) = futures::join!(accept_bound(bound), port.connect());
+
+            let (_, mut client_sink) =
+                ConnectedDirect::from_defn(Connection::AsClient(client)).into_source_sink();
+            let (mut server_source, _) = accept(accepted).into_source_sink();
+            client_sink.send(Bytes::from("hello")).await.unwrap();
+            assert_eq!(
+                &b"hello"[..],
+                &server_source.next().await.unwrap().unwrap()[..]
+            );
+        });
+    }
 }
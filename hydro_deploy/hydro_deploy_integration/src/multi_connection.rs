@@ -5,7 +5,7 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use futures::{Sink, SinkExt, Stream, StreamExt};
-#[cfg(unix)]
+#[cfg(any(unix, windows))]
 use tempfile::TempDir;
 use tokio::net::TcpListener;
 #[cfg(unix)]
@@ -15,6 +15,8 @@ use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_util::codec::{Decoder, Encoder, Framed, FramedRead, FramedWrite};
 
+#[cfg(windows)]
+use crate::named_pipe::UnixListener;
 use crate::{AcceptedServer, BoundServer, Connected, Connection};
 
 pub struct ConnectedMultiConnection<I, O, C: Decoder<Item = I> + Encoder<O>> {
@@ -36,7 +38,7 @@ impl<
                 let (membership_sender, membership_receiver) = mpsc::unbounded_channel();
 
                 let source = match *bound_server {
-                    #[cfg(unix)]
+                    #[cfg(any(unix, windows))]
                     BoundServer::UnixSocket(listener, dir) => MultiConnectionSource {
                         unix_listener: Some(listener),
                         tcp_listener: None,
@@ -48,10 +50,10 @@ impl<
                         membership_sender,
                     },
                     BoundServer::TcpPort(listener, _) => MultiConnectionSource {
-                        #[cfg(unix)]
+                        #[cfg(any(unix, windows))]
                         unix_listener: None,
                         tcp_listener: Some(listener.into_inner()),
-                        #[cfg(unix)]
+                        #[cfg(any(unix, windows))]
                         _dir_holder: None,
                         next_connection_id: 0,
                         active_connections: Vec::new(),
@@ -83,10 +85,10 @@ type DynDecodedStream<I, C> =
 type DynEncodedSink<O, C> = Pin<Box<dyn Sink<O, Error = <C as Encoder<O>>::Error> + Send + Sync>>;
 
 pub struct MultiConnectionSource<I, O, C: Decoder<Item = I> + Encoder<O>> {
-    #[cfg(unix)]
+    #[cfg(any(unix, windows))]
     unix_listener: Option<UnixListener>,
     tcp_listener: Option<TcpListener>,
-    #[cfg(unix)]
+    #[cfg(any(unix, windows))]
     _dir_holder: Option<TempDir>, // keeps the folder containing the socket alive
     next_connection_id: u64,
     /// Ordered list for fair polling, will never be `None` at the beginning of a poll
@@ -113,7 +115,7 @@ impl<
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let me = self.deref_mut();
         // Handle Unix socket accepts
-        #[cfg(unix)]
+        #[cfg(any(unix, windows))]
         if let Some(listener) = me.unix_listener.as_mut() {
             loop {
                 match listener.poll_accept(cx) {
@@ -0,0 +1,164 @@
+//! Named pipes, which stand in for Unix domain sockets on Windows. Mirrors the parts of the API
+//! of [`tokio::net::UnixListener`] and [`tokio::net::UnixStream`] used by this crate, so that
+//! `ServerBindConfig::UnixSocket` works the same on every platform in local deployments.
+//!
+//! The "socket path" of a pipe is still a path in a temporary directory, which is mapped to a
+//! pipe name by [`pipe_name`].
+
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::windows::named_pipe::{
+    ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions,
+};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// All pipe instances are busy.
+const ERROR_PIPE_BUSY: i32 = 231;
+
+/// How long to wait for a busy pipe to create its next instance before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The name of the pipe standing in for the Unix socket at `path`.
+fn pipe_name(path: &Path) -> String {
+    // Pipe names may contain any character except a backslash.
+    let path = path.to_string_lossy().replace(['\\', '/', ':'], "-");
+    format!(r"\\.\pipe\hydro{path}")
+}
+
+/// A named pipe which accepts connections.
+///
+/// Each pipe instance serves a single connection, so a background task connects instances and
+/// creates the next one as each connection arrives.
+#[derive(Debug)]
+pub struct UnixListener {
+    connections: Mutex<mpsc::UnboundedReceiver<io::Result<NamedPipeServer>>>,
+    accept_task: JoinHandle<()>,
+}
+
+impl UnixListener {
+    /// Creates the pipe for the socket `path`. Must be called within a Tokio runtime.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let name = pipe_name(path.as_ref());
+        let mut next = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&name)?;
+
+        let (send, connections) = mpsc::unbounded_channel();
+        let accept_task = tokio::spawn(async move {
+            loop {
+                let accepted = match next.connect().await {
+                    Ok(()) => ServerOptions::new()
+                        .create(&name)
+                        .map(|instance| std::mem::replace(&mut next, instance)),
+                    Err(e) => Err(e),
+                };
+                let failed = accepted.is_err();
+                if send.send(accepted).is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self {
+            connections: Mutex::new(connections),
+            accept_task,
+        })
+    }
+
+    /// Accepts a new connection to the pipe.
+    pub async fn accept(&self) -> io::Result<(UnixStream, ())> {
+        std::future::poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    /// Polls to accept a new connection to the pipe.
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(UnixStream, ())>> {
+        let accepted = ready!(self.connections.lock().unwrap().poll_recv(cx));
+        Poll::Ready(match accepted {
+            Some(accepted) => accepted.map(|server| (UnixStream::Server(server), ())),
+            None => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the pipe stopped accepting connections",
+            )),
+        })
+    }
+}
+
+impl Drop for UnixListener {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+    }
+}
+
+/// Either end of a named pipe connection.
+#[derive(Debug)]
+pub enum UnixStream {
+    Server(NamedPipeServer),
+    Client(NamedPipeClient),
+}
+
+impl UnixStream {
+    /// Connects to the pipe for the socket `path`, waiting while all of its instances are busy.
+    pub async fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        let name = pipe_name(path.as_ref());
+        let deadline = tokio::time::Instant::now() + BUSY_TIMEOUT;
+        loop {
+            match ClientOptions::new().open(&name) {
+                Ok(client) => return Ok(UnixStream::Client(client)),
+                Err(e)
+                    if e.raw_os_error() == Some(ERROR_PIPE_BUSY)
+                        && tokio::time::Instant::now() < deadline =>
+                {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            UnixStream::Server(server) => Pin::new(server).poll_read(cx, buf),
+            UnixStream::Client(client) => Pin::new(client).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            UnixStream::Server(server) => Pin::new(server).poll_write(cx, buf),
+            UnixStream::Client(client) => Pin::new(client).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            UnixStream::Server(server) => Pin::new(server).poll_flush(cx),
+            UnixStream::Client(client) => Pin::new(client).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            UnixStream::Server(server) => Pin::new(server).poll_shutdown(cx),
+            UnixStream::Client(client) => Pin::new(client).poll_shutdown(cx),
+        }
+    }
+}
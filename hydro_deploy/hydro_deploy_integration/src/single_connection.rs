@@ -1,11 +1,11 @@
 use std::ops::DerefMut;
 use std::pin::Pin;
-#[cfg(unix)]
+#[cfg(any(unix, windows))]
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use futures::{Sink, SinkExt, Stream, StreamExt, ready};
-#[cfg(unix)]
+#[cfg(any(unix, windows))]
 use tempfile::TempDir;
 use tokio::sync::mpsc;
 use tokio_util::codec::{Decoder, Encoder, Framed};
@@ -33,11 +33,11 @@ impl<
                 let (new_sink_sender, new_sink_receiver) = mpsc::unbounded_channel();
 
                 #[cfg_attr(
-                    not(unix),
-                    expect(unused_variables, reason = "dir is only used on non-Unix")
+                    not(any(unix, windows)),
+                    expect(unused_variables, reason = "dir is unused without local sockets")
                 )]
                 let dir = match *bound_server {
-                    #[cfg(unix)]
+                    #[cfg(any(unix, windows))]
                     BoundServer::UnixSocket(listener, dir) => {
                         tokio::spawn(async move {
                             tokio::task::yield_now().await;
@@ -82,12 +82,12 @@ impl<
                             }
                         });
 
-                        #[cfg(unix)]
+                        #[cfg(any(unix, windows))]
                         {
                             None
                         }
 
-                        #[cfg(not(unix))]
+                        #[cfg(not(any(unix, windows)))]
                         {
                             None::<()>
                         }
@@ -95,18 +95,18 @@ impl<
                     _ => panic!("SingleConnection only supports UnixSocket and TcpPort"),
                 };
 
-                #[cfg(unix)]
+                #[cfg(any(unix, windows))]
                 let dir_holder_arc = dir.map(Arc::new);
 
                 let source = SingleConnectionSource {
                     new_stream_receiver,
-                    #[cfg(unix)]
+                    #[cfg(any(unix, windows))]
                     _dir_holder: dir_holder_arc.clone(),
                     active_stream: None,
                 };
 
                 let sink = SingleConnectionSink::<O, C> {
-                    #[cfg(unix)]
+                    #[cfg(any(unix, windows))]
                     _dir_holder: dir_holder_arc,
                     connection_sink: None,
                     new_sink_receiver,
@@ -125,14 +125,14 @@ type DynEncodedSink<O, C> = Pin<Box<dyn Sink<O, Error = <C as Encoder<O>>::Error
 
 pub struct SingleConnectionSource<I, C: Decoder<Item = I>> {
     new_stream_receiver: mpsc::UnboundedReceiver<DynDecodedStream<I, C>>,
-    #[cfg(unix)]
+    #[cfg(any(unix, windows))]
     _dir_holder: Option<Arc<TempDir>>, // keeps the folder containing the socket alive
     /// The active stream for the single connection, if taken
     active_stream: Option<DynDecodedStream<I, C>>,
 }
 
 pub struct SingleConnectionSink<O, C: Encoder<O>> {
-    #[cfg(unix)]
+    #[cfg(any(unix, windows))]
     _dir_holder: Option<Arc<TempDir>>, // keeps the folder containing the socket alive
     connection_sink: Option<DynEncodedSink<O, C>>,
     new_sink_receiver: mpsc::UnboundedReceiver<DynEncodedSink<O, C>>,
@@ -1,9 +1,14 @@
 use std::any::type_name;
 use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::marker::PhantomData;
+use std::path::PathBuf;
 use std::rc::{Rc, Weak};
 
+use serde::de::DeserializeOwned;
 use slotmap::{SecondaryMap, SlotMap};
+use stageleft::quote_type;
+use syn::parse_quote;
 
 #[cfg(feature = "build")]
 use super::compiled::CompiledFlow;
@@ -14,7 +19,12 @@ use super::deploy_provider::{ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec}
 #[cfg(feature = "build")]
 use super::ir::HydroIrOpMetadata;
 use super::ir::{HydroNode, HydroRoot};
-use crate::location::{Cluster, External, LocationKey, LocationType, Process};
+#[cfg(feature = "build")]
+use super::placement::{AutoPlace, PlacementConstraints};
+use crate::live_collections::boundedness::Bounded;
+use crate::live_collections::singleton::Singleton;
+use crate::location::{Cluster, External, Location, LocationKey, LocationType, Process};
+use crate::manual_expr::ManualExpr;
 
 /// A compile-time directive to spawn a future on a location's `LocalSet`
 /// alongside the DFIR scheduler.
@@ -36,7 +46,7 @@ pub enum Sidecar {
 #[cfg(feature = "sim")]
 #[cfg(stageleft_runtime)]
 use crate::sim::flow::SimFlow;
-use crate::staging_util::Invariant;
+use crate::staging_util::{Invariant, get_this_crate};
 
 #[stageleft::export(ExternalPortId, CycleId, ClockId, SidecarId, StmtId, HandoffId)]
 crate::newtype_counter! {
@@ -77,6 +87,76 @@ impl SidecarId {
     }
 }
 
+/// The environment variable from which the value of the configuration parameter `name` (declared
+/// with [`FlowBuilder::config`]) is read at runtime: `HYDRO_CONFIG_` followed by the name in upper
+/// case, with any characters other than ASCII letters and digits replaced by `_`.
+pub fn config_env_var(name: &str) -> String {
+    let suffix = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("HYDRO_CONFIG_{}", suffix)
+}
+
+/// An error providing the values of configuration parameters (declared with
+/// [`FlowBuilder::config`]) to a deployment.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// No configuration parameter with this name was declared.
+    Undeclared(String),
+    /// The value of a configuration parameter could not be serialized as JSON.
+    Serialize {
+        /// The name of the configuration parameter.
+        name: String,
+        /// Why the value could not be serialized.
+        message: String,
+    },
+    /// A configuration file could not be read or parsed.
+    File {
+        /// The path of the configuration file.
+        path: PathBuf,
+        /// Why the file could not be read or parsed.
+        message: String,
+    },
+    /// The deployment backend cannot set environment variables for the launched binaries, so the
+    /// values must be provided in their environment instead.
+    Unsupported,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Undeclared(name) => {
+                write!(
+                    f,
+                    "no configuration parameter named `{}` was declared",
+                    name
+                )
+            }
+            ConfigError::Serialize { name, message } => write!(
+                f,
+                "failed to serialize the value of configuration parameter `{}`: {}",
+                name, message
+            ),
+            ConfigError::File { path, message } => {
+                write!(f, "invalid config file {}: {}", path.display(), message)
+            }
+            ConfigError::Unsupported => write!(
+                f,
+                "this deployment backend cannot set environment variables, so configuration values must be provided in the environment of the launched binaries"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 pub(crate) type FlowState = Rc<RefCell<FlowStateInner>>;
 
 pub(crate) struct FlowStateInner {
@@ -101,6 +181,17 @@ pub(crate) struct FlowStateInner {
     /// not part of the dataflow IR.
     pub sidecars: Vec<Sidecar>,
 
+    /// Names of the configuration parameters declared with [`FlowBuilder::config`].
+    pub config_params: BTreeSet<String>,
+
+    /// Fixture files replayed into each location with
+    /// [`External::source_fixture`](crate::location::External::source_fixture).
+    pub fixtures: Vec<(LocationKey, PathBuf)>,
+
+    /// Names given to external ports with
+    /// [`External::name_port`](crate::location::External::name_port).
+    pub port_names: BTreeMap<ExternalPortId, String>,
+
     /// Weak references to the IR nodes of all live collections (streams, singletons, ...)
     /// created against this flow. When the flow is finalized, any collection that is still
     /// alive (its `Rc` has not been dropped) has its IR yanked and registered as a root,
@@ -213,6 +304,9 @@ impl<'a> FlowBuilder<'a> {
                 next_clock_id: crate::Counter::default(),
                 next_sidecar_id: crate::Counter::default(),
                 sidecars: Vec::new(),
+                config_params: BTreeSet::new(),
+                fixtures: Vec::new(),
+                port_names: BTreeMap::new(),
                 live_collection_nodes: Vec::new(),
             })),
             locations: SlotMap::with_key(),
@@ -269,6 +363,37 @@ impl<'a> FlowBuilder<'a> {
         }
     }
 
+    /// Declares a configuration parameter named `name` on `location`, whose value is supplied
+    /// when the flow is deployed instead of being baked into the compiled binary. This allows the
+    /// same binary to be reused with, for example, different timeouts or cluster sizes.
+    ///
+    /// At runtime, the value is read from the environment variable given by
+    /// [`config_env_var`] (`HYDRO_CONFIG_<NAME>`) and parsed as JSON (or used as a plain string).
+    /// With Hydro Deploy, values can also be given with
+    /// [`DeployFlow::with_config`] or [`DeployFlow::with_config_file`], which set this variable
+    /// on every launched binary.
+    ///
+    /// # Panics
+    /// At runtime, if no value is provided or it cannot be parsed as a `T`.
+    pub fn config<T: DeserializeOwned, L: Location<'a>>(
+        &self,
+        location: &L,
+        name: &str,
+    ) -> Singleton<T, L::DropConsistency, Bounded> {
+        self.flow_state
+            .borrow_mut()
+            .config_params
+            .insert(name.to_owned());
+
+        let var = config_env_var(name);
+        let name = name.to_owned();
+        location.singleton(ManualExpr::new(move |_: &L| -> syn::Expr {
+            let root = get_this_crate();
+            let t_type: syn::Type = quote_type::<T>();
+            parse_quote!(#root::runtime_support::config::load::<#t_type>(#name, #var))
+        }))
+    }
+
     #[cfg(feature = "sim")]
     pub fn next_version<C>(&mut self, cluster: &Cluster<'a, C>) -> Cluster<'a, C> {
         let group_root = self.location_version_group_root[cluster.key];
@@ -315,15 +440,50 @@ impl<'a> FlowBuilder<'a> {
 
         let mut ir = flow_state.roots.take().unwrap();
         let sidecars = std::mem::take(&mut flow_state.sidecars);
+        let config_params = std::mem::take(&mut flow_state.config_params);
+        let fixtures = std::mem::take(&mut flow_state.fixtures);
+        let port_names = std::mem::take(&mut flow_state.port_names);
         drop(flow_state);
 
+        for node in super::ir::eliminate_dead_code(&mut ir) {
+            let printed = node.print_root();
+            let span = node
+                .op_metadata()
+                .backtrace
+                .format_span()
+                .map(|span| format!(" ({})", span))
+                .unwrap_or_default();
+            tracing::warn!(
+                "removing `{}` at `{}`{}, since its output is never used",
+                printed.split('(').next().unwrap_or(&printed),
+                self.location_names[node.metadata().location_id.root().key()],
+                span
+            );
+        }
+
+        let used_locations = super::ir::used_locations(&ir);
+        self.locations.retain(|key, location_type| {
+            let used = *location_type == LocationType::External || used_locations.contains(&key);
+            if !used {
+                tracing::warn!(
+                    "`{}` has no operators, so it will not be deployed",
+                    self.location_names[key]
+                );
+            }
+            used
+        });
+
         super::ir::unify_atomic_ticks(&mut ir);
+        super::ir::assign_stable_ids(&mut ir);
 
         super::built::BuiltFlow {
             ir,
             locations: std::mem::take(&mut self.locations),
             location_names: std::mem::take(&mut self.location_names),
             sidecars,
+            config_params,
+            fixtures,
+            port_names,
             flow_name: std::mem::take(&mut self.flow_name),
             #[cfg(feature = "sim")]
             location_version: std::mem::take(&mut self.location_version),
@@ -333,6 +493,15 @@ impl<'a> FlowBuilder<'a> {
         }
     }
 
+    /// Finalizes the flow and prints a [`FlowReport`](super::report::FlowReport) summarizing
+    /// the operators, network channels, and ticks at each location. Returns the finalized flow,
+    /// so that it can still be deployed.
+    pub fn report(self) -> super::built::BuiltFlow<'a> {
+        let built = self.finalize();
+        println!("{}", built.report());
+        built
+    }
+
     pub fn with_default_optimize<D: Deploy<'a>>(self) -> DeployFlow<'a, D> {
         self.finalize().with_default_optimize()
     }
@@ -356,6 +525,10 @@ impl<'a> FlowBuilder<'a> {
         self.with_default_optimize().with_remaining_processes(spec)
     }
 
+    pub fn auto_place<D: Deploy<'a>>(self, constraints: PlacementConstraints) -> AutoPlace<'a, D> {
+        self.with_default_optimize().auto_place(constraints)
+    }
+
     pub fn with_external<P, D: Deploy<'a>>(
         self,
         process: &External<P>,
@@ -410,6 +583,9 @@ impl<'a> FlowBuilder<'a> {
                 next_clock_id: crate::Counter::default(),
                 next_sidecar_id: crate::Counter::default(),
                 sidecars: Vec::new(),
+                config_params: built.config_params.clone(),
+                fixtures: built.fixtures.clone(),
+                port_names: built.port_names.clone(),
                 live_collection_nodes: Vec::new(),
             })),
             locations: built.locations.clone(),
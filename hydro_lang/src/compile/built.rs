@@ -1,14 +1,18 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::marker::PhantomData;
+use std::path::PathBuf;
 
 use dfir_lang::graph::{
     DfirGraph, FlatGraphBuilderOutput, eliminate_extra_unions_tees, partition_graph,
 };
 use slotmap::{SecondaryMap, SlotMap};
 
+use super::builder::ExternalPortId;
 use super::compiled::CompiledFlow;
 use super::deploy::{DeployFlow, DeployResult};
 use super::deploy_provider::{ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec};
 use super::ir::{HydroRoot, emit};
+use super::placement::{AutoPlace, PlacementConstraints};
 use crate::location::{Cluster, External, LocationKey, LocationType, Process};
 #[cfg(stageleft_runtime)]
 #[cfg(feature = "sim")]
@@ -26,6 +30,15 @@ pub struct BuiltFlow<'a> {
     /// Compile-time sidecar directives extracted from the flow state.
     pub(super) sidecars: Vec<super::builder::Sidecar>,
 
+    /// Names of the configuration parameters declared in the flow.
+    pub(super) config_params: BTreeSet<String>,
+
+    /// Fixture files replayed into each location.
+    pub(super) fixtures: Vec<(LocationKey, PathBuf)>,
+
+    /// Names given to external ports, used by [`Self::interface`].
+    pub(super) port_names: BTreeMap<ExternalPortId, String>,
+
     /// Application name used in telemetry.
     pub(super) flow_name: String,
 
@@ -48,6 +61,9 @@ pub(crate) fn build_inner(ir: &mut Vec<HydroRoot>) -> SecondaryMap<LocationKey,
         .map(|(k, v)| {
             let FlatGraphBuilderOutput { mut flat_graph, .. } =
                 v.build().expect("Failed to build DFIR flat graph.");
+            flat_graph
+                .merge_modules()
+                .expect("Failed to merge DFIR modules.");
             eliminate_extra_unions_tees(&mut flat_graph);
             let partitioned_graph =
                 partition_graph(flat_graph).expect("Failed to partition (cycle detected).");
@@ -114,8 +130,14 @@ impl<'a> BuiltFlow<'a> {
         self.graph_api().generate_graph(config)
     }
 
+    /// Rewrites the IR with the optimization `f`.
+    ///
+    /// Operators already carry their [stable IDs](super::ir::HydroIrOpMetadata::stable_id), so `f`
+    /// can use them to target operators across edits to the program. Operators created by `f` are
+    /// assigned stable IDs once it returns.
     pub fn optimize_with(mut self, f: impl FnOnce(&mut [HydroRoot])) -> Self {
         f(&mut self.ir);
+        super::ir::assign_stable_ids(&mut self.ir);
         self
     }
 
@@ -175,7 +197,11 @@ impl<'a> BuiltFlow<'a> {
             location_version_group_root: self.location_version_group_root,
             test_safety_only: false,
             skip_consistency_assertions: false,
+            check_manual_proofs: false,
             unit_test_fuzz_iterations: 8192,
+            link_models: Default::default(),
+            default_link_model: None,
+            explore_latencies: false,
             _phantom: PhantomData,
         }
     }
@@ -190,7 +216,11 @@ impl<'a> BuiltFlow<'a> {
             clusters,
             externals,
             sidecars: self.sidecars,
+            config_params: self.config_params,
+            fixtures: self.fixtures,
+            config_values: BTreeMap::new(),
             flow_name: self.flow_name,
+            network_tracing: None,
             _phantom: PhantomData,
         }
     }
@@ -210,6 +240,10 @@ impl<'a> BuiltFlow<'a> {
         self.into_deploy().with_remaining_processes(spec)
     }
 
+    pub fn auto_place<D: Deploy<'a>>(self, constraints: PlacementConstraints) -> AutoPlace<'a, D> {
+        self.into_deploy().auto_place(constraints)
+    }
+
     pub fn with_external<P, D: Deploy<'a>>(
         self,
         process: &External<P>,
@@ -1,6 +1,7 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::io::Error;
 use std::marker::PhantomData;
+use std::path::PathBuf;
 use std::pin::Pin;
 
 use bytes::{Bytes, BytesMut};
@@ -11,6 +12,7 @@ use serde::de::DeserializeOwned;
 use slotmap::{SecondaryMap, SlotMap, SparseSecondaryMap};
 use stageleft::QuotedWithContext;
 
+use super::builder::ConfigError;
 use super::built::build_inner;
 use super::compiled::CompiledFlow;
 use super::deploy_provider::{
@@ -19,6 +21,9 @@ use super::deploy_provider::{
 use super::ir::HydroRoot;
 use crate::live_collections::stream::{Ordering, Retries};
 use crate::location::dynamic::LocationId;
+use crate::location::exactly_once::{
+    ExactlyOnceReceiver, ExternalExactlyOnceStream, ReceiverMessage, SenderMessage,
+};
 use crate::location::external_process::{
     ExternalBincodeBidi, ExternalBincodeSink, ExternalBincodeStream, ExternalBytesPort,
 };
@@ -26,6 +31,46 @@ use crate::location::{Cluster, External, Location, LocationKey, LocationType, Pr
 use crate::staging_util::Invariant;
 use crate::telemetry::Sidecar;
 
+/// An error deploying a flow, returned by [`DeployFlow::try_deploy`] before anything is
+/// instantiated.
+#[derive(Debug)]
+pub enum DeployError {
+    /// A location replays a fixture (see
+    /// [`External::source_fixture`](crate::location::External::source_fixture)) but is not
+    /// deployed to this machine, so its binaries cannot read the file.
+    RemoteFixture {
+        /// The name of the location.
+        location: String,
+        /// The path of the fixture.
+        path: PathBuf,
+    },
+    /// The configuration parameters could not be provided to a location.
+    Config {
+        /// The name of the location.
+        location: String,
+        /// Why the configuration could not be provided.
+        error: ConfigError,
+    },
+}
+
+impl std::fmt::Display for DeployError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeployError::RemoteFixture { location, path } => write!(
+                f,
+                "location `{}` replays the fixture `{}`, so it must be deployed to this machine (fixtures are only supported in simulations and local deployments)",
+                location,
+                path.display()
+            ),
+            DeployError::Config { location, error } => {
+                write!(f, "failed to configure location `{}`: {}", location, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeployError {}
+
 pub struct DeployFlow<'a, D>
 where
     D: Deploy<'a>,
@@ -43,9 +88,20 @@ where
     /// Compile-time sidecar directives (both simple futures and external TCP sidecars).
     pub(super) sidecars: Vec<super::builder::Sidecar>,
 
+    /// Names of the configuration parameters declared in the flow.
+    pub(super) config_params: BTreeSet<String>,
+    /// JSON-encoded values of configuration parameters, by name.
+    pub(super) config_values: BTreeMap<String, String>,
+
+    /// Fixture files replayed into each location, which must run on this machine.
+    pub(super) fixtures: Vec<(LocationKey, PathBuf)>,
+
     /// Application name used in telemetry.
     pub(super) flow_name: String,
 
+    /// The OTLP endpoint network spans are exported to, if network tracing is enabled.
+    pub(super) network_tracing: Option<String>,
+
     pub(super) _phantom: Invariant<'a, D>,
 }
 
@@ -59,6 +115,11 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
         &self.flow_name
     }
 
+    /// If the location was removed when the flow was finalized, since it has no operators.
+    fn is_pruned(&self, location_key: LocationKey) -> bool {
+        !self.locations.contains_key(location_key) && self.location_names.contains_key(location_key)
+    }
+
     pub fn with_process<P>(
         mut self,
         process: &Process<P>,
@@ -79,6 +140,9 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
         process_loc_key: LocationKey,
         spec: impl IntoProcessSpec<'a, D>,
     ) -> Self {
+        if self.is_pruned(process_loc_key) {
+            return self;
+        }
         assert_eq!(
             Some(&LocationType::Process),
             self.locations.get(process_loc_key),
@@ -126,6 +190,9 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
         cluster_loc_key: LocationKey,
         spec: impl ClusterSpec<'a, D>,
     ) -> Self {
+        if self.is_pruned(cluster_loc_key) {
+            return self;
+        }
         assert_eq!(
             Some(&LocationType::Cluster),
             self.locations.get(cluster_loc_key),
@@ -215,6 +282,9 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
         location_key: LocationKey,
         sidecar: &impl Sidecar,
     ) -> Self {
+        if self.is_pruned(location_key) {
+            return self;
+        }
         let location_type = self.locations[location_key];
         let location_name = &self.location_names[location_key];
         let future_expr = sidecar.to_expr(
@@ -241,6 +311,87 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
         self.with_sidecar_internal(cluster.key, sidecar)
     }
 
+    /// Sets the value of the configuration parameter `name` (declared with
+    /// [`FlowBuilder::config`](crate::compile::builder::FlowBuilder::config)) for every launched
+    /// binary, taking precedence over the environment and configuration files.
+    ///
+    /// # Errors
+    /// If no configuration parameter named `name` was declared, or `value` cannot be serialized.
+    #[cfg(feature = "deploy")]
+    pub fn with_config(mut self, name: &str, value: impl Serialize) -> Result<Self, ConfigError> {
+        if !self.config_params.contains(name) {
+            return Err(ConfigError::Undeclared(name.to_owned()));
+        }
+        let value = serde_json::to_string(&value).map_err(|e| ConfigError::Serialize {
+            name: name.to_owned(),
+            message: e.to_string(),
+        })?;
+        self.config_values.insert(name.to_owned(), value);
+        Ok(self)
+    }
+
+    /// Reads the values of configuration parameters from the top-level keys of a TOML file.
+    /// Values already set with [`Self::with_config`] take precedence, and keys which are not
+    /// configuration parameters of this flow are ignored.
+    ///
+    /// # Errors
+    /// If the file cannot be read or is not valid TOML.
+    #[cfg(feature = "deploy")]
+    pub fn with_config_file(
+        mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let file_error = |message: String| ConfigError::File {
+            path: path.to_owned(),
+            message,
+        };
+        let contents = std::fs::read_to_string(path).map_err(|e| file_error(e.to_string()))?;
+        let table: toml::Table =
+            toml::from_str(&contents).map_err(|e| file_error(e.to_string()))?;
+        for (name, value) in table {
+            if self.config_params.contains(&name) && !self.config_values.contains_key(&name) {
+                let value = serde_json::to_string(&value).map_err(|e| ConfigError::Serialize {
+                    name: name.clone(),
+                    message: e.to_string(),
+                })?;
+                self.config_values.insert(name, value);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Enables distributed tracing across network channels, exporting spans to the OTLP (HTTP)
+    /// collector at `endpoint` (e.g. `http://localhost:4318/v1/traces`).
+    ///
+    /// Each message sent over the network carries the trace context of a `hydro_send` span
+    /// recorded by the sender, and the receiver records a `hydro_recv` span as its child, so
+    /// that end-to-end latency can be followed across processes without changing the flow. Spans
+    /// are reported under the service name `{flow_name}/{location_name}`. See
+    /// [`crate::telemetry::network_tracing`].
+    #[cfg(feature = "network_tracing")]
+    pub fn with_network_tracing(mut self, endpoint: impl Into<String>) -> Self {
+        self.network_tracing = Some(endpoint.into());
+        self
+    }
+
+    /// The environment variables which supply the configuration parameters, using values set with
+    /// [`Self::with_config`] or else the environment of the deploying process.
+    fn config_env(&self) -> Vec<(String, String)> {
+        self.config_params
+            .iter()
+            .filter_map(|name| {
+                let var = super::builder::config_env_var(name);
+                let value = self
+                    .config_values
+                    .get(name)
+                    .cloned()
+                    .or_else(|| std::env::var(&var).ok())?;
+                Some((var, value))
+            })
+            .collect()
+    }
+
     /// Compiles the flow into DFIR ([`dfir_lang::graph::DfirGraph`]) without networking.
     /// Useful for generating Mermaid diagrams of the DFIR.
     ///
@@ -270,6 +421,10 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
     ///
     /// Empties `self.sidecars` and modifies `self.ir`, leaving `self` in a partial state.
     pub(super) fn compile_internal(&mut self, env: &mut D::InstantiateEnv) -> CompiledFlow<'a> {
+        if self.network_tracing.is_some() {
+            super::ir::add_network_tracing(&mut self.ir);
+        }
+
         let mut seen_tees: HashMap<_, _> = HashMap::new();
         let mut seen_cluster_members = HashSet::new();
         let mut extra_stmts = SparseSecondaryMap::new();
@@ -285,6 +440,29 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
             );
         }
 
+        if let Some(endpoint) = &self.network_tracing {
+            let root = crate::staging_util::get_this_crate();
+            for (location_key, &location_type) in self.locations.iter() {
+                if !matches!(location_type, LocationType::Process | LocationType::Cluster) {
+                    continue;
+                }
+
+                let service_name =
+                    format!("{}/{}", self.flow_name, self.location_names[location_key]);
+                // Runs first, so that spans recorded while setting up the location are exported.
+                extra_stmts
+                    .entry(location_key)
+                    .expect("location was removed")
+                    .or_default()
+                    .insert(
+                        0,
+                        syn::parse_quote! {
+                            #root::telemetry::network_tracing::init(#service_name, #endpoint);
+                        },
+                    );
+            }
+        }
+
         // Process sidecar declarations — compile-time directives that
         // produce futures to spawn on each location's LocalSet.
         let mut sidecars: SparseSecondaryMap<LocationKey, Vec<syn::Expr>> =
@@ -382,8 +560,56 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
     /// * Instantiates nodes as configured.
     /// * Compiles the corresponding DFIR into binaries for nodes as needed.
     /// * Connects up networking as needed.
+    ///
+    /// # Panics
+    /// If the flow cannot be deployed as configured, see [`Self::try_deploy`].
     #[must_use]
-    pub fn deploy(mut self, env: &mut D::InstantiateEnv) -> DeployResult<'a, D> {
+    pub fn deploy(self, env: &mut D::InstantiateEnv) -> DeployResult<'a, D> {
+        self.try_deploy(env).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compiles and deploys the flow, like [`Self::deploy`].
+    ///
+    /// # Errors
+    /// If a location replays a fixture but is not deployed to this machine, or the configuration
+    /// parameters cannot be provided to a location. Nothing is instantiated in that case.
+    pub fn try_deploy(
+        mut self,
+        env: &mut D::InstantiateEnv,
+    ) -> Result<DeployResult<'a, D>, DeployError> {
+        for (location_key, path) in &self.fixtures {
+            let runs_locally = if let Some(node) = self.processes.get(*location_key) {
+                node.runs_locally()
+            } else if let Some(cluster) = self.clusters.get(*location_key) {
+                cluster.runs_locally()
+            } else {
+                continue;
+            };
+            if !runs_locally {
+                return Err(DeployError::RemoteFixture {
+                    location: self.location_names[*location_key].clone(),
+                    path: path.clone(),
+                });
+            }
+        }
+
+        let config_env = self.config_env();
+        if !config_env.is_empty() {
+            for (location_key, location_name) in self.location_names.iter() {
+                let result = if let Some(node) = self.processes.get(location_key) {
+                    node.set_env(&config_env)
+                } else if let Some(cluster) = self.clusters.get(location_key) {
+                    cluster.set_env(&config_env)
+                } else {
+                    continue;
+                };
+                result.map_err(|error| DeployError::Config {
+                    location: location_name.clone(),
+                    error,
+                })?;
+            }
+        }
+
         let CompiledFlow {
             dfir,
             mut extra_stmts,
@@ -458,12 +684,12 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
             leaf.connect_network(&mut seen_tees_connect);
         }
 
-        DeployResult {
+        Ok(DeployResult {
             location_names: self.location_names,
             processes,
             clusters,
             externals,
-        }
+        })
     }
 }
 
@@ -475,18 +701,31 @@ pub struct DeployResult<'a, D: Deploy<'a>> {
 }
 
 impl<'a, D: Deploy<'a>> DeployResult<'a, D> {
+    /// Panics for a location which was not deployed, naming it and explaining why, since it was
+    /// most likely removed when the flow was finalized.
+    fn not_deployed(&self, location_key: LocationKey) -> ! {
+        panic!(
+            "`{}` was not deployed, since it has no operators after removing unused pipelines when the flow was finalized",
+            self.location_names[location_key]
+        )
+    }
+
     pub fn get_process<P>(&self, p: &Process<P>) -> &D::Process {
         let LocationId::Process(location_key) = p.id() else {
             panic!("Process ID expected")
         };
-        self.processes.get(location_key).unwrap()
+        self.processes
+            .get(location_key)
+            .unwrap_or_else(|| self.not_deployed(location_key))
     }
 
     pub fn get_cluster<C>(&self, c: &Cluster<'a, C>) -> &D::Cluster {
         let LocationId::Cluster(location_key) = c.id() else {
             panic!("Cluster ID expected")
         };
-        self.clusters.get(location_key).unwrap()
+        self.clusters
+            .get(location_key)
+            .unwrap_or_else(|| self.not_deployed(location_key))
     }
 
     pub fn get_external<P>(&self, e: &External<P>) -> &D::External {
@@ -600,6 +839,26 @@ impl DeployResult<'_, crate::deploy::HydroDeploy> {
             .unwrap()
             .raw_port(port.port_id)
     }
+
+    /// Get the URL of an HTTP endpoint created with [`External::http_endpoint`].
+    pub async fn http_url(
+        &self,
+        endpoint: &crate::location::external_process::ExternalHttpEndpoint,
+    ) -> String {
+        let server_port = self
+            .externals
+            .get(endpoint.process_key)
+            .unwrap()
+            .raw_port(endpoint.port_id)
+            .server_port()
+            .await;
+        match server_port {
+            hydro_deploy_integration::ServerPort::TcpPort(addr) => {
+                format!("http://{}{}", addr, endpoint.route)
+            }
+            other => panic!("HTTP endpoint is not bound to a TCP port: {:?}", other),
+        }
+    }
 }
 
 pub trait ConnectableAsync<Ctx> {
@@ -650,3 +909,149 @@ impl<'a, D: Deploy<'a>, T: Serialize + 'static, Many> ConnectableAsync<&DeployRe
             .await
     }
 }
+
+impl<'a, D: Deploy<'a>, T: Serialize + DeserializeOwned + 'static>
+    ConnectableAsync<&DeployResult<'a, D>> for ExternalExactlyOnceStream<T>
+{
+    type Output = ExactlyOnceReceiver<
+        T,
+        Pin<Box<dyn Stream<Item = SenderMessage<T>>>>,
+        Pin<Box<dyn Sink<ReceiverMessage, Error = Error>>>,
+    >;
+
+    async fn connect(self, ctx: &DeployResult<'a, D>) -> Self::Output {
+        let elements = self.elements.connect(ctx).await;
+        let acks = self.acks.connect(ctx).await;
+        ExactlyOnceReceiver::new(elements, acks)
+    }
+}
+
+#[cfg(all(test, feature = "deploy"))]
+mod tests {
+    use super::*;
+    use crate::prelude::{FlowBuilder, q};
+
+    fn config_flow() -> DeployFlow<'static, crate::deploy::HydroDeploy> {
+        let mut flow = FlowBuilder::new();
+        let process = flow.process::<()>();
+        let _timeout = flow.config::<u32, _>(&process, "timeout");
+        flow.finalize()
+            .with_default_optimize::<crate::deploy::HydroDeploy>()
+    }
+
+    #[test]
+    fn with_config_rejects_undeclared_parameter() {
+        assert!(matches!(
+            config_flow().with_config("missing", 1),
+            Err(ConfigError::Undeclared(name)) if name == "missing"
+        ));
+
+        let flow = config_flow().with_config("timeout", 5).unwrap();
+        assert_eq!(
+            vec![("HYDRO_CONFIG_TIMEOUT".to_owned(), "5".to_owned())],
+            flow.config_env()
+        );
+    }
+
+    #[test]
+    fn with_config_file_reads_declared_parameters() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "timeout = 10\nunused = \"ignored\"\n").unwrap();
+
+        let flow = config_flow().with_config_file(&path).unwrap();
+        assert_eq!(
+            vec![("HYDRO_CONFIG_TIMEOUT".to_owned(), "10".to_owned())],
+            flow.config_env()
+        );
+
+        // Values set with `with_config` take precedence over the file.
+        let flow = config_flow()
+            .with_config("timeout", 5)
+            .unwrap()
+            .with_config_file(&path)
+            .unwrap();
+        assert_eq!(
+            vec![("HYDRO_CONFIG_TIMEOUT".to_owned(), "5".to_owned())],
+            flow.config_env()
+        );
+    }
+
+    #[test]
+    fn with_config_file_reports_invalid_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.toml");
+        assert!(matches!(
+            config_flow().with_config_file(&missing),
+            Err(ConfigError::File { path, .. }) if path == missing
+        ));
+
+        let invalid = dir.path().join("invalid.toml");
+        std::fs::write(&invalid, "timeout = ").unwrap();
+        assert!(matches!(
+            config_flow().with_config_file(&invalid),
+            Err(ConfigError::File { path, .. }) if path == invalid
+        ));
+    }
+
+    struct Used;
+    struct Pruned;
+
+    #[tokio::test]
+    #[should_panic(expected = "Pruned` was not deployed")]
+    async fn get_process_names_pruned_location() {
+        let mut flow = FlowBuilder::new();
+        let process = flow.process::<Used>();
+        let pruned = flow.process::<Pruned>();
+        let external = flow.external::<Used>();
+        // The output of this pipeline is never used, so it is removed along with `pruned`.
+        let _unused = pruned.source_iter(q!([1, 2, 3])).map(q!(|x| x + 1));
+        let _port = process
+            .source_iter(q!([1, 2, 3]))
+            .send_bincode_external(&external);
+
+        let mut deployment = hydro_deploy::Deployment::new();
+        let nodes = flow
+            .with_process(&process, deployment.Localhost())
+            .with_process(&pruned, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        nodes.get_process(&process);
+        nodes.get_process(&pruned);
+    }
+
+    #[test]
+    fn try_deploy_rejects_remote_fixture() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = dir.path().join("inputs.jsonl");
+        std::fs::write(&fixture, "1\n").unwrap();
+
+        let mut flow = FlowBuilder::new();
+        let process = flow.process::<()>();
+        let external = flow.external::<()>();
+        external
+            .source_fixture::<_, i32>(&process, &fixture)
+            .for_each(q!(|_| {}));
+
+        let mut deployment = hydro_deploy::Deployment::new();
+        let network = hydro_deploy::gcp::GcpNetwork::new("project", None);
+        let host = deployment
+            .GcpComputeEngineHost()
+            .project("project")
+            .machine_type("e2-micro")
+            .image("debian-cloud/debian-12")
+            .region("us-west1-a")
+            .network(network)
+            .add();
+
+        let result = flow
+            .with_process(&process, crate::deploy::TrybuildHost::new(host))
+            .with_external(&external, deployment.Localhost())
+            .try_deploy(&mut deployment);
+        assert!(matches!(
+            result,
+            Err(DeployError::RemoteFixture { path, .. }) if path == fixture.canonicalize().unwrap()
+        ));
+    }
+}
@@ -8,7 +8,7 @@ use serde::Serialize;
 use serde::de::DeserializeOwned;
 use stageleft::QuotedWithContext;
 
-use crate::compile::builder::ExternalPortId;
+use crate::compile::builder::{ConfigError, ExternalPortId};
 use crate::location::dynamic::LocationId;
 use crate::location::member_id::TaglessMemberId;
 use crate::location::{LocationKey, MembershipEvent, NetworkHint};
@@ -170,6 +170,17 @@ pub trait Deploy<'a> {
     ) -> syn::Expr;
     fn e2o_many_sink(shared_handle: String) -> syn::Expr;
 
+    /// Generates the source expression for an HTTP endpoint served by [`Self::Process`] at
+    /// `route`, which yields the ID and body of each request. Responses are sent with the sink
+    /// returned by [`Self::e2o_many_sink`] for the same `shared_handle`.
+    fn e2o_http_source(
+        extra_stmts: &mut Vec<syn::Stmt>,
+        p2: &Self::Process,
+        p2_port: &<Self::Process as Node>::Port,
+        route: &str,
+        shared_handle: String,
+    ) -> syn::Expr;
+
     fn e2o_source(
         extra_stmts: &mut Vec<syn::Stmt>,
         p1: &Self::External,
@@ -355,6 +366,27 @@ pub trait Node {
         extra_stmts: &[syn::Stmt],
         sidecars: &[syn::Expr],
     );
+
+    /// Sets environment variables for the binaries launched for this node, called before
+    /// [`Self::instantiate`]. Used to supply the values of deploy-time configuration parameters
+    /// (see [`FlowBuilder::config`](crate::compile::builder::FlowBuilder::config)).
+    ///
+    /// By default, returns [`ConfigError::Unsupported`] if `env` is not empty, since the backend
+    /// cannot set environment variables.
+    fn set_env(&self, env: &[(String, String)]) -> Result<(), ConfigError> {
+        if env.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Unsupported)
+        }
+    }
+
+    /// Whether the binaries launched for this node run on the machine deploying the flow, so that
+    /// they can read files from its filesystem, such as the fixtures replayed by
+    /// [`External::source_fixture`](crate::location::External::source_fixture).
+    fn runs_locally(&self) -> bool {
+        false
+    }
 }
 
 pub type DynSourceSink<Out, In, InErr> = (
@@ -415,6 +415,16 @@ impl<'a> Deploy<'a> for EmbeddedDeploy {
         panic!("EmbeddedDeploy does not support networking (e2o)")
     }
 
+    fn e2o_http_source(
+        _extra_stmts: &mut Vec<syn::Stmt>,
+        _p2: &Self::Process,
+        _p2_port: &<Self::Process as Node>::Port,
+        _route: &str,
+        _shared_handle: String,
+    ) -> syn::Expr {
+        panic!("EmbeddedDeploy does not support networking (e2o)")
+    }
+
     fn e2o_source(
         _extra_stmts: &mut Vec<syn::Stmt>,
         _p1: &Self::External,
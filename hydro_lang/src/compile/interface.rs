@@ -0,0 +1,686 @@
+//! The guarantees a Hydro program makes about the collections it exchanges with the outside world,
+//! for checking that a new version of the program does not silently weaken them.
+//!
+//! A [`FlowInterface`] records the [`CollectionContract`] of each externally visible port: the
+//! ports of [`External`](crate::location::External) locations, and the inputs and outputs of
+//! embedded functions. It can be published as a JSON file with [`FlowInterface::write`], and later
+//! versions of the program checked against it with
+//! [`BuiltFlow::assert_interface_compatible`].
+
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::path::Path;
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+
+use super::builder::ExternalPortId;
+use super::built::BuiltFlow;
+use super::ir::{
+    BoundKind, CollectionKind, HydroNode, HydroRoot, HydroSource, KeyedSingletonBoundKind,
+    SingletonBoundKind, StreamOrder, StreamRetry, deep_clone, transform_bottom_up,
+};
+use crate::location::LocationKey;
+
+/// The externally visible ports of a Hydro program, by name.
+///
+/// Ports of an external location are named `<external>/<name>` when given a name with
+/// [`External::name_port`](crate::location::External::name_port), `<external><route>` for HTTP
+/// endpoints, and `<external>/<port ID>` otherwise. The inputs and outputs of embedded functions
+/// are named `<location>/<name>`. Types are recorded in a normalized form, see
+/// [`CollectionContract`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowInterface {
+    /// The collections the program receives, with the guarantees it relies on.
+    pub inputs: BTreeMap<String, CollectionContract>,
+    /// The collections the program sends, with the guarantees it provides.
+    pub outputs: BTreeMap<String, CollectionContract>,
+}
+
+/// The type and guarantees of a collection sent or received through a port, as in
+/// [`CollectionKind`].
+///
+/// Types are printed without whitespace between tokens (except between words), without leading
+/// `::`, with the `alloc` and `core` crates spelled as `std`, and without the `__staged` modules of
+/// staged crates, so that they do not depend on how the compiler happened to spell them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+#[expect(missing_docs, reason = "fields are as in `CollectionKind`")]
+pub enum CollectionContract {
+    /// A [`Stream`](crate::live_collections::Stream).
+    Stream {
+        bound: BoundKind,
+        order: StreamOrder,
+        retry: StreamRetry,
+        element_type: String,
+    },
+    /// A [`Singleton`](crate::live_collections::Singleton).
+    Singleton {
+        bound: SingletonBoundKind,
+        element_type: String,
+    },
+    /// An [`Optional`](crate::live_collections::Optional).
+    Optional {
+        bound: BoundKind,
+        element_type: String,
+    },
+    /// A [`KeyedStream`](crate::live_collections::KeyedStream).
+    KeyedStream {
+        bound: BoundKind,
+        value_order: StreamOrder,
+        value_retry: StreamRetry,
+        key_type: String,
+        value_type: String,
+    },
+    /// A [`KeyedSingleton`](crate::live_collections::KeyedSingleton).
+    KeyedSingleton {
+        bound: KeyedSingletonBoundKind,
+        key_type: String,
+        value_type: String,
+    },
+}
+
+impl From<&CollectionKind> for CollectionContract {
+    fn from(kind: &CollectionKind) -> Self {
+        let ty = normalize_type;
+        match kind {
+            CollectionKind::Stream {
+                bound,
+                order,
+                retry,
+                element_type,
+            } => CollectionContract::Stream {
+                bound: bound.clone(),
+                order: order.clone(),
+                retry: retry.clone(),
+                element_type: ty(element_type),
+            },
+            CollectionKind::Singleton {
+                bound,
+                element_type,
+            } => CollectionContract::Singleton {
+                bound: bound.clone(),
+                element_type: ty(element_type),
+            },
+            CollectionKind::Optional {
+                bound,
+                element_type,
+            } => CollectionContract::Optional {
+                bound: bound.clone(),
+                element_type: ty(element_type),
+            },
+            CollectionKind::KeyedStream {
+                bound,
+                value_order,
+                value_retry,
+                key_type,
+                value_type,
+            } => CollectionContract::KeyedStream {
+                bound: bound.clone(),
+                value_order: value_order.clone(),
+                value_retry: value_retry.clone(),
+                key_type: ty(key_type),
+                value_type: ty(value_type),
+            },
+            CollectionKind::KeyedSingleton {
+                bound,
+                key_type,
+                value_type,
+            } => CollectionContract::KeyedSingleton {
+                bound: bound.clone(),
+                key_type: ty(key_type),
+                value_type: ty(value_type),
+            },
+        }
+    }
+}
+
+impl CollectionContract {
+    /// The ways in which this contract does not uphold the guarantees of `other`, i.e. why a
+    /// consumer relying on `other` could observe different behavior.
+    fn weakenings_of(&self, other: &CollectionContract) -> Vec<String> {
+        let mut weakenings = vec![];
+        let mut check = |field: &str, upholds: bool, new: &dyn Debug, old: &dyn Debug| {
+            if !upholds {
+                weakenings.push(format!("{field} changed from `{old:?}` to `{new:?}`"));
+            }
+        };
+        match (self, other) {
+            (
+                CollectionContract::Stream {
+                    bound,
+                    order,
+                    retry,
+                    ..
+                },
+                CollectionContract::Stream {
+                    bound: old_bound,
+                    order: old_order,
+                    retry: old_retry,
+                    ..
+                },
+            )
+            | (
+                CollectionContract::KeyedStream {
+                    bound,
+                    value_order: order,
+                    value_retry: retry,
+                    ..
+                },
+                CollectionContract::KeyedStream {
+                    bound: old_bound,
+                    value_order: old_order,
+                    value_retry: old_retry,
+                    ..
+                },
+            ) => {
+                check("bound", bound_upholds(bound, old_bound), bound, old_bound);
+                check(
+                    "order",
+                    *order == StreamOrder::TotalOrder || *old_order == StreamOrder::NoOrder,
+                    order,
+                    old_order,
+                );
+                check(
+                    "retry",
+                    *retry == StreamRetry::ExactlyOnce || *old_retry == StreamRetry::AtLeastOnce,
+                    retry,
+                    old_retry,
+                );
+            }
+            (
+                CollectionContract::Singleton { bound, .. },
+                CollectionContract::Singleton {
+                    bound: old_bound, ..
+                },
+            ) => {
+                let rank = |bound: &SingletonBoundKind| match bound {
+                    SingletonBoundKind::Unbounded => 0,
+                    SingletonBoundKind::Monotonic => 1,
+                    SingletonBoundKind::Bounded => 2,
+                };
+                check("bound", rank(bound) >= rank(old_bound), bound, old_bound);
+            }
+            (
+                CollectionContract::Optional { bound, .. },
+                CollectionContract::Optional {
+                    bound: old_bound, ..
+                },
+            ) => {
+                check("bound", bound_upholds(bound, old_bound), bound, old_bound);
+            }
+            (
+                CollectionContract::KeyedSingleton { bound, .. },
+                CollectionContract::KeyedSingleton {
+                    bound: old_bound, ..
+                },
+            ) => {
+                let upholds = bound == old_bound
+                    || *bound == KeyedSingletonBoundKind::Bounded
+                    || *old_bound == KeyedSingletonBoundKind::Unbounded
+                    || (*bound == KeyedSingletonBoundKind::BoundedValue
+                        && matches!(
+                            old_bound,
+                            KeyedSingletonBoundKind::MonotonicKeys
+                                | KeyedSingletonBoundKind::MonotonicValue
+                        ));
+                check("bound", upholds, bound, old_bound);
+            }
+            _ => {
+                return vec![format!(
+                    "collection changed from `{}` to `{}`",
+                    other.name(),
+                    self.name()
+                )];
+            }
+        }
+
+        if self.key_type() != other.key_type() {
+            weakenings.push(format!(
+                "key type changed from `{}` to `{}`",
+                other.key_type().unwrap_or_default(),
+                self.key_type().unwrap_or_default()
+            ));
+        }
+        if self.value_type() != other.value_type() {
+            weakenings.push(format!(
+                "element type changed from `{}` to `{}`",
+                other.value_type(),
+                self.value_type()
+            ));
+        }
+        weakenings
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            CollectionContract::Stream { .. } => "Stream",
+            CollectionContract::Singleton { .. } => "Singleton",
+            CollectionContract::Optional { .. } => "Optional",
+            CollectionContract::KeyedStream { .. } => "KeyedStream",
+            CollectionContract::KeyedSingleton { .. } => "KeyedSingleton",
+        }
+    }
+
+    /// The type of each element, or of each value for keyed collections.
+    fn value_type(&self) -> &str {
+        match self {
+            CollectionContract::Stream { element_type, .. }
+            | CollectionContract::Singleton { element_type, .. }
+            | CollectionContract::Optional { element_type, .. } => element_type,
+            CollectionContract::KeyedStream { value_type, .. }
+            | CollectionContract::KeyedSingleton { value_type, .. } => value_type,
+        }
+    }
+
+    fn key_type(&self) -> Option<&str> {
+        match self {
+            CollectionContract::KeyedStream { key_type, .. }
+            | CollectionContract::KeyedSingleton { key_type, .. } => Some(key_type),
+            _ => None,
+        }
+    }
+}
+
+/// Prints `ty` in the normalized form described in [`CollectionContract`].
+fn normalize_type(ty: &syn::Type) -> String {
+    let mut printed = String::new();
+    write_normalized(ty.to_token_stream(), &mut printed);
+    printed
+}
+
+fn write_normalized(tokens: TokenStream, out: &mut String) {
+    let tokens = tokens.into_iter().collect::<Vec<_>>();
+    let is_path_sep = |i: usize| {
+        matches!(
+            (tokens.get(i), tokens.get(i + 1)),
+            (Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b)))
+                if a.as_char() == ':' && b.as_char() == ':'
+        )
+    };
+    let push_word = |out: &mut String, word: &str| {
+        if out.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+            out.push(' ');
+        }
+        out.push_str(word);
+    };
+
+    // Whether the next path segment starts a path, rather than following a `::`.
+    let mut path_start = true;
+    let mut i = 0;
+    while i < tokens.len() {
+        if is_path_sep(i) {
+            if !path_start {
+                out.push_str("::");
+            }
+            i += 2;
+            continue;
+        }
+
+        match &tokens[i] {
+            TokenTree::Ident(ident) => {
+                let word = ident.to_string();
+                if is_path_sep(i + 1) && (word == "__staged" || word == "__deps") {
+                    i += 3;
+                    continue;
+                }
+                if path_start && is_path_sep(i + 1) && (word == "alloc" || word == "core") {
+                    push_word(out, "std");
+                } else {
+                    push_word(out, &word);
+                }
+                path_start = false;
+            }
+            TokenTree::Punct(punct) => {
+                out.push(punct.as_char());
+                // `<T as Trait>::Assoc` continues the path after the `>`.
+                path_start = punct.as_char() != '>';
+            }
+            TokenTree::Literal(literal) => {
+                push_word(out, &literal.to_string());
+                path_start = true;
+            }
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+                out.push_str(open);
+                write_normalized(group.stream(), out);
+                out.push_str(close);
+                path_start = true;
+            }
+        }
+        i += 1;
+    }
+}
+
+/// Whether a collection with `bound` upholds the guarantees of one with `old_bound`.
+fn bound_upholds(bound: &BoundKind, old_bound: &BoundKind) -> bool {
+    *bound == BoundKind::Bounded || *old_bound == BoundKind::Unbounded
+}
+
+/// A change to a port which breaks consumers of a previously published [`FlowInterface`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Incompatibility {
+    /// The name of the port.
+    pub port: String,
+    /// If the port is an input, rather than an output.
+    pub is_input: bool,
+    /// A description of the change.
+    pub reason: String,
+}
+
+impl Display for Incompatibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let direction = if self.is_input { "input" } else { "output" };
+        write!(f, "{direction} `{}`: {}", self.port, self.reason)
+    }
+}
+
+impl FlowInterface {
+    /// Reads an interface published with [`Self::write`].
+    pub fn read(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Publishes this interface as a JSON file at `path`.
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+    }
+
+    /// Checks that this interface upholds the guarantees of the `published` one, returning each
+    /// change which would break its consumers.
+    ///
+    /// Every published port must still exist, with the same types. Outputs may only strengthen
+    /// their guarantees (for example, from [`StreamOrder::NoOrder`] to [`StreamOrder::TotalOrder`]),
+    /// while inputs may only relax the guarantees they rely on. New ports are always compatible.
+    pub fn check_compatible(&self, published: &FlowInterface) -> Vec<Incompatibility> {
+        let mut incompatibilities = vec![];
+        for (is_input, ports, published_ports) in [
+            (true, &self.inputs, &published.inputs),
+            (false, &self.outputs, &published.outputs),
+        ] {
+            for (port, published_contract) in published_ports {
+                let reasons = match ports.get(port) {
+                    None => vec!["port was removed".to_owned()],
+                    // Inputs are contravariant: the published guarantees must uphold the new ones.
+                    Some(contract) if is_input => published_contract.weakenings_of(contract),
+                    Some(contract) => contract.weakenings_of(published_contract),
+                };
+                incompatibilities.extend(reasons.into_iter().map(|reason| Incompatibility {
+                    port: port.clone(),
+                    is_input,
+                    reason,
+                }));
+            }
+        }
+        incompatibilities
+    }
+}
+
+impl<'a> BuiltFlow<'a> {
+    fn port_name(&self, location: LocationKey, port: impl Display) -> String {
+        let location = self
+            .location_names
+            .get(location)
+            .cloned()
+            .unwrap_or_default();
+        format!("{location}/{port}")
+    }
+
+    fn external_port_name(&self, external: LocationKey, port_id: &ExternalPortId) -> String {
+        match self.port_names.get(port_id) {
+            Some(name) => self.port_name(external, name),
+            None => self.port_name(external, port_id),
+        }
+    }
+
+    /// The externally visible ports of this program and their guarantees, see [`FlowInterface`].
+    pub fn interface(&self) -> FlowInterface {
+        // The traversal needs mutable access, so it runs on a copy of the IR.
+        let mut ir = deep_clone(&self.ir);
+        let mut interface = FlowInterface::default();
+        transform_bottom_up(
+            &mut ir,
+            &mut |root: &mut HydroRoot| match &*root {
+                HydroRoot::SendExternal {
+                    to_external_key,
+                    to_port_id,
+                    input,
+                    ..
+                } => {
+                    interface.outputs.insert(
+                        self.external_port_name(*to_external_key, to_port_id),
+                        (&input.metadata().collection_kind).into(),
+                    );
+                }
+                HydroRoot::EmbeddedOutput { ident, input, .. } => {
+                    interface.outputs.insert(
+                        self.port_name(input.metadata().location_id.root().key(), ident),
+                        (&input.metadata().collection_kind).into(),
+                    );
+                }
+                _ => {}
+            },
+            &mut |node: &mut HydroNode| match &*node {
+                HydroNode::ExternalInput {
+                    from_external_key,
+                    from_port_id,
+                    http_route,
+                    metadata,
+                    ..
+                } => {
+                    let name = match http_route {
+                        Some(route) => format!(
+                            "{}{route}",
+                            self.location_names
+                                .get(*from_external_key)
+                                .cloned()
+                                .unwrap_or_default()
+                        ),
+                        None => self.external_port_name(*from_external_key, from_port_id),
+                    };
+                    interface
+                        .inputs
+                        .insert(name, (&metadata.collection_kind).into());
+                }
+                HydroNode::Source {
+                    source: HydroSource::Embedded(ident) | HydroSource::EmbeddedSingleton(ident),
+                    metadata,
+                } => {
+                    interface.inputs.insert(
+                        self.port_name(metadata.location_id.root().key(), ident),
+                        (&metadata.collection_kind).into(),
+                    );
+                }
+                _ => {}
+            },
+            false,
+        );
+        interface
+    }
+
+    /// Checks that this program upholds the guarantees of the interface published at `path` (see
+    /// [`FlowInterface::check_compatible`]), returning the flow so that it can still be deployed.
+    ///
+    /// # Panics
+    /// If the published interface cannot be read, or if any of its guarantees are broken, listing
+    /// each incompatible change.
+    pub fn assert_interface_compatible(self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let published = FlowInterface::read(path).unwrap_or_else(|e| {
+            panic!(
+                "Failed to read the published interface {}: {}",
+                path.display(),
+                e
+            )
+        });
+
+        let incompatibilities = self.interface().check_compatible(&published);
+        if !incompatibilities.is_empty() {
+            let changes = incompatibilities
+                .iter()
+                .map(|incompatibility| format!("  {incompatibility}"))
+                .collect::<Vec<_>>();
+            panic!(
+                "The program breaks the guarantees of the published interface {}:\n{}",
+                path.display(),
+                changes.join("\n")
+            );
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use super::*;
+    use crate::live_collections::stream::{ExactlyOnce, TotalOrder};
+    use crate::location::Location;
+    use crate::prelude::FlowBuilder;
+
+    #[test]
+    fn interface_records_external_ports() {
+        let mut flow = FlowBuilder::new();
+        let process = flow.process::<()>();
+        let external = flow.external::<()>();
+
+        let (_port, input) =
+            process.source_external_bincode::<_, u32, TotalOrder, ExactlyOnce>(&external);
+        let _output = input.map(q!(|x| x + 1)).send_bincode_external(&external);
+
+        let interface = flow.finalize().interface();
+        assert_eq!(1, interface.inputs.len());
+        // The source port also has an (empty) response stream.
+        assert_eq!(2, interface.outputs.len());
+        assert!(interface.outputs.values().any(|contract| matches!(
+            contract,
+            CollectionContract::Stream {
+                order: StreamOrder::TotalOrder,
+                element_type,
+                ..
+            } if element_type == "u32"
+        )));
+
+        let json = serde_json::to_string(&interface).unwrap();
+        assert_eq!(interface, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn named_ports_are_stable() {
+        let interface = |extra_port: bool| {
+            let mut flow = FlowBuilder::new();
+            let process = flow.process::<()>();
+            let external = flow.external::<()>();
+            if extra_port {
+                let _extra = process
+                    .source_iter(q!(["extra"]))
+                    .send_bincode_external(&external);
+            }
+            let numbers = process
+                .source_iter(q!([1u32, 2, 3]))
+                .send_bincode_external(&external);
+            external.name_port(&numbers, "numbers");
+            flow.finalize().interface()
+        };
+
+        let published = interface(false);
+        let (name, contract) = published
+            .outputs
+            .iter()
+            .find(|(name, _)| name.ends_with("/numbers"))
+            .unwrap();
+        // Adding a port before the named one does not rename it.
+        assert_eq!(Some(contract), interface(true).outputs.get(name));
+        assert!(interface(true).check_compatible(&published).is_empty());
+    }
+
+    #[test]
+    fn normalizes_types() {
+        let ty: syn::Type = syn::parse_quote! {
+            ::alloc::vec::Vec<(::core::option::Option<my_crate::__staged::Foo>, &'static str)>
+        };
+        assert_eq!(
+            "std::vec::Vec<(std::option::Option<my_crate::Foo>,&'static str)>",
+            normalize_type(&ty)
+        );
+
+        let ty: syn::Type = syn::parse_quote!(<T as Trait>::Assoc);
+        assert_eq!("<T as Trait>::Assoc", normalize_type(&ty));
+    }
+
+    fn stream(order: StreamOrder, retry: StreamRetry) -> CollectionContract {
+        CollectionContract::Stream {
+            bound: BoundKind::Unbounded,
+            order,
+            retry,
+            element_type: "u32".to_owned(),
+        }
+    }
+
+    #[test]
+    fn check_compatible() {
+        let published = FlowInterface {
+            inputs: BTreeMap::from([(
+                "in".to_owned(),
+                stream(StreamOrder::TotalOrder, StreamRetry::ExactlyOnce),
+            )]),
+            outputs: BTreeMap::from([
+                (
+                    "out".to_owned(),
+                    stream(StreamOrder::NoOrder, StreamRetry::ExactlyOnce),
+                ),
+                (
+                    "removed".to_owned(),
+                    stream(StreamOrder::NoOrder, StreamRetry::AtLeastOnce),
+                ),
+            ]),
+        };
+
+        // Inputs may relax what they rely on, and outputs may strengthen what they provide.
+        let compatible = FlowInterface {
+            inputs: BTreeMap::from([(
+                "in".to_owned(),
+                stream(StreamOrder::NoOrder, StreamRetry::AtLeastOnce),
+            )]),
+            outputs: BTreeMap::from([
+                (
+                    "out".to_owned(),
+                    stream(StreamOrder::TotalOrder, StreamRetry::ExactlyOnce),
+                ),
+                (
+                    "removed".to_owned(),
+                    stream(StreamOrder::NoOrder, StreamRetry::AtLeastOnce),
+                ),
+            ]),
+        };
+        assert!(compatible.check_compatible(&published).is_empty());
+
+        let incompatible = FlowInterface {
+            inputs: BTreeMap::new(),
+            outputs: BTreeMap::from([(
+                "out".to_owned(),
+                stream(StreamOrder::NoOrder, StreamRetry::AtLeastOnce),
+            )]),
+        };
+        assert_eq!(
+            vec![
+                "input `in`: port was removed",
+                "output `out`: retry changed from `ExactlyOnce` to `AtLeastOnce`",
+                "output `removed`: port was removed",
+            ],
+            incompatible
+                .check_compatible(&published)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+    }
+}
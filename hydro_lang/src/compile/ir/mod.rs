@@ -607,6 +607,20 @@ pub trait DfirBuilder {
         op_meta: &HydroIrOpMetadata,
     ) -> Option<syn::Ident>;
 
+    /// Wraps the accumulator of a fold (or the combinator of a reduce, if `is_reduce`) whose
+    /// operator carries [`ClaimedAlgebra`] so that the claimed properties are checked at runtime.
+    /// In production this returns the closure unchanged.
+    fn check_claimed_algebra(
+        &mut self,
+        acc: TokenStream,
+        _in_kind: &CollectionKind,
+        _out_kind: &CollectionKind,
+        _op_meta: &HydroIrOpMetadata,
+        _is_reduce: bool,
+    ) -> TokenStream {
+        acc
+    }
+
     /// Inserts necessary code to validate a manual assertion that at this point the
     /// input live collection is consistent. In production, this is a no-op, but in simulation
     /// this will (not yet implemented) inject assertions that validate consistency.
@@ -630,6 +644,36 @@ pub trait DfirBuilder {
         op_meta: &HydroIrOpMetadata,
     );
 
+    /// Emits the lookups of a [`HydroNode::MapAsyncCached`]. In production, each element is
+    /// looked up through an LRU cache that persists across ticks, and the lookups are resolved
+    /// concurrently, within the `limits` (an `AsyncCacheLimits` expression). Simulations instead resolve lookups deterministically, with `sim_resolver`
+    /// if one was provided.
+    #[expect(clippy::too_many_arguments, reason = "TODO")]
+    fn map_async_cached(
+        &mut self,
+        location: &LocationId,
+        in_ident: syn::Ident,
+        out_ident: &syn::Ident,
+        limits: &DebugExpr,
+        f: TokenStream,
+        sim_resolver: Option<&DebugExpr>,
+        operator_tag: Option<&str>,
+    );
+
+    /// Emits a [`HydroNode::RawDfir`] fragment, which reads from the `input` port and writes to
+    /// the `output` port of a DFIR module with the given element types.
+    #[expect(clippy::too_many_arguments, reason = "TODO")]
+    fn raw_dfir(
+        &mut self,
+        location: &LocationId,
+        in_ident: syn::Ident,
+        in_type: &DebugType,
+        out_ident: &syn::Ident,
+        out_type: &DebugType,
+        syntax: &str,
+        operator_tag: Option<&str>,
+    );
+
     fn create_versioned_network_fork(
         &mut self,
         channel_id: u32,
@@ -676,6 +720,42 @@ impl ProdDfirBuilder {
     }
 }
 
+/// Registers the fragment of a [`HydroNode::RawDfir`] as a module of `graph`, and invokes it on
+/// `in_ident`, binding its `output` port to `out_ident`.
+#[cfg(feature = "build")]
+pub(crate) fn add_raw_dfir(
+    graph: &mut FlatGraphBuilder,
+    in_ident: syn::Ident,
+    in_type: &DebugType,
+    out_ident: &syn::Ident,
+    out_type: &DebugType,
+    syntax: &str,
+    operator_tag: Option<&str>,
+) {
+    let module_path = format!("__hydro_raw_dfir_{}", out_ident);
+    let module_ident = syn::Ident::new(&module_path, Span::call_site());
+    let invocation_ident = syn::Ident::new(&format!("{}_module", out_ident), Span::call_site());
+    let syntax: TokenStream = syntax
+        .parse()
+        .expect("raw DFIR syntax is checked when it is added to the flow");
+
+    graph.add_module(
+        module_path,
+        quote! {
+            mod(in input: #in_type, out output: #out_type);
+            #syntax
+        },
+    );
+    graph.add_dfir(
+        parse_quote! {
+            #invocation_ident = use #module_ident(#in_ident);
+            #out_ident = #invocation_ident[output] -> identity();
+        },
+        None,
+        operator_tag,
+    );
+}
+
 #[cfg(feature = "build")]
 impl DfirBuilder for ProdDfirBuilder {
     fn singleton_intermediates(&self) -> bool {
@@ -981,6 +1061,53 @@ impl DfirBuilder for ProdDfirBuilder {
         );
     }
 
+    fn map_async_cached(
+        &mut self,
+        location: &LocationId,
+        in_ident: syn::Ident,
+        out_ident: &syn::Ident,
+        limits: &DebugExpr,
+        f: TokenStream,
+        _sim_resolver: Option<&DebugExpr>,
+        operator_tag: Option<&str>,
+    ) {
+        let root = crate::staging_util::get_this_crate();
+        let lifetime = self.cross_tick_state_lifetime(location);
+        self.graph_mut(location).add_dfir(
+            parse_quote! {
+                #out_ident = #in_ident
+                    -> scan::<#lifetime>(
+                        || #root::runtime_support::async_cache::AsyncCache::new(#limits, #f),
+                        |cache: &mut _, key| Some(cache.lookup(key)),
+                    )
+                    -> resolve_futures();
+            },
+            None,
+            operator_tag,
+        );
+    }
+
+    fn raw_dfir(
+        &mut self,
+        location: &LocationId,
+        in_ident: syn::Ident,
+        in_type: &DebugType,
+        out_ident: &syn::Ident,
+        out_type: &DebugType,
+        syntax: &str,
+        operator_tag: Option<&str>,
+    ) {
+        add_raw_dfir(
+            self.graph_mut(location),
+            in_ident,
+            in_type,
+            out_ident,
+            out_type,
+            syntax,
+            operator_tag,
+        );
+    }
+
     fn create_versioned_network_fork(
         &mut self,
         _channel_id: u32,
@@ -1283,6 +1410,7 @@ impl HydroRoot {
                     from_external_key,
                     from_port_id,
                     from_many,
+                    http_route,
                     codec_type,
                     port_hint,
                     instantiate_fn,
@@ -1319,7 +1447,14 @@ impl HydroRoot {
                                     (
                                         (
                                             parse_quote!(DUMMY),
-                                            if *from_many {
+                                            if let Some(route) = http_route {
+                                                D::e2o_http_source(
+                                                    refcell_extra_stmts.borrow_mut().entry(process_key).expect("location was removed").or_default(),
+                                                    &to_node, &source_port,
+                                                    route,
+                                                    format!("{}_{}", *from_external_key, *from_port_id)
+                                                )
+                                            } else if *from_many {
                                                 D::e2o_many_source(
                                                     refcell_extra_stmts.borrow_mut().entry(process_key).expect("location was removed").or_default(),
                                                     &to_node, &source_port,
@@ -2026,6 +2161,334 @@ pub fn unify_atomic_ticks(ir: &mut [HydroRoot]) {
     );
 }
 
+/// Number of frames of an operator's backtrace, starting from the user code which created it, that
+/// contribute to its [`HydroIrOpMetadata::stable_id`].
+#[cfg(feature = "build")]
+const STABLE_ID_FRAMES: usize = 3;
+
+/// Feeds `bytes` into a 64-bit FNV-1a hash. Unlike [`std::hash::DefaultHasher`], the result is
+/// guaranteed to be the same across Rust versions and platforms.
+#[cfg(feature = "build")]
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001B3)
+    })
+}
+
+/// The [`HydroIrOpMetadata::stable_id`]s of the inputs of `node`, including those behind shared
+/// nodes which [`HydroNode::input`] skips.
+#[cfg(feature = "build")]
+fn input_stable_ids(node: &HydroNode) -> Vec<Option<String>> {
+    match node {
+        HydroNode::Tee { inner, .. }
+        | HydroNode::Reference { inner, .. }
+        | HydroNode::Partition { inner, .. }
+        | HydroNode::VersionedNetwork { fork: inner, .. } => match &*inner.0.borrow() {
+            HydroNode::Placeholder => vec![None],
+            inner => vec![inner.op_metadata().stable_id.clone()],
+        },
+        _ => node
+            .input()
+            .into_iter()
+            .map(|input| input.op_metadata().stable_id.clone())
+            .collect(),
+    }
+}
+
+/// Computes the next [`HydroIrOpMetadata::stable_id`] for an operator printed as `printed` (see
+/// [`HydroNode::print_root`]) whose inputs have the IDs `inputs`, where `occurrences` counts the
+/// operators seen so far with the same content, to disambiguate them.
+#[cfg(feature = "build")]
+fn next_stable_id(
+    occurrences: &RefCell<HashMap<u64, usize>>,
+    printed: &str,
+    tag: Option<&str>,
+    op: &HydroIrOpMetadata,
+    inputs: &[Option<String>],
+) -> String {
+    // Only the kind of operator, not its (frequently edited) closures.
+    let kind = printed.split('(').next().unwrap_or(printed);
+    let mut hash = fnv1a(0xCBF29CE484222325, kind.as_bytes());
+    hash = fnv1a(hash, &[0xFF]);
+    hash = fnv1a(hash, tag.unwrap_or_default().as_bytes());
+    // Function names rather than line numbers, which shift whenever code above is edited.
+    for element in op.backtrace.elements().take(STABLE_ID_FRAMES) {
+        hash = fnv1a(hash, &[0xFF]);
+        hash = fnv1a(hash, element.fn_name.as_bytes());
+    }
+    // Operators of the same kind created by the same function are told apart by what they consume,
+    // so adding one does not shift the IDs of the others.
+    for input in inputs {
+        hash = fnv1a(hash, &[0xFE]);
+        hash = fnv1a(hash, input.as_deref().unwrap_or_default().as_bytes());
+    }
+
+    let mut occurrences = occurrences.borrow_mut();
+    let occurrence = occurrences.entry(hash).or_default();
+    *occurrence += 1;
+    format!("{:016x}", fnv1a(hash, &occurrence.to_le_bytes()))
+}
+
+/// Assigns a [`HydroIrOpMetadata::stable_id`] to each operator which does not have one yet.
+///
+/// An ID is derived from the kind, tag, and creating functions of the operator and the IDs of its
+/// inputs. Only operators which agree on all of these are disambiguated in the order they are
+/// reached by a traversal of the IR, so their IDs change only when such identical operators are
+/// added or reordered.
+#[cfg(feature = "build")]
+pub fn assign_stable_ids(ir: &mut [HydroRoot]) {
+    let occurrences = RefCell::new(HashMap::new());
+    transform_bottom_up(
+        ir,
+        &mut |root: &mut HydroRoot| {
+            if root.op_metadata().stable_id.is_none() {
+                let inputs = [root.input().op_metadata().stable_id.clone()];
+                let stable_id = next_stable_id(
+                    &occurrences,
+                    &root.print_root(),
+                    None,
+                    root.op_metadata(),
+                    &inputs,
+                );
+                root.op_metadata_mut().stable_id = Some(stable_id);
+            }
+        },
+        &mut |node: &mut HydroNode| {
+            if node.op_metadata().stable_id.is_none() {
+                let metadata = node.metadata();
+                let stable_id = next_stable_id(
+                    &occurrences,
+                    &node.print_root(),
+                    metadata.tag.as_deref(),
+                    &metadata.op,
+                    &input_stable_ids(node),
+                );
+                node.op_metadata_mut().stable_id = Some(stable_id);
+            }
+        },
+        false,
+    );
+}
+
+/// The closures of `node`, whose singleton references are read by it.
+#[cfg(feature = "build")]
+fn node_closures(node: &HydroNode) -> Vec<&ClosureExpr> {
+    match node {
+        HydroNode::Map { f, .. }
+        | HydroNode::FlatMap { f, .. }
+        | HydroNode::FlatMapStreamBlocking { f, .. }
+        | HydroNode::MapAsyncCached { f, .. }
+        | HydroNode::Filter { f, .. }
+        | HydroNode::FilterMap { f, .. }
+        | HydroNode::Inspect { f, .. }
+        | HydroNode::Reduce { f, .. }
+        | HydroNode::ReduceKeyed { f, .. }
+        | HydroNode::ReduceKeyedWatermark { f, .. }
+        | HydroNode::Partition { f, .. } => vec![f],
+        HydroNode::Fold { init, acc, .. }
+        | HydroNode::Scan { init, acc, .. }
+        | HydroNode::ScanAsyncBlocking { init, acc, .. }
+        | HydroNode::FoldKeyed { init, acc, .. } => vec![init, acc],
+        _ => vec![],
+    }
+}
+
+/// Calls `visit` on `node` and every operator upstream of it, including the singletons referenced
+/// by its closures. Shared operators already in `seen` are skipped.
+#[cfg(feature = "build")]
+fn visit_upstream(
+    node: &HydroNode,
+    seen: &mut HashSet<*const RefCell<HydroNode>>,
+    visit: &mut impl FnMut(&HydroNode),
+) {
+    visit(node);
+    for closure in node_closures(node) {
+        for (ref_node, _is_mut) in &closure.singleton_refs {
+            visit_upstream(ref_node, seen, visit);
+        }
+    }
+
+    match node {
+        HydroNode::Tee { inner, .. }
+        | HydroNode::Reference { inner, .. }
+        | HydroNode::Partition { inner, .. }
+        | HydroNode::VersionedNetwork { fork: inner, .. } => {
+            if seen.insert(inner.as_ptr()) {
+                visit_upstream(&inner.0.borrow(), seen, visit);
+            }
+        }
+        _ => {
+            for input in node.input() {
+                visit_upstream(input, seen, visit);
+            }
+        }
+    }
+}
+
+/// Whether `node` has an effect beyond producing its output, so that it must be kept even if its
+/// output is never consumed.
+///
+/// This is conservative: sources fed from outside the program, asynchronous operators, and
+/// operators whose output is also read elsewhere (partitions and references) are all treated as
+/// side effects.
+#[cfg(feature = "build")]
+fn has_side_effects(node: &HydroNode) -> bool {
+    match node {
+        HydroNode::Source { source, .. } => !matches!(
+            source,
+            HydroSource::Iter(_) | HydroSource::Spin() | HydroSource::ClusterMembers(..)
+        ),
+        HydroNode::Inspect { .. }
+        | HydroNode::ExternalInput { .. }
+        | HydroNode::RawDfir { .. }
+        | HydroNode::MapAsyncCached { .. }
+        | HydroNode::ResolveFutures { .. }
+        | HydroNode::ResolveFuturesBlocking { .. }
+        | HydroNode::ResolveFuturesOrdered { .. }
+        | HydroNode::FlatMapStreamBlocking { .. }
+        | HydroNode::ScanAsyncBlocking { .. }
+        | HydroNode::AssertIsConsistent { .. }
+        | HydroNode::Partition { .. }
+        | HydroNode::Reference { .. }
+        | HydroNode::VersionedNetworkFork { .. }
+        | HydroNode::VersionedNetwork { .. } => true,
+        _ => node_closures(node)
+            .iter()
+            .any(|closure| closure.has_mut_ref()),
+    }
+}
+
+/// Removes the pipelines whose outputs are never consumed, returning the last operator of each
+/// removed pipeline.
+///
+/// A pipeline is unused if it ends in a [`HydroRoot::Null`] (created for live collections which
+/// were dropped) and has no side effects upstream, such as an `inspect` or a source fed from
+/// outside the program. Operators shared with other pipelines are kept for those pipelines. Cycles
+/// which are no longer read from are drained instead, so that the pipelines feeding them can be
+/// removed as well.
+#[cfg(feature = "build")]
+pub fn eliminate_dead_code(ir: &mut Vec<HydroRoot>) -> Vec<HydroNode> {
+    let mut removed = vec![];
+    loop {
+        let (dead, live): (Vec<_>, Vec<_>) = std::mem::take(ir).into_iter().partition(|root| {
+            let HydroRoot::Null { input, .. } = root else {
+                return false;
+            };
+            let mut side_effects = false;
+            visit_upstream(input, &mut HashSet::new(), &mut |node| {
+                side_effects |= has_side_effects(node);
+            });
+            !side_effects
+        });
+        *ir = live;
+
+        let mut read_cycles = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut visit = |node: &HydroNode| {
+            if let HydroNode::CycleSource { cycle_id, .. } = node {
+                read_cycles.insert(*cycle_id);
+            }
+        };
+        for root in ir.iter() {
+            if let HydroRoot::ForEach { f, .. } = root {
+                for (ref_node, _is_mut) in &f.singleton_refs {
+                    visit_upstream(ref_node, &mut seen, &mut visit);
+                }
+            }
+            visit_upstream(root.input(), &mut seen, &mut visit);
+        }
+
+        let mut drained_cycles = false;
+        *ir = std::mem::take(ir)
+            .into_iter()
+            .map(|root| match root {
+                HydroRoot::CycleSink {
+                    cycle_id,
+                    input,
+                    op_metadata,
+                } if !read_cycles.contains(&cycle_id) => {
+                    drained_cycles = true;
+                    HydroRoot::Null { input, op_metadata }
+                }
+                root => root,
+            })
+            .collect();
+
+        if dead.is_empty() && !drained_cycles {
+            return removed;
+        }
+        removed.extend(dead.into_iter().map(|root| match root {
+            HydroRoot::Null { input, .. } => *input,
+            _ => unreachable!(),
+        }));
+    }
+}
+
+/// The processes and clusters which have operators placed at them, or whose members are read.
+#[cfg(feature = "build")]
+pub fn used_locations(ir: &[HydroRoot]) -> HashSet<LocationKey> {
+    let mut used = HashSet::new();
+    let mut seen = HashSet::new();
+    for root in ir {
+        visit_upstream(root.input(), &mut seen, &mut |node| {
+            used.insert(node.metadata().location_id.root().key());
+            if let HydroNode::Source {
+                source: HydroSource::ClusterMembers(location_id, _),
+                ..
+            } = node
+            {
+                used.insert(location_id.root().key());
+            }
+        });
+    }
+    used
+}
+
+/// Wraps the serialization of every network channel which serializes within Hydro, so that each
+/// message carries the trace context of its sender. See [`crate::telemetry::network_tracing`].
+#[cfg(feature = "build")]
+pub fn add_network_tracing(ir: &mut [HydroRoot]) {
+    let root = crate::staging_util::get_this_crate();
+    transform_bottom_up(
+        ir,
+        &mut |_| {},
+        &mut |node: &mut HydroNode| {
+            if let HydroNode::Network {
+                name,
+                serialize:
+                    NetworkSend::Custom {
+                        serialize_fn: Some(serialize_fn),
+                    },
+                deserialize:
+                    NetworkRecv::Custom {
+                        deserialize_fn: Some(deserialize_fn),
+                    },
+                ..
+            } = node
+            {
+                let channel = name.as_deref().unwrap_or("network");
+                let serialize: &syn::Expr = serialize_fn;
+                let deserialize: &syn::Expr = deserialize_fn;
+                let traced_serialize: syn::Expr = parse_quote! {
+                    {
+                        let __hydro_serialize = #serialize;
+                        move |data| #root::telemetry::network_tracing::inject(#channel, __hydro_serialize(data))
+                    }
+                };
+                let traced_deserialize: syn::Expr = parse_quote! {
+                    {
+                        let __hydro_deserialize = #deserialize;
+                        move |res| __hydro_deserialize(#root::telemetry::network_tracing::extract(#channel, res))
+                    }
+                };
+                *serialize_fn = traced_serialize.into();
+                *deserialize_fn = traced_deserialize.into();
+            }
+        },
+        false,
+    );
+}
+
 #[cfg(feature = "build")]
 pub fn emit(ir: &mut Vec<HydroRoot>) -> SecondaryMap<LocationKey, FlatGraphBuilder> {
     let mut builders = ProdDfirBuilder::default();
@@ -2308,25 +2771,25 @@ impl serde::Serialize for AccessCounter {
     }
 }
 
-#[derive(serde::Serialize, Clone, PartialEq, Eq, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
 pub enum BoundKind {
     Unbounded,
     Bounded,
 }
 
-#[derive(serde::Serialize, Clone, PartialEq, Eq, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
 pub enum StreamOrder {
     NoOrder,
     TotalOrder,
 }
 
-#[derive(serde::Serialize, Clone, PartialEq, Eq, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
 pub enum StreamRetry {
     AtLeastOnce,
     ExactlyOnce,
 }
 
-#[derive(serde::Serialize, Clone, PartialEq, Eq, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
 pub enum KeyedSingletonBoundKind {
     Unbounded,
     MonotonicKeys,
@@ -2335,7 +2798,7 @@ pub enum KeyedSingletonBoundKind {
     Bounded,
 }
 
-#[derive(serde::Serialize, Clone, PartialEq, Eq, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
 pub enum SingletonBoundKind {
     Unbounded,
     Monotonic,
@@ -2488,6 +2951,16 @@ pub struct HydroIrOpMetadata {
     pub cpu_usage: Option<f64>,
     pub network_recv_cpu_usage: Option<f64>,
     pub id: Option<usize>,
+    /// A content-based ID, derived from the kind of operator, the user code which created it, and
+    /// its user-provided tag (name). Unlike [`Self::id`], which depends on the position of the
+    /// operator in the IR, this is preserved when unrelated parts of the program are edited, so it
+    /// can be used to match up operators (and their metrics) across versions of a program.
+    ///
+    /// Assigned by [`assign_stable_ids`] when the flow is finalized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stable_id: Option<String>,
+    #[serde(skip_serializing_if = "ClaimedAlgebra::is_empty")]
+    pub claimed_algebra: ClaimedAlgebra,
 }
 
 impl HydroIrOpMetadata {
@@ -2505,10 +2978,32 @@ impl HydroIrOpMetadata {
             cpu_usage: None,
             network_recv_cpu_usage: None,
             id: None,
+            stable_id: None,
+            claimed_algebra: ClaimedAlgebra::default(),
         }
     }
 }
 
+/// Algebraic properties of an operator's closure that were asserted by the user with a
+/// [`manual_proof!`](crate::properties::manual_proof), e.g. `commutative = manual_proof!(...)` on
+/// [`Stream::fold`](crate::live_collections::stream::Stream::fold).
+///
+/// In production these are purely informational, but when checking manual proofs the simulator
+/// actively tries to falsify each claim by replaying inputs in a different order or with
+/// duplicates, and fails with the offending pair of inputs as a counterexample.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, serde::Serialize)]
+pub struct ClaimedAlgebra {
+    pub commutative: bool,
+    pub associative: bool,
+    pub idempotent: bool,
+}
+
+impl ClaimedAlgebra {
+    pub fn is_empty(&self) -> bool {
+        !(self.commutative || self.associative || self.idempotent)
+    }
+}
+
 impl Debug for HydroIrOpMetadata {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("HydroIrOpMetadata").finish()
@@ -2519,6 +3014,21 @@ impl Hash for HydroIrOpMetadata {
     fn hash<H: Hasher>(&self, _: &mut H) {}
 }
 
+/// How a [`HydroNode::Counter`] reports the elements passing through it.
+#[derive(Debug, Clone, Hash, serde::Serialize)]
+pub enum CounterKind {
+    /// Prints the number of elements seen so far every `duration`, as `{prefix}({tag}): {count}`.
+    /// Lowered to the `_counter` DFIR operator.
+    Print { duration: DebugExpr, prefix: String },
+    /// Records throughput and latency histograms under the counter's tag into the
+    /// [`crate::telemetry::meter`] registry. Created by
+    /// [`Stream::metered`](crate::live_collections::stream::Stream::metered).
+    Metered,
+    /// Records that the operator tagged `tag` output an element, so that an interactive
+    /// simulation can stop at a breakpoint on it. Only inserted when compiling simulations.
+    Breakpoint,
+}
+
 /// How a network channel's *sender* prepares each message before it is handed to the transport.
 ///
 /// A channel's serialization is split into a send half ([`NetworkSend`]) and a receive half
@@ -2795,6 +3305,27 @@ pub enum HydroNode {
         input: Box<HydroNode>,
         metadata: HydroIrMetadata,
     },
+    /// Splices a user-provided DFIR fragment into the graph, which reads the input stream from
+    /// `mod[input]` and writes the output stream to `[output]mod` (see
+    /// [`Stream::raw_dfir`](crate::live_collections::stream::Stream::raw_dfir)).
+    ///
+    /// The output is always an unordered stream, which is strengthened by an
+    /// [`HydroNode::ObserveNonDet`] when an ordering is assumed, so that the simulator explores
+    /// its possible interpretations.
+    RawDfir {
+        syntax: String,
+        input: Box<HydroNode>,
+        metadata: HydroIrMetadata,
+    },
+    /// Maps each element through an asynchronous function, whose results are memoized in an LRU
+    /// cache within the given `AsyncCacheLimits` (see [`DfirBuilder::map_async_cached`]).
+    MapAsyncCached {
+        limits: DebugExpr,
+        f: ClosureExpr,
+        sim_resolver: Option<DebugExpr>,
+        input: Box<HydroNode>,
+        metadata: HydroIrMetadata,
+    },
 
     Map {
         f: ClosureExpr,
@@ -2840,6 +3371,13 @@ pub enum HydroNode {
         input: Box<HydroNode>,
         metadata: HydroIrMetadata,
     },
+    /// Like [`HydroNode::Unique`], but only remembers the values in a bounded window, built by
+    /// `window` (a `WindowedUnique`), evicting older values.
+    UniqueWithin {
+        window: DebugExpr,
+        input: Box<HydroNode>,
+        metadata: HydroIrMetadata,
+    },
 
     Sort {
         input: Box<HydroNode>,
@@ -2852,6 +3390,16 @@ pub enum HydroNode {
         metadata: HydroIrMetadata,
     },
 
+    /// Merges a stream of lattice values into a single accumulated value, starting from
+    /// `Default::default()` and combining with `lattices::Merge::merge`.
+    ///
+    /// Because merges are associative, commutative, and idempotent, the input may be
+    /// unordered and contain duplicates.
+    LatticeFold {
+        input: Box<HydroNode>,
+        metadata: HydroIrMetadata,
+    },
+
     Scan {
         init: ClosureExpr,
         acc: ClosureExpr,
@@ -2916,6 +3464,9 @@ pub enum HydroNode {
         from_external_key: LocationKey,
         from_port_id: ExternalPortId,
         from_many: bool,
+        /// If set, the input is served as an HTTP endpoint at this route, instead of with
+        /// `codec_type`.
+        http_route: Option<String>,
         codec_type: DebugType,
         #[serde(skip)]
         port_hint: NetworkHint,
@@ -2926,8 +3477,7 @@ pub enum HydroNode {
 
     Counter {
         tag: String,
-        duration: DebugExpr,
-        prefix: String,
+        kind: CounterKind,
         input: Box<HydroNode>,
         metadata: HydroIrMetadata,
     },
@@ -3100,6 +3650,7 @@ impl HydroNode {
             }
             HydroNode::FlatMap { f, input, .. }
             | HydroNode::FlatMapStreamBlocking { f, input, .. }
+            | HydroNode::MapAsyncCached { f, input, .. }
             | HydroNode::Filter { f, input, .. }
             | HydroNode::FilterMap { f, input, .. }
             | HydroNode::Inspect { f, input, .. }
@@ -3141,6 +3692,8 @@ impl HydroNode {
             | HydroNode::DeferTick { input, .. }
             | HydroNode::Enumerate { input, .. }
             | HydroNode::Unique { input, .. }
+            | HydroNode::UniqueWithin { input, .. }
+            | HydroNode::LatticeFold { input, .. }
             | HydroNode::Network { input, .. }
             | HydroNode::Counter { input, .. } => {
                 transform(input.as_mut(), seen_tees);
@@ -3393,6 +3946,28 @@ impl HydroNode {
                     metadata: metadata.clone(),
                 }
             }
+            HydroNode::RawDfir {
+                syntax,
+                input,
+                metadata,
+            } => HydroNode::RawDfir {
+                syntax: syntax.clone(),
+                input: Box::new(input.deep_clone(seen_tees)),
+                metadata: metadata.clone(),
+            },
+            HydroNode::MapAsyncCached {
+                limits,
+                f,
+                sim_resolver,
+                input,
+                metadata,
+            } => HydroNode::MapAsyncCached {
+                limits: limits.clone(),
+                f: f.deep_clone(seen_tees),
+                sim_resolver: sim_resolver.clone(),
+                input: Box::new(input.deep_clone(seen_tees)),
+                metadata: metadata.clone(),
+            },
             HydroNode::Filter { f, input, metadata } => HydroNode::Filter {
                 f: f.deep_clone(seen_tees),
                 input: Box::new(input.deep_clone(seen_tees)),
@@ -3420,10 +3995,23 @@ impl HydroNode {
                 input: Box::new(input.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
+            HydroNode::UniqueWithin {
+                window,
+                input,
+                metadata,
+            } => HydroNode::UniqueWithin {
+                window: window.clone(),
+                input: Box::new(input.deep_clone(seen_tees)),
+                metadata: metadata.clone(),
+            },
             HydroNode::Sort { input, metadata } => HydroNode::Sort {
                 input: Box::new(input.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
+            HydroNode::LatticeFold { input, metadata } => HydroNode::LatticeFold {
+                input: Box::new(input.deep_clone(seen_tees)),
+                metadata: metadata.clone(),
+            },
             HydroNode::Fold {
                 init,
                 acc,
@@ -3510,6 +4098,7 @@ impl HydroNode {
                 from_external_key,
                 from_port_id,
                 from_many,
+                http_route,
                 codec_type,
                 port_hint,
                 instantiate_fn,
@@ -3519,6 +4108,7 @@ impl HydroNode {
                 from_external_key: *from_external_key,
                 from_port_id: *from_port_id,
                 from_many: *from_many,
+                http_route: http_route.clone(),
                 codec_type: codec_type.clone(),
                 port_hint: *port_hint,
                 instantiate_fn: instantiate_fn.clone(),
@@ -3527,14 +4117,12 @@ impl HydroNode {
             },
             HydroNode::Counter {
                 tag,
-                duration,
-                prefix,
+                kind,
                 input,
                 metadata,
             } => HydroNode::Counter {
                 tag: tag.clone(),
-                duration: duration.clone(),
-                prefix: prefix.clone(),
+                kind: kind.clone(),
                 input: Box::new(input.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
@@ -4605,6 +5193,85 @@ impl HydroNode {
                         ident_stack.push(flat_map_ident);
                     }
 
+                    HydroNode::RawDfir {
+                        syntax,
+                        input,
+                        metadata,
+                    } => {
+                        let input_ident = ident_stack.pop().unwrap();
+
+                        let stmt_id = next_stmt_id.get_and_increment();
+                        let raw_dfir_ident =
+                            syn::Ident::new(&format!("stream_{}", stmt_id), Span::call_site());
+
+                        match builders_or_callback {
+                            BuildersOrCallback::Builders(graph_builders) => {
+                                let CollectionKind::Stream {
+                                    element_type: in_type,
+                                    ..
+                                } = &input.metadata().collection_kind
+                                else {
+                                    panic!("raw DFIR input must be a stream");
+                                };
+                                let CollectionKind::Stream {
+                                    element_type: out_type,
+                                    ..
+                                } = &metadata.collection_kind
+                                else {
+                                    panic!("raw DFIR output must be a stream");
+                                };
+
+                                graph_builders.raw_dfir(
+                                    &out_location,
+                                    input_ident,
+                                    in_type,
+                                    &raw_dfir_ident,
+                                    out_type,
+                                    syntax,
+                                    Some(&stmt_id.to_string()),
+                                );
+                            }
+                            BuildersOrCallback::Callback(_, node_callback) => {
+                                node_callback(node, next_stmt_id);
+                            }
+                        }
+
+                        ident_stack.push(raw_dfir_ident);
+                    }
+
+                    HydroNode::MapAsyncCached {
+                        limits,
+                        f,
+                        sim_resolver,
+                        ..
+                    } => {
+                        let input_ident = ident_stack.pop().unwrap();
+                        let f_tokens = f.emit_tokens(&mut ident_stack);
+
+                        let stmt_id = next_stmt_id.get_and_increment();
+                        let map_async_ident =
+                            syn::Ident::new(&format!("stream_{}", stmt_id), Span::call_site());
+
+                        match builders_or_callback {
+                            BuildersOrCallback::Builders(graph_builders) => {
+                                graph_builders.map_async_cached(
+                                    &out_location,
+                                    input_ident,
+                                    &map_async_ident,
+                                    limits,
+                                    f_tokens,
+                                    sim_resolver.as_ref(),
+                                    Some(&stmt_id.to_string()),
+                                );
+                            }
+                            BuildersOrCallback::Callback(_, node_callback) => {
+                                node_callback(node, next_stmt_id);
+                            }
+                        }
+
+                        ident_stack.push(map_async_ident);
+                    }
+
                     HydroNode::FlatMapStreamBlocking { f, input, metadata } => {
                         let input_ident = ident_stack.pop().unwrap();
                         let f_tokens = f.emit_tokens(&mut ident_stack);
@@ -4852,8 +5519,44 @@ impl HydroNode {
                         ident_stack.push(unique_ident);
                     }
 
-                    HydroNode::Fold { .. } | HydroNode::FoldKeyed { .. } | HydroNode::Scan { .. } | HydroNode::ScanAsyncBlocking { .. } => {
-                        let operator: syn::Ident = if let HydroNode::Fold { input, .. } = node {
+                    HydroNode::UniqueWithin { window, input, .. } => {
+                        let input_ident = ident_stack.pop().unwrap();
+
+                        let stmt_id = next_stmt_id.get_and_increment();
+                        let unique_ident =
+                            syn::Ident::new(&format!("stream_{}", stmt_id), Span::call_site());
+
+                        match builders_or_callback {
+                            BuildersOrCallback::Builders(graph_builders) => {
+                                let lifetime = if input.metadata().location_id.is_top_level() {
+                                    graph_builders.cross_tick_state_lifetime(&out_location)
+                                } else {
+                                    graph_builders.tick_state_lifetime(&out_location)
+                                };
+
+                                graph_builders.add_dfir_at(
+                                    &out_location,
+                                    parse_quote! {
+                                        #unique_ident = #input_ident
+                                            -> scan::<#lifetime>(
+                                                || #window,
+                                                |seen: &mut _, item| Some(seen.insert(&item).then_some(item)),
+                                            )
+                                            -> flatten();
+                                    },
+                                    Some(&stmt_id.to_string()),
+                                );
+                            }
+                            BuildersOrCallback::Callback(_, node_callback) => {
+                                node_callback(node, next_stmt_id);
+                            }
+                        }
+
+                        ident_stack.push(unique_ident);
+                    }
+
+                    HydroNode::Fold { .. } | HydroNode::LatticeFold { .. } | HydroNode::FoldKeyed { .. } | HydroNode::Scan { .. } | HydroNode::ScanAsyncBlocking { .. } => {
+                        let operator: syn::Ident = if let HydroNode::Fold { input, .. } | HydroNode::LatticeFold { input, .. } = node {
                             if input.metadata().location_id.is_top_level()
                                 && input.metadata().collection_kind.is_bounded()
                             {
@@ -4878,6 +5581,7 @@ impl HydroNode {
                         };
 
                         let (HydroNode::Fold { input, .. }
+                        | HydroNode::LatticeFold { input, .. }
                         | HydroNode::FoldKeyed { input, .. }
                         | HydroNode::Scan { input, .. }
                         | HydroNode::ScanAsyncBlocking { input, .. }) = node
@@ -4889,16 +5593,45 @@ impl HydroNode {
 
                         let input_ident = ident_stack.pop().unwrap();
 
-                        let (HydroNode::Fold { init, acc, .. }
-                        | HydroNode::FoldKeyed { init, acc, .. }
-                        | HydroNode::Scan { init, acc, .. }
-                        | HydroNode::ScanAsyncBlocking { init, acc, .. }) = &*node
-                        else {
-                            unreachable!()
+                        let (mut acc_tokens, init_tokens) = if let HydroNode::LatticeFold { metadata, .. } = &*node {
+                            let CollectionKind::Singleton { element_type, .. } = &metadata.collection_kind else {
+                                panic!("LatticeFold must produce a singleton");
+                            };
+                            let root = crate::staging_util::get_this_crate();
+                            (
+                                quote! {
+                                    |__acc: &mut #element_type, __value| {
+                                        #root::runtime_support::dfir_rs::lattices::Merge::merge(__acc, __value);
+                                    }
+                                },
+                                quote!(|| -> #element_type { ::std::default::Default::default() }),
+                            )
+                        } else {
+                            let (HydroNode::Fold { init, acc, .. }
+                            | HydroNode::FoldKeyed { init, acc, .. }
+                            | HydroNode::Scan { init, acc, .. }
+                            | HydroNode::ScanAsyncBlocking { init, acc, .. }) = &*node
+                            else {
+                                unreachable!()
+                            };
+
+                            let acc_tokens = acc.emit_tokens(&mut ident_stack);
+                            let init_tokens = init.emit_tokens(&mut ident_stack);
+                            (acc_tokens, init_tokens)
                         };
 
-                        let acc_tokens = acc.emit_tokens(&mut ident_stack);
-                        let init_tokens = init.emit_tokens(&mut ident_stack);
+                        if let HydroNode::Fold { input, metadata, .. } = &*node
+                            && !metadata.op.claimed_algebra.is_empty()
+                            && let BuildersOrCallback::Builders(graph_builders) = builders_or_callback
+                        {
+                            acc_tokens = graph_builders.check_claimed_algebra(
+                                acc_tokens,
+                                &input.metadata().collection_kind,
+                                &metadata.collection_kind,
+                                &metadata.op,
+                                false,
+                            );
+                        }
 
                         let stmt_id = next_stmt_id.get_and_increment();
                         let fold_ident =
@@ -4912,13 +5645,13 @@ impl HydroNode {
                                     graph_builders.tick_state_lifetime(&out_location)
                                 };
 
-                                if matches!(node, HydroNode::Fold { .. })
+                                if matches!(node, HydroNode::Fold { .. } | HydroNode::LatticeFold { .. })
                                     && node.metadata().location_id.is_top_level()
                                     && !(matches!(node.metadata().location_id, LocationId::Atomic(_)))
                                     && graph_builders.singleton_intermediates()
                                     && !node.metadata().collection_kind.is_bounded()
                                 {
-                                    let HydroNode::Fold { input, .. } = &*node else { unreachable!() };
+                                    let (HydroNode::Fold { input, .. } | HydroNode::LatticeFold { input, .. }) = &*node else { unreachable!() };
                                     let hooked_input_ident = graph_builders.emit_fold_hook(
                                         &input.metadata().location_id,
                                         &input_ident,
@@ -5010,13 +5743,13 @@ impl HydroNode {
                                             Some(&stmt_id.to_string()),
                                         );
                                     }
-                                } else if (matches!(node, HydroNode::Fold { .. })
+                                } else if (matches!(node, HydroNode::Fold { .. } | HydroNode::LatticeFold { .. })
                                     || matches!(node, HydroNode::FoldKeyed { .. }))
                                     && !node.metadata().location_id.is_top_level()
                                     && graph_builders.singleton_intermediates()
                                 {
                                     let input_ref = match &*node {
-                                        HydroNode::Fold { input, .. } => input,
+                                        HydroNode::Fold { input, .. } | HydroNode::LatticeFold { input, .. } => input,
                                         HydroNode::FoldKeyed { input, .. } => input,
                                         _ => unreachable!(),
                                     };
@@ -5090,7 +5823,20 @@ impl HydroNode {
                             unreachable!()
                         };
 
-                        let f_tokens = f.emit_tokens(&mut ident_stack);
+                        let mut f_tokens = f.emit_tokens(&mut ident_stack);
+
+                        if let HydroNode::Reduce { input, metadata, .. } = &*node
+                            && !metadata.op.claimed_algebra.is_empty()
+                            && let BuildersOrCallback::Builders(graph_builders) = builders_or_callback
+                        {
+                            f_tokens = graph_builders.check_claimed_algebra(
+                                f_tokens,
+                                &input.metadata().collection_kind,
+                                &metadata.collection_kind,
+                                &metadata.op,
+                                true,
+                            );
+                        }
 
                         let stmt_id = next_stmt_id.get_and_increment();
                         let reduce_ident =
@@ -5336,8 +6082,8 @@ impl HydroNode {
 
                     HydroNode::Counter {
                         tag,
-                        duration,
-                        prefix,
+                        kind,
+                        metadata,
                         ..
                     } => {
                         let input_ident = ident_stack.pop().unwrap();
@@ -5348,12 +6094,38 @@ impl HydroNode {
 
                         match builders_or_callback {
                             BuildersOrCallback::Builders(graph_builders) => {
-                                let arg = format!("{}({})", prefix, tag);
+                                let pipeline: dfir_lang::parse::DfirCode = match kind {
+                                    CounterKind::Print { duration, prefix } => {
+                                        let arg = format!("{}({})", prefix, tag);
+                                        parse_quote! {
+                                            #counter_ident = #input_ident -> _counter(#arg, #duration);
+                                        }
+                                    }
+                                    CounterKind::Metered => {
+                                        let root = crate::staging_util::get_this_crate();
+                                        let stable_id = match &metadata.op.stable_id {
+                                            Some(stable_id) => quote!(Some(#stable_id)),
+                                            None => quote!(None),
+                                        };
+                                        parse_quote! {
+                                            #counter_ident = #input_ident -> inspect({
+                                                // Registered once, rather than in every tick.
+                                                static __METER: ::std::sync::OnceLock<#root::telemetry::meter::Meter> = ::std::sync::OnceLock::new();
+                                                let __meter = __METER.get_or_init(|| #root::telemetry::meter::register(#tag, #stable_id));
+                                                move |_| __meter.record(context.current_tick().0)
+                                            });
+                                        }
+                                    }
+                                    CounterKind::Breakpoint => {
+                                        let root = crate::staging_util::get_this_crate();
+                                        parse_quote! {
+                                            #counter_ident = #input_ident -> inspect(|_| #root::sim::runtime::hit_breakpoint(#tag));
+                                        }
+                                    }
+                                };
                                 graph_builders.add_dfir_at(
                                     &out_location,
-                                    parse_quote! {
-                                        #counter_ident = #input_ident -> _counter(#arg, #duration);
-                                    },
+                                    pipeline,
                                     Some(&stmt_id.to_string()),
                                 );
                             }
@@ -5487,7 +6259,8 @@ impl HydroNode {
             HydroNode::Cast { .. }
             | HydroNode::ObserveNonDet { .. }
             | HydroNode::UnboundSingleton { .. }
-            | HydroNode::AssertIsConsistent { .. } => {}
+            | HydroNode::AssertIsConsistent { .. }
+            | HydroNode::RawDfir { .. } => {}
             HydroNode::Source { source, .. } => match source {
                 HydroSource::Stream(expr) | HydroSource::Iter(expr) => transform(expr),
                 HydroSource::ExternalNetwork()
@@ -5522,8 +6295,24 @@ impl HydroNode {
             | HydroNode::Enumerate { .. }
             | HydroNode::Unique { .. }
             | HydroNode::Sort { .. }
+            | HydroNode::LatticeFold { .. }
             | HydroNode::VersionedNetworkFork { .. }
             | HydroNode::VersionedNetwork { .. } => {}
+            HydroNode::UniqueWithin { window, .. } => {
+                transform(window);
+            }
+            HydroNode::MapAsyncCached {
+                limits,
+                f,
+                sim_resolver,
+                ..
+            } => {
+                transform(limits);
+                transform(&mut f.expr);
+                if let Some(sim_resolver) = sim_resolver {
+                    transform(sim_resolver);
+                }
+            }
             HydroNode::Map { f, .. }
             | HydroNode::FlatMap { f, .. }
             | HydroNode::FlatMapStreamBlocking { f, .. }
@@ -5566,8 +6355,10 @@ impl HydroNode {
                     transform(deserialize_fn);
                 }
             }
-            HydroNode::Counter { duration, .. } => {
-                transform(duration);
+            HydroNode::Counter { kind, .. } => {
+                if let CounterKind::Print { duration, .. } = kind {
+                    transform(duration);
+                }
             }
         }
     }
@@ -5612,16 +6403,20 @@ impl HydroNode {
             | HydroNode::Map { metadata, .. }
             | HydroNode::FlatMap { metadata, .. }
             | HydroNode::FlatMapStreamBlocking { metadata, .. }
+            | HydroNode::MapAsyncCached { metadata, .. }
+            | HydroNode::RawDfir { metadata, .. }
             | HydroNode::Filter { metadata, .. }
             | HydroNode::FilterMap { metadata, .. }
             | HydroNode::DeferTick { metadata, .. }
             | HydroNode::Enumerate { metadata, .. }
             | HydroNode::Inspect { metadata, .. }
             | HydroNode::Unique { metadata, .. }
+            | HydroNode::UniqueWithin { metadata, .. }
             | HydroNode::Sort { metadata, .. }
             | HydroNode::Scan { metadata, .. }
             | HydroNode::ScanAsyncBlocking { metadata, .. }
             | HydroNode::Fold { metadata, .. }
+            | HydroNode::LatticeFold { metadata, .. }
             | HydroNode::FoldKeyed { metadata, .. }
             | HydroNode::Reduce { metadata, .. }
             | HydroNode::ReduceKeyed { metadata, .. }
@@ -5672,16 +6467,20 @@ impl HydroNode {
             | HydroNode::Map { metadata, .. }
             | HydroNode::FlatMap { metadata, .. }
             | HydroNode::FlatMapStreamBlocking { metadata, .. }
+            | HydroNode::MapAsyncCached { metadata, .. }
+            | HydroNode::RawDfir { metadata, .. }
             | HydroNode::Filter { metadata, .. }
             | HydroNode::FilterMap { metadata, .. }
             | HydroNode::DeferTick { metadata, .. }
             | HydroNode::Enumerate { metadata, .. }
             | HydroNode::Inspect { metadata, .. }
             | HydroNode::Unique { metadata, .. }
+            | HydroNode::UniqueWithin { metadata, .. }
             | HydroNode::Sort { metadata, .. }
             | HydroNode::Scan { metadata, .. }
             | HydroNode::ScanAsyncBlocking { metadata, .. }
             | HydroNode::Fold { metadata, .. }
+            | HydroNode::LatticeFold { metadata, .. }
             | HydroNode::FoldKeyed { metadata, .. }
             | HydroNode::Reduce { metadata, .. }
             | HydroNode::ReduceKeyed { metadata, .. }
@@ -5739,6 +6538,8 @@ impl HydroNode {
             HydroNode::Map { input, .. }
             | HydroNode::FlatMap { input, .. }
             | HydroNode::FlatMapStreamBlocking { input, .. }
+            | HydroNode::MapAsyncCached { input, .. }
+            | HydroNode::RawDfir { input, .. }
             | HydroNode::Filter { input, .. }
             | HydroNode::FilterMap { input, .. }
             | HydroNode::Sort { input, .. }
@@ -5746,12 +6547,14 @@ impl HydroNode {
             | HydroNode::Enumerate { input, .. }
             | HydroNode::Inspect { input, .. }
             | HydroNode::Unique { input, .. }
+            | HydroNode::UniqueWithin { input, .. }
             | HydroNode::Network { input, .. }
             | HydroNode::Counter { input, .. }
             | HydroNode::ResolveFutures { input, .. }
             | HydroNode::ResolveFuturesBlocking { input, .. }
             | HydroNode::ResolveFuturesOrdered { input, .. }
             | HydroNode::Fold { input, .. }
+            | HydroNode::LatticeFold { input, .. }
             | HydroNode::FoldKeyed { input, .. }
             | HydroNode::Reduce { input, .. }
             | HydroNode::ReduceKeyed { input, .. }
@@ -5874,14 +6677,20 @@ impl HydroNode {
             HydroNode::Map { f, .. } => format!("Map({:?})", f),
             HydroNode::FlatMap { f, .. } => format!("FlatMap({:?})", f),
             HydroNode::FlatMapStreamBlocking { f, .. } => format!("FlatMapStreamBlocking({:?})", f),
+            HydroNode::MapAsyncCached { limits, f, .. } => {
+                format!("MapAsyncCached({:?}, {:?})", limits, f)
+            }
+            HydroNode::RawDfir { syntax, .. } => format!("RawDfir({:?})", syntax),
             HydroNode::Filter { f, .. } => format!("Filter({:?})", f),
             HydroNode::FilterMap { f, .. } => format!("FilterMap({:?})", f),
             HydroNode::DeferTick { .. } => "DeferTick()".to_owned(),
             HydroNode::Enumerate { .. } => "Enumerate()".to_owned(),
             HydroNode::Inspect { f, .. } => format!("Inspect({:?})", f),
             HydroNode::Unique { .. } => "Unique()".to_owned(),
+            HydroNode::UniqueWithin { window, .. } => format!("UniqueWithin({:?})", window),
             HydroNode::Sort { .. } => "Sort()".to_owned(),
             HydroNode::Fold { init, acc, .. } => format!("Fold({:?}, {:?})", init, acc),
+            HydroNode::LatticeFold { .. } => "LatticeFold()".to_owned(),
             HydroNode::Scan { init, acc, .. } => format!("Scan({:?}, {:?})", init, acc),
             HydroNode::ScanAsyncBlocking { init, acc, .. } => {
                 format!("ScanAsyncBlocking({:?}, {:?})", init, acc)
@@ -5892,9 +6701,13 @@ impl HydroNode {
             HydroNode::ReduceKeyedWatermark { f, .. } => format!("ReduceKeyedWatermark({:?})", f),
             HydroNode::Network { .. } => "Network()".to_owned(),
             HydroNode::ExternalInput { .. } => "ExternalInput()".to_owned(),
-            HydroNode::Counter { tag, duration, .. } => {
-                format!("Counter({:?}, {:?})", tag, duration)
-            }
+            HydroNode::Counter { tag, kind, .. } => match kind {
+                CounterKind::Print { duration, .. } => {
+                    format!("Counter({:?}, {:?})", tag, duration)
+                }
+                CounterKind::Metered => format!("Metered({:?})", tag),
+                CounterKind::Breakpoint => format!("Breakpoint({:?})", tag),
+            },
             HydroNode::VersionedNetworkFork {
                 channel_name,
                 senders,
@@ -6098,6 +6911,89 @@ mod test {
         assert_eq!(size_of::<HydroRoot>(), 136);
     }
 
+    #[cfg(feature = "build")]
+    #[test]
+    fn test_eliminate_dead_code() {
+        use crate::live_collections::boundedness::Unbounded;
+        use crate::live_collections::stream::{NoOrder, Stream};
+        use crate::location::Location;
+        use crate::prelude::FlowBuilder;
+
+        let mut flow = FlowBuilder::new();
+        let process = flow.process::<()>();
+        let unused = flow.process::<()>();
+
+        let numbers = process.source_iter(q!(0..10));
+        numbers.clone().map(q!(|x| x + 1)).for_each(q!(|_| {}));
+        let _doubled = numbers.clone().map(q!(|x| x * 2));
+        let _inspected = numbers.inspect(q!(|x| println!("{}", x)));
+
+        let (complete, cycle) = process.forward_ref::<Stream<i32, _, Unbounded, NoOrder>>();
+        let source: Stream<_, _, Unbounded> = process.source_iter(q!(0..10)).into();
+        complete.complete(source.weaken_ordering());
+        let _filtered = cycle.filter(q!(|x| *x > 5));
+
+        let _unused_numbers = unused.source_iter(q!(0..10)).map(q!(|x| x * 2));
+
+        let built = flow.finalize();
+        assert_eq!(2, built.ir().len());
+        assert!(matches!(built.ir()[0], HydroRoot::ForEach { .. }));
+        assert!(matches!(built.ir()[1], HydroRoot::Null { .. }));
+        assert!(built.locations.contains_key(process.key));
+        assert!(!built.locations.contains_key(unused.key));
+    }
+
+    #[cfg(feature = "build")]
+    fn stable_ids(extra: bool) -> Vec<String> {
+        use crate::location::Location;
+        use crate::prelude::FlowBuilder;
+
+        let mut flow = FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        if extra {
+            process
+                .source_iter(q!(10..20))
+                .ir_node_named("extra")
+                .map(q!(|x| x + 1))
+                .for_each(q!(|_| {}));
+        }
+        let numbers = process.source_iter(q!(0..10));
+        numbers.clone().map(q!(|x| x * 2)).for_each(q!(|_| {}));
+        numbers.filter(q!(|x| *x > 5)).for_each(q!(|_| {}));
+
+        let ids = RefCell::new(vec![]);
+        let _built = flow.optimize_with(|ir| {
+            transform_bottom_up(
+                ir,
+                &mut |root: &mut HydroRoot| {
+                    ids.borrow_mut()
+                        .push(root.op_metadata().stable_id.clone().unwrap());
+                },
+                &mut |node: &mut HydroNode| {
+                    ids.borrow_mut()
+                        .push(node.op_metadata().stable_id.clone().unwrap());
+                },
+                false,
+            );
+        });
+        ids.into_inner()
+    }
+
+    #[cfg(feature = "build")]
+    #[test]
+    fn stable_ids_survive_added_operators() {
+        let original = stable_ids(false);
+        let unique = original.iter().collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(unique.len(), original.len());
+
+        let extended = stable_ids(true);
+        assert_eq!(extended.len(), original.len() + 3);
+        for id in &original {
+            assert!(extended.contains(id), "stable ID {id} changed");
+        }
+    }
+
     #[test]
     fn test_simplify_q_macro_basic() {
         // Test basic non-q! expression
@@ -136,6 +136,18 @@ mod tests {
         let json = json.replace(&json_escaped_root, "[workspace]");
         // Normalize any remaining JSON-escaped backslashes to forward slashes
         let json = json.replace("\\\\", "/");
+        // Stable IDs hash function names from backtraces, which are not portable either.
+        let json = json
+            .lines()
+            .map(|line| match line.split_once("\"stable_id\": ") {
+                Some((indent, rest)) => format!(
+                    "{indent}\"stable_id\": \"[stable_id]\"{}",
+                    if rest.ends_with(',') { "," } else { "" }
+                ),
+                None => line.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
 
         insta::assert_snapshot!(json);
     }
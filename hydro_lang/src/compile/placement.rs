@@ -0,0 +1,563 @@
+//! Automatic placement of processes onto hosts, see [`DeployFlow::auto_place`].
+//!
+//! Instead of assigning a host to every process with [`DeployFlow::with_process`], processes can
+//! declare the [`Resources`] they need, along with which processes they should be colocated with
+//! or kept apart from, in [`PlacementConstraints`]. [`AutoPlace`] then assigns each process to
+//! one of the available hosts, so that the demands of the processes on each host fit within its
+//! capacity:
+//!
+//! ```rust,ignore
+//! let constraints = PlacementConstraints::new()
+//!     .demand(&leader, Resources::new(2.0, 4096))
+//!     .demand(&storage, Resources::new(1.0, 8192))
+//!     .colocate(&leader, &proxy)
+//!     .avoid(&leader, &storage);
+//!
+//! let nodes = flow
+//!     .auto_place(constraints)
+//!     .host(Resources::new(4.0, 16384), TrybuildHost::new(vm_1))
+//!     .host(Resources::new(4.0, 16384), TrybuildHost::new(vm_2))
+//!     .fallback(deployment.Localhost())
+//!     .place()?
+//!     .deploy(&mut deployment);
+//! ```
+//!
+//! Placement is deterministic: the same constraints and hosts always produce the same placement.
+//! Processes with larger demands are placed first, each onto the first host (in the order the
+//! hosts were added) where it fits, backtracking when the remaining processes cannot be placed.
+//! The search gives up with [`PlacementError::SearchLimit`] after trying too many assignments.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use slotmap::SecondaryMap;
+
+use super::deploy::DeployFlow;
+use super::deploy_provider::{Deploy, IntoProcessSpec, ProcessSpec};
+use crate::location::{LocationKey, LocationType, Process};
+
+/// Resources demanded by a process, or offered by a host.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Resources {
+    /// Number of CPU cores, which may be fractional.
+    pub cpus: f64,
+    /// Memory, in megabytes.
+    pub memory_mb: u64,
+}
+
+impl Resources {
+    /// Creates resources with the given number of CPU cores and megabytes of memory.
+    pub fn new(cpus: f64, memory_mb: u64) -> Self {
+        Self { cpus, memory_mb }
+    }
+
+    fn fits_within(&self, capacity: &Resources) -> bool {
+        // Tolerate rounding errors from summing fractional demands.
+        self.cpus <= capacity.cpus + 1e-9 && self.memory_mb <= capacity.memory_mb
+    }
+
+    fn add(&mut self, other: &Resources) {
+        self.cpus += other.cpus;
+        self.memory_mb += other.memory_mb;
+    }
+
+    fn sub(&mut self, other: &Resources) {
+        self.cpus -= other.cpus;
+        self.memory_mb -= other.memory_mb;
+    }
+}
+
+/// The resource demands of processes, and constraints on which processes share a host, used by
+/// [`DeployFlow::auto_place`].
+#[derive(Clone, Debug, Default)]
+pub struct PlacementConstraints {
+    demands: BTreeMap<LocationKey, Resources>,
+    colocate: Vec<(LocationKey, LocationKey)>,
+    avoid: Vec<(LocationKey, LocationKey)>,
+}
+
+impl PlacementConstraints {
+    /// Creates empty constraints, where every process demands no resources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares the resources needed by `process`. Processes without a declared demand need no
+    /// resources, and can be placed on any host.
+    pub fn demand<P>(mut self, process: &Process<P>, demand: Resources) -> Self {
+        self.demands.insert(process.key, demand);
+        self
+    }
+
+    /// Requires `a` and `b` to be placed on the same host.
+    pub fn colocate<P1, P2>(mut self, a: &Process<P1>, b: &Process<P2>) -> Self {
+        self.colocate.push((a.key, b.key));
+        self
+    }
+
+    /// Requires `a` and `b` to be placed on different hosts.
+    pub fn avoid<P1, P2>(mut self, a: &Process<P1>, b: &Process<P2>) -> Self {
+        self.avoid.push((a.key, b.key));
+        self
+    }
+}
+
+/// The most assignments of a process to a host tried while searching for a placement.
+const MAX_SEARCH_STEPS: usize = 100_000;
+
+/// An error placing processes with [`AutoPlace::place`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlacementError {
+    /// Two processes must be kept apart, but are also (transitively) colocated.
+    Contradiction {
+        /// The name of one of the processes.
+        a: String,
+        /// The name of the other process.
+        b: String,
+    },
+    /// The processes do not fit onto the hosts.
+    Infeasible,
+    /// No placement was found within the search limit, although one may exist.
+    SearchLimit,
+}
+
+impl std::fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlacementError::Contradiction { a, b } => write!(
+                f,
+                "processes `{}` and `{}` must be both colocated and kept apart",
+                a, b
+            ),
+            PlacementError::Infeasible => {
+                write!(f, "the processes do not fit onto the available hosts")
+            }
+            PlacementError::SearchLimit => write!(
+                f,
+                "no placement was found after trying {} assignments",
+                MAX_SEARCH_STEPS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
+type HostBuilder<'a, D> = Box<dyn Fn(LocationKey, &str) -> <D as Deploy<'a>>::Process + 'a>;
+
+/// Places the processes of a flow onto hosts, created by [`DeployFlow::auto_place`].
+pub struct AutoPlace<'a, D: Deploy<'a>> {
+    flow: DeployFlow<'a, D>,
+    constraints: PlacementConstraints,
+    hosts: Vec<(Resources, HostBuilder<'a, D>)>,
+    fallback: Option<HostBuilder<'a, D>>,
+}
+
+fn host_builder<'a, D: Deploy<'a>, S: IntoProcessSpec<'a, D> + Clone + 'a>(
+    spec: S,
+) -> HostBuilder<'a, D> {
+    Box::new(move |location_key, name_hint| {
+        spec.clone()
+            .into_process_spec()
+            .build(location_key, name_hint)
+    })
+}
+
+impl<'a, D: Deploy<'a>> AutoPlace<'a, D> {
+    /// Adds a host which processes can be placed onto, with the given `capacity`. Every process
+    /// placed onto the host is deployed with a clone of `spec`, so `spec` should refer to a single
+    /// machine (e.g. a shared handle to a host).
+    pub fn host<S: IntoProcessSpec<'a, D> + Clone + 'a>(
+        mut self,
+        capacity: Resources,
+        spec: S,
+    ) -> Self {
+        self.hosts.push((capacity, host_builder(spec)));
+        self
+    }
+
+    /// Places every process onto `spec` if no hosts were added, ignoring the constraints. This
+    /// lets tests deploy the same flow onto a single machine (e.g. localhost), while production
+    /// deployments add their hosts.
+    pub fn fallback<S: IntoProcessSpec<'a, D> + Clone + 'a>(mut self, spec: S) -> Self {
+        self.fallback = Some(host_builder(spec));
+        self
+    }
+
+    /// Places the processes which have not been assigned a host yet. Processes which were already
+    /// assigned one with [`DeployFlow::with_process`] keep their host, and constraints involving
+    /// them are ignored.
+    pub fn place(self) -> Result<DeployFlow<'a, D>, PlacementError> {
+        let AutoPlace {
+            mut flow,
+            constraints,
+            hosts,
+            fallback,
+        } = self;
+
+        let unplaced = flow
+            .locations
+            .iter()
+            .filter(|&(key, location_type)| {
+                LocationType::Process == *location_type && !flow.processes.contains_key(key)
+            })
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+
+        if hosts.is_empty()
+            && let Some(fallback) = fallback
+        {
+            for key in unplaced {
+                let process = fallback(key, &flow.location_names[key]);
+                flow.processes.insert(key, process);
+            }
+            return Ok(flow);
+        }
+
+        let mut index = SecondaryMap::new();
+        for (i, &key) in unplaced.iter().enumerate() {
+            index.insert(key, i);
+        }
+        let pairs = |pairs: &[(LocationKey, LocationKey)]| {
+            pairs
+                .iter()
+                .filter_map(|(a, b)| Some((*index.get(*a)?, *index.get(*b)?)))
+                .collect::<Vec<_>>()
+        };
+
+        let demands = unplaced
+            .iter()
+            .map(|key| constraints.demands.get(key).copied().unwrap_or_default())
+            .collect::<Vec<_>>();
+        let capacities = hosts
+            .iter()
+            .map(|(capacity, _)| *capacity)
+            .collect::<Vec<_>>();
+
+        let placement = solve(
+            &demands,
+            &pairs(&constraints.colocate),
+            &pairs(&constraints.avoid),
+            &capacities,
+        )
+        .map_err(|err| match err {
+            SolveError::Contradiction(a, b) => PlacementError::Contradiction {
+                a: flow.location_names[unplaced[a]].clone(),
+                b: flow.location_names[unplaced[b]].clone(),
+            },
+            SolveError::Infeasible => PlacementError::Infeasible,
+            SolveError::SearchLimit => PlacementError::SearchLimit,
+        })?;
+
+        for (key, host) in unplaced.into_iter().zip(placement) {
+            let process = (hosts[host].1)(key, &flow.location_names[key]);
+            flow.processes.insert(key, process);
+        }
+        Ok(flow)
+    }
+}
+
+impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
+    /// Starts placing the processes of this flow onto hosts automatically, according to
+    /// `constraints`. See [`crate::compile::placement`].
+    pub fn auto_place(self, constraints: PlacementConstraints) -> AutoPlace<'a, D> {
+        AutoPlace {
+            flow: self,
+            constraints,
+            hosts: Vec::new(),
+            fallback: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum SolveError {
+    Contradiction(usize, usize),
+    Infeasible,
+    SearchLimit,
+}
+
+/// Assigns each item with the given `demands` to one of the hosts with the given `capacities`,
+/// such that the items in each pair of `colocate` share a host, the items in each pair of `avoid`
+/// do not, and the demands on each host fit within its capacity. Returns the host of each item.
+///
+/// Gives up after trying [`MAX_SEARCH_STEPS`] assignments, since the search is exponential in the
+/// worst case.
+fn solve(
+    demands: &[Resources],
+    colocate: &[(usize, usize)],
+    avoid: &[(usize, usize)],
+    capacities: &[Resources],
+) -> Result<Vec<usize>, SolveError> {
+    fn find(parents: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parents[root] != root {
+            root = parents[root];
+        }
+        parents[i] = root;
+        root
+    }
+
+    // Colocated items are placed together as a group, represented by its smallest item.
+    let mut parents = (0..demands.len()).collect::<Vec<_>>();
+    for &(a, b) in colocate {
+        let (root_a, root_b) = (find(&mut parents, a), find(&mut parents, b));
+        parents[root_a.max(root_b)] = root_a.min(root_b);
+    }
+    let groups_of = (0..demands.len())
+        .map(|i| find(&mut parents, i))
+        .collect::<Vec<_>>();
+
+    let mut group_demands = BTreeMap::<usize, Resources>::new();
+    for (i, demand) in demands.iter().enumerate() {
+        group_demands.entry(groups_of[i]).or_default().add(demand);
+    }
+
+    let mut group_avoid = BTreeSet::new();
+    for &(a, b) in avoid {
+        let (group_a, group_b) = (groups_of[a], groups_of[b]);
+        if group_a == group_b {
+            return Err(SolveError::Contradiction(a, b));
+        }
+        group_avoid.insert((group_a, group_b));
+        group_avoid.insert((group_b, group_a));
+    }
+
+    // Place the largest groups first, since they are the hardest to fit.
+    let mut order = group_demands.keys().copied().collect::<Vec<_>>();
+    order.sort_by(|a, b| {
+        let (demand_a, demand_b) = (&group_demands[a], &group_demands[b]);
+        demand_b
+            .cpus
+            .total_cmp(&demand_a.cpus)
+            .then(demand_b.memory_mb.cmp(&demand_a.memory_mb))
+            .then(a.cmp(b))
+    });
+
+    struct Search<'s> {
+        group_demands: &'s BTreeMap<usize, Resources>,
+        group_avoid: &'s BTreeSet<(usize, usize)>,
+        capacities: &'s [Resources],
+        remaining: Vec<Resources>,
+        group_hosts: BTreeMap<usize, usize>,
+        steps: usize,
+    }
+
+    impl Search<'_> {
+        fn place(&mut self, order: &[usize]) -> Result<bool, SolveError> {
+            let Some((&group, rest)) = order.split_first() else {
+                return Ok(true);
+            };
+            let demand = &self.group_demands[&group];
+            // Empty hosts with the same capacity are interchangeable, so only the first is tried.
+            let mut tried_empty = Vec::new();
+            for host in 0..self.remaining.len() {
+                let mut conflicts = false;
+                let mut empty = true;
+                for (&other, &other_host) in &self.group_hosts {
+                    if other_host == host {
+                        empty = false;
+                        conflicts |= self.group_avoid.contains(&(group, other));
+                    }
+                }
+                if conflicts || !demand.fits_within(&self.remaining[host]) {
+                    continue;
+                }
+                if empty {
+                    if tried_empty.contains(&self.capacities[host]) {
+                        continue;
+                    }
+                    tried_empty.push(self.capacities[host]);
+                }
+
+                self.steps += 1;
+                if self.steps > MAX_SEARCH_STEPS {
+                    return Err(SolveError::SearchLimit);
+                }
+                self.remaining[host].sub(demand);
+                self.group_hosts.insert(group, host);
+                if self.place(rest)? {
+                    return Ok(true);
+                }
+                self.group_hosts.remove(&group);
+                self.remaining[host].add(demand);
+            }
+            Ok(false)
+        }
+    }
+
+    let mut search = Search {
+        group_demands: &group_demands,
+        group_avoid: &group_avoid,
+        capacities,
+        remaining: capacities.to_vec(),
+        group_hosts: BTreeMap::new(),
+        steps: 0,
+    };
+    if !search.place(&order)? {
+        return Err(SolveError::Infeasible);
+    }
+    let group_hosts = search.group_hosts;
+
+    Ok(groups_of.iter().map(|group| group_hosts[group]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use super::*;
+    use crate::compile::builder::FlowBuilder;
+    use crate::compile::embedded::EmbeddedDeploy;
+    use crate::location::Location;
+
+    fn processes<'a>(flow: &mut FlowBuilder<'a>, count: usize) -> Vec<Process<'a, ()>> {
+        (0..count)
+            .map(|_| {
+                let process = flow.process::<()>();
+                // Locations without operators are pruned from the flow.
+                process.source_iter(q!([0])).for_each(q!(|_| {}));
+                process
+            })
+            .collect()
+    }
+
+    fn hosts_of(
+        flow: &DeployFlow<'_, EmbeddedDeploy>,
+        processes: &[Process<'_, ()>],
+    ) -> Vec<String> {
+        processes
+            .iter()
+            .map(|process| flow.processes[process.key].fn_name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn places_within_capacity() {
+        let host = Resources::new(4.0, 8192);
+        let demands = [
+            Resources::new(1.0, 1024),
+            Resources::new(3.0, 1024),
+            Resources::new(2.0, 1024),
+            Resources::new(2.0, 1024),
+        ];
+        assert_eq!(
+            Ok(vec![0, 0, 1, 1]),
+            solve(&demands, &[], &[], &[host, host])
+        );
+        assert_eq!(
+            Err(SolveError::Infeasible),
+            solve(&demands, &[], &[], &[host])
+        );
+    }
+
+    #[test]
+    fn colocates_and_avoids() {
+        let host = Resources::new(4.0, 8192);
+        let demands = [Resources::new(1.0, 1024); 3];
+        assert_eq!(
+            Ok(vec![0, 1, 0]),
+            solve(&demands, &[(0, 2)], &[(1, 2)], &[host, host])
+        );
+        assert_eq!(
+            Err(SolveError::Contradiction(1, 2)),
+            solve(&demands, &[(0, 2), (0, 1)], &[(1, 2)], &[host, host])
+        );
+    }
+
+    #[test]
+    fn search_is_bounded() {
+        // Only one item fits onto each host, so 13 items do not fit onto 12 hosts, which is only
+        // found after trying every arrangement of the items.
+        let demands = [Resources::new(1.5, 0); 13];
+        let capacities = (0..12)
+            .map(|i| Resources::new(2.0 + f64::from(i) * 0.01, 0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            Err(SolveError::SearchLimit),
+            solve(&demands, &[], &[], &capacities)
+        );
+    }
+
+    #[test]
+    fn interchangeable_hosts_are_tried_once() {
+        // Unlike above, the hosts are identical, so the arrangements need not be tried.
+        let demands = [Resources::new(1.5, 0); 13];
+        let host = Resources::new(2.0, 0);
+        assert_eq!(
+            Err(SolveError::Infeasible),
+            solve(&demands, &[], &[], &[host; 12])
+        );
+    }
+
+    #[test]
+    fn place_assigns_unplaced_processes() {
+        let mut flow = FlowBuilder::new();
+        let [a, b, c, pinned] = processes(&mut flow, 4).try_into().unwrap();
+        let constraints = PlacementConstraints::new()
+            .demand(&a, Resources::new(3.0, 1024))
+            .demand(&b, Resources::new(2.0, 1024))
+            .demand(&c, Resources::new(1.0, 1024))
+            .demand(&pinned, Resources::new(4.0, 1024))
+            .avoid(&a, &c);
+
+        let placed = flow
+            .with_default_optimize::<EmbeddedDeploy>()
+            .with_process(&pinned, "pinned")
+            .auto_place(constraints)
+            .host(Resources::new(4.0, 8192), "vm_1")
+            .host(Resources::new(4.0, 8192), "vm_2")
+            .fallback("local")
+            .place()
+            .unwrap();
+        assert_eq!(
+            vec!["vm_1", "vm_2", "vm_2", "pinned"],
+            hosts_of(&placed, &[a, b, c, pinned])
+        );
+    }
+
+    #[test]
+    fn place_reports_errors() {
+        let mut flow = FlowBuilder::new();
+        let [a, b] = processes(&mut flow, 2).try_into().unwrap();
+        let contradiction = PlacementConstraints::new().colocate(&a, &b).avoid(&a, &b);
+        let result = flow
+            .with_default_optimize::<EmbeddedDeploy>()
+            .auto_place(contradiction)
+            .host(Resources::new(4.0, 8192), "vm_1")
+            .host(Resources::new(4.0, 8192), "vm_2")
+            .place();
+        assert!(matches!(result, Err(PlacementError::Contradiction { .. })));
+
+        let mut flow = FlowBuilder::new();
+        let [a, b] = processes(&mut flow, 2).try_into().unwrap();
+        let too_large = PlacementConstraints::new()
+            .demand(&a, Resources::new(3.0, 1024))
+            .demand(&b, Resources::new(3.0, 1024));
+        let result = flow
+            .with_default_optimize::<EmbeddedDeploy>()
+            .auto_place(too_large)
+            .host(Resources::new(4.0, 8192), "vm_1")
+            .fallback("local")
+            .place();
+        assert!(matches!(result, Err(PlacementError::Infeasible)));
+    }
+
+    #[test]
+    fn fallback_places_everything_without_hosts() {
+        let mut flow = FlowBuilder::new();
+        let [a, b] = processes(&mut flow, 2).try_into().unwrap();
+        // The constraints cannot be satisfied, but are ignored by the fallback.
+        let constraints = PlacementConstraints::new()
+            .demand(&a, Resources::new(64.0, 1 << 20))
+            .colocate(&a, &b)
+            .avoid(&a, &b);
+
+        let placed = flow
+            .with_default_optimize::<EmbeddedDeploy>()
+            .auto_place(constraints)
+            .fallback("local")
+            .place()
+            .unwrap();
+        assert_eq!(vec!["local", "local"], hosts_of(&placed, &[a, b]));
+    }
+}
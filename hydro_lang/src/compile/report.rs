@@ -0,0 +1,376 @@
+//! A summary of the structure of a Hydro program, for reviewing large dataflows.
+//!
+//! See [`FlowBuilder::report`](super::builder::FlowBuilder::report) and [`BuiltFlow::report`].
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use super::built::BuiltFlow;
+use super::ir::{CollectionKind, HydroNode, HydroRoot, deep_clone, transform_bottom_up};
+use crate::location::{LocationId, LocationKey, LocationType};
+
+/// A summary of a Hydro program, with one [`LocationReport`] for each location.
+///
+/// Displaying the report prints a human-readable summary of each location.
+#[derive(Clone, Debug)]
+pub struct FlowReport {
+    /// The report for each location, in the order the locations were created.
+    pub locations: Vec<LocationReport>,
+}
+
+/// A summary of the operators placed at a single location.
+#[derive(Clone, Debug)]
+pub struct LocationReport {
+    /// The name of the location.
+    pub name: String,
+    /// The type of the location.
+    pub location_type: LocationType,
+    /// The number of operators of each kind (such as `map` or `fold_keyed`).
+    pub operators: BTreeMap<String, usize>,
+    /// The number of operators which hold state across ticks, such as a `fold` outside of a
+    /// tick or a `defer_tick`.
+    pub stateful_operators: usize,
+    /// The number of operators inside each tick of this location, by tick ID.
+    pub ticks: BTreeMap<usize, usize>,
+    /// The network channels which send data from this location.
+    pub outgoing_network: Vec<NetworkEdgeReport>,
+}
+
+/// A summary of a single network channel.
+#[derive(Clone, Debug)]
+pub struct NetworkEdgeReport {
+    /// The name of the channel, if it was given one.
+    pub name: Option<String>,
+    /// The name of the location receiving the data.
+    pub to: String,
+    /// The type of each message sent over the channel.
+    pub payload_type: String,
+    /// An estimate of the serialized size of each message in bytes, or `None` if the payload has
+    /// a variable size (such as a `String` or `Vec`).
+    pub estimated_bytes: Option<usize>,
+}
+
+impl LocationReport {
+    /// The total number of operators at this location.
+    pub fn total_operators(&self) -> usize {
+        self.operators.values().sum()
+    }
+}
+
+impl<'a> BuiltFlow<'a> {
+    fn location_name(&self, key: LocationKey) -> String {
+        self.location_names.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Summarizes the structure of this program, see [`FlowReport`].
+    pub fn report(&self) -> FlowReport {
+        let mut locations = self
+            .locations
+            .iter()
+            .map(|(key, location_type)| {
+                (
+                    key,
+                    LocationReport {
+                        name: self.location_name(key),
+                        location_type: *location_type,
+                        operators: BTreeMap::new(),
+                        stateful_operators: 0,
+                        ticks: BTreeMap::new(),
+                        outgoing_network: vec![],
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // The traversal needs mutable access, so it runs on a copy of the IR.
+        let mut ir = deep_clone(&self.ir);
+        let mut root_ops = vec![];
+        let mut node_ops = vec![];
+        let mut network = vec![];
+        transform_bottom_up(
+            &mut ir,
+            &mut |root: &mut HydroRoot| {
+                root_ops.push((
+                    root.input_metadata().location_id.clone(),
+                    op_kind(&root.print_root()),
+                ));
+            },
+            &mut |node: &mut HydroNode| {
+                let node = &*node;
+                if matches!(node, HydroNode::Placeholder) {
+                    return;
+                }
+                let location_id = &node.metadata().location_id;
+                node_ops.push((
+                    location_id.clone(),
+                    op_kind(&node.print_root()),
+                    holds_state(node, location_id),
+                ));
+
+                if let HydroNode::Network {
+                    name,
+                    input,
+                    metadata,
+                    ..
+                } = node
+                {
+                    let payload_type = payload_type(&metadata.collection_kind);
+                    network.push((
+                        input.metadata().location_id.root().key(),
+                        metadata.location_id.root().key(),
+                        NetworkEdgeReport {
+                            name: name.clone(),
+                            to: String::new(),
+                            payload_type: quote::ToTokens::to_token_stream(payload_type)
+                                .to_string(),
+                            estimated_bytes: estimated_size(payload_type),
+                        },
+                    ));
+                }
+            },
+            false,
+        );
+
+        let ops = root_ops
+            .into_iter()
+            .map(|(location_id, kind)| (location_id, kind, false))
+            .chain(node_ops);
+        for (location_id, kind, stateful) in ops {
+            let Some(location) = location_mut(&mut locations, location_id.root().key()) else {
+                continue;
+            };
+            *location.operators.entry(kind).or_default() += 1;
+            if stateful {
+                location.stateful_operators += 1;
+            }
+            if let Some(tick) = tick_of(&location_id) {
+                *location.ticks.entry(tick).or_default() += 1;
+            }
+        }
+        for (from, to, mut edge) in network {
+            edge.to = self.location_name(to);
+            if let Some(location) = location_mut(&mut locations, from) {
+                location.outgoing_network.push(edge);
+            }
+        }
+
+        FlowReport {
+            locations: locations
+                .into_iter()
+                .map(|(_, location)| location)
+                .collect(),
+        }
+    }
+}
+
+impl Display for FlowReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for location in &self.locations {
+            writeln!(f, "{:?} `{}`:", location.location_type, location.name)?;
+            write!(f, "  operators: {}", location.total_operators())?;
+            if !location.operators.is_empty() {
+                let kinds = location
+                    .operators
+                    .iter()
+                    .map(|(kind, count)| format!("{kind}: {count}"))
+                    .collect::<Vec<_>>();
+                write!(f, " ({})", kinds.join(", "))?;
+            }
+            writeln!(f)?;
+            writeln!(f, "  stateful operators: {}", location.stateful_operators)?;
+            write!(f, "  ticks: {}", location.ticks.len())?;
+            if !location.ticks.is_empty() {
+                let ticks = location
+                    .ticks
+                    .iter()
+                    .map(|(tick, count)| format!("tick {tick}: {count} operators"))
+                    .collect::<Vec<_>>();
+                write!(f, " ({})", ticks.join(", "))?;
+            }
+            writeln!(f)?;
+            if !location.outgoing_network.is_empty() {
+                writeln!(f, "  outgoing network edges:")?;
+                for edge in &location.outgoing_network {
+                    write!(f, "    -> `{}`", edge.to)?;
+                    if let Some(name) = &edge.name {
+                        write!(f, " ({name})")?;
+                    }
+                    write!(f, ": `{}`, ", edge.payload_type)?;
+                    match edge.estimated_bytes {
+                        Some(bytes) => writeln!(f, "~{bytes} bytes per message")?,
+                        None => writeln!(f, "variable size")?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn location_mut(
+    locations: &mut [(LocationKey, LocationReport)],
+    key: LocationKey,
+) -> Option<&mut LocationReport> {
+    locations
+        .iter_mut()
+        .find(|(k, _)| *k == key)
+        .map(|(_, location)| location)
+}
+
+/// The kind of an operator, as a snake case name, from its [`HydroNode::print_root`].
+fn op_kind(printed: &str) -> String {
+    let name = printed.split('(').next().unwrap_or(printed);
+    let mut kind = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                kind.push('_');
+            }
+            kind.push(c.to_ascii_lowercase());
+        } else {
+            kind.push(c);
+        }
+    }
+    kind
+}
+
+/// Whether the operator holds state across ticks, which is the case for stateful operators
+/// outside of a tick and for `defer_tick`.
+fn holds_state(node: &HydroNode, location_id: &LocationId) -> bool {
+    let stateful = matches!(
+        node,
+        HydroNode::Fold { .. }
+            | HydroNode::LatticeFold { .. }
+            | HydroNode::FoldKeyed { .. }
+            | HydroNode::Scan { .. }
+            | HydroNode::ScanAsyncBlocking { .. }
+            | HydroNode::MapAsyncCached { .. }
+            | HydroNode::Reduce { .. }
+            | HydroNode::ReduceKeyed { .. }
+            | HydroNode::ReduceKeyedWatermark { .. }
+            | HydroNode::Join { .. }
+            | HydroNode::JoinHalf { .. }
+            | HydroNode::CrossProduct { .. }
+            | HydroNode::CrossSingleton { .. }
+            | HydroNode::Difference { .. }
+            | HydroNode::AntiJoin { .. }
+            | HydroNode::Unique { .. }
+            | HydroNode::UniqueWithin { .. }
+            | HydroNode::Enumerate { .. }
+    );
+    (stateful && location_id.is_top_level()) || matches!(node, HydroNode::DeferTick { .. })
+}
+
+/// The innermost tick containing `location_id`, if any.
+fn tick_of(location_id: &LocationId) -> Option<usize> {
+    match location_id {
+        LocationId::Process(_) | LocationId::Cluster(_) => None,
+        LocationId::Atomic(tick) => tick_of(tick),
+        LocationId::Tick(clock_id, _) => Some(clock_id.into_inner()),
+    }
+}
+
+/// The type of each message carrying elements of a collection of the given kind.
+fn payload_type(kind: &CollectionKind) -> &syn::Type {
+    match kind {
+        CollectionKind::Stream { element_type, .. }
+        | CollectionKind::Singleton { element_type, .. }
+        | CollectionKind::Optional { element_type, .. } => element_type,
+        // Received keyed collections are keyed by the sender, which is not part of the payload.
+        CollectionKind::KeyedStream { value_type, .. }
+        | CollectionKind::KeyedSingleton { value_type, .. } => value_type,
+    }
+}
+
+/// An estimate of the size of a value of type `ty` when serialized with bincode, or `None` if
+/// the size is not fixed.
+fn estimated_size(ty: &syn::Type) -> Option<usize> {
+    match ty {
+        syn::Type::Tuple(tuple) => tuple.elems.iter().map(estimated_size).sum(),
+        syn::Type::Paren(paren) => estimated_size(&paren.elem),
+        syn::Type::Group(group) => estimated_size(&group.elem),
+        syn::Type::Reference(reference) => estimated_size(&reference.elem),
+        syn::Type::Array(array) => {
+            let syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Int(len),
+                ..
+            }) = &array.len
+            else {
+                return None;
+            };
+            Some(estimated_size(&array.elem)? * len.base10_parse::<usize>().ok()?)
+        }
+        syn::Type::Path(path) => {
+            let segment = path.path.segments.last()?;
+            match segment.ident.to_string().as_str() {
+                "bool" | "u8" | "i8" => Some(1),
+                "u16" | "i16" => Some(2),
+                "u32" | "i32" | "f32" | "char" => Some(4),
+                "u64" | "i64" | "f64" | "usize" | "isize" | "MemberId" => Some(8),
+                "u128" | "i128" => Some(16),
+                "Option" => {
+                    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+                        return None;
+                    };
+                    let Some(syn::GenericArgument::Type(inner)) = args.args.first() else {
+                        return None;
+                    };
+                    // The discriminant, plus the value if present.
+                    Some(1 + estimated_size(inner)?)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use super::estimated_size;
+    use crate::location::Location;
+    use crate::networking::TCP;
+    use crate::nondet::nondet;
+    use crate::prelude::FlowBuilder;
+
+    #[test]
+    fn estimated_sizes() {
+        assert_eq!(Some(12), estimated_size(&syn::parse_quote!((u32, u64))));
+        assert_eq!(
+            Some(33),
+            estimated_size(&syn::parse_quote!(Option<[u8; 32]>))
+        );
+        assert_eq!(Some(0), estimated_size(&syn::parse_quote!(())));
+        assert_eq!(None, estimated_size(&syn::parse_quote!((u32, String))));
+    }
+
+    #[test]
+    fn report_counts_operators_and_network() {
+        let mut flow = FlowBuilder::new();
+        let process = flow.process::<()>();
+        let cluster = flow.cluster::<()>();
+
+        process
+            .source_iter(q!(0..10u32))
+            .map(q!(|x| (x, x as u64)))
+            .broadcast(&cluster, TCP.fail_stop().bincode(), nondet!(/** test */))
+            .unique()
+            .for_each(q!(|_| {}));
+
+        let report = flow.finalize().report();
+        let process_report = &report.locations[0];
+        assert!(process_report.operators.contains_key("map"));
+        assert_eq!(1, process_report.outgoing_network.len());
+        let edge = &process_report.outgoing_network[0];
+        assert_eq!(Some(12), edge.estimated_bytes);
+
+        let cluster_report = &report.locations[1];
+        assert_eq!(Some(&1), cluster_report.operators.get("network"));
+        assert_eq!(1, cluster_report.stateful_operators);
+        assert!(cluster_report.outgoing_network.is_empty());
+
+        assert!(report.to_string().contains("~12 bytes per message"));
+    }
+}
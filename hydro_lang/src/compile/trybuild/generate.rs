@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fs::{self, File};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
@@ -21,8 +22,43 @@ pub const HYDRO_RUNTIME_FEATURES: &[&str] = &[
     "ecs_runtime",
     "maelstrom_runtime",
     "sim_runtime",
+    "sql",
+    "local_store",
+    "object_store",
+    "http",
 ];
 
+/// Prefix of the features of the generated crate which each enable one optional dependency of the
+/// source crate, so that it can be enabled for a single location.
+pub const HYDRO_DEPENDENCY_FEATURE_PREFIX: &str = "hydro___dep_";
+
+/// The features enabling each of `optional_dependencies`, named with
+/// [`HYDRO_DEPENDENCY_FEATURE_PREFIX`].
+///
+/// Dependencies which `features` already enable with `dep:` have no implicit feature of the same
+/// name, so they must be enabled with `dep:` too. Using `dep:` for the others would instead
+/// remove their implicit features, which `features` may refer to.
+fn dependency_features<'a>(
+    features: &BTreeMap<String, Vec<String>>,
+    optional_dependencies: impl IntoIterator<Item = &'a String>,
+) -> Vec<(String, Vec<String>)> {
+    optional_dependencies
+        .into_iter()
+        .map(|dependency| {
+            let explicit = format!("dep:{dependency}");
+            let enable = if features.values().flatten().any(|f| f == &explicit) {
+                explicit
+            } else {
+                dependency.clone()
+            };
+            (
+                format!("{HYDRO_DEPENDENCY_FEATURE_PREFIX}{dependency}"),
+                vec![enable],
+            )
+        })
+        .collect()
+}
+
 #[cfg(any(feature = "deploy", feature = "maelstrom"))]
 /// Whether to use dynamic linking for the generated binary.
 /// - `Static`: Place in base crate examples (for remote/containerized deploys)
@@ -277,9 +313,11 @@ pub fn compile_graph_trybuild(
                         let _ = local_set.spawn_local( #sidecars ); // Uses #dfir_ident
                     )*
 
+                    let __hydro_drain = __hydro_lang_trybuild_cli_owned.drain.clone();
                     let _ = local_set.run_until(#root::runtime_support::launch::run_stdin_commands(
+                        __hydro_drain.clone(),
                         async move {
-                            #dfir_ident.run().await
+                            #dfir_ident.run_until(__hydro_drain.drained()).await
                         }
                     )).await;
                 }
@@ -878,6 +916,17 @@ pub fn create_trybuild()
         .features
         .insert("hydro___test".to_owned(), dev_dependency_features);
 
+    let optional_dependencies = manifest
+        .dependencies
+        .iter()
+        .filter(|(_, dependency)| dependency.optional)
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    manifest.features.extend(dependency_features(
+        &manifest.features,
+        &optional_dependencies,
+    ));
+
     if manifest
         .workspace
         .as_ref()
@@ -1219,3 +1268,26 @@ pub(crate) fn write_atomic(contents: &[u8], path: &Path) -> Result<(), std::io::
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::dependency_features;
+
+    #[test]
+    fn dependency_features_keep_implicit_features() {
+        let features = BTreeMap::from([
+            ("kafka".to_owned(), vec!["rdkafka".to_owned()]),
+            ("compression".to_owned(), vec!["dep:zstd".to_owned()]),
+        ]);
+        let optional = ["rdkafka".to_owned(), "zstd".to_owned()];
+        assert_eq!(
+            dependency_features(&features, &optional),
+            vec![
+                ("hydro___dep_rdkafka".to_owned(), vec!["rdkafka".to_owned()]),
+                ("hydro___dep_zstd".to_owned(), vec!["dep:zstd".to_owned()]),
+            ]
+        );
+    }
+}
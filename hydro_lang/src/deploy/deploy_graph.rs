@@ -4,6 +4,7 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::future::Future;
 use std::io::Error;
+use std::ops::RangeInclusive;
 use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -26,12 +27,12 @@ use stageleft::{QuotedWithContext, RuntimeData};
 use syn::parse_quote;
 
 use super::deploy_runtime::*;
-use crate::compile::builder::ExternalPortId;
+use crate::compile::builder::{ConfigError, ExternalPortId};
 use crate::compile::deploy_provider::{
     ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec, Node, ProcessSpec, RegisterPort,
 };
 use crate::compile::trybuild::generate::{
-    HYDRO_RUNTIME_FEATURES, LinkingMode, create_graph_trybuild,
+    HYDRO_DEPENDENCY_FEATURE_PREFIX, HYDRO_RUNTIME_FEATURES, LinkingMode, create_graph_trybuild,
 };
 use crate::location::dynamic::LocationId;
 use crate::location::member_id::TaglessMemberId;
@@ -336,6 +337,34 @@ impl<'a> Deploy<'a> for HydroDeploy {
         parse_quote!(#sink_ident)
     }
 
+    fn e2o_http_source(
+        extra_stmts: &mut Vec<syn::Stmt>,
+        _p2: &Self::Process,
+        p2_port: &<Self::Process as Node>::Port,
+        route: &str,
+        shared_handle: String,
+    ) -> syn::Expr {
+        let source_ident = syn::Ident::new(
+            &format!("__hydro_deploy_many_{}_source", &shared_handle),
+            Span::call_site(),
+        );
+        let sink_ident = syn::Ident::new(
+            &format!("__hydro_deploy_many_{}_sink", &shared_handle),
+            Span::call_site(),
+        );
+
+        let root = get_this_crate();
+
+        extra_stmts.push(syn::parse_quote! {
+            let (#source_ident, #sink_ident) = __hydro_lang_trybuild_cli
+                .port(#p2_port)
+                .connect::<#root::runtime_support::hydro_deploy_integration::http::ConnectedHttp>()
+                .serve(#route);
+        });
+
+        parse_quote!(#source_ident)
+    }
+
     fn e2o_source(
         extra_stmts: &mut Vec<syn::Stmt>,
         _p1: &Self::External,
@@ -483,10 +512,12 @@ pub struct TrybuildHost {
     profile: Option<String>,
     additional_hydro_features: Vec<String>,
     features: Vec<String>,
+    excluded_features: Vec<String>,
     tracing: Option<TracingOptions>,
     build_envs: Vec<(String, String)>,
     env: HashMap<String, String>,
     pin_to_core: Option<usize>,
+    port_fallback: Option<RangeInclusive<u16>>,
     name_hint: Option<String>,
     cluster_idx: Option<usize>,
 }
@@ -500,10 +531,12 @@ impl From<Arc<dyn Host>> for TrybuildHost {
             profile: None,
             additional_hydro_features: vec![],
             features: vec![],
+            excluded_features: vec![],
             tracing: None,
             build_envs: vec![],
             env: HashMap::new(),
             pin_to_core: None,
+            port_fallback: None,
             name_hint: None,
             cluster_idx: None,
         }
@@ -519,10 +552,12 @@ impl<H: Host + 'static> From<Arc<H>> for TrybuildHost {
             profile: None,
             additional_hydro_features: vec![],
             features: vec![],
+            excluded_features: vec![],
             tracing: None,
             build_envs: vec![],
             env: HashMap::new(),
             pin_to_core: None,
+            port_fallback: None,
             name_hint: None,
             cluster_idx: None,
         }
@@ -539,10 +574,12 @@ impl TrybuildHost {
             profile: None,
             additional_hydro_features: vec![],
             features: vec![],
+            excluded_features: vec![],
             tracing: None,
             build_envs: vec![],
             env: HashMap::new(),
             pin_to_core: None,
+            port_fallback: None,
             name_hint: None,
             cluster_idx: None,
         }
@@ -605,6 +642,57 @@ impl TrybuildHost {
         self
     }
 
+    /// Excludes features which would otherwise be inherited from the build of the deployment
+    /// script, so that they are only enabled for the locations which need them.
+    ///
+    /// By default, the binary of every location is compiled with the features of the crate that
+    /// were enabled for the deployment script, plus those added with [`Self::features`]. If only
+    /// some locations need a heavy feature (or an optional dependency enabled by one), the other
+    /// locations can exclude it to reduce their compile times and binary sizes:
+    ///
+    /// ```rust,ignore
+    /// flow.with_process(&ingest, TrybuildHost::new(host.clone()).feature("kafka"))
+    ///     .with_process(&aggregate, TrybuildHost::new(host).exclude_feature("kafka"))
+    /// ```
+    pub fn exclude_features(
+        mut self,
+        features: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.excluded_features
+            .extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Excludes a single feature, see [`Self::exclude_features`].
+    pub fn exclude_feature(mut self, feature: impl Into<String>) -> Self {
+        self.excluded_features.push(feature.into());
+        self
+    }
+
+    /// Enables optional dependencies of the crate for this location only, without enabling a
+    /// feature of the crate which would pull them into every location:
+    ///
+    /// ```rust,ignore
+    /// flow.with_process(&ingest, TrybuildHost::new(host.clone()).dependency("rdkafka"))
+    ///     .with_process(&aggregate, TrybuildHost::new(host))
+    /// ```
+    pub fn dependencies(
+        mut self,
+        dependencies: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.features.extend(
+            dependencies.into_iter().map(|dependency| {
+                format!("{HYDRO_DEPENDENCY_FEATURE_PREFIX}{}", dependency.into())
+            }),
+        );
+        self
+    }
+
+    /// Enables a single optional dependency, see [`Self::dependencies`].
+    pub fn dependency(self, dependency: impl Into<String>) -> Self {
+        self.dependencies([dependency])
+    }
+
     pub fn tracing(self, tracing: TracingOptions) -> Self {
         if self.tracing.is_some() {
             panic!("{} already set", name_of!(tracing in Self));
@@ -633,12 +721,44 @@ impl TrybuildHost {
         Self { env, ..self }
     }
 
+    /// Forwards the object store credentials of the deploying machine (any environment variable
+    /// starting with `AWS_`, `GOOGLE_`, or `AZURE_`) to the launched binary, so that it can use
+    /// `Stream::sink_object_store` (with the `object_store` feature).
+    ///
+    /// This is not needed for hosts that obtain credentials from the cloud provider, such as
+    /// GCP instances with a service account or AWS instances with an instance profile.
+    pub fn forward_object_store_credentials(self) -> Self {
+        const CREDENTIAL_PREFIXES: &[&str] = &["AWS_", "GOOGLE_", "AZURE_"];
+
+        let mut env = self.env;
+        env.extend(std::env::vars().filter(|(key, _)| {
+            CREDENTIAL_PREFIXES
+                .iter()
+                .any(|prefix| key.starts_with(prefix))
+        }));
+        Self { env, ..self }
+    }
+
     pub fn pin_to_core(self, core: usize) -> Self {
         Self {
             pin_to_core: Some(core),
             ..self
         }
     }
+
+    /// Sets the ports to retry with when a port requested by this process (such as with
+    /// [`NetworkHint::TcpPort`](crate::location::NetworkHint::TcpPort)) is already in use on its
+    /// host, see [`RustCrate::port_fallback`].
+    pub fn port_fallback(self, ports: RangeInclusive<u16>) -> Self {
+        if self.port_fallback.is_some() {
+            panic!("{} already set", name_of!(port_fallback in Self));
+        }
+
+        Self {
+            port_fallback: Some(ports),
+            ..self
+        }
+    }
 }
 
 impl IntoProcessSpec<'_, HydroDeploy> for Arc<dyn Host> {
@@ -651,10 +771,12 @@ impl IntoProcessSpec<'_, HydroDeploy> for Arc<dyn Host> {
             profile: None,
             additional_hydro_features: vec![],
             features: vec![],
+            excluded_features: vec![],
             tracing: None,
             build_envs: vec![],
             env: HashMap::new(),
             pin_to_core: None,
+            port_fallback: None,
             name_hint: None,
             cluster_idx: None,
         }
@@ -671,10 +793,12 @@ impl<H: Host + 'static> IntoProcessSpec<'_, HydroDeploy> for Arc<H> {
             profile: None,
             additional_hydro_features: vec![],
             features: vec![],
+            excluded_features: vec![],
             tracing: None,
             build_envs: vec![],
             env: HashMap::new(),
             pin_to_core: None,
+            port_fallback: None,
             name_hint: None,
             cluster_idx: None,
         }
@@ -846,6 +970,31 @@ pub(crate) enum CrateOrTrybuild {
     Trybuild(TrybuildHost),
 }
 
+impl CrateOrTrybuild {
+    fn is_local(&self) -> bool {
+        let host = match self {
+            CrateOrTrybuild::Crate(_, host) => host,
+            CrateOrTrybuild::Trybuild(trybuild) => &trybuild.host,
+        };
+        host.target_type() == hydro_deploy::HostTargetType::Local
+    }
+
+    fn set_env(&mut self, env: &[(String, String)]) {
+        match self {
+            CrateOrTrybuild::Crate(c, _) => {
+                let mut crate_with_env = c.clone();
+                for (key, value) in env {
+                    crate_with_env = crate_with_env.env(key, value);
+                }
+                *c = crate_with_env;
+            }
+            CrateOrTrybuild::Trybuild(trybuild) => {
+                trybuild.env.extend(env.iter().cloned());
+            }
+        }
+    }
+}
+
 #[expect(missing_docs, reason = "TODO")]
 #[derive(Clone)]
 pub struct DeployNode {
@@ -881,6 +1030,19 @@ impl Node for DeployNode {
         });
     }
 
+    fn set_env(&self, env: &[(String, String)]) -> Result<(), ConfigError> {
+        self.service_spec
+            .borrow_mut()
+            .as_mut()
+            .unwrap()
+            .set_env(env);
+        Ok(())
+    }
+
+    fn runs_locally(&self) -> bool {
+        self.service_spec.borrow().as_ref().unwrap().is_local()
+    }
+
     fn instantiate(
         &self,
         env: &mut Self::InstantiateEnv,
@@ -971,6 +1133,22 @@ impl Node for DeployCluster {
         format!("port_{}", next_port)
     }
 
+    fn set_env(&self, env: &[(String, String)]) -> Result<(), ConfigError> {
+        for spec in self.cluster_spec.borrow_mut().as_mut().unwrap() {
+            spec.set_env(env);
+        }
+        Ok(())
+    }
+
+    fn runs_locally(&self) -> bool {
+        self.cluster_spec
+            .borrow()
+            .as_ref()
+            .unwrap()
+            .iter()
+            .all(CrateOrTrybuild::is_local)
+    }
+
     fn instantiate(
         &self,
         env: &mut Self::InstantiateEnv,
@@ -1169,6 +1347,7 @@ fn create_trybuild_service(
         LinkingMode::Static => dir.to_path_buf(),
     };
 
+    let features = location_features(&trybuild, features);
     let mut ret = RustCrate::new(&crate_dir, dir)
         .target_dir(target_dir)
         .example(bin_name)
@@ -1202,23 +1381,11 @@ fn create_trybuild_service(
         ret = ret.pin_to_core(core);
     }
 
-    ret = ret.features(
-        vec!["hydro___feature_deploy_integration".to_owned()]
-            .into_iter()
-            .chain(
-                trybuild
-                    .additional_hydro_features
-                    .into_iter()
-                    .map(|runtime_feature| {
-                        assert!(
-                            HYDRO_RUNTIME_FEATURES.iter().any(|f| f == &runtime_feature),
-                            "{runtime_feature} is not a valid Hydro runtime feature"
-                        );
-                        format!("hydro___feature_{runtime_feature}")
-                    }),
-            )
-            .chain(trybuild.features),
-    );
+    if let Some(ports) = trybuild.port_fallback {
+        ret = ret.port_fallback(ports);
+    }
+
+    ret = ret.features(features);
 
     for (key, value) in trybuild.build_envs {
         ret = ret.build_env(key, value);
@@ -1231,9 +1398,98 @@ fn create_trybuild_service(
     ret = ret.build_env("STAGELEFT_TRYBUILD_BUILD_STAGED", "1");
     ret = ret.config("build.incremental = false");
 
-    if let Some(features) = features {
-        ret = ret.features(features);
+    ret
+}
+
+/// The features of the generated crate to build the binary of a location with: its runtime
+/// features, features, and dependencies, followed by the features `inherited` from the build of
+/// the deployment script which it does not exclude.
+fn location_features(trybuild: &TrybuildHost, inherited: Option<&[String]>) -> Vec<String> {
+    for excluded in &trybuild.excluded_features {
+        assert!(
+            !trybuild.features.contains(excluded),
+            "feature `{excluded}` is both added and excluded"
+        );
     }
 
-    ret
+    vec!["hydro___feature_deploy_integration".to_owned()]
+        .into_iter()
+        .chain(
+            trybuild
+                .additional_hydro_features
+                .iter()
+                .map(|runtime_feature| {
+                    assert!(
+                        HYDRO_RUNTIME_FEATURES.contains(&runtime_feature.as_str()),
+                        "{runtime_feature} is not a valid Hydro runtime feature"
+                    );
+                    format!("hydro___feature_{runtime_feature}")
+                }),
+        )
+        .chain(trybuild.features.iter().cloned())
+        .chain(
+            inherited
+                .unwrap_or_default()
+                .iter()
+                .filter(|feature| !trybuild.excluded_features.contains(feature))
+                .cloned(),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hydro_deploy::LocalhostHost;
+
+    use super::*;
+
+    fn trybuild_host() -> TrybuildHost {
+        TrybuildHost::new(Arc::new(LocalhostHost::new(0)))
+    }
+
+    #[test]
+    fn location_features_exclude_inherited() {
+        let inherited = ["kafka".to_owned(), "metrics".to_owned()];
+        let trybuild = trybuild_host()
+            .additional_hydro_feature("runtime_measure")
+            .feature("compression")
+            .exclude_feature("kafka");
+        assert_eq!(
+            location_features(&trybuild, Some(&inherited[..])),
+            vec![
+                "hydro___feature_deploy_integration",
+                "hydro___feature_runtime_measure",
+                "compression",
+                "metrics"
+            ]
+        );
+        assert_eq!(
+            location_features(&trybuild, None),
+            vec![
+                "hydro___feature_deploy_integration",
+                "hydro___feature_runtime_measure",
+                "compression"
+            ]
+        );
+    }
+
+    #[test]
+    fn location_features_enable_dependencies() {
+        let trybuild = trybuild_host().dependencies(["rdkafka", "zstd"]);
+        assert_eq!(
+            location_features(&trybuild, Some(&[][..])),
+            vec![
+                "hydro___feature_deploy_integration",
+                "hydro___dep_rdkafka",
+                "hydro___dep_zstd"
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "feature `kafka` is both added and excluded")]
+    fn location_features_reject_conflicts() {
+        let trybuild = trybuild_host().feature("kafka").exclude_feature("kafka");
+        location_features(&trybuild, None);
+    }
 }
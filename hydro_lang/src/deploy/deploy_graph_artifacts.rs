@@ -0,0 +1,1004 @@
+//! Deployment backend for Hydro that compiles each location into a standalone binary, for
+//! deploying with tooling other than [`hydro_deploy`] (for example systemd or Nomad).
+//!
+//! [`DeployFlow::compile_to_artifacts`] (or [`ArtifactDeployment::write_artifacts`]) writes one
+//! binary per location into `bin/` of the output directory, along with a `manifest.json`
+//! describing every instance (each process, and each member of each cluster) that has to be
+//! launched. Cluster sizes are fixed at compile time, and every port that an instance listens
+//! on is assigned a fixed TCP port number, starting from [`ArtifactDeployment::base_port`]. Port
+//! numbers are unique across the whole deployment, so any instances may share a host.
+//!
+//! # Manifest format
+//!
+//! The manifest is a serialized [`ArtifactManifest`]:
+//!
+//! ```json
+//! {
+//!   "instances": {
+//!     "leader": {
+//!       "location": "leader",
+//!       "member_id": null,
+//!       "binary": "bin/leader",
+//!       "bind": { "port_0": { "Merge": [{ "Tagged": [{ "TcpPort": ["0.0.0.0", 40000] }, 0] }] } },
+//!       "connect": { "port_1": { "Demux": { "0": { "Tcp": { "instance": "workers-0", "port": 40000 } } } } },
+//!       "meta": "{...}"
+//!     },
+//!     "workers-0": { ... }
+//!   }
+//! }
+//! ```
+//!
+//! `bind` lists the ports an instance listens on, and is already in its final form. `connect`
+//! lists the ports an instance connects to, as [`PeerPort`]s which name the instance and TCP
+//! port to connect to. Once the address of each instance is known, they are resolved into an
+//! [`InstanceConfig`] with [`InstanceArtifact::instance_config`] (or by replacing each `Tcp`
+//! entry with `{ "TcpPort": "<ip>:<port>" }`).
+//!
+//! # Launching
+//!
+//! Each instance is launched by running its binary with the [`INSTANCE_CONFIG_ENV`] environment
+//! variable set to the path of its serialized [`InstanceConfig`]. Instances may be started in
+//! any order: an instance retries its connections, with a backoff of up to 5 seconds, until the
+//! other side is listening, and only starts running its dataflow once all of them are
+//! established. A drain can be requested by writing `drain` to the instance's stdin, the same as
+//! under Hydro Deploy.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::Error;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use bytes::{Bytes, BytesMut};
+use dfir_lang::graph::DfirGraph;
+use futures::{Sink, Stream};
+use hydro_deploy::rust_crate::build::build_crate_memoized;
+use hydro_deploy::{HostTargetType, LinuxCompileType, RustCrate};
+pub use hydro_deploy_integration::{INSTANCE_CONFIG_ENV, InstanceConfig};
+use hydro_deploy_integration::{ServerBindConfig, ServerPort};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use slotmap::SparseSecondaryMap;
+use stageleft::{QuotedWithContext, RuntimeData};
+
+use super::deploy_runtime::*;
+use crate::compile::builder::ExternalPortId;
+use crate::compile::deploy::DeployFlow;
+use crate::compile::deploy_provider::{ClusterSpec, Deploy, Node, ProcessSpec, RegisterPort};
+use crate::compile::trybuild::generate::{
+    DeployMode, LinkingMode, TrybuildConfig, create_graph_trybuild,
+};
+use crate::location::dynamic::LocationId;
+use crate::location::member_id::TaglessMemberId;
+use crate::location::{LocationKey, MembershipEvent, NetworkHint};
+
+/// Default for [`ArtifactDeployment::base_port`].
+pub const DEFAULT_BASE_PORT: u16 = 40000;
+
+/// Deployment backend that compiles each location into a standalone binary, see the
+/// [module documentation](self).
+pub enum ArtifactDeploy {}
+
+fn check_networking_info(networking_info: &crate::networking::NetworkingInfo) {
+    match networking_info {
+        crate::networking::NetworkingInfo::Tcp {
+            fault: crate::networking::TcpFault::FailStop,
+        } => {}
+        _ => panic!("Unsupported networking info: {:?}", networking_info),
+    }
+}
+
+impl<'a> Deploy<'a> for ArtifactDeploy {
+    /// Map from Cluster location ID to member IDs.
+    type Meta = SparseSecondaryMap<LocationKey, Vec<TaglessMemberId>>;
+    type InstantiateEnv = ArtifactDeployment;
+
+    type Process = ArtifactNode;
+    type Cluster = ArtifactNode;
+    type External = ArtifactExternal;
+
+    fn o2o_sink_source(
+        _env: &mut Self::InstantiateEnv,
+        _p1: &Self::Process,
+        p1_port: &<Self::Process as Node>::Port,
+        _p2: &Self::Process,
+        p2_port: &<Self::Process as Node>::Port,
+        _name: Option<&str>,
+        networking_info: &crate::networking::NetworkingInfo,
+        _external_types: Option<(&syn::Type, &syn::Type)>,
+    ) -> (syn::Expr, syn::Expr) {
+        check_networking_info(networking_info);
+        deploy_o2o(
+            RuntimeData::new("__hydro_lang_trybuild_cli"),
+            p1_port.as_str(),
+            p2_port.as_str(),
+        )
+    }
+
+    fn o2o_connect(
+        p1: &Self::Process,
+        p1_port: &<Self::Process as Node>::Port,
+        p2: &Self::Process,
+        p2_port: &<Self::Process as Node>::Port,
+    ) -> Box<dyn FnOnce()> {
+        let p1 = p1.clone();
+        let p1_port = p1_port.clone();
+        let p2 = p2.clone();
+        let p2_port = p2_port.clone();
+
+        Box::new(move || {
+            let tcp_port = p2.allocate_tcp_ports(1);
+            p2.bind(
+                0,
+                p2_port,
+                ServerBindConfig::TcpPort(ANY_HOST.to_owned(), Some(tcp_port)),
+            );
+            p1.connect(0, p1_port, p2.peer(0, tcp_port));
+        })
+    }
+
+    fn o2m_sink_source(
+        _env: &mut Self::InstantiateEnv,
+        _p1: &Self::Process,
+        p1_port: &<Self::Process as Node>::Port,
+        _c2: &Self::Cluster,
+        c2_port: &<Self::Cluster as Node>::Port,
+        _name: Option<&str>,
+        networking_info: &crate::networking::NetworkingInfo,
+        _external_types: Option<(&syn::Type, &syn::Type)>,
+    ) -> (syn::Expr, syn::Expr) {
+        check_networking_info(networking_info);
+        deploy_o2m(
+            RuntimeData::new("__hydro_lang_trybuild_cli"),
+            p1_port.as_str(),
+            c2_port.as_str(),
+        )
+    }
+
+    fn o2m_connect(
+        p1: &Self::Process,
+        p1_port: &<Self::Process as Node>::Port,
+        c2: &Self::Cluster,
+        c2_port: &<Self::Cluster as Node>::Port,
+    ) -> Box<dyn FnOnce()> {
+        let p1 = p1.clone();
+        let p1_port = p1_port.clone();
+        let c2 = c2.clone();
+        let c2_port = c2_port.clone();
+
+        Box::new(move || {
+            let mut demux = BTreeMap::new();
+            for member in 0..c2.instance_count() {
+                let tcp_port = c2.allocate_tcp_ports(1);
+                c2.bind(
+                    member,
+                    c2_port.clone(),
+                    ServerBindConfig::TcpPort(ANY_HOST.to_owned(), Some(tcp_port)),
+                );
+                demux.insert(member as u32, c2.peer(member, tcp_port));
+            }
+            p1.connect(0, p1_port, PeerPort::Demux(demux));
+        })
+    }
+
+    fn m2o_sink_source(
+        _env: &mut Self::InstantiateEnv,
+        _c1: &Self::Cluster,
+        c1_port: &<Self::Cluster as Node>::Port,
+        _p2: &Self::Process,
+        p2_port: &<Self::Process as Node>::Port,
+        _name: Option<&str>,
+        networking_info: &crate::networking::NetworkingInfo,
+        _external_types: Option<(&syn::Type, &syn::Type)>,
+    ) -> (syn::Expr, syn::Expr) {
+        check_networking_info(networking_info);
+        deploy_m2o(
+            RuntimeData::new("__hydro_lang_trybuild_cli"),
+            c1_port.as_str(),
+            p2_port.as_str(),
+        )
+    }
+
+    fn m2o_connect(
+        c1: &Self::Cluster,
+        c1_port: &<Self::Cluster as Node>::Port,
+        p2: &Self::Process,
+        p2_port: &<Self::Process as Node>::Port,
+    ) -> Box<dyn FnOnce()> {
+        let c1 = c1.clone();
+        let c1_port = c1_port.clone();
+        let p2 = p2.clone();
+        let p2_port = p2_port.clone();
+
+        Box::new(move || {
+            let senders = c1.instance_count();
+            let base_tcp_port = p2.allocate_tcp_ports(senders);
+            p2.bind(0, p2_port, merged_bind_config(base_tcp_port, senders));
+            for sender in 0..senders {
+                c1.connect(
+                    sender,
+                    c1_port.clone(),
+                    p2.peer(0, base_tcp_port + sender as u16),
+                );
+            }
+        })
+    }
+
+    fn m2m_sink_source(
+        _env: &mut Self::InstantiateEnv,
+        _c1: &Self::Cluster,
+        c1_port: &<Self::Cluster as Node>::Port,
+        _c2: &Self::Cluster,
+        c2_port: &<Self::Cluster as Node>::Port,
+        _name: Option<&str>,
+        networking_info: &crate::networking::NetworkingInfo,
+        _external_types: Option<(&syn::Type, &syn::Type)>,
+    ) -> (syn::Expr, syn::Expr) {
+        check_networking_info(networking_info);
+        deploy_m2m(
+            RuntimeData::new("__hydro_lang_trybuild_cli"),
+            c1_port.as_str(),
+            c2_port.as_str(),
+        )
+    }
+
+    fn m2m_connect(
+        c1: &Self::Cluster,
+        c1_port: &<Self::Cluster as Node>::Port,
+        c2: &Self::Cluster,
+        c2_port: &<Self::Cluster as Node>::Port,
+    ) -> Box<dyn FnOnce()> {
+        let c1 = c1.clone();
+        let c1_port = c1_port.clone();
+        let c2 = c2.clone();
+        let c2_port = c2_port.clone();
+
+        Box::new(move || {
+            let senders = c1.instance_count();
+            let base_tcp_ports = (0..c2.instance_count())
+                .map(|receiver| {
+                    let base_tcp_port = c2.allocate_tcp_ports(senders);
+                    c2.bind(
+                        receiver,
+                        c2_port.clone(),
+                        merged_bind_config(base_tcp_port, senders),
+                    );
+                    base_tcp_port
+                })
+                .collect::<Vec<_>>();
+            for sender in 0..senders {
+                let demux = base_tcp_ports
+                    .iter()
+                    .enumerate()
+                    .map(|(receiver, base_tcp_port)| {
+                        (
+                            receiver as u32,
+                            c2.peer(receiver, base_tcp_port + sender as u16),
+                        )
+                    })
+                    .collect();
+                c1.connect(sender, c1_port.clone(), PeerPort::Demux(demux));
+            }
+        })
+    }
+
+    fn e2o_many_source(
+        _extra_stmts: &mut Vec<syn::Stmt>,
+        _p2: &Self::Process,
+        _p2_port: &<Self::Process as Node>::Port,
+        _codec_type: &syn::Type,
+        _shared_handle: String,
+    ) -> syn::Expr {
+        panic!("Artifact deployments do not support external locations")
+    }
+
+    fn e2o_many_sink(_shared_handle: String) -> syn::Expr {
+        panic!("Artifact deployments do not support external locations")
+    }
+
+    fn e2o_http_source(
+        _extra_stmts: &mut Vec<syn::Stmt>,
+        _p2: &Self::Process,
+        _p2_port: &<Self::Process as Node>::Port,
+        _route: &str,
+        _shared_handle: String,
+    ) -> syn::Expr {
+        panic!("Artifact deployments do not support external locations")
+    }
+
+    fn e2o_source(
+        _extra_stmts: &mut Vec<syn::Stmt>,
+        _p1: &Self::External,
+        _p1_port: &<Self::External as Node>::Port,
+        _p2: &Self::Process,
+        _p2_port: &<Self::Process as Node>::Port,
+        _codec_type: &syn::Type,
+        _shared_handle: String,
+    ) -> syn::Expr {
+        panic!("Artifact deployments do not support external locations")
+    }
+
+    fn e2o_connect(
+        _p1: &Self::External,
+        _p1_port: &<Self::External as Node>::Port,
+        _p2: &Self::Process,
+        _p2_port: &<Self::Process as Node>::Port,
+        _many: bool,
+        _server_hint: NetworkHint,
+    ) -> Box<dyn FnOnce()> {
+        panic!("Artifact deployments do not support external locations")
+    }
+
+    fn o2e_sink(
+        _p1: &Self::Process,
+        _p1_port: &<Self::Process as Node>::Port,
+        _p2: &Self::External,
+        _p2_port: &<Self::External as Node>::Port,
+        _shared_handle: String,
+    ) -> syn::Expr {
+        panic!("Artifact deployments do not support external locations")
+    }
+
+    fn cluster_ids(
+        of_cluster: LocationKey,
+    ) -> impl QuotedWithContext<'a, &'a [TaglessMemberId], ()> + Clone + 'a {
+        cluster_members(RuntimeData::new("__hydro_lang_trybuild_cli"), of_cluster)
+    }
+
+    fn cluster_self_id() -> impl QuotedWithContext<'a, TaglessMemberId, ()> + Clone + 'a {
+        cluster_self_id(RuntimeData::new("__hydro_lang_trybuild_cli"))
+    }
+
+    fn cluster_membership_stream(
+        _env: &mut Self::InstantiateEnv,
+        _at_location: &LocationId,
+        location_id: &LocationId,
+    ) -> impl QuotedWithContext<'a, Box<dyn Stream<Item = (TaglessMemberId, MembershipEvent)> + Unpin>, ()>
+    {
+        cluster_membership_stream(location_id)
+    }
+}
+
+/// The host that every port is bound on.
+const ANY_HOST: &str = "0.0.0.0";
+
+/// Binds one TCP port per sender, starting at `base_tcp_port`, each tagged with the sender's
+/// member ID. This matches how Hydro Deploy wires many-to-one channels.
+fn merged_bind_config(base_tcp_port: u16, senders: usize) -> ServerBindConfig {
+    ServerBindConfig::Merge(
+        (0..senders)
+            .map(|sender| {
+                ServerBindConfig::Tagged(
+                    Box::new(ServerBindConfig::TcpPort(
+                        ANY_HOST.to_owned(),
+                        Some(base_tcp_port + sender as u16),
+                    )),
+                    sender as u32,
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Converts a location name into a string that can be used as a file name.
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The ports of a single instance, accumulated while wiring up channels.
+#[derive(Default)]
+struct InstanceWiring {
+    bind: BTreeMap<String, ServerBindConfig>,
+    connect: BTreeMap<String, PeerPort>,
+    meta: Option<String>,
+}
+
+struct ArtifactNodeState {
+    name: String,
+    instances: Vec<InstanceWiring>,
+    /// The next free TCP port number, shared by every location of the deployment.
+    next_tcp_port: Option<Rc<Cell<u16>>>,
+    build: Option<(String, TrybuildConfig)>,
+}
+
+/// A process or cluster in an [`ArtifactDeploy`] deployment.
+#[derive(Clone)]
+pub struct ArtifactNode {
+    key: LocationKey,
+    is_cluster: bool,
+    next_port: Rc<RefCell<usize>>,
+    state: Rc<RefCell<ArtifactNodeState>>,
+}
+
+impl ArtifactNode {
+    fn new(key: LocationKey, name_hint: &str, cluster_size: Option<usize>) -> Self {
+        ArtifactNode {
+            key,
+            is_cluster: cluster_size.is_some(),
+            next_port: Rc::new(RefCell::new(0)),
+            state: Rc::new(RefCell::new(ArtifactNodeState {
+                name: sanitize_name(name_hint),
+                instances: (0..cluster_size.unwrap_or(1))
+                    .map(|_| InstanceWiring::default())
+                    .collect(),
+                next_tcp_port: None,
+                build: None,
+            })),
+        }
+    }
+
+    /// The name of the location, used as the name of its binary.
+    pub fn name(&self) -> String {
+        self.state.borrow().name.clone()
+    }
+
+    /// The names of the instances of this location in the manifest, in member ID order.
+    pub fn instance_names(&self) -> Vec<String> {
+        (0..self.instance_count())
+            .map(|i| self.instance_name(i))
+            .collect()
+    }
+
+    fn instance_name(&self, instance: usize) -> String {
+        let state = self.state.borrow();
+        if self.is_cluster {
+            format!("{}-{}", state.name, instance)
+        } else {
+            state.name.clone()
+        }
+    }
+
+    fn instance_count(&self) -> usize {
+        self.state.borrow().instances.len()
+    }
+
+    /// Reserves `count` consecutive TCP port numbers, which are not used by any other port in
+    /// the deployment.
+    fn allocate_tcp_ports(&self, count: usize) -> u16 {
+        let state = self.state.borrow();
+        let next_tcp_port = state
+            .next_tcp_port
+            .as_ref()
+            .expect("TCP ports should only be allocated after instantiation");
+        let first = next_tcp_port.get();
+        next_tcp_port.set(
+            u16::try_from(count)
+                .ok()
+                .and_then(|count| first.checked_add(count))
+                .expect("ran out of TCP port numbers, try a lower base port"),
+        );
+        first
+    }
+
+    fn peer(&self, instance: usize, tcp_port: u16) -> PeerPort {
+        PeerPort::Tcp {
+            instance: self.instance_name(instance),
+            port: tcp_port,
+        }
+    }
+
+    fn bind(&self, instance: usize, port: String, config: ServerBindConfig) {
+        let prev = self.state.borrow_mut().instances[instance]
+            .bind
+            .insert(port, config);
+        assert!(prev.is_none(), "port already set!");
+    }
+
+    fn connect(&self, instance: usize, port: String, peer: PeerPort) {
+        let prev = self.state.borrow_mut().instances[instance]
+            .connect
+            .insert(port, peer);
+        assert!(prev.is_none(), "port already set!");
+    }
+}
+
+impl Node for ArtifactNode {
+    type Port = String;
+    /// Map from Cluster location ID to member IDs.
+    type Meta = SparseSecondaryMap<LocationKey, Vec<TaglessMemberId>>;
+    type InstantiateEnv = ArtifactDeployment;
+
+    fn next_port(&self) -> String {
+        let next_port = *self.next_port.borrow();
+        *self.next_port.borrow_mut() += 1;
+
+        format!("port_{}", next_port)
+    }
+
+    fn update_meta(&self, meta: &Self::Meta) {
+        let is_cluster = self.is_cluster;
+        for (member, instance) in self.state.borrow_mut().instances.iter_mut().enumerate() {
+            let meta = HydroMeta {
+                clusters: meta.clone(),
+                cluster_id: is_cluster.then(|| TaglessMemberId::from_raw_id(member as u32)),
+            };
+            instance.meta = Some(serde_json::to_string(&meta).unwrap());
+        }
+    }
+
+    fn instantiate(
+        &self,
+        env: &mut Self::InstantiateEnv,
+        meta: &mut Self::Meta,
+        graph: DfirGraph,
+        extra_stmts: &[syn::Stmt],
+        sidecars: &[syn::Expr],
+    ) {
+        // Location names are not necessarily unique, but binary and instance names must be.
+        let mut name = self.name();
+        if env.nodes.iter().any(|other| other.name() == name) {
+            name = format!("{}_{}", name, self.key);
+        }
+
+        let build = create_graph_trybuild(
+            graph,
+            extra_stmts,
+            sidecars,
+            Some(&name),
+            DeployMode::HydroDeploy,
+            // The binaries are run outside of the build environment, so they must not depend on
+            // any dynamic libraries from it.
+            LinkingMode::Static,
+        );
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.name = name;
+            state.next_tcp_port = Some(env.next_tcp_port.clone());
+            state.build = Some(build);
+        }
+
+        if self.is_cluster {
+            meta.insert(
+                self.key,
+                (0..(self.instance_count() as u32))
+                    .map(TaglessMemberId::from_raw_id)
+                    .collect(),
+            );
+        }
+
+        env.nodes.push(self.clone());
+    }
+}
+
+/// Specification for a process in an [`ArtifactDeploy`] deployment.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ArtifactProcessSpec;
+
+impl ArtifactProcessSpec {
+    /// Creates a new process specification.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<'a> ProcessSpec<'a, ArtifactDeploy> for ArtifactProcessSpec {
+    fn build(self, key: LocationKey, name_hint: &str) -> ArtifactNode {
+        ArtifactNode::new(key, name_hint, None)
+    }
+}
+
+/// Specification for a cluster in an [`ArtifactDeploy`] deployment, with a fixed number of
+/// members.
+#[derive(Clone, Copy, Debug)]
+pub struct ArtifactClusterSpec {
+    members: usize,
+}
+
+impl ArtifactClusterSpec {
+    /// Creates a cluster specification with the given number of members.
+    pub fn new(members: usize) -> Self {
+        Self { members }
+    }
+}
+
+impl<'a> ClusterSpec<'a, ArtifactDeploy> for ArtifactClusterSpec {
+    fn build(self, key: LocationKey, name_hint: &str) -> ArtifactNode {
+        ArtifactNode::new(key, name_hint, Some(self.members))
+    }
+}
+
+/// External locations are not supported by [`ArtifactDeploy`].
+#[derive(Clone)]
+pub enum ArtifactExternal {}
+
+impl Node for ArtifactExternal {
+    type Port = String;
+    type Meta = SparseSecondaryMap<LocationKey, Vec<TaglessMemberId>>;
+    type InstantiateEnv = ArtifactDeployment;
+
+    fn next_port(&self) -> Self::Port {
+        unreachable!()
+    }
+
+    fn update_meta(&self, _meta: &Self::Meta) {}
+
+    fn instantiate(
+        &self,
+        _env: &mut Self::InstantiateEnv,
+        _meta: &mut Self::Meta,
+        _graph: DfirGraph,
+        _extra_stmts: &[syn::Stmt],
+        _sidecars: &[syn::Expr],
+    ) {
+        unreachable!()
+    }
+}
+
+impl<'a> RegisterPort<'a, ArtifactDeploy> for ArtifactExternal {
+    fn register(&self, _external_port_id: ExternalPortId, _port: Self::Port) {
+        unreachable!()
+    }
+
+    #[expect(clippy::manual_async_fn, reason = "false positive, involves lifetimes")]
+    fn as_bytes_bidi(
+        &self,
+        _external_port_id: ExternalPortId,
+    ) -> impl Future<
+        Output = (
+            Pin<Box<dyn Stream<Item = Result<BytesMut, Error>>>>,
+            Pin<Box<dyn Sink<Bytes, Error = Error>>>,
+        ),
+    > + 'a {
+        async move { unreachable!() }
+    }
+
+    #[expect(clippy::manual_async_fn, reason = "false positive, involves lifetimes")]
+    fn as_bincode_bidi<InT, OutT>(
+        &self,
+        _external_port_id: ExternalPortId,
+    ) -> impl Future<
+        Output = (
+            Pin<Box<dyn Stream<Item = OutT>>>,
+            Pin<Box<dyn Sink<InT, Error = Error>>>,
+        ),
+    > + 'a
+    where
+        InT: Serialize + 'static,
+        OutT: DeserializeOwned + 'static,
+    {
+        async move { unreachable!() }
+    }
+
+    #[expect(clippy::manual_async_fn, reason = "false positive, involves lifetimes")]
+    fn as_bincode_sink<T: Serialize + 'static>(
+        &self,
+        _external_port_id: ExternalPortId,
+    ) -> impl Future<Output = Pin<Box<dyn Sink<T, Error = Error>>>> + 'a {
+        async move { unreachable!() }
+    }
+
+    #[expect(clippy::manual_async_fn, reason = "false positive, involves lifetimes")]
+    fn as_bincode_source<T: DeserializeOwned + 'static>(
+        &self,
+        _external_port_id: ExternalPortId,
+    ) -> impl Future<Output = Pin<Box<dyn Stream<Item = T>>>> + 'a {
+        async move { unreachable!() }
+    }
+}
+
+/// A port on another instance that an instance connects to, as listed in the manifest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum PeerPort {
+    /// A TCP port of the named instance.
+    Tcp {
+        /// The name of the instance in the manifest.
+        instance: String,
+        /// The TCP port number the instance listens on.
+        port: u16,
+    },
+    /// One port per member of a cluster, keyed by member ID.
+    Demux(BTreeMap<u32, PeerPort>),
+}
+
+impl PeerPort {
+    /// Resolves the instance names into addresses, using `resolve` to look up the IP address of
+    /// each instance.
+    pub fn resolve(&self, resolve: &impl Fn(&str) -> IpAddr) -> ServerPort {
+        match self {
+            PeerPort::Tcp { instance, port } => {
+                ServerPort::TcpPort(SocketAddr::new(resolve(instance), *port))
+            }
+            PeerPort::Demux(demux) => ServerPort::Demux(
+                demux
+                    .iter()
+                    .map(|(member, peer)| (*member, peer.resolve(resolve)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A single instance to launch, as listed in the manifest.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InstanceArtifact {
+    /// The name of the location this is an instance of.
+    pub location: String,
+    /// The member ID, if this is a member of a cluster.
+    pub member_id: Option<u32>,
+    /// The path of the binary, relative to the output directory.
+    pub binary: PathBuf,
+    /// The ports this instance listens on.
+    pub bind: BTreeMap<String, ServerBindConfig>,
+    /// The ports of other instances that this instance connects to.
+    pub connect: BTreeMap<String, PeerPort>,
+    /// Serialized metadata describing the clusters in the deployment.
+    pub meta: String,
+}
+
+impl InstanceArtifact {
+    /// The configuration to launch this instance with, using `resolve` to look up the IP address
+    /// of each instance by name.
+    pub fn instance_config(&self, resolve: impl Fn(&str) -> IpAddr) -> InstanceConfig {
+        InstanceConfig {
+            bind: self.bind.clone().into_iter().collect(),
+            connect: self
+                .connect
+                .iter()
+                .map(|(port, peer)| (port.clone(), peer.resolve(&resolve)))
+                .collect(),
+            meta: Some(self.meta.clone()),
+            frame: Default::default(),
+        }
+    }
+}
+
+/// The contents of the `manifest.json` written by [`ArtifactDeployment::write_artifacts`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ArtifactManifest {
+    /// Every instance to launch, by name.
+    pub instances: BTreeMap<String, InstanceArtifact>,
+}
+
+/// The environment for an [`ArtifactDeploy`] deployment, which accumulates the compiled
+/// locations until they are written out with [`Self::write_artifacts`].
+pub struct ArtifactDeployment {
+    next_tcp_port: Rc<Cell<u16>>,
+    target: HostTargetType,
+    nodes: Vec<ArtifactNode>,
+}
+
+impl Default for ArtifactDeployment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArtifactDeployment {
+    /// Creates a deployment which builds for the local machine and assigns TCP ports starting at
+    /// [`DEFAULT_BASE_PORT`].
+    pub fn new() -> Self {
+        Self {
+            next_tcp_port: Rc::new(Cell::new(DEFAULT_BASE_PORT)),
+            target: HostTargetType::Local,
+            nodes: vec![],
+        }
+    }
+
+    /// Sets the first TCP port number to assign. Ports are numbered consecutively from it,
+    /// across all locations.
+    pub fn base_port(self, base_port: u16) -> Self {
+        self.next_tcp_port.set(base_port);
+        self
+    }
+
+    /// Builds Linux binaries, rather than binaries for the local machine.
+    pub fn linux(mut self, compile_type: LinuxCompileType) -> Self {
+        self.target = HostTargetType::Linux(compile_type);
+        self
+    }
+
+    /// Builds the binary of every location and writes it into `bin/` of `out_dir`, along with
+    /// the `manifest.json` describing how to launch them. Must be called after
+    /// [`DeployFlow::deploy`].
+    pub async fn write_artifacts(
+        &self,
+        out_dir: impl AsRef<Path>,
+    ) -> Result<ArtifactManifest, Error> {
+        let out_dir = out_dir.as_ref();
+        std::fs::create_dir_all(out_dir.join("bin"))?;
+
+        let mut manifest = ArtifactManifest::default();
+        for node in &self.nodes {
+            let name = node.name();
+            let (bin_name, config) = node
+                .state
+                .borrow()
+                .build
+                .clone()
+                .expect("No build config set - did you call deploy?");
+
+            let mut rust_crate = RustCrate::new(&config.project_dir, &config.project_dir)
+                .target_dir(&config.target_dir)
+                .example(bin_name)
+                .no_default_features()
+                .features(["hydro___feature_deploy_integration"]);
+            if let Some(features) = config.features {
+                rust_crate = rust_crate.features(features);
+            }
+            rust_crate = rust_crate
+                .build_env("STAGELEFT_TRYBUILD_BUILD_STAGED", "1")
+                .config("build.incremental = false");
+
+            let build_output = build_crate_memoized(rust_crate.get_build_params(self.target))
+                .await
+                .map_err(|e| Error::other(format!("Failed to build `{}`: {:?}", name, e)))?;
+
+            let binary = Path::new("bin").join(&name);
+            std::fs::write(out_dir.join(&binary), &build_output.bin_data)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(
+                    out_dir.join(&binary),
+                    std::fs::Permissions::from_mode(0o755),
+                )?;
+            }
+
+            let instance_names = node.instance_names();
+            let state = node.state.borrow();
+            for (member, (instance_name, wiring)) in
+                instance_names.into_iter().zip(&state.instances).enumerate()
+            {
+                manifest.instances.insert(
+                    instance_name,
+                    InstanceArtifact {
+                        location: name.clone(),
+                        member_id: node.is_cluster.then_some(member as u32),
+                        binary: binary.clone(),
+                        bind: wiring.bind.clone(),
+                        connect: wiring.connect.clone(),
+                        meta: wiring.meta.clone().unwrap_or_default(),
+                    },
+                );
+            }
+        }
+
+        std::fs::write(
+            out_dir.join("manifest.json"),
+            serde_json::to_string_pretty(&manifest).map_err(Error::other)?,
+        )?;
+
+        Ok(manifest)
+    }
+}
+
+impl<'a> DeployFlow<'a, ArtifactDeploy> {
+    /// Compiles every location into a standalone binary and writes them into `out_dir`, along
+    /// with a manifest describing how to launch and wire them together. See the
+    /// [`deploy_graph_artifacts`](self) module for the manifest format.
+    ///
+    /// To configure the build, use [`Self::deploy`] with an [`ArtifactDeployment`] and then call
+    /// [`ArtifactDeployment::write_artifacts`].
+    pub async fn compile_to_artifacts(
+        self,
+        out_dir: impl AsRef<Path>,
+    ) -> Result<ArtifactManifest, Error> {
+        let mut deployment = ArtifactDeployment::new();
+        let _nodes = self.deploy(&mut deployment);
+        deployment.write_artifacts(out_dir).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merged_bind_config_tags_each_sender() {
+        let config = merged_bind_config(40000, 2);
+        assert_eq!(
+            r#"{"Merge":[{"Tagged":[{"TcpPort":["0.0.0.0",40000]},0]},{"Tagged":[{"TcpPort":["0.0.0.0",40001]},1]}]}"#,
+            serde_json::to_string(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn tcp_ports_are_unique_across_deployment() {
+        let deployment = ArtifactDeployment::new().base_port(50000);
+        let process = ArtifactNode::new(LocationKey::TEST_KEY_1, "leader", None);
+        let cluster = ArtifactNode::new(LocationKey::TEST_KEY_2, "workers", Some(2));
+        for node in [&process, &cluster] {
+            node.state.borrow_mut().next_tcp_port = Some(deployment.next_tcp_port.clone());
+        }
+
+        <ArtifactDeploy as Deploy<'_>>::o2m_connect(
+            &process,
+            &"port_0".to_owned(),
+            &cluster,
+            &"port_1".to_owned(),
+        )();
+        <ArtifactDeploy as Deploy<'_>>::m2o_connect(
+            &cluster,
+            &"port_2".to_owned(),
+            &process,
+            &"port_3".to_owned(),
+        )();
+        <ArtifactDeploy as Deploy<'_>>::m2m_connect(
+            &cluster,
+            &"port_4".to_owned(),
+            &cluster,
+            &"port_5".to_owned(),
+        )();
+
+        fn tcp_ports(config: &ServerBindConfig, out: &mut Vec<u16>) {
+            match config {
+                ServerBindConfig::TcpPort(_, Some(port)) => out.push(*port),
+                ServerBindConfig::Merge(configs) => {
+                    configs.iter().for_each(|config| tcp_ports(config, out))
+                }
+                ServerBindConfig::Tagged(config, _) => tcp_ports(config, out),
+                _ => panic!("unexpected bind config {:?}", config),
+            }
+        }
+        let mut ports = vec![];
+        for node in [&process, &cluster] {
+            for instance in &node.state.borrow().instances {
+                instance
+                    .bind
+                    .values()
+                    .for_each(|config| tcp_ports(config, &mut ports));
+            }
+        }
+
+        // 2 members for o2m, 2 senders for m2o, and 2 senders to each of 2 members for m2m.
+        assert_eq!(ports.len(), 8);
+        let mut unique = ports.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique, (50000..50008).collect::<Vec<_>>());
+
+        // Every connection names a port its peer actually binds.
+        let workers_1 = &cluster.state.borrow().instances[1];
+        let PeerPort::Demux(demux) = &process.state.borrow().instances[0].connect["port_0"] else {
+            panic!("expected a demux");
+        };
+        let PeerPort::Tcp { instance, port } = &demux[&1] else {
+            panic!("expected a TCP port");
+        };
+        assert_eq!(instance, "workers-1");
+        let mut bound = vec![];
+        tcp_ports(&workers_1.bind["port_1"], &mut bound);
+        assert_eq!(bound, vec![*port]);
+    }
+
+    #[test]
+    fn peer_port_resolves_demux() {
+        let peer = PeerPort::Demux(BTreeMap::from([
+            (
+                0,
+                PeerPort::Tcp {
+                    instance: "workers-0".to_owned(),
+                    port: 40000,
+                },
+            ),
+            (
+                1,
+                PeerPort::Tcp {
+                    instance: "workers-1".to_owned(),
+                    port: 40000,
+                },
+            ),
+        ]));
+        let resolved = peer.resolve(&|instance| match instance {
+            "workers-0" => "10.0.0.1".parse().unwrap(),
+            "workers-1" => "10.0.0.2".parse().unwrap(),
+            _ => unreachable!(),
+        });
+        assert_eq!(
+            r#"{"Demux":{"0":{"TcpPort":"10.0.0.1:40000"},"1":{"TcpPort":"10.0.0.2:40000"}}}"#,
+            serde_json::to_string(&resolved).unwrap()
+        );
+    }
+}
@@ -576,6 +576,7 @@ pub struct DockerDeploy {
     docker_clusters: Vec<DockerDeployClusterSpec>,
     network: DockerNetwork,
     deployment_instance: String,
+    frame_checksums: bool,
 }
 
 #[instrument(level = "trace", skip_all, fields(%image_name, %container_name, %network_name, %deployment_instance))]
@@ -774,9 +775,17 @@ impl DockerDeploy {
             docker_clusters: Vec::new(),
             network,
             deployment_instance: nanoid!(6, &CONTAINER_ALPHABET),
+            frame_checksums: false,
         }
     }
 
+    /// Enables checksums on the frames sent between containers, see
+    /// [`FrameCodec`](hydro_deploy_integration::integrity::FrameCodec).
+    pub fn frame_checksums(mut self, enabled: bool) -> Self {
+        self.frame_checksums = enabled;
+        self
+    }
+
     /// Add an internal docker service to the deployment.
     pub fn add_localhost_docker(
         &mut self,
@@ -1002,7 +1011,7 @@ impl<'a> Deploy<'a> for DockerDeploy {
 
     #[instrument(level = "trace", skip_all, fields(p1 = p1.name, %p1_port, p2 = p2.name, %p2_port))]
     fn o2o_sink_source(
-        _env: &mut Self::InstantiateEnv,
+        env: &mut Self::InstantiateEnv,
         p1: &Self::Process,
         p1_port: &<Self::Process as Node>::Port,
         p2: &Self::Process,
@@ -1021,6 +1030,7 @@ impl<'a> Deploy<'a> for DockerDeploy {
         deploy_containerized_o2o(
             &p2.name,
             name.expect("channel name is required for containerized deployment"),
+            FrameChecksums(env.frame_checksums),
         )
     }
 
@@ -1040,7 +1050,7 @@ impl<'a> Deploy<'a> for DockerDeploy {
 
     #[instrument(level = "trace", skip_all, fields(p1 = p1.name, %p1_port, c2 = c2.name, %c2_port))]
     fn o2m_sink_source(
-        _env: &mut Self::InstantiateEnv,
+        env: &mut Self::InstantiateEnv,
         p1: &Self::Process,
         p1_port: &<Self::Process as Node>::Port,
         c2: &Self::Cluster,
@@ -1058,6 +1068,7 @@ impl<'a> Deploy<'a> for DockerDeploy {
 
         deploy_containerized_o2m(
             name.expect("channel name is required for containerized deployment"),
+            FrameChecksums(env.frame_checksums),
         )
     }
 
@@ -1077,7 +1088,7 @@ impl<'a> Deploy<'a> for DockerDeploy {
 
     #[instrument(level = "trace", skip_all, fields(c1 = c1.name, %c1_port, p2 = p2.name, %p2_port))]
     fn m2o_sink_source(
-        _env: &mut Self::InstantiateEnv,
+        env: &mut Self::InstantiateEnv,
         c1: &Self::Cluster,
         c1_port: &<Self::Cluster as Node>::Port,
         p2: &Self::Process,
@@ -1096,6 +1107,7 @@ impl<'a> Deploy<'a> for DockerDeploy {
         deploy_containerized_m2o(
             &p2.name,
             name.expect("channel name is required for containerized deployment"),
+            FrameChecksums(env.frame_checksums),
         )
     }
 
@@ -1115,7 +1127,7 @@ impl<'a> Deploy<'a> for DockerDeploy {
 
     #[instrument(level = "trace", skip_all, fields(c1 = c1.name, %c1_port, c2 = c2.name, %c2_port))]
     fn m2m_sink_source(
-        _env: &mut Self::InstantiateEnv,
+        env: &mut Self::InstantiateEnv,
         c1: &Self::Cluster,
         c1_port: &<Self::Cluster as Node>::Port,
         c2: &Self::Cluster,
@@ -1133,6 +1145,7 @@ impl<'a> Deploy<'a> for DockerDeploy {
 
         deploy_containerized_m2m(
             name.expect("channel name is required for containerized deployment"),
+            FrameChecksums(env.frame_checksums),
         )
     }
 
@@ -1204,6 +1217,16 @@ impl<'a> Deploy<'a> for DockerDeploy {
         parse_quote!(#sink_ident)
     }
 
+    fn e2o_http_source(
+        _extra_stmts: &mut Vec<syn::Stmt>,
+        _p2: &Self::Process,
+        _p2_port: &<Self::Process as Node>::Port,
+        _route: &str,
+        _shared_handle: String,
+    ) -> syn::Expr {
+        panic!("Docker deployments do not support HTTP external sources")
+    }
+
     #[instrument(level = "trace", skip_all, fields(p1 = p1.name, %p1_port, p2 = p2.name, %p2_port, %shared_handle))]
     fn e2o_source(
         extra_stmts: &mut Vec<syn::Stmt>,
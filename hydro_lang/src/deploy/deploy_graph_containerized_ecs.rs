@@ -324,7 +324,9 @@ impl<'a> RegisterPort<'a, EcsDeploy> for EcsDeployExternal {
 }
 
 /// Represents an aws ecs deployment.
-pub struct EcsDeploy;
+pub struct EcsDeploy {
+    frame_checksums: bool,
+}
 
 impl Default for EcsDeploy {
     fn default() -> Self {
@@ -335,7 +337,16 @@ impl Default for EcsDeploy {
 impl EcsDeploy {
     /// Creates a new ecs deployment.
     pub fn new() -> Self {
-        Self
+        Self {
+            frame_checksums: false,
+        }
+    }
+
+    /// Enables checksums on the frames sent between tasks, see
+    /// [`FrameCodec`](hydro_deploy_integration::integrity::FrameCodec).
+    pub fn frame_checksums(mut self, enabled: bool) -> Self {
+        self.frame_checksums = enabled;
+        self
     }
 
     /// Add an internal ecs process to the deployment.
@@ -451,7 +462,7 @@ impl<'a> Deploy<'a> for EcsDeploy {
 
     #[instrument(level = "trace", skip_all, fields(p1 = p1.name, %p1_port, p2 = p2.name, %p2_port))]
     fn o2o_sink_source(
-        _env: &mut Self::InstantiateEnv,
+        env: &mut Self::InstantiateEnv,
         p1: &Self::Process,
         p1_port: &<Self::Process as Node>::Port,
         p2: &Self::Process,
@@ -470,6 +481,7 @@ impl<'a> Deploy<'a> for EcsDeploy {
         deploy_containerized_o2o(
             &p2.name,
             name.expect("channel name is required for containerized deployment"),
+            FrameChecksums(env.frame_checksums),
         )
     }
 
@@ -492,7 +504,7 @@ impl<'a> Deploy<'a> for EcsDeploy {
 
     #[instrument(level = "trace", skip_all, fields(p1 = p1.name, %p1_port, c2 = c2.name, %c2_port))]
     fn o2m_sink_source(
-        _env: &mut Self::InstantiateEnv,
+        env: &mut Self::InstantiateEnv,
         p1: &Self::Process,
         p1_port: &<Self::Process as Node>::Port,
         c2: &Self::Cluster,
@@ -510,6 +522,7 @@ impl<'a> Deploy<'a> for EcsDeploy {
 
         deploy_containerized_o2m(
             name.expect("channel name is required for containerized deployment"),
+            FrameChecksums(env.frame_checksums),
         )
     }
 
@@ -532,7 +545,7 @@ impl<'a> Deploy<'a> for EcsDeploy {
 
     #[instrument(level = "trace", skip_all, fields(c1 = c1.name, %c1_port, p2 = p2.name, %p2_port))]
     fn m2o_sink_source(
-        _env: &mut Self::InstantiateEnv,
+        env: &mut Self::InstantiateEnv,
         c1: &Self::Cluster,
         c1_port: &<Self::Cluster as Node>::Port,
         p2: &Self::Process,
@@ -551,6 +564,7 @@ impl<'a> Deploy<'a> for EcsDeploy {
         deploy_containerized_m2o(
             &p2.name,
             name.expect("channel name is required for containerized deployment"),
+            FrameChecksums(env.frame_checksums),
         )
     }
 
@@ -573,7 +587,7 @@ impl<'a> Deploy<'a> for EcsDeploy {
 
     #[instrument(level = "trace", skip_all, fields(c1 = c1.name, %c1_port, c2 = c2.name, %c2_port))]
     fn m2m_sink_source(
-        _env: &mut Self::InstantiateEnv,
+        env: &mut Self::InstantiateEnv,
         c1: &Self::Cluster,
         c1_port: &<Self::Cluster as Node>::Port,
         c2: &Self::Cluster,
@@ -591,6 +605,7 @@ impl<'a> Deploy<'a> for EcsDeploy {
 
         deploy_containerized_m2m(
             name.expect("channel name is required for containerized deployment"),
+            FrameChecksums(env.frame_checksums),
         )
     }
 
@@ -667,6 +682,16 @@ impl<'a> Deploy<'a> for EcsDeploy {
         parse_quote!(#sink_ident)
     }
 
+    fn e2o_http_source(
+        _extra_stmts: &mut Vec<syn::Stmt>,
+        _p2: &Self::Process,
+        _p2_port: &<Self::Process as Node>::Port,
+        _route: &str,
+        _shared_handle: String,
+    ) -> syn::Expr {
+        panic!("ECS deployments do not support HTTP external sources")
+    }
+
     #[instrument(level = "trace", skip_all, fields(p1 = p1.name, %p1_port, p2 = p2.name, %p2_port, ?codec_type, %shared_handle))]
     fn e2o_source(
         extra_stmts: &mut Vec<syn::Stmt>,
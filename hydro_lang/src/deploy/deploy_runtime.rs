@@ -81,7 +81,10 @@ pub(super) fn deploy_o2o(
     (
         { q!(env.port(p1_port).connect::<ConnectedDirect>().into_sink()).splice_untyped_ctx(&()) },
         {
-            q!(env.port(p2_port).connect::<ConnectedDirect>().into_source()).splice_untyped_ctx(&())
+            q!(env
+                .drain
+                .close_on_drain(env.port(p2_port).connect::<ConnectedDirect>().into_source()))
+            .splice_untyped_ctx(&())
         },
     )
 }
@@ -102,7 +105,10 @@ pub(super) fn deploy_o2m(
             .splice_untyped_ctx(&())
         },
         {
-            q!(env.port(c2_port).connect::<ConnectedDirect>().into_source()).splice_untyped_ctx(&())
+            q!(env
+                .drain
+                .close_on_drain(env.port(c2_port).connect::<ConnectedDirect>().into_source()))
+            .splice_untyped_ctx(&())
         },
     )
 }
@@ -116,9 +122,12 @@ pub(super) fn deploy_m2o(
         { q!(env.port(c1_port).connect::<ConnectedDirect>().into_sink()).splice_untyped_ctx(&()) },
         {
             q!({
-                env.port(p2_port)
-                    .connect::<ConnectedTagged<ConnectedDirect>>()
-                    .into_source()
+                env.drain
+                    .close_on_drain(
+                        env.port(p2_port)
+                            .connect::<ConnectedTagged<ConnectedDirect>>()
+                            .into_source(),
+                    )
                     .map(|v| v.map(|(k, v)| (TaglessMemberId::from_raw_id(k), v)))
             })
             .splice_untyped_ctx(&())
@@ -143,9 +152,12 @@ pub(super) fn deploy_m2m(
         },
         {
             q!({
-                env.port(c2_port)
-                    .connect::<ConnectedTagged<ConnectedDirect>>()
-                    .into_source()
+                env.drain
+                    .close_on_drain(
+                        env.port(c2_port)
+                            .connect::<ConnectedTagged<ConnectedDirect>>()
+                            .into_source(),
+                    )
                     .map(|v| v.map(|(k, v)| (TaglessMemberId::from_raw_id(k), v)))
             })
             .splice_untyped_ctx(&())
@@ -158,7 +170,10 @@ pub(super) fn deploy_e2o(
     _e1_port: &str,
     p2_port: &str,
 ) -> syn::Expr {
-    q!(env.port(p2_port).connect::<ConnectedDirect>().into_source()).splice_untyped_ctx(&())
+    q!(env
+        .drain
+        .close_on_drain(env.port(p2_port).connect::<ConnectedDirect>().into_source()))
+    .splice_untyped_ctx(&())
 }
 
 pub(super) fn deploy_o2e(
@@ -15,6 +15,7 @@ use std::time::Duration;
 
 use bytes::BytesMut;
 use futures::{FutureExt, Sink, SinkExt, Stream, StreamExt};
+use hydro_deploy_integration::integrity::FrameCodec;
 use proc_macro2::Span;
 use sinktools::demux_map_lazy::LazyDemuxSink;
 use sinktools::lazy::{LazySink, LazySource};
@@ -76,10 +77,7 @@ pub struct ChannelHandshake {
 }
 
 /// A dispatched channel connection: optional sender ID and the read stream.
-type MuxConnection = (
-    Option<String>,
-    FramedRead<OwnedReadHalf, LengthDelimitedCodec>,
-);
+type MuxConnection = (Option<String>, FramedRead<OwnedReadHalf, FrameCodec>);
 
 /// A shared accept loop that listens on a single port and dispatches
 /// incoming connections to the right consumer based on the channel name
@@ -90,18 +88,21 @@ type MuxConnection = (
 pub struct ChannelMux {
     /// Map from channel name to a sender that delivers accepted connections.
     channels: std::sync::Mutex<HashMap<String, tokio::sync::mpsc::UnboundedSender<MuxConnection>>>,
+    /// Whether frames carry checksums, see [`FrameCodec`].
+    checksums: bool,
 }
 
 impl Default for ChannelMux {
     fn default() -> Self {
-        Self::new()
+        Self::new(false)
     }
 }
 
 impl ChannelMux {
-    pub fn new() -> Self {
+    pub fn new(checksums: bool) -> Self {
         Self {
             channels: std::sync::Mutex::new(HashMap::new()),
+            checksums,
         }
     }
 
@@ -129,7 +130,7 @@ impl ChannelMux {
             let mux = self.clone();
             tokio::spawn(async move {
                 let (rx, _tx) = stream.into_split();
-                let mut source = FramedRead::new(rx, LengthDelimitedCodec::new());
+                let mut source = FramedRead::new(rx, FrameCodec::new(mux.checksums));
 
                 let Some(Ok(magic_frame)) = source.next().await else {
                     warn!(name: "magic_failed", ?peer, "no magic frame");
@@ -201,13 +202,14 @@ impl ChannelMux {
 /// Get or initialize the global ChannelMux for this process.
 ///
 /// The first call creates the TcpListener and spawns the accept loop.
-/// Subsequent calls return the same `Arc<ChannelMux>`.
-pub fn get_or_init_channel_mux() -> Arc<ChannelMux> {
+/// Subsequent calls return the same `Arc<ChannelMux>`. All calls pass the same `checksums`, since
+/// it is fixed for a deployment.
+pub fn get_or_init_channel_mux(checksums: bool) -> Arc<ChannelMux> {
     use std::sync::OnceLock;
     static MUX: OnceLock<Arc<ChannelMux>> = OnceLock::new();
 
     MUX.get_or_init(|| {
-        let mux = Arc::new(ChannelMux::new());
+        let mux = Arc::new(ChannelMux::new(checksums));
         let mux_clone = mux.clone();
 
         // Spawn the accept loop in a background task.
@@ -229,7 +231,7 @@ pub fn get_or_init_channel_mux() -> Arc<ChannelMux> {
 /// Sends a [`ChannelMagic`], then a [`ChannelProtocolVersion`], then a
 /// [`ChannelHandshake`] as three separate frames over the given sink.
 pub async fn send_handshake(
-    sink: &mut FramedWrite<TcpStream, LengthDelimitedCodec>,
+    sink: &mut FramedWrite<TcpStream, FrameCodec>,
     channel_name: &str,
     sender_id: Option<&str>,
 ) -> Result<(), std::io::Error> {
@@ -254,7 +256,11 @@ pub async fn send_handshake(
     Ok(())
 }
 
-pub fn deploy_containerized_o2o(target: &str, channel_name: &str) -> (syn::Expr, syn::Expr) {
+pub fn deploy_containerized_o2o(
+    target: &str,
+    channel_name: &str,
+    checksums: FrameChecksums,
+) -> (syn::Expr, syn::Expr) {
     (
         q!(LazySink::<_, _, _, bytes::Bytes>::new(move || Box::pin(
             async move {
@@ -263,7 +269,7 @@ pub fn deploy_containerized_o2o(target: &str, channel_name: &str) -> (syn::Expr,
                 debug!(name: "connecting", %target, %channel_name);
 
                 let stream = TcpStream::connect(&target).await?;
-                let mut sink = FramedWrite::new(stream, LengthDelimitedCodec::new());
+                let mut sink = FramedWrite::new(stream, FrameCodec::new(checksums));
 
                 self::send_handshake(&mut sink, channel_name, None).await?;
 
@@ -273,7 +279,7 @@ pub fn deploy_containerized_o2o(target: &str, channel_name: &str) -> (syn::Expr,
         .splice_untyped_ctx(&()),
         q!(LazySource::new(move || Box::pin(async move {
             let channel_name = channel_name;
-            let mux = self::get_or_init_channel_mux();
+            let mux = self::get_or_init_channel_mux(checksums);
             let mut rx = mux.register(channel_name.to_owned());
 
             let (_sender_id, source) = rx.recv().await.ok_or_else(|| {
@@ -288,7 +294,10 @@ pub fn deploy_containerized_o2o(target: &str, channel_name: &str) -> (syn::Expr,
     )
 }
 
-pub fn deploy_containerized_o2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
+pub fn deploy_containerized_o2m(
+    channel_name: &str,
+    checksums: FrameChecksums,
+) -> (syn::Expr, syn::Expr) {
     (
         q!(sinktools::demux_map_lazy::<_, _, _, _>(
             move |key: &TaglessMemberId| {
@@ -302,7 +311,7 @@ pub fn deploy_containerized_o2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
                         debug!(name: "connecting", %target, channel_name = %channel_name);
 
                         let stream = TcpStream::connect(&target).await?;
-                        let mut sink = FramedWrite::new(stream, LengthDelimitedCodec::new());
+                        let mut sink = FramedWrite::new(stream, FrameCodec::new(checksums));
 
                         self::send_handshake(&mut sink, &channel_name, None).await?;
 
@@ -314,7 +323,7 @@ pub fn deploy_containerized_o2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
         .splice_untyped_ctx(&()),
         q!(LazySource::new(move || Box::pin(async move {
             let channel_name = channel_name;
-            let mux = self::get_or_init_channel_mux();
+            let mux = self::get_or_init_channel_mux(checksums);
             let mut rx = mux.register(channel_name.to_owned());
 
             let (_sender_id, source) = rx.recv().await.ok_or_else(|| {
@@ -329,7 +338,11 @@ pub fn deploy_containerized_o2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
     )
 }
 
-pub fn deploy_containerized_m2o(target_host: &str, channel_name: &str) -> (syn::Expr, syn::Expr) {
+pub fn deploy_containerized_m2o(
+    target_host: &str,
+    channel_name: &str,
+    checksums: FrameChecksums,
+) -> (syn::Expr, syn::Expr) {
     (
         q!(LazySink::<_, _, _, bytes::Bytes>::new(move || {
             Box::pin(async move {
@@ -338,7 +351,7 @@ pub fn deploy_containerized_m2o(target_host: &str, channel_name: &str) -> (syn::
                 debug!(name: "connecting", %target, %channel_name);
 
                 let stream = TcpStream::connect(&target).await?;
-                let mut sink = FramedWrite::new(stream, LengthDelimitedCodec::new());
+                let mut sink = FramedWrite::new(stream, FrameCodec::new(checksums));
 
                 let container_name = std::env::var("CONTAINER_NAME").unwrap();
                 self::send_handshake(&mut sink, channel_name, Some(&container_name)).await?;
@@ -349,7 +362,7 @@ pub fn deploy_containerized_m2o(target_host: &str, channel_name: &str) -> (syn::
         .splice_untyped_ctx(&()),
         q!(LazySource::new(move || Box::pin(async move {
             let channel_name = channel_name;
-            let mux = self::get_or_init_channel_mux();
+            let mux = self::get_or_init_channel_mux(checksums);
             let mut rx = mux.register(channel_name.to_owned());
 
             Result::<_, std::io::Error>::Ok(
@@ -375,7 +388,10 @@ pub fn deploy_containerized_m2o(target_host: &str, channel_name: &str) -> (syn::
     )
 }
 
-pub fn deploy_containerized_m2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
+pub fn deploy_containerized_m2m(
+    channel_name: &str,
+    checksums: FrameChecksums,
+) -> (syn::Expr, syn::Expr) {
     (
         q!(sinktools::demux_map_lazy::<_, _, _, _>(
             move |key: &TaglessMemberId| {
@@ -389,7 +405,7 @@ pub fn deploy_containerized_m2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
                         debug!(name: "connecting", %target, channel_name = %channel_name);
 
                         let stream = TcpStream::connect(&target).await?;
-                        let mut sink = FramedWrite::new(stream, LengthDelimitedCodec::new());
+                        let mut sink = FramedWrite::new(stream, FrameCodec::new(checksums));
 
                         let container_name = std::env::var("CONTAINER_NAME").unwrap();
                         self::send_handshake(&mut sink, &channel_name, Some(&container_name))
@@ -403,7 +419,7 @@ pub fn deploy_containerized_m2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
         .splice_untyped_ctx(&()),
         q!(LazySource::new(move || Box::pin(async move {
             let channel_name = channel_name;
-            let mux = self::get_or_init_channel_mux();
+            let mux = self::get_or_init_channel_mux(checksums);
             let mut rx = mux.register(channel_name.to_owned());
 
             Result::<_, std::io::Error>::Ok(
@@ -429,6 +445,30 @@ pub fn deploy_containerized_m2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
     )
 }
 
+/// Whether the frames of a deployment carry checksums, see [`FrameCodec`]. Turns into a `bool`
+/// inside `q!` code.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameChecksums(pub bool);
+
+impl<Ctx> FreeVariableWithContextWithProps<Ctx, ()> for FrameChecksums {
+    type O = bool;
+
+    fn to_tokens(self, _ctx: &Ctx) -> (QuoteTokens, ())
+    where
+        Self: Sized,
+    {
+        let checksums = self.0;
+
+        (
+            QuoteTokens {
+                prelude: None,
+                expr: Some(quote::quote! { #checksums }),
+            },
+            (),
+        )
+    }
+}
+
 pub struct SocketIdent {
     pub socket_ident: syn::Ident,
 }
@@ -452,6 +492,8 @@ impl<Ctx> FreeVariableWithContextWithProps<Ctx, ()> for SocketIdent {
     }
 }
 
+/// External clients speak plain length-delimited frames, so external ports never carry
+/// checksums.
 pub fn deploy_containerized_external_sink_source_ident(socket_ident: syn::Ident) -> syn::Expr {
     let socket_ident = SocketIdent { socket_ident };
 
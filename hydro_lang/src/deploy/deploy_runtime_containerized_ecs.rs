@@ -7,6 +7,7 @@
 use std::time::Duration;
 
 use futures::{SinkExt, Stream, StreamExt};
+use hydro_deploy_integration::integrity::FrameCodec;
 use sinktools::lazy::{LazySink, LazySource};
 use sinktools::lazy_sink_source::LazySinkSource;
 use stageleft::{QuotedWithContext, q};
@@ -17,8 +18,8 @@ use tracing::{Instrument, debug, instrument, span, trace, trace_span};
 
 pub use super::deploy_runtime_containerized::{
     CHANNEL_MAGIC, CHANNEL_MUX_PORT, CHANNEL_PROTOCOL_VERSION, ChannelHandshake, ChannelMagic,
-    ChannelMux, ChannelProtocolVersion, SocketIdent, cluster_ids, get_or_init_channel_mux,
-    send_handshake,
+    ChannelMux, ChannelProtocolVersion, FrameChecksums, SocketIdent, cluster_ids,
+    get_or_init_channel_mux, send_handshake,
 };
 use crate::location::dynamic::LocationId;
 use crate::location::member_id::TaglessMemberId;
@@ -27,6 +28,7 @@ use crate::location::{LocationKey, MembershipEvent};
 pub fn deploy_containerized_o2o(
     target_task_family: &str,
     channel_name: &str,
+    checksums: FrameChecksums,
 ) -> (syn::Expr, syn::Expr) {
     (
         q!(LazySink::<_, _, _, bytes::Bytes>::new(move || Box::pin(
@@ -39,7 +41,7 @@ pub fn deploy_containerized_o2o(
                 debug!(name: "connecting", %target, %target_task_family, %task_id, %channel_name);
 
                 let stream = TcpStream::connect(&target).await?;
-                let mut sink = FramedWrite::new(stream, LengthDelimitedCodec::new());
+                let mut sink = FramedWrite::new(stream, FrameCodec::new(checksums));
 
                 self::send_handshake(&mut sink, channel_name, None).await?;
 
@@ -49,7 +51,7 @@ pub fn deploy_containerized_o2o(
         .splice_untyped_ctx(&()),
         q!(LazySource::new(move || Box::pin(async move {
             let channel_name = channel_name;
-            let mux = self::get_or_init_channel_mux();
+            let mux = self::get_or_init_channel_mux(checksums);
             let mut rx = mux.register(channel_name.to_owned());
 
             let (_sender_id, source) = rx.recv().await.ok_or_else(|| {
@@ -64,7 +66,10 @@ pub fn deploy_containerized_o2o(
     )
 }
 
-pub fn deploy_containerized_o2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
+pub fn deploy_containerized_o2m(
+    channel_name: &str,
+    checksums: FrameChecksums,
+) -> (syn::Expr, syn::Expr) {
     (
         q!(sinktools::demux_map_lazy::<_, _, _, _>(
             move |key: &TaglessMemberId| {
@@ -79,7 +84,7 @@ pub fn deploy_containerized_o2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
                         debug!(name: "connecting", %target, %task_id, channel_name = %channel_name);
 
                         let stream = TcpStream::connect(&target).await?;
-                        let mut sink = FramedWrite::new(stream, LengthDelimitedCodec::new());
+                        let mut sink = FramedWrite::new(stream, FrameCodec::new(checksums));
 
                         self::send_handshake(&mut sink, &channel_name, None).await?;
 
@@ -91,7 +96,7 @@ pub fn deploy_containerized_o2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
         .splice_untyped_ctx(&()),
         q!(LazySource::new(move || Box::pin(async move {
             let channel_name = channel_name;
-            let mux = self::get_or_init_channel_mux();
+            let mux = self::get_or_init_channel_mux(checksums);
             let mut rx = mux.register(channel_name.to_owned());
 
             let (_sender_id, source) = rx.recv().await.ok_or_else(|| {
@@ -109,6 +114,7 @@ pub fn deploy_containerized_o2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
 pub fn deploy_containerized_m2o(
     target_task_family: &str,
     channel_name: &str,
+    checksums: FrameChecksums,
 ) -> (syn::Expr, syn::Expr) {
     (
         q!(LazySink::<_, _, _, bytes::Bytes>::new(move || {
@@ -121,7 +127,7 @@ pub fn deploy_containerized_m2o(
                 debug!(name: "connecting", %target, %target_task_family, %target_task_id, %channel_name);
 
                 let stream = TcpStream::connect(&target).await?;
-                let mut sink = FramedWrite::new(stream, LengthDelimitedCodec::new());
+                let mut sink = FramedWrite::new(stream, FrameCodec::new(checksums));
 
                 let self_task_id = self::get_self_task_id();
                 self::send_handshake(&mut sink, channel_name, Some(&self_task_id)).await?;
@@ -132,7 +138,7 @@ pub fn deploy_containerized_m2o(
         .splice_untyped_ctx(&()),
         q!(LazySource::new(move || Box::pin(async move {
             let channel_name = channel_name;
-            let mux = self::get_or_init_channel_mux();
+            let mux = self::get_or_init_channel_mux(checksums);
             let mut rx = mux.register(channel_name.to_owned());
 
             Result::<_, std::io::Error>::Ok(
@@ -159,7 +165,10 @@ pub fn deploy_containerized_m2o(
     )
 }
 
-pub fn deploy_containerized_m2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
+pub fn deploy_containerized_m2m(
+    channel_name: &str,
+    checksums: FrameChecksums,
+) -> (syn::Expr, syn::Expr) {
     (
         q!(sinktools::demux_map_lazy::<_, _, _, _>(
             move |key: &TaglessMemberId| {
@@ -174,7 +183,7 @@ pub fn deploy_containerized_m2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
                         debug!(name: "connecting", %target, %task_id, channel_name = %channel_name);
 
                         let stream = TcpStream::connect(&target).await?;
-                        let mut sink = FramedWrite::new(stream, LengthDelimitedCodec::new());
+                        let mut sink = FramedWrite::new(stream, FrameCodec::new(checksums));
 
                         let self_task_id = self::get_self_task_id();
                         self::send_handshake(&mut sink, &channel_name, Some(&self_task_id)).await?;
@@ -187,7 +196,7 @@ pub fn deploy_containerized_m2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
         .splice_untyped_ctx(&()),
         q!(LazySource::new(move || Box::pin(async move {
             let channel_name = channel_name;
-            let mux = self::get_or_init_channel_mux();
+            let mux = self::get_or_init_channel_mux(checksums);
             let mut rx = mux.register(channel_name.to_owned());
 
             Result::<_, std::io::Error>::Ok(
@@ -218,6 +227,8 @@ pub fn deploy_containerized_m2m(channel_name: &str) -> (syn::Expr, syn::Expr) {
     )
 }
 
+/// External clients speak plain length-delimited frames, so external ports never carry
+/// checksums.
 pub fn deploy_containerized_external_sink_source_ident(
     bind_addr: String,
     socket_ident: syn::Ident,
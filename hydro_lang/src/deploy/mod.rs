@@ -27,6 +27,18 @@ pub mod deploy_graph;
 #[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
 pub use deploy_graph::*;
 
+#[cfg(stageleft_runtime)]
+#[cfg(feature = "deploy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
+pub mod deploy_graph_artifacts;
+
+#[cfg(stageleft_runtime)]
+#[cfg(feature = "deploy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
+pub use deploy_graph_artifacts::{
+    ArtifactClusterSpec, ArtifactDeploy, ArtifactDeployment, ArtifactManifest, ArtifactProcessSpec,
+};
+
 #[cfg(stageleft_runtime)]
 #[cfg(feature = "docker_deploy")]
 #[cfg_attr(docsrs, doc(cfg(feature = "docker_deploy")))]
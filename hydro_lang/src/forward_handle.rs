@@ -86,6 +86,13 @@ impl<'a, C: ReceiverComplete<'a, ForwardRef>> ForwardHandle<'a, C> {
             _phantom: std::marker::PhantomData,
         }
     }
+
+    /// Marks the handle as completed, returning the cycle and location it must be completed with,
+    /// for handles completed by Hydro itself rather than through [`Self::complete`].
+    pub(crate) fn into_parts(mut self) -> (CycleId, LocationId) {
+        self.completed = true;
+        (self.cycle_id, self.expected_location.clone())
+    }
 }
 
 impl<'a, C: ReceiverComplete<'a, ForwardRef>> Drop for ForwardHandle<'a, C> {
@@ -18,7 +18,7 @@ stageleft::stageleft_no_entry_crate!();
 #[cfg_attr(docsrs, doc(cfg(feature = "runtime_support")))]
 #[doc(hidden)]
 pub mod runtime_support {
-    pub use ::{bincode, dfir_rs, slotmap, stageleft};
+    pub use ::{bincode, dfir_rs, serde_json, slotmap, stageleft};
     #[cfg(feature = "sim")]
     pub use colored;
     #[cfg(feature = "deploy_integration")]
@@ -26,6 +26,11 @@ pub mod runtime_support {
     #[cfg(feature = "tokio")]
     pub use tokio;
 
+    pub mod async_cache;
+    pub mod config;
+    pub mod fixture;
+    pub mod unique_window;
+
     #[cfg(feature = "deploy_integration")]
     pub mod launch;
 }
@@ -101,12 +106,28 @@ pub mod nondet;
 
 pub mod live_collections;
 
+#[cfg(feature = "local_store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "local_store")))]
+pub mod local_store;
+
 pub mod location;
 
 pub mod networking;
 
+#[cfg(feature = "object_store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "object_store")))]
+pub mod object_storage;
+
 pub mod properties;
 
+pub mod replicated;
+
+pub mod sketch;
+
+#[cfg(feature = "sql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sql")))]
+pub mod sql;
+
 pub mod telemetry;
 
 #[cfg(any(
@@ -141,8 +162,8 @@ pub mod viz;
 )]
 mod staging_util;
 
-#[cfg(feature = "deploy")]
-#[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
+#[cfg(any(feature = "deploy", feature = "sim"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "deploy", feature = "sim"))))]
 pub mod test_util;
 
 #[cfg(feature = "build")]
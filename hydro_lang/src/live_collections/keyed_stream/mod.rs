@@ -12,6 +12,8 @@ use stageleft::{IntoQuotedMut, QuotedWithContext, QuotedWithContextWithProps, q}
 use super::boundedness::{Bounded, Boundedness, IsBounded, Unbounded};
 use super::keyed_singleton::KeyedSingleton;
 use super::optional::Optional;
+use super::singleton::Singleton;
+use super::sliced::sliced;
 use super::stream::{
     ExactlyOnce, IsExactlyOnce, IsOrdered, MinOrder, MinRetries, NoOrder, Stream, TotalOrder,
 };
@@ -30,13 +32,14 @@ use crate::live_collections::stream::{
 #[cfg(stageleft_runtime)]
 use crate::location::dynamic::{DynLocation, LocationId};
 use crate::location::tick::DeferTick;
-use crate::location::{Atomic, Location, Tick, check_matching_location};
+use crate::location::{Atomic, Cluster, Location, MemberId, Tick, check_matching_location};
 use crate::manual_expr::ManualExpr;
 use crate::nondet::{NonDet, nondet};
 use crate::properties::{
     AggFuncAlgebra, ApplyMonotoneKeyedStream, ValidCommutativityFor, ValidIdempotenceFor,
     manual_proof,
 };
+use crate::sketch::CountMin;
 
 pub mod networking;
 
@@ -1969,6 +1972,95 @@ impl<'a, K, V, L: Location<'a>, B: Boundedness, O: Ordering, R: Retries>
             )
     }
 
+    /// Estimates the number of elements in each group with a [`CountMin`] sketch, producing a
+    /// [`Singleton`] which uses a fixed amount of memory regardless of the number of keys.
+    ///
+    /// Unlike [`KeyedStream::value_counts`], the estimated count for a key may be larger than the
+    /// true count (but never smaller). Sketches of the same stream taken at different times can
+    /// be combined with [`Stream::merge_lattice`], while the sketches of disjoint streams (such as
+    /// those of different cluster members) must be combined with [`CountMin::add`] or
+    /// [`CountMinByMember`](crate::sketch::CountMinByMember), since merging them undercounts.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let numbers = process
+    ///     .source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4), (1, 5)]))
+    ///     .into_keyed();
+    /// numbers
+    ///     .approx_count()
+    ///     .into_stream()
+    ///     .map(q!(|sketch| (sketch.estimate(&1), sketch.estimate(&2))))
+    /// # }, |mut stream| async move {
+    /// // (3, 2)
+    /// # assert_eq!(stream.next().await.unwrap(), (3, 2));
+    /// # }));
+    /// # }
+    /// ```
+    pub fn approx_count(self) -> Singleton<CountMin, L, B::StreamToMonotone>
+    where
+        R: IsExactlyOnce,
+        K: Hash,
+    {
+        self.entries().make_exactly_once().fold(
+            q!(|| crate::sketch::CountMin::default()),
+            q!(
+                |sketch, (key, _)| sketch.insert(&key, 1),
+                commutative = manual_proof!(/** counters are only incremented */),
+                monotone = manual_proof!(/** counters only increase */)
+            ),
+        )
+    }
+
+    /// Emits `()` for each key once `n` distinct values have been received for that key. Because
+    /// duplicate values are only counted once and their order does not matter, this works on any
+    /// keyed stream. Once a key reaches the threshold, its values are discarded and any later
+    /// values for it are ignored.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// process
+    ///     .source_iter(q!(vec![(1, 'a'), (2, 'a'), (1, 'a'), (1, 'b'), (2, 'a')]))
+    ///     .into_keyed()
+    ///     .count_until_threshold(q!(2))
+    /// #   .keys()
+    /// # }, |mut stream| async move {
+    /// // { 1: () }, since key 2 has only received one distinct value
+    /// # assert_eq!(stream.next().await.unwrap(), 1);
+    /// # }));
+    /// # }
+    /// ```
+    pub fn count_until_threshold(
+        self,
+        n: impl QuotedWithContext<'a, usize, L> + Copy + 'a,
+    ) -> KeyedSingleton<K, (), L, B::WithBoundedValue>
+    where
+        K: Clone + Eq + Hash,
+        V: Eq + Hash,
+    {
+        self.assume_retries_trusted::<ExactlyOnce>(
+            nondet!(/** duplicate values are only counted once */),
+        )
+        .assume_ordering_trusted::<TotalOrder>(
+            nondet!(/** the number of distinct values, and so whether the threshold has been reached, does not depend on their order */),
+        )
+        .fold_early_stop(
+            q!(|| std::collections::HashSet::new()),
+            q!(move |seen, v| {
+                seen.insert(v);
+                seen.len() >= n
+            }),
+        )
+        .map(q!(|_| ()))
+    }
+
     /// Like [`Stream::fold`] but in the spirit of SQL `GROUP BY`, aggregates the values in each
     /// group via the `comb` closure.
     ///
@@ -2713,6 +2805,23 @@ impl<'a, K1, K2, V, L: Location<'a>, B: Boundedness, O: Ordering, R: Retries>
     }
 }
 
+impl<'a, K, C, L: Location<'a>, B: Boundedness, O: Ordering, R: Retries>
+    KeyedStream<K, MemberId<C>, L, B, O, R>
+{
+    /// Like [`KeyedStream::count_until_threshold`], but emits `()` for each key once `n` distinct
+    /// members of the cluster `_of` have voted for it, such as acceptors responding to a ballot.
+    pub fn quorum(
+        self,
+        n: impl QuotedWithContext<'a, usize, L> + Copy + 'a,
+        _of: &Cluster<'a, C>,
+    ) -> KeyedSingleton<K, (), L, B::WithBoundedValue>
+    where
+        K: Clone + Eq + Hash,
+    {
+        self.count_until_threshold(n)
+    }
+}
+
 impl<'a, K, V, L: Location<'a>, O: Ordering, R: Retries> KeyedStream<K, V, L, Unbounded, O, R> {
     /// Produces a new keyed stream that "merges" the inputs by interleaving the elements
     /// of any overlapping groups. The result has [`NoOrder`] on each group because the
@@ -2779,6 +2888,218 @@ impl<'a, K, V, L: Location<'a>, O: Ordering, R: Retries> KeyedStream<K, V, L, Un
     {
         self.merge_unordered(other)
     }
+
+    /// Performs an as-of join with another keyed stream, matching each element of `self` with
+    /// the element of `other` under the same key that has the latest timestamp at or before its
+    /// own. Timestamps are extracted from the values with `self_ts` and `other_ts`.
+    ///
+    /// Each element of `self` is emitted once, paired with its match, or with [`None`] if no
+    /// element of `other` has an earlier or equal timestamp. If several elements of `other` share
+    /// the latest timestamp, the element of `self` is emitted once for each of them.
+    ///
+    /// Because a matching element of `other` may arrive after the element of `self` it should be
+    /// matched with, the join is driven by the `watermark`, which must guarantee that every
+    /// element of either input with a timestamp at or before the watermark has already arrived.
+    /// Elements of `self` are buffered until the watermark reaches their timestamp, and elements
+    /// of `other` are discarded once they are superseded by a later element that is at or before
+    /// the watermark.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let trades = process
+    ///     .source_iter(q!(vec![(1, (5, 'a')), (1, (12, 'b')), (2, (7, 'c'))]))
+    ///     .into_keyed();
+    /// let quotes = process
+    ///     .source_iter(q!(vec![(1, (3, 100)), (1, (10, 110)), (1, (13, 120))]))
+    ///     .into_keyed();
+    /// let watermark = process.singleton(q!(20));
+    /// trades
+    ///     .join_asof(quotes, watermark, q!(|(t, _)| *t), q!(|(t, _)| *t))
+    ///     .entries()
+    /// # }, |mut stream| async move {
+    /// // { 1: [((5, 'a'), Some((3, 100))), ((12, 'b'), Some((10, 110)))], 2: [((7, 'c'), None)] }
+    /// # let mut results = Vec::new();
+    /// # for _ in 0..3 {
+    /// #     results.push(stream.next().await.unwrap());
+    /// # }
+    /// # results.sort();
+    /// # assert_eq!(results, vec![(1, ((5, 'a'), Some((3, 100)))), (1, ((12, 'b'), Some((10, 110)))), (2, ((7, 'c'), None))]);
+    /// # }));
+    /// # }
+    /// ```
+    pub fn join_asof<V2, Ts, O2: Ordering, R2: Retries, B2: Boundedness, F1, F2>(
+        self,
+        other: KeyedStream<K, V2, L, Unbounded, O2, R2>,
+        watermark: impl Into<Optional<Ts, L, B2>>,
+        self_ts: impl IntoQuotedMut<'a, F1, L> + Copy,
+        other_ts: impl IntoQuotedMut<'a, F2, L> + Copy,
+    ) -> KeyedStream<K, (V, Option<V2>), L, Unbounded, NoOrder, <R as MinRetries<R2>>::Min>
+    where
+        K: Eq + Hash + Clone,
+        V: Clone,
+        V2: Clone,
+        Ts: Ord + Hash + Clone,
+        F1: Fn(&V) -> Ts + 'a,
+        F2: Fn(&V2) -> Ts + 'a,
+        R: MinRetries<R2>,
+    {
+        let location = self.location.clone();
+        let watermark: Optional<Ts, L, B2> = watermark.into();
+
+        let self_ts: ManualExpr<F1, _> =
+            ManualExpr::new(move |ctx: &L| self_ts.splice_fn1_borrow_ctx(ctx));
+        let left = KeyedStream::<K, (Ts, V), L, Unbounded, O, R>::new(
+            location.clone(),
+            HydroNode::Map {
+                f: q!({
+                    let ts = self_ts;
+                    move |(k, v)| (k, (ts(&v), v))
+                })
+                .splice_fn1_ctx::<(K, V), (K, (Ts, V))>(&location)
+                .into(),
+                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                metadata: location.new_node_metadata(
+                    KeyedStream::<K, (Ts, V), L, Unbounded, O, R>::collection_kind(),
+                ),
+            },
+        );
+
+        let other_ts: ManualExpr<F2, _> =
+            ManualExpr::new(move |ctx: &L| other_ts.splice_fn1_borrow_ctx(ctx));
+        let right = KeyedStream::<K, (Ts, V2), L, Unbounded, O2, R2>::new(
+            location.clone(),
+            HydroNode::Map {
+                f: q!({
+                    let ts = other_ts;
+                    move |(k, v)| (k, (ts(&v), v))
+                })
+                .splice_fn1_ctx::<(K, V2), (K, (Ts, V2))>(&location)
+                .into(),
+                input: Box::new(other.ir_node.replace(HydroNode::Placeholder)),
+                metadata: location.new_node_metadata(KeyedStream::<
+                    K,
+                    (Ts, V2),
+                    L,
+                    Unbounded,
+                    O2,
+                    R2,
+                >::collection_kind()),
+            },
+        );
+
+        let joined = sliced! {
+            let new_left = use(left.entries(), nondet!(
+                /// elements of `self` are buffered until the watermark reaches them
+            ));
+            let new_right = use(right.entries(), nondet!(
+                /// elements of `other` are buffered until they are superseded
+            ));
+            let watermark = use(watermark, nondet!(
+                /// the watermark guarantees that all elements at or before it have arrived,
+                /// so every element of `self` it releases sees all of its candidate matches
+            ));
+            let mut pending_left =
+                use::state_null::<Stream<(K, (Ts, V)), Tick<_>, Bounded, NoOrder, R>>();
+            let mut pending_right =
+                use::state_null::<Stream<(K, (Ts, V2)), Tick<_>, Bounded, NoOrder, R2>>();
+
+            let watermark = watermark.into_singleton();
+            let all_left = pending_left.chain(new_left).cross_singleton(watermark.clone());
+            let all_right = pending_right.chain(new_right);
+
+            let ready = all_left
+                .clone()
+                .filter(q!(|((_, (ts, _)), watermark)| watermark.as_ref().is_some_and(|w| ts <= w)))
+                .map(q!(|((k, (ts, v)), _)| ((k, ts), v)))
+                .into_keyed();
+            pending_left = all_left
+                .filter(q!(|((_, (ts, _)), watermark)| !watermark.as_ref().is_some_and(|w| ts <= w)))
+                .map(q!(|(item, _)| item));
+
+            let latest_before = ready
+                .clone()
+                .keys()
+                .join(all_right.clone())
+                .filter_map(q!(|(k, (ts, (other_ts, _)))| {
+                    if other_ts <= ts {
+                        Some(((k, ts), other_ts))
+                    } else {
+                        None
+                    }
+                }))
+                .into_keyed()
+                .reduce(q!(
+                    |latest, ts| {
+                        if ts > *latest {
+                            *latest = ts;
+                        }
+                    },
+                    commutative = manual_proof!(/** max is commutative */),
+                    idempotent = manual_proof!(/** max is idempotent */)
+                ));
+
+            let matches = latest_before
+                .entries()
+                .map(q!(|((k, ts), other_ts)| ((k.clone(), other_ts), (k, ts))))
+                .join(all_right.clone().map(q!(|(k, (ts, v))| ((k, ts), v))))
+                .map(q!(|(_, (key, v))| (key, v)))
+                .into_keyed();
+
+            let found = ready
+                .clone()
+                .join_keyed_stream(matches.clone())
+                .entries()
+                .map(q!(|((k, _), (v, other_v))| (k, (v, Some(other_v)))));
+            let not_found = ready
+                .filter_key_not_in(matches.keys())
+                .entries()
+                .map(q!(|((k, _), v)| (k, (v, None))));
+
+            let right_with_watermark = all_right.cross_singleton(watermark);
+            let floor = right_with_watermark
+                .clone()
+                .filter_map(q!(|((k, (ts, _)), watermark)| {
+                    if watermark.is_some_and(|w| ts <= w) {
+                        Some((k, ts))
+                    } else {
+                        None
+                    }
+                }))
+                .into_keyed()
+                .reduce(q!(
+                    |latest, ts| {
+                        if ts > *latest {
+                            *latest = ts;
+                        }
+                    },
+                    commutative = manual_proof!(/** max is commutative */),
+                    idempotent = manual_proof!(/** max is idempotent */)
+                ));
+            let after_watermark = right_with_watermark
+                .clone()
+                .filter(q!(|((_, (ts, _)), watermark)| !watermark.as_ref().is_some_and(|w| ts <= w)))
+                .map(q!(|(item, _)| item));
+            let at_floor = right_with_watermark
+                .map(q!(|(item, _)| item))
+                .into_keyed()
+                .join_keyed_singleton(floor)
+                .entries()
+                .filter(q!(|(_, ((ts, _), floor))| ts == floor))
+                .map(q!(|(k, (item, _))| (k, item)));
+            pending_right = after_watermark.chain(at_floor);
+
+            found.chain(not_found.weaken_retries::<<R as MinRetries<R2>>::Min>())
+        };
+
+        KeyedStream::new(
+            location,
+            joined.into_keyed().ir_node.replace(HydroNode::Placeholder),
+        )
+    }
 }
 
 impl<'a, K, V, L: Location<'a>, B: Boundedness, R: Retries> KeyedStream<K, V, L, B, TotalOrder, R> {
@@ -3385,6 +3706,53 @@ mod tests {
         // - one case: all three together (order of (1, 1), (1, 2) doesn't matter because batched is still unordered)
     }
 
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_count_until_threshold_unordered() {
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+
+        let (port, input) = node.sim_input::<_, NoOrder, _>();
+        let out_recv = input
+            .into_keyed()
+            .count_until_threshold(q!(2))
+            .keys()
+            .sim_output();
+
+        flow.sim().exhaustive(async || {
+            port.send_many_unordered([(1, 'a'), (1, 'a'), (2, 'a'), (1, 'b'), (2, 'a')]);
+            out_recv.assert_yields_only_unordered([1]).await;
+        });
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_quorum_of_cluster() {
+        use crate::location::cluster::CLUSTER_SELF_ID;
+
+        let mut flow = FlowBuilder::new();
+        let cluster = flow.cluster::<()>();
+        let node = flow.process::<()>();
+
+        // Every member votes for key 1, but only member 0 votes for key 2.
+        let out_recv = cluster
+            .source_iter(q!(vec![1, 1, 2]))
+            .filter(q!(|key| *key == 1 || CLUSTER_SELF_ID.get_raw_id() == 0))
+            .send(&node, TCP.fail_stop().bincode())
+            .entries()
+            .map(q!(|(member, key)| (key, member)))
+            .into_keyed()
+            .quorum(q!(2), &cluster)
+            .keys()
+            .sim_output();
+
+        flow.sim()
+            .with_cluster_size(&cluster, 3)
+            .exhaustive(async || {
+                out_recv.assert_yields_only_unordered([1]).await;
+            });
+    }
+
     #[cfg(feature = "sim")]
     #[test]
     #[should_panic]
@@ -3900,4 +4268,54 @@ mod tests {
         );
         assert_eq!(instances, 2944);
     }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_join_asof_waits_for_watermark() {
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+
+        let (left_send, left) = node.sim_input::<(u32, (u32, char)), _, _>();
+        let (right_send, right) = node.sim_input::<(u32, (u32, char)), _, _>();
+        let (watermark_send, watermark) = node.sim_input::<u32, _, _>();
+
+        let out_recv = left
+            .into_keyed()
+            .join_asof(
+                right.into_keyed(),
+                watermark.reduce(q!(|latest, w| *latest = w)),
+                q!(|(ts, _)| *ts),
+                q!(|(ts, _)| *ts),
+            )
+            .entries()
+            .sim_output();
+
+        flow.sim().exhaustive(async || {
+            left_send.send((1, (5, 'a')));
+            left_send.send((2, (6, 'b')));
+            right_send.send((1, (3, 'x')));
+            right_send.send((1, (4, 'y')));
+
+            // nothing is emitted until the watermark passes the elements of the left side
+            out_recv.assert_no_more().await;
+
+            watermark_send.send(10);
+            right_send.send((1, (12, 'z')));
+            left_send.send((1, (15, 'c')));
+
+            out_recv
+                .assert_yields_only_unordered([
+                    (1, ((5, 'a'), Some((4, 'y')))),
+                    (2, ((6, 'b'), None)),
+                ])
+                .await;
+
+            watermark_send.send(20);
+
+            // the element at 4 is superseded by the element at 12, which is now before the watermark
+            out_recv
+                .assert_yields_only_unordered([(1, ((15, 'c'), Some((12, 'z'))))])
+                .await;
+        });
+    }
 }
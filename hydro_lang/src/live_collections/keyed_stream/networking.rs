@@ -1,18 +1,29 @@
 //! Networking APIs for [`KeyedStream`].
 
+use std::hash::Hash;
+
 use serde::Serialize;
 use serde::de::DeserializeOwned;
-use stageleft::{q, quote_type};
+use stageleft::{IntoQuotedMut, q, quote_type};
 
 use super::KeyedStream;
 use crate::compile::ir::{DebugInstantiate, HydroNode, NetworkRecv, NetworkSend};
 use crate::live_collections::boundedness::{Boundedness, Unbounded};
-use crate::live_collections::stream::{MinOrder, Ordering, Retries, Stream};
+use crate::live_collections::keyed_singleton::{KeyedSingleton, MonotonicKeys};
+use crate::live_collections::sliced::sliced;
+use crate::live_collections::stream::networking::{
+    StreamFrame, apply_decode_error_policy, track_membership,
+};
+use crate::live_collections::stream::{
+    ExactlyOnce, MinOrder, NoOrder, Ordering, Retries, Stream, TotalOrder,
+};
 use crate::location::cluster::{Consistency, NoConsistency};
 #[cfg(stageleft_runtime)]
 use crate::location::dynamic::DynLocation;
-use crate::location::{Cluster, MemberId, Process};
+use crate::location::{Cluster, Location, MemberId, Process, Tick};
+use crate::manual_expr::ManualExpr;
 use crate::networking::{NetworkFor, TCP};
+use crate::nondet::{NonDet, nondet};
 
 impl<'a, T, L, L2, B: Boundedness, O: Ordering, R: Retries>
     KeyedStream<MemberId<L2>, T, Process<'a, L>, B, O, R>
@@ -106,7 +117,7 @@ impl<'a, T, L, L2, B: Boundedness, O: Ordering, R: Retries>
     pub fn demux<N: NetworkFor<T>>(
         self,
         to: &Cluster<'a, L2>,
-        via: N,
+        mut via: N,
     ) -> Stream<
         T,
         // NoConsistency because there each replica member may receive different streams
@@ -119,6 +130,7 @@ impl<'a, T, L, L2, B: Boundedness, O: Ordering, R: Retries>
         T: Serialize + DeserializeOwned,
         O: MinOrder<N::OrderingGuarantee>,
     {
+        let on_decode_error = via.take_decode_error_policy();
         let name = via.name();
         if to.multiversioned() && name.is_none() {
             panic!(
@@ -143,28 +155,32 @@ impl<'a, T, L, L2, B: Boundedness, O: Ordering, R: Retries>
                     serialize_fn: Some(N::serialize_thunk(true).into()),
                 },
                 NetworkRecv::Custom {
-                    deserialize_fn: Some(N::deserialize_thunk(None).into()),
+                    deserialize_fn: Some(on_decode_error.deserialize_thunk::<T, N>(None).into()),
                 },
             )
         };
 
         Stream::new(
             to.clone(),
-            HydroNode::Network {
-                name: name.map(ToOwned::to_owned),
-                networking_info: N::networking_info(),
-                serialize,
-                deserialize,
-                instantiate_fn: DebugInstantiate::Building,
-                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
-                metadata: to.new_node_metadata(Stream::<
-                    T,
-                    Cluster<'a, L2>,
-                    Unbounded,
-                    <O as MinOrder<N::OrderingGuarantee>>::Min,
-                    R,
-                >::collection_kind()),
-            },
+            apply_decode_error_policy(
+                to,
+                on_decode_error,
+                HydroNode::Network {
+                    name: name.map(ToOwned::to_owned),
+                    networking_info: N::networking_info(),
+                    serialize,
+                    deserialize,
+                    instantiate_fn: DebugInstantiate::Building,
+                    input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                    metadata: to.new_node_metadata(Stream::<
+                        T,
+                        Cluster<'a, L2>,
+                        Unbounded,
+                        <O as MinOrder<N::OrderingGuarantee>>::Min,
+                        R,
+                    >::collection_kind()),
+                },
+            ),
         )
     }
 }
@@ -253,7 +269,7 @@ impl<'a, K, T, L, L2, B: Boundedness, O: Ordering, R: Retries>
     pub fn demux<N: NetworkFor<(K, T)>>(
         self,
         to: &Cluster<'a, L2>,
-        via: N,
+        mut via: N,
     ) -> KeyedStream<
         K,
         T,
@@ -267,6 +283,7 @@ impl<'a, K, T, L, L2, B: Boundedness, O: Ordering, R: Retries>
         T: Serialize + DeserializeOwned,
         O: MinOrder<N::OrderingGuarantee>,
     {
+        let on_decode_error = via.take_decode_error_policy();
         let name = via.name();
         if to.multiversioned() && name.is_none() {
             panic!(
@@ -291,34 +308,40 @@ impl<'a, K, T, L, L2, B: Boundedness, O: Ordering, R: Retries>
                     serialize_fn: Some(N::serialize_thunk(true).into()),
                 },
                 NetworkRecv::Custom {
-                    deserialize_fn: Some(N::deserialize_thunk(None).into()),
+                    deserialize_fn: Some(
+                        on_decode_error.deserialize_thunk::<(K, T), N>(None).into(),
+                    ),
                 },
             )
         };
 
         KeyedStream::new(
             to.clone(),
-            HydroNode::Network {
-                name: name.map(ToOwned::to_owned),
-                networking_info: N::networking_info(),
-                serialize,
-                deserialize,
-                instantiate_fn: DebugInstantiate::Building,
-                input: Box::new(
-                    self.entries()
-                        .map(q!(|((id, k), v)| (id, (k, v))))
-                        .ir_node
-                        .replace(HydroNode::Placeholder),
-                ),
-                metadata: to.new_node_metadata(KeyedStream::<
-                    K,
-                    T,
-                    Cluster<'a, L2>,
-                    Unbounded,
-                    <O as MinOrder<N::OrderingGuarantee>>::Min,
-                    R,
-                >::collection_kind()),
-            },
+            apply_decode_error_policy(
+                to,
+                on_decode_error,
+                HydroNode::Network {
+                    name: name.map(ToOwned::to_owned),
+                    networking_info: N::networking_info(),
+                    serialize,
+                    deserialize,
+                    instantiate_fn: DebugInstantiate::Building,
+                    input: Box::new(
+                        self.entries()
+                            .map(q!(|((id, k), v)| (id, (k, v))))
+                            .ir_node
+                            .replace(HydroNode::Placeholder),
+                    ),
+                    metadata: to.new_node_metadata(KeyedStream::<
+                        K,
+                        T,
+                        Cluster<'a, L2>,
+                        Unbounded,
+                        <O as MinOrder<N::OrderingGuarantee>>::Min,
+                        R,
+                    >::collection_kind()),
+                },
+            ),
         )
     }
 }
@@ -434,7 +457,7 @@ impl<'a, T, L, L2, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
     pub fn demux<N: NetworkFor<T>>(
         self,
         to: &Cluster<'a, L2>,
-        via: N,
+        mut via: N,
     ) -> KeyedStream<
         MemberId<L>,
         T,
@@ -447,6 +470,7 @@ impl<'a, T, L, L2, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
         T: Serialize + DeserializeOwned,
         O: MinOrder<N::OrderingGuarantee>,
     {
+        let on_decode_error = via.take_decode_error_policy();
         let name = via.name();
         if to.multiversioned() && name.is_none() {
             panic!(
@@ -471,29 +495,37 @@ impl<'a, T, L, L2, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
                     serialize_fn: Some(N::serialize_thunk(true).into()),
                 },
                 NetworkRecv::Custom {
-                    deserialize_fn: Some(N::deserialize_thunk(Some(&quote_type::<L>())).into()),
+                    deserialize_fn: Some(
+                        on_decode_error
+                            .deserialize_thunk::<T, N>(Some(&quote_type::<L>()))
+                            .into(),
+                    ),
                 },
             )
         };
 
         KeyedStream::new(
             to.clone(),
-            HydroNode::Network {
-                name: name.map(ToOwned::to_owned),
-                networking_info: N::networking_info(),
-                serialize,
-                deserialize,
-                instantiate_fn: DebugInstantiate::Building,
-                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
-                metadata: to.new_node_metadata(KeyedStream::<
-                    MemberId<L>,
-                    T,
-                    Cluster<'a, L2>,
-                    Unbounded,
-                    <O as MinOrder<N::OrderingGuarantee>>::Min,
-                    R,
-                >::collection_kind()),
-            },
+            apply_decode_error_policy(
+                to,
+                on_decode_error,
+                HydroNode::Network {
+                    name: name.map(ToOwned::to_owned),
+                    networking_info: N::networking_info(),
+                    serialize,
+                    deserialize,
+                    instantiate_fn: DebugInstantiate::Building,
+                    input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                    metadata: to.new_node_metadata(KeyedStream::<
+                        MemberId<L>,
+                        T,
+                        Cluster<'a, L2>,
+                        Unbounded,
+                        <O as MinOrder<N::OrderingGuarantee>>::Min,
+                        R,
+                    >::collection_kind()),
+                },
+            ),
         )
     }
 }
@@ -624,7 +656,7 @@ impl<'a, K, V, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
     pub fn send<L2, N: NetworkFor<(K, V)>>(
         self,
         to: &Process<'a, L2>,
-        via: N,
+        mut via: N,
     ) -> KeyedStream<
         (MemberId<L>, K),
         V,
@@ -638,6 +670,7 @@ impl<'a, K, V, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
         V: Serialize + DeserializeOwned,
         O: MinOrder<N::OrderingGuarantee>,
     {
+        let on_decode_error = via.take_decode_error_policy();
         let name = via.name();
         if to.multiversioned() && name.is_none() {
             panic!(
@@ -662,7 +695,11 @@ impl<'a, K, V, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
                     serialize_fn: Some(N::serialize_thunk(false).into()),
                 },
                 NetworkRecv::Custom {
-                    deserialize_fn: Some(N::deserialize_thunk(Some(&quote_type::<L>())).into()),
+                    deserialize_fn: Some(
+                        on_decode_error
+                            .deserialize_thunk::<(K, V), N>(Some(&quote_type::<L>()))
+                            .into(),
+                    ),
                 },
             )
         };
@@ -675,21 +712,25 @@ impl<'a, K, V, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
             R,
         > = Stream::new(
             to.clone(),
-            HydroNode::Network {
-                name: name.map(ToOwned::to_owned),
-                networking_info: N::networking_info(),
-                serialize,
-                deserialize,
-                instantiate_fn: DebugInstantiate::Building,
-                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
-                metadata: to.new_node_metadata(Stream::<
-                    (MemberId<L>, (K, V)),
-                    Cluster<'a, L2>,
-                    Unbounded,
-                    <O as MinOrder<N::OrderingGuarantee>>::Min,
-                    R,
-                >::collection_kind()),
-            },
+            apply_decode_error_policy(
+                to,
+                on_decode_error,
+                HydroNode::Network {
+                    name: name.map(ToOwned::to_owned),
+                    networking_info: N::networking_info(),
+                    serialize,
+                    deserialize,
+                    instantiate_fn: DebugInstantiate::Building,
+                    input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                    metadata: to.new_node_metadata(Stream::<
+                        (MemberId<L>, (K, V)),
+                        Cluster<'a, L2>,
+                        Unbounded,
+                        <O as MinOrder<N::OrderingGuarantee>>::Min,
+                        R,
+                    >::collection_kind()),
+                },
+            ),
         );
 
         raw_stream
@@ -697,3 +738,260 @@ impl<'a, K, V, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
             .into_keyed()
     }
 }
+
+impl<'a, K, V, L, B: Boundedness, O: Ordering, R: Retries>
+    KeyedStream<K, V, Process<'a, L>, B, O, R>
+{
+    /// Routes each entry of this keyed stream to a member of a cluster chosen by `partitioner`,
+    /// using the configuration in `via` to set up the message transport. This is the shuffle
+    /// step of MapReduce-style computations: all entries with the same key are sent to the same
+    /// member, which receives them as a member-local [`KeyedStream`].
+    ///
+    /// The `partitioner` is called with each key and the number of cluster members, and returns
+    /// the index of the member which should receive the entry (wrapped around if out of bounds),
+    /// where members are ordered by their [`MemberId`]. For example, hash partitioning can be
+    /// implemented as `q!(|k: &K, n: usize| hash(k) as usize % n)` and range partitioning as
+    /// `q!(|k: &u32, n: usize| *k as usize * n / (u32::MAX as usize + 1))`. The argument types
+    /// of the closure must be annotated.
+    ///
+    /// # Non-Determinism
+    /// The set of cluster members may asynchronously change over time. Each entry is routed based
+    /// on the current cluster membership _at that point in time_, so a key is only guaranteed to
+    /// be sent to the same member while the membership is stable. Entries which are routed before
+    /// any members are known are dropped.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::multi_location_test(|flow, p2| {
+    /// let p1 = flow.process::<()>();
+    /// let workers: Cluster<()> = flow.cluster::<()>();
+    /// let entries: KeyedStream<u32, u32, Process<_>, _> = p1
+    ///     .source_iter(q!(vec![(0, 100), (1, 101), (2, 102), (3, 103), (4, 104)]))
+    ///     .into_keyed();
+    /// let on_worker = entries.repartition(
+    ///     &workers,
+    ///     q!(|k: &u32, n: usize| *k as usize % n),
+    ///     TCP.fail_stop().bincode(),
+    ///     nondet!(/** assuming stable membership */),
+    /// );
+    /// on_worker.entries().send(&p2, TCP.fail_stop().bincode()).entries()
+    /// // with 4 cluster members, each key is sent to member `key % 4`
+    /// // - MemberId::<()>(0): { 0: [100], 4: [104] }
+    /// // - MemberId::<()>(1): { 1: [101] }
+    /// // - MemberId::<()>(2): { 2: [102] }
+    /// // - MemberId::<()>(3): { 3: [103] }
+    /// # }, |mut stream| async move {
+    /// # let mut results = Vec::new();
+    /// # for w in 0..5 {
+    /// #     results.push(format!("{:?}", stream.next().await.unwrap()));
+    /// # }
+    /// # results.sort();
+    /// # assert_eq!(results, vec![
+    /// #   "(MemberId::<()>(0), (0, 100))", "(MemberId::<()>(0), (4, 104))",
+    /// #   "(MemberId::<()>(1), (1, 101))", "(MemberId::<()>(2), (2, 102))",
+    /// #   "(MemberId::<()>(3), (3, 103))"
+    /// # ]);
+    /// # }));
+    /// # }
+    /// ```
+    pub fn repartition<L2: 'a, F, N: NetworkFor<(K, V)>>(
+        self,
+        to: &Cluster<'a, L2>,
+        partitioner: impl IntoQuotedMut<'a, F, Process<'a, L>> + Copy,
+        via: N,
+        nondet_membership: NonDet,
+    ) -> KeyedStream<K, V, Cluster<'a, L2, NoConsistency>, Unbounded, NoOrder, R>
+    where
+        K: Serialize + DeserializeOwned,
+        V: Serialize + DeserializeOwned,
+        F: Fn(&K, usize) -> usize + 'a,
+    {
+        let ids = track_membership(self.location.source_cluster_membership_stream(
+            to,
+            nondet!(/** dropped prefixes don't affect repartitioning */),
+        ));
+
+        let splice_location = self.location.clone();
+        let partitioner: ManualExpr<F, _> = ManualExpr::new(move |_: &Tick<Process<'a, L>>| {
+            partitioner.splice_untyped_ctx(&splice_location)
+        });
+
+        sliced! {
+            let members_snapshot = use(ids, nondet_membership);
+            let entries = use(self.entries(), nondet_membership);
+
+            let current_members = members_snapshot
+                .filter(q!(|b| *b))
+                .keys()
+                .sort()
+                .collect_vec();
+
+            entries
+                .cross_singleton(current_members)
+                .filter_map(q!({
+                    let partitioner = partitioner;
+                    move |((key, value), members)| {
+                        if members.is_empty() {
+                            None
+                        } else {
+                            let index = partitioner(&key, members.len()) % members.len();
+                            Some((members[index].clone(), (key, value)))
+                        }
+                    }
+                }))
+        }
+        .into_keyed()
+        .demux(to, via)
+        .into_keyed()
+    }
+}
+
+impl<'a, K, V, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
+    KeyedStream<K, V, Cluster<'a, L, C>, B, O, R>
+{
+    /// Routes each entry of this keyed stream, at every source member, to a member of the
+    /// destination cluster chosen by `partitioner`, using the configuration in `via` to set up
+    /// the message transport. This is the shuffle step of MapReduce-style computations: all
+    /// entries with the same key, from any source member, are sent to the same destination
+    /// member, which receives them as a member-local [`KeyedStream`].
+    ///
+    /// See [`KeyedStream::repartition`] on a [`Process`] for how `partitioner` chooses members,
+    /// and the non-determinism of the routing while the membership changes. Since every source
+    /// member routes with its own view of the membership, entries with the same key may be sent to
+    /// different members until the views agree.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::multi_location_test(|flow, p2| {
+    /// let mappers: Cluster<()> = flow.cluster::<()>();
+    /// let reducers: Cluster<()> = flow.cluster::<()>();
+    /// let words = mappers
+    ///     .source_iter(q!(vec!["a", "b", "a"]))
+    ///     .map(q!(|w| (w.to_owned(), 1)))
+    ///     .into_keyed();
+    /// let on_reducer = words.repartition(
+    ///     &reducers,
+    ///     q!(|k: &String, n: usize| k.len() % n),
+    ///     TCP.fail_stop().bincode(),
+    ///     nondet!(/** assuming stable membership */),
+    /// );
+    /// # on_reducer.entries().send(&p2, TCP.fail_stop().bincode()).entries()
+    /// # }, |mut stream| async move {
+    /// // with 4 mappers, all 8 occurrences of "a" (and all 4 of "b") are sent to the same reducer
+    /// # let mut reducer_of = std::collections::HashMap::new();
+    /// # for _ in 0..12 {
+    /// #     let (reducer, (word, _)) = stream.next().await.unwrap();
+    /// #     assert_eq!(*reducer_of.entry(word).or_insert(reducer.clone()), reducer);
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn repartition<L2: 'a, F, N: NetworkFor<(K, V)>>(
+        self,
+        to: &Cluster<'a, L2>,
+        partitioner: impl IntoQuotedMut<'a, F, Cluster<'a, L, C>> + Copy,
+        via: N,
+        nondet_membership: NonDet,
+    ) -> KeyedStream<K, V, Cluster<'a, L2, NoConsistency>, Unbounded, NoOrder, R>
+    where
+        K: Serialize + DeserializeOwned,
+        V: Serialize + DeserializeOwned,
+        F: Fn(&K, usize) -> usize + 'a,
+    {
+        let ids = track_membership(self.location.source_cluster_membership_stream(
+            to,
+            nondet!(/** dropped prefixes don't affect repartitioning */),
+        ));
+
+        let splice_location = self.location.clone();
+        let partitioner: ManualExpr<F, _> = ManualExpr::new(move |_: &Tick<Cluster<'a, L, C>>| {
+            partitioner.splice_untyped_ctx(&splice_location)
+        });
+
+        sliced! {
+            let members_snapshot = use(ids, nondet_membership);
+            let entries = use(self.entries(), nondet_membership);
+
+            let current_members = members_snapshot
+                .filter(q!(|b| *b))
+                .keys()
+                .sort()
+                .collect_vec();
+
+            entries
+                .cross_singleton(current_members)
+                .filter_map(q!({
+                    let partitioner = partitioner;
+                    move |((key, value), members)| {
+                        if members.is_empty() {
+                            None
+                        } else {
+                            let index = partitioner(&key, members.len()) % members.len();
+                            Some((members[index].clone(), (key, value)))
+                        }
+                    }
+                }))
+        }
+        .into_keyed()
+        .demux(to, via)
+        .values()
+        .into_keyed()
+    }
+}
+
+impl<'a, K, T, L: Location<'a>>
+    KeyedStream<K, StreamFrame<T>, L, Unbounded, TotalOrder, ExactlyOnce>
+{
+    /// Collects the elements of each group, framed with [`Stream::framed`], into a [`Vec`] once
+    /// the entire group has been received. This is how a process collects a bounded stream sent
+    /// by each member of a cluster.
+    ///
+    /// A key appears in the returned [`KeyedSingleton`] only once its [`StreamFrame::End`] marker
+    /// arrives. Keys whose connection is dropped before then never appear.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::multi_location_test(|flow, process| {
+    /// let workers: Cluster<()> = flow.cluster::<()>();
+    /// let numbers: Stream<_, Cluster<_>, Bounded> = workers.source_iter(q!(vec![1, 2]));
+    /// let per_worker = numbers
+    ///     .framed()
+    ///     .send(&process, TCP.fail_stop().bincode())
+    ///     .collect_framed_vec();
+    /// # per_worker.entries()
+    /// // if there are 4 members in the cluster, each sends [1, 2]
+    /// # }, |mut stream| async move {
+    /// # let mut results = Vec::new();
+    /// # for _ in 0..4 {
+    /// #     results.push(stream.next().await.unwrap().1);
+    /// # }
+    /// # assert_eq!(results, vec![vec![1, 2]; 4]);
+    /// # }));
+    /// # }
+    /// ```
+    pub fn collect_framed_vec(self) -> KeyedSingleton<K, Vec<T>, L, MonotonicKeys>
+    where
+        K: Eq + Hash,
+    {
+        let received: KeyedSingleton<K, (Vec<T>, bool), L, MonotonicKeys> = self.fold(
+            q!(|| (Vec::new(), false)),
+            q!(|(items, done), frame| match frame {
+                crate::live_collections::stream::networking::StreamFrame::Element(item) => {
+                    items.push(item)
+                }
+                crate::live_collections::stream::networking::StreamFrame::End => *done = true,
+            }),
+        );
+
+        received.filter_map(q!(|(items, done)| if done { Some(items) } else { None }))
+    }
+}
@@ -0,0 +1,163 @@
+//! Definitions for the [`LatticeSingleton`] live collection.
+
+#[cfg(feature = "tokio")]
+use std::time::Duration;
+
+#[cfg(feature = "tokio")]
+use lattices::Merge;
+#[cfg(feature = "tokio")]
+use serde::Serialize;
+#[cfg(feature = "tokio")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "tokio")]
+use stageleft::QuotedWithContext;
+
+use super::boundedness::{Bounded, Unbounded};
+use super::singleton::{Singleton, SingletonBound};
+#[cfg(feature = "tokio")]
+use super::stream::{AtLeastOnce, NoOrder, Stream};
+#[cfg(feature = "tokio")]
+use crate::location::{Cluster, Process, TopLevel};
+use crate::location::{Location, Tick};
+#[cfg(feature = "tokio")]
+use crate::networking::NetworkFor;
+use crate::nondet::NonDet;
+#[cfg(feature = "tokio")]
+use crate::nondet::nondet;
+
+/// A [`Singleton`] whose value is the merge (via [`lattices::Merge`]) of every lattice value
+/// that has been contributed to it, starting from the lattice's [`Default`] (bottom) value.
+///
+/// Because lattice merges are associative, commutative, and idempotent, a [`LatticeSingleton`]
+/// can be built from streams with any ordering and retries guarantees without any additional
+/// proofs. In particular, a lattice can be replicated over a channel that loses, duplicates, or
+/// reorders messages by periodically resending its current value (see
+/// [`LatticeSingleton::send_lattice`]), since duplicates are absorbed by the merge and do not need
+/// to be deduplicated.
+///
+/// Type Parameters:
+/// - `Lat`: the type of the lattice value
+/// - `Loc`: the location where the lattice is materialized
+/// - `Bound`: tracks whether the value is [`Bounded`] (fixed) or [`Unbounded`] (changing asynchronously)
+pub struct LatticeSingleton<Lat, Loc, Bound: SingletonBound> {
+    pub(crate) inner: Singleton<Lat, Loc, Bound>,
+}
+
+impl<'a, Lat, L, B: SingletonBound> Clone for LatticeSingleton<Lat, L, B>
+where
+    Lat: Clone,
+    L: Location<'a>,
+{
+    fn clone(&self) -> Self {
+        LatticeSingleton {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, Lat, L, B: SingletonBound> LatticeSingleton<Lat, L, B>
+where
+    L: Location<'a>,
+{
+    /// Returns the [`Location`] where this lattice is being materialized.
+    pub fn location(&self) -> &L {
+        self.inner.location()
+    }
+
+    /// Erases the lattice structure, returning the merged value as a plain [`Singleton`].
+    pub fn into_singleton(self) -> Singleton<Lat, L, B> {
+        self.inner
+    }
+
+    /// Given a tick, returns a lattice value corresponding to a snapshot of the lattice as of
+    /// that tick. The snapshot at tick `t + 1` is guaranteed to be at least as large (in the
+    /// lattice order) as the snapshot at tick `t`.
+    ///
+    /// # Non-Determinism
+    /// Because this picks a snapshot of a lattice whose value is continuously growing, the
+    /// output value is non-deterministic.
+    pub fn snapshot<L2: Location<'a, DropConsistency = L::DropConsistency>>(
+        self,
+        tick: &Tick<L2>,
+        nondet: NonDet,
+    ) -> Singleton<Lat, Tick<L::DropConsistency>, Bounded> {
+        self.inner.snapshot(tick, nondet)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<'a, Lat, L> LatticeSingleton<Lat, L, Unbounded>
+where
+    L: TopLevel<'a>,
+{
+    /// Returns the intermediate states of the lattice as they are produced, along with the
+    /// current state every `resend_interval`, so that a state lost in the network is eventually
+    /// superseded by a later one.
+    fn sample_with_resends(
+        self,
+        resend_interval: impl QuotedWithContext<'a, Duration, L> + Copy + 'a,
+    ) -> Stream<Lat, L::DropConsistency, Unbounded, NoOrder, AtLeastOnce>
+    where
+        Lat: Clone,
+    {
+        let nondet_samples = nondet!(
+            /// Merging any subset of intermediate states of a lattice at the destination
+            /// produces a state that is at most the state at the source, and resending the
+            /// current state eventually brings the destination up to date.
+        );
+        self.inner
+            .clone()
+            .sample_eager(nondet_samples)
+            .weaken_ordering::<NoOrder>()
+            .merge_unordered(self.inner.sample_every(resend_interval, nondet_samples))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<'a, Lat, L> LatticeSingleton<Lat, Process<'a, L>, Unbounded> {
+    /// Replicates this lattice to another process, returning a lattice at the destination
+    /// that eventually converges to the value at the source.
+    ///
+    /// Intermediate states of the lattice are sent as they are produced, and the current state
+    /// is resent every `resend_interval`. Because merges are idempotent and commutative, the
+    /// destination does not need to deduplicate or reorder messages, and a state lost by `via`
+    /// is made up for by a later resend. The destination converges once the source stops
+    /// changing and one of the following resends is delivered.
+    pub fn send_lattice<L2, N: NetworkFor<Lat>>(
+        self,
+        to: &Process<'a, L2>,
+        via: N,
+        resend_interval: impl QuotedWithContext<'a, Duration, Process<'a, L>> + Copy + 'a,
+    ) -> LatticeSingleton<Lat, Process<'a, L2>, Unbounded>
+    where
+        Lat: Merge<Lat> + Default + Clone + Serialize + DeserializeOwned,
+    {
+        self.sample_with_resends(resend_interval)
+            .send(to, via)
+            .merge_lattice()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<'a, Lat, L> LatticeSingleton<Lat, Cluster<'a, L>, Unbounded> {
+    /// Replicates the lattice at every member of this cluster to a process, returning a lattice
+    /// at the destination that eventually converges to the merge of the values at all members.
+    ///
+    /// Like [`LatticeSingleton::send_lattice`] on a process, intermediate states are sent as
+    /// they are produced and the current state of each member is resent every
+    /// `resend_interval`, so a state lost by `via` is made up for by a later resend.
+    pub fn send_lattice<L2, N: NetworkFor<Lat>>(
+        self,
+        to: &Process<'a, L2>,
+        via: N,
+        resend_interval: impl QuotedWithContext<'a, Duration, Cluster<'a, L>> + Copy + 'a,
+    ) -> LatticeSingleton<Lat, Process<'a, L2>, Unbounded>
+    where
+        Lat: Merge<Lat> + Default + Clone + Serialize + DeserializeOwned,
+    {
+        self.sample_with_resends(resend_interval)
+            .send(to, via)
+            .values()
+            .merge_lattice()
+    }
+}
@@ -24,6 +24,10 @@ pub mod keyed_stream;
 #[doc(inline)]
 pub use keyed_stream::KeyedStream;
 
+pub mod lattice_singleton;
+#[doc(inline)]
+pub use lattice_singleton::LatticeSingleton;
+
 pub mod optional;
 #[doc(inline)]
 pub use optional::Optional;
@@ -0,0 +1,178 @@
+//! Fallible transformations of [`Stream`], which route errors to a separate error stream.
+//!
+//! [`Stream::map_fallible`] and [`Stream::filter_map_fallible`] apply a closure returning a
+//! [`Result`], and split its output into a stream of successes and a stream of errors at the same
+//! location, rather than panicking on errors or silently dropping them. Error streams of different
+//! error types can be converted into [`ErrorReport`]s with [`Stream::report_errors`], merged into
+//! a single stream per location with [`Stream::merge_unordered`], and exported to an external
+//! process (for example with [`Stream::send_bincode_external`]) or any other sink.
+
+use std::fmt::Display;
+
+use quote::quote;
+use serde::{Deserialize, Serialize};
+use stageleft::runtime_support::{FreeVariableWithContextWithProps, QuoteTokens};
+use stageleft::{IntoQuotedMut, q};
+
+use super::{Ordering, Retries, Stream};
+use crate::live_collections::boundedness::Boundedness;
+use crate::location::Location;
+use crate::properties::{StreamMapFuncAlgebra, ValidMutCommutativityFor, ValidMutIdempotenceFor};
+
+/// An error produced by a fallible operator, tagged with the operator it came from, see
+/// [`Stream::report_errors`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// The name given to the operator that produced the error.
+    pub operator: String,
+    /// The error, formatted with [`Display`].
+    pub message: String,
+}
+
+impl ErrorReport {
+    #[doc(hidden)]
+    pub fn new(operator: &str, error: impl Display) -> Self {
+        Self {
+            operator: operator.to_owned(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// The name of an operator, spliced into `q!` code as a string literal.
+#[derive(Clone, Copy)]
+struct OperatorName(&'static str);
+
+impl<'a, L: Location<'a>> FreeVariableWithContextWithProps<L, ()> for OperatorName {
+    type O = &'static str;
+
+    fn to_tokens(self, _ctx: &L) -> (QuoteTokens, ())
+    where
+        Self: Sized,
+    {
+        let name = self.0;
+        (
+            QuoteTokens {
+                prelude: None,
+                expr: Some(quote!(#name)),
+            },
+            (),
+        )
+    }
+}
+
+impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, L, B, O, R>
+where
+    L: Location<'a>,
+{
+    /// Produces a stream based on invoking the fallible `f` on each element, like
+    /// [`Stream::map`]. Returns the stream of `Ok` outputs, along with the stream of `Err`
+    /// outputs, which preserve the order of the corresponding inputs.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let (numbers, errors) = process
+    ///     .source_iter(q!(vec!["1", "hello", "2"]))
+    ///     .map_fallible(q!(|s| s.parse::<usize>()));
+    /// errors
+    ///     .report_errors("parse")
+    ///     .for_each(q!(|report| eprintln!("{:?}", report)));
+    /// numbers
+    /// # }, |mut stream| async move {
+    /// // 1, 2
+    /// # for w in (1..3) {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn map_fallible<U, E, F, C, I, const WAS_MUT: bool>(
+        self,
+        f: impl IntoQuotedMut<'a, F, L, StreamMapFuncAlgebra<C, I>>,
+    ) -> (Stream<U, L, B, O, R>, Stream<E, L, B, O, R>)
+    where
+        F: FnMut(T) -> Result<U, E> + 'a,
+        C: ValidMutCommutativityFor<F, T, Result<U, E>, O, WAS_MUT>,
+        I: ValidMutIdempotenceFor<F, T, Result<U, E>, R, WAS_MUT>,
+    {
+        let (ok, err) = self.map(f).partition(q!(|result| result.is_ok()));
+        (
+            ok.map(q!(|result| result.ok().unwrap())),
+            err.map(q!(|result| result.err().unwrap())),
+        )
+    }
+
+    /// Like [`Stream::filter_map`], but with a fallible `f`. Returns the stream of `Ok(Some(_))`
+    /// outputs, along with the stream of `Err` outputs; `Ok(None)` outputs are dropped.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let (numbers, errors) = process
+    ///     .source_iter(q!(vec!["1", "", "hello", "2"]))
+    ///     .filter_map_fallible(q!(|s| {
+    ///         if s.is_empty() {
+    ///             Ok(None)
+    ///         } else {
+    ///             s.parse::<usize>().map(Some)
+    ///         }
+    ///     }));
+    /// errors
+    ///     .report_errors("parse")
+    ///     .for_each(q!(|report| eprintln!("{:?}", report)));
+    /// numbers
+    /// # }, |mut stream| async move {
+    /// // 1, 2
+    /// # for w in (1..3) {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn filter_map_fallible<U, E, F, C, I, const WAS_MUT: bool>(
+        self,
+        f: impl IntoQuotedMut<'a, F, L, StreamMapFuncAlgebra<C, I>>,
+    ) -> (Stream<U, L, B, O, R>, Stream<E, L, B, O, R>)
+    where
+        F: FnMut(T) -> Result<Option<U>, E> + 'a,
+        C: ValidMutCommutativityFor<F, T, Result<Option<U>, E>, O, WAS_MUT>,
+        I: ValidMutIdempotenceFor<F, T, Result<Option<U>, E>, R, WAS_MUT>,
+    {
+        let (ok, err) = self.map(f).partition(q!(|result| result.is_ok()));
+        (
+            ok.filter_map(q!(|result| result.ok().unwrap())),
+            err.map(q!(|result| result.err().unwrap())),
+        )
+    }
+}
+
+impl<'a, E, L, B: Boundedness, O: Ordering, R: Retries> Stream<E, L, B, O, R>
+where
+    L: Location<'a>,
+    E: Display,
+{
+    /// Converts a stream of errors (such as one returned by [`Stream::map_fallible`]) into
+    /// [`ErrorReport`]s tagged with `operator`, so that the errors of several operators can be
+    /// merged into a single stream and exported together.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let (ints, int_errors) = input.clone().map_fallible(q!(|s| s.parse::<i64>()));
+    /// let (floats, float_errors) = input.map_fallible(q!(|s| s.parse::<f64>()));
+    /// let errors = int_errors
+    ///     .report_errors("parse_int")
+    ///     .merge_unordered(float_errors.report_errors("parse_float"));
+    /// let errors_port = errors.send_bincode_external(&external);
+    /// ```
+    pub fn report_errors(self, operator: &'static str) -> Stream<ErrorReport, L, B, O, R> {
+        let operator = OperatorName(operator);
+        self.map(q!(move |error| ErrorReport::new(operator, error)))
+    }
+}
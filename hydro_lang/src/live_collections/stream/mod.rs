@@ -6,19 +6,24 @@ use std::hash::Hash;
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::time::Duration;
 
+use lattices::Merge;
 use stageleft::{IntoQuotedMut, QuotedWithContext, QuotedWithContextWithProps, q, quote_type};
+use syn::parse_quote;
 #[cfg(feature = "tokio")]
 use tokio::time::Instant;
 
 use super::boundedness::{Bounded, Boundedness, IsBounded, Unbounded};
 use super::keyed_singleton::KeyedSingleton;
 use super::keyed_stream::{Generate, KeyedStream};
+use super::lattice_singleton::LatticeSingleton;
 use super::optional::Optional;
 use super::singleton::Singleton;
 use crate::compile::builder::{CycleId, FlowState};
 use crate::compile::ir::{
-    CollectionKind, HydroIrOpMetadata, HydroNode, HydroRoot, SharedNode, StreamOrder, StreamRetry,
+    ClaimedAlgebra, ClosureExpr, CollectionKind, CounterKind, DebugExpr, HydroIrOpMetadata,
+    HydroNode, HydroRoot, SharedNode, StreamOrder, StreamRetry,
 };
 #[cfg(stageleft_runtime)]
 use crate::forward_handle::{CycleCollection, CycleCollectionWithInitial, ReceiverComplete};
@@ -37,7 +42,10 @@ use crate::properties::{
     ValidIdempotenceFor, ValidMutBorrowCommutativityFor, ValidMutBorrowIdempotenceFor,
     ValidMutCommutativityFor, ValidMutIdempotenceFor,
 };
+use crate::sketch::HyperLogLog;
+use crate::staging_util::get_this_crate;
 
+pub mod fallible;
 pub mod networking;
 
 /// A trait implemented by valid ordering markers ([`TotalOrder`] and [`NoOrder`]).
@@ -176,6 +184,84 @@ pub trait IsExactlyOnce: Retries {}
 #[diagnostic::do_not_recommend]
 impl IsExactlyOnce for ExactlyOnce {}
 
+/// The window of recently seen values which [`Stream::unique_within`] remembers to detect
+/// duplicates.
+#[derive(Clone, Copy, Debug)]
+pub struct UniqueWindow(UniqueWindowKind);
+
+#[derive(Clone, Copy, Debug)]
+enum UniqueWindowKind {
+    Count(usize),
+    Duration(Duration),
+}
+
+impl UniqueWindow {
+    /// Remembers the `count` most recently seen distinct values.
+    pub fn count(count: usize) -> Self {
+        assert!(
+            count > 0,
+            "the deduplication window must hold at least one value"
+        );
+        UniqueWindow(UniqueWindowKind::Count(count))
+    }
+
+    /// Remembers the values first seen within the last `duration`.
+    ///
+    /// # Non-Determinism
+    /// Whether a repeated value is filtered out depends on how long after its first occurrence it
+    /// arrives, which is non-deterministic.
+    pub fn duration(duration: Duration, _nondet: NonDet) -> Self {
+        UniqueWindow(UniqueWindowKind::Duration(duration))
+    }
+
+    /// An expression which creates the deduplication state for this window at runtime.
+    fn state_expr(self) -> syn::Expr {
+        let root = get_this_crate();
+        match self.0 {
+            UniqueWindowKind::Count(count) => parse_quote! {
+                #root::runtime_support::unique_window::WindowedUnique::with_count(#count)
+            },
+            UniqueWindowKind::Duration(duration) => {
+                let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+                parse_quote! {
+                    #root::runtime_support::unique_window::WindowedUnique::with_duration(
+                        ::std::time::Duration::from_nanos(#nanos)
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// What [`Stream::shed_when`] does with the elements that arrive while overloaded.
+#[derive(Clone, Copy, Debug)]
+pub struct ShedPolicy(ShedPolicyKind);
+
+#[derive(Clone, Copy, Debug)]
+enum ShedPolicyKind {
+    DropAll,
+    KeepOneIn(usize),
+}
+
+impl ShedPolicy {
+    /// Drops every element while overloaded.
+    pub fn drop_all() -> Self {
+        ShedPolicy(ShedPolicyKind::DropAll)
+    }
+
+    /// Keeps one out of every `n` elements while overloaded, dropping the rest. This keeps a
+    /// sample of the traffic flowing (for example to keep health checks or metrics alive), while
+    /// shedding most of the load.
+    ///
+    /// # Non-Determinism
+    /// Which elements are kept depends on the order in which they arrive, which is
+    /// non-deterministic for unordered streams.
+    pub fn keep_one_in(n: usize, _nondet: NonDet) -> Self {
+        assert!(n > 0, "must keep one in at least one element");
+        ShedPolicy(ShedPolicyKind::KeepOneIn(n))
+    }
+}
+
 /// Streaming sequence of elements with type `Type`.
 ///
 /// This live collection represents a growing sequence of elements, with new elements being
@@ -1139,6 +1225,56 @@ where
         self.filter_if(other.is_none())
     }
 
+    /// Routes this stream to one of two outputs based on a [`Bounded`] boolean control signal:
+    /// elements are sent to the first output while the signal is `true`, and to the second output
+    /// while it is `false`. Because the signal is bounded, routing can change at each tick, which
+    /// is useful for feature toggles or switching over from a primary to a backup pipeline.
+    ///
+    /// Unlike using [`Stream::filter_if`] twice, this does not require `T: Clone`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// // ticks are lazy by default, forces the second tick to run
+    /// tick.spin_batch(q!(1)).all_ticks().for_each(q!(|_| {}));
+    ///
+    /// let use_primary = tick.optional_first_tick(q!(())).is_some(); // true on tick 1, false on tick 2
+    /// let batch_first_tick = process
+    ///   .source_iter(q!(vec![1, 2]))
+    ///   .batch(&tick, nondet!(/** test */));
+    /// let batch_second_tick = process
+    ///   .source_iter(q!(vec![3, 4]))
+    ///   .batch(&tick, nondet!(/** test */))
+    ///   .defer_tick(); // appears on the second tick
+    /// let (primary, backup) = batch_first_tick.chain(batch_second_tick).switch(use_primary);
+    /// primary.map(q!(|x| (x, "primary")))
+    ///   .chain(backup.map(q!(|x| (x, "backup"))))
+    ///   .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, "primary"), (2, "primary"), (3, "backup"), (4, "backup")
+    /// # for w in vec![(1, "primary"), (2, "primary"), (3, "backup"), (4, "backup")] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn switch(
+        self,
+        signal: Singleton<bool, L, Bounded>,
+    ) -> (Stream<T, L, B, O, R>, Stream<T, L, B, O, R>) {
+        let (when_true, when_false) = self
+            .cross_singleton(signal)
+            .partition(q!(|(_, signal)| *signal));
+        (
+            when_true.map(q!(|(d, _)| d)),
+            when_false.map(q!(|(d, _)| d)),
+        )
+    }
+
     /// Forms the cross-product (Cartesian product, cross-join) of the items in the 2 input streams,
     /// returning all tupled pairs.
     ///
@@ -1210,6 +1346,53 @@ where
         )
     }
 
+    /// Like [`Stream::unique`], but only remembers the values in a bounded `window`, so that
+    /// the memory used for deduplication does not grow with the number of distinct values.
+    ///
+    /// Once a value is evicted from the window, a later occurrence of it is output again. The
+    /// window can either hold a fixed number of the most recent distinct values
+    /// ([`UniqueWindow::count`]), or the values seen within a period of time
+    /// ([`UniqueWindow::duration`]).
+    ///
+    /// Which values are in the window depends on the order of the elements, so the stream must be
+    /// totally ordered. An unordered stream must first be ordered with
+    /// [`Stream::assume_ordering`], which makes the non-determinism explicit.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use hydro_lang::live_collections::stream::UniqueWindow;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// process
+    ///     .source_iter(q!(vec![1, 2, 1, 3, 1, 2]))
+    ///     .unique_within(UniqueWindow::count(2))
+    /// # }, |mut stream| async move {
+    /// // 1, 2, 3, 1, 2 (`1` is evicted by `3`, and then `2` by `1`)
+    /// # for w in vec![1, 2, 3, 1, 2] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn unique_within(self, window: UniqueWindow) -> Stream<T, L, B, O, R>
+    where
+        T: Eq + Hash + Clone,
+        O: IsOrdered,
+    {
+        Stream::new(
+            self.location.clone(),
+            HydroNode::UniqueWithin {
+                window: window.state_expr().into(),
+                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                metadata: self
+                    .location
+                    .new_node_metadata(Stream::<T, L, B, O, R>::collection_kind()),
+            },
+        )
+    }
+
     /// Outputs everything in this stream that is *not* contained in the `other` stream.
     ///
     /// The `other` stream must be [`Bounded`], since this function will wait until
@@ -1299,6 +1482,46 @@ where
         )
     }
 
+    /// Records metrics about the elements passing through this stream under the given `name`,
+    /// without modifying the stream.
+    ///
+    /// The total number of elements is recorded, along with histograms of the number of elements
+    /// in each tick (throughput) and the time between the first and last element of each tick
+    /// passing through (latency). Metrics are recorded into the [`crate::telemetry::meter`]
+    /// registry; deployments include them in the EMF metrics sidecar, and simulations can read
+    /// them with `CompiledSim::meter` to make assertions on message volumes.
+    ///
+    /// Meters are process-global: each is registered once, and all metered streams with the same
+    /// `name` in a process (such as on every member of a cluster simulated in one process) record
+    /// into the same meter.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let nums = process.source_iter(q!(vec![1, 2, 3]));
+    /// nums.metered("nums")
+    /// # }, |mut stream| async move {
+    /// # for w in vec![1, 2, 3] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn metered(self, name: &str) -> Self {
+        Stream::new(
+            self.location.clone(),
+            HydroNode::Counter {
+                tag: name.to_owned(),
+                kind: CounterKind::Metered,
+                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                metadata: self.location.new_node_metadata(Self::collection_kind()),
+            },
+        )
+    }
+
     /// Executes the provided closure for every element in this stream.
     ///
     /// If the stream is unordered or has retries, the closure must demonstrate commutativity
@@ -1433,6 +1656,7 @@ where
     {
         let init = init.splice_fn0_ctx(&self.location).into();
         let (comb, proof) = comb.splice_fn2_borrow_mut_ctx_props(&self.location);
+        let claims = proof.claimed_algebra();
         proof.register_proof(&comb);
 
         // Only assume_retries (for idempotence), not assume_ordering.
@@ -1440,13 +1664,16 @@ where
         let nondet = nondet!(/** the combinator function is commutative and idempotent */);
         let retried: Stream<T, L::DropConsistency, B, O, ExactlyOnce> = self.assume_retries(nondet);
 
+        let mut metadata = retried
+            .location
+            .new_node_metadata(Singleton::<A, L::DropConsistency, B2>::collection_kind());
+        metadata.op.claimed_algebra = claims;
+
         let core = HydroNode::Fold {
             init,
             acc: comb.into(),
             input: Box::new(retried.ir_node.replace(HydroNode::Placeholder)),
-            metadata: retried
-                .location
-                .new_node_metadata(Singleton::<A, L::DropConsistency, B2>::collection_kind()),
+            metadata,
             // we do not guarantee consistency at this point because if the algebraic properties
             // do not hold in practice, replica consistency may fail to be maintained, so we
             // would like the simulator to assert consistency; in the future, this will be dynamic
@@ -1489,24 +1716,123 @@ where
         Idemp: ValidIdempotenceFor<R>,
     {
         let (f, proof) = comb.splice_fn2_borrow_mut_ctx_props(&self.location);
+        let claims = proof.claimed_algebra();
         proof.register_proof(&f);
 
         let nondet = nondet!(/** the combinator function is commutative and idempotent */);
         let ordered_etc: Stream<T, L::DropConsistency, B> =
             self.assume_retries(nondet).assume_ordering(nondet);
 
+        let mut metadata =
+            ordered_etc
+                .location
+                .new_node_metadata(Optional::<T, L::DropConsistency, B>::collection_kind());
+        // reordering the inputs of a reduce also regroups them, so it must be associative too
+        metadata.op.claimed_algebra = ClaimedAlgebra {
+            associative: claims.commutative,
+            ..claims
+        };
+
         let core = HydroNode::Reduce {
             f: f.into(),
             input: Box::new(ordered_etc.ir_node.replace(HydroNode::Placeholder)),
-            metadata: ordered_etc
-                .location
-                .new_node_metadata(Optional::<T, L::DropConsistency, B>::collection_kind()),
+            metadata,
         };
 
         Optional::new(ordered_etc.location.clone(), core)
             .assert_has_consistency_of(manual_proof!(/** algebraic properties */))
     }
 
+    /// Merges every element of the stream into a [`LatticeSingleton`], starting from the
+    /// lattice's [`Default`] (bottom) value and combining elements with [`lattices::Merge`].
+    ///
+    /// Because lattice merges are associative, commutative, and idempotent, this can be applied
+    /// to a stream with any ordering and retries guarantees without providing a proof.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use hydro_lang::live_collections::stream::{AtLeastOnce, NoOrder};
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let numbers = process
+    ///     .source_iter(q!(vec![1, 5, 3].into_iter().map(lattices::Max::new)))
+    ///     .weaken_ordering::<NoOrder>()
+    ///     .weaken_retries::<AtLeastOnce>();
+    /// numbers
+    ///     .merge_lattice()
+    ///     .into_singleton()
+    ///     .into_stream()
+    ///     .map(q!(|max| max.into_reveal()))
+    /// # }, |mut stream| async move {
+    /// // 5
+    /// # assert_eq!(stream.next().await.unwrap(), 5);
+    /// # }));
+    /// # }
+    /// ```
+    pub fn merge_lattice(self) -> LatticeSingleton<T, L, B>
+    where
+        T: Merge<T> + Default + Clone,
+        B: SingletonBound,
+    {
+        let nondet = nondet!(/** lattice merges are idempotent */);
+        let retried: Stream<T, L::DropConsistency, B, O, ExactlyOnce> = self.assume_retries(nondet);
+
+        let core = HydroNode::LatticeFold {
+            input: Box::new(retried.ir_node.replace(HydroNode::Placeholder)),
+            metadata: retried
+                .location
+                .new_node_metadata(Singleton::<T, L::DropConsistency, B>::collection_kind()),
+        };
+
+        LatticeSingleton {
+            inner: Singleton::new(retried.location.clone(), core)
+                .assert_has_consistency_of(manual_proof!(/** lattice merges are ACI */)),
+        }
+    }
+
+    /// Estimates the number of distinct elements in the stream with a [`HyperLogLog`] sketch,
+    /// which uses a fixed amount of memory regardless of the number of elements.
+    ///
+    /// Because inserting into the sketch is commutative and idempotent, this can be applied to a
+    /// stream with any ordering and retries guarantees. The sketches of different streams can be
+    /// combined with [`Stream::merge_lattice`] to estimate the number of distinct elements in
+    /// their union, for example across the members of a cluster.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let words = process.source_iter(q!(vec!["a", "b", "a", "c", "b"]));
+    /// words
+    ///     .approx_distinct()
+    ///     .into_stream()
+    ///     .map(q!(|sketch| sketch.estimate()))
+    /// # }, |mut stream| async move {
+    /// // 3
+    /// # assert_eq!(stream.next().await.unwrap(), 3);
+    /// # }));
+    /// # }
+    /// ```
+    pub fn approx_distinct(self) -> Singleton<HyperLogLog, L, B::StreamToMonotone>
+    where
+        T: Hash,
+    {
+        self.fold(
+            q!(|| crate::sketch::HyperLogLog::default()),
+            q!(
+                |sketch, item| sketch.insert(&item),
+                commutative = manual_proof!(/** registers are updated with max */),
+                idempotent =
+                    manual_proof!(/** re-inserting an item does not change its register */),
+                monotone = manual_proof!(/** registers only increase */)
+            ),
+        )
+    }
+
     /// Computes the maximum element in the stream as an [`Optional`], which
     /// will be empty until the first element in the input arrives.
     ///
@@ -1692,6 +2018,9 @@ where
     /// are added. On such a value, you can use [`Singleton::snapshot`] to grab an instance of
     /// the vector at an arbitrary point in time.
     ///
+    /// To collect a bounded stream sent over the network only once all of its elements have
+    /// arrived, send it with [`Stream::framed`] and collect it with [`Stream::collect_framed_vec`].
+    ///
     /// # Example
     /// ```rust
     /// # #[cfg(feature = "deploy")] {
@@ -2252,6 +2581,68 @@ where
         }
     }
 
+    /// Splices a fragment of DFIR surface syntax into the generated graph, as an escape hatch for
+    /// dataflow that cannot be expressed with Hydro's operators. The fragment reads the elements
+    /// of this stream from the `mod[input]` port and writes its output elements, of type `U`, to
+    /// the `[output]mod` port, each of which must be used exactly once.
+    ///
+    /// Hydro cannot check the fragment, so its output is treated as unordered and possibly
+    /// duplicated, and placed at a location with no consistency guarantees. The guarantees `O2`
+    /// and `R2` are then assumed from that:
+    /// - If `O2` is [`TotalOrder`], the simulator explores the possible orderings of each batch of
+    ///   outputs, as with [`Stream::assume_ordering`].
+    /// - If `R2` is [`ExactlyOnce`], it is taken on trust: the simulator does not explore
+    ///   duplicated outputs, so the fragment must never emit an element more than once. Use
+    ///   [`AtLeastOnce`] for fragments which may.
+    ///
+    /// # Panics
+    /// Panics if the fragment cannot be tokenized, or does not use each port exactly once.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use hydro_lang::prelude::*;
+    /// # use hydro_lang::live_collections::stream::{ExactlyOnce, TotalOrder};
+    /// # let mut flow = FlowBuilder::new();
+    /// let process = flow.process::<()>();
+    /// let doubled = process.source_iter(q!(vec![1, 2, 3])).raw_dfir::<i32, TotalOrder, ExactlyOnce>(
+    ///     "mod[input] -> map(|x: i32| x * 2) -> [output]mod;",
+    ///     nondet!(/** the fragment preserves order and does not duplicate elements */),
+    /// );
+    /// # let _ = doubled;
+    /// ```
+    pub fn raw_dfir<U, O2: Ordering, R2: Retries>(
+        self,
+        syntax: &str,
+        _nondet: NonDet,
+    ) -> Stream<U, L::DropConsistency, B, O2, R2> {
+        let tokens: proc_macro2::TokenStream = syntax
+            .parse()
+            .unwrap_or_else(|err| panic!("Failed to tokenize raw DFIR fragment: {}", err));
+        let mut ports = RawDfirPorts::default();
+        ports.scan(tokens);
+        ports.check();
+
+        let target_location = self.location().drop_consistency();
+        let raw = Stream::<U, L::DropConsistency, B, NoOrder, AtLeastOnce>::new(
+            target_location.clone(),
+            HydroNode::RawDfir {
+                syntax: syntax.to_owned(),
+                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                metadata: target_location.new_node_metadata(Stream::<
+                    U,
+                    L::DropConsistency,
+                    B,
+                    NoOrder,
+                    AtLeastOnce,
+                >::collection_kind()),
+            },
+        );
+        raw.assume_retries_trusted::<R2>(nondet!(
+            /// the caller vouches for `R2`, which the simulator cannot explore
+        ))
+        .assume_ordering::<O2>(nondet!(/** the fragment's ordering is assumed */))
+    }
+
     #[deprecated = "use `weaken_ordering::<NoOrder>()` instead"]
     /// Weakens the ordering guarantee provided by the stream to [`NoOrder`],
     /// which is always safe because that is the weakest possible guarantee.
@@ -2468,6 +2859,73 @@ where
             ),
         )
     }
+
+    /// Sheds load while the [`Bounded`] `overload` signal is `true`, by dropping elements
+    /// according to the `policy`. While the signal is `false`, all elements are passed through.
+    ///
+    /// The signal is typically derived from a measure of load, such as the length of a queue of
+    /// pending requests, so that a service keeps responding (to some requests) under overload
+    /// instead of buffering without bound. Returns the elements which were kept, along with the
+    /// number of elements which were shed, which can be exported as a metric.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use hydro_lang::live_collections::stream::ShedPolicy;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// // ticks are lazy by default, forces the second tick to run
+    /// tick.spin_batch(q!(1)).all_ticks().for_each(q!(|_| {}));
+    ///
+    /// let overloaded = tick.optional_first_tick(q!(())).is_none(); // false on tick 1, true on tick 2
+    /// let batch_first_tick = process
+    ///   .source_iter(q!(vec![1, 2]))
+    ///   .batch(&tick, nondet!(/** test */));
+    /// let batch_second_tick = process
+    ///   .source_iter(q!(vec![3, 4, 5, 6]))
+    ///   .batch(&tick, nondet!(/** test */))
+    ///   .defer_tick(); // appears on the second tick
+    /// let (kept, _shed_count) = batch_first_tick
+    ///   .chain(batch_second_tick)
+    ///   .shed_when(overloaded, ShedPolicy::keep_one_in(2, nondet!(/** test */)));
+    /// kept.all_ticks()
+    /// # }, |mut stream| async move {
+    /// // 1, 2, 3, 5
+    /// # for w in vec![1, 2, 3, 5] {
+    /// #     assert_eq!(stream.next().await.unwrap(), w);
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn shed_when(
+        self,
+        overload: Singleton<bool, L, Bounded>,
+        policy: ShedPolicy,
+    ) -> (
+        Stream<T, L, B, O, ExactlyOnce>,
+        Singleton<usize, L, B::StreamToMonotone>,
+    ) {
+        let with_signal = self.cross_singleton(overload);
+        let (kept, shed) = match policy.0 {
+            ShedPolicyKind::DropAll => with_signal.partition(q!(|(_, overloaded)| !*overloaded)),
+            ShedPolicyKind::KeepOneIn(n) => with_signal.partition(q!(
+                {
+                    let mut seen = 0usize;
+                    move |(_, overloaded)| {
+                        if !*overloaded {
+                            return true;
+                        }
+                        seen = (seen + 1) % n;
+                        seen == 1 % n
+                    }
+                },
+                commutative = manual_proof!(/** which elements are kept is non-deterministic, acknowledged by `ShedPolicy::keep_one_in` */)
+            )),
+        };
+        (kept.map(q!(|(d, _)| d)), shed.count())
+    }
 }
 
 impl<'a, T, L: Location<'a>, O: Ordering, R: Retries> Stream<T, L, Unbounded, O, R> {
@@ -2528,6 +2986,42 @@ impl<'a, T, L: Location<'a>, O: Ordering, R: Retries> Stream<T, L, Unbounded, O,
     {
         self.merge_unordered(other)
     }
+
+    /// Returns an [`Optional`] holding the most recent element of the stream, which is empty
+    /// until the first element arrives and is asynchronously updated as further elements arrive.
+    ///
+    /// Unlike [`Stream::last`], this does not require the stream to be [`TotalOrder`] or
+    /// [`ExactlyOnce`]: the latest element is whichever element happened to arrive last.
+    ///
+    /// # Non-Determinism
+    /// If the stream is not [`TotalOrder`], which element is the latest depends on the order in
+    /// which elements arrive, so the value of the optional is non-deterministic. In simulations,
+    /// the possible arrival orders are explored.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # use hydro_lang::live_collections::stream::NoOrder;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// # let readings: Stream<i32, _, Unbounded> = process.source_iter(q!(vec![3])).into();
+    /// let readings: Stream<i32, _, Unbounded, NoOrder> = // readings in any order
+    /// # readings.weaken_ordering();
+    /// readings
+    ///     .latest(nondet!(/** any recent reading is acceptable */))
+    ///     .sample_eager(nondet!(/** test */))
+    /// # }, |mut stream| async move {
+    /// // the most recent reading at each sample
+    /// # assert_eq!(stream.next().await.unwrap(), 3);
+    /// # }));
+    /// # }
+    /// ```
+    pub fn latest(self, nondet: NonDet) -> Optional<T, L::DropConsistency, Unbounded> {
+        self.assume_ordering::<TotalOrder>(nondet)
+            .assume_retries::<ExactlyOnce>(nondet)
+            .last()
+    }
 }
 
 impl<'a, T, L: Location<'a>, B: Boundedness, R: Retries> Stream<T, L, B, TotalOrder, R> {
@@ -3181,6 +3675,132 @@ where
     }
 }
 
+impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, L, B, O, R>
+where
+    L: TopLevel<'a>,
+{
+    /// Maps each element through the asynchronous function `f`, such as a lookup to a remote
+    /// service, memoizing the results in a least-recently-used cache of `capacity` entries.
+    ///
+    /// Elements whose result is cached are mapped without calling `f`. At most `capacity` calls
+    /// to `f` are in flight at once (see [`Stream::map_async_cached_with_max_in_flight`] to set
+    /// this separately), and their outputs are produced as they complete, regardless of input
+    /// order. Because the cache may return a result computed earlier, `f` should return
+    /// the same result for the same input while the flow is running.
+    ///
+    /// In simulations, lookups are resolved one at a time in the order of their inputs. Use
+    /// [`Stream::map_async_cached_with_resolver`] to replace `f` with a synchronous function
+    /// in simulations, for example when the remote service is not available.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use hydro_lang::prelude::*;
+    /// # let mut flow = FlowBuilder::new();
+    /// let process = flow.process::<()>();
+    /// let names = process.source_iter(q!([1u32, 2, 1])).map_async_cached(
+    ///     q!(128),
+    ///     q!(|id| async move {
+    ///         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    ///         format!("user-{}", id)
+    ///     }),
+    /// );
+    /// # let _ = names;
+    /// ```
+    pub fn map_async_cached<U, F, Fut>(
+        self,
+        capacity: impl QuotedWithContext<'a, usize, L>,
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<U, L, Unbounded, NoOrder, R>
+    where
+        T: Hash + Eq + Clone,
+        U: Clone,
+        F: Fn(T) -> Fut + 'a,
+        Fut: Future<Output = U> + 'a,
+    {
+        let capacity: syn::Expr = capacity.splice_typed_ctx(&self.location);
+        let root = get_this_crate();
+        let limits =
+            parse_quote!(#root::runtime_support::async_cache::AsyncCacheLimits::new(#capacity));
+        let f = f.splice_fn1_ctx(&self.location).into();
+        self.map_async_cached_node(limits, f, None)
+    }
+
+    /// Like [`Stream::map_async_cached`], but allows at most `max_in_flight` calls to `f` at once,
+    /// independently of the `capacity` of the cache. For example, a large cache in front of a
+    /// service which only accepts a few concurrent requests.
+    ///
+    /// # Panics
+    /// At runtime, if `capacity` or `max_in_flight` is zero.
+    pub fn map_async_cached_with_max_in_flight<U, F, Fut>(
+        self,
+        capacity: impl QuotedWithContext<'a, usize, L>,
+        max_in_flight: impl QuotedWithContext<'a, usize, L>,
+        f: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<U, L, Unbounded, NoOrder, R>
+    where
+        T: Hash + Eq + Clone,
+        U: Clone,
+        F: Fn(T) -> Fut + 'a,
+        Fut: Future<Output = U> + 'a,
+    {
+        let capacity: syn::Expr = capacity.splice_typed_ctx(&self.location);
+        let max_in_flight: syn::Expr = max_in_flight.splice_typed_ctx(&self.location);
+        let root = get_this_crate();
+        let limits = parse_quote! {
+            #root::runtime_support::async_cache::AsyncCacheLimits {
+                capacity: #capacity,
+                max_in_flight: #max_in_flight,
+            }
+        };
+        let f = f.splice_fn1_ctx(&self.location).into();
+        self.map_async_cached_node(limits, f, None)
+    }
+
+    /// Like [`Stream::map_async_cached`], but simulations map each element with the synchronous
+    /// `sim_resolver` instead of calling `f`.
+    pub fn map_async_cached_with_resolver<U, F, Fut, F2>(
+        self,
+        capacity: impl QuotedWithContext<'a, usize, L>,
+        f: impl IntoQuotedMut<'a, F, L>,
+        sim_resolver: impl IntoQuotedMut<'a, F2, L>,
+    ) -> Stream<U, L, Unbounded, NoOrder, R>
+    where
+        T: Hash + Eq + Clone,
+        U: Clone,
+        F: Fn(T) -> Fut + 'a,
+        Fut: Future<Output = U> + 'a,
+        F2: Fn(&T) -> U + 'a,
+    {
+        let capacity: syn::Expr = capacity.splice_typed_ctx(&self.location);
+        let root = get_this_crate();
+        let limits =
+            parse_quote!(#root::runtime_support::async_cache::AsyncCacheLimits::new(#capacity));
+        let f = f.splice_fn1_ctx(&self.location).into();
+        let sim_resolver = sim_resolver.splice_fn1_borrow_ctx(&self.location).into();
+        self.map_async_cached_node(limits, f, Some(sim_resolver))
+    }
+
+    fn map_async_cached_node<U>(
+        self,
+        limits: syn::Expr,
+        f: ClosureExpr,
+        sim_resolver: Option<DebugExpr>,
+    ) -> Stream<U, L, Unbounded, NoOrder, R> {
+        Stream::new(
+            self.location.clone(),
+            HydroNode::MapAsyncCached {
+                limits: limits.into(),
+                f,
+                sim_resolver,
+                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                metadata: self
+                    .location
+                    .new_node_metadata(Stream::<U, L, Unbounded, NoOrder, R>::collection_kind()),
+            },
+        )
+    }
+}
+
 impl<'a, T, L, O: Ordering, R: Retries> Stream<T, Tick<L>, Bounded, O, R>
 where
     L: Location<'a>,
@@ -3314,6 +3934,56 @@ where
     }
 }
 
+/// The ports used by a fragment passed to [`Stream::raw_dfir`].
+#[derive(Default)]
+struct RawDfirPorts {
+    /// Ports read with `mod[port]`.
+    inputs: Vec<String>,
+    /// Ports written with `[port]mod`.
+    outputs: Vec<String>,
+}
+
+impl RawDfirPorts {
+    fn scan(&mut self, tokens: proc_macro2::TokenStream) {
+        use proc_macro2::{Delimiter, TokenTree};
+
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Ident(ident) if ident == "mod" => {
+                    if let Some(TokenTree::Group(group)) = tokens.get(i + 1)
+                        && group.delimiter() == Delimiter::Bracket
+                    {
+                        self.inputs.push(group.stream().to_string());
+                    }
+                    if let Some(TokenTree::Group(group)) = i.checked_sub(1).map(|i| &tokens[i])
+                        && group.delimiter() == Delimiter::Bracket
+                    {
+                        self.outputs.push(group.stream().to_string());
+                    }
+                }
+                TokenTree::Group(group) => self.scan(group.stream()),
+                _ => {}
+            }
+        }
+    }
+
+    fn check(&self) {
+        if self.inputs != ["input"] {
+            panic!(
+                "A raw DFIR fragment must read from `mod[input]` exactly once, but it reads from: {:?}",
+                self.inputs
+            );
+        }
+        if self.outputs != ["output"] {
+            panic!(
+                "A raw DFIR fragment must write to `[output]mod` exactly once, but it writes to: {:?}",
+                self.outputs
+            );
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(feature = "deploy")]
@@ -3796,6 +4466,29 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "sim")]
+    #[test]
+    #[should_panic(expected = "saw 1 as the latest element")]
+    fn sim_latest_explores_arrival_order() {
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+
+        let (in_send, input) = node.sim_input::<_, NoOrder, _>();
+
+        let out_recv = input
+            .latest(nondet!(/** test */))
+            .sample_eager(nondet!(/** test */))
+            .sim_output();
+
+        flow.sim().exhaustive(async || {
+            in_send.send_many_unordered([1, 2]);
+
+            if out_recv.collect::<Vec<_>>().await.last() == Some(&1) {
+                panic!("saw 1 as the latest element, so arrival order must have been explored");
+            }
+        });
+    }
+
     #[cfg(feature = "sim")]
     #[test]
     fn sim_batch_unordered_shuffles_count() {
@@ -4116,6 +4809,44 @@ mod tests {
         assert_eq!(odd_results, vec![1, 3, 5]);
     }
 
+    #[cfg(feature = "deploy")]
+    #[tokio::test]
+    async fn map_fallible_routes_errors() {
+        let mut deployment = Deployment::new();
+
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let external = flow.external::<()>();
+
+        let (numbers, errors) = node
+            .source_iter(q!(vec!["1", "hello", "2", "world"]))
+            .map_fallible(q!(|s| s.parse::<i32>()));
+        let numbers_port = numbers.send_bincode_external(&external);
+        let errors_port = errors
+            .report_errors("parse")
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let mut numbers_out = nodes.connect(numbers_port).await;
+        let mut errors_out = nodes.connect(errors_port).await;
+
+        deployment.start().await.unwrap();
+
+        assert_eq!(numbers_out.next().await.unwrap(), 1);
+        assert_eq!(numbers_out.next().await.unwrap(), 2);
+        for _ in 0..2 {
+            let report = errors_out.next().await.unwrap();
+            assert_eq!(report.operator, "parse");
+            assert_eq!(report.message, "invalid digit found in string");
+        }
+    }
+
     #[cfg(feature = "deploy")]
     #[tokio::test]
     async fn unconsumed_inspect_still_runs() {
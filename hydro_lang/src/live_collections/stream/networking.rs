@@ -1,29 +1,45 @@
 //! Networking APIs for [`Stream`].
 
+use std::cell::RefCell;
 use std::marker::PhantomData;
+use std::rc::Rc;
 
-use serde::Serialize;
+use quote::quote;
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use stageleft::{q, quote_type};
 use syn::parse_quote;
 
-use super::{ExactlyOnce, MinOrder, Ordering, Stream, TotalOrder};
+use super::{
+    AtLeastOnce, ExactlyOnce, IsExactlyOnce, IsOrdered, MinOrder, NoOrder, Ordering, Stream,
+    TotalOrder,
+};
 use crate::compile::ir::{
-    DebugInstantiate, HydroIrOpMetadata, HydroNode, HydroRoot, NetworkRecv, NetworkSend,
+    CollectionKind, DebugInstantiate, DebugType, HydroIrOpMetadata, HydroNode, HydroRoot,
+    NetworkRecv, NetworkSend, SharedNode,
 };
-use crate::live_collections::boundedness::{Boundedness, Unbounded};
+use crate::live_collections::boundedness::{Bounded, Boundedness, Unbounded};
 use crate::live_collections::keyed_singleton::{KeyedSingleton, MonotonicKeys};
 use crate::live_collections::keyed_stream::KeyedStream;
+use crate::live_collections::optional::Optional;
+use crate::live_collections::singleton::Singleton;
 use crate::live_collections::sliced::sliced;
 use crate::live_collections::stream::Retries;
+#[cfg(feature = "tokio")]
+use crate::location::CreditGate;
 #[cfg(feature = "sim")]
 use crate::location::LocationKey;
 use crate::location::cluster::{ClusterIds, Consistency, NoConsistency};
 #[cfg(stageleft_runtime)]
 use crate::location::dynamic::DynLocation;
+#[cfg(feature = "tokio")]
+use crate::location::exactly_once::{ExternalExactlyOnceStream, ReceiverMessage, SendLogHandle};
+#[cfg(feature = "tokio")]
+use crate::location::external_process::ExternalBincodeSink;
 use crate::location::external_process::ExternalBincodeStream;
 use crate::location::{Cluster, External, Location, MemberId, MembershipEvent, Process};
-use crate::networking::{NetworkFor, TCP};
+use crate::networking::versioned::HydroMessage;
+use crate::networking::{DeadLetters, DecodeError, DecodeErrorPolicy, NetworkFor, TCP};
 use crate::nondet::{NonDet, nondet};
 use crate::properties::manual_proof;
 #[cfg(feature = "sim")]
@@ -31,7 +47,7 @@ use crate::sim::SimReceiver;
 use crate::staging_util::get_this_crate;
 
 // same as the one in `hydro_std`, but internal use only
-fn track_membership<'a, C, L: Location<'a>>(
+pub(crate) fn track_membership<'a, C, L: Location<'a>>(
     membership: KeyedStream<MemberId<C>, MembershipEvent, L, Unbounded>,
 ) -> KeyedSingleton<MemberId<C>, bool, L, MonotonicKeys> {
     membership.fold(
@@ -93,6 +109,230 @@ pub(crate) fn deserialize_bincode<T: DeserializeOwned>(tagged: Option<&syn::Type
     deserialize_bincode_with_type(tagged, &quote_type::<T>())
 }
 
+fn try_deserialize_bincode_with_type(tagged: Option<&syn::Type>, t_type: &syn::Type) -> syn::Expr {
+    let root = get_this_crate();
+    let decode_error = quote_type::<DecodeError>();
+    let try_deserialize = quote! {
+        |b: &[u8]| #root::runtime_support::bincode::deserialize::<#t_type>(b).map_err(|e| #decode_error {
+            bytes: b.to_vec(),
+            message: e.to_string(),
+        })
+    };
+
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                (#try_deserialize)(&b).map(|data| (#root::__staged::location::MemberId::<#c_type>::from_tagless(id as #root::__staged::location::TaglessMemberId), data))
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                (#try_deserialize)(&res.unwrap())
+            }
+        }
+    }
+}
+
+pub(crate) fn try_deserialize_bincode<T: DeserializeOwned>(
+    tagged: Option<&syn::Type>,
+) -> syn::Expr {
+    try_deserialize_bincode_with_type(tagged, &quote_type::<T>())
+}
+
+/// Like [`serialize_bincode`], but wraps each message in a versioned envelope (see
+/// [`crate::networking::versioned`]).
+pub(crate) fn serialize_versioned<T: HydroMessage>(is_demux: bool) -> syn::Expr {
+    let root = get_this_crate();
+    let t_type = quote_type::<T>();
+
+    if is_demux {
+        parse_quote! {
+            #root::runtime_support::stageleft::runtime_support::fn1_type_hint::<(#root::__staged::location::MemberId<_>, #t_type), _>(
+                |(id, data)| {
+                    (id.into_tagless(), #root::networking::versioned::encode::<#t_type>(data).unwrap().into())
+                }
+            )
+        }
+    } else {
+        parse_quote! {
+            #root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#t_type, _>(
+                |data| {
+                    #root::networking::versioned::encode::<#t_type>(data).unwrap().into()
+                }
+            )
+        }
+    }
+}
+
+/// Like [`deserialize_bincode`], but for messages in a versioned envelope.
+pub(crate) fn deserialize_versioned<T: HydroMessage>(tagged: Option<&syn::Type>) -> syn::Expr {
+    let root = get_this_crate();
+    let t_type = quote_type::<T>();
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                (#root::__staged::location::MemberId::<#c_type>::from_tagless(id as #root::__staged::location::TaglessMemberId), #root::networking::versioned::decode::<#t_type>(&b).unwrap())
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                #root::networking::versioned::decode::<#t_type>(&res.unwrap()).unwrap()
+            }
+        }
+    }
+}
+
+/// Like [`try_deserialize_bincode`], but for messages in a versioned envelope.
+pub(crate) fn try_deserialize_versioned<T: HydroMessage>(tagged: Option<&syn::Type>) -> syn::Expr {
+    let root = get_this_crate();
+    let t_type = quote_type::<T>();
+    let decode_error = quote_type::<DecodeError>();
+    let try_deserialize = quote! {
+        |b: &[u8]| #root::networking::versioned::decode::<#t_type>(b).map_err(|e| #decode_error {
+            bytes: b.to_vec(),
+            message: e.to_string(),
+        })
+    };
+
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                (#try_deserialize)(&b).map(|data| (#root::__staged::location::MemberId::<#c_type>::from_tagless(id as #root::__staged::location::TaglessMemberId), data))
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                (#try_deserialize)(&res.unwrap())
+            }
+        }
+    }
+}
+
+/// Lowers a channel's [`DecodeErrorPolicy`] around its `Network` node, whose deserialize pipeline
+/// must have been built with [`DecodeErrorPolicy::deserialize_thunk`].
+///
+/// Under [`DecodeErrorPolicy::Panic`] the node is returned unchanged. Otherwise the node instead
+/// emits `Result<_, DecodeError>`, and the errors are filtered out of the returned node, which
+/// keeps the metadata of the original node. Under [`DecodeErrorPolicy::DeadLetter`], the errors
+/// are also sent to the dead letter stream.
+pub(crate) fn apply_decode_error_policy<'a>(
+    to: &impl Location<'a>,
+    policy: DecodeErrorPolicy,
+    mut network: HydroNode,
+) -> HydroNode {
+    let HydroNode::Network {
+        deserialize,
+        metadata,
+        ..
+    } = &mut network
+    else {
+        panic!("expected a Network node");
+    };
+
+    if matches!(policy, DecodeErrorPolicy::Panic) {
+        return network;
+    }
+
+    assert!(
+        !matches!(deserialize, NetworkRecv::Embedded { .. }),
+        "Only `DecodeErrorPolicy::Panic` is supported on embedded network channels."
+    );
+
+    let decode_error = quote_type::<DecodeError>();
+    let fallible = |t: syn::Type| -> DebugType {
+        let result_type: syn::Type = parse_quote!(::std::result::Result<#t, #decode_error>);
+        result_type.into()
+    };
+    let fallible_kind = match &metadata.collection_kind {
+        CollectionKind::Stream {
+            bound,
+            order,
+            retry,
+            element_type,
+        } => CollectionKind::Stream {
+            bound: bound.clone(),
+            order: order.clone(),
+            retry: retry.clone(),
+            element_type: fallible(*element_type.0.clone()),
+        },
+        CollectionKind::KeyedStream {
+            bound,
+            value_order,
+            value_retry,
+            key_type,
+            value_type,
+        } => {
+            let (key_type, value_type) = (&key_type.0, &value_type.0);
+            CollectionKind::Stream {
+                bound: bound.clone(),
+                order: value_order.clone(),
+                retry: value_retry.clone(),
+                element_type: fallible(parse_quote!((#key_type, #value_type))),
+            }
+        }
+        other => panic!("unexpected collection kind for a Network node: {:?}", other),
+    };
+    let ok: syn::Expr = parse_quote!(|r: ::std::result::Result<_, #decode_error>| r.ok());
+    let out_metadata = std::mem::replace(metadata, to.new_node_metadata(fallible_kind.clone()));
+
+    match policy {
+        DecodeErrorPolicy::Panic => unreachable!(),
+        DecodeErrorPolicy::Drop => HydroNode::FilterMap {
+            f: ok.into(),
+            input: Box::new(network),
+            metadata: out_metadata,
+        },
+        DecodeErrorPolicy::DeadLetter(DeadLetters { cycle_id, location }) => {
+            assert_eq!(Location::id(to), location, "locations do not match");
+
+            let shared = SharedNode(Rc::new(RefCell::new(network)));
+            let is_ok: syn::Expr =
+                parse_quote!(|r: &::std::result::Result<_, #decode_error>| r.is_ok());
+            let err: syn::Expr = parse_quote!(|r: ::std::result::Result<_, #decode_error>| r.err());
+
+            let errors = HydroNode::FilterMap {
+                f: err.into(),
+                input: Box::new(HydroNode::Partition {
+                    inner: SharedNode(shared.0.clone()),
+                    f: is_ok.clone().into(),
+                    is_true: false,
+                    metadata: to.new_node_metadata(fallible_kind.clone()),
+                }),
+                metadata: to.new_node_metadata(CollectionKind::Stream {
+                    bound: Unbounded::BOUND_KIND,
+                    order: NoOrder::ORDERING_KIND,
+                    retry: AtLeastOnce::RETRIES_KIND,
+                    element_type: decode_error.clone().into(),
+                }),
+            };
+            to.flow_state()
+                .borrow_mut()
+                .push_root(HydroRoot::CycleSink {
+                    cycle_id,
+                    input: Box::new(errors),
+                    op_metadata: HydroIrOpMetadata::new(),
+                });
+
+            HydroNode::FilterMap {
+                f: ok.into(),
+                input: Box::new(HydroNode::Partition {
+                    inner: shared,
+                    f: is_ok.into(),
+                    is_true: true,
+                    metadata: to.new_node_metadata(fallible_kind),
+                }),
+                metadata: out_metadata,
+            }
+        }
+    }
+}
+
 impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, Process<'a, L>, B, O, R> {
     #[deprecated = "use Stream::send(..., TCP.fail_stop().bincode()) instead"]
     /// "Moves" elements of this stream to a new distributed location by sending them over the network,
@@ -164,12 +404,13 @@ impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, Process<'a, L>
     pub fn send<L2, N: NetworkFor<T>>(
         self,
         to: &Process<'a, L2>,
-        via: N,
+        mut via: N,
     ) -> Stream<T, Process<'a, L2>, Unbounded, <O as MinOrder<N::OrderingGuarantee>>::Min, R>
     where
         T: Serialize + DeserializeOwned,
         O: MinOrder<N::OrderingGuarantee>,
     {
+        let on_decode_error = via.take_decode_error_policy();
         let name = via.name();
         if to.multiversioned() && name.is_none() {
             panic!(
@@ -194,28 +435,32 @@ impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, Process<'a, L>
                     serialize_fn: Some(N::serialize_thunk(false).into()),
                 },
                 NetworkRecv::Custom {
-                    deserialize_fn: Some(N::deserialize_thunk(None).into()),
+                    deserialize_fn: Some(on_decode_error.deserialize_thunk::<T, N>(None).into()),
                 },
             )
         };
 
         Stream::new(
             to.clone(),
-            HydroNode::Network {
-                name: name.map(ToOwned::to_owned),
-                networking_info: N::networking_info(),
-                serialize,
-                deserialize,
-                instantiate_fn: DebugInstantiate::Building,
-                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
-                metadata: to.new_node_metadata(Stream::<
-                    T,
-                    Process<'a, L2>,
-                    Unbounded,
-                    <O as MinOrder<N::OrderingGuarantee>>::Min,
-                    R,
-                >::collection_kind()),
-            },
+            apply_decode_error_policy(
+                to,
+                on_decode_error,
+                HydroNode::Network {
+                    name: name.map(ToOwned::to_owned),
+                    networking_info: N::networking_info(),
+                    serialize,
+                    deserialize,
+                    instantiate_fn: DebugInstantiate::Building,
+                    input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                    metadata: to.new_node_metadata(Stream::<
+                        T,
+                        Process<'a, L2>,
+                        Unbounded,
+                        <O as MinOrder<N::OrderingGuarantee>>::Min,
+                        R,
+                    >::collection_kind()),
+                },
+            ),
         )
     }
 
@@ -421,6 +666,10 @@ impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, Process<'a, L>
     /// serialization. The external process can receive these elements by establishing a TCP
     /// connection and decoding using [`tokio_util::codec::LengthDelimitedCodec`].
     ///
+    /// Elements are sent as fast as they are produced, regardless of how fast the external process
+    /// consumes them. To slow down the sources of the stream instead, see
+    /// [`Stream::send_bincode_external_credited`].
+    ///
     /// # Example
     /// ```rust
     /// # #[cfg(feature = "deploy")] {
@@ -478,6 +727,64 @@ impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, Process<'a, L>
         }
     }
 
+    /// Like [`Stream::send_bincode_external`], but with credit-based flow control on this edge:
+    /// the external process grants credits through the returned sink, and the sources paused by
+    /// `gate` stop being polled once its credits are exhausted. See [`crate::location::credits`].
+    ///
+    /// The gate is created with [`Process::credit_gate`] before the sources of this stream, and
+    /// passed here to tie it to this edge. Each item produced by a paused source uses up one
+    /// credit, so the external process usually grants one credit for each output it has finished
+    /// processing.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::{SinkExt, StreamExt};
+    /// # tokio_test::block_on(async move {
+    /// let mut flow = FlowBuilder::new();
+    /// let process = flow.process::<()>();
+    /// let external = flow.external::<()>();
+    /// // Only two numbers are produced before the external process grants credits.
+    /// let gate = process.credit_gate(2);
+    /// let (external_handle, credits_handle) = process
+    ///     .source_stream(q!(gate.pause(futures::stream::iter(1..=4))))
+    ///     .send_bincode_external_credited(&external, gate);
+    ///
+    /// let mut deployment = hydro_deploy::Deployment::new();
+    /// let nodes = flow
+    ///     .with_process(&process, deployment.Localhost())
+    ///     .with_external(&external, deployment.Localhost())
+    ///     .deploy(&mut deployment);
+    ///
+    /// deployment.deploy().await.unwrap();
+    /// let mut external_recv_stream = nodes.connect(external_handle).await;
+    /// let mut credits = nodes.connect(credits_handle).await;
+    /// deployment.start().await.unwrap();
+    ///
+    /// for w in 1..=4 {
+    ///     assert_eq!(external_recv_stream.next().await, Some(w));
+    ///     // grant a credit for each processed output
+    ///     credits.send(1).await.unwrap();
+    /// }
+    /// # });
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn send_bincode_external_credited<L2>(
+        self,
+        other: &External<L2>,
+        gate: CreditGate<'a, L>,
+    ) -> (ExternalBincodeStream<T, O, R>, ExternalBincodeSink<u32>)
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let (credits, grants) = self.location.source_external_bincode(other);
+        grants.for_each(q!(move |credits| gate.grant(credits)));
+
+        (self.send_bincode_external(other), credits)
+    }
+
     #[cfg(feature = "sim")]
     /// Sets up a simulation output port for this stream, allowing test code to receive elements
     /// sent to this stream during simulation.
@@ -497,6 +804,87 @@ impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, Process<'a, L>
     }
 }
 
+/// A message of a bounded stream framed with [`Stream::framed`], so that a receiver can tell when
+/// it has received the entire stream.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StreamFrame<T> {
+    /// An element of the stream.
+    Element(T),
+    /// Marks the end of the stream, after all of its elements.
+    End,
+}
+
+impl<'a, T, L: Location<'a>, O: Ordering, R: Retries> Stream<T, L, Bounded, O, R> {
+    /// Wraps each element of this bounded stream in a [`StreamFrame::Element`], followed by a
+    /// final [`StreamFrame::End`] marker.
+    ///
+    /// Sending the framed stream over the network (with any of [`Stream::send`],
+    /// [`Stream::broadcast_closed`], [`Stream::demux`], ...) lets the destination collect it with
+    /// [`Stream::collect_framed_vec`] (or [`KeyedStream::collect_framed_vec`] for streams sent
+    /// from a cluster), which resolves once every element has arrived, without sending a count of
+    /// the elements out-of-band. Because the marker must arrive after every element, this requires
+    /// a network which preserves ordering and delivers every message exactly once.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::multi_location_test(|flow, p_out| {
+    /// let p1 = flow.process::<()>();
+    /// let numbers: Stream<_, Process<_>, Bounded> = p1.source_iter(q!(vec![1, 2, 3]));
+    /// let p2 = flow.process::<()>();
+    /// let on_p2: Optional<Vec<_>, Process<_>, Unbounded> = numbers
+    ///     .framed()
+    ///     .send(&p2, TCP.fail_stop().bincode())
+    ///     .collect_framed_vec();
+    /// // vec![1, 2, 3]
+    /// # on_p2.sample_eager(nondet!(/** test */)).send(&p_out, TCP.fail_stop().bincode())
+    /// # }, |mut stream| async move {
+    /// # assert_eq!(stream.next().await, Some(vec![1, 2, 3]));
+    /// # }));
+    /// # }
+    /// ```
+    pub fn framed(self) -> Stream<StreamFrame<T>, L, Bounded, TotalOrder, ExactlyOnce>
+    where
+        O: IsOrdered,
+        R: IsExactlyOnce,
+    {
+        let location = self.location.clone();
+        self.make_totally_ordered()
+            .make_exactly_once()
+            .map(q!(|item| {
+                crate::live_collections::stream::networking::StreamFrame::Element(item)
+            }))
+            .chain(location.source_iter(q!([
+                crate::live_collections::stream::networking::StreamFrame::End
+            ])))
+    }
+}
+
+impl<'a, T, L: Location<'a>> Stream<StreamFrame<T>, L, Unbounded, TotalOrder, ExactlyOnce> {
+    /// Collects the elements of a stream framed with [`Stream::framed`] into a [`Vec`], once the
+    /// entire stream has been received.
+    ///
+    /// Unlike [`Stream::collect_vec`], whose result grows as elements asynchronously arrive, the
+    /// returned [`Optional`] is empty until the [`StreamFrame::End`] marker arrives, and then
+    /// holds all of the elements. If the connection is dropped before the marker is received, it
+    /// remains empty.
+    pub fn collect_framed_vec(self) -> Optional<Vec<T>, L, Unbounded> {
+        let received: Singleton<(Vec<T>, bool), L, Unbounded> = self.fold(
+            q!(|| (Vec::new(), false)),
+            q!(|(items, done), frame| match frame {
+                crate::live_collections::stream::networking::StreamFrame::Element(item) => {
+                    items.push(item)
+                }
+                crate::live_collections::stream::networking::StreamFrame::End => *done = true,
+            }),
+        );
+
+        received.filter_map(q!(|(items, done)| if done { Some(items) } else { None }))
+    }
+}
+
 impl<'a, T, L: Location<'a>, B: Boundedness> Stream<T, L, B, TotalOrder, ExactlyOnce> {
     /// Creates an external output for embedded deployment mode.
     ///
@@ -761,6 +1149,85 @@ impl<'a, T, L, B: Boundedness> Stream<T, Process<'a, L>, B, TotalOrder, ExactlyO
         }
         .demux(to, via)
     }
+
+    /// Sends the elements of this stream to an external (non-Hydro) process exactly once and in
+    /// order, using [`bincode`] serialization. Unlike [`Stream::send_bincode_external`], each
+    /// element is kept in a send log until the external process acknowledges it, and is resent
+    /// whenever a receiver connects before then, including after this process restarts. See the
+    /// [`exactly_once`] module for details.
+    ///
+    /// The external process receives the elements by connecting to the returned handle, which
+    /// gives an [`ExactlyOnceReceiver`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # tokio_test::block_on(async move {
+    /// let mut flow = FlowBuilder::new();
+    /// let process = flow.process::<()>();
+    /// let numbers: Stream<_, Process<_>, Bounded> = process.source_iter(q!(vec![1, 2, 3]));
+    /// let external = flow.external::<()>();
+    /// let external_handle = numbers.send_bincode_external_exactly_once(&external);
+    ///
+    /// let mut deployment = hydro_deploy::Deployment::new();
+    /// let nodes = flow
+    ///     .with_process(&process, deployment.Localhost())
+    ///     .with_external(&external, deployment.Localhost())
+    ///     .deploy(&mut deployment);
+    ///
+    /// deployment.deploy().await.unwrap();
+    /// let mut receiver = nodes.connect(external_handle).await;
+    /// deployment.start().await.unwrap();
+    ///
+    /// for w in 1..=3 {
+    ///     assert_eq!(receiver.recv().await.unwrap(), Some(w));
+    /// }
+    /// assert_eq!(receiver.last_seq(), 3);
+    /// # });
+    /// # }
+    /// ```
+    ///
+    /// [`exactly_once`]: crate::location::exactly_once
+    /// [`ExactlyOnceReceiver`]: crate::location::exactly_once::ExactlyOnceReceiver
+    #[cfg(feature = "tokio")]
+    pub fn send_bincode_external_exactly_once<L2>(
+        self,
+        other: &External<L2>,
+    ) -> ExternalExactlyOnceStream<T>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let (acks, messages) = self
+            .location
+            .source_external_bincode::<_, ReceiverMessage, TotalOrder, ExactlyOnce>(other);
+        let log = SendLogHandle::<T> {
+            location_key: self.location.key,
+            port_id: acks.port_id,
+            _phantom: PhantomData,
+        };
+        let connected = messages.filter_map(q!(move |message| log.receive(message)));
+
+        // Within a tick, the log is resent to new receivers before the fresh elements are
+        // appended to it. Fresh elements of earlier ticks may arrive before the resent ones, which
+        // the receiver puts back in order.
+        let tick = self.location.tick();
+        let resent = connected
+            .batch(
+                &tick,
+                nondet!(/** resent elements are deduplicated by the receiver */),
+            )
+            .flat_map_ordered(q!(move |()| log.resync()));
+        let fresh = self
+            .batch(
+                &tick,
+                nondet!(/** the elements are numbered in order either way */),
+            )
+            .map(q!(move |element| log.append(element)));
+        let elements = resent.chain(fresh).all_ticks().send_bincode_external(other);
+
+        ExternalExactlyOnceStream { elements, acks }
+    }
 }
 
 impl<'a, T, L, B: Boundedness, C: Consistency>
@@ -1035,7 +1502,7 @@ impl<'a, T, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
     pub fn send<L2, N: NetworkFor<T>>(
         self,
         to: &Process<'a, L2>,
-        via: N,
+        mut via: N,
     ) -> KeyedStream<
         MemberId<L>,
         T,
@@ -1048,6 +1515,7 @@ impl<'a, T, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
         T: Serialize + DeserializeOwned,
         O: MinOrder<N::OrderingGuarantee>,
     {
+        let on_decode_error = via.take_decode_error_policy();
         let name = via.name();
         if to.multiversioned() && name.is_none() {
             panic!(
@@ -1072,7 +1540,11 @@ impl<'a, T, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
                     serialize_fn: Some(N::serialize_thunk(false).into()),
                 },
                 NetworkRecv::Custom {
-                    deserialize_fn: Some(N::deserialize_thunk(Some(&quote_type::<L>())).into()),
+                    deserialize_fn: Some(
+                        on_decode_error
+                            .deserialize_thunk::<T, N>(Some(&quote_type::<L>()))
+                            .into(),
+                    ),
                 },
             )
         };
@@ -1085,21 +1557,25 @@ impl<'a, T, L, B: Boundedness, C: Consistency, O: Ordering, R: Retries>
             R,
         > = Stream::new(
             to.clone(),
-            HydroNode::Network {
-                name: name.map(ToOwned::to_owned),
-                networking_info: N::networking_info(),
-                serialize,
-                deserialize,
-                instantiate_fn: DebugInstantiate::Building,
-                input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
-                metadata: to.new_node_metadata(Stream::<
-                    (MemberId<L>, T),
-                    Process<'a, L2>,
-                    Unbounded,
-                    <O as MinOrder<N::OrderingGuarantee>>::Min,
-                    R,
-                >::collection_kind()),
-            },
+            apply_decode_error_policy(
+                to,
+                on_decode_error,
+                HydroNode::Network {
+                    name: name.map(ToOwned::to_owned),
+                    networking_info: N::networking_info(),
+                    serialize,
+                    deserialize,
+                    instantiate_fn: DebugInstantiate::Building,
+                    input: Box::new(self.ir_node.replace(HydroNode::Placeholder)),
+                    metadata: to.new_node_metadata(Stream::<
+                        (MemberId<L>, T),
+                        Process<'a, L2>,
+                        Unbounded,
+                        <O as MinOrder<N::OrderingGuarantee>>::Min,
+                        R,
+                    >::collection_kind()),
+                },
+            ),
         );
 
         raw_stream.into_keyed()
@@ -1521,6 +1997,81 @@ mod tests {
         assert_eq!(instances, 4); // 2^{3 - 1}
     }
 
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_send_bincode_o2o_drop_decode_errors() {
+        use crate::networking::DecodeErrorPolicy;
+
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let node2 = flow.process::<()>();
+
+        let (in_send, input) = node.sim_input();
+
+        let out_recv = input
+            .send(
+                &node2,
+                TCP.fail_stop()
+                    .bincode()
+                    .on_decode_error(DecodeErrorPolicy::Drop),
+            )
+            .batch(&node2.tick(), nondet!(/** test */))
+            .count()
+            .all_ticks()
+            .sim_output();
+
+        let instances = flow.sim().exhaustive(async || {
+            in_send.send(());
+            in_send.send(());
+            in_send.send(());
+
+            let received = out_recv.collect::<Vec<_>>().await;
+            assert!(received.into_iter().sum::<usize>() == 3);
+        });
+
+        assert_eq!(instances, 4); // 2^{3 - 1}
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_send_bincode_o2o_dead_letter() {
+        use crate::live_collections::boundedness::Unbounded;
+        use crate::live_collections::stream::{AtLeastOnce, NoOrder, Stream};
+        use crate::location::Process;
+        use crate::networking::{DecodeError, DecodeErrorPolicy};
+
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let node2 = flow.process::<()>();
+
+        let (in_send, input) = node.sim_input();
+        let (dead_letters_handle, dead_letters) = node2.forward_ref::<Stream<
+            DecodeError,
+            Process<'_, ()>,
+            Unbounded,
+            NoOrder,
+            AtLeastOnce,
+        >>();
+
+        let out_recv = input
+            .send(
+                &node2,
+                TCP.fail_stop()
+                    .bincode()
+                    .on_decode_error(DecodeErrorPolicy::DeadLetter(dead_letters_handle.into())),
+            )
+            .sim_output();
+        let dead_letters_recv = dead_letters.sim_output();
+
+        flow.sim().exhaustive(async || {
+            in_send.send(1);
+            in_send.send(2);
+
+            out_recv.assert_yields_only([1, 2]).await;
+            dead_letters_recv.assert_no_more().await;
+        });
+    }
+
     #[cfg(feature = "sim")]
     #[test]
     fn sim_send_bincode_m2o() {
@@ -1894,6 +2445,112 @@ mod tests {
             });
     }
 
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_framed_collect_o2o() {
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let node2 = flow.process::<()>();
+
+        let collected = node
+            .source_iter(q!(vec![1, 2, 3]))
+            .framed()
+            .send(&node2, TCP.fail_stop().bincode())
+            .collect_framed_vec();
+        let out_recv = sliced! {
+            let snapshot = use(collected, nondet!(/** test */));
+            snapshot.into_stream()
+        }
+        .sim_output();
+
+        flow.sim().exhaustive(async || {
+            let snapshots: Vec<Vec<i32>> = out_recv.collect().await;
+            // The vector is only observed once every element has arrived.
+            assert!(!snapshots.is_empty());
+            assert!(snapshots.iter().all(|snapshot| snapshot == &vec![1, 2, 3]));
+        });
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_framed_collect_o2m() {
+        let mut flow = FlowBuilder::new();
+        let cluster = flow.cluster::<()>();
+        let node = flow.process::<()>();
+
+        let collected = node
+            .source_iter(q!(vec![1, 2]))
+            .framed()
+            .broadcast_closed(&cluster, TCP.fail_stop().bincode())
+            .collect_framed_vec();
+        let out_recv = sliced! {
+            let snapshot = use(collected, nondet!(/** test */));
+            snapshot.into_stream()
+        }
+        .send(&node, TCP.fail_stop().bincode())
+        .entries()
+        .sim_output();
+
+        flow.sim()
+            .with_cluster_size(&cluster, 2)
+            .exhaustive(async || {
+                let mut snapshots: Vec<(MemberId<()>, Vec<i32>)> = out_recv.collect().await;
+                assert!(
+                    snapshots
+                        .iter()
+                        .all(|(_, snapshot)| snapshot == &vec![1, 2])
+                );
+                snapshots.sort();
+                snapshots.dedup();
+                assert_eq!(
+                    snapshots,
+                    vec![
+                        (MemberId::from_raw_id(0), vec![1, 2]),
+                        (MemberId::from_raw_id(1), vec![1, 2]),
+                    ]
+                );
+            });
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_framed_collect_m2o() {
+        let mut flow = FlowBuilder::new();
+        let cluster = flow.cluster::<()>();
+        let node = flow.process::<()>();
+
+        let collected = cluster
+            .source_iter(q!(vec![1, 2]))
+            .framed()
+            .send(&node, TCP.fail_stop().bincode())
+            .collect_framed_vec();
+        let out_recv = sliced! {
+            let snapshot = use(collected, nondet!(/** test */));
+            snapshot.entries()
+        }
+        .sim_output();
+
+        flow.sim()
+            .with_cluster_size(&cluster, 2)
+            .exhaustive(async || {
+                let mut snapshots: Vec<(MemberId<()>, Vec<i32>)> = out_recv.collect().await;
+                assert!(
+                    snapshots
+                        .iter()
+                        .all(|(_, snapshot)| snapshot == &vec![1, 2])
+                );
+                snapshots.sort();
+                snapshots.dedup();
+                assert_eq!(
+                    snapshots,
+                    vec![
+                        (MemberId::from_raw_id(0), vec![1, 2]),
+                        (MemberId::from_raw_id(1), vec![1, 2]),
+                    ]
+                );
+            });
+    }
+
     /// Compile-time check that the consistency guarantee of `broadcast_closed` output tracks
     /// the network's failure policy: `fail_stop` and `lossy_delayed_forever` preserve
     /// [`EventualConsistency`], while plain `lossy` only provides [`NoConsistency`].
@@ -0,0 +1,427 @@
+//! Persistent key-value stores local to each member of a cluster, backed by [`redb`].
+//!
+//! A store is opened by name with [`Cluster::local_store`], and lives in the data directory that
+//! Hydro Deploy provides to each member (see [`hydro_deploy_integration::data_dir`]). Each member
+//! has its own copy of every store, which is preserved when the member restarts, so stateful
+//! services can reload their state with [`LocalStoreHandle::load`] instead of rebuilding it from
+//! their peers. All stores on a member share a single database, which is opened the first time
+//! one of them is used.
+//!
+//! Keys and values are serialized with [`bincode`], so a store can only be read back with the
+//! same key and value types it was written with.
+//!
+//! The same data directory can also hold the checkpoints of a DFIR graph, see [`save_checkpoint`]
+//! and [`restore_checkpoint`].
+
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+
+use dfir_rs::scheduled::context::{Dfir, TickClosure};
+use hydro_deploy_integration::DATA_DIR_ENV;
+use quote::quote;
+use redb::{Database, ReadableDatabase, TableDefinition, TableError};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use stageleft::runtime_support::{FreeVariableWithContextWithProps, QuoteTokens};
+use stageleft::{q, quote_type};
+
+use crate::live_collections::boundedness::{Bounded, Boundedness};
+use crate::live_collections::stream::{ExactlyOnce, Stream, TotalOrder};
+use crate::location::{Cluster, Location};
+use crate::staging_util::get_this_crate;
+
+/// The data directory of this member, see [`hydro_deploy_integration::data_dir`].
+fn data_dir() -> PathBuf {
+    hydro_deploy_integration::data_dir()
+        .unwrap_or_else(|| panic!("`{DATA_DIR_ENV}` must be set to use local stores"))
+}
+
+fn db() -> Arc<Database> {
+    static DB: OnceLock<Arc<Database>> = OnceLock::new();
+    DB.get_or_init(|| {
+        let dir = data_dir();
+        let path = dir.join("local_store.redb");
+        let db = std::fs::create_dir_all(&dir)
+            .map_err(redb::DatabaseError::from)
+            .and_then(|()| Database::create(&path))
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to open the local store at {}: {}",
+                    path.display(),
+                    e
+                )
+            });
+        Arc::new(db)
+    })
+    .clone()
+}
+
+/// A persistent key-value store local to one cluster member.
+///
+/// Every write is durable on disk when it returns. Use [`Self::apply`] (or [`Self::sink`]) to
+/// write many entries at once, which is much faster than writing them one by one.
+///
+/// # Panics
+/// Every method panics if the underlying database fails, since the member cannot keep its state
+/// consistent with the store afterwards.
+pub struct LocalStore<K, V> {
+    name: String,
+    db: Arc<Database>,
+    _phantom: PhantomData<fn(K, V) -> (K, V)>,
+}
+
+impl<K, V> Clone for LocalStore<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            db: self.db.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: Serialize + DeserializeOwned, V: Serialize + DeserializeOwned> LocalStore<K, V> {
+    /// Opens the store `name`, which is created when it is first written to.
+    ///
+    /// # Panics
+    /// If [`DATA_DIR_ENV`] is not set, or the database cannot be opened.
+    pub fn open(name: &str) -> Self {
+        Self::open_in(db(), name)
+    }
+
+    fn open_in(db: Arc<Database>, name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            db,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn table(&self) -> TableDefinition<'_, &'static [u8], &'static [u8]> {
+        TableDefinition::new(&self.name)
+    }
+
+    /// The value stored for `key`, if any.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let txn = self.expect(self.db.begin_read());
+        let table = match txn.open_table(self.table()) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return None,
+            Err(e) => self.fail(e),
+        };
+        let value = self.expect(table.get(encode(key).as_slice()))?;
+        Some(decode(value.value()))
+    }
+
+    /// Stores `value` for `key`, returning the previous value.
+    pub fn insert(&self, key: &K, value: &V) -> Option<V> {
+        self.write(|table| {
+            let previous =
+                self.expect(table.insert(encode(key).as_slice(), encode(value).as_slice()));
+            previous.map(|previous| decode(previous.value()))
+        })
+    }
+
+    /// Removes the value stored for `key`, returning it.
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.write(|table| {
+            let previous = self.expect(table.remove(encode(key).as_slice()));
+            previous.map(|previous| decode(previous.value()))
+        })
+    }
+
+    /// Applies each of `updates` in order, atomically: `(key, Some(value))` stores `value` for
+    /// `key`, and `(key, None)` removes `key`.
+    pub fn apply(&self, updates: impl IntoIterator<Item = (K, Option<V>)>) {
+        self.write(|table| {
+            for (key, value) in updates {
+                match value {
+                    Some(value) => {
+                        self.expect(
+                            table.insert(encode(&key).as_slice(), encode(&value).as_slice()),
+                        );
+                    }
+                    None => {
+                        self.expect(table.remove(encode(&key).as_slice()));
+                    }
+                }
+            }
+        })
+    }
+
+    /// All entries in the store, ordered by their serialized keys, as of when this is called.
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> + use<K, V> {
+        let txn = self.expect(self.db.begin_read());
+        let range = match txn.open_table(self.table()) {
+            Ok(table) => Some(self.expect(table.range::<&[u8]>(..))),
+            Err(TableError::TableDoesNotExist(_)) => None,
+            Err(e) => self.fail(e),
+        };
+        let name = self.name.clone();
+        range.into_iter().flatten().map(move |entry| {
+            let (key, value) = entry.unwrap_or_else(|e| {
+                panic!("Failed to read local store `{}`: {}", name, e);
+            });
+            (decode(key.value()), decode(value.value()))
+        })
+    }
+
+    /// A [`futures::Sink`] which buffers the updates sent to it (see [`Self::apply`]), and writes
+    /// them in a single transaction whenever it is flushed.
+    pub fn sink(&self) -> LocalStoreSink<K, V> {
+        LocalStoreSink {
+            store: self.clone(),
+            buffered: Vec::new(),
+        }
+    }
+
+    /// Runs `f` on the table in a write transaction, and commits it durably.
+    fn write<T>(
+        &self,
+        f: impl FnOnce(&mut redb::Table<'_, &'static [u8], &'static [u8]>) -> T,
+    ) -> T {
+        let txn = self.expect(self.db.begin_write());
+        let result = {
+            let mut table = self.expect(txn.open_table(self.table()));
+            f(&mut table)
+        };
+        self.expect(txn.commit());
+        result
+    }
+
+    fn expect<T>(&self, result: Result<T, impl Into<redb::Error>>) -> T {
+        result.unwrap_or_else(|e| self.fail(e))
+    }
+
+    fn fail(&self, error: impl Into<redb::Error>) -> ! {
+        panic!(
+            "Failed to access local store `{}`: {}",
+            self.name,
+            error.into()
+        )
+    }
+}
+
+/// A [`futures::Sink`] of updates to a [`LocalStore`], see [`LocalStore::sink`].
+pub struct LocalStoreSink<K, V> {
+    store: LocalStore<K, V>,
+    buffered: Vec<(K, Option<V>)>,
+}
+
+impl<K: Serialize + DeserializeOwned, V: Serialize + DeserializeOwned> futures::Sink<(K, Option<V>)>
+    for LocalStoreSink<K, V>
+{
+    type Error = std::convert::Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, update: (K, Option<V>)) -> Result<(), Self::Error> {
+        self.get_mut().buffered.push(update);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if !this.buffered.is_empty() {
+            this.store.apply(std::mem::take(&mut this.buffered));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    bincode::serialize(value).unwrap()
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> T {
+    bincode::deserialize(bytes).unwrap_or_else(|e| {
+        panic!("Failed to decode a local store entry, was it written with different types? {e}")
+    })
+}
+
+/// Writes a checkpoint of the operators of `flow` (see [`Dfir::checkpoint`]) to the data
+/// directory, replacing any previous checkpoint.
+pub fn save_checkpoint(flow: &mut Dfir<impl TickClosure>) -> std::io::Result<()> {
+    let dir = data_dir();
+    std::fs::create_dir_all(&dir)?;
+    // Write to a temporary file first, so that a crash never leaves a partial checkpoint.
+    let partial = dir.join("checkpoint.partial");
+    flow.checkpoint(std::io::BufWriter::new(std::fs::File::create(&partial)?))?;
+    std::fs::rename(partial, dir.join("checkpoint"))
+}
+
+/// Restores the checkpoint written by [`save_checkpoint`] into `flow` (see [`Dfir::restore`]),
+/// returning `false` if there is none.
+pub fn restore_checkpoint(flow: &mut Dfir<impl TickClosure>) -> std::io::Result<bool> {
+    match std::fs::File::open(data_dir().join("checkpoint")) {
+        Ok(file) => {
+            flow.restore(std::io::BufReader::new(file))?;
+            Ok(true)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// The name of a store, spliced into `q!` code as the opened [`LocalStore`].
+struct LocalStoreName<K, V> {
+    name: &'static str,
+    _phantom: PhantomData<fn(K, V) -> (K, V)>,
+}
+
+impl<K, V> Clone for LocalStoreName<K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for LocalStoreName<K, V> {}
+
+impl<'a, K, V, L: Location<'a>> FreeVariableWithContextWithProps<L, ()> for LocalStoreName<K, V> {
+    type O = LocalStore<K, V>;
+
+    fn to_tokens(self, _ctx: &L) -> (QuoteTokens, ())
+    where
+        Self: Sized,
+    {
+        let root = get_this_crate();
+        let name = self.name;
+        let k_type: syn::Type = quote_type::<K>();
+        let v_type: syn::Type = quote_type::<V>();
+        (
+            QuoteTokens {
+                prelude: None,
+                expr: Some(quote! {
+                    #root::local_store::LocalStore::<#k_type, #v_type>::open(#name)
+                }),
+            },
+            (),
+        )
+    }
+}
+
+/// A persistent key-value store on each member of a cluster, created by
+/// [`Cluster::local_store`].
+pub struct LocalStoreHandle<'a, K, V, C> {
+    location: Cluster<'a, C>,
+    name: LocalStoreName<K, V>,
+}
+
+impl<'a, C: 'a> Cluster<'a, C> {
+    /// Opens the persistent key-value store `name` on each member of this cluster. Each member has
+    /// its own copy of the store, kept in its data directory across restarts.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// # use hydro_lang::prelude::*;
+    /// # let mut flow = FlowBuilder::new();
+    /// let workers = flow.cluster::<()>();
+    /// let store = workers.local_store::<String, u64>("counts");
+    /// let restored = store.load();
+    /// store.persist(updates); // a `Stream<(String, Option<u64>), _>`
+    /// ```
+    pub fn local_store<K, V>(&self, name: &'static str) -> LocalStoreHandle<'a, K, V, C>
+    where
+        K: Serialize + DeserializeOwned,
+        V: Serialize + DeserializeOwned,
+    {
+        LocalStoreHandle {
+            location: self.clone(),
+            name: LocalStoreName {
+                name,
+                _phantom: PhantomData,
+            },
+        }
+    }
+}
+
+impl<'a, K, V, C: 'a> LocalStoreHandle<'a, K, V, C>
+where
+    K: Serialize + DeserializeOwned + 'a,
+    V: Serialize + DeserializeOwned + 'a,
+{
+    /// The entries of the store when each member starts, ordered by their serialized keys.
+    pub fn load(&self) -> Stream<(K, V), Cluster<'a, C>, Bounded, TotalOrder, ExactlyOnce> {
+        let store = self.name;
+        self.location.source_iter(q!(store.iter()))
+    }
+
+    /// Applies each update in `updates` to the store, in order. An update of `(key, Some(value))`
+    /// stores `value` for `key`, and `(key, None)` removes `key` from the store.
+    ///
+    /// Updates are written in a single durable transaction per batch of the stream, so the store
+    /// always reflects a prefix of `updates` after a crash.
+    pub fn persist<B: Boundedness>(
+        &self,
+        updates: Stream<(K, Option<V>), Cluster<'a, C>, B, TotalOrder, ExactlyOnce>,
+    ) {
+        let store = self.name;
+        updates.dest_sink(q!(store.sink()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+
+    use super::*;
+
+    fn open_db(dir: &tempfile::TempDir) -> Arc<Database> {
+        Arc::new(Database::create(dir.path().join("local_store.redb")).unwrap())
+    }
+
+    #[test]
+    fn missing_store_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalStore::<String, u64>::open_in(open_db(&dir), "counts");
+        assert_eq!(None, store.get(&"a".to_owned()));
+        assert_eq!(0, store.iter().count());
+    }
+
+    #[test]
+    fn reads_back_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalStore::<String, u64>::open_in(open_db(&dir), "counts");
+        assert_eq!(None, store.insert(&"a".to_owned(), &1));
+        assert_eq!(Some(1), store.insert(&"a".to_owned(), &2));
+        store.insert(&"b".to_owned(), &3);
+        assert_eq!(Some(3), store.remove(&"b".to_owned()));
+        store.apply([("c".to_owned(), Some(4)), ("a".to_owned(), None)]);
+        assert_eq!(vec![("c".to_owned(), 4)], store.iter().collect::<Vec<_>>());
+
+        // Stores with different names do not share entries.
+        let other = LocalStore::<String, u64>::open_in(store.db.clone(), "other");
+        assert_eq!(None, other.get(&"c".to_owned()));
+    }
+
+    #[test]
+    fn sink_writes_are_kept_after_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalStore::<u32, String>::open_in(open_db(&dir), "entries");
+        let mut sink = store.sink();
+        futures::executor::block_on(async {
+            sink.feed((1, Some("one".to_owned()))).await.unwrap();
+            sink.feed((2, Some("two".to_owned()))).await.unwrap();
+            sink.feed((1, None)).await.unwrap();
+        });
+        // Nothing is written until the sink is flushed.
+        assert_eq!(0, store.iter().count());
+        futures::executor::block_on(sink.flush()).unwrap();
+        drop((sink, store));
+
+        let store = LocalStore::<u32, String>::open_in(open_db(&dir), "entries");
+        assert_eq!(
+            vec![(2, "two".to_owned())],
+            store.iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Credit-based flow control from external consumers, see
+//! [`Stream::send_bincode_external_credited`].
+//!
+//! Sending to an external process with [`Stream::send_bincode_external`] does not slow down the
+//! sender when the external consumer falls behind, so outputs queue up in memory. Sending with
+//! [`Stream::send_bincode_external_credited`] instead lets the consumer bound this through a
+//! [`CreditGate`]: the gate starts with an initial number of credits, every item pulled from a
+//! source paused by the gate uses up one credit, and the source stops being polled once the
+//! credits are exhausted, until the consumer grants more credits on that edge (usually one for
+//! each output it has processed). Since the source itself stops ingesting, items back up in the
+//! external system feeding the source (e.g. a socket or a queue) rather than in the process.
+//!
+//! [`Stream::send_bincode_external`]: crate::live_collections::stream::Stream::send_bincode_external
+//! [`Stream::send_bincode_external_credited`]: crate::live_collections::stream::Stream::send_bincode_external_credited
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker, ready};
+
+use futures::Stream;
+use quote::quote;
+use slotmap::Key;
+use stageleft::runtime_support::{FreeVariableWithContextWithProps, QuoteTokens};
+
+use super::{LocationKey, Process};
+use crate::compile::builder::ExternalPortId;
+use crate::staging_util::{Invariant, get_this_crate};
+
+/// Pauses sources on a [`Process`] until an external consumer grants credits, created by
+/// [`Process::credit_gate`](super::Process::credit_gate).
+///
+/// When used inside `q!` code, the gate turns into a [`Credits`] handle, whose
+/// [`pause`](Credits::pause) method wraps the stream of a
+/// [`Location::source_stream`](super::Location::source_stream). Credits are granted through the
+/// edge the gate is passed to, see
+/// [`Stream::send_bincode_external_credited`](crate::live_collections::stream::Stream::send_bincode_external_credited)
+/// for an example.
+pub struct CreditGate<'a, P> {
+    pub(crate) location_key: LocationKey,
+    /// Distinguishes the gates of a location. Allocated like an external port, but never bound.
+    pub(crate) gate_id: ExternalPortId,
+    pub(crate) initial_credits: usize,
+    pub(crate) _phantom: Invariant<'a, P>,
+}
+
+impl<P> Clone for CreditGate<'_, P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for CreditGate<'_, P> {}
+
+impl<'a, P> FreeVariableWithContextWithProps<Process<'a, P>, ()> for CreditGate<'a, P> {
+    type O = Credits;
+
+    fn to_tokens(self, _ctx: &Process<'a, P>) -> (QuoteTokens, ())
+    where
+        Self: Sized,
+    {
+        let root = get_this_crate();
+        let location = Key::data(&self.location_key).as_ffi();
+        let gate = self.gate_id.into_inner();
+        let initial_credits = self.initial_credits;
+        (
+            QuoteTokens {
+                prelude: None,
+                expr: Some(quote! {
+                    #root::location::credits::Credits::get(#location, #gate, #initial_credits)
+                }),
+            },
+            (),
+        )
+    }
+}
+
+struct CreditState {
+    available: usize,
+    /// Paused sources waiting for credits.
+    waiting: Vec<Waker>,
+}
+
+/// The credits of a [`CreditGate`] at runtime.
+///
+/// All handles to the same gate share their credits. The credits are dropped along with the last
+/// handle, so each instance of a simulation starts again from the initial credits.
+#[derive(Clone)]
+pub struct Credits(Rc<RefCell<CreditState>>);
+
+impl Credits {
+    /// The credits of the given gate of a location, created with `initial_credits` if there is no
+    /// live handle to it.
+    #[doc(hidden)]
+    pub fn get(location: u64, gate: usize, initial_credits: usize) -> Self {
+        thread_local! {
+            static GATES: RefCell<HashMap<(u64, usize), Weak<RefCell<CreditState>>>> =
+                RefCell::new(HashMap::new());
+        }
+
+        GATES.with_borrow_mut(|gates| {
+            if let Some(state) = gates.get(&(location, gate)).and_then(Weak::upgrade) {
+                return Credits(state);
+            }
+            let state = Rc::new(RefCell::new(CreditState {
+                available: initial_credits,
+                waiting: Vec::new(),
+            }));
+            gates.insert((location, gate), Rc::downgrade(&state));
+            Credits(state)
+        })
+    }
+
+    /// The number of items which paused sources can currently produce.
+    pub fn available(&self) -> usize {
+        self.0.borrow().available
+    }
+
+    /// Adds `credits`, resuming any paused sources.
+    pub fn grant(&self, credits: u32) {
+        let mut state = self.0.borrow_mut();
+        state.available = state.available.saturating_add(credits as usize);
+        if state.available > 0 {
+            state.waiting.drain(..).for_each(Waker::wake);
+        }
+    }
+
+    /// Wraps `stream` so that each item it produces uses up one credit, and it is not polled while
+    /// there are no credits left.
+    pub fn pause<S: Stream>(&self, stream: S) -> Paused<S> {
+        Paused {
+            credits: self.clone(),
+            stream: Box::pin(stream),
+        }
+    }
+}
+
+/// A stream paused by [`Credits::pause`].
+pub struct Paused<S> {
+    credits: Credits,
+    stream: Pin<Box<S>>,
+}
+
+impl<S: Stream> Stream for Paused<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        {
+            let mut state = this.credits.0.borrow_mut();
+            if state.available == 0 {
+                if !state.waiting.iter().any(|w| w.will_wake(cx.waker())) {
+                    state.waiting.push(cx.waker().clone());
+                }
+                return Poll::Pending;
+            }
+        }
+
+        let item = ready!(this.stream.as_mut().poll_next(cx));
+        if item.is_some() {
+            this.credits.0.borrow_mut().available -= 1;
+        }
+        Poll::Ready(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+
+    use futures::StreamExt;
+    use futures::task::noop_waker_ref;
+
+    use super::Credits;
+
+    #[test]
+    fn pauses_without_credits() {
+        let credits = Credits::get(0, 0, 2);
+        let mut paused = credits.pause(futures::stream::iter(0..5));
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        assert_eq!(Poll::Ready(Some(0)), paused.poll_next_unpin(&mut cx));
+        assert_eq!(Poll::Ready(Some(1)), paused.poll_next_unpin(&mut cx));
+        assert_eq!(Poll::Pending, paused.poll_next_unpin(&mut cx));
+
+        // Handles to the same gate share credits.
+        Credits::get(0, 0, 2).grant(1);
+        assert_eq!(Poll::Ready(Some(2)), paused.poll_next_unpin(&mut cx));
+        assert_eq!(Poll::Pending, paused.poll_next_unpin(&mut cx));
+        assert_eq!(0, credits.available());
+    }
+
+    #[cfg(feature = "deploy")]
+    #[tokio::test]
+    async fn external_edge_grants_credits() {
+        use std::time::Duration;
+
+        use futures::SinkExt;
+        use hydro_deploy::Deployment;
+        use stageleft::q;
+
+        use crate::compile::builder::FlowBuilder;
+        use crate::location::Location;
+
+        let mut deployment = Deployment::new();
+        let mut flow = FlowBuilder::new();
+        let process = flow.process::<()>();
+        let external = flow.external::<()>();
+        let gate = process.credit_gate(2);
+        let (outputs, credits) = process
+            .source_stream(q!(gate.pause(tokio_stream::iter(0..10))))
+            .map(q!(|n| n * 10))
+            .send_bincode_external_credited(&external, gate);
+
+        let nodes = flow
+            .with_process(&process, deployment.Localhost())
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+        let mut outputs = nodes.connect(outputs).await;
+        let mut credits = nodes.connect(credits).await;
+        deployment.start().await.unwrap();
+
+        assert_eq!(Some(0), outputs.next().await);
+        assert_eq!(Some(10), outputs.next().await);
+        // The source is paused until more credits are granted.
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), outputs.next())
+                .await
+                .is_err()
+        );
+
+        credits.send(3).await.unwrap();
+        for expected in [20, 30, 40] {
+            assert_eq!(Some(expected), outputs.next().await);
+        }
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), outputs.next())
+                .await
+                .is_err()
+        );
+
+        credits.send(100).await.unwrap();
+        for expected in [50, 60, 70, 80, 90] {
+            assert_eq!(Some(expected), outputs.next().await);
+        }
+    }
+}
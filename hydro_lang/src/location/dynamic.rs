@@ -133,6 +133,8 @@ impl LocationId {
                 cpu_usage: None,
                 network_recv_cpu_usage: None,
                 id: None,
+                stable_id: None,
+                claimed_algebra: Default::default(),
             },
         }
     }
@@ -0,0 +1,535 @@
+//! Exactly-once delivery to external processes, see
+//! [`Stream::send_bincode_external_exactly_once`].
+//!
+//! [`Stream::send_bincode_external`] sends each element once, so elements in flight are lost if
+//! the connection or the process fails, and a reconnecting consumer cannot tell which elements
+//! it has already seen. With exactly-once delivery, the sender numbers each element with a
+//! sequence number (starting at 1) and keeps it in a [`SendLog`] until the consumer acknowledges
+//! it. The consumer, an [`ExactlyOnceReceiver`], drops any element whose sequence number it has
+//! already seen, and acknowledges each element it delivers.
+//!
+//! Each time a receiver connects, it tells the sender the last sequence number it has seen, and
+//! the sender resends the elements after it which are still in the log. This covers elements sent
+//! while no receiver was connected, and elements lost with a previous connection.
+//!
+//! If the process runs with a data directory provided by Hydro Deploy (see
+//! `hydro_deploy_integration::data_dir`), the log is persisted there. After the process
+//! restarts, it resends the elements which were not acknowledged once a receiver connects, and
+//! continues numbering elements after the last sequence number it sent. The log only holds the
+//! unacknowledged elements, and is compacted as they are acknowledged. It is written through to
+//! the operating system as each element is sent, but not synced to disk, so it survives crashes
+//! of the process but not of the machine.
+//!
+//! [`Stream::send_bincode_external`]: crate::live_collections::stream::Stream::send_bincode_external
+//! [`Stream::send_bincode_external_exactly_once`]: crate::live_collections::stream::Stream::send_bincode_external_exactly_once
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use quote::quote;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use slotmap::Key;
+use stageleft::quote_type;
+use stageleft::runtime_support::{FreeVariableWithContextWithProps, QuoteTokens};
+
+use super::external_process::{ExternalBincodeSink, ExternalBincodeStream};
+use super::{Location, LocationKey};
+use crate::compile::builder::ExternalPortId;
+use crate::staging_util::get_this_crate;
+
+/// The ports of a stream sent with
+/// [`Stream::send_bincode_external_exactly_once`](crate::live_collections::stream::Stream::send_bincode_external_exactly_once),
+/// which connect to an [`ExactlyOnceReceiver`].
+pub struct ExternalExactlyOnceStream<T: Serialize + DeserializeOwned> {
+    pub(crate) elements: ExternalBincodeStream<SenderMessage<T>>,
+    pub(crate) acks: ExternalBincodeSink<ReceiverMessage>,
+}
+
+/// A message sent by an [`ExactlyOnceReceiver`] to the sender.
+#[doc(hidden)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReceiverMessage {
+    /// The receiver connected, having seen all elements up to and including this sequence number.
+    /// The sender resends the elements after it.
+    Connected(u64),
+    /// The receiver delivered all elements up to and including this sequence number.
+    Acked(u64),
+}
+
+/// A message sent to an [`ExactlyOnceReceiver`] by the sender.
+#[doc(hidden)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum SenderMessage<T> {
+    /// Sent in reply to [`ReceiverMessage::Connected`], before resending the elements which were
+    /// not acknowledged: all elements up to and including this sequence number were acknowledged.
+    Resync(u64),
+    /// An element with its sequence number.
+    Element(u64, T),
+}
+
+/// The send log of an exactly-once external stream, which turns into a [`SendLog`] inside `q!`
+/// code.
+#[cfg_attr(
+    not(feature = "tokio"),
+    expect(dead_code, reason = "only constructed with the tokio feature")
+)]
+pub(crate) struct SendLogHandle<T> {
+    pub(crate) location_key: LocationKey,
+    pub(crate) port_id: ExternalPortId,
+    pub(crate) _phantom: PhantomData<fn(T) -> T>,
+}
+
+impl<T> Clone for SendLogHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SendLogHandle<T> {}
+
+impl<'a, T, L: Location<'a>> FreeVariableWithContextWithProps<L, ()> for SendLogHandle<T> {
+    type O = SendLog<T>;
+
+    fn to_tokens(self, _ctx: &L) -> (QuoteTokens, ())
+    where
+        Self: Sized,
+    {
+        let root = get_this_crate();
+        let location = Key::data(&self.location_key).as_ffi();
+        let port = self.port_id.into_inner();
+        let t_type: syn::Type = quote_type::<T>();
+        (
+            QuoteTokens {
+                prelude: None,
+                expr: Some(quote! {
+                    #root::location::exactly_once::SendLog::<#t_type>::get(#location, #port)
+                }),
+            },
+            (),
+        )
+    }
+}
+
+/// The number of records in the log file beyond those needed for its current state, above which
+/// it is compacted.
+const COMPACT_THRESHOLD: usize = 1024;
+
+/// A record of the on-disk send log.
+#[derive(Serialize, Deserialize)]
+enum Record {
+    Sent(u64, Vec<u8>),
+    /// All elements up to and including this sequence number were acknowledged.
+    Acked(u64),
+}
+
+struct SendLogState {
+    /// The sequence number of the next element.
+    next_seq: u64,
+    /// The highest sequence number acknowledged so far, or 0.
+    acked: u64,
+    /// The serialized elements which were not acknowledged yet, in order.
+    unacked: VecDeque<(u64, Vec<u8>)>,
+    /// The file the log is persisted to, if there is a data directory.
+    file: Option<(PathBuf, File)>,
+    /// The number of records in the file.
+    records: usize,
+}
+
+impl SendLogState {
+    fn open(path: Option<PathBuf>) -> Self {
+        let mut state = SendLogState {
+            next_seq: 1,
+            acked: 0,
+            unacked: VecDeque::new(),
+            file: None,
+            records: 0,
+        };
+        let Some(path) = path else {
+            return state;
+        };
+
+        if let Ok(file) = File::open(&path) {
+            let mut reader = BufReader::new(file);
+            // A record which was only partially written when the process stopped is dropped by
+            // the compaction below.
+            while let Ok(record) = bincode::deserialize_from::<_, Record>(&mut reader) {
+                state.apply(record);
+            }
+        }
+        state.compact(path);
+        state
+    }
+
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Sent(seq, element) => {
+                self.next_seq = self.next_seq.max(seq + 1);
+                if seq > self.acked {
+                    self.unacked.push_back((seq, element));
+                }
+            }
+            Record::Acked(seq) => {
+                self.acked = self.acked.max(seq);
+                self.next_seq = self.next_seq.max(seq + 1);
+                while self.unacked.front().is_some_and(|(s, _)| *s <= self.acked) {
+                    self.unacked.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Applies `record`, and appends it to the log file.
+    fn record(&mut self, record: Record) {
+        if let Some((path, file)) = &mut self.file {
+            // Written with a single call, so that the record is not interleaved with others.
+            file.write_all(&bincode::serialize(&record).unwrap())
+                .unwrap_or_else(|e| {
+                    panic!("Failed to write the send log {}: {}", path.display(), e)
+                });
+            self.records += 1;
+        }
+        self.apply(record);
+
+        if self.records > 1 + self.unacked.len() + COMPACT_THRESHOLD
+            && let Some((path, _)) = self.file.take()
+        {
+            self.compact(path);
+        }
+    }
+
+    /// Rewrites the log file at `path` with only the records needed for the current state.
+    fn compact(&mut self, path: PathBuf) {
+        let result = (|| {
+            // Written to a temporary file first, so that a crash never leaves a partial log.
+            let partial = path.with_extension("partial");
+            let mut writer = BufWriter::new(File::create(&partial)?);
+            bincode::serialize_into(&mut writer, &Record::Acked(self.acked))
+                .map_err(std::io::Error::other)?;
+            for (seq, element) in &self.unacked {
+                bincode::serialize_into(&mut writer, &Record::Sent(*seq, element.clone()))
+                    .map_err(std::io::Error::other)?;
+            }
+            writer.flush()?;
+            drop(writer);
+            std::fs::rename(&partial, &path)?;
+            OpenOptions::new().append(true).open(&path)
+        })();
+        let file = result
+            .unwrap_or_else(|e| panic!("Failed to write the send log {}: {}", path.display(), e));
+        self.records = 1 + self.unacked.len();
+        self.file = Some((path, file));
+    }
+}
+
+/// The send log of an exactly-once external stream at runtime, see the [module docs](self).
+///
+/// All handles to the same log share its state.
+pub struct SendLog<T> {
+    state: Rc<RefCell<SendLogState>>,
+    _phantom: PhantomData<fn(T) -> T>,
+}
+
+impl<T> Clone for SendLog<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> SendLog<T> {
+    /// The send log for the given location and port, opened from the data directory if there is
+    /// no live handle to it.
+    #[doc(hidden)]
+    pub fn get(location: u64, port: usize) -> Self {
+        thread_local! {
+            static LOGS: RefCell<HashMap<(u64, usize), Weak<RefCell<SendLogState>>>> =
+                RefCell::new(HashMap::new());
+        }
+
+        let state = LOGS.with_borrow_mut(|logs| {
+            if let Some(state) = logs.get(&(location, port)).and_then(Weak::upgrade) {
+                return state;
+            }
+            let path = data_dir().map(|dir| {
+                std::fs::create_dir_all(&dir).unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to create the data directory {}: {}",
+                        dir.display(),
+                        e
+                    )
+                });
+                dir.join(format!("send_log_{location}_{port}"))
+            });
+            let state = Rc::new(RefCell::new(SendLogState::open(path)));
+            logs.insert((location, port), Rc::downgrade(&state));
+            state
+        });
+        SendLog {
+            state,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Records `element` as sent, returning the message which sends it with its sequence number.
+    pub fn append(&self, element: T) -> SenderMessage<T> {
+        let mut state = self.state.borrow_mut();
+        let seq = state.next_seq;
+        state.record(Record::Sent(seq, bincode::serialize(&element).unwrap()));
+        SenderMessage::Element(seq, element)
+    }
+
+    /// Records that all elements up to and including `seq` were acknowledged, removing them from
+    /// the log.
+    pub fn ack(&self, seq: u64) {
+        let mut state = self.state.borrow_mut();
+        if seq > state.acked {
+            state.record(Record::Acked(seq));
+        }
+    }
+
+    /// Applies a message from a receiver, returning `Some` if the receiver just connected.
+    pub fn receive(&self, message: ReceiverMessage) -> Option<()> {
+        match message {
+            ReceiverMessage::Connected(seq) => {
+                self.ack(seq);
+                Some(())
+            }
+            ReceiverMessage::Acked(seq) => {
+                self.ack(seq);
+                None
+            }
+        }
+    }
+
+    /// The messages sent to a newly connected receiver: the last acknowledged sequence number,
+    /// followed by the elements which were not acknowledged yet.
+    pub fn resync(&self) -> Vec<SenderMessage<T>> {
+        let state = self.state.borrow();
+        std::iter::once(SenderMessage::Resync(state.acked))
+            .chain(state.unacked.iter().map(|(seq, element)| {
+                SenderMessage::Element(*seq, bincode::deserialize(element).unwrap())
+            }))
+            .collect()
+    }
+}
+
+#[cfg(feature = "deploy_integration")]
+fn data_dir() -> Option<PathBuf> {
+    hydro_deploy_integration::data_dir()
+}
+
+#[cfg(not(feature = "deploy_integration"))]
+fn data_dir() -> Option<PathBuf> {
+    None
+}
+
+/// Receives the elements of a stream sent with
+/// [`Stream::send_bincode_external_exactly_once`](crate::live_collections::stream::Stream::send_bincode_external_exactly_once)
+/// exactly once, created by connecting to its [`ExternalExactlyOnceStream`].
+///
+/// Elements are returned in order of their sequence numbers, and each is acknowledged once it is
+/// returned by [`Self::recv`]. Elements which were already received (which the sender resends to
+/// each receiver when it connects) are dropped. To also drop the elements received by a previous
+/// receiver, such as one before the external process restarted, start the receiver with
+/// [`Self::resume`].
+pub struct ExactlyOnceReceiver<T, S, K> {
+    elements: S,
+    acks: K,
+    last_seq: u64,
+    /// Whether the sender was told that this receiver connected.
+    announced: bool,
+    /// Whether the sender replied with [`SenderMessage::Resync`]. Until then, elements are not
+    /// returned, since earlier elements may still be resent.
+    resynced: bool,
+    /// Elements received ahead of earlier ones, by sequence number.
+    pending: BTreeMap<u64, T>,
+}
+
+impl<T, S, K> ExactlyOnceReceiver<T, S, K>
+where
+    S: Stream<Item = SenderMessage<T>> + Unpin,
+    K: Sink<ReceiverMessage> + Unpin,
+{
+    /// Creates a receiver from the connected `elements` and `acks` ports. The sender is told
+    /// about the receiver, and resends its unacknowledged elements, on the first call to
+    /// [`Self::recv`] or [`Self::resume`].
+    pub fn new(elements: S, acks: K) -> Self {
+        Self {
+            elements,
+            acks,
+            last_seq: 0,
+            announced: false,
+            resynced: false,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Continues after the element with sequence number `last_seq`, which was received by a
+    /// previous receiver. The sender is told to drop the elements up to it from its log, and to
+    /// resend the elements after it.
+    pub async fn resume(&mut self, last_seq: u64) -> Result<(), K::Error> {
+        self.last_seq = self.last_seq.max(last_seq);
+        self.announced = true;
+        self.acks
+            .send(ReceiverMessage::Connected(self.last_seq))
+            .await
+    }
+
+    /// The sequence number of the last element received, or 0. Can be saved to
+    /// [`Self::resume`] a later receiver.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Receives the next element, or `None` if the connection was closed.
+    pub async fn recv(&mut self) -> Result<Option<T>, K::Error> {
+        if !self.announced {
+            self.resume(self.last_seq).await?;
+        }
+        loop {
+            if self.resynced
+                && let Some(element) = self.pending.remove(&(self.last_seq + 1))
+            {
+                self.last_seq += 1;
+                self.acks
+                    .send(ReceiverMessage::Acked(self.last_seq))
+                    .await?;
+                return Ok(Some(element));
+            }
+
+            match self.elements.next().await {
+                None => return Ok(None),
+                Some(SenderMessage::Resync(acked)) => {
+                    // Elements up to `acked` were received by a previous receiver.
+                    self.resynced = true;
+                    self.last_seq = self.last_seq.max(acked);
+                    self.pending = self.pending.split_off(&(self.last_seq + 1));
+                }
+                Some(SenderMessage::Element(seq, element)) => {
+                    if seq > self.last_seq {
+                        self.pending.insert(seq, element);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExactlyOnceReceiver, ReceiverMessage, Record, SendLogState, SenderMessage};
+
+    #[test]
+    fn resumes_from_persisted_log() {
+        let path = std::env::temp_dir().join(format!("send_log_test_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut state = SendLogState::open(Some(path.clone()));
+        for seq in 1..=3 {
+            assert_eq!(seq, state.next_seq);
+            state.record(Record::Sent(seq, vec![seq as u8]));
+        }
+        state.record(Record::Acked(2));
+        drop(state);
+
+        let state = SendLogState::open(Some(path.clone()));
+        assert_eq!(4, state.next_seq);
+        assert_eq!(2, state.acked);
+        assert_eq!(vec![(3, vec![3])], Vec::from(state.unacked.clone()));
+        drop(state);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn receiver_orders_elements_after_resync() {
+        // Fresh elements sent before the sender saw the receiver connect overtake the resent
+        // ones, and the element with sequence number 1 was received by a previous receiver.
+        let elements = futures::stream::iter([
+            SenderMessage::Element(4, 'd'),
+            SenderMessage::Element(2, 'x'),
+            SenderMessage::Resync(2),
+            SenderMessage::Element(3, 'c'),
+            SenderMessage::Element(4, 'd'),
+            SenderMessage::Element(5, 'e'),
+        ]);
+        let mut receiver = ExactlyOnceReceiver::new(elements, Vec::new());
+
+        let mut received = Vec::new();
+        while let Some(element) = receiver.recv().await.unwrap() {
+            received.push(element);
+        }
+        assert_eq!(vec!['c', 'd', 'e'], received);
+        assert_eq!(5, receiver.last_seq());
+        assert_eq!(
+            vec![
+                ReceiverMessage::Connected(0),
+                ReceiverMessage::Acked(3),
+                ReceiverMessage::Acked(4),
+                ReceiverMessage::Acked(5),
+            ],
+            receiver.acks
+        );
+    }
+
+    #[cfg(feature = "deploy")]
+    #[tokio::test]
+    async fn resends_unacked_after_sender_restart() {
+        use hydro_deploy::Deployment;
+        use hydro_deploy_integration::DATA_DIR_ENV;
+        use stageleft::q;
+
+        use crate::compile::builder::FlowBuilder;
+        use crate::deploy::TrybuildHost;
+        use crate::location::Location;
+
+        let data_dir =
+            std::env::temp_dir().join(format!("send_log_restart_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+
+        // Each run sends 1, 2, 3 after the elements left over from the previous run.
+        let mut last_seq = 0;
+        for expected in [vec![1], vec![2, 3, 1, 2, 3]] {
+            let mut deployment = Deployment::new();
+            let mut flow = FlowBuilder::new();
+            let process = flow.process::<()>();
+            let external = flow.external::<()>();
+            let port = process
+                .source_iter(q!(vec![1, 2, 3]))
+                .send_bincode_external_exactly_once(&external);
+
+            let nodes = flow
+                .with_process(
+                    &process,
+                    TrybuildHost::new(deployment.Localhost())
+                        .env(DATA_DIR_ENV, data_dir.to_str().unwrap()),
+                )
+                .with_external(&external, deployment.Localhost())
+                .deploy(&mut deployment);
+
+            deployment.deploy().await.unwrap();
+            let mut receiver = nodes.connect(port).await;
+            deployment.start().await.unwrap();
+
+            receiver.resume(last_seq).await.unwrap();
+            for element in expected {
+                assert_eq!(Some(element), receiver.recv().await.unwrap());
+            }
+            last_seq = receiver.last_seq();
+            // The sender is stopped with 2 and 3 of the first run not yet received.
+            deployment.stop().await.unwrap();
+        }
+        assert_eq!(6, last_seq);
+
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+}
@@ -6,19 +6,30 @@
 //!
 //! The main type in this module is [`External`], which represents a handle to an external
 //! process. Port types such as [`ExternalBytesPort`], [`ExternalBincodeSink`],
-//! [`ExternalBincodeBidi`], and [`ExternalBincodeStream`] represent the different kinds of
-//! communication channels that can be established between an external process and the
-//! dataflow.
+//! [`ExternalBincodeBidi`], [`ExternalBincodeStream`], and [`ExternalHttpEndpoint`] represent the
+//! different kinds of communication channels that can be established between an external process
+//! and the dataflow.
 
 use std::marker::PhantomData;
+use std::path::Path;
 
 use serde::Serialize;
 use serde::de::DeserializeOwned;
+use stageleft::{q, quote_type};
+use syn::parse_quote;
 
+use super::NetworkHint;
 use crate::compile::builder::{ExternalPortId, FlowState};
-use crate::live_collections::stream::{ExactlyOnce, Ordering, Retries, TotalOrder};
-use crate::location::LocationKey;
-use crate::staging_util::Invariant;
+use crate::compile::ir::{DebugInstantiate, HydroIrOpMetadata, HydroNode, HydroRoot};
+use crate::forward_handle::ForwardHandle;
+use crate::live_collections::boundedness::Unbounded;
+use crate::live_collections::keyed_stream::KeyedStream;
+use crate::live_collections::stream::{
+    ExactlyOnce, NoOrder, Ordering, Retries, Stream, TotalOrder,
+};
+use crate::location::{Location, LocationKey, TopLevel};
+use crate::manual_expr::ManualExpr;
+use crate::staging_util::{Invariant, get_this_crate};
 
 /// Marker type indicating that a port is connected to a single external client.
 pub enum NotMany {}
@@ -109,14 +120,64 @@ where
         expect(unused, reason = "unused without feature")
     )]
     pub(crate) process_key: LocationKey,
-    #[cfg_attr(
-        not(feature = "build"),
-        expect(unused, reason = "unused without feature")
-    )]
     pub(crate) port_id: ExternalPortId,
     pub(crate) _phantom: PhantomData<(Type, O, R)>,
 }
 
+/// An HTTP endpoint served by a location, created by [`External::http_endpoint`].
+///
+/// External clients send requests to the URL of the endpoint, which can be looked up after
+/// deployment with `DeployResult::http_url`.
+#[derive(Clone, Debug)]
+pub struct ExternalHttpEndpoint {
+    pub(crate) process_key: LocationKey,
+    pub(crate) port_id: ExternalPortId,
+    pub(crate) route: String,
+}
+
+/// A handle to a port of an [`External`] process, which can be given a stable name with
+/// [`External::name_port`].
+#[sealed::sealed]
+pub trait ExternalPort {
+    #[doc(hidden)]
+    fn port_id(&self) -> ExternalPortId;
+}
+
+#[sealed::sealed]
+impl<M> ExternalPort for ExternalBytesPort<M> {
+    fn port_id(&self) -> ExternalPortId {
+        self.port_id
+    }
+}
+
+#[sealed::sealed]
+impl<T: Serialize, M, O: Ordering, R: Retries> ExternalPort for ExternalBincodeSink<T, M, O, R> {
+    fn port_id(&self) -> ExternalPortId {
+        self.port_id
+    }
+}
+
+#[sealed::sealed]
+impl<InT, OutT, M> ExternalPort for ExternalBincodeBidi<InT, OutT, M> {
+    fn port_id(&self) -> ExternalPortId {
+        self.port_id
+    }
+}
+
+#[sealed::sealed]
+impl<T: DeserializeOwned, O: Ordering, R: Retries> ExternalPort for ExternalBincodeStream<T, O, R> {
+    fn port_id(&self) -> ExternalPortId {
+        self.port_id
+    }
+}
+
+#[sealed::sealed]
+impl ExternalPort for ExternalHttpEndpoint {
+    fn port_id(&self) -> ExternalPortId {
+        self.port_id
+    }
+}
+
 /// A handle representing an external process that can communicate with the Hydro dataflow.
 ///
 /// External processes live outside the compiled dataflow graph and interact with it by
@@ -145,3 +206,217 @@ impl<P> Clone for External<'_, P> {
         }
     }
 }
+
+impl<'a, Tag> External<'a, Tag> {
+    /// Names `port` in the [`FlowInterface`](crate::compile::interface::FlowInterface) of the
+    /// program as `<external>/<name>`. Unnamed ports are named after their IDs, which change when
+    /// ports are added or removed, so ports whose interface is published should be named.
+    ///
+    /// # Panics
+    /// If another port of this flow already has the given name.
+    pub fn name_port(&self, port: &impl ExternalPort, name: &str) {
+        let mut flow_state = self.flow_state.borrow_mut();
+        let port_id = port.port_id();
+        assert!(
+            !flow_state
+                .port_names
+                .iter()
+                .any(|(other, other_name)| *other != port_id && other_name == name),
+            "Another external port is already named `{}`",
+            name
+        );
+        flow_state.port_names.insert(port_id, name.to_owned());
+    }
+
+    /// Replays the values recorded in the fixture file at `path` into `to`, as if this external
+    /// process had sent them through [`source_external_bincode`](Location::source_external_bincode).
+    /// This gives integration tests a standard way to provide recorded inputs, without a client
+    /// having to connect and send them.
+    ///
+    /// Files ending in `.jsonl` are read as one JSON value per line, and all other files as
+    /// consecutive bincode-encoded values. The file is read by the compiled binary when it starts,
+    /// so fixtures are only supported in simulations and in deployments to the local machine;
+    /// deploying a location which replays a fixture to any other host fails with
+    /// [`DeployError::RemoteFixture`](crate::compile::deploy::DeployError::RemoteFixture).
+    ///
+    /// # Panics
+    /// If `path` does not exist. At runtime, if the fixture does not contain values of type `T`.
+    pub fn source_fixture<L, T>(
+        &self,
+        to: &L,
+        path: impl AsRef<Path>,
+    ) -> Stream<T, L::DropConsistency, Unbounded, TotalOrder, ExactlyOnce>
+    where
+        L: TopLevel<'a>,
+        T: DeserializeOwned,
+    {
+        let path = path.as_ref();
+        let path = std::fs::canonicalize(path)
+            .unwrap_or_else(|e| panic!("Failed to find fixture `{}`: {}", path.display(), e));
+        self.flow_state
+            .borrow_mut()
+            .fixtures
+            .push((to.id().key(), path.clone()));
+
+        let path = path
+            .to_str()
+            .expect("Fixture paths must be valid UTF-8")
+            .to_owned();
+        to.source_iter(ManualExpr::<Vec<T>, _>::new(move |_: &L| -> syn::Expr {
+            let root = get_this_crate();
+            let t_type: syn::Type = quote_type::<T>();
+            parse_quote!(#root::runtime_support::fixture::load::<#t_type>(#path))
+        }))
+        .weaken_boundedness()
+    }
+
+    /// Serves an HTTP endpoint at `route` (e.g. `"/orders"`) on `to`, so that non-Rust clients
+    /// can send it requests. The endpoint is an [`axum`](https://docs.rs/axum) server embedded in
+    /// the compiled binary, listening on a TCP port allocated by the deployment.
+    ///
+    /// Returns the endpoint handle, a keyed stream of the JSON bodies of `POST` requests to the
+    /// route parsed as `InT`, keyed by a unique request ID, and a handle to complete with the
+    /// responses, keyed by the ID of the request they answer. Responses are serialized as JSON.
+    /// Requests whose body cannot be parsed are answered with a `400 Bad Request` response
+    /// describing the error, and are not emitted.
+    ///
+    /// The `http` feature must be enabled for the compiled binary to serve the endpoint, and only
+    /// processes deployed with Hydro Deploy are supported.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let (endpoint, requests, responses) =
+    ///     external.http_endpoint::<_, Order, OrderStatus>(&server, "/orders");
+    /// responses.complete(requests.map(q!(|order| OrderStatus::accepted(order))));
+    ///
+    /// // after deploying
+    /// let url = nodes.http_url(&endpoint).await;
+    /// // curl -X POST -d '{"item": "apple"}' $url
+    /// ```
+    ///
+    /// # Panics
+    /// If `route` does not start with `/`.
+    #[expect(clippy::type_complexity, reason = "stream markers")]
+    pub fn http_endpoint<L, InT, OutT>(
+        &self,
+        to: &L,
+        route: &str,
+    ) -> (
+        ExternalHttpEndpoint,
+        KeyedStream<u64, InT, L::DropConsistency, Unbounded, TotalOrder, ExactlyOnce>,
+        ForwardHandle<
+            'a,
+            KeyedStream<u64, OutT, L::DropConsistency, Unbounded, NoOrder, ExactlyOnce>,
+        >,
+    )
+    where
+        L: TopLevel<'a>,
+        InT: DeserializeOwned,
+        OutT: Serialize,
+    {
+        assert!(
+            route.starts_with('/'),
+            "HTTP routes must start with `/`, got `{}`",
+            route
+        );
+
+        let next_external_port_id = self.flow_state.borrow_mut().next_external_port();
+        let target_consistency = to.drop_consistency();
+        let (fwd_ref, to_sink) = target_consistency.forward_ref::<KeyedStream<
+            u64,
+            OutT,
+            L::DropConsistency,
+            Unbounded,
+            NoOrder,
+            ExactlyOnce,
+        >>();
+
+        let root = get_this_crate();
+        let in_t_type = quote_type::<InT>();
+        let deser_fn: syn::Expr = parse_quote! {
+            |(id, body)| {
+                (
+                    id,
+                    #root::runtime_support::serde_json::from_slice::<#in_t_type>(&body)
+                        .map_err(|e| e.to_string()),
+                )
+            }
+        };
+
+        let parsed: Stream<
+            (u64, Result<InT, String>),
+            L::DropConsistency,
+            Unbounded,
+            TotalOrder,
+            ExactlyOnce,
+        > = Stream::new(
+            target_consistency.clone(),
+            HydroNode::ExternalInput {
+                from_external_key: self.key,
+                from_port_id: next_external_port_id,
+                from_many: true,
+                http_route: Some(route.to_owned()),
+                codec_type: quote_type::<()>().into(),
+                port_hint: NetworkHint::TcpPort(None),
+                instantiate_fn: DebugInstantiate::Building,
+                deserialize_fn: Some(deser_fn.into()),
+                metadata: target_consistency.new_node_metadata(Stream::<
+                    (u64, Result<InT, String>),
+                    L::DropConsistency,
+                    Unbounded,
+                    TotalOrder,
+                    ExactlyOnce,
+                >::collection_kind(
+                )),
+            },
+        );
+        let (requests, rejected) = parsed.partition(q!(|(_, body)| body.is_ok()));
+
+        let responses = to_sink
+            .entries()
+            .map(q!(|(id, response)| (id, Ok(response))))
+            .merge_unordered(rejected.map(q!(|(id, body)| (id, Err(body.err().unwrap())))));
+
+        let out_t_type = quote_type::<OutT>();
+        let ser_fn: syn::Expr = parse_quote! {
+            #root::runtime_support::stageleft::runtime_support::fn1_type_hint::<(u64, Result<#out_t_type, String>), _>(
+                |(id, response)| {
+                    let response = match response {
+                        Ok(response) => #root::runtime_support::hydro_deploy_integration::http::HttpResponse::ok(
+                            #root::runtime_support::serde_json::to_vec(&response).unwrap(),
+                        ),
+                        Err(error) => #root::runtime_support::hydro_deploy_integration::http::HttpResponse::bad_request(
+                            #root::runtime_support::serde_json::to_vec(&error).unwrap(),
+                        ),
+                    };
+                    (id, response)
+                }
+            )
+        };
+
+        self.flow_state
+            .borrow_mut()
+            .push_root(HydroRoot::SendExternal {
+                to_external_key: self.key,
+                to_port_id: next_external_port_id,
+                to_many: true,
+                unpaired: false,
+                serialize_fn: Some(ser_fn.into()),
+                instantiate_fn: DebugInstantiate::Building,
+                input: Box::new(responses.ir_node.replace(HydroNode::Placeholder)),
+                op_metadata: HydroIrOpMetadata::new(),
+            });
+
+        (
+            ExternalHttpEndpoint {
+                process_key: self.key,
+                port_id: next_external_port_id,
+                route: route.to_owned(),
+            },
+            requests
+                .map(q!(|(id, body)| (id, body.ok().unwrap())))
+                .into_keyed(),
+            fwd_ref,
+        )
+    }
+}
@@ -83,6 +83,15 @@ pub use member_id::{MemberId, TaglessMemberId};
 pub mod tick;
 pub use tick::{Atomic, Tick};
 
+pub mod random;
+pub use random::SeedPolicy;
+
+pub mod credits;
+pub use credits::CreditGate;
+
+pub mod exactly_once;
+pub use exactly_once::{ExactlyOnceReceiver, ExternalExactlyOnceStream};
+
 /// An event indicating a change in membership status of a location in a group
 /// (e.g. a node in a [`Cluster`] or an external client connection).
 #[derive(PartialEq, Eq, Clone, Debug, Hash, Serialize, Deserialize)]
@@ -113,6 +122,28 @@ pub(crate) fn check_matching_location<'a, L: Location<'a>>(l1: &L, l2: &L) {
     assert_eq!(Location::id(l1), Location::id(l2), "locations do not match");
 }
 
+/// The stream behind [`Location::source_interval`], which follows the virtual clock when running
+/// in the simulator.
+#[cfg(feature = "tokio")]
+#[doc(hidden)]
+pub fn interval_stream(
+    delay: Duration,
+    period: Duration,
+) -> std::pin::Pin<Box<dyn FuturesStream<Item = ()>>> {
+    #[cfg(feature = "sim")]
+    if let Some(network) = crate::sim::runtime::current_network() {
+        return Box::pin(network.interval(delay, period));
+    }
+
+    Box::pin(tokio_stream::StreamExt::map(
+        tokio_stream::wrappers::IntervalStream::new(tokio::time::interval_at(
+            tokio::time::Instant::now() + delay,
+            period,
+        )),
+        |_| (),
+    ))
+}
+
 #[stageleft::export(LocationKey)]
 new_key_type! {
     /// A unique identifier for a clock tick.
@@ -778,6 +809,7 @@ pub trait Location<'a>: DynLocation {
                 from_external_key: from.key,
                 from_port_id: next_external_port_id,
                 from_many: false,
+                http_route: None,
                 codec_type: quote_type::<Codec>().into(),
                 port_hint,
                 instantiate_fn: DebugInstantiate::Building,
@@ -875,6 +907,7 @@ pub trait Location<'a>: DynLocation {
                     from_external_key: from.key,
                     from_port_id: next_external_port_id,
                     from_many: false,
+                    http_route: None,
                     codec_type: quote_type::<LengthDelimitedCodec>().into(),
                     port_hint: NetworkHint::Auto,
                     instantiate_fn: DebugInstantiate::Building,
@@ -982,6 +1015,7 @@ pub trait Location<'a>: DynLocation {
                 from_external_key: from.key,
                 from_port_id: next_external_port_id,
                 from_many: true,
+                http_route: None,
                 codec_type: quote_type::<Codec>().into(),
                 port_hint,
                 instantiate_fn: DebugInstantiate::Building,
@@ -1144,6 +1178,7 @@ pub trait Location<'a>: DynLocation {
                 from_external_key: from.key,
                 from_port_id: next_external_port_id,
                 from_many: true,
+                http_route: None,
                 codec_type: quote_type::<LengthDelimitedCodec>().into(),
                 port_hint: NetworkHint::Auto,
                 instantiate_fn: DebugInstantiate::Building,
@@ -1405,7 +1440,8 @@ pub trait Location<'a>: DynLocation {
     /// Generates a stream that emits `()` at a fixed interval.
     ///
     /// The first tick completes immediately. Missed ticks will be scheduled
-    /// as soon as possible.
+    /// as soon as possible. In the simulator, ticks follow the virtual clock
+    /// (see [`crate::sim::network_model`]).
     ///
     /// Because this only emits `()`, the non-determinism of *when* events fire
     /// is captured by the `AtLeastOnce` retry semantics downstream, so no
@@ -1418,9 +1454,9 @@ pub trait Location<'a>: DynLocation {
     where
         Self: TopLevel<'a> + Sized,
     {
-        self.source_stream(q!(tokio_stream::StreamExt::map(
-            tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(interval)),
-            |_| ()
+        self.source_stream(q!(crate::location::interval_stream(
+            std::time::Duration::ZERO,
+            interval
         )))
         .assert_has_consistency_of_trusted(
             manual_proof!(/** interval does not reveal timestamps */),
@@ -1442,16 +1478,10 @@ pub trait Location<'a>: DynLocation {
     where
         Self: TopLevel<'a> + Sized,
     {
-        self.source_stream(q!(tokio_stream::StreamExt::map(
-            tokio_stream::wrappers::IntervalStream::new(tokio::time::interval_at(
-                tokio::time::Instant::now() + delay,
-                interval,
-            )),
-            |_| ()
-        )))
-        .assert_has_consistency_of_trusted(
-            manual_proof!(/** interval does not reveal timestamps */),
-        )
+        self.source_stream(q!(crate::location::interval_stream(delay, interval)))
+            .assert_has_consistency_of_trusted(
+                manual_proof!(/** interval does not reveal timestamps */),
+            )
     }
 
     /// Creates a forward reference, allowing a stream to be used before its source is defined.
@@ -1509,6 +1539,7 @@ pub trait Location<'a>: DynLocation {
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
+    use std::net::Ipv4Addr;
 
     use futures::{SinkExt, StreamExt};
     use hydro_deploy::Deployment;
@@ -1516,6 +1547,7 @@ mod tests {
     use tokio_util::codec::LengthDelimitedCodec;
 
     use crate::compile::builder::FlowBuilder;
+    use crate::deploy::TrybuildHost;
     use crate::live_collections::stream::{ExactlyOnce, TotalOrder};
     use crate::location::{Location, NetworkHint};
     use crate::nondet::nondet;
@@ -1782,6 +1814,54 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn port_conflict_retries_with_fallback() {
+        // Hold the port requested by the process, and find a free port to fall back to.
+        let busy = std::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let busy_port = busy.local_addr().unwrap().port();
+        let fallback_port = std::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let mut deployment = Deployment::new();
+        let mut flow = FlowBuilder::new();
+        let first_node = flow.process::<()>();
+        let external = flow.external::<()>();
+        let (port, input, complete_sink) = first_node
+            .bind_single_client::<_, _, LengthDelimitedCodec>(
+                &external,
+                NetworkHint::TcpPort(Some(busy_port)),
+            );
+        complete_sink.complete(input.map(q!(|data| {
+            let mut resp: Vec<u8> = data.into();
+            resp.push(42);
+            resp.into() // : Bytes
+        })));
+
+        let nodes = flow
+            .with_process(
+                &first_node,
+                TrybuildHost::new(deployment.Localhost())
+                    .port_fallback(fallback_port..=fallback_port),
+            )
+            .with_external(&external, deployment.Localhost())
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+        deployment.start().await.unwrap();
+
+        // The external client is given the fallback port that was actually bound.
+        let (mut external_out, mut external_in) = nodes.connect(port).await;
+        external_in.send(vec![1, 2, 3].into()).await.unwrap();
+        assert_eq!(
+            external_out.next().await.unwrap().unwrap(),
+            vec![1, 2, 3, 42]
+        );
+        drop(busy);
+    }
+
     #[tokio::test]
     async fn echo_external_bytes() {
         let mut deployment = Deployment::new();
@@ -15,7 +15,7 @@ use std::marker::PhantomData;
 
 use super::{Location, LocationId};
 use crate::compile::builder::FlowState;
-use crate::location::{LocationKey, TopLevel};
+use crate::location::{CreditGate, LocationKey, TopLevel};
 use crate::staging_util::Invariant;
 
 /// A single-node location in a distributed Hydro program.
@@ -112,3 +112,20 @@ impl<'a, P> Location<'a> for Process<'a, P> {
 }
 
 impl<'a, P> TopLevel<'a> for Process<'a, P> {}
+
+impl<'a, P> Process<'a, P> {
+    /// Creates a [`CreditGate`] with `initial_credits`, which pauses sources on this process until
+    /// an external process grants them more credits, see [`crate::location::credits`].
+    ///
+    /// The credits are granted through the external edge the gate is passed to with
+    /// [`Stream::send_bincode_external_credited`](crate::live_collections::stream::Stream::send_bincode_external_credited).
+    /// Each gate is independent, so different external edges can be flow-controlled separately.
+    pub fn credit_gate(&self, initial_credits: usize) -> CreditGate<'a, P> {
+        CreditGate {
+            location_key: self.key,
+            gate_id: self.flow_state.borrow_mut().next_external_port(),
+            initial_credits,
+            _phantom: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Deterministic random values, see [`Tick::source_random`](super::Tick::source_random).
+//!
+//! Each location derives its own seed from the [`SeedPolicy`], the location, and (on a
+//! [`Cluster`](super::Cluster)) the ID of the cluster member, and the value produced at each tick
+//! is a hash of that seed and the number of times the tick has run. So with a fixed seed, a location produces the
+//! same sequence of values on every run, whether it is simulated or deployed.
+
+use std::sync::OnceLock;
+
+use proc_macro2::Span;
+use quote::quote;
+use slotmap::Key;
+use stageleft::runtime_support::{FreeVariableWithContextWithProps, QuoteTokens};
+
+use super::dynamic::LocationId;
+use super::{Location, TaglessMemberId};
+use crate::staging_util::get_this_crate;
+
+/// Environment variable which overrides the seed of [`SeedPolicy::Env`] at runtime.
+pub const RANDOM_SEED_ENV: &str = "HYDRO_RANDOM_SEED";
+
+/// How the seed of a [`Tick::source_random`](super::Tick::source_random) singleton is
+/// chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedPolicy {
+    /// A fixed seed, so that the values are the same on every run, in simulation and in production.
+    Fixed(u64),
+    /// The seed in the [`RANDOM_SEED_ENV`] environment variable, or `default` if it is not set.
+    /// Lets deployments use a different seed on each run, while simulations stay reproducible.
+    Env {
+        /// The seed to use if [`RANDOM_SEED_ENV`] is not set.
+        default: u64,
+    },
+}
+
+/// The seed of a location, spliced into `q!` code running on that location.
+#[derive(Clone, Copy)]
+pub(crate) struct LocationSeed(pub(crate) SeedPolicy);
+
+impl<'a, L: Location<'a>> FreeVariableWithContextWithProps<L, ()> for LocationSeed {
+    type O = SeededRandom;
+
+    fn to_tokens(self, ctx: &L) -> (QuoteTokens, ())
+    where
+        Self: Sized,
+    {
+        let root = get_this_crate();
+        let seed = match self.0 {
+            SeedPolicy::Fixed(seed) => quote! { #seed },
+            SeedPolicy::Env { default } => {
+                quote! { #root::location::random::env_seed(#default) }
+            }
+        };
+
+        let root_id = ctx.root().id();
+        let location = Key::data(&root_id.key()).as_ffi();
+        let member = if let LocationId::Cluster(cluster_id) = root_id {
+            let ident = syn::Ident::new(
+                &format!("__hydro_lang_cluster_self_id_{}", cluster_id),
+                Span::call_site(),
+            );
+            quote! { ::std::option::Option::Some(&#ident) }
+        } else {
+            quote! { ::std::option::Option::None }
+        };
+
+        (
+            QuoteTokens {
+                prelude: None,
+                expr: Some(quote! {
+                    #root::location::random::SeededRandom::new(#seed, #location, #member)
+                }),
+            },
+            (),
+        )
+    }
+}
+
+/// The seed in [`RANDOM_SEED_ENV`], or `default` if it is not set.
+#[doc(hidden)]
+pub fn env_seed(default: u64) -> u64 {
+    static SEED: OnceLock<Option<u64>> = OnceLock::new();
+    SEED.get_or_init(|| {
+        let seed = std::env::var(RANDOM_SEED_ENV).ok()?;
+        Some(seed.trim().parse().unwrap_or_else(|_| {
+            panic!(
+                "Invalid `{}` value `{}`, expected an integer.",
+                RANDOM_SEED_ENV, seed
+            )
+        }))
+    })
+    .unwrap_or(default)
+}
+
+/// Produces the random values of a single location (and cluster member).
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub struct SeededRandom {
+    seed: u64,
+}
+
+impl SeededRandom {
+    /// Derives the seed of `location` (and `member`, on a cluster) from the base `seed`.
+    pub fn new(seed: u64, location: u64, member: Option<&TaglessMemberId>) -> Self {
+        let mut seed = splitmix64(seed ^ splitmix64(location));
+        if let Some(member) = member {
+            // The `Debug` representation is stable across runs, unlike the default hasher.
+            for byte in format!("{:?}", member).bytes() {
+                seed = splitmix64(seed ^ u64::from(byte));
+            }
+        }
+        Self { seed }
+    }
+
+    /// The random value of the tick with the given index.
+    pub fn value(&self, tick: usize) -> u64 {
+        splitmix64(self.seed ^ splitmix64(tick as u64))
+    }
+}
+
+/// The SplitMix64 mixing function, a fast bijective hash with good statistical properties.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeededRandom;
+
+    #[test]
+    fn values_depend_on_seed_and_location() {
+        let values = |seed, location| {
+            let random = SeededRandom::new(seed, location, None);
+            (0..4).map(|tick| random.value(tick)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(values(1, 1), values(1, 1));
+        assert_ne!(values(1, 1), values(2, 1));
+        assert_ne!(values(1, 1), values(1, 2));
+    }
+}
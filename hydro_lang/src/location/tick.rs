@@ -14,21 +14,25 @@
 //! The [`Atomic`] wrapper provides atomicity guarantees within a tick, ensuring
 //! that reads and writes within a tick are serialized.
 
+use std::hash::Hash;
+
 use stageleft::{QuotedWithContext, q};
 
 #[cfg(stageleft_runtime)]
 use super::dynamic::DynLocation;
+use super::random::{LocationSeed, SeedPolicy};
 use super::{Location, LocationId};
 use crate::compile::builder::{ClockId, FlowState};
 use crate::compile::ir::{HydroNode, HydroSource};
 #[cfg(stageleft_runtime)]
 use crate::forward_handle::{CycleCollection, CycleCollectionWithInitial};
 use crate::forward_handle::{TickCycle, TickCycleHandle};
-#[cfg(feature = "tokio")]
 use crate::live_collections::Singleton;
 use crate::live_collections::boundedness::Bounded;
 use crate::live_collections::optional::Optional;
-use crate::live_collections::stream::{ExactlyOnce, Stream, TotalOrder};
+use crate::live_collections::stream::{
+    AtLeastOnce, ExactlyOnce, NoOrder, Ordering, Retries, Stream, TotalOrder,
+};
 use crate::location::TopLevel;
 #[cfg(feature = "tokio")]
 use crate::nondet::NonDet;
@@ -299,6 +303,53 @@ where
         self.singleton(q!(tokio::time::Instant::now()))
     }
 
+    /// Creates a [`Singleton`] with a new random value on each tick, which is reproducible: the
+    /// value is derived from the `seed_policy`, this location (and on a [`Cluster`](super::Cluster),
+    /// the ID of the member), and how many times this tick has run. So with a fixed seed, each
+    /// location produces the same sequence of values on every run with the same batching, both in
+    /// simulation and in production.
+    ///
+    /// Like any other source inside a tick, this does not cause the tick to run, so a location
+    /// with no other input is still quiescent. This is useful for randomized protocols, such as
+    /// picking a random peer to gossip with, which can then be tested deterministically in the
+    /// simulator.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use hydro_lang::location::SeedPolicy;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// process
+    ///     .source_iter(q!(vec![(); 3]))
+    ///     .batch(&tick, nondet!(/** test */))
+    ///     .cross_singleton(tick.source_random(SeedPolicy::Fixed(42)))
+    ///     .map(q!(|((), value)| value % 6 + 1))
+    ///     .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // dice rolls, the same on every run
+    /// # for _ in 0..3 {
+    /// #     assert!((1..=6).contains(&stream.next().await.unwrap()));
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn source_random(
+        &self,
+        seed_policy: SeedPolicy,
+    ) -> Singleton<u64, Tick<L::DropConsistency>, Bounded> {
+        let (complete_index, index) = self
+            .cycle_with_initial::<Singleton<usize, Tick<L::DropConsistency>, Bounded>, _>(
+                self.singleton(q!(0usize)),
+            );
+        complete_index.complete_next_tick(index.clone().map(q!(|index| index + 1)));
+
+        let random = LocationSeed(seed_policy);
+        index.map(q!(move |index| random.value(index)))
+    }
+
     /// Creates a feedback cycle within this tick for implementing iterative computations.
     ///
     /// Returns a handle that must be completed with the actual collection, and a placeholder
@@ -348,6 +399,91 @@ where
             S::create_source_with_initial(cycle_id, initial, self.clone().with_consistency_of()),
         )
     }
+
+    /// Iterates `step` to a fixpoint, starting from the elements of `initial`, running one round
+    /// of the iteration per tick.
+    ///
+    /// On each round, `step` is given the *frontier*: the elements that were newly derived in
+    /// the previous round. Any elements it returns which have not been seen before become the
+    /// frontier of the next round. This is semi-naive evaluation, so `step` only needs to
+    /// handle the new facts rather than recomputing everything derived so far.
+    ///
+    /// Returns a pair of:
+    /// - the elements newly derived in each tick (including those of `initial`), so that each
+    ///   element is emitted exactly once, on the tick it is first reached
+    /// - a singleton which is `true` on any tick in which the iteration has converged: some input
+    ///   has been received, and the frontier has been drained without deriving new elements
+    ///
+    /// Hydro does not allow cycles within a single tick, so unlike a nested loop, each round of
+    /// the iteration runs in its own tick, with the frontier carried across ticks. The frontier
+    /// is deferred lazily, so the tick must be driven by some other input (for example a
+    /// [`Location::spin`] batch) until convergence. Elements of `initial` may arrive on any tick
+    /// and are folded into the ongoing iteration.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "deploy")] {
+    /// # use hydro_lang::prelude::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(hydro_lang::test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let roots = process
+    ///     .source_iter(q!(vec![1]))
+    ///     .batch(&tick, nondet!(/** test */));
+    /// let (reached, _converged) = tick.iterate_across_ticks(roots, |frontier| {
+    ///     // follow the edges n -> n + 1, up to 4
+    ///     frontier.filter_map(q!(|n| if n < 4 { Some(n + 1) } else { None }))
+    /// });
+    /// // keep ticking until the iteration converges
+    /// let spin = process.spin().batch(&tick, nondet!(/** test */)).count();
+    /// reached
+    ///     .cross_singleton(spin)
+    ///     .map(q!(|(n, _)| n))
+    ///     .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // 1, 2, 3, 4, one per round
+    /// # for n in 1..=4 {
+    /// #     assert_eq!(stream.next().await.unwrap(), n);
+    /// # }
+    /// # }));
+    /// # }
+    /// ```
+    pub fn iterate_across_ticks<T, O: Ordering, R: Retries>(
+        &self,
+        initial: Stream<T, Tick<L::DropConsistency>, Bounded, O, R>,
+        step: impl FnOnce(
+            Stream<T, Tick<L::DropConsistency>, Bounded, NoOrder>,
+        ) -> Stream<T, Tick<L::DropConsistency>, Bounded, NoOrder>,
+    ) -> (
+        Stream<T, Tick<L::DropConsistency>, Bounded, NoOrder>,
+        Singleton<bool, Tick<L::DropConsistency>, Bounded>,
+    )
+    where
+        T: Clone + Eq + Hash,
+    {
+        let (complete_reached, reached) =
+            self.cycle::<Stream<T, Tick<L::DropConsistency>, Bounded, NoOrder>, _>();
+        let (complete_frontier, frontier) =
+            self.cycle::<Stream<T, Tick<L::DropConsistency>, Bounded, NoOrder>, _>();
+
+        let new_elements = step(frontier)
+            .chain(
+                initial
+                    .weaken_ordering::<NoOrder>()
+                    .weaken_retries::<AtLeastOnce>(),
+            )
+            .unique()
+            .filter_not_in(reached.clone());
+        let reached = reached.chain(new_elements.clone());
+
+        let seen_input = reached.clone().is_empty().map(q!(|empty| !empty));
+        let converged = seen_input.and(new_elements.clone().is_empty());
+
+        complete_reached.complete_next_tick(reached);
+        complete_frontier.complete_next_tick(new_elements.clone());
+
+        (new_elements, converged)
+    }
 }
 
 #[cfg(test)]
@@ -409,6 +545,85 @@ mod tests {
         assert_eq!(instances_read_before_write, 3); // read before write, write before read, both in same tick
     }
 
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_iterate_across_ticks_converges() {
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let tick = node.tick();
+
+        let (roots_send, roots) = node.sim_input::<u32, _, _>();
+        let (trigger_send, trigger) = node.sim_input::<(), _, _>();
+        let (reached, converged) = tick
+            .iterate_across_ticks(roots.batch(&tick, nondet!(/** test */)), |frontier| {
+                frontier.filter_map(q!(|n| if n < 4 { Some(n + 1) } else { None }))
+            });
+        // Each trigger runs a tick, as the frontier does not schedule ticks itself.
+        let converged_recv = converged
+            .zip(trigger.batch(&tick, nondet!(/** test */)).count())
+            .map(q!(|(converged, _)| converged))
+            .all_ticks()
+            .sim_output();
+        let reached_recv = reached.all_ticks().sim_output();
+
+        flow.sim().fuzz(async || {
+            // Not converged before any input has been received.
+            trigger_send.send(());
+            assert_eq!(Some(false), converged_recv.next().await);
+
+            roots_send.send(1);
+            let mut rounds = 0;
+            loop {
+                trigger_send.send(());
+                if converged_recv.next().await.unwrap() {
+                    break;
+                }
+                rounds += 1;
+                assert!(rounds <= 8, "iteration did not converge");
+            }
+
+            // Each element is emitted once, even though the tick ran many times.
+            assert_eq!(
+                vec![1, 2, 3, 4],
+                reached_recv.collect_sorted::<Vec<_>>().await
+            );
+        });
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_source_random_is_tick_driven() {
+        use slotmap::Key;
+
+        use crate::location::SeedPolicy;
+        use crate::location::random::SeededRandom;
+
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let tick = node.tick();
+
+        let (in_send, input) = node.sim_input::<u32, _, _>();
+        let out_recv = input
+            .batch(&tick, nondet!(/** test */))
+            .cross_singleton(tick.source_random(SeedPolicy::Fixed(7)))
+            .all_ticks()
+            .sim_output();
+
+        let random = SeededRandom::new(7, Key::data(&node.id().key()).as_ffi(), None);
+        let instances = flow.sim().exhaustive(async || {
+            in_send.send(1);
+            in_send.send(2);
+            // Only completes if the location quiesces once the input has been processed.
+            let out = out_recv.collect::<Vec<_>>().await;
+            assert_eq!(vec![1, 2], out.iter().map(|&(n, _)| n).collect::<Vec<_>>());
+            // Each batch gets the value of the tick it ran in.
+            for (_, value) in &out {
+                assert!((0..16).any(|tick| random.value(tick) == *value));
+            }
+        });
+        assert!(instances > 1);
+    }
+
     #[cfg(feature = "sim")]
     #[test]
     #[should_panic]
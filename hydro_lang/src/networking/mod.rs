@@ -2,20 +2,33 @@
 
 use std::marker::PhantomData;
 
-use serde::Serialize;
 use serde::de::DeserializeOwned;
-
-use crate::live_collections::stream::networking::{deserialize_bincode, serialize_bincode};
-use crate::live_collections::stream::{NoOrder, TotalOrder};
+use serde::{Deserialize, Serialize};
+
+use crate::compile::builder::CycleId;
+use crate::forward_handle::ForwardHandle;
+use crate::live_collections::boundedness::Unbounded;
+use crate::live_collections::stream::networking::{
+    deserialize_bincode, deserialize_versioned, serialize_bincode, serialize_versioned,
+    try_deserialize_bincode, try_deserialize_versioned,
+};
+use crate::live_collections::stream::{AtLeastOnce, NoOrder, Stream, TotalOrder};
+use crate::location::Location;
 use crate::location::cluster::{Consistency, EventualConsistency, NoConsistency};
+use crate::location::dynamic::LocationId;
 use crate::nondet::NonDet;
 
+pub mod versioned;
+use versioned::HydroMessage;
+
 #[sealed::sealed]
 trait SerKind<T: ?Sized> {
     fn serialize_thunk(is_demux: bool) -> syn::Expr;
 
     fn deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr;
 
+    fn try_deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr;
+
     /// Whether this serialization backend leaves serialization to code outside of Hydro (see
     /// [`Embedded`]). When `true`, [`Self::serialize_thunk`] and [`Self::deserialize_thunk`] are
     /// never called; the raw element type flows across the channel unserialized.
@@ -36,6 +49,30 @@ impl<T: Serialize + DeserializeOwned> SerKind<T> for Bincode {
     fn deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr {
         deserialize_bincode::<T>(tagged)
     }
+
+    fn try_deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr {
+        try_deserialize_bincode::<T>(tagged)
+    }
+}
+
+/// Serialize items using the [`bincode`] crate, wrapped in a versioned envelope so that old and
+/// new versions of a message type can be exchanged during a rolling upgrade. Requires the items to
+/// implement [`HydroMessage`], see [`versioned`] for details.
+pub enum VersionedBincode {}
+
+#[sealed::sealed]
+impl<T: HydroMessage> SerKind<T> for VersionedBincode {
+    fn serialize_thunk(is_demux: bool) -> syn::Expr {
+        serialize_versioned::<T>(is_demux)
+    }
+
+    fn deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr {
+        deserialize_versioned::<T>(tagged)
+    }
+
+    fn try_deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr {
+        try_deserialize_versioned::<T>(tagged)
+    }
 }
 
 /// Leaves serialization of items to code outside of Hydro.
@@ -56,6 +93,10 @@ impl<T> SerKind<T> for Embedded {
         unreachable!("embedded serialization does not use a deserialize thunk")
     }
 
+    fn try_deserialize_thunk(_tagged: Option<&syn::Type>) -> syn::Expr {
+        unreachable!("embedded serialization does not use a deserialize thunk")
+    }
+
     fn is_embedded() -> bool {
         true
     }
@@ -256,6 +297,10 @@ pub trait NetworkFor<T: ?Sized> {
     /// Generates deserialization logic for receiving `T`.
     fn deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr;
 
+    /// Generates deserialization logic for receiving `T` which produces a
+    /// `Result<T, DecodeError>` instead of panicking on malformed messages.
+    fn try_deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr;
+
     /// Whether this network channel leaves serialization to code outside of Hydro (see
     /// [`Embedded`]). When `true`, [`Self::serialize_thunk`] and [`Self::deserialize_thunk`] are
     /// never called; the raw element type flows across the channel unserialized.
@@ -266,6 +311,9 @@ pub trait NetworkFor<T: ?Sized> {
     /// Returns the optional name of the network channel.
     fn name(&self) -> Option<&str>;
 
+    /// Takes the [`DecodeErrorPolicy`] of the network channel, leaving [`DecodeErrorPolicy::Panic`].
+    fn take_decode_error_policy(&mut self) -> DecodeErrorPolicy;
+
     /// Returns the [`NetworkingInfo`] describing this network channel's transport and fault model.
     fn networking_info() -> NetworkingInfo;
 }
@@ -308,10 +356,83 @@ pub enum NetworkingInfo {
     },
 }
 
+/// A message received over a network channel which could not be deserialized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodeError {
+    /// The raw bytes of the message.
+    pub bytes: Vec<u8>,
+    /// A description of the deserialization error.
+    pub message: String,
+}
+
+/// How the receiving side of a network channel handles messages that fail to deserialize, set
+/// with [`NetworkingConfig::on_decode_error`].
+#[derive(Debug, Default)]
+pub enum DecodeErrorPolicy {
+    /// Panic, crashing the receiving process. This is the default.
+    #[default]
+    Panic,
+    /// Silently drop the malformed message.
+    Drop,
+    /// Drop the malformed message from the output, and emit a [`DecodeError`] on a separate
+    /// stream at the receiving location instead.
+    DeadLetter(DeadLetters),
+}
+
+impl DecodeErrorPolicy {
+    /// Generates deserialization logic for receiving `T` over `N`, which produces a
+    /// `Result<T, DecodeError>` unless this policy is [`DecodeErrorPolicy::Panic`].
+    pub(crate) fn deserialize_thunk<T: ?Sized, N: NetworkFor<T>>(
+        &self,
+        tagged: Option<&syn::Type>,
+    ) -> syn::Expr {
+        if matches!(self, DecodeErrorPolicy::Panic) {
+            N::deserialize_thunk(tagged)
+        } else {
+            N::try_deserialize_thunk(tagged)
+        }
+    }
+}
+
+/// The stream which [`DecodeErrorPolicy::DeadLetter`] sends malformed messages to, created from a
+/// [`ForwardHandle`] obtained from [`Location::forward_ref`] at the receiving location.
+///
+/// The dead letter stream has [`NoOrder`] and [`AtLeastOnce`] guarantees, since malformed
+/// messages may be interleaved arbitrarily with other sources and retried by the sender.
+///
+/// # Example
+/// ```rust,ignore
+/// let (dead_letters_handle, dead_letters) = p2.forward_ref();
+/// let on_p2 = numbers.send(
+///     &p2,
+///     TCP.fail_stop()
+///         .bincode()
+///         .on_decode_error(DecodeErrorPolicy::DeadLetter(dead_letters_handle.into())),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct DeadLetters {
+    pub(crate) cycle_id: CycleId,
+    pub(crate) location: LocationId,
+}
+
+impl<'a, L: Location<'a>>
+    From<ForwardHandle<'a, Stream<DecodeError, L, Unbounded, NoOrder, AtLeastOnce>>>
+    for DeadLetters
+{
+    fn from(
+        handle: ForwardHandle<'a, Stream<DecodeError, L, Unbounded, NoOrder, AtLeastOnce>>,
+    ) -> Self {
+        let (cycle_id, location) = handle.into_parts();
+        DeadLetters { cycle_id, location }
+    }
+}
+
 /// A network channel configuration with `T` as transport backend and `S` as the serialization
 /// backend.
 pub struct NetworkingConfig<Tr: ?Sized, S: ?Sized, Name = ()> {
     name: Option<Name>,
+    on_decode_error: DecodeErrorPolicy,
     _phantom: (PhantomData<Tr>, PhantomData<S>),
 }
 
@@ -320,21 +441,48 @@ impl<Tr: ?Sized, S: ?Sized> NetworkingConfig<Tr, S> {
     pub fn name(self, name: impl Into<String>) -> NetworkingConfig<Tr, S, String> {
         NetworkingConfig {
             name: Some(name.into()),
+            on_decode_error: self.on_decode_error,
             _phantom: (PhantomData, PhantomData),
         }
     }
 }
 
-impl<Tr: ?Sized, N> NetworkingConfig<Tr, NoSer, N> {
-    /// Configures the network channel to use [`bincode`] to serialize items.
-    pub const fn bincode(mut self) -> NetworkingConfig<Tr, Bincode, N> {
+impl<Tr: ?Sized, S: ?Sized, N> NetworkingConfig<Tr, S, N> {
+    /// Sets how the receiving side handles messages that fail to deserialize, which by default
+    /// ([`DecodeErrorPolicy::Panic`]) crash the receiving process.
+    ///
+    /// Only [`DecodeErrorPolicy::Panic`] is supported on [`Embedded`] channels, where
+    /// deserialization happens outside of Hydro.
+    pub fn on_decode_error(mut self, policy: DecodeErrorPolicy) -> Self {
+        self.on_decode_error = policy;
+        self
+    }
+
+    /// Changes the transport or serialization backend, keeping the rest of the configuration.
+    const fn cast<Tr2: ?Sized, S2: ?Sized>(mut self) -> NetworkingConfig<Tr2, S2, N> {
         let taken_name = self.name.take();
+        let taken_policy = std::mem::replace(&mut self.on_decode_error, DecodeErrorPolicy::Panic);
         std::mem::forget(self); // nothing else is stored
         NetworkingConfig {
             name: taken_name,
+            on_decode_error: taken_policy,
             _phantom: (PhantomData, PhantomData),
         }
     }
+}
+
+impl<Tr: ?Sized, N> NetworkingConfig<Tr, NoSer, N> {
+    /// Configures the network channel to use [`bincode`] to serialize items.
+    pub const fn bincode(self) -> NetworkingConfig<Tr, Bincode, N> {
+        self.cast()
+    }
+
+    /// Configures the network channel to use [`bincode`] to serialize items, wrapped in a
+    /// versioned envelope which allows old and new versions of the item type to be exchanged
+    /// during a rolling upgrade. See [`versioned`] for details.
+    pub const fn versioned_bincode(self) -> NetworkingConfig<Tr, VersionedBincode, N> {
+        self.cast()
+    }
 
     /// Configures the network channel to leave serialization to code outside of Hydro.
     ///
@@ -342,13 +490,8 @@ impl<Tr: ?Sized, N> NetworkingConfig<Tr, NoSer, N> {
     /// backends). The generated network channel exposes the raw element type to the developer
     /// (rather than serialized bytes), so they can perform custom serialization logic outside of
     /// the Hydro program for that channel.
-    pub const fn embedded(mut self) -> NetworkingConfig<Tr, Embedded, N> {
-        let taken_name = self.name.take();
-        std::mem::forget(self); // nothing else is stored
-        NetworkingConfig {
-            name: taken_name,
-            _phantom: (PhantomData, PhantomData),
-        }
+    pub const fn embedded(self) -> NetworkingConfig<Tr, Embedded, N> {
+        self.cast()
     }
 }
 
@@ -361,10 +504,7 @@ impl<S: ?Sized> NetworkingConfig<Tcp<()>, S> {
     /// block indefinitely. However, any *safety* issues caused by connection failures will still
     /// be caught, such as a race condition between a failed connection and some other message.
     pub const fn fail_stop(self) -> NetworkingConfig<Tcp<FailStop>, S> {
-        NetworkingConfig {
-            name: self.name,
-            _phantom: (PhantomData, PhantomData),
-        }
+        self.cast()
     }
 
     /// Configures the TCP transport to allow messages to be lost.
@@ -377,10 +517,7 @@ impl<S: ?Sized> NetworkingConfig<Tcp<()>, S> {
     /// A lossy TCP channel will non-deterministically drop messages during execution.
     pub const fn lossy(self, nondet: NonDet) -> NetworkingConfig<Tcp<Lossy>, S> {
         let _ = nondet;
-        NetworkingConfig {
-            name: self.name,
-            _phantom: (PhantomData, PhantomData),
-        }
+        self.cast()
     }
 
     /// Configures the TCP transport to treat dropped messages as indefinitely delayed.
@@ -399,10 +536,7 @@ impl<S: ?Sized> NetworkingConfig<Tcp<()>, S> {
     /// the simulator will not actually drop packets—it delays "dropped" messages until
     /// the end of the execution, which catches safety bugs but cannot test liveness.
     pub const fn lossy_delayed_forever(self) -> NetworkingConfig<Tcp<LossyDelayedForever>, S> {
-        NetworkingConfig {
-            name: self.name,
-            _phantom: (PhantomData, PhantomData),
-        }
+        self.cast()
     }
 }
 
@@ -417,10 +551,7 @@ impl<S: ?Sized> NetworkingConfig<Udp<()>, S> {
     /// A lossy UDP channel will non-deterministically drop messages during execution.
     pub const fn lossy(self, nondet: NonDet) -> NetworkingConfig<Udp<Lossy>, S> {
         let _ = nondet;
-        NetworkingConfig {
-            name: self.name,
-            _phantom: (PhantomData, PhantomData),
-        }
+        self.cast()
     }
 
     /// Configures the UDP transport to treat dropped messages as indefinitely delayed.
@@ -439,10 +570,7 @@ impl<S: ?Sized> NetworkingConfig<Udp<()>, S> {
     /// the simulator will not actually drop packets—it delays "dropped" messages until
     /// the end of the execution, which catches safety bugs but cannot test liveness.
     pub const fn lossy_delayed_forever(self) -> NetworkingConfig<Udp<LossyDelayedForever>, S> {
-        NetworkingConfig {
-            name: self.name,
-            _phantom: (PhantomData, PhantomData),
-        }
+        self.cast()
     }
 }
 
@@ -464,6 +592,10 @@ where
         S::deserialize_thunk(tagged)
     }
 
+    fn try_deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr {
+        S::try_deserialize_thunk(tagged)
+    }
+
     fn is_embedded() -> bool {
         S::is_embedded()
     }
@@ -472,6 +604,10 @@ where
         None
     }
 
+    fn take_decode_error_policy(&mut self) -> DecodeErrorPolicy {
+        std::mem::replace(&mut self.on_decode_error, DecodeErrorPolicy::Panic)
+    }
+
     fn networking_info() -> NetworkingInfo {
         Tr::networking_info()
     }
@@ -495,6 +631,10 @@ where
         S::deserialize_thunk(tagged)
     }
 
+    fn try_deserialize_thunk(tagged: Option<&syn::Type>) -> syn::Expr {
+        S::try_deserialize_thunk(tagged)
+    }
+
     fn is_embedded() -> bool {
         S::is_embedded()
     }
@@ -503,6 +643,10 @@ where
         self.name.as_deref()
     }
 
+    fn take_decode_error_policy(&mut self) -> DecodeErrorPolicy {
+        std::mem::replace(&mut self.on_decode_error, DecodeErrorPolicy::Panic)
+    }
+
     fn networking_info() -> NetworkingInfo {
         Tr::networking_info()
     }
@@ -511,6 +655,7 @@ where
 /// A network channel that uses length-delimited TCP for transport.
 pub const TCP: NetworkingConfig<Tcp<()>, NoSer> = NetworkingConfig {
     name: None,
+    on_decode_error: DecodeErrorPolicy::Panic,
     _phantom: (PhantomData, PhantomData),
 };
 
@@ -532,5 +677,6 @@ pub const TCP: NetworkingConfig<Tcp<()>, NoSer> = NetworkingConfig {
 /// end of the execution, which catches safety bugs but cannot test liveness.
 pub const UDP: NetworkingConfig<Udp<()>, NoSer> = NetworkingConfig {
     name: None,
+    on_decode_error: DecodeErrorPolicy::Panic,
     _phantom: (PhantomData, PhantomData),
 };
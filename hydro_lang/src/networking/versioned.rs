@@ -0,0 +1,322 @@
+//! Versioned envelopes for messages sent over the network, which allow old and new versions of a
+//! program to exchange messages during a rolling upgrade.
+//!
+//! Plain [`bincode`] has no notion of versioning, so changing the definition of a message type
+//! breaks communication between binaries built before and after the change. Types which implement
+//! [`HydroMessage`] (usually with `#[derive(HydroMessage)]`) can instead be sent with the
+//! [`VersionedBincode`](super::VersionedBincode) serialization backend, which prefixes each
+//! message with the version of its type:
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize, HydroMessage)]
+//! #[hydro_message(version = 1)]
+//! struct RequestV1 {
+//!     key: String,
+//! }
+//!
+//! #[derive(Serialize, Deserialize, HydroMessage)]
+//! #[hydro_message(version = 2, previous = RequestV1)]
+//! struct Request {
+//!     key: String,
+//!     deadline_ms: Option<u64>,
+//! }
+//!
+//! // Up-conversion, used to receive messages from old senders.
+//! impl From<RequestV1> for Request {
+//!     fn from(old: RequestV1) -> Self {
+//!         Request { key: old.key, deadline_ms: None }
+//!     }
+//! }
+//!
+//! // Down-conversion, used to send messages to old receivers.
+//! impl From<Request> for RequestV1 {
+//!     fn from(new: Request) -> Self {
+//!         RequestV1 { key: new.key }
+//!     }
+//! }
+//!
+//! requests.send(&server, TCP.fail_stop().versioned_bincode())
+//! ```
+//!
+//! A receiver decodes messages of its own version or any older version in the `previous` chain,
+//! but cannot decode messages of a newer version. So during a rolling upgrade, upgraded senders
+//! should keep sending the old version until every receiver has been upgraded, by setting the
+//! [`SEND_VERSIONS_ENV`] environment variable (e.g. `HYDRO_MESSAGE_VERSIONS=Request=1`).
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+pub use hydro_lang_macro::HydroMessage;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Environment variable which overrides the version that messages are sent as, as a
+/// comma-separated list of `Name=version` pairs, where `Name` is the [`HydroMessage::NAME`] of a
+/// message type. Types which are not listed are sent as their current [`HydroMessage::VERSION`].
+pub const SEND_VERSIONS_ENV: &str = "HYDRO_MESSAGE_VERSIONS";
+
+/// Length of the version prefix of each message.
+const VERSION_LEN: usize = 4;
+
+/// A message type with an explicit version, which can be converted to and from the previous
+/// versions of the type.
+///
+/// Usually implemented with `#[derive(HydroMessage)]`, configured with a
+/// `#[hydro_message(version = N, previous = PreviousType, name = "Name")]` attribute, where
+/// `previous` and `name` are optional. The previous version must itself implement
+/// [`HydroMessage`], and conversions in both directions must be provided with [`From`]
+/// implementations. The `name` (which defaults to the name of the type) identifies the message
+/// type in [`SEND_VERSIONS_ENV`].
+pub trait HydroMessage: Serialize + DeserializeOwned + Sized {
+    /// The name of the message type, shared by all of its versions.
+    const NAME: &'static str;
+
+    /// The current version of the message type.
+    const VERSION: u32;
+
+    /// Decodes the payload of a message sent as `version`, converting it from an older version if
+    /// necessary.
+    fn decode_version(version: u32, payload: &[u8]) -> Result<Self, VersionError>;
+
+    /// Encodes the payload of this message as `version`, converting it to an older version if
+    /// necessary.
+    fn encode_version(self, version: u32) -> Result<Vec<u8>, VersionError>;
+}
+
+/// An error encoding or decoding a versioned message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionError {
+    /// The message type cannot be converted to or from the given version, for example because
+    /// the message was sent by a newer version of the program.
+    UnsupportedVersion {
+        /// The [`HydroMessage::NAME`] of the message type.
+        name: &'static str,
+        /// The version which could not be converted.
+        version: u32,
+    },
+    /// The message is too short to hold a version.
+    MissingVersion,
+    /// The payload could not be (de)serialized.
+    Bincode(String),
+    /// An entry of [`SEND_VERSIONS_ENV`] is not of the form `Name=version`.
+    InvalidSendVersion(String),
+}
+
+impl std::fmt::Display for VersionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionError::UnsupportedVersion { name, version } => {
+                write!(f, "unsupported version {} of message `{}`", version, name)
+            }
+            VersionError::MissingVersion => write!(f, "message is too short to hold a version"),
+            VersionError::Bincode(message) => write!(f, "{}", message),
+            VersionError::InvalidSendVersion(entry) => write!(
+                f,
+                "invalid entry `{}` in `{}`, expected `Name=version`",
+                entry, SEND_VERSIONS_ENV
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VersionError {}
+
+/// The version that messages of type `T` are sent as, see [`SEND_VERSIONS_ENV`]. Fails if the
+/// environment variable is malformed.
+pub fn send_version<T: HydroMessage>() -> Result<u32, VersionError> {
+    static SEND_VERSIONS: OnceLock<Result<HashMap<String, u32>, VersionError>> = OnceLock::new();
+    let send_versions = SEND_VERSIONS
+        .get_or_init(|| parse_send_versions(&std::env::var(SEND_VERSIONS_ENV).unwrap_or_default()))
+        .as_ref()
+        .map_err(Clone::clone)?;
+    Ok(send_versions.get(T::NAME).copied().unwrap_or(T::VERSION))
+}
+
+/// Parses the value of [`SEND_VERSIONS_ENV`].
+fn parse_send_versions(value: &str) -> Result<HashMap<String, u32>, VersionError> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            entry
+                .split_once('=')
+                .and_then(|(name, version)| {
+                    Some((name.trim().to_owned(), version.trim().parse().ok()?))
+                })
+                .ok_or_else(|| VersionError::InvalidSendVersion(entry.to_owned()))
+        })
+        .collect()
+}
+
+/// Serializes the payload of a message with [`bincode`], used by implementations of
+/// [`HydroMessage::encode_version`].
+#[doc(hidden)]
+pub fn encode_payload<T: Serialize>(message: &T) -> Result<Vec<u8>, VersionError> {
+    bincode::serialize(message).map_err(|e| VersionError::Bincode(e.to_string()))
+}
+
+/// Deserializes the payload of a message with [`bincode`], used by implementations of
+/// [`HydroMessage::decode_version`].
+#[doc(hidden)]
+pub fn decode_payload<T: DeserializeOwned>(payload: &[u8]) -> Result<T, VersionError> {
+    bincode::deserialize(payload).map_err(|e| VersionError::Bincode(e.to_string()))
+}
+
+/// Encodes `message` in a versioned envelope, as the version given by [`send_version`].
+pub fn encode<T: HydroMessage>(message: T) -> Result<Vec<u8>, VersionError> {
+    let version = send_version::<T>()?;
+    let payload = message.encode_version(version)?;
+    let mut bytes = Vec::with_capacity(VERSION_LEN + payload.len());
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Decodes a message from a versioned envelope created by [`encode`].
+pub fn decode<T: HydroMessage>(bytes: &[u8]) -> Result<T, VersionError> {
+    let (version, payload) = bytes
+        .split_first_chunk::<VERSION_LEN>()
+        .ok_or(VersionError::MissingVersion)?;
+    T::decode_version(u32::from_le_bytes(*version), payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, HydroMessage, Debug, PartialEq)]
+    #[hydro_message(version = 1, name = "Request")]
+    struct RequestV1 {
+        key: String,
+    }
+
+    #[derive(Serialize, Deserialize, HydroMessage, Debug, PartialEq)]
+    #[hydro_message(version = 2, previous = RequestV1)]
+    struct Request {
+        key: String,
+        deadline_ms: Option<u64>,
+    }
+
+    impl From<RequestV1> for Request {
+        fn from(old: RequestV1) -> Self {
+            Request {
+                key: old.key,
+                deadline_ms: None,
+            }
+        }
+    }
+
+    impl From<Request> for RequestV1 {
+        fn from(new: Request) -> Self {
+            RequestV1 { key: new.key }
+        }
+    }
+
+    #[test]
+    fn converts_between_versions() {
+        let request = || Request {
+            key: "a".to_owned(),
+            deadline_ms: Some(5),
+        };
+
+        // Old sender, new receiver.
+        let old_bytes = encode(RequestV1 {
+            key: "a".to_owned(),
+        })
+        .unwrap();
+        assert_eq!(
+            Request {
+                key: "a".to_owned(),
+                deadline_ms: None,
+            },
+            decode::<Request>(&old_bytes).unwrap()
+        );
+
+        // New sender, new receiver.
+        assert_eq!(
+            request(),
+            decode::<Request>(&encode(request()).unwrap()).unwrap()
+        );
+
+        // New sender downgrading for an old receiver.
+        let mut downgraded = 1u32.to_le_bytes().to_vec();
+        downgraded.extend(request().encode_version(1).unwrap());
+        assert_eq!(
+            RequestV1 {
+                key: "a".to_owned(),
+            },
+            decode::<RequestV1>(&downgraded).unwrap()
+        );
+
+        // An old receiver cannot decode a newer version.
+        assert_eq!(
+            Err(VersionError::UnsupportedVersion {
+                name: "Request",
+                version: 2,
+            }),
+            decode::<RequestV1>(&encode(request()).unwrap())
+        );
+    }
+
+    #[test]
+    fn parses_send_versions() {
+        assert_eq!(
+            Ok(HashMap::from([
+                ("Request".to_owned(), 1),
+                ("Response".to_owned(), 3)
+            ])),
+            parse_send_versions(" Request = 1, Response=3,")
+        );
+        assert_eq!(Ok(HashMap::new()), parse_send_versions(""));
+        assert_eq!(
+            Err(VersionError::InvalidSendVersion("Request=one".to_owned())),
+            parse_send_versions("Request=one")
+        );
+        assert_eq!(
+            Err(VersionError::InvalidSendVersion("Request".to_owned())),
+            parse_send_versions("Response=3,Request")
+        );
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_send_versioned_bincode() {
+        use crate::networking::TCP;
+        use crate::prelude::FlowBuilder;
+
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let node2 = flow.process::<()>();
+
+        let (in_send, input) = node.sim_input();
+        let out_recv = input
+            .send(&node2, TCP.fail_stop().versioned_bincode())
+            .sim_output();
+
+        flow.sim().exhaustive(async || {
+            in_send.send(Request {
+                key: "a".to_owned(),
+                deadline_ms: Some(5),
+            });
+            in_send.send(Request {
+                key: "b".to_owned(),
+                deadline_ms: None,
+            });
+
+            out_recv
+                .assert_yields_only([
+                    Request {
+                        key: "a".to_owned(),
+                        deadline_ms: Some(5),
+                    },
+                    Request {
+                        key: "b".to_owned(),
+                        deadline_ms: None,
+                    },
+                ])
+                .await;
+        });
+    }
+}
@@ -0,0 +1,316 @@
+//! Sinks that export live collections to object stores such as Amazon S3 and Google Cloud
+//! Storage, built on the [`object_store`] crate.
+//!
+//! The store is chosen by the scheme of the bucket URL (`s3://`, `gs://`, `az://`, or `file://`),
+//! and credentials are read from the standard environment variables of each provider, such as
+//! `AWS_ACCESS_KEY_ID` or `GOOGLE_SERVICE_ACCOUNT`. When deploying with Hydro Deploy, these can
+//! be forwarded to the launched processes with `TrybuildHost::forward_object_store_credentials`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use futures::Sink;
+use futures::future::BoxFuture;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use serde::Serialize;
+use stageleft::{IntoQuotedMut, QuotedWithContext, q};
+
+use crate::live_collections::boundedness::Boundedness;
+use crate::live_collections::stream::{IsExactlyOnce, IsOrdered, Ordering, Retries, Stream};
+use crate::location::Location;
+use crate::manual_expr::ManualExpr;
+
+/// The maximum number of elements written to a single object. Larger batches are split across
+/// several objects.
+pub const MAX_OBJECT_ELEMENTS: usize = 100_000;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// The file format of the objects written by [`Stream::sink_object_store`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectFormat {
+    /// Newline-delimited JSON, with one element per line.
+    JsonLines,
+    /// Apache Parquet, with one row per element. The schema of each object is inferred from the
+    /// elements it contains.
+    Parquet,
+}
+
+impl ObjectFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ObjectFormat::JsonLines => "jsonl",
+            ObjectFormat::Parquet => "parquet",
+        }
+    }
+
+    fn encode<T: Serialize>(self, elements: &[T]) -> Result<Bytes, BoxError> {
+        match self {
+            ObjectFormat::JsonLines => {
+                let mut buf = Vec::new();
+                for element in elements {
+                    serde_json::to_writer(&mut buf, element)?;
+                    buf.push(b'\n');
+                }
+                Ok(buf.into())
+            }
+            ObjectFormat::Parquet => {
+                use arrow_schema::FieldRef;
+                use serde_arrow::schema::{SchemaLike, TracingOptions};
+
+                let fields = Vec::<FieldRef>::from_samples(elements, TracingOptions::default())?;
+                let batch = serde_arrow::to_record_batch(&fields, &elements)?;
+
+                let mut buf = Vec::new();
+                let mut writer =
+                    parquet::arrow::ArrowWriter::try_new(&mut buf, batch.schema(), None)?;
+                writer.write(&batch)?;
+                writer.close()?;
+                Ok(buf.into())
+            }
+        }
+    }
+}
+
+/// Opens the object store at the URL `bucket`, returning it along with the path prefix of the
+/// bucket within it.
+fn open_store(bucket: &str) -> Result<(Arc<dyn ObjectStore>, String), String> {
+    let url =
+        url::Url::parse(bucket).map_err(|e| format!("invalid object store URL `{bucket}`: {e}"))?;
+    let (store, prefix) = object_store::parse_url_opts(
+        &url,
+        std::env::vars().map(|(key, value)| (key.to_ascii_lowercase(), value)),
+    )
+    .map_err(|e| format!("failed to configure object store for `{bucket}`: {e}"))?;
+    Ok((Arc::from(store), prefix.to_string()))
+}
+
+/// A [`Sink`] that groups elements by the key computed by `key_fn`, and writes each group to a
+/// new object whenever the sink is flushed.
+///
+/// If the object store could not be opened, every send fails with the error.
+#[doc(hidden)]
+pub struct ObjectStoreSink<T, F> {
+    store: Result<(Arc<dyn ObjectStore>, String), String>,
+    key_fn: F,
+    format: ObjectFormat,
+    run_id: u128,
+    next_object: u64,
+    buffered: HashMap<String, Vec<T>>,
+    num_buffered: usize,
+    in_flight: Option<BoxFuture<'static, Result<(), BoxError>>>,
+}
+
+#[doc(hidden)]
+pub fn object_store_sink<T, F>(
+    bucket: &str,
+    key_fn: F,
+    format: ObjectFormat,
+) -> ObjectStoreSink<T, F>
+where
+    F: Fn(&T) -> String,
+{
+    ObjectStoreSink {
+        store: open_store(bucket),
+        key_fn,
+        format,
+        run_id: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis(),
+        next_object: 0,
+        buffered: HashMap::new(),
+        num_buffered: 0,
+        in_flight: None,
+    }
+}
+
+impl<T: Serialize, F> ObjectStoreSink<T, F> {
+    fn start_upload(&mut self) -> Result<(), BoxError> {
+        let (store, prefix) = self.store.clone()?;
+        let mut objects = Vec::new();
+        for (key, elements) in std::mem::take(&mut self.buffered) {
+            for chunk in elements.chunks(MAX_OBJECT_ELEMENTS) {
+                let file = format!(
+                    "{}-{:06}.{}",
+                    self.run_id,
+                    self.next_object,
+                    self.format.extension()
+                );
+                self.next_object += 1;
+
+                let path = [prefix.as_str(), key.as_str(), file.as_str()]
+                    .iter()
+                    .flat_map(|segment| segment.split('/'))
+                    .filter(|segment| !segment.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                objects.push((Path::from(path), self.format.encode(chunk)?));
+            }
+        }
+        self.num_buffered = 0;
+
+        self.in_flight = Some(Box::pin(async move {
+            for (path, data) in objects {
+                store.put(&path, PutPayload::from(data)).await?;
+            }
+            Ok(())
+        }));
+        Ok(())
+    }
+
+    fn poll_in_flight(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        if let Some(in_flight) = self.in_flight.as_mut() {
+            let result = ready!(in_flight.as_mut().poll(cx));
+            self.in_flight = None;
+            result?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: Serialize, F: Fn(&T) -> String + Unpin> Sink<T> for ObjectStoreSink<T, F> {
+    type Error = BoxError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if let Err(e) = &this.store {
+            return Poll::Ready(Err(e.clone().into()));
+        }
+        ready!(this.poll_in_flight(cx))?;
+        if this.num_buffered >= MAX_OBJECT_ELEMENTS {
+            this.start_upload()?;
+            ready!(this.poll_in_flight(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let key = (this.key_fn)(&item);
+        this.buffered.entry(key).or_default().push(item);
+        this.num_buffered += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_in_flight(cx))?;
+        if this.num_buffered > 0 {
+            this.start_upload()?;
+            ready!(this.poll_in_flight(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, L, B, O, R>
+where
+    L: Location<'a>,
+{
+    /// Exports the elements of this stream to an object store, such as Amazon S3 or Google Cloud
+    /// Storage.
+    ///
+    /// Elements are grouped by the key computed by `key_fn`, and each time the stream is flushed,
+    /// every group is written as a new object under `{bucket}/{key}/` in the given `format`.
+    /// Objects are named by the time the process started and a sequence number, so objects
+    /// written by different runs (or by different processes, if they use distinct keys) never
+    /// overwrite each other.
+    ///
+    /// If `bucket` is not a valid URL, or the object store cannot be configured (for example due
+    /// to missing credentials), the sink fails with an error when the first element is sent.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// # use hydro_lang::prelude::*;
+    /// # use hydro_lang::object_storage::ObjectFormat;
+    /// # let mut flow = FlowBuilder::new();
+    /// let process = flow.process::<()>();
+    /// process
+    ///     .source_iter(q!(vec![("2024-01-01", 1), ("2024-01-02", 2)]))
+    ///     .sink_object_store(
+    ///         q!("s3://my-bucket/events"),
+    ///         q!(|(day, _)| format!("day={}", day)),
+    ///         q!(ObjectFormat::Parquet),
+    ///     );
+    /// ```
+    pub fn sink_object_store<F>(
+        self,
+        bucket: impl QuotedWithContext<'a, &'static str, L>,
+        key_fn: impl IntoQuotedMut<'a, F, L> + Copy,
+        format: impl QuotedWithContext<'a, ObjectFormat, L>,
+    ) where
+        T: Serialize,
+        F: Fn(&T) -> String + 'a,
+        O: IsOrdered,
+        R: IsExactlyOnce,
+    {
+        let key_fn: ManualExpr<F, _> =
+            ManualExpr::new(move |ctx: &L| key_fn.splice_fn1_borrow_ctx(ctx));
+        self.dest_sink(q!(crate::object_storage::object_store_sink(
+            bucket, key_fn, format
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+
+    use super::*;
+
+    #[test]
+    fn sink_reports_invalid_url() {
+        let mut sink = object_store_sink(
+            "not a url",
+            |_: &u32| String::new(),
+            ObjectFormat::JsonLines,
+        );
+        let err = futures::executor::block_on(sink.send(1)).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("invalid object store URL `not a url`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn sink_writes_json_lines_by_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let bucket = url::Url::from_directory_path(dir.path()).unwrap();
+        let mut sink = object_store_sink(
+            bucket.as_str(),
+            |(day, _): &(&str, u32)| format!("day={}", day),
+            ObjectFormat::JsonLines,
+        );
+        futures::executor::block_on(async {
+            sink.feed(("mon", 1)).await.unwrap();
+            sink.feed(("tue", 2)).await.unwrap();
+            sink.feed(("mon", 3)).await.unwrap();
+            sink.close().await.unwrap();
+        });
+
+        let read_day = |day: &str| {
+            let files = std::fs::read_dir(dir.path().join(format!("day={}", day)))
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect::<Vec<_>>();
+            assert_eq!(1, files.len(), "{:?}", files);
+            assert_eq!(Some(std::ffi::OsStr::new("jsonl")), files[0].extension());
+            std::fs::read_to_string(&files[0]).unwrap()
+        };
+        assert_eq!("[\"mon\",1]\n[\"mon\",3]\n", read_day("mon"));
+        assert_eq!("[\"tue\",2]\n", read_day("tue"));
+    }
+}
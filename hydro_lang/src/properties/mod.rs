@@ -4,6 +4,7 @@ use std::marker::PhantomData;
 
 use stageleft::properties::Property;
 
+use crate::compile::ir::ClaimedAlgebra;
 use crate::live_collections::boundedness::Boundedness;
 use crate::live_collections::keyed_singleton::KeyedSingletonBound;
 use crate::live_collections::singleton::SingletonBound;
@@ -160,6 +161,16 @@ impl<C, I, M> AggFuncAlgebra<C, I, M> {
         AggFuncAlgebra(self.0, self.1, Some(Box::new(proof)), PhantomData)
     }
 
+    /// Returns the properties that were asserted with a proof, which the simulator can try to
+    /// falsify.
+    pub(crate) fn claimed_algebra(&self) -> ClaimedAlgebra {
+        ClaimedAlgebra {
+            commutative: self.0.is_some(),
+            idempotent: self.1.is_some(),
+            ..Default::default()
+        }
+    }
+
     /// Registers the expression with the underlying proof mechanisms.
     pub(crate) fn register_proof(self, expr: &syn::Expr) {
         if let Some(comm_proof) = self.0 {
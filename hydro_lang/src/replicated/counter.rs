@@ -0,0 +1,148 @@
+//! Replicated counters, where each replica tracks the contributions of every other replica.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use lattices::Merge;
+use serde::{Deserialize, Serialize};
+
+use super::Crdt;
+
+/// A grow-only counter, which can only be incremented.
+///
+/// Each replica keeps the total it has contributed, keyed by its ID. Merging takes the maximum
+/// contribution of each replica, and the value of the counter is the sum of all contributions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GCounter<Id>
+where
+    Id: Eq + Hash,
+{
+    counts: HashMap<Id, u64>,
+}
+
+impl<Id: Eq + Hash> Default for GCounter<Id> {
+    fn default() -> Self {
+        GCounter {
+            counts: HashMap::new(),
+        }
+    }
+}
+
+impl<Id: Eq + Hash> GCounter<Id> {
+    /// Returns the current value of the counter, summed across all replicas.
+    pub fn value(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Returns the amount contributed to the counter by the given replica.
+    pub fn contribution(&self, replica: &Id) -> u64 {
+        self.counts.get(replica).copied().unwrap_or_default()
+    }
+}
+
+impl<Id: Eq + Hash> Merge<GCounter<Id>> for GCounter<Id> {
+    fn merge(&mut self, other: GCounter<Id>) -> bool {
+        let mut changed = false;
+        for (replica, count) in other.counts {
+            let entry = self.counts.entry(replica).or_default();
+            if count > *entry {
+                *entry = count;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+impl<Id: Eq + Hash + Clone> Crdt<Id> for GCounter<Id> {
+    /// The amount to increment the counter by.
+    type Op = u64;
+
+    fn apply(&mut self, replica: &Id, op: u64) {
+        *self.counts.entry(replica.clone()).or_default() += op;
+    }
+}
+
+/// A counter that supports both increments and decrements.
+///
+/// Internally, this is a pair of [`GCounter`]s, one for increments and one for decrements.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PNCounter<Id>
+where
+    Id: Eq + Hash,
+{
+    increments: GCounter<Id>,
+    decrements: GCounter<Id>,
+}
+
+impl<Id: Eq + Hash> Default for PNCounter<Id> {
+    fn default() -> Self {
+        PNCounter {
+            increments: GCounter::default(),
+            decrements: GCounter::default(),
+        }
+    }
+}
+
+impl<Id: Eq + Hash> PNCounter<Id> {
+    /// Returns the current value of the counter, summed across all replicas.
+    pub fn value(&self) -> i64 {
+        self.increments.value() as i64 - self.decrements.value() as i64
+    }
+}
+
+impl<Id: Eq + Hash> Merge<PNCounter<Id>> for PNCounter<Id> {
+    fn merge(&mut self, other: PNCounter<Id>) -> bool {
+        let increments_changed = self.increments.merge(other.increments);
+        let decrements_changed = self.decrements.merge(other.decrements);
+        increments_changed || decrements_changed
+    }
+}
+
+impl<Id: Eq + Hash + Clone> Crdt<Id> for PNCounter<Id> {
+    /// The amount to add to the counter, which is negative for decrements.
+    type Op = i64;
+
+    fn apply(&mut self, replica: &Id, op: i64) {
+        if op >= 0 {
+            self.increments.apply(replica, op as u64);
+        } else {
+            self.decrements.apply(replica, op.unsigned_abs());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lattices::Merge;
+
+    use super::{GCounter, PNCounter};
+    use crate::replicated::Crdt;
+
+    #[test]
+    fn gcounter_merge_takes_max_per_replica() {
+        let mut a = GCounter::default();
+        a.apply(&0, 3);
+        let mut b = GCounter::default();
+        b.apply(&0, 1);
+        b.apply(&1, 2);
+
+        assert!(a.merge(b.clone()));
+        assert_eq!(a.value(), 5);
+        assert!(!a.merge(b));
+        assert_eq!(a.value(), 5);
+    }
+
+    #[test]
+    fn pncounter_merge_is_commutative() {
+        let mut a = PNCounter::default();
+        a.apply(&0, 5);
+        let mut b = PNCounter::default();
+        b.apply(&1, -2);
+
+        let ab = PNCounter::merge_owned(a.clone(), b.clone());
+        let ba = PNCounter::merge_owned(b, a);
+        assert_eq!(ab, ba);
+        assert_eq!(ab.value(), 3);
+    }
+}
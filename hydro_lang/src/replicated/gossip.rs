@@ -0,0 +1,268 @@
+//! Anti-entropy for arbitrary lattices, by periodically gossiping with a few peers.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+#[cfg(feature = "tokio")]
+use std::time::Duration;
+
+use lattices::Merge;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+#[cfg(feature = "tokio")]
+use stageleft::QuotedWithContext;
+use stageleft::q;
+
+use crate::live_collections::boundedness::Unbounded;
+use crate::live_collections::lattice_singleton::LatticeSingleton;
+use crate::live_collections::sliced::sliced;
+use crate::live_collections::stream::networking::track_membership;
+use crate::live_collections::stream::{AtLeastOnce, NoOrder, Ordering, Retries, Stream};
+use crate::location::cluster::CLUSTER_SELF_ID;
+use crate::location::{Cluster, Location, MemberId};
+use crate::networking::NetworkFor;
+#[cfg(feature = "tokio")]
+use crate::networking::TCP;
+use crate::nondet::nondet;
+
+impl<'a, C: 'a> Cluster<'a, C> {
+    /// Gossips a lattice across the members of this cluster until every member converges to the
+    /// merge of all the values contributed to it.
+    ///
+    /// Each member merges the lattice values in `state` into its local replica. Every `interval`,
+    /// each member sends its entire replica to `fanout` peers chosen at random from the members it
+    /// currently knows of, which merge it into their own. Unlike [`Cluster::replicate`], which
+    /// sends every member's state to every other member, the cost of each round is proportional
+    /// to `fanout` rather than to the size of the cluster, and updates instead spread to the whole
+    /// cluster within a number of rounds logarithmic in its size.
+    ///
+    /// Messages are sent over a lossy channel, since a lost message is recovered by any later
+    /// round which reaches the same member.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use hydro_lang::prelude::*;
+    /// # use hydro_lang::location::cluster::CLUSTER_SELF_ID;
+    /// # use hydro_lang::replicated::{Crdt, GCounter};
+    /// # let mut flow = FlowBuilder::new();
+    /// let cluster = flow.cluster::<()>();
+    /// let contributions = cluster.source_iter(q!(vec![1, 2, 3])).map(q!(|amount| {
+    ///     let mut counter = GCounter::default();
+    ///     counter.apply(&CLUSTER_SELF_ID, amount);
+    ///     counter
+    /// }));
+    /// let counter = cluster.gossip(contributions, 2, q!(std::time::Duration::from_millis(100)));
+    /// # let _ = counter;
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn gossip<Lat, O: Ordering, R: Retries>(
+        &self,
+        state: Stream<Lat, Self, Unbounded, O, R>,
+        fanout: usize,
+        interval: impl QuotedWithContext<'a, Duration, Self> + Copy + 'a,
+    ) -> LatticeSingleton<Lat, Self, Unbounded>
+    where
+        Lat: Merge<Lat> + Default + Clone + Serialize + DeserializeOwned,
+    {
+        self.gossip_on(
+            state,
+            fanout,
+            self.source_interval(interval),
+            TCP.lossy(nondet!(/** lost messages are recovered by later rounds */))
+                .bincode(),
+        )
+    }
+
+    /// Like [`Cluster::gossip`], but runs a round of gossip for each element of `rounds` and sends
+    /// messages over `via`, so that rounds can be driven explicitly in simulations.
+    #[cfg_attr(
+        not(feature = "tokio"),
+        allow(dead_code, reason = "only used by `gossip` and tests")
+    )]
+    pub(crate) fn gossip_on<Lat, O: Ordering, R: Retries, O2: Ordering, R2: Retries, N>(
+        &self,
+        state: Stream<Lat, Self, Unbounded, O, R>,
+        fanout: usize,
+        rounds: Stream<(), Self, Unbounded, O2, R2>,
+        via: N,
+    ) -> LatticeSingleton<Lat, Self, Unbounded>
+    where
+        Lat: Merge<Lat> + Default + Clone + Serialize + DeserializeOwned,
+        N: NetworkFor<Lat>,
+    {
+        let (gossip_complete, gossip) =
+            self.forward_ref::<Stream<Lat, Self, Unbounded, NoOrder, AtLeastOnce>>();
+
+        let merged = state.merge_unordered(gossip).merge_lattice();
+
+        let members = track_membership(self.source_cluster_membership_stream(
+            self,
+            nondet!(/** members which are not yet known are reached in later rounds */),
+        ));
+
+        let to_send = sliced! {
+            let members = use(members, nondet!(
+                /** peers are chosen from any recent view of the membership */
+            ));
+            let snapshot = use(merged.clone().into_singleton(), nondet!(
+                /** any snapshot of the state is a valid update for the other members */
+            ));
+            let round_batch = use(rounds, nondet!(
+                /** rounds only affect when the state is sent, not the converged value */
+            ));
+            let mut round = use::state(|l| l.singleton(q!(0u64)));
+
+            let peers = members
+                .filter(q!(|present| *present))
+                .keys()
+                .filter(q!(|member| *member != CLUSTER_SELF_ID))
+                .sort()
+                .collect_vec();
+            let sends = snapshot
+                .filter_if(round_batch.first().is_some())
+                .into_stream()
+                .cross_singleton(peers)
+                .cross_singleton(round.clone())
+                .flat_map_ordered(q!(move |((replica, candidates), round_number)| {
+                    let seed = crate::replicated::gossip::round_seed(&CLUSTER_SELF_ID, round_number);
+                    crate::replicated::gossip::choose_peers(candidates, fanout, seed)
+                        .into_iter()
+                        .map(move |peer| (peer, replica.clone()))
+                }));
+
+            round = round.map(q!(|round_number| round_number + 1));
+            sends
+        };
+
+        gossip_complete.complete(
+            to_send
+                .weaken_retries::<AtLeastOnce>()
+                .demux(self, via)
+                .values(),
+        );
+
+        merged
+    }
+}
+
+/// The seed used by `member` to choose its peers in the given round.
+#[doc(hidden)]
+pub fn round_seed<C>(member: &MemberId<C>, round: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    member.hash(&mut hasher);
+    round.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Chooses up to `fanout` distinct elements of `candidates`, pseudo-randomly based on `seed`.
+///
+/// The choice is deterministic in `seed`, so that runs of the same program (in particular in the
+/// simulator) can be reproduced.
+#[doc(hidden)]
+pub fn choose_peers<T>(mut candidates: Vec<T>, fanout: usize, seed: u64) -> Vec<T> {
+    let fanout = fanout.min(candidates.len());
+    let mut state = seed;
+    // Partial Fisher-Yates shuffle, with a SplitMix64 generator.
+    for i in 0..fanout {
+        state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+
+        let j = i + (z % (candidates.len() - i) as u64) as usize;
+        candidates.swap(i, j);
+    }
+    candidates.truncate(fanout);
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "sim")]
+    use stageleft::q;
+
+    use super::choose_peers;
+    #[cfg(feature = "sim")]
+    use crate::live_collections::sliced::sliced;
+    #[cfg(feature = "sim")]
+    use crate::live_collections::stream::{ExactlyOnce, TotalOrder};
+    #[cfg(feature = "sim")]
+    use crate::location::MemberId;
+    #[cfg(feature = "sim")]
+    use crate::location::cluster::CLUSTER_SELF_ID;
+    #[cfg(feature = "sim")]
+    use crate::networking::TCP;
+    #[cfg(feature = "sim")]
+    use crate::nondet::nondet;
+    #[cfg(feature = "sim")]
+    use crate::prelude::FlowBuilder;
+    #[cfg(feature = "sim")]
+    use crate::replicated::{Crdt, GCounter};
+
+    #[test]
+    fn choose_peers_is_distinct_and_deterministic() {
+        let candidates = (0..10).collect::<Vec<_>>();
+        let chosen = choose_peers(candidates.clone(), 3, 42);
+        assert_eq!(chosen.len(), 3);
+        assert!(chosen.iter().all(|c| candidates.contains(c)));
+        assert!(chosen[0] != chosen[1] && chosen[1] != chosen[2] && chosen[0] != chosen[2]);
+        assert_eq!(chosen, choose_peers(candidates.clone(), 3, 42));
+
+        // Asking for more peers than there are candidates chooses all of them.
+        let mut all = choose_peers(candidates.clone(), 20, 7);
+        all.sort();
+        assert_eq!(all, candidates);
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_gossip_converges_under_message_loss() {
+        const MEMBERS: u32 = 3;
+
+        let mut flow = FlowBuilder::new();
+        let cluster = flow.cluster::<()>();
+
+        let (contribute, contributions) = cluster.sim_input::<u64, TotalOrder, ExactlyOnce>();
+        let (round_send, rounds) = cluster.sim_input::<(), TotalOrder, ExactlyOnce>();
+
+        let counters = contributions.map(q!(|amount| {
+            let mut counter = GCounter::<MemberId<()>>::default();
+            counter.apply(&CLUSTER_SELF_ID, amount);
+            counter
+        }));
+        let counter = cluster.gossip_on(counters, 1, rounds, TCP.lossy_delayed_forever().bincode());
+
+        let out_recv = sliced! {
+            let snapshot = use(counter.into_singleton(), nondet!(/** test */));
+            snapshot.into_stream().map(q!(|replica| replica.value()))
+        }
+        .sim_cluster_output();
+
+        flow.sim()
+            .with_cluster_size(&cluster, MEMBERS as usize)
+            .test_safety_only()
+            .fuzz(async || {
+                // Each member contributes its ID + 1, for a total of 1 + 2 + 3.
+                let total = (1..=MEMBERS as u64).sum::<u64>();
+                for member in 0..MEMBERS {
+                    contribute.send(member, member as u64 + 1);
+                }
+
+                let mut latest = [0; MEMBERS as usize];
+                // Messages from any round may be lost, but later rounds eventually reach every
+                // member, so keep gossiping until all of them have converged.
+                while latest.iter().any(|value| *value != total) {
+                    for member in 0..MEMBERS {
+                        round_send.send(member, ());
+                    }
+
+                    for member in 0..MEMBERS {
+                        while let Some(value) = out_recv.next(member).await {
+                            // Replicas only grow, and never beyond the merge of all contributions.
+                            assert!(value >= latest[member as usize] && value <= total);
+                            latest[member as usize] = value;
+                        }
+                    }
+                }
+            });
+    }
+}
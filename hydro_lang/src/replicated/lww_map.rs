@@ -0,0 +1,125 @@
+//! A replicated map where the most recent write to each key wins.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use lattices::Merge;
+use serde::{Deserialize, Serialize};
+
+use super::Crdt;
+
+/// A last-writer-wins map, where concurrent writes to the same key are resolved by keeping the
+/// write with the highest timestamp.
+///
+/// Timestamps are Lamport clocks, so a write always wins over every write that the writing
+/// replica had already observed. Ties between concurrent writes with the same timestamp are
+/// broken by the ID of the writing replica. Removed keys are kept as tombstones so that the
+/// removal can win over older writes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LWWMap<K, V, Id>
+where
+    K: Eq + Hash,
+{
+    entries: HashMap<K, (u64, Id, Option<V>)>,
+    clock: u64,
+}
+
+impl<K: Eq + Hash, V, Id> Default for LWWMap<K, V, Id> {
+    fn default() -> Self {
+        LWWMap {
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+}
+
+impl<K: Eq + Hash, V, Id> LWWMap<K, V, Id> {
+    /// Returns the value for the given key, if it is present in the map.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries
+            .get(key)
+            .and_then(|(_, _, value)| value.as_ref())
+    }
+
+    /// Returns an iterator over the key-value pairs currently in the map.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries
+            .iter()
+            .filter_map(|(key, (_, _, value))| value.as_ref().map(|value| (key, value)))
+    }
+}
+
+impl<K: Eq + Hash, V, Id: Ord> Merge<LWWMap<K, V, Id>> for LWWMap<K, V, Id> {
+    fn merge(&mut self, other: LWWMap<K, V, Id>) -> bool {
+        let mut changed = false;
+
+        if other.clock > self.clock {
+            self.clock = other.clock;
+            changed = true;
+        }
+
+        for (key, (timestamp, writer, value)) in other.entries {
+            match self.entries.get_mut(&key) {
+                Some(existing) if (existing.0, &existing.1) >= (timestamp, &writer) => {}
+                Some(existing) => {
+                    *existing = (timestamp, writer, value);
+                    changed = true;
+                }
+                None => {
+                    self.entries.insert(key, (timestamp, writer, value));
+                    changed = true;
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+impl<K: Eq + Hash, V, Id: Ord + Clone> Crdt<Id> for LWWMap<K, V, Id> {
+    /// A write to a key, where `None` removes the key.
+    type Op = (K, Option<V>);
+
+    fn apply(&mut self, replica: &Id, (key, value): (K, Option<V>)) {
+        self.clock += 1;
+        self.entries
+            .insert(key, (self.clock, replica.clone(), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lattices::Merge;
+
+    use super::LWWMap;
+    use crate::replicated::Crdt;
+
+    #[test]
+    fn lww_map_later_write_wins() {
+        let mut a = LWWMap::default();
+        a.apply(&0, ("k", Some(1)));
+
+        let mut b = a.clone();
+        b.apply(&1, ("k", Some(2)));
+
+        assert!(a.merge(b.clone()));
+        assert_eq!(a.get(&"k"), Some(&2));
+
+        a.apply(&0, ("k", None));
+        assert!(b.merge(a));
+        assert_eq!(b.get(&"k"), None);
+    }
+
+    #[test]
+    fn lww_map_concurrent_writes_break_ties_by_replica() {
+        let mut a = LWWMap::default();
+        a.apply(&0, ("k", Some("a")));
+        let mut b = LWWMap::default();
+        b.apply(&1, ("k", Some("b")));
+
+        let ab = LWWMap::merge_owned(a.clone(), b.clone());
+        let ba = LWWMap::merge_owned(b, a);
+        assert_eq!(ab, ba);
+        assert_eq!(ab.get(&"k"), Some(&"b"));
+    }
+}
@@ -0,0 +1,249 @@
+//! Replicated data types (CRDTs) and a combinator for replicating them across a [`Cluster`].
+//!
+//! Each data type in this module is a lattice (it implements [`lattices::Merge`]), so replicas
+//! can exchange their states in any order, any number of times, and still converge to the same
+//! value. Local updates are described by the [`Crdt::Op`] type of each data type, and are applied
+//! by [`Cluster::replicate`], which also handles disseminating the state to the other members of
+//! the cluster.
+//!
+//! The provided data types are:
+//! - [`GCounter`]: a counter that can only be incremented
+//! - [`PNCounter`]: a counter that can be incremented and decremented
+//! - [`ORSet`]: a set where concurrent adds win over removes
+//! - [`LWWMap`]: a map where the latest write to each key wins
+//!
+//! [`Cluster::gossip`] disseminates any lattice (not only the data types above) by exchanging
+//! states with a few randomly chosen peers per round, rather than with the whole cluster.
+//!
+//! [`KeyedSingleton::rebalance`](crate::live_collections::KeyedSingleton::rebalance) instead
+//! partitions keyed lattice state across the members of a cluster, migrating keys as members join
+//! and leave.
+
+#[cfg(feature = "tokio")]
+use std::time::Duration;
+
+use lattices::Merge;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+#[cfg(feature = "tokio")]
+use stageleft::QuotedWithContext;
+use stageleft::q;
+
+use crate::live_collections::boundedness::Unbounded;
+use crate::live_collections::lattice_singleton::LatticeSingleton;
+use crate::live_collections::sliced::sliced;
+use crate::live_collections::stream::{AtLeastOnce, NoOrder, Ordering, Retries, Stream};
+use crate::location::cluster::CLUSTER_SELF_ID;
+use crate::location::{Cluster, Location, MemberId};
+use crate::networking::NetworkFor;
+#[cfg(feature = "tokio")]
+use crate::networking::TCP;
+use crate::nondet::nondet;
+
+mod counter;
+pub use counter::{GCounter, PNCounter};
+
+mod or_set;
+pub use or_set::{ORSet, ORSetOp};
+
+mod lww_map;
+pub use lww_map::LWWMap;
+
+pub mod gossip;
+
+pub mod rebalance;
+
+/// A replicated data type, whose local updates are tagged with the ID of the replica that
+/// performed them.
+///
+/// Implementations must ensure that applying an update produces a state that is larger (in the
+/// lattice order defined by [`Merge`]) than the original, so that the update is never lost when
+/// the state is merged with the states of other replicas.
+pub trait Crdt<Id>: Merge<Self> + Default + Clone + Sized {
+    /// The type of local updates to the data type.
+    type Op;
+
+    /// Applies a local update performed by `replica`.
+    fn apply(&mut self, replica: &Id, op: Self::Op);
+}
+
+impl<'a, C: 'a> Cluster<'a, C> {
+    /// Replicates a data type across all members of this cluster.
+    ///
+    /// Each member applies the updates in `updates` to its local replica, in order. Every
+    /// `interval`, each member broadcasts its entire state to all other members, which merge it
+    /// into their own replica. Because the broadcast includes the full state, this doubles as
+    /// anti-entropy: members that missed messages (or joined late) catch up on the next round.
+    ///
+    /// The returned lattice at each member eventually converges to the same value once updates
+    /// stop arriving.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use hydro_lang::prelude::*;
+    /// # use hydro_lang::replicated::GCounter;
+    /// # let mut flow = FlowBuilder::new();
+    /// let cluster = flow.cluster::<()>();
+    /// let increments = cluster.source_iter(q!(vec![1, 2, 3]));
+    /// let counter =
+    ///     cluster.replicate::<GCounter<_>>(increments, q!(std::time::Duration::from_millis(100)));
+    /// # let _ = counter;
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn replicate<S>(
+        &self,
+        updates: Stream<S::Op, Self, Unbounded>,
+        interval: impl QuotedWithContext<'a, Duration, Self> + Copy + 'a,
+    ) -> LatticeSingleton<S, Self, Unbounded>
+    where
+        S: Crdt<MemberId<C>> + Serialize + DeserializeOwned,
+    {
+        self.replicate_on(
+            updates,
+            self.source_interval(interval),
+            TCP.lossy(nondet!(/** lost messages are recovered by the next round */))
+                .bincode(),
+        )
+    }
+
+    /// Like [`Cluster::replicate`], but broadcasts the state for each element of `rounds` and
+    /// sends messages over `via`, so that rounds can be driven explicitly in simulations.
+    #[cfg_attr(
+        not(feature = "tokio"),
+        allow(dead_code, reason = "only used by `replicate` and tests")
+    )]
+    pub(crate) fn replicate_on<S, O: Ordering, R: Retries, N>(
+        &self,
+        updates: Stream<S::Op, Self, Unbounded>,
+        rounds: Stream<(), Self, Unbounded, O, R>,
+        via: N,
+    ) -> LatticeSingleton<S, Self, Unbounded>
+    where
+        S: Crdt<MemberId<C>> + Serialize + DeserializeOwned,
+        N: NetworkFor<S>,
+    {
+        let (gossip_complete, gossip) =
+            self.forward_ref::<Stream<S, Self, Unbounded, NoOrder, AtLeastOnce>>();
+
+        let state = sliced! {
+            let local = use(updates, nondet!(
+                /// local updates are applied in order, batching only affects which remote
+                /// updates they are applied after, which does not affect the merged result
+            ));
+            let remote = use(gossip, nondet!(
+                /// remote states are merged, so batching does not affect the converged value
+            ));
+            let mut state = use::state(|l| l.singleton(q!(Default::default())));
+
+            let merged = state
+                .into_stream()
+                .chain(remote)
+                .merge_lattice()
+                .into_singleton();
+            let updated = merged.zip(local.collect_vec()).map(q!(|(mut replica, ops)| {
+                for op in ops {
+                    replica.apply(&CLUSTER_SELF_ID, op);
+                }
+                replica
+            }));
+
+            state = updated.clone();
+            updated
+        };
+
+        let to_send = sliced! {
+            let snapshot = use(state.clone(), nondet!(
+                /** any snapshot of the state is a valid update for the other replicas */
+            ));
+            let round_batch = use(rounds, nondet!(
+                /** rounds only affect when the state is sent, not the converged value */
+            ));
+
+            snapshot.filter_if(round_batch.first().is_some()).into_stream()
+        };
+
+        gossip_complete.complete(
+            to_send
+                .weaken_retries::<AtLeastOnce>()
+                .broadcast(
+                    self,
+                    via,
+                    nondet!(/** new members receive the full state on the next round */),
+                )
+                .values(),
+        );
+
+        LatticeSingleton { inner: state }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "sim")]
+    use stageleft::q;
+
+    #[cfg(feature = "sim")]
+    use crate::live_collections::sliced::sliced;
+    #[cfg(feature = "sim")]
+    use crate::live_collections::stream::{ExactlyOnce, TotalOrder};
+    #[cfg(feature = "sim")]
+    use crate::networking::TCP;
+    #[cfg(feature = "sim")]
+    use crate::nondet::nondet;
+    #[cfg(feature = "sim")]
+    use crate::prelude::FlowBuilder;
+    #[cfg(feature = "sim")]
+    use crate::replicated::GCounter;
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_replicate_converges_under_message_loss() {
+        const MEMBERS: u32 = 3;
+
+        let mut flow = FlowBuilder::new();
+        let cluster = flow.cluster::<()>();
+
+        let (increment, increments) = cluster.sim_input::<u64, TotalOrder, ExactlyOnce>();
+        let (round_send, rounds) = cluster.sim_input::<(), TotalOrder, ExactlyOnce>();
+
+        let counter = cluster.replicate_on::<GCounter<_>, _, _, _>(
+            increments,
+            rounds,
+            TCP.lossy_delayed_forever().bincode(),
+        );
+
+        let out_recv = sliced! {
+            let snapshot = use(counter.into_singleton(), nondet!(/** test */));
+            snapshot.into_stream().map(q!(|replica| replica.value()))
+        }
+        .sim_cluster_output();
+
+        flow.sim()
+            .with_cluster_size(&cluster, MEMBERS as usize)
+            .test_safety_only()
+            .fuzz(async || {
+                // Each member increments by its ID + 1, for a total of 1 + 2 + 3.
+                let total = (1..=MEMBERS as u64).sum::<u64>();
+                for member in 0..MEMBERS {
+                    increment.send(member, member as u64 + 1);
+                }
+
+                let mut latest = [0; MEMBERS as usize];
+                // Broadcasts from any round may be lost, but the full state is sent again in
+                // every round, so keep broadcasting until all members have converged.
+                while latest.iter().any(|value| *value != total) {
+                    for member in 0..MEMBERS {
+                        round_send.send(member, ());
+                    }
+
+                    for member in 0..MEMBERS {
+                        while let Some(value) = out_recv.next(member).await {
+                            // Replicas only grow, and never beyond the sum of all increments.
+                            assert!(value >= latest[member as usize] && value <= total);
+                            latest[member as usize] = value;
+                        }
+                    }
+                }
+            });
+    }
+}
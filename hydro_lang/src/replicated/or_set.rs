@@ -0,0 +1,162 @@
+//! A replicated set with add-wins semantics for concurrent adds and removes.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use lattices::Merge;
+use serde::{Deserialize, Serialize};
+
+use super::Crdt;
+
+/// An update to an [`ORSet`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ORSetOp<T> {
+    /// Adds the element to the set.
+    Add(T),
+    /// Removes the element from the set, if it has been observed by this replica.
+    Remove(T),
+}
+
+/// An observed-remove set, where a remove only cancels the adds that the removing replica had
+/// observed. If an element is concurrently added and removed, the add wins.
+///
+/// Every add is tagged with a unique `(replica, sequence number)` pair. Removes turn the
+/// currently-observed tags of an element into tombstones, and an element is present in the set
+/// as long as it has at least one tag that has not been tombstoned.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ORSet<T, Id>
+where
+    T: Eq + Hash,
+    Id: Eq + Hash,
+{
+    entries: HashMap<T, HashSet<(Id, u64)>>,
+    tombstones: HashSet<(Id, u64)>,
+    clock: HashMap<Id, u64>,
+}
+
+impl<T: Eq + Hash, Id: Eq + Hash> Default for ORSet<T, Id> {
+    fn default() -> Self {
+        ORSet {
+            entries: HashMap::new(),
+            tombstones: HashSet::new(),
+            clock: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Eq + Hash, Id: Eq + Hash> ORSet<T, Id> {
+    /// Returns `true` if the set contains the given element.
+    pub fn contains(&self, value: &T) -> bool {
+        self.entries.contains_key(value)
+    }
+
+    /// Returns an iterator over the elements currently in the set.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.keys()
+    }
+
+    /// Returns the number of elements currently in the set.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the set has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<T: Eq + Hash, Id: Eq + Hash + Clone> Merge<ORSet<T, Id>> for ORSet<T, Id> {
+    fn merge(&mut self, other: ORSet<T, Id>) -> bool {
+        let mut changed = false;
+
+        for (replica, seq) in other.clock {
+            let entry = self.clock.entry(replica).or_default();
+            if seq > *entry {
+                *entry = seq;
+                changed = true;
+            }
+        }
+
+        for tag in other.tombstones {
+            changed |= self.tombstones.insert(tag);
+        }
+
+        for (value, tags) in other.entries {
+            let existing = self.entries.entry(value).or_default();
+            for tag in tags {
+                if !self.tombstones.contains(&tag) {
+                    changed |= existing.insert(tag);
+                }
+            }
+        }
+
+        let tombstones = &self.tombstones;
+        self.entries.retain(|_, tags| {
+            tags.retain(|tag| !tombstones.contains(tag));
+            !tags.is_empty()
+        });
+
+        changed
+    }
+}
+
+impl<T: Eq + Hash, Id: Eq + Hash + Clone> Crdt<Id> for ORSet<T, Id> {
+    type Op = ORSetOp<T>;
+
+    fn apply(&mut self, replica: &Id, op: ORSetOp<T>) {
+        match op {
+            ORSetOp::Add(value) => {
+                let seq = self.clock.entry(replica.clone()).or_default();
+                *seq += 1;
+                self.entries
+                    .entry(value)
+                    .or_default()
+                    .insert((replica.clone(), *seq));
+            }
+            ORSetOp::Remove(value) => {
+                if let Some(tags) = self.entries.remove(&value) {
+                    self.tombstones.extend(tags);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lattices::Merge;
+
+    use super::{ORSet, ORSetOp};
+    use crate::replicated::Crdt;
+
+    #[test]
+    fn or_set_concurrent_add_wins() {
+        let mut base = ORSet::default();
+        base.apply(&0, ORSetOp::Add("a"));
+
+        let mut removed = base.clone();
+        removed.apply(&0, ORSetOp::Remove("a"));
+
+        let mut re_added = base;
+        re_added.apply(&1, ORSetOp::Add("a"));
+
+        let merged = ORSet::merge_owned(removed.clone(), re_added.clone());
+        assert!(merged.contains(&"a"));
+        assert_eq!(merged, ORSet::merge_owned(re_added, removed));
+    }
+
+    #[test]
+    fn or_set_observed_remove() {
+        let mut a = ORSet::default();
+        a.apply(&0, ORSetOp::Add(1));
+
+        let mut b = ORSet::default();
+        b.merge(a.clone());
+        b.apply(&1, ORSetOp::Remove(1));
+
+        assert!(a.merge(b));
+        assert!(!a.contains(&1));
+        assert!(a.is_empty());
+    }
+}
@@ -0,0 +1,497 @@
+//! Migrating keyed state between the members of a cluster as its membership changes.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use lattices::Merge;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use stageleft::{IntoQuotedMut, q};
+
+use crate::live_collections::boundedness::Unbounded;
+use crate::live_collections::keyed_singleton::{KeyedSingleton, KeyedSingletonBound};
+use crate::live_collections::keyed_stream::KeyedStream;
+use crate::live_collections::singleton::Singleton;
+use crate::live_collections::sliced::sliced;
+use crate::live_collections::stream::networking::track_membership;
+use crate::live_collections::stream::{AtLeastOnce, ExactlyOnce, NoOrder, Stream, TotalOrder};
+use crate::location::cluster::CLUSTER_SELF_ID;
+use crate::location::{Cluster, Location, MemberId, MembershipEvent, Tick};
+use crate::manual_expr::ManualExpr;
+use crate::networking::NetworkFor;
+#[cfg(feature = "tokio")]
+use crate::networking::TCP;
+use crate::nondet::nondet;
+
+impl<'a, K, V, C: 'a, B> KeyedSingleton<K, V, Cluster<'a, C>, B>
+where
+    B: KeyedSingletonBound<ValueBound = Unbounded>,
+{
+    /// Partitions the entries of this keyed singleton across the members of the cluster, so that
+    /// each key is owned by exactly one member, and migrates entries to their new owners as
+    /// members join and leave.
+    ///
+    /// Every member may contribute values for any key. Each contribution is sent to the member
+    /// which currently owns its key, which merges it into its own value. The owner of a key is
+    /// chosen by rendezvous hashing of `partitioner(&key)` against the present members, so that
+    /// a change in membership only moves the keys owned by the members that joined or left. When
+    /// the owner of a key changes, the previous owner hands its merged value off to the new
+    /// owner, and contributions made after that are routed to the new owner directly.
+    ///
+    /// Whenever the owner of a key changes, every member also re-sends its own contribution for
+    /// that key to the new owner. So if a member fails, the keys it owned are rebuilt from the
+    /// contributions of the remaining members, and only the contributions made by the failed
+    /// member itself are lost.
+    ///
+    /// Returns the entries currently owned by each member. Because values are merged, handoffs
+    /// and contributions may arrive in any order and still produce the same value, and once the
+    /// membership stops changing every key is eventually owned by exactly one member.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use hydro_lang::prelude::*;
+    /// # let mut flow = FlowBuilder::new();
+    /// let cluster = flow.cluster::<()>();
+    /// let counts = cluster
+    ///     .source_iter(q!(vec![
+    ///         ("a", lattices::Max::new(1)),
+    ///         ("b", lattices::Max::new(2))
+    ///     ]))
+    ///     .into_keyed()
+    ///     .fold(
+    ///         q!(|| lattices::Max::new(0)),
+    ///         q!(|acc, v| {
+    ///             lattices::Merge::merge(acc, v);
+    ///         }),
+    ///     );
+    /// let owned = counts.rebalance(q!(|key: &&str| key.len() as u64));
+    /// # let _ = owned;
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn rebalance<F>(
+        self,
+        partitioner: impl IntoQuotedMut<'a, F, Cluster<'a, C>> + Copy,
+    ) -> Singleton<HashMap<K, V>, Cluster<'a, C>, Unbounded>
+    where
+        K: Hash + Eq + Clone + Serialize + DeserializeOwned,
+        V: Merge<V> + Clone + PartialEq + Serialize + DeserializeOwned,
+        F: Fn(&K) -> u64 + 'a,
+    {
+        let cluster = self.location.clone();
+        let membership = cluster.source_cluster_membership_stream(
+            &cluster,
+            nondet!(/** ownership is recomputed once members become known */),
+        );
+        self.rebalance_with_control(partitioner, membership)
+    }
+
+    /// Like [`KeyedSingleton::rebalance`], but chooses owners from the members announced by the
+    /// `control` stream instead of the cluster membership.
+    ///
+    /// This allows a member to leave gracefully: announcing [`MembershipEvent::Left`] for it to
+    /// every member, while it is still running, makes it hand off all of the keys it owns before
+    /// it shuts down. Members only hand keys off to members that have [`MembershipEvent::Joined`]
+    /// in their own view of `control`, so every member should receive the same events.
+    #[cfg(feature = "tokio")]
+    pub fn rebalance_with_control<F>(
+        self,
+        partitioner: impl IntoQuotedMut<'a, F, Cluster<'a, C>> + Copy,
+        control: KeyedStream<MemberId<C>, MembershipEvent, Cluster<'a, C>, Unbounded>,
+    ) -> Singleton<HashMap<K, V>, Cluster<'a, C>, Unbounded>
+    where
+        K: Hash + Eq + Clone + Serialize + DeserializeOwned,
+        V: Merge<V> + Clone + PartialEq + Serialize + DeserializeOwned,
+        F: Fn(&K) -> u64 + 'a,
+    {
+        self.rebalance_on(partitioner, control, TCP.fail_stop().bincode())
+    }
+
+    /// Like [`KeyedSingleton::rebalance_with_control`], but sends handoffs over `via`, so that the
+    /// network can be replaced in simulations.
+    #[cfg_attr(
+        not(feature = "tokio"),
+        allow(dead_code, reason = "only used by `rebalance` and tests")
+    )]
+    pub(crate) fn rebalance_on<F, N>(
+        self,
+        partitioner: impl IntoQuotedMut<'a, F, Cluster<'a, C>> + Copy,
+        control: KeyedStream<MemberId<C>, MembershipEvent, Cluster<'a, C>, Unbounded>,
+        via: N,
+    ) -> Singleton<HashMap<K, V>, Cluster<'a, C>, Unbounded>
+    where
+        K: Hash + Eq + Clone + Serialize + DeserializeOwned,
+        V: Merge<V> + Clone + PartialEq + Serialize + DeserializeOwned,
+        F: Fn(&K) -> u64 + 'a,
+        N: NetworkFor<(K, V)>,
+    {
+        let cluster = self.location.clone();
+        let (handoffs_complete, handoffs) =
+            cluster
+                .forward_ref::<Stream<(K, V), Cluster<'a, C>, Unbounded, NoOrder, AtLeastOnce>>();
+
+        let members = track_membership(control);
+
+        let splice_location = cluster.clone();
+        let partitioner: ManualExpr<F, _> = ManualExpr::new(move |_: &Tick<Cluster<'a, C>>| {
+            partitioner.splice_fn1_borrow_ctx(&splice_location)
+        });
+
+        let (owned, to_send) = sliced! {
+            let members = use(members, nondet!(
+                /** owners are chosen from any recent view of the membership */
+            ));
+            let local = use(self, nondet!(
+                /** contributions are forwarded from any snapshot, and later snapshots forward
+                 * whatever changed since */
+            ));
+            let received = use(handoffs, nondet!(
+                /** handoffs are merged, so the tick they arrive in does not affect the value */
+            ));
+            let mut state = use::state(|l| l.singleton(q!(Default::default())));
+
+            let present = members
+                .filter(q!(|present| *present))
+                .keys()
+                .sort()
+                .collect_vec();
+            let stepped = state
+                .zip(local.into_singleton())
+                .zip(
+                    received
+                        .assume_ordering::<TotalOrder>(nondet!(
+                            /** handoffs are merged, which is commutative */
+                        ))
+                        .assume_retries::<ExactlyOnce>(nondet!(
+                            /** handoffs are merged, which is idempotent */
+                        ))
+                        .collect_vec(),
+                )
+                .zip(present)
+                .map(q!({
+                    let partitioner = partitioner;
+                    move |(((state, local), received), members)| {
+                        crate::replicated::rebalance::rebalance_step(
+                            state,
+                            local,
+                            received,
+                            &members,
+                            &CLUSTER_SELF_ID,
+                            &partitioner,
+                        )
+                    }
+                }));
+
+            state = stepped.clone().map(q!(|(state, _)| state));
+            let owned = state.clone().map(q!(|state| state.owned.clone()));
+            let to_send = stepped
+                .into_stream()
+                .flat_map_unordered(q!(|(_, handoffs)| handoffs));
+            (owned, to_send)
+        };
+
+        handoffs_complete.complete(
+            to_send
+                .weaken_retries::<AtLeastOnce>()
+                .demux(&cluster, via)
+                .values(),
+        );
+
+        owned
+    }
+}
+
+/// The state of [`KeyedSingleton::rebalance`] at each member, between ticks.
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct RebalanceState<K, V, M> {
+    /// The merged values of the keys owned by this member.
+    pub owned: HashMap<K, V>,
+    /// The local contribution for each key, and the owner it was last sent to.
+    forwarded: HashMap<K, (M, V)>,
+}
+
+impl<K, V, M> Default for RebalanceState<K, V, M> {
+    fn default() -> Self {
+        Self {
+            owned: HashMap::new(),
+            forwarded: HashMap::new(),
+        }
+    }
+}
+
+/// Merges newly arrived values into the state, and returns the entries which must be handed off
+/// to another member along with that member.
+///
+/// Local contributions are only sent if they changed, or their owner changed, since they were
+/// last sent. If `members` is empty (the membership is not yet known), all keys are kept locally.
+#[doc(hidden)]
+pub fn rebalance_step<K, V, M>(
+    mut state: RebalanceState<K, V, M>,
+    local: HashMap<K, V>,
+    received: Vec<(K, V)>,
+    members: &[M],
+    self_id: &M,
+    partitioner: impl Fn(&K) -> u64,
+) -> (RebalanceState<K, V, M>, Vec<(M, (K, V))>)
+where
+    K: Hash + Eq + Clone,
+    M: Hash + Eq + Clone,
+    V: Merge<V> + Clone + PartialEq,
+{
+    let owner = |key: &K| {
+        if members.is_empty() {
+            self_id
+        } else {
+            owner_of(partitioner(key), members)
+        }
+    };
+
+    let mut handoffs = Vec::new();
+    let mut arrived = received;
+    for (key, value) in local {
+        let owner = owner(&key);
+        if state
+            .forwarded
+            .get(&key)
+            .is_some_and(|(sent_to, sent)| sent_to == owner && *sent == value)
+        {
+            continue;
+        }
+        state
+            .forwarded
+            .insert(key.clone(), (owner.clone(), value.clone()));
+        if owner == self_id {
+            arrived.push((key, value));
+        } else {
+            handoffs.push((owner.clone(), (key, value)));
+        }
+    }
+
+    for (key, value) in arrived {
+        match state.owned.entry(key) {
+            Entry::Occupied(mut existing) => {
+                existing.get_mut().merge(value);
+            }
+            Entry::Vacant(vacant) => {
+                vacant.insert(value);
+            }
+        }
+    }
+
+    for (key, value) in std::mem::take(&mut state.owned) {
+        let owner = owner(&key);
+        if owner == self_id {
+            state.owned.insert(key, value);
+        } else {
+            handoffs.push((owner.clone(), (key, value)));
+        }
+    }
+
+    (state, handoffs)
+}
+
+/// Chooses the owner of a key with the given placement among a non-empty set of `members`, by
+/// rendezvous (highest random weight) hashing.
+fn owner_of<M: Hash>(placement: u64, members: &[M]) -> &M {
+    members
+        .iter()
+        .max_by_key(|member| {
+            let mut hasher = DefaultHasher::new();
+            placement.hash(&mut hasher);
+            member.hash(&mut hasher);
+            hasher.finish()
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use lattices::Max;
+    #[cfg(feature = "sim")]
+    use stageleft::q;
+
+    use super::{RebalanceState, owner_of, rebalance_step};
+    #[cfg(feature = "sim")]
+    use crate::live_collections::sliced::sliced;
+    #[cfg(feature = "sim")]
+    use crate::live_collections::stream::{ExactlyOnce, TotalOrder};
+    #[cfg(feature = "sim")]
+    use crate::location::{MemberId, MembershipEvent};
+    #[cfg(feature = "sim")]
+    use crate::networking::TCP;
+    #[cfg(feature = "sim")]
+    use crate::nondet::nondet;
+    #[cfg(feature = "sim")]
+    use crate::prelude::FlowBuilder;
+
+    #[test]
+    fn owner_is_stable_when_other_members_leave() {
+        let members = (0..5u32).collect::<Vec<_>>();
+        for placement in 0..100 {
+            let owner = *owner_of(placement, &members);
+            let remaining = members
+                .iter()
+                .filter(|member| **member == owner || **member % 2 == 0)
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(owner_of(placement, &remaining), &owner);
+        }
+    }
+
+    #[test]
+    fn rebalance_step_hands_off_keys_to_owners() {
+        let members = vec![0u32, 1];
+        let local = (0..10u64)
+            .map(|key| (key, Max::new(key)))
+            .collect::<HashMap<_, _>>();
+
+        let (state, handoffs) = rebalance_step(
+            RebalanceState::default(),
+            local.clone(),
+            vec![],
+            &members,
+            &members[0],
+            |key| *key,
+        );
+        for (owner, (key, _)) in &handoffs {
+            assert_eq!(*owner, 1);
+            assert_eq!(owner_of(*key, &members), owner);
+        }
+        assert_eq!(state.owned.len() + handoffs.len(), 10);
+
+        // Unchanged contributions are not sent again.
+        let (_, handoffs) = rebalance_step(state, local, vec![], &members, &members[0], |key| *key);
+        assert!(handoffs.is_empty());
+    }
+
+    #[test]
+    fn rebalance_step_resends_contributions_when_owner_leaves() {
+        let members = vec![0u32, 1, 2];
+        let local = (0..30u64)
+            .map(|key| (key, Max::new(key)))
+            .collect::<HashMap<_, _>>();
+        let (state, _) = rebalance_step(
+            RebalanceState::default(),
+            local.clone(),
+            vec![],
+            &members,
+            &0,
+            |key| *key,
+        );
+
+        // Member 2 fails along with the values it owned, so the contributions for its keys must
+        // be sent to their new owners again.
+        let remaining = vec![0u32, 1];
+        let (state, handoffs) =
+            rebalance_step(state, local.clone(), vec![], &remaining, &0, |key| *key);
+        let orphaned = (0..30u64)
+            .filter(|key| *owner_of(*key, &members) == 2)
+            .collect::<Vec<_>>();
+        assert!(!orphaned.is_empty());
+        for key in orphaned {
+            let new_owner = *owner_of(key, &remaining);
+            if new_owner == 0 {
+                assert_eq!(state.owned.get(&key), Some(&Max::new(key)));
+            } else {
+                assert!(handoffs.contains(&(new_owner, (key, Max::new(key)))));
+            }
+        }
+        assert!(handoffs.iter().all(|(owner, _)| *owner == 1));
+
+        // When member 2 comes back, the contributions are sent to it again, even though they are
+        // unchanged since they were last sent to it.
+        let (state, handoffs) = rebalance_step(state, local, vec![], &members, &0, |key| *key);
+        for key in (0..30u64).filter(|key| *owner_of(*key, &members) == 2) {
+            assert!(handoffs.contains(&(2, (key, Max::new(key)))));
+            assert!(!state.owned.contains_key(&key));
+        }
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn sim_rebalance_recovers_keys_under_churn() {
+        const KEYS: u64 = 12;
+
+        let mut flow = FlowBuilder::new();
+        let cluster = flow.cluster::<()>();
+
+        let (contribute, contributions) =
+            cluster.sim_input::<(u64, u64), TotalOrder, ExactlyOnce>();
+        let (control_send, control) =
+            cluster.sim_input::<(MemberId<()>, MembershipEvent), TotalOrder, ExactlyOnce>();
+
+        let owned = contributions
+            .into_keyed()
+            .fold(
+                q!(|| lattices::Max::new(0)),
+                q!(|acc, v| {
+                    lattices::Merge::merge(acc, lattices::Max::new(v));
+                }),
+            )
+            .rebalance_on(
+                q!(|key: &u64| *key),
+                control.into_keyed(),
+                TCP.fail_stop().bincode(),
+            );
+
+        let out_recv = sliced! {
+            let snapshot = use(owned, nondet!(/** test */));
+            snapshot.into_stream().map(q!(|owned| {
+                let mut owned = owned
+                    .into_iter()
+                    .map(|(key, value)| (key, *value.as_reveal_ref()))
+                    .collect::<Vec<_>>();
+                owned.sort();
+                owned
+            }))
+        }
+        .sim_cluster_output();
+
+        flow.sim()
+            .with_cluster_size(&cluster, 3)
+            .test_safety_only()
+            .fuzz(async || {
+                let announce = |member: u32, event: MembershipEvent, to: &[u32]| {
+                    for &to in to {
+                        control_send.send(to, (MemberId::from_raw_id(member), event.clone()));
+                    }
+                };
+                // Collects the latest snapshot of each member once the simulation quiesces, and
+                // returns the value of each key across all members.
+                let settle = async |members: &[u32]| {
+                    let mut merged = Vec::new();
+                    for &member in members {
+                        let mut latest = Vec::new();
+                        while let Some(snapshot) = out_recv.next(member).await {
+                            latest = snapshot;
+                        }
+                        merged.extend(latest);
+                    }
+                    merged.sort();
+                    merged
+                };
+                let expected = (0..KEYS).map(|key| (key, key + 1)).collect::<Vec<_>>();
+
+                for member in 0..3 {
+                    announce(member, MembershipEvent::Joined, &[0, 1, 2]);
+                }
+                // Only members 0 and 1 contribute, so member 2 holds nothing but its keys.
+                for key in 0..KEYS {
+                    contribute.send((key % 2) as u32, (key, key + 1));
+                }
+                assert_eq!(settle(&[0, 1, 2]).await, expected);
+
+                // Member 2 fails, from the view of the others: its keys are rebuilt from their
+                // contributions, with every key owned exactly once.
+                announce(2, MembershipEvent::Left, &[0, 1]);
+                assert_eq!(settle(&[0, 1]).await, expected);
+
+                // Member 2 comes back, and takes its keys back.
+                announce(2, MembershipEvent::Joined, &[0, 1]);
+                assert_eq!(settle(&[0, 1, 2]).await, expected);
+            });
+    }
+}
@@ -0,0 +1,223 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::{Future, poll_fn};
+use std::hash::Hash;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+/// How many values an [`AsyncCache`] holds, and how many calls to its function may be in flight
+/// at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AsyncCacheLimits {
+    /// The maximum number of cached values.
+    pub capacity: usize,
+    /// The maximum number of calls to the function which are in flight at once.
+    pub max_in_flight: usize,
+}
+
+impl AsyncCacheLimits {
+    /// Limits which hold up to `capacity` values and allow as many calls in flight at once.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            max_in_flight: capacity,
+        }
+    }
+}
+
+/// Memoizes an asynchronous function in a least-recently-used cache, and bounds the number of
+/// calls to it which are in flight at once.
+///
+/// Used by [`Stream::map_async_cached`](crate::live_collections::stream::Stream::map_async_cached).
+pub struct AsyncCache<K, V, F> {
+    f: Rc<F>,
+    state: Rc<RefCell<CacheState<K, V>>>,
+}
+
+struct CacheState<K, V> {
+    limits: AsyncCacheLimits,
+    /// Cached values, along with the time they were last used.
+    entries: HashMap<K, (V, u64)>,
+    /// The cached keys, ordered by when they were last used.
+    recency: BTreeMap<u64, K>,
+    clock: u64,
+    in_flight: usize,
+    waiting: VecDeque<Waker>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> CacheState<K, V> {
+    fn get(&mut self, key: &K) -> Option<V> {
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.recency.remove(last_used);
+        self.clock += 1;
+        *last_used = self.clock;
+        self.recency.insert(self.clock, key.clone());
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.clock += 1;
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, self.clock)) {
+            self.recency.remove(&last_used);
+        }
+        self.recency.insert(self.clock, key);
+
+        while self.entries.len() > self.limits.capacity {
+            let (_, evicted) = self.recency.pop_first().unwrap();
+            self.entries.remove(&evicted);
+        }
+    }
+}
+
+/// Releases a slot for an in-flight call when dropped, waking the next waiting lookup.
+struct InFlight<K, V>(Rc<RefCell<CacheState<K, V>>>);
+
+impl<K, V> Drop for InFlight<K, V> {
+    fn drop(&mut self) {
+        let mut state = self.0.borrow_mut();
+        state.in_flight -= 1;
+        if let Some(waker) = state.waiting.pop_front() {
+            waker.wake();
+        }
+    }
+}
+
+impl<K, V, F, Fut> AsyncCache<K, V, F>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    F: Fn(K) -> Fut,
+    Fut: Future<Output = V>,
+{
+    /// Creates a cache of the results of `f` within the given limits.
+    pub fn new(limits: AsyncCacheLimits, f: F) -> Self {
+        assert!(
+            limits.capacity > 0,
+            "the capacity of an async cache must be positive"
+        );
+        assert!(
+            limits.max_in_flight > 0,
+            "an async cache must allow at least one call in flight"
+        );
+        Self {
+            f: Rc::new(f),
+            state: Rc::new(RefCell::new(CacheState {
+                limits,
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                clock: 0,
+                in_flight: 0,
+                waiting: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Looks up the value for `key`, calling the function if it is not cached.
+    pub fn lookup(&self, key: K) -> impl Future<Output = V> + use<K, V, F, Fut> {
+        let f = self.f.clone();
+        let state = self.state.clone();
+        async move {
+            if let Some(value) = state.borrow_mut().get(&key) {
+                return value;
+            }
+
+            let _slot = poll_fn(|cx| {
+                let mut locked = state.borrow_mut();
+                if locked.in_flight < locked.limits.max_in_flight {
+                    locked.in_flight += 1;
+                    Poll::Ready(InFlight(state.clone()))
+                } else {
+                    locked.waiting.push_back(cx.waker().clone());
+                    Poll::Pending
+                }
+            })
+            .await;
+
+            // Another lookup of the same key may have completed while this one was waiting.
+            if let Some(value) = state.borrow_mut().get(&key) {
+                return value;
+            }
+
+            let value = f(key.clone()).await;
+            state.borrow_mut().insert(key, value.clone());
+            value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::future::poll_fn;
+    use std::rc::Rc;
+    use std::task::Poll;
+
+    use super::{AsyncCache, AsyncCacheLimits};
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let calls = Rc::new(Cell::new(0));
+        let cache = AsyncCache::new(AsyncCacheLimits::new(2), {
+            let calls = calls.clone();
+            move |key: u32| {
+                calls.set(calls.get() + 1);
+                async move { key * 10 }
+            }
+        });
+
+        let lookup = |key| futures::executor::block_on(cache.lookup(key));
+        assert_eq!(lookup(1), 10);
+        assert_eq!(lookup(2), 20);
+        assert_eq!(lookup(1), 10);
+        assert_eq!(calls.get(), 2);
+
+        // Evicts 2, which was used less recently than 1.
+        assert_eq!(lookup(3), 30);
+        assert_eq!(lookup(1), 10);
+        assert_eq!(calls.get(), 3);
+        assert_eq!(lookup(2), 20);
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn bounds_calls_in_flight_separately() {
+        let in_flight = Rc::new(Cell::new(0));
+        let max_seen = Rc::new(Cell::new(0));
+        let limits = AsyncCacheLimits {
+            capacity: 1,
+            max_in_flight: 2,
+        };
+        let cache = AsyncCache::new(limits, {
+            let in_flight = in_flight.clone();
+            let max_seen = max_seen.clone();
+            move |key: u32| {
+                let in_flight = in_flight.clone();
+                let max_seen = max_seen.clone();
+                async move {
+                    in_flight.set(in_flight.get() + 1);
+                    max_seen.set(max_seen.get().max(in_flight.get()));
+                    // Yield once, so that other lookups can start in the meantime.
+                    let mut yielded = false;
+                    poll_fn(|cx| {
+                        if yielded {
+                            Poll::Ready(())
+                        } else {
+                            yielded = true;
+                            cx.waker().wake_by_ref();
+                            Poll::Pending
+                        }
+                    })
+                    .await;
+                    in_flight.set(in_flight.get() - 1);
+                    key * 10
+                }
+            }
+        });
+
+        let results = futures::executor::block_on(futures::future::join_all(
+            (0..4).map(|key| cache.lookup(key)),
+        ));
+        assert_eq!(results, vec![0, 10, 20, 30]);
+        assert_eq!(max_seen.get(), 2);
+    }
+}
@@ -0,0 +1,23 @@
+use serde::de::DeserializeOwned;
+
+/// Reads the value of the configuration parameter `name` from the environment variable `var`.
+///
+/// The value is parsed as JSON, falling back to the raw string (as a JSON string) if it is not
+/// valid JSON, so that string parameters do not need to be quoted.
+pub fn load<T: DeserializeOwned>(name: &str, var: &str) -> T {
+    let value = std::env::var(var).unwrap_or_else(|_| {
+        panic!(
+            "No value for configuration parameter `{}`, set the environment variable `{}`",
+            name, var
+        )
+    });
+
+    serde_json::from_str(&value)
+        .or_else(|_| serde_json::from_value(serde_json::Value::String(value.clone())))
+        .unwrap_or_else(|e| {
+            panic!(
+                "Invalid value for configuration parameter `{}` ({:?}): {}",
+                name, value, e
+            )
+        })
+}
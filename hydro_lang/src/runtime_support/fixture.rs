@@ -0,0 +1,52 @@
+use std::io::{BufRead, BufReader};
+
+use serde::de::DeserializeOwned;
+
+/// Reads the values recorded in the fixture file at `path`.
+///
+/// Files ending in `.jsonl` are parsed as one JSON value per line (blank lines are skipped), and
+/// all other files as consecutive bincode-encoded values.
+pub fn load<T: DeserializeOwned>(path: &str) -> Vec<T> {
+    let file = std::fs::File::open(path)
+        .unwrap_or_else(|e| panic!("Failed to open fixture `{}`: {}", path, e));
+    let mut reader = BufReader::new(file);
+
+    if path.ends_with(".jsonl") {
+        reader
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let line =
+                    line.unwrap_or_else(|e| panic!("Failed to read fixture `{}`: {}", path, e));
+                if line.trim().is_empty() {
+                    return None;
+                }
+                Some(serde_json::from_str(&line).unwrap_or_else(|e| {
+                    panic!(
+                        "Invalid value on line {} of fixture `{}`: {}",
+                        i + 1,
+                        path,
+                        e
+                    )
+                }))
+            })
+            .collect()
+    } else {
+        let mut values = Vec::new();
+        while !reader
+            .fill_buf()
+            .unwrap_or_else(|e| panic!("Failed to read fixture `{}`: {}", path, e))
+            .is_empty()
+        {
+            values.push(bincode::deserialize_from(&mut reader).unwrap_or_else(|e| {
+                panic!(
+                    "Invalid value #{} in fixture `{}`: {}",
+                    values.len(),
+                    path,
+                    e
+                )
+            }));
+        }
+        values
+    }
+}
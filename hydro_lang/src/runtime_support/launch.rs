@@ -12,14 +12,14 @@ use procfs::WithCurrentSystemInfo;
 use serde::de::DeserializeOwned;
 
 #[cfg(not(feature = "runtime_measure"))]
-pub async fn run_stdin_commands(flow: impl Future) {
-    launch_flow_stdin_commands(flow).await;
+pub async fn run_stdin_commands(drain: DrainSignal, flow: impl Future) {
+    launch_flow_stdin_commands(drain, flow).await;
 }
 
 #[cfg(feature = "runtime_measure")]
-pub async fn run_stdin_commands(flow: impl Future) {
+pub async fn run_stdin_commands(drain: DrainSignal, flow: impl Future) {
     // Make sure to print CPU even if we crash
-    let res = std::panic::AssertUnwindSafe(launch_flow_stdin_commands(flow))
+    let res = std::panic::AssertUnwindSafe(launch_flow_stdin_commands(drain, flow))
         .catch_unwind()
         .await;
 
@@ -80,42 +80,94 @@ pub async fn run_stdin_commands(flow: impl Future) {
     res.unwrap();
 }
 
-pub async fn launch_flow_stdin_commands(flow: impl Future) {
+/// Runs `flow` while handling the [`ControlMessage`]s sent by Hydro Deploy on stdin.
+///
+/// On [`ControlMessage::Drain`], `drain` is triggered, which closes the network sources. The flow
+/// is expected to finish once it has processed and sent all outstanding data (for example by
+/// running the DFIR graph with `run_until(drain.drained())`), after which
+/// [`ControlMessage::DRAINED`] is printed to report completion. On [`ControlMessage::Terminate`],
+/// the flow is dropped immediately. If stdin is closed (for example because Hydro Deploy exited),
+/// the flow is drained as if [`ControlMessage::Drain`] had been sent, so that it does not keep
+/// running without anything to stop it.
+pub async fn launch_flow_stdin_commands(drain: DrainSignal, flow: impl Future) {
     // TODO(mingwei): convert to use CancellationToken at some point
     // Not trivial: https://github.com/hydro-project/hydro/pull/2495/changes#r2733428502
-    let stop = tokio::sync::oneshot::channel();
-    tokio::task::spawn_blocking(|| {
-        let mut line = String::new();
-        std::io::stdin().read_line(&mut line).unwrap();
-        if line.starts_with("stop") {
-            stop.0.send(()).unwrap();
-        } else {
-            eprintln!("Unexpected stdin input: {:?}", line);
+    let (control_send, control_recv) = tokio::sync::mpsc::unbounded_channel();
+    tokio::task::spawn_blocking(move || {
+        loop {
+            let mut line = String::new();
+            if std::io::stdin().read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            match ControlMessage::parse(&line) {
+                Some(message) => {
+                    let terminate = message == ControlMessage::Terminate;
+                    if control_send.send(message).is_err() || terminate {
+                        break;
+                    }
+                }
+                None => eprintln!("Unexpected stdin input: {:?}", line),
+            }
         }
     });
 
-    tokio::select! {
-        _ = stop.1 => {},
-        _ = flow => {}
+    if run_flow_with_control(&drain, flow, control_recv).await {
+        println!("{}", ControlMessage::DRAINED);
     }
 }
 
-pub async fn init_no_ack_start<T: DeserializeOwned + Default>() -> DeployPorts<T> {
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input).unwrap();
-    let trimmed = input.trim();
+/// Runs `flow` while handling the control messages received from `control`, returning whether it
+/// finished after being drained (see [`launch_flow_stdin_commands`]). Once `control` is closed,
+/// the flow is drained.
+async fn run_flow_with_control(
+    drain: &DrainSignal,
+    flow: impl Future,
+    mut control: tokio::sync::mpsc::UnboundedReceiver<ControlMessage>,
+) -> bool {
+    let mut flow = std::pin::pin!(flow);
+    loop {
+        tokio::select! {
+            message = control.recv() => match message {
+                Some(ControlMessage::Drain) => drain.trigger(),
+                Some(ControlMessage::Terminate) => return false,
+                None => {
+                    drain.trigger();
+                    (&mut flow).await;
+                    break;
+                }
+            },
+            _ = &mut flow => break,
+        }
+    }
 
-    let bind_config = serde_json::from_str::<InitConfig>(trimmed).unwrap();
+    drain.is_draining()
+}
 
-    // config telling other services how to connect to me
-    let mut bind_results: HashMap<String, ServerPort> = HashMap::new();
-    let mut binds = HashMap::new();
-    for (name, config) in bind_config.0 {
-        let bound = config.bind().await;
-        bind_results.insert(name.clone(), bound.server_port());
-        binds.insert(name.clone(), bound);
+pub async fn init_no_ack_start<T: DeserializeOwned + Default>() -> DeployPorts<T> {
+    if let Some(path) = std::env::var_os(INSTANCE_CONFIG_ENV) {
+        return init_from_instance_config(std::path::Path::new(&path)).await;
     }
 
+    // if a port cannot be bound, Hydro Deploy may send a new config with a different port
+    let (bind_results, binds, meta) = loop {
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).unwrap();
+        let trimmed = input.trim();
+
+        let (bind_config, meta, frame) = serde_json::from_str::<InitConfig>(trimmed).unwrap();
+        frame.apply();
+        match bind_all(bind_config).await {
+            Ok((bind_results, binds)) => {
+                break (bind_results, binds, meta.map(|meta| meta.into_owned()));
+            }
+            Err(error) => println!(
+                "{}{}",
+                BindError::PREFIX,
+                serde_json::to_string(&error).unwrap()
+            ),
+        }
+    };
+
     let bind_serialized = serde_json::to_string(&bind_results).unwrap();
     println!("ready: {bind_serialized}");
 
@@ -130,10 +182,79 @@ pub async fn init_no_ack_start<T: DeserializeOwned + Default>() -> DeployPorts<T
         panic!("expected start");
     };
 
+    DeployPorts {
+        ports: RefCell::new(connect_all(connection_defns, binds, false).await),
+        meta: meta
+            .map(|b| serde_json::from_str(&b).unwrap())
+            .unwrap_or_default(),
+        drain: DrainSignal::default(),
+    }
+}
+
+/// Binds every port in `bind_config`, returning the config telling other services how to
+/// connect to each one along with the bound ports. If any port cannot be bound, the ports bound
+/// so far are released.
+async fn bind_all(
+    bind_config: HashMap<String, ServerBindConfig>,
+) -> Result<(HashMap<String, ServerPort>, HashMap<String, BoundServer>), BindError> {
+    let mut bind_results = HashMap::new();
+    let mut binds = HashMap::new();
+    for (name, config) in bind_config {
+        let bound = config.try_bind().await?;
+        bind_results.insert(name.clone(), bound.server_port());
+        binds.insert(name, bound);
+    }
+    Ok((bind_results, binds))
+}
+
+/// Binds and connects the ports described by the [`InstanceConfig`] in the file at `path`,
+/// without any handshake over stdin. Used by programs deployed with their own tooling rather
+/// than Hydro Deploy.
+async fn init_from_instance_config<T: DeserializeOwned + Default>(
+    path: &std::path::Path,
+) -> DeployPorts<T> {
+    let contents = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read instance config {}: {}", path.display(), e));
+    let config = serde_json::from_str::<InstanceConfig>(&contents)
+        .unwrap_or_else(|e| panic!("Failed to parse instance config {}: {}", path.display(), e));
+    config.frame.apply();
+
+    let mut binds = HashMap::new();
+    for (name, bind) in config.bind {
+        binds.insert(name, bind.bind().await);
+    }
+
+    // The other instances are started by other tooling, possibly much later than this one, so
+    // keep retrying until they are listening.
+    DeployPorts {
+        ports: RefCell::new(connect_all(config.connect, binds, true).await),
+        meta: config
+            .meta
+            .map(|b| serde_json::from_str(&b).unwrap())
+            .unwrap_or_default(),
+        drain: DrainSignal::default(),
+    }
+}
+
+/// Connects to every port in `connection_defns` while accepting connections on every port in
+/// `binds`. If `until_listening`, connections are retried until the other side is listening,
+/// rather than for a limited time, see [`ServerPort::connect_until_listening`].
+async fn connect_all(
+    connection_defns: HashMap<String, ServerPort>,
+    binds: HashMap<String, BoundServer>,
+    until_listening: bool,
+) -> HashMap<String, Connection> {
     let (client_conns, server_conns) = futures::join!(
         connection_defns
             .into_iter()
-            .map(|(name, defn)| async move { (name, Connection::AsClient(defn.connect().await)) })
+            .map(|(name, defn)| async move {
+                let connection = if until_listening {
+                    defn.connect_until_listening().await
+                } else {
+                    defn.connect().await
+                };
+                (name, Connection::AsClient(connection))
+            })
             .collect::<FuturesUnordered<_>>()
             .collect::<Vec<_>>(),
         binds
@@ -145,15 +266,7 @@ pub async fn init_no_ack_start<T: DeserializeOwned + Default>() -> DeployPorts<T
             .collect::<Vec<_>>()
     );
 
-    let all_connected = client_conns.into_iter().chain(server_conns).collect();
-
-    DeployPorts {
-        ports: RefCell::new(all_connected),
-        meta: bind_config
-            .1
-            .map(|b| serde_json::from_str(&b).unwrap())
-            .unwrap_or_default(),
-    }
+    client_conns.into_iter().chain(server_conns).collect()
 }
 
 pub async fn init<T: DeserializeOwned + Default>() -> DeployPorts<T> {
@@ -163,3 +276,69 @@ pub async fn init<T: DeserializeOwned + Default>() -> DeployPorts<T> {
 
     ret
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A flow which finishes once it is drained, like a DFIR graph run with
+    /// `run_until(drain.drained())`.
+    fn drainable_flow(drain: &DrainSignal) -> impl Future<Output = ()> + use<> {
+        let drain = drain.clone();
+        async move { drain.drained().await }
+    }
+
+    #[test]
+    fn drain_finishes_flow() {
+        let drain = DrainSignal::default();
+        let (control_send, control_recv) = tokio::sync::mpsc::unbounded_channel();
+        control_send.send(ControlMessage::Drain).unwrap();
+
+        let drained = tokio_test::block_on(run_flow_with_control(
+            &drain,
+            drainable_flow(&drain),
+            control_recv,
+        ));
+        assert!(drained);
+        // The control stream is still open, so the flow finished because it was drained.
+        drop(control_send);
+    }
+
+    #[test]
+    fn closed_stdin_drains_flow() {
+        let drain = DrainSignal::default();
+        let (control_send, control_recv) = tokio::sync::mpsc::unbounded_channel();
+        drop(control_send);
+
+        let drained = tokio_test::block_on(run_flow_with_control(
+            &drain,
+            drainable_flow(&drain),
+            control_recv,
+        ));
+        assert!(drained);
+    }
+
+    #[test]
+    fn terminate_drops_flow() {
+        let drain = DrainSignal::default();
+        let (control_send, control_recv) = tokio::sync::mpsc::unbounded_channel();
+        control_send.send(ControlMessage::Terminate).unwrap();
+
+        let drained = tokio_test::block_on(run_flow_with_control(
+            &drain,
+            std::future::pending::<()>(),
+            control_recv,
+        ));
+        assert!(!drained);
+        assert!(!drain.is_draining());
+    }
+
+    #[test]
+    fn finished_flow_is_not_drained() {
+        let drain = DrainSignal::default();
+        let (_control_send, control_recv) = tokio::sync::mpsc::unbounded_channel();
+
+        let drained = tokio_test::block_on(run_flow_with_control(&drain, async {}, control_recv));
+        assert!(!drained);
+    }
+}
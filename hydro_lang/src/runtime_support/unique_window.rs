@@ -0,0 +1,105 @@
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Deduplication state which only remembers recently seen values, evicting the oldest values once
+/// there are more than `capacity` of them or they were first seen more than `max_age` ago.
+///
+/// Used by [`Stream::unique_within`](crate::live_collections::stream::Stream::unique_within).
+pub struct WindowedUnique<T> {
+    capacity: Option<usize>,
+    max_age: Option<Duration>,
+    seen: HashSet<T>,
+    /// The remembered values, ordered by when they were first seen.
+    order: VecDeque<(T, Instant)>,
+}
+
+impl<T: Hash + Eq + Clone> WindowedUnique<T> {
+    /// Remembers the `capacity` most recently seen distinct values.
+    pub fn with_count(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "the deduplication window must hold at least one value"
+        );
+        Self::new(Some(capacity), None)
+    }
+
+    /// Remembers the values first seen within the last `max_age`.
+    pub fn with_duration(max_age: Duration) -> Self {
+        Self::new(None, Some(max_age))
+    }
+
+    fn new(capacity: Option<usize>, max_age: Option<Duration>) -> Self {
+        Self {
+            capacity,
+            max_age,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Records `item`, returning `true` if it is not a duplicate of a value in the window.
+    pub fn insert(&mut self, item: &T) -> bool {
+        self.insert_at(item, Instant::now())
+    }
+
+    /// [`Self::insert`], with `item` seen at `now`.
+    fn insert_at(&mut self, item: &T, now: Instant) -> bool {
+        if let Some(max_age) = self.max_age {
+            while let Some((_, first_seen)) = self.order.front()
+                && now.duration_since(*first_seen) > max_age
+            {
+                self.evict_oldest();
+            }
+        }
+
+        if self.seen.contains(item) {
+            return false;
+        }
+
+        if let Some(capacity) = self.capacity
+            && self.order.len() >= capacity
+        {
+            self.evict_oldest();
+        }
+        self.seen.insert(item.clone());
+        self.order.push_back((item.clone(), now));
+        true
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((oldest, _)) = self.order.pop_front() {
+            self.seen.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::WindowedUnique;
+
+    #[test]
+    fn count_window_evicts_oldest() {
+        let mut unique = WindowedUnique::with_count(2);
+        let passed = [1, 2, 1, 3, 1, 2]
+            .into_iter()
+            .filter(|item| unique.insert(item))
+            .collect::<Vec<_>>();
+        // `1` is evicted by `3`, and then `2` by `1`.
+        assert_eq!(vec![1, 2, 3, 1, 2], passed);
+    }
+
+    #[test]
+    fn duration_window_evicts_expired() {
+        let mut unique = WindowedUnique::with_duration(Duration::from_secs(10));
+        let start = Instant::now();
+        let passed = [(1, 0), (2, 5), (1, 10), (1, 11), (2, 12), (2, 14)]
+            .into_iter()
+            .filter(|(item, secs)| unique.insert_at(item, start + Duration::from_secs(*secs)))
+            .collect::<Vec<_>>();
+        // `1` expires after 10 seconds (and is remembered again from 11), `2` after 15.
+        assert_eq!(vec![(1, 0), (2, 5), (1, 11)], passed);
+    }
+}
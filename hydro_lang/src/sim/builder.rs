@@ -1,16 +1,18 @@
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use dfir_lang::graph::FlatGraphBuilder;
-use proc_macro2::Span;
-use quote::ToTokens;
+use proc_macro2::{Span, TokenStream};
+use quote::{ToTokens, quote};
 use syn::parse_quote;
 
 use crate::compile::builder::{HandoffId, StmtId};
 use crate::compile::ir::{
-    CollectionKind, DebugExpr, DfirBuilder, HydroIrOpMetadata, KeyedSingletonBoundKind,
-    StreamOrder, StreamRetry,
+    ClaimedAlgebra, CollectionKind, DebugExpr, DebugType, DfirBuilder, HydroIrOpMetadata,
+    KeyedSingletonBoundKind, StreamOrder, StreamRetry, add_raw_dfir,
 };
+use crate::location::LocationKey;
 use crate::location::dynamic::LocationId;
+use crate::sim::network_model::LinkModel;
 use crate::staging_util::get_this_crate;
 
 /// A builder for DFIR graphs used in simulations.
@@ -38,7 +40,12 @@ pub struct SimBuilder {
     pub next_hoff_id: crate::Counter<HandoffId>,
     pub test_safety_only: bool,
     pub skip_consistency_assertions: bool,
+    pub check_manual_proofs: bool,
     pub channel_tables: BTreeMap<u32, syn::Ident>,
+    /// Link models for network edges between specific locations, keyed by `(from, to)`.
+    pub link_models: HashMap<(LocationKey, LocationKey), LinkModel>,
+    /// Link model for network edges without a specific model, if any.
+    pub default_link_model: Option<LinkModel>,
 }
 
 impl SimBuilder {
@@ -63,6 +70,79 @@ impl SimBuilder {
         }
     }
 
+    /// Wraps `deliver`, which sends the element `v` over the network edge `tag_id`, so that the
+    /// element is held in flight according to the edge's [`LinkModel`]. Edges without a model
+    /// deliver immediately, and `deliver` is returned unchanged. If `ordered`, elements from each
+    /// sender arrive in the order they were sent.
+    #[expect(clippy::too_many_arguments, reason = "internal code")]
+    fn modeled_send(
+        &mut self,
+        from: &LocationId,
+        to: &LocationId,
+        sink: &syn::Expr,
+        tag_id: StmtId,
+        is_embedded: bool,
+        ordered: bool,
+        deliver: TokenStream,
+    ) -> TokenStream {
+        let Some(model) = self
+            .link_models
+            .get(&(from.root().key(), to.root().key()))
+            .or(self.default_link_model.as_ref())
+        else {
+            return deliver;
+        };
+
+        let root = get_this_crate();
+        let network_ident =
+            syn::Ident::new(&format!("__hydro_network_{tag_id}"), Span::call_site());
+        self.extra_stmts_global.push(parse_quote! {
+            let #network_ident = __hydro_network.clone();
+        });
+        if let LocationId::Cluster(_) = from {
+            self.extra_stmts_cluster
+                .entry(from.clone())
+                .or_default()
+                .push(parse_quote! {
+                    let #network_ident = #network_ident.clone();
+                });
+        }
+
+        let (min_latency_nanos, max_latency_nanos) = model.latency.bounds_nanos();
+        let bandwidth = match model.bandwidth_bytes_per_sec {
+            Some(bandwidth) => quote!(Some(#bandwidth)),
+            None => quote!(None),
+        };
+        let edge = tag_id.into_inner();
+        let sender = if let LocationId::Cluster(_) = from {
+            quote!(Some(__current_cluster_id))
+        } else {
+            quote!(None)
+        };
+        let size = if is_embedded {
+            quote!(::std::mem::size_of_val(&v))
+        } else {
+            quote!(v.len())
+        };
+
+        quote! {{
+            let __size = #size;
+            let #sink = #sink.clone();
+            #network_ident.send(
+                #root::sim::runtime::SimLinkParams {
+                    edge: #edge,
+                    min_latency_nanos: #min_latency_nanos,
+                    max_latency_nanos: #max_latency_nanos,
+                    bandwidth_bytes_per_sec: #bandwidth,
+                    ordered: #ordered,
+                },
+                #sender,
+                __size,
+                ::std::boxed::Box::new(move || { #deliver; }),
+            );
+        }}
+    }
+
     fn add_extra_stmt_internal(&mut self, location: &LocationId, stmt: syn::Stmt) {
         match location {
             LocationId::Process(_) => {
@@ -1474,6 +1554,9 @@ impl DfirBuilder for SimBuilder {
             },
         }
 
+        // TCP delivers each sender's messages in order, even when their latencies vary.
+        let ordered = matches!(networking_info, NetworkingInfo::Tcp { .. });
+
         let root = get_this_crate();
 
         // For embedded (external) serialization, the raw payload type flows across the in-memory
@@ -1498,10 +1581,20 @@ impl DfirBuilder for SimBuilder {
                     let (#sink, #source) = __root_dfir_rs::util::unbounded_channel::<#payload>();
                 });
 
+                let send = self.modeled_send(
+                    from,
+                    to,
+                    &sink,
+                    tag_id,
+                    external_element_type.is_some(),
+                    ordered,
+                    quote!(#sink.send(v).unwrap()),
+                );
+
                 if let Some(serialize_pipeline) = serialize {
                     self.get_dfir_mut(from).add_dfir(
                         parse_quote! {
-                            #input_ident -> map(#serialize_pipeline) -> for_each(|v| #sink.send(v).unwrap());
+                            #input_ident -> map(#serialize_pipeline) -> for_each(|v| #send);
                         },
                         None,
                         Some(&format!("send{}", tag_id)),
@@ -1509,7 +1602,7 @@ impl DfirBuilder for SimBuilder {
                 } else {
                     self.get_dfir_mut(from).add_dfir(
                         parse_quote! {
-                            #input_ident -> for_each(|v| #sink.send(v).unwrap());
+                            #input_ident -> for_each(|v| #send);
                         },
                         None,
                         Some(&format!("send{}", tag_id)),
@@ -1546,10 +1639,20 @@ impl DfirBuilder for SimBuilder {
                         let #sink = #sink.clone();
                     });
 
+                let send = self.modeled_send(
+                    from,
+                    to,
+                    &sink,
+                    tag_id,
+                    external_element_type.is_some(),
+                    ordered,
+                    quote!(#sink.send((#root::__staged::location::TaglessMemberId::from_raw_id(__current_cluster_id), v)).unwrap()),
+                );
+
                 if let Some(serialize_pipeline) = serialize {
                     self.get_dfir_mut(from).add_dfir(
                         parse_quote! {
-                            #input_ident -> map(#serialize_pipeline) -> for_each(|v| #sink.send((#root::__staged::location::TaglessMemberId::from_raw_id(__current_cluster_id), v)).unwrap());
+                            #input_ident -> map(#serialize_pipeline) -> for_each(|v| #send);
                         },
                         None,
                         Some(&format!("send{}", tag_id)),
@@ -1557,7 +1660,7 @@ impl DfirBuilder for SimBuilder {
                 } else {
                     self.get_dfir_mut(from).add_dfir(
                         parse_quote! {
-                            #input_ident -> for_each(|v| #sink.send((#root::__staged::location::TaglessMemberId::from_raw_id(__current_cluster_id), v)).unwrap());
+                            #input_ident -> for_each(|v| #send);
                         },
                         None,
                         Some(&format!("send{}", tag_id)),
@@ -1606,10 +1709,20 @@ impl DfirBuilder for SimBuilder {
                         };
                     });
 
+                let send = self.modeled_send(
+                    from,
+                    to,
+                    &sink,
+                    tag_id,
+                    external_element_type.is_some(),
+                    ordered,
+                    quote!((#sink.borrow())[#root::__staged::location::TaglessMemberId::get_raw_id(&target_member_id) as usize].send(v).unwrap()),
+                );
+
                 if let Some(serialize_pipeline) = serialize {
                     self.get_dfir_mut(from).add_dfir(
                         parse_quote! {
-                            #input_ident -> map(#serialize_pipeline) -> for_each(|(target_member_id, v)| (#sink.borrow())[#root::__staged::location::TaglessMemberId::get_raw_id(&target_member_id) as usize].send(v).unwrap());
+                            #input_ident -> map(#serialize_pipeline) -> for_each(|(target_member_id, v)| #send);
                         },
                         None,
                         Some(&format!("send{}", tag_id)),
@@ -1617,7 +1730,7 @@ impl DfirBuilder for SimBuilder {
                 } else {
                     self.get_dfir_mut(from).add_dfir(
                         parse_quote! {
-                            #input_ident -> for_each(|(target_member_id, v)| (#sink.borrow())[#root::__staged::location::TaglessMemberId::get_raw_id(&target_member_id) as usize].send(v).unwrap());
+                            #input_ident -> for_each(|(target_member_id, v)| #send);
                         },
                         None,
                         Some(&format!("send{}", tag_id)),
@@ -1673,10 +1786,20 @@ impl DfirBuilder for SimBuilder {
                         };
                     });
 
+                let send = self.modeled_send(
+                    from,
+                    to,
+                    &sink,
+                    tag_id,
+                    external_element_type.is_some(),
+                    ordered,
+                    quote!((#sink.borrow())[#root::__staged::location::TaglessMemberId::get_raw_id(&target_member_id) as usize].send((#root::__staged::location::TaglessMemberId::from_raw_id(__current_cluster_id), v)).unwrap()),
+                );
+
                 if let Some(serialize_pipeline) = serialize {
                     self.get_dfir_mut(from).add_dfir(
                         parse_quote! {
-                            #input_ident -> map(#serialize_pipeline) -> for_each(|(target_member_id, v)| (#sink.borrow())[#root::__staged::location::TaglessMemberId::get_raw_id(&target_member_id) as usize].send((#root::__staged::location::TaglessMemberId::from_raw_id(__current_cluster_id), v)).unwrap());
+                            #input_ident -> map(#serialize_pipeline) -> for_each(|(target_member_id, v)| #send);
                         },
                         None,
                         Some(&format!("send{}", tag_id)),
@@ -1684,7 +1807,7 @@ impl DfirBuilder for SimBuilder {
                 } else {
                     self.get_dfir_mut(from).add_dfir(
                         parse_quote! {
-                            #input_ident -> for_each(|(target_member_id, v)| (#sink.borrow())[#root::__staged::location::TaglessMemberId::get_raw_id(&target_member_id) as usize].send((#root::__staged::location::TaglessMemberId::from_raw_id(__current_cluster_id), v)).unwrap());
+                            #input_ident -> for_each(|(target_member_id, v)| #send);
                         },
                         None,
                         Some(&format!("send{}", tag_id)),
@@ -1937,6 +2060,75 @@ impl DfirBuilder for SimBuilder {
         Some(out_ident)
     }
 
+    fn check_claimed_algebra(
+        &mut self,
+        acc: TokenStream,
+        in_kind: &CollectionKind,
+        out_kind: &CollectionKind,
+        op_meta: &HydroIrOpMetadata,
+        is_reduce: bool,
+    ) -> TokenStream {
+        if !self.check_manual_proofs {
+            return acc;
+        }
+
+        let CollectionKind::Stream { element_type, .. } = in_kind else {
+            return acc;
+        };
+        let (CollectionKind::Singleton {
+            element_type: state_type,
+            ..
+        }
+        | CollectionKind::Optional {
+            element_type: state_type,
+            ..
+        }) = out_kind
+        else {
+            return acc;
+        };
+
+        let (op_location, line, caret) = location_for_op(op_meta);
+        let root = get_this_crate();
+        let ClaimedAlgebra {
+            commutative,
+            associative,
+            idempotent,
+        } = op_meta.claimed_algebra;
+
+        // The claims can only be checked if the state and inputs can be cloned and compared,
+        // which `ClaimChecker` selects by autoref specialization.
+        let wrapped = if is_reduce {
+            quote! {
+                checker.wrap_reduce(
+                    #acc,
+                    #commutative,
+                    #associative,
+                    #idempotent,
+                    (#op_location, #line, #caret),
+                    #root::__maybe_debug__!(#element_type),
+                )
+            }
+        } else {
+            quote! {
+                checker.wrap_fold(
+                    #acc,
+                    #commutative,
+                    #idempotent,
+                    (#op_location, #line, #caret),
+                    #root::__maybe_debug__!(#element_type),
+                )
+            }
+        };
+
+        quote! {{
+            use #root::sim::runtime::{CheckClaims as _, SkipClaims as _};
+            let checker = &#root::sim::runtime::ClaimChecker::<#state_type, #element_type>(
+                ::std::marker::PhantomData,
+            );
+            #wrapped
+        }}
+    }
+
     fn assert_is_consistent(
         &mut self,
         trusted: bool,
@@ -1975,6 +2167,62 @@ impl DfirBuilder for SimBuilder {
         );
     }
 
+    fn map_async_cached(
+        &mut self,
+        location: &LocationId,
+        in_ident: syn::Ident,
+        out_ident: &syn::Ident,
+        limits: &DebugExpr,
+        f: TokenStream,
+        sim_resolver: Option<&DebugExpr>,
+        operator_tag: Option<&str>,
+    ) {
+        // Lookups are resolved one at a time, in order, so that simulations are reproducible.
+        let dfir = if let Some(resolver) = sim_resolver {
+            parse_quote! {
+                #out_ident = #in_ident -> map({
+                    let resolver = #resolver;
+                    move |key| resolver(&key)
+                });
+            }
+        } else {
+            let root = get_this_crate();
+            let lifetime = self.cross_tick_state_lifetime(location);
+            parse_quote! {
+                #out_ident = #in_ident
+                    -> scan::<#lifetime>(
+                        || #root::runtime_support::async_cache::AsyncCache::new(#limits, #f),
+                        |cache: &mut _, key| Some(cache.lookup(key)),
+                    )
+                    -> resolve_futures_blocking();
+            }
+        };
+
+        self.get_dfir_mut(location)
+            .add_dfir(dfir, None, operator_tag);
+    }
+
+    fn raw_dfir(
+        &mut self,
+        location: &LocationId,
+        in_ident: syn::Ident,
+        in_type: &DebugType,
+        out_ident: &syn::Ident,
+        out_type: &DebugType,
+        syntax: &str,
+        operator_tag: Option<&str>,
+    ) {
+        add_raw_dfir(
+            self.get_dfir_mut(location),
+            in_ident,
+            in_type,
+            out_ident,
+            out_type,
+            syntax,
+            operator_tag,
+        );
+    }
+
     fn create_versioned_network_fork(
         &mut self,
         channel_id: u32,
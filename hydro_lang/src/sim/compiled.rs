@@ -21,13 +21,14 @@ macro_rules! abort_assert {
 
 use core::{fmt, panic};
 use std::cell::{Cell, RefCell};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::fmt::Debug;
 use std::panic::RefUnwindSafe;
 use std::path::Path;
 use std::pin::{Pin, pin};
 use std::rc::Rc;
 use std::task::ready;
+use std::time::Duration;
 
 use bytes::Bytes;
 use colored::Colorize;
@@ -41,13 +42,15 @@ use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::{Mutex, Notify};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
-use super::runtime::{Hooks, InlineHooks};
+use super::runtime::{Hooks, InlineHooks, SimNetwork};
 use super::{SimClusterReceiver, SimClusterSender, SimReceiver, SimSender};
 use crate::compile::builder::ExternalPortId;
+use crate::compile::ir::{StreamOrder, StreamRetry};
 use crate::live_collections::stream::{ExactlyOnce, NoOrder, Ordering, Retries, TotalOrder};
 use crate::location::dynamic::LocationId;
 use crate::sim::graph::{SimExternalPort, SimExternalPortRegistry};
 use crate::sim::runtime::SimHook;
+use crate::telemetry::meter::MeterSnapshot;
 
 struct QuiescenceState {
     /// Set to true when the scheduler reaches quiescence; reset to false when new input is sent.
@@ -75,10 +78,15 @@ impl QuiescenceState {
         self.quiescence_notify.notified()
     }
 
-    /// Enter quiescence and wait for new input before continuing.
-    async fn wait_for_resume(&self) {
+    /// Enter quiescence without waiting for new input, waking receivers waiting for data.
+    fn pause(&self) {
         self.quiescent.set(true);
         self.quiescence_notify.notify_waiters();
+    }
+
+    /// Enter quiescence and wait for new input before continuing.
+    async fn wait_for_resume(&self) {
+        self.pause();
         self.resume_notify.notified().await;
         self.quiescent.set(false);
     }
@@ -92,18 +100,56 @@ struct SimConnections {
         HashMap<SimExternalPort, HashMap<u32, Rc<Mutex<UnboundedReceiverStream<Bytes>>>>>,
     external_registered: HashMap<ExternalPortId, SimExternalPort>,
     quiescence: Rc<QuiescenceState>,
+    network: Rc<SimNetwork>,
+    /// Where to record the outputs of `TotalOrder` + `ExactlyOnce` receivers, when checking
+    /// determinism.
+    deterministic_outputs: Option<Rc<RefCell<DeterministicOutputs>>>,
+}
+
+/// The serialized messages received from each deterministic output port (and cluster member),
+/// see [`CompiledSim::check_determinism`].
+type DeterministicOutputs = BTreeMap<(ExternalPortId, Option<u32>), Vec<Bytes>>;
+
+/// The order in which the scheduler runs async DFIRs and considers ready ticks, which does not
+/// depend on the fuzzer's decisions.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InternalOrder {
+    /// Async DFIRs run in the order they are loaded, and ticks are considered in the order they
+    /// become ready.
+    Default,
+    /// Like [`InternalOrder::Default`], but ready ticks and observations are sorted by location
+    /// before the fuzzer chooses one, so that the choice does not depend on the async DFIR order.
+    Canonical,
+    /// Like [`InternalOrder::Canonical`], but async DFIRs run in reverse order.
+    Reversed,
 }
 
 tokio::task_local! {
     static CURRENT_SIM_CONNECTIONS: RefCell<SimConnections>;
 }
 
+/// Returns the virtual time elapsed in the current simulation instance, which advances as
+/// messages are delivered over links with a [`LinkModel`](super::network_model::LinkModel).
+///
+/// Without any link models, the virtual time is always zero.
+///
+/// # Panics
+/// If called outside of a simulation instance.
+pub fn virtual_time() -> Duration {
+    CURRENT_SIM_CONNECTIONS.with(|connections| connections.borrow().network.now())
+}
+
 /// A handle to a compiled Hydro simulation, which can be instantiated and run.
 pub struct CompiledSim {
     pub(super) _path: TempPath,
     pub(super) lib: Library,
     pub(super) externals_port_registry: SimExternalPortRegistry,
     pub(super) unit_test_fuzz_iterations: usize,
+    /// Whether the fuzzer chooses the latency of messages over links with a latency range.
+    pub(super) explore_latencies: bool,
+    /// The tags of operators (set with `ir_node_named`), on which [`SimStepper`] breakpoints can
+    /// be set.
+    pub(super) tags: BTreeSet<String>,
 }
 
 #[sealed::sealed]
@@ -137,6 +183,7 @@ type SimLoaded<'a> = libloading::Symbol<
         external_in: &mut HashMap<usize, UnboundedSender<Bytes>>,
         cluster_external_out: &mut HashMap<usize, HashMap<u32, UnboundedReceiverStream<Bytes>>>,
         cluster_external_in: &mut HashMap<usize, HashMap<u32, UnboundedSender<Bytes>>>,
+        network: Rc<SimNetwork>,
         println_handler: fn(fmt::Arguments<'_>),
         eprintln_handler: fn(fmt::Arguments<'_>),
     ) -> (
@@ -173,10 +220,25 @@ impl CompiledSim {
                 externals_port_registry: self.externals_port_registry.clone(),
                 dylib_result: None,
                 log,
+                internal_order: InternalOrder::Default,
+                deterministic_outputs: None,
+                explore_latencies: self.explore_latencies,
             }),
         )
     }
 
+    /// Returns the metrics recorded by the [`Stream::metered`](crate::live_collections::stream::Stream::metered)
+    /// meter with the given name in the most recently created instance of this simulation, or
+    /// `None` if no such meter has been registered.
+    ///
+    /// Meters are reset whenever a new instance is created, so when using [`Self::fuzz`] or
+    /// [`Self::exhaustive`] the result only reflects the last execution.
+    pub fn meter(&self, name: &str) -> Option<MeterSnapshot> {
+        let func: libloading::Symbol<unsafe extern "Rust" fn() -> BTreeMap<String, MeterSnapshot>> =
+            unsafe { self.lib.get(b"__hydro_meters").unwrap() };
+        unsafe { func() }.remove(name)
+    }
+
     /// Uses a fuzzing strategy to explore possible executions of the simulation. The provided
     /// closure will be repeatedly executed with instances of the Hydro program where the
     /// batching boundaries, order of messages, and retries are varied.
@@ -325,6 +387,43 @@ impl CompiledSim {
         });
     }
 
+    /// Runs a single instance of the simulation one step at a time, under the control of the
+    /// given closure, which is useful for debugging protocol logic. See [`SimStepper`].
+    ///
+    /// Non-deterministic decisions are made deterministically, so repeated runs with the same
+    /// inputs step through the same execution. Logging is always enabled.
+    pub fn interactive(&self, thunk: impl AsyncFnOnce(SimStepper) + RefUnwindSafe) {
+        let tags = self.tags.clone();
+        let take_breakpoint_hits: libloading::Symbol<
+            unsafe extern "Rust" fn() -> BTreeSet<String>,
+        > = unsafe { self.lib.get(b"__hydro_breakpoint_hits").unwrap() };
+        let take_breakpoint_hits = *take_breakpoint_hits;
+        self.with_instance(|instance| {
+            bolero::bolero_engine::any::scope::with(
+                // Without any input, a forced driver always makes the smallest decision.
+                Box::new(bolero::bolero_engine::driver::object::Object(
+                    bolero::bolero_engine::driver::bytes::Driver::new(
+                        vec![],
+                        &bolero::bolero_engine::driver::Options::default()
+                            .with_driver_mode(bolero::bolero_engine::driver::DriverMode::Forced),
+                    ),
+                )),
+                || {
+                    tokio::runtime::Builder::new_current_thread()
+                        .build()
+                        .unwrap()
+                        .block_on(async {
+                            instance
+                                .run_without_launching(async move |instance| {
+                                    thunk(instance.into_stepper(tags, take_breakpoint_hits)).await
+                                })
+                                .await
+                        })
+                },
+            )
+        });
+    }
+
     /// Exhaustively searches all possible executions of the simulation. The provided
     /// closure will be repeatedly executed with instances of the Hydro program where the
     /// batching boundaries, order of messages, and retries are varied.
@@ -388,6 +487,105 @@ impl CompiledSim {
 
         count
     }
+
+    /// Checks that the outputs of the simulation which are marked as deterministic (received
+    /// through a [`SimReceiver`] or [`SimClusterReceiver`] with [`TotalOrder`] and
+    /// [`ExactlyOnce`]) do not depend on the internal order in which the simulator runs
+    /// locations.
+    ///
+    /// For each of several random schedules, the provided closure is executed twice with the same
+    /// batching boundaries, message orders, and inputs drawn with [`bolero::any`], but with the
+    /// locations of the program stepped in a different order. The serialized messages the closure
+    /// receives from each deterministic output must be identical in both executions, which
+    /// catches non-determinism that is not declared in the types of the program, such as
+    /// iterating over a `HashMap` or an incorrect `assume_ordering`.
+    ///
+    /// # Panics
+    /// If the deterministic outputs of the two executions differ, or the closure panics.
+    pub fn check_determinism(&self, thunk: impl AsyncFn() + RefUnwindSafe) {
+        self.with_instantiator(
+            |instantiator| {
+                bolero::test(bolero::TargetLocation {
+                    package_name: "",
+                    manifest_dir: "",
+                    module_path: "",
+                    file: ".",
+                    line: 0,
+                    item_path: "<unknown>::__bolero_item_path__",
+                    test_name: None,
+                })
+                .with_type::<Vec<u8>>()
+                .with_iterations(self.unit_test_fuzz_iterations)
+                .for_each(|bytes| {
+                    let run = |internal_order| {
+                        let mut instance = instantiator();
+                        instance.internal_order = internal_order;
+                        let outputs = Rc::new(RefCell::new(DeterministicOutputs::new()));
+                        instance.deterministic_outputs = Some(outputs.clone());
+
+                        bolero::bolero_engine::any::scope::with(
+                            Box::new(bolero::bolero_engine::driver::object::Object(
+                                bolero::bolero_engine::driver::bytes::Driver::new(
+                                    bytes.clone(),
+                                    &Default::default(),
+                                ),
+                            )),
+                            || {
+                                tokio::runtime::Builder::new_current_thread()
+                                    .build()
+                                    .unwrap()
+                                    .block_on(async { instance.run(&thunk).await })
+                            },
+                        );
+
+                        outputs.take()
+                    };
+
+                    let canonical = run(InternalOrder::Canonical);
+                    let reversed = run(InternalOrder::Reversed);
+                    if let Some(difference) = describe_difference(&canonical, &reversed) {
+                        panic!("non-deterministic output: {}", difference);
+                    }
+                })
+            },
+            false,
+        );
+    }
+}
+
+/// Describes the first message which differs between the deterministic outputs of two executions,
+/// or returns `None` if they are identical.
+fn describe_difference(
+    first: &DeterministicOutputs,
+    second: &DeterministicOutputs,
+) -> Option<String> {
+    let ports = first.keys().chain(second.keys()).collect::<BTreeSet<_>>();
+    for port in ports {
+        let (port_id, member_id) = port;
+        let first_messages = first.get(port).map(Vec::as_slice).unwrap_or_default();
+        let second_messages = second.get(port).map(Vec::as_slice).unwrap_or_default();
+        if first_messages == second_messages {
+            continue;
+        }
+
+        let index = first_messages
+            .iter()
+            .zip(second_messages)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let member = member_id
+            .map(|id| format!(" (cluster member {})", id))
+            .unwrap_or_default();
+        return Some(format!(
+            "{:?}{} diverged at message {}: {:?} vs. {:?}",
+            port_id,
+            member,
+            index,
+            first_messages.get(index),
+            second_messages.get(index),
+        ));
+    }
+    None
 }
 
 // This must be a tuple because it is referenced from generated code in `graph.rs`.
@@ -405,6 +603,9 @@ pub struct CompiledSimInstance<'a> {
     externals_port_registry: SimExternalPortRegistry,
     dylib_result: Option<DylibResult>,
     log: bool,
+    internal_order: InternalOrder,
+    deterministic_outputs: Option<Rc<RefCell<DeterministicOutputs>>>,
+    explore_latencies: bool,
 }
 
 impl<'a> CompiledSimInstance<'a> {
@@ -427,6 +628,8 @@ impl<'a> CompiledSimInstance<'a> {
         let mut cluster_external_in: HashMap<usize, HashMap<u32, UnboundedSender<Bytes>>> =
             HashMap::new();
 
+        let network = Rc::new(SimNetwork::default());
+
         let dylib_result = unsafe {
             (self.func)(
                 colored::control::SHOULD_COLORIZE.should_colorize(),
@@ -434,6 +637,7 @@ impl<'a> CompiledSimInstance<'a> {
                 &mut external_in,
                 &mut cluster_external_out,
                 &mut cluster_external_in,
+                network.clone(),
                 if self.log {
                     println_handler
                 } else {
@@ -504,6 +708,8 @@ impl<'a> CompiledSimInstance<'a> {
                     cluster_output_receivers,
                     external_registered: self.externals_port_registry.registered.clone(),
                     quiescence: quiescence.clone(),
+                    network,
+                    deterministic_outputs: self.deterministic_outputs.clone(),
                 }),
                 async move {
                     thunk(self).await;
@@ -528,22 +734,56 @@ impl<'a> CompiledSimInstance<'a> {
     }
 
     fn schedule_with_maybe_logger<W: std::io::Write>(
-        mut self,
+        self,
         log_override: Option<W>,
     ) -> impl use<W> + Future<Output = ()> {
-        let (async_dfirs, tick_dfirs, hooks, inline_hooks) = self.dylib_result.take().unwrap();
+        let log = if self.log {
+            if let Some(w) = log_override {
+                LogKind::Custom(w)
+            } else {
+                LogKind::Stderr
+            }
+        } else {
+            LogKind::Null
+        };
+
+        let mut launched = self.into_launched(log);
+        async move { launched.scheduler().await }
+    }
+
+    /// Returns a handle to this simulation which runs it one step at a time, see
+    /// [`CompiledSim::interactive`].
+    fn into_stepper(
+        self,
+        tags: BTreeSet<String>,
+        take_breakpoint_hits: unsafe extern "Rust" fn() -> BTreeSet<String>,
+    ) -> SimStepper {
+        SimStepper {
+            launched: self.into_launched(LogKind::Custom(Vec::new())),
+            tags,
+            take_breakpoint_hits,
+            breakpoints: BTreeSet::new(),
+            trace: None,
+        }
+    }
+
+    fn into_launched<W: std::io::Write>(mut self, log: LogKind<W>) -> LaunchedSim<W> {
+        let (mut async_dfirs, tick_dfirs, hooks, inline_hooks) = self.dylib_result.take().unwrap();
+        if self.internal_order == InternalOrder::Reversed {
+            async_dfirs.reverse();
+        }
 
         let not_ready_observation = async_dfirs
             .iter()
             .map(|(lid, c_id, _)| (serde_json::from_str(lid).unwrap(), *c_id))
             .collect();
 
-        let quiescence = CURRENT_SIM_CONNECTIONS.with(|connections| {
+        let (quiescence, network) = CURRENT_SIM_CONNECTIONS.with(|connections| {
             let connections = connections.borrow();
-            connections.quiescence.clone()
+            (connections.quiescence.clone(), connections.network.clone())
         });
 
-        let mut launched = LaunchedSim {
+        LaunchedSim {
             async_dfirs: async_dfirs
                 .into_iter()
                 .map(|(lid, c_id, dfir)| (serde_json::from_str(lid).unwrap(), c_id, dfir))
@@ -563,22 +803,161 @@ impl<'a> CompiledSimInstance<'a> {
                 .into_iter()
                 .map(|((lid, cid), hs)| ((serde_json::from_str(lid).unwrap(), cid), hs))
                 .collect(),
-            log: if self.log {
-                if let Some(w) = log_override {
-                    LogKind::Custom(w)
-                } else {
-                    LogKind::Stderr
-                }
-            } else {
-                LogKind::Null
-            },
+            log,
             quiescence,
+            network,
+            sort_ready: self.internal_order != InternalOrder::Default,
+            explore_latencies: self.explore_latencies,
+        }
+    }
+}
+
+/// A single step of an interactive simulation, see [`SimStepper::step`].
+#[derive(Clone, Debug)]
+pub struct SimStep {
+    /// The location that was stepped: a tick which was run, or a top-level location whose
+    /// non-deterministic decisions (such as an `assume_ordering`) were resolved.
+    pub location: LocationId,
+    /// The cluster member that was stepped, if the location is within a cluster.
+    pub cluster_member: Option<u32>,
+    /// The decisions made in this step, such as the items released into a batch, in the same
+    /// format as the simulation log.
+    pub log: String,
+    /// The tags of the operators (set with `ir_node_named`) which output elements in this step.
+    pub tagged_operators: BTreeSet<String>,
+}
+
+/// A simulation which is advanced one step at a time, to inspect how a Hydro program processes
+/// its inputs. Created by [`CompiledSim::interactive`].
+///
+/// Each step runs the top-level logic of every location until it stops making progress, and then
+/// runs a single tick (or resolves the non-deterministic decisions at a single top-level
+/// location). Between steps, [`SimReceiver`]s yield the messages which have been emitted so far
+/// and then end, rather than waiting for more.
+pub struct SimStepper {
+    launched: LaunchedSim<Vec<u8>>,
+    /// The tags of operators set with `ir_node_named`.
+    tags: BTreeSet<String>,
+    /// Returns the tags of the operators which output elements since it was last called, from
+    /// the loaded simulation library, which outlives the stepper.
+    take_breakpoint_hits: unsafe extern "Rust" fn() -> BTreeSet<String>,
+    breakpoints: BTreeSet<String>,
+    /// The rendered steps taken since [`Self::record_trace`], if recording.
+    trace: Option<Vec<String>>,
+}
+
+impl SimStepper {
+    /// Runs a single step of the simulation, returning `None` if no progress is possible without
+    /// new inputs.
+    pub async fn step(&mut self) -> Option<SimStep> {
+        let stepped = self.launched.step().await;
+        // Let receivers return the messages emitted so far instead of waiting for the next step.
+        self.launched.quiescence.pause();
+
+        let LogKind::Custom(log) = &mut self.launched.log else {
+            unreachable!()
         };
+        let log = String::from_utf8_lossy(&std::mem::take(log)).into_owned();
+        // Elements output without a step (by top-level operators before the simulation became
+        // quiescent) are reported with the next step.
+        let step = stepped.map(|(location, cluster_member)| SimStep {
+            location,
+            cluster_member,
+            log,
+            tagged_operators: unsafe { (self.take_breakpoint_hits)() },
+        });
 
-        async move { launched.scheduler().await }
+        if let (Some(trace), Some(step)) = (&mut self.trace, &step) {
+            let mut header = format!("step {}: {:?}", trace.len(), step.location);
+            if let Some(member) = step.cluster_member {
+                header.push_str(&format!(" (member {})", member));
+            }
+            let tags = step
+                .tagged_operators
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            if !tags.is_empty() {
+                header.push_str(&format!(" [{}]", tags.join(", ")));
+            }
+            trace.push(format!("{}\n{}", header, step.log.trim_matches('\n')));
+        }
+        step
+    }
+
+    /// Starts recording the steps taken, which are returned by [`Self::take_trace`].
+    pub(crate) fn record_trace(&mut self) {
+        self.trace = Some(vec![]);
+    }
+
+    /// The steps taken since [`Self::record_trace`], each rendered as a header with the stepped
+    /// location (and the tags of the operators which output elements) followed by the log of the
+    /// step.
+    pub(crate) fn take_trace(&mut self) -> Vec<String> {
+        self.trace.take().unwrap_or_default()
+    }
+
+    /// Runs steps until one stops at a breakpoint (see [`Self::add_breakpoint`]), returning that
+    /// step, or `None` if the simulation can no longer make progress without new inputs.
+    pub async fn run_until_breakpoint(&mut self) -> Option<SimStep> {
+        loop {
+            let step = self.step().await?;
+            if !self.breakpoints.is_disjoint(&step.tagged_operators) {
+                return Some(step);
+            }
+        }
+    }
+
+    /// Runs steps until no progress is possible without new inputs, returning the steps taken.
+    pub async fn run_until_quiescent(&mut self) -> Vec<SimStep> {
+        let mut steps = vec![];
+        while let Some(step) = self.step().await {
+            steps.push(step);
+        }
+        steps
+    }
+
+    /// Sets a breakpoint on the operator tagged with the given name (by `ir_node_named`), which
+    /// stops [`Self::run_until_breakpoint`] at every step in which that operator outputs an
+    /// element. Other operators in the same location or tick do not trigger it.
+    ///
+    /// # Panics
+    /// If no operator has the given tag.
+    pub fn add_breakpoint(&mut self, tag: &str) {
+        if !self.tags.contains(tag) {
+            panic!("No operator is tagged `{}`.", tag);
+        }
+        self.breakpoints.insert(tag.to_owned());
+    }
+
+    /// Removes a breakpoint set with [`Self::add_breakpoint`].
+    pub fn remove_breakpoint(&mut self, tag: &str) {
+        self.breakpoints.remove(tag);
+    }
+
+    /// Describes the items waiting at each location (and cluster member) to be released into a
+    /// tick or past a non-deterministic operator, such as the inputs to a `batch`.
+    pub fn pending(&self) -> BTreeMap<(LocationId, Option<u32>), Vec<String>> {
+        let mut pending = BTreeMap::new();
+        #[expect(clippy::disallowed_methods, reason = "collected into an ordered map")]
+        for (key, hooks) in self.launched.hooks.iter() {
+            let descriptions = hooks
+                .iter()
+                .filter_map(|hook| hook.describe_pending())
+                .collect::<Vec<_>>();
+            if !descriptions.is_empty() {
+                pending.insert(key.clone(), descriptions);
+            }
+        }
+        pending
     }
 }
 
+/// Whether outputs with ordering `O` and retries `R` must be the same in every execution.
+fn is_deterministic<O: Ordering, R: Retries>() -> bool {
+    O::ORDERING_KIND == StreamOrder::TotalOrder && R::RETRIES_KIND == StreamRetry::ExactlyOnce
+}
+
 impl<T: Serialize + DeserializeOwned, O: Ordering, R: Retries> Clone for SimReceiver<T, O, R> {
     fn clone(&self) -> Self {
         *self
@@ -592,14 +971,19 @@ impl<T: Serialize + DeserializeOwned, O: Ordering, R: Retries> SimReceiver<T, O,
         &self,
         thunk: impl AsyncFnOnce(&mut Pin<&mut dyn Stream<Item = T>>) -> Out,
     ) -> Out {
-        let (receiver, quiescence) = CURRENT_SIM_CONNECTIONS.with(|connections| {
-            let connections = connections.borrow();
-            let port = connections.external_registered.get(&self.0).unwrap();
-            (
-                connections.output_receivers.get(port).unwrap().clone(),
-                connections.quiescence.clone(),
-            )
-        });
+        let (receiver, quiescence, deterministic_outputs) =
+            CURRENT_SIM_CONNECTIONS.with(|connections| {
+                let connections = connections.borrow();
+                let port = connections.external_registered.get(&self.0).unwrap();
+                (
+                    connections.output_receivers.get(port).unwrap().clone(),
+                    connections.quiescence.clone(),
+                    connections
+                        .deterministic_outputs
+                        .clone()
+                        .filter(|_| is_deterministic::<O, R>()),
+                )
+            });
 
         let mut receiver_stream = receiver.lock().await;
         let mut notified_fut = pin!(quiescence.notified());
@@ -607,6 +991,13 @@ impl<T: Serialize + DeserializeOwned, O: Ordering, R: Retries> SimReceiver<T, O,
             use std::task::Poll;
             match receiver_stream.poll_next_unpin(cx) {
                 Poll::Ready(Some(bytes)) => {
+                    if let Some(outputs) = &deterministic_outputs {
+                        outputs
+                            .borrow_mut()
+                            .entry((self.0, None))
+                            .or_default()
+                            .push(bytes.clone());
+                    }
                     return Poll::Ready(Some(bincode::deserialize(&bytes).unwrap()));
                 }
                 Poll::Ready(None) => return Poll::Ready(None),
@@ -917,15 +1308,20 @@ impl<T: Serialize + DeserializeOwned, O: Ordering, R: Retries> SimClusterReceive
         member_id: u32,
         thunk: impl AsyncFnOnce(&mut Pin<&mut dyn Stream<Item = T>>) -> Out,
     ) -> Out {
-        let (receiver, quiescence) = CURRENT_SIM_CONNECTIONS.with(|connections| {
-            let connections = connections.borrow();
-            let port = connections.external_registered.get(&self.0).unwrap();
-            let receivers = connections.cluster_output_receivers.get(port).unwrap();
-            (
-                receivers[&member_id].clone(),
-                connections.quiescence.clone(),
-            )
-        });
+        let (receiver, quiescence, deterministic_outputs) =
+            CURRENT_SIM_CONNECTIONS.with(|connections| {
+                let connections = connections.borrow();
+                let port = connections.external_registered.get(&self.0).unwrap();
+                let receivers = connections.cluster_output_receivers.get(port).unwrap();
+                (
+                    receivers[&member_id].clone(),
+                    connections.quiescence.clone(),
+                    connections
+                        .deterministic_outputs
+                        .clone()
+                        .filter(|_| is_deterministic::<O, R>()),
+                )
+            });
 
         let mut lock = receiver.lock().await;
         let mut notified_fut = pin!(quiescence.notified());
@@ -933,6 +1329,13 @@ impl<T: Serialize + DeserializeOwned, O: Ordering, R: Retries> SimClusterReceive
             use std::task::Poll;
             match lock.poll_next_unpin(cx) {
                 Poll::Ready(Some(bytes)) => {
+                    if let Some(outputs) = &deterministic_outputs {
+                        outputs
+                            .borrow_mut()
+                            .entry((self.0, Some(member_id)))
+                            .or_default()
+                            .push(bytes.clone());
+                    }
                     return Poll::Ready(Some(bincode::deserialize(&bytes).unwrap()));
                 }
                 Poll::Ready(None) => return Poll::Ready(None),
@@ -1090,12 +1493,46 @@ struct LaunchedSim<W: std::io::Write> {
     log: LogKind<W>,
     /// Represents quiescence state of the simulation.
     quiescence: Rc<QuiescenceState>,
+    /// Messages in flight over modeled network links.
+    network: Rc<SimNetwork>,
+    /// Whether to sort the ready ticks and observations before choosing one, so that the choice
+    /// does not depend on the order of `async_dfirs`.
+    sort_ready: bool,
+    /// Whether the fuzzer chooses the latency of messages over links with a latency range, rather
+    /// than always using the smallest latency.
+    explore_latencies: bool,
 }
 
 impl<W: std::io::Write> LaunchedSim<W> {
     async fn scheduler(&mut self) {
+        loop {
+            if self.step().await.is_none() {
+                // Signal quiescence and wait for new input.
+                self.quiescence.wait_for_resume().await;
+            }
+        }
+    }
+
+    /// Runs the async DFIRs until they stop making progress, then either runs one tick or
+    /// resolves the hooks of one observation. Returns the location (and cluster member) that was
+    /// stepped, or `None` if the simulation is quiescent and cannot make progress without input.
+    async fn step(&mut self) -> Option<(LocationId, Option<u32>)> {
         loop {
             tokio::task::yield_now().await;
+
+            // Assign arrival times to messages sent over modeled links. If latency exploration
+            // is enabled, the fuzzer chooses among evenly spaced latencies for links with a
+            // latency range.
+            let explore_latencies = self.explore_latencies;
+            self.network.schedule_pending(|min, max| {
+                use bolero::generator::*;
+                if explore_latencies {
+                    min + (max - min) * (0..9u64).any() / 8
+                } else {
+                    min
+                }
+            });
+
             let mut any_made_progress = false;
             for (loc, c_id, dfir) in &mut self.async_dfirs {
                 if dfir.run_tick().await {
@@ -1165,6 +1602,12 @@ impl<W: std::io::Write> LaunchedSim<W> {
                 if self.possibly_ready_ticks.is_empty()
                     && self.possibly_ready_observation.is_empty()
                 {
+                    // Before declaring quiescence, advance the virtual clock to deliver the
+                    // next messages in flight or fire the next timers, if any.
+                    if self.network.deliver_next() {
+                        continue;
+                    }
+
                     // If any tick is blocked because a hook is not ready, that's a
                     // simulator bug — it means a singleton never received a value.
                     for (name, cid, _) in &self.not_ready_ticks {
@@ -1175,9 +1618,14 @@ impl<W: std::io::Write> LaunchedSim<W> {
                         );
                     }
 
-                    // Signal quiescence and wait for new input.
-                    self.quiescence.wait_for_resume().await;
+                    return None;
                 } else {
+                    if self.sort_ready {
+                        self.possibly_ready_ticks
+                            .sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+                        self.possibly_ready_observation.sort();
+                    }
+
                     let next_tick_or_obs = (0..(self.possibly_ready_ticks.len()
                         + self.possibly_ready_observation.len()))
                         .any();
@@ -1261,16 +1709,17 @@ impl<W: std::io::Write> LaunchedSim<W> {
                             );
                         }
 
+                        let stepped = (removed.0.clone(), removed.1);
                         self.possibly_ready_ticks.push(removed);
+                        return Some(stepped);
                     } else {
                         let next_obs = next_tick_or_obs - self.possibly_ready_ticks.len();
+                        let stepped = self.possibly_ready_observation[next_obs].clone();
                         let mut default_hooks = vec![];
-                        let hooks = self
-                            .hooks
-                            .get_mut(&self.possibly_ready_observation[next_obs])
-                            .unwrap_or(&mut default_hooks);
+                        let hooks = self.hooks.get_mut(&stepped).unwrap_or(&mut default_hooks);
 
                         run_hooks(&mut self.log, hooks);
+                        return Some(stepped);
                     }
                 }
             }
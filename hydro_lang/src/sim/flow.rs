@@ -1,7 +1,7 @@
 //! Entrypoint for compiling and running Hydro simulations.
 
 use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::panic::RefUnwindSafe;
 use std::rc::Rc;
 
@@ -10,12 +10,13 @@ use libloading::Library;
 use slotmap::{SecondaryMap, SparseSecondaryMap};
 
 use super::builder::SimBuilder;
-use super::compiled::{CompiledSim, CompiledSimInstance};
+use super::compiled::{CompiledSim, CompiledSimInstance, SimStepper};
 use super::graph::{SimDeploy, SimExternal, SimNode, compile_sim, create_sim_graph_trybuild};
+use super::network_model::LinkModel;
 use crate::compile::builder::StmtId;
-use crate::compile::ir::HydroRoot;
-use crate::location::LocationKey;
+use crate::compile::ir::{CounterKind, HydroIrMetadata, HydroIrOpMetadata, HydroNode, HydroRoot};
 use crate::location::dynamic::LocationId;
+use crate::location::{Location, LocationKey};
 use crate::prelude::Cluster;
 use crate::sim::graph::SimExternalPortRegistry;
 use crate::staging_util::Invariant;
@@ -53,9 +54,19 @@ pub struct SimFlow<'a> {
     /// validating consistency assertions is not yet supported in the simulator.
     pub(crate) skip_consistency_assertions: bool,
 
+    /// When true, the claims made with `manual_proof!` on folds and reduces are checked.
+    pub(crate) check_manual_proofs: bool,
+
     /// Number of iterations to use for fuzzing, defaults to 8192
     pub(crate) unit_test_fuzz_iterations: usize,
 
+    /// Link models for network edges between specific locations, keyed by `(from, to)`.
+    pub(crate) link_models: HashMap<(LocationKey, LocationKey), LinkModel>,
+    /// Link model for all other network edges, if any.
+    pub(crate) default_link_model: Option<LinkModel>,
+    /// Whether the fuzzer explores the latencies of links with a latency range.
+    pub(crate) explore_latencies: bool,
+
     pub(crate) _phantom: Invariant<'a>,
 }
 
@@ -66,6 +77,37 @@ impl<'a> SimFlow<'a> {
         self
     }
 
+    /// Models the latency and bandwidth of messages sent from `from` to `to`, which are then held
+    /// in flight until the simulation's virtual clock reaches their arrival time. See
+    /// [`network_model`](super::network_model) for details.
+    pub fn with_link_model<L1: Location<'a>, L2: Location<'a>>(
+        mut self,
+        from: &L1,
+        to: &L2,
+        model: LinkModel,
+    ) -> Self {
+        self.link_models
+            .insert((from.id().root().key(), to.id().root().key()), model);
+        self
+    }
+
+    /// Models the latency and bandwidth of all network links which do not have a model set with
+    /// [`Self::with_link_model`].
+    pub fn with_default_link_model(mut self, model: LinkModel) -> Self {
+        self.default_link_model = Some(model);
+        self
+    }
+
+    /// Opts in to exploring the latencies of links with a
+    /// [`Latency::Uniform`](super::network_model::Latency::Uniform) model. By default, every
+    /// message takes the smallest latency in the range; with exploration enabled, the fuzzer
+    /// chooses among evenly spaced latencies over the range, which can reorder messages on links
+    /// that are not [`TotalOrder`](crate::live_collections::stream::TotalOrder).
+    pub fn explore_latencies(mut self) -> Self {
+        self.explore_latencies = true;
+        self
+    }
+
     /// Opts in to safety-only testing, which is required when using
     /// [`lossy_delayed_forever`](crate::networking::NetworkingConfig::lossy_delayed_forever)
     /// networking.
@@ -88,6 +130,22 @@ impl<'a> SimFlow<'a> {
         self
     }
 
+    /// Opts in to checking the algebraic properties claimed with
+    /// [`manual_proof!`](crate::properties::manual_proof) on
+    /// [`Stream::fold`](crate::live_collections::stream::Stream::fold) and
+    /// [`Stream::reduce`](crate::live_collections::stream::Stream::reduce).
+    ///
+    /// After each application of the closure, the simulator replays the last two inputs in the
+    /// opposite order (for `commutative`, which also checks associativity for a reduce) and
+    /// applies the latest input again (for `idempotent`), and fails with the offending inputs if
+    /// the state differs. This requires the state to implement [`Clone`] and [`PartialEq`] and
+    /// the inputs to implement [`Clone`]; other folds and reduces are not checked. The closure is
+    /// called more than once per input, so closures with side effects may misbehave.
+    pub fn check_manual_proofs(mut self) -> Self {
+        self.check_manual_proofs = true;
+        self
+    }
+
     /// Sets the number of fuzz iterations for this test. Overrides the
     /// the default value of 8192
     pub fn unit_test_fuzz_iterations(mut self, iterations: usize) -> Self {
@@ -128,6 +186,23 @@ impl<'a> SimFlow<'a> {
         self.compiled().exhaustive(thunk)
     }
 
+    /// Checks that the outputs marked as deterministic (with [`TotalOrder`] and [`ExactlyOnce`])
+    /// do not depend on the internal order in which the simulator runs locations, by executing
+    /// each random schedule twice. See [`CompiledSim::check_determinism`].
+    ///
+    /// [`TotalOrder`]: crate::live_collections::stream::TotalOrder
+    /// [`ExactlyOnce`]: crate::live_collections::stream::ExactlyOnce
+    pub fn check_determinism(self, thunk: impl AsyncFn() + RefUnwindSafe) {
+        self.compiled().check_determinism(thunk)
+    }
+
+    /// Runs a single instance of the simulation one step at a time, under the control of the
+    /// given closure, which can inspect pending inputs and set breakpoints on tagged operators.
+    /// See [`CompiledSim::interactive`] and [`SimStepper`](super::compiled::SimStepper).
+    pub fn interactive(self, thunk: impl AsyncFnOnce(SimStepper) + RefUnwindSafe) {
+        self.compiled().interactive(thunk)
+    }
+
     /// Compiles the simulation into a dynamically loadable library, and returns a handle to it.
     pub fn compiled(mut self) -> CompiledSim {
         use dfir_lang::graph::{eliminate_extra_unions_tees, partition_graph};
@@ -147,7 +222,10 @@ impl<'a> SimFlow<'a> {
             next_hoff_id: crate::Counter::default(),
             test_safety_only: self.test_safety_only,
             skip_consistency_assertions: self.skip_consistency_assertions,
+            check_manual_proofs: self.check_manual_proofs,
             channel_tables: BTreeMap::new(),
+            link_models: std::mem::take(&mut self.link_models),
+            default_link_model: self.default_link_model,
         };
 
         // Ensure the default (0) external is always present.
@@ -180,6 +258,32 @@ impl<'a> SimFlow<'a> {
             );
         }
 
+        // Follow each tagged operator with one that records when it outputs an element, so
+        // that interactive simulations can stop at breakpoints on it.
+        let mut tags = BTreeSet::new();
+        crate::compile::ir::transform_bottom_up(
+            &mut self.ir,
+            &mut |_| {},
+            &mut |node| {
+                if let Some(tag) = node.metadata().tag.clone() {
+                    let metadata = HydroIrMetadata {
+                        tag: None,
+                        op: HydroIrOpMetadata::new(),
+                        ..node.metadata().clone()
+                    };
+                    let input = std::mem::replace(node, HydroNode::Placeholder);
+                    *node = HydroNode::Counter {
+                        tag: tag.clone(),
+                        kind: CounterKind::Breakpoint,
+                        input: Box::new(input),
+                        metadata,
+                    };
+                    tags.insert(tag);
+                }
+            },
+            false,
+        );
+
         let mut seen_tees = HashMap::new();
         let mut built_tees = HashMap::new();
         let mut next_stmt_id = crate::Counter::<StmtId>::default();
@@ -202,6 +306,9 @@ impl<'a> SimFlow<'a> {
                 .map(|(l, g)| {
                     let FlatGraphBuilderOutput { mut flat_graph, .. } =
                         g.build().expect("Failed to build DFIR flat graph.");
+                    flat_graph
+                        .merge_modules()
+                        .expect("Failed to merge DFIR modules.");
                     eliminate_extra_unions_tees(&mut flat_graph);
                     (
                         l,
@@ -254,6 +361,8 @@ impl<'a> SimFlow<'a> {
             lib,
             externals_port_registry: self.externals_port_registry.take(),
             unit_test_fuzz_iterations: self.unit_test_fuzz_iterations,
+            explore_latencies: self.explore_latencies,
+            tags,
         }
     }
 
@@ -316,6 +316,16 @@ impl<'a> Deploy<'a> for SimDeploy {
         todo!("e2o_many_sink is not yet supported in simulation")
     }
 
+    fn e2o_http_source(
+        _extra_stmts: &mut Vec<syn::Stmt>,
+        _p2: &Self::Process,
+        _p2_port: &<Self::Process as Node>::Port,
+        _route: &str,
+        _shared_handle: String,
+    ) -> syn::Expr {
+        panic!("HTTP external sources are not supported in simulation")
+    }
+
     fn e2o_source(
         _extra_stmts: &mut Vec<syn::Stmt>,
         _p1: &Self::External,
@@ -710,6 +720,7 @@ fn compile_sim_graph_trybuild(
             __hydro_external_in: &mut ::std::collections::HashMap<usize, __root_dfir_rs::tokio::sync::mpsc::UnboundedSender<__root_dfir_rs::bytes::Bytes>>,
             __hydro_cluster_external_out: &mut ::std::collections::HashMap<usize, ::std::collections::HashMap<u32, __root_dfir_rs::tokio_stream::wrappers::UnboundedReceiverStream<__root_dfir_rs::bytes::Bytes>>>,
             __hydro_cluster_external_in: &mut ::std::collections::HashMap<usize, ::std::collections::HashMap<u32, __root_dfir_rs::tokio::sync::mpsc::UnboundedSender<__root_dfir_rs::bytes::Bytes>>>,
+            __hydro_network: ::std::rc::Rc<#root::sim::runtime::SimNetwork>,
             __println_handler: fn(::std::fmt::Arguments<'_>),
             __eprintln_handler: fn(::std::fmt::Arguments<'_>),
         ) -> (
@@ -764,12 +775,15 @@ fn compile_sim_graph_trybuild(
 
             let mut __hydro_hooks: ::std::collections::HashMap<(&'static str, Option<u32>), ::std::vec::Vec<Box<dyn #root::sim::runtime::SimHook>>> = ::std::collections::HashMap::new();
             let mut __hydro_inline_hooks: ::std::collections::HashMap<(&'static str, Option<u32>), ::std::vec::Vec<Box<dyn #root::sim::runtime::SimInlineHook>>> = ::std::collections::HashMap::new();
+            // Timers created while constructing the DFIRs follow this instance's virtual clock.
+            #root::sim::runtime::set_current_network(Some(__hydro_network.clone()));
             #(#extra_stmts_global)*
             #(#cluster_ids_stmts)*
 
             let mut __async_dfirs = vec![#(#process_dfir_exprs),*];
             let mut __tick_dfirs = vec![#(#process_tick_dfir_exprs),*];
             #(#cluster_dfir_stmts)*
+            #root::sim::runtime::set_current_network(None);
             (__async_dfirs, __tick_dfirs, __hydro_hooks, __hydro_inline_hooks)
         }
 
@@ -780,6 +794,7 @@ fn compile_sim_graph_trybuild(
             __hydro_external_in: &mut ::std::collections::HashMap<usize, __root_dfir_rs::tokio::sync::mpsc::UnboundedSender<__root_dfir_rs::bytes::Bytes>>,
             __hydro_cluster_external_out: &mut ::std::collections::HashMap<usize, ::std::collections::HashMap<u32, __root_dfir_rs::tokio_stream::wrappers::UnboundedReceiverStream<__root_dfir_rs::bytes::Bytes>>>,
             __hydro_cluster_external_in: &mut ::std::collections::HashMap<usize, ::std::collections::HashMap<u32, __root_dfir_rs::tokio::sync::mpsc::UnboundedSender<__root_dfir_rs::bytes::Bytes>>>,
+            __hydro_network: ::std::rc::Rc<#root::sim::runtime::SimNetwork>,
             __println_handler: fn(::std::fmt::Arguments<'_>),
             __eprintln_handler: fn(::std::fmt::Arguments<'_>),
         ) -> (
@@ -789,7 +804,18 @@ fn compile_sim_graph_trybuild(
             #root::sim::runtime::InlineHooks<&'static str>,
         ) {
             #root::runtime_support::colored::control::set_override(should_color);
-            __hydro_runtime_core(__hydro_external_out, __hydro_external_in, __hydro_cluster_external_out, __hydro_cluster_external_in, __println_handler, __eprintln_handler)
+            #root::telemetry::meter::reset();
+            __hydro_runtime_core(__hydro_external_out, __hydro_external_in, __hydro_cluster_external_out, __hydro_cluster_external_in, __hydro_network, __println_handler, __eprintln_handler)
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe extern "Rust" fn __hydro_breakpoint_hits() -> ::std::collections::BTreeSet<String> {
+            #root::sim::runtime::take_breakpoint_hits()
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe extern "Rust" fn __hydro_meters() -> ::std::collections::BTreeMap<String, #root::telemetry::meter::MeterSnapshot> {
+            #root::telemetry::meter::snapshot_all()
         }
     };
     source_ast
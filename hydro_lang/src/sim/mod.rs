@@ -52,6 +52,9 @@ pub(crate) mod graph;
 #[cfg(stageleft_runtime)]
 pub mod flow;
 
+#[cfg(stageleft_runtime)]
+pub mod network_model;
+
 #[cfg(stageleft_runtime)]
 pub(crate) mod versioned_network;
 
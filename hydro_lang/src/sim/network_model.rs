@@ -0,0 +1,74 @@
+//! Latency and bandwidth models for the network between simulated locations.
+//!
+//! By default, the simulator delivers messages between locations as soon as they are sent, and
+//! only explores how they are batched and ordered. Assigning a [`LinkModel`] to a network edge
+//! (with [`SimFlow::with_link_model`](super::flow::SimFlow::with_link_model)) instead holds each
+//! message in flight until a virtual clock reaches its arrival time. The virtual clock advances
+//! whenever the simulation would otherwise be idle, so messages on slow links arrive after
+//! messages on fast links, and the elapsed virtual time can be checked with
+//! [`virtual_time`](super::compiled::virtual_time). Timers such as
+//! [`source_interval`](crate::location::Location::source_interval) fire as the same clock
+//! advances, so a simulation with a running interval never quiesces.
+//!
+//! Messages over TCP links arrive in the order they were sent by each sender, even when the
+//! latency varies; a later message which would overtake an earlier one arrives with it instead.
+
+use std::time::Duration;
+
+/// The latency of a simulated network link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Latency {
+    /// Every message takes exactly this long to arrive.
+    Fixed(Duration),
+    /// Each message takes between `min` and `max` to arrive. By default, every message takes
+    /// `min`; with [`SimFlow::explore_latencies`](super::flow::SimFlow::explore_latencies), the
+    /// simulator explores latencies evenly spaced over this range, so that exhaustive searches
+    /// remain tractable.
+    Uniform {
+        /// The smallest latency.
+        min: Duration,
+        /// The largest latency.
+        max: Duration,
+    },
+}
+
+impl Latency {
+    /// The smallest and largest latency, in nanoseconds.
+    pub(crate) fn bounds_nanos(&self) -> (u64, u64) {
+        match self {
+            Latency::Fixed(latency) => (latency.as_nanos() as u64, latency.as_nanos() as u64),
+            Latency::Uniform { min, max } => {
+                assert!(min <= max, "latency `min` must not be larger than `max`");
+                (min.as_nanos() as u64, max.as_nanos() as u64)
+            }
+        }
+    }
+}
+
+/// The model of a simulated network link, describing how long messages take to arrive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkModel {
+    /// The time between a message being sent (or finishing transmission, if the link has a
+    /// bandwidth cap) and it arriving.
+    pub latency: Latency,
+    /// The maximum number of bytes per second that each sender can transmit over the link, or
+    /// `None` if unlimited. Messages from the same sender are transmitted one at a time.
+    pub bandwidth_bytes_per_sec: Option<u64>,
+}
+
+impl LinkModel {
+    /// A link with the given latency and unlimited bandwidth.
+    pub fn new(latency: Latency) -> Self {
+        Self {
+            latency,
+            bandwidth_bytes_per_sec: None,
+        }
+    }
+
+    /// Caps the link's bandwidth at the given number of bytes per second.
+    pub fn with_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        assert!(bytes_per_sec > 0, "bandwidth must be positive");
+        self.bandwidth_bytes_per_sec = Some(bytes_per_sec);
+        self
+    }
+}
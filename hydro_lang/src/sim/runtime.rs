@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::rc::Rc;
@@ -74,6 +74,29 @@ pub trait SimHook {
     fn is_ready(&self) -> bool {
         true
     }
+
+    /// Describes the items waiting to be released by this hook, or `None` if there are none (or
+    /// the hook does not support inspection). Used by [`SimStepper::pending`](super::compiled::SimStepper::pending).
+    fn describe_pending(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Describes the items buffered at a hook, see [`SimHook::describe_pending`].
+fn describe_buffered<T>(
+    location: HookLocationMeta,
+    input: &VecDeque<T>,
+    format_item_debug: fn(&T) -> Option<String>,
+) -> Option<String> {
+    if input.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "{}: {:?}",
+        location.0,
+        TruncatedVecDebug(RefCell::new(Some(input.iter())), 8, format_item_debug)
+    ))
 }
 
 /// A hook that can make inline decisions during the execution of a tick.
@@ -246,6 +269,14 @@ impl<T> SimHook for StreamHook<T, TotalOrder> {
             panic!("No decision to release");
         }
     }
+
+    fn describe_pending(&self) -> Option<String> {
+        describe_buffered(
+            self.batch_location,
+            &self.input.borrow(),
+            self.format_item_debug,
+        )
+    }
 }
 
 impl<T> SimHook for StreamHook<T, NoOrder> {
@@ -330,6 +361,14 @@ impl<T> SimHook for StreamHook<T, NoOrder> {
             panic!("No decision to release");
         }
     }
+
+    fn describe_pending(&self) -> Option<String> {
+        describe_buffered(
+            self.batch_location,
+            &self.input.borrow(),
+            self.format_item_debug,
+        )
+    }
 }
 
 pub struct KeyedStreamHook<K: Hash + Eq + Clone, V, Order: Ordering> {
@@ -1446,6 +1485,10 @@ impl<T> SimHook for TopLevelStreamOrderHook<T> {
             panic!("No decision to release");
         }
     }
+
+    fn describe_pending(&self) -> Option<String> {
+        describe_buffered(self.location, &self.input.borrow(), self.format_item_debug)
+    }
 }
 
 /// Hook for top-level folds. Selects a non-empty subset of buffered inputs to release,
@@ -2148,6 +2191,692 @@ impl<K: Hash + Eq + Clone, V> SimHook for TopLevelKeyedMergeOrderedHook<K, V> {
     }
 }
 
+/// The most recent application of a closure with claimed algebraic properties: the state before
+/// the call, the input, and the state after. Used to replay the last two inputs differently.
+struct LastApplication<A, T> {
+    before: A,
+    input: T,
+    after: A,
+}
+
+fn claimed_algebra_violation<T>(
+    property: &str,
+    location: HookLocationMeta,
+    format_item_debug: fn(&T) -> Option<String>,
+    inputs: &[&T],
+) -> ! {
+    let (op_location, line, caret_indent) = location;
+    let counterexample = inputs
+        .iter()
+        .map(|v| format!("{:?}", ManualDebug(*v, format_item_debug)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    panic!(
+        "claimed {} does not hold\n{} {}\n {}{}\n {}{}{}",
+        property,
+        "-->".color(colored::Color::Blue),
+        op_location,
+        "|".color(colored::Color::Blue),
+        line,
+        "|".color(colored::Color::Blue),
+        caret_indent,
+        format!("^ counterexample inputs: {}", counterexample).color(colored::Color::Red)
+    );
+}
+
+/// Checks the claimed commutativity / idempotence of `f`, which has just been applied to `input`
+/// to move from `before` to `state`.
+#[expect(clippy::too_many_arguments, reason = "internal helper")]
+fn check_fold_claims<A: Clone + PartialEq, T: Clone>(
+    f: &mut impl FnMut(&mut A, T),
+    before: &A,
+    state: &A,
+    input: &T,
+    last: Option<&LastApplication<A, T>>,
+    commutative: bool,
+    idempotent: bool,
+    location: HookLocationMeta,
+    format_item_debug: fn(&T) -> Option<String>,
+) {
+    if idempotent {
+        let mut repeated = state.clone();
+        f(&mut repeated, input.clone());
+        if repeated != *state {
+            claimed_algebra_violation("idempotence", location, format_item_debug, &[input, input]);
+        }
+    }
+
+    // The state may have been reset in between (e.g. a new tick), in which case the previous
+    // input is not part of the current state and cannot be swapped with the latest one.
+    if commutative
+        && let Some(last) = last
+        && last.after == *before
+    {
+        let mut swapped = last.before.clone();
+        f(&mut swapped, input.clone());
+        f(&mut swapped, last.input.clone());
+        if swapped != *state {
+            claimed_algebra_violation(
+                "commutativity",
+                location,
+                format_item_debug,
+                &[&last.input, input],
+            );
+        }
+    }
+}
+
+/// Wraps the accumulator of a fold whose commutativity / idempotence was claimed without a proof.
+///
+/// After every application, the last two inputs are replayed against the earlier state in the
+/// opposite order (commutativity) and the latest input is applied a second time (idempotence).
+/// If the resulting state differs, the simulation fails with the offending inputs.
+pub fn check_claimed_fold<A: Clone + PartialEq, T: Clone>(
+    mut f: impl FnMut(&mut A, T),
+    commutative: bool,
+    idempotent: bool,
+    location: HookLocationMeta,
+    format_item_debug: fn(&T) -> Option<String>,
+) -> impl FnMut(&mut A, T) {
+    let mut last: Option<LastApplication<A, T>> = None;
+    move |state, input| {
+        let before = state.clone();
+        f(state, input.clone());
+
+        check_fold_claims(
+            &mut f,
+            &before,
+            state,
+            &input,
+            last.as_ref(),
+            commutative,
+            idempotent,
+            location,
+            format_item_debug,
+        );
+
+        last = Some(LastApplication {
+            before,
+            input,
+            after: state.clone(),
+        });
+    }
+}
+
+/// Wraps the combinator of a reduce whose commutativity / associativity / idempotence was claimed
+/// without a proof. See [`check_claimed_fold`]; associativity is checked by first combining the
+/// last two inputs and then merging the result into the earlier state.
+pub fn check_claimed_reduce<T: Clone + PartialEq>(
+    mut f: impl FnMut(&mut T, T),
+    commutative: bool,
+    associative: bool,
+    idempotent: bool,
+    location: HookLocationMeta,
+    format_item_debug: fn(&T) -> Option<String>,
+) -> impl FnMut(&mut T, T) {
+    let mut last: Option<LastApplication<T, T>> = None;
+    move |state, input| {
+        let before = state.clone();
+        f(state, input.clone());
+
+        check_fold_claims(
+            &mut f,
+            &before,
+            state,
+            &input,
+            last.as_ref(),
+            commutative,
+            idempotent,
+            location,
+            format_item_debug,
+        );
+
+        if associative
+            && let Some(last) = &last
+            && last.after == before
+        {
+            let mut combined = last.input.clone();
+            f(&mut combined, input.clone());
+            let mut regrouped = last.before.clone();
+            f(&mut regrouped, combined);
+            if regrouped != *state {
+                claimed_algebra_violation(
+                    "associativity",
+                    location,
+                    format_item_debug,
+                    &[&last.input, &input],
+                );
+            }
+        }
+
+        last = Some(LastApplication {
+            before,
+            input,
+            after: state.clone(),
+        });
+    }
+}
+
+/// Selects how the claims of a fold with state `A` and inputs `T` (or of a reduce, with
+/// `A = T`) are checked.
+///
+/// Checking the claims requires cloning and comparing the state and inputs, so the simulator
+/// calls the methods of [`CheckClaims`] and [`SkipClaims`] on `&ClaimChecker::<A, T>(..)`,
+/// which resolve to [`CheckClaims`] only if `A: Clone + PartialEq` and `T: Clone`. Otherwise
+/// the closure is left unchanged.
+pub struct ClaimChecker<A, T>(pub std::marker::PhantomData<fn(A, T)>);
+
+/// Wraps a closure with [`check_claimed_fold`] or [`check_claimed_reduce`].
+pub trait CheckClaims<A, T> {
+    /// Wraps the accumulator of a fold.
+    fn wrap_fold(
+        &self,
+        f: impl FnMut(&mut A, T),
+        commutative: bool,
+        idempotent: bool,
+        location: HookLocationMeta,
+        format_item_debug: fn(&T) -> Option<String>,
+    ) -> impl FnMut(&mut A, T);
+
+    /// Wraps the combinator of a reduce.
+    fn wrap_reduce(
+        &self,
+        f: impl FnMut(&mut A, A),
+        commutative: bool,
+        associative: bool,
+        idempotent: bool,
+        location: HookLocationMeta,
+        format_item_debug: fn(&A) -> Option<String>,
+    ) -> impl FnMut(&mut A, A);
+}
+
+impl<A: Clone + PartialEq, T: Clone> CheckClaims<A, T> for ClaimChecker<A, T> {
+    fn wrap_fold(
+        &self,
+        f: impl FnMut(&mut A, T),
+        commutative: bool,
+        idempotent: bool,
+        location: HookLocationMeta,
+        format_item_debug: fn(&T) -> Option<String>,
+    ) -> impl FnMut(&mut A, T) {
+        check_claimed_fold(f, commutative, idempotent, location, format_item_debug)
+    }
+
+    fn wrap_reduce(
+        &self,
+        f: impl FnMut(&mut A, A),
+        commutative: bool,
+        associative: bool,
+        idempotent: bool,
+        location: HookLocationMeta,
+        format_item_debug: fn(&A) -> Option<String>,
+    ) -> impl FnMut(&mut A, A) {
+        check_claimed_reduce(
+            f,
+            commutative,
+            associative,
+            idempotent,
+            location,
+            format_item_debug,
+        )
+    }
+}
+
+/// Leaves a closure unchanged, for states or inputs whose claims cannot be checked.
+pub trait SkipClaims<A, T> {
+    /// Returns the accumulator of a fold unchanged.
+    fn wrap_fold(
+        &self,
+        f: impl FnMut(&mut A, T),
+        _commutative: bool,
+        _idempotent: bool,
+        _location: HookLocationMeta,
+        _format_item_debug: fn(&T) -> Option<String>,
+    ) -> impl FnMut(&mut A, T) {
+        f
+    }
+
+    /// Returns the combinator of a reduce unchanged.
+    fn wrap_reduce(
+        &self,
+        f: impl FnMut(&mut A, A),
+        _commutative: bool,
+        _associative: bool,
+        _idempotent: bool,
+        _location: HookLocationMeta,
+        _format_item_debug: fn(&A) -> Option<String>,
+    ) -> impl FnMut(&mut A, A) {
+        f
+    }
+}
+
+impl<A, T> SkipClaims<A, T> for &ClaimChecker<A, T> {}
+
+/// The parameters of a modeled network link, embedded by the simulator into the code that sends
+/// each message (see `crate::sim::network_model::LinkModel`).
+#[derive(Clone, Copy, Debug)]
+pub struct SimLinkParams {
+    /// Identifies the network edge.
+    pub edge: usize,
+    /// The smallest latency, in nanoseconds.
+    pub min_latency_nanos: u64,
+    /// The largest latency, in nanoseconds.
+    pub max_latency_nanos: u64,
+    /// The bandwidth cap of each sender, if any.
+    pub bandwidth_bytes_per_sec: Option<u64>,
+    /// Whether messages from each sender must arrive in the order they were sent (for
+    /// [`TotalOrder`] edges).
+    pub ordered: bool,
+}
+
+/// A message which has been sent, but not yet assigned an arrival time.
+struct SimPendingSend {
+    params: SimLinkParams,
+    sender: Option<u32>,
+    size: usize,
+    deliver: Box<dyn FnOnce()>,
+}
+
+/// A message which is in flight, waiting for the virtual clock to reach `deliver_at`.
+struct SimInFlight {
+    deliver_at: std::time::Duration,
+    /// Tie-breaker so that messages with the same arrival time are delivered in send order.
+    seq: u64,
+    deliver: Box<dyn FnOnce()>,
+}
+
+impl PartialEq for SimInFlight {
+    fn eq(&self, other: &Self) -> bool {
+        (self.deliver_at, self.seq) == (other.deliver_at, other.seq)
+    }
+}
+
+impl Eq for SimInFlight {}
+
+impl PartialOrd for SimInFlight {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SimInFlight {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.deliver_at, self.seq).cmp(&(other.deliver_at, other.seq))
+    }
+}
+
+/// The in-flight messages of all modeled network links in a simulation instance, along with the
+/// virtual clock which determines when they arrive.
+///
+/// Messages are sent by the simulated program with [`SimNetwork::send`]. The simulation
+/// scheduler then assigns each one an arrival time, and advances the virtual clock to deliver
+/// them whenever the program is otherwise idle. Timers created with [`SimNetwork::interval`] fire
+/// as the same virtual clock advances.
+#[derive(Default)]
+pub struct SimNetwork {
+    now: std::cell::Cell<std::time::Duration>,
+    next_seq: std::cell::Cell<u64>,
+    pending: RefCell<Vec<SimPendingSend>>,
+    in_flight: RefCell<std::collections::BinaryHeap<std::cmp::Reverse<SimInFlight>>>,
+    /// For each edge and sender, the virtual time at which it finishes transmitting.
+    busy_until: RefCell<HashMap<(usize, Option<u32>), std::time::Duration>>,
+    /// For each ordered edge and sender, the arrival time of the last message sent.
+    last_arrival: RefCell<HashMap<(usize, Option<u32>), std::time::Duration>>,
+    /// Tasks waiting for the virtual clock to reach a deadline, keyed by `(deadline, seq)`.
+    timers: RefCell<std::collections::BTreeMap<(std::time::Duration, u64), std::task::Waker>>,
+}
+
+thread_local! {
+    static CURRENT_NETWORK: RefCell<Option<Rc<SimNetwork>>> = const { RefCell::new(None) };
+    static BREAKPOINT_HITS: RefCell<BTreeSet<&'static str>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Records that the operator tagged `tag` output an element, for the breakpoints of
+/// [`SimStepper`](super::compiled::SimStepper).
+pub fn hit_breakpoint(tag: &'static str) {
+    BREAKPOINT_HITS.with(|hits| hits.borrow_mut().insert(tag));
+}
+
+/// Returns the tags recorded by [`hit_breakpoint`] since the last call.
+pub fn take_breakpoint_hits() -> BTreeSet<String> {
+    BREAKPOINT_HITS.with(|hits| {
+        std::mem::take(&mut *hits.borrow_mut())
+            .into_iter()
+            .map(str::to_owned)
+            .collect()
+    })
+}
+
+/// Sets the network of the simulation instance being constructed, so that timers created while
+/// constructing it (see [`current_network`]) use its virtual clock.
+pub fn set_current_network(network: Option<Rc<SimNetwork>>) {
+    CURRENT_NETWORK.with(|current| *current.borrow_mut() = network);
+}
+
+/// The network of the simulation instance being constructed, if any.
+pub fn current_network() -> Option<Rc<SimNetwork>> {
+    CURRENT_NETWORK.with(|current| current.borrow().clone())
+}
+
+impl SimNetwork {
+    /// Sends a message of `size` bytes from `sender` (the cluster member ID, if any) over the
+    /// given link. `deliver` is called once the message arrives.
+    pub fn send(
+        &self,
+        params: SimLinkParams,
+        sender: Option<u32>,
+        size: usize,
+        deliver: Box<dyn FnOnce()>,
+    ) {
+        self.pending.borrow_mut().push(SimPendingSend {
+            params,
+            sender,
+            size,
+            deliver,
+        });
+    }
+
+    /// The current virtual time, since the start of the simulation.
+    pub fn now(&self) -> std::time::Duration {
+        self.now.get()
+    }
+
+    /// Returns a stream which yields `()` once the virtual clock reaches `delay` from now, and
+    /// then every `period` after that. Like [`tokio::time::interval`], missed ticks are yielded
+    /// in a burst.
+    pub fn interval(
+        self: &Rc<Self>,
+        delay: std::time::Duration,
+        period: std::time::Duration,
+    ) -> SimInterval {
+        assert!(!period.is_zero(), "`period` must be non-zero");
+        SimInterval {
+            network: self.clone(),
+            next: self.now() + delay,
+            period,
+            registered: None,
+        }
+    }
+
+    /// Assigns an arrival time to each message sent since the last call. `choose_latency` is
+    /// given the smallest and largest latency of the link in nanoseconds, and picks the latency.
+    ///
+    /// Messages on ordered links never arrive before an earlier message from the same sender.
+    pub(crate) fn schedule_pending(&self, mut choose_latency: impl FnMut(u64, u64) -> u64) {
+        let pending = std::mem::take(&mut *self.pending.borrow_mut());
+        let now = self.now.get();
+        for send in pending {
+            let SimLinkParams {
+                edge,
+                min_latency_nanos,
+                max_latency_nanos,
+                bandwidth_bytes_per_sec,
+                ordered,
+            } = send.params;
+
+            let transmitted_at = if let Some(bandwidth) = bandwidth_bytes_per_sec {
+                let mut busy_until = self.busy_until.borrow_mut();
+                let busy_until = busy_until.entry((edge, send.sender)).or_default();
+                let start = (*busy_until).max(now);
+                let transmission = std::time::Duration::from_nanos(
+                    (send.size as u128 * 1_000_000_000 / bandwidth as u128) as u64,
+                );
+                *busy_until = start + transmission;
+                *busy_until
+            } else {
+                now
+            };
+
+            let latency = if min_latency_nanos == max_latency_nanos {
+                min_latency_nanos
+            } else {
+                choose_latency(min_latency_nanos, max_latency_nanos)
+            };
+
+            let mut deliver_at = transmitted_at + std::time::Duration::from_nanos(latency);
+            if ordered {
+                // Messages arriving at the same time are delivered in send order, so it is
+                // enough to never arrive before the previous message.
+                let mut last_arrival = self.last_arrival.borrow_mut();
+                let last_arrival = last_arrival.entry((edge, send.sender)).or_default();
+                deliver_at = deliver_at.max(*last_arrival);
+                *last_arrival = deliver_at;
+            }
+
+            let seq = self.next_seq();
+            self.in_flight
+                .borrow_mut()
+                .push(std::cmp::Reverse(SimInFlight {
+                    deliver_at,
+                    seq,
+                    deliver: send.deliver,
+                }));
+        }
+    }
+
+    fn next_seq(&self) -> u64 {
+        let seq = self.next_seq.get();
+        self.next_seq.set(seq + 1);
+        seq
+    }
+
+    /// Advances the virtual clock to the earliest arrival time of any in-flight message or
+    /// deadline of any timer, then delivers all messages arriving at that time and wakes all
+    /// timers which have expired. Returns `false` if nothing was in flight or waiting.
+    pub(crate) fn deliver_next(&self) -> bool {
+        let next_arrival = self
+            .in_flight
+            .borrow()
+            .peek()
+            .map(|std::cmp::Reverse(next)| next.deliver_at);
+        let next_deadline = self
+            .timers
+            .borrow()
+            .first_key_value()
+            .map(|((deadline, _), _)| *deadline);
+        let Some(deliver_at) = [next_arrival, next_deadline].into_iter().flatten().min() else {
+            return false;
+        };
+
+        self.now.set(self.now.get().max(deliver_at));
+
+        let expired = {
+            let mut timers = self.timers.borrow_mut();
+            let pending = timers.split_off(&(deliver_at, u64::MAX));
+            std::mem::replace(&mut *timers, pending)
+        };
+        for waker in expired.into_values() {
+            waker.wake();
+        }
+
+        loop {
+            let next = {
+                let mut in_flight = self.in_flight.borrow_mut();
+                match in_flight.peek() {
+                    Some(std::cmp::Reverse(next)) if next.deliver_at <= deliver_at => {
+                        in_flight.pop()
+                    }
+                    _ => None,
+                }
+            };
+            let Some(std::cmp::Reverse(next)) = next else {
+                break;
+            };
+            (next.deliver)();
+        }
+        true
+    }
+}
+
+/// A stream of virtual clock ticks, created with [`SimNetwork::interval`].
+pub struct SimInterval {
+    network: Rc<SimNetwork>,
+    next: std::time::Duration,
+    period: std::time::Duration,
+    /// The key of this stream's registered timer, if any.
+    registered: Option<(std::time::Duration, u64)>,
+}
+
+impl futures::Stream for SimInterval {
+    type Item = ();
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<()>> {
+        if let Some(key) = self.registered.take() {
+            self.network.timers.borrow_mut().remove(&key);
+        }
+
+        if self.network.now() >= self.next {
+            self.next += self.period;
+            std::task::Poll::Ready(Some(()))
+        } else {
+            let key = (self.next, self.network.next_seq());
+            self.network
+                .timers
+                .borrow_mut()
+                .insert(key, cx.waker().clone());
+            self.registered = Some(key);
+            std::task::Poll::Pending
+        }
+    }
+}
+
+impl Drop for SimInterval {
+    fn drop(&mut self) {
+        if let Some(key) = self.registered.take() {
+            self.network.timers.borrow_mut().remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod sim_network_tests {
+    use super::*;
+
+    #[test]
+    fn test_sim_network_latency_and_bandwidth() {
+        use std::time::Duration;
+
+        let network = SimNetwork::default();
+        let delivered = Rc::new(RefCell::new(Vec::new()));
+        let params = SimLinkParams {
+            edge: 0,
+            min_latency_nanos: 1_000,
+            max_latency_nanos: 1_000,
+            bandwidth_bytes_per_sec: Some(1_000_000_000),
+            ordered: false,
+        };
+        for i in 0..2 {
+            let delivered = delivered.clone();
+            network.send(
+                params,
+                None,
+                500,
+                Box::new(move || delivered.borrow_mut().push(i)),
+            );
+        }
+        network.schedule_pending(|_, _| unreachable!());
+
+        // The first message is transmitted in 500ns, then arrives after 1000ns of latency.
+        assert!(network.deliver_next());
+        assert_eq!(Duration::from_nanos(1_500), network.now());
+        assert_eq!(vec![0], *delivered.borrow());
+
+        // The second message waits for the first to be transmitted.
+        assert!(network.deliver_next());
+        assert_eq!(Duration::from_nanos(2_000), network.now());
+        assert_eq!(vec![0, 1], *delivered.borrow());
+
+        assert!(!network.deliver_next());
+    }
+
+    #[test]
+    fn test_sim_network_ordered_links_keep_send_order() {
+        use std::time::Duration;
+
+        let network = SimNetwork::default();
+        let delivered = Rc::new(RefCell::new(Vec::new()));
+        let params = SimLinkParams {
+            edge: 0,
+            min_latency_nanos: 1_000,
+            max_latency_nanos: 2_000,
+            bandwidth_bytes_per_sec: None,
+            ordered: true,
+        };
+        for i in 0..3 {
+            let delivered = delivered.clone();
+            network.send(
+                params,
+                None,
+                1,
+                Box::new(move || delivered.borrow_mut().push(i)),
+            );
+        }
+
+        // The first message takes the longest, so the later ones must wait for it.
+        let mut latencies = vec![2_000, 1_000, 1_500].into_iter();
+        network.schedule_pending(|_, _| latencies.next().unwrap());
+
+        assert!(network.deliver_next());
+        assert_eq!(Duration::from_nanos(2_000), network.now());
+        assert_eq!(vec![0, 1, 2], *delivered.borrow());
+        assert!(!network.deliver_next());
+
+        // Unordered links deliver in arrival order instead.
+        let delivered = Rc::new(RefCell::new(Vec::new()));
+        for i in 0..3 {
+            let delivered = delivered.clone();
+            network.send(
+                SimLinkParams {
+                    ordered: false,
+                    ..params
+                },
+                None,
+                1,
+                Box::new(move || delivered.borrow_mut().push(i)),
+            );
+        }
+        let mut latencies = vec![2_000, 1_000, 1_500].into_iter();
+        network.schedule_pending(|_, _| latencies.next().unwrap());
+        while network.deliver_next() {}
+        assert_eq!(vec![1, 2, 0], *delivered.borrow());
+    }
+
+    #[test]
+    fn test_sim_network_interval_follows_virtual_clock() {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+        use std::time::Duration;
+
+        use futures::Stream;
+
+        let network = Rc::new(SimNetwork::default());
+        let mut interval = network.interval(Duration::ZERO, Duration::from_millis(10));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(
+            Poll::Ready(Some(())),
+            Pin::new(&mut interval).poll_next(&mut cx)
+        );
+        assert_eq!(Poll::Pending, Pin::new(&mut interval).poll_next(&mut cx));
+
+        // With nothing in flight, the clock advances straight to the timer's deadline.
+        assert!(network.deliver_next());
+        assert_eq!(Duration::from_millis(10), network.now());
+        assert_eq!(
+            Poll::Ready(Some(())),
+            Pin::new(&mut interval).poll_next(&mut cx)
+        );
+        assert_eq!(Poll::Pending, Pin::new(&mut interval).poll_next(&mut cx));
+
+        drop(interval);
+        assert!(!network.deliver_next());
+    }
+}
+
 #[cfg(test)]
 mod maybe_debug_tests {
     struct NotDebuggable;
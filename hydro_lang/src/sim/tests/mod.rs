@@ -306,6 +306,338 @@ fn sim_embedded_send() {
     });
 }
 
+#[test]
+fn sim_source_fixture() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("inputs.jsonl");
+    std::fs::write(&path, "1\n2\n\n3\n").unwrap();
+
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+    let external = flow.external::<()>();
+
+    let out_recv = external
+        .source_fixture::<_, i32>(&node, &path)
+        .map(q!(|x| x * 10))
+        .sim_output();
+
+    flow.sim().exhaustive(async || {
+        let all: Vec<i32> = out_recv.collect().await;
+        assert_eq!(all, vec![10, 20, 30]);
+    });
+}
+
+#[test]
+fn sim_raw_dfir() {
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<u32, TotalOrder, ExactlyOnce>();
+    let out_recv = input
+        .raw_dfir::<u32, TotalOrder, ExactlyOnce>(
+            "mod[input] -> map(|x: u32| x * 2) -> [output]mod;",
+            nondet!(/** test */),
+        )
+        .sim_output();
+
+    flow.sim().exhaustive(async || {
+        in_send.send(1);
+        in_send.send(2);
+
+        let out: Vec<u32> = out_recv.collect_sorted().await;
+        assert_eq!(out, vec![2, 4]);
+    });
+}
+
+#[test]
+fn sim_raw_dfir_at_least_once() {
+    use crate::live_collections::stream::{AtLeastOnce, NoOrder};
+
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<u32, TotalOrder, ExactlyOnce>();
+    let out_recv = input
+        .raw_dfir::<u32, NoOrder, AtLeastOnce>(
+            "mod[input] -> flat_map(|x: u32| [x, x]) -> [output]mod;",
+            nondet!(/** test */),
+        )
+        .unique()
+        .sim_output();
+
+    flow.sim().exhaustive(async || {
+        in_send.send(1);
+        in_send.send(2);
+
+        let out: Vec<u32> = out_recv.collect_sorted().await;
+        assert_eq!(out, vec![1, 2]);
+    });
+}
+
+#[test]
+#[should_panic(expected = "must write to `[output]mod` exactly once")]
+fn sim_raw_dfir_missing_output() {
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (_in_send, input) = node.sim_input::<u32, TotalOrder, ExactlyOnce>();
+    let _ = input.raw_dfir::<u32, TotalOrder, ExactlyOnce>(
+        "mod[input] -> for_each(|x: u32| println!(\"{}\", x));",
+        nondet!(/** test */),
+    );
+}
+
+#[test]
+fn sim_map_async_cached() {
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<u32, TotalOrder, ExactlyOnce>();
+    let cached_recv = input
+        .clone()
+        .map_async_cached(q!(2), q!(|x| async move { x * 10 }))
+        .sim_output();
+    let bounded_recv = input
+        .clone()
+        .map_async_cached_with_max_in_flight(q!(1), q!(4), q!(|x| async move { x * 10 }))
+        .sim_output();
+    let resolved_recv = input
+        .map_async_cached_with_resolver(q!(2), q!(|x| async move { x * 10 }), q!(|x: &u32| x + 1))
+        .sim_output();
+
+    flow.sim().exhaustive(async || {
+        for x in [1, 2, 1, 3] {
+            in_send.send(x);
+        }
+
+        let cached: Vec<u32> = cached_recv.collect_sorted().await;
+        assert_eq!(cached, vec![10, 10, 20, 30]);
+        let bounded: Vec<u32> = bounded_recv.collect_sorted().await;
+        assert_eq!(bounded, vec![10, 10, 20, 30]);
+        let resolved: Vec<u32> = resolved_recv.collect_sorted().await;
+        assert_eq!(resolved, vec![2, 2, 3, 4]);
+    });
+}
+
+#[test]
+fn sim_interactive_breakpoint() {
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+    let tick = node.tick();
+
+    let (in_send, input) = node.sim_input::<u32, TotalOrder, ExactlyOnce>();
+    let out_recv = input
+        .batch(&tick, nondet!(/** test */))
+        .ir_node_named("batched")
+        .map(q!(|x| x * 2))
+        .all_ticks()
+        .sim_output();
+
+    flow.sim().interactive(async |mut stepper| {
+        stepper.add_breakpoint("batched");
+        in_send.send_many([1, 2, 3]);
+
+        let step = stepper.run_until_breakpoint().await.unwrap();
+        assert!(step.log.contains("releasing"), "{}", step.log);
+        assert!(!stepper.pending().is_empty());
+
+        stepper.run_until_quiescent().await;
+        assert!(stepper.pending().is_empty());
+        let out: Vec<u32> = out_recv.collect().await;
+        assert_eq!(out, vec![2, 4, 6]);
+    });
+}
+
+#[test]
+fn sim_interactive_breakpoint_is_per_operator() {
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+    let tick = node.tick();
+
+    let (in_send, input) = node.sim_input::<u32, TotalOrder, ExactlyOnce>();
+    let out_recv = input
+        .batch(&tick, nondet!(/** test */))
+        .ir_node_named("batched")
+        .filter(q!(|x| *x > 10))
+        .ir_node_named("large")
+        .all_ticks()
+        .sim_output();
+
+    flow.sim().interactive(async |mut stepper| {
+        stepper.add_breakpoint("large");
+
+        // The tick containing `large` runs, but no element passes through it.
+        in_send.send_many([1, 2]);
+        assert!(stepper.run_until_breakpoint().await.is_none());
+
+        in_send.send(20);
+        let step = stepper.run_until_breakpoint().await.unwrap();
+        assert!(step.tagged_operators.contains("batched"), "{:?}", step);
+        assert!(step.tagged_operators.contains("large"), "{:?}", step);
+
+        stepper.run_until_quiescent().await;
+        let out: Vec<u32> = out_recv.collect().await;
+        assert_eq!(out, vec![20]);
+    });
+}
+
+#[test]
+fn sim_golden_trace() {
+    use crate::sim::compiled::SimStepper;
+    use crate::test_util::GoldenTrace;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("trace.golden");
+    let check = |released: &'static str, update: bool| {
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let tick = node.tick();
+
+        let (in_send, input) = node.sim_input::<u32, TotalOrder, ExactlyOnce>();
+        input
+            .batch(&tick, nondet!(/** test */))
+            .ir_node_named("batched")
+            .map(q!(|x| x * 2))
+            .all_ticks()
+            .sim_output();
+
+        GoldenTrace::new(&path)
+            .mask(r"releasing items: \[.*\]", released)
+            .update(update)
+            .check(flow.sim(), async |_stepper: &mut SimStepper| {
+                in_send.send_many([1, 2, 3]);
+            });
+    };
+
+    crate::test_util::assert_panics_with_message(
+        || check("releasing items: [..]", false),
+        "does not exist",
+    );
+    assert!(!path.exists());
+
+    check("releasing items: [..]", true);
+    let golden = std::fs::read_to_string(&path).unwrap();
+    assert!(golden.contains("[batched]"), "{}", golden);
+    assert!(golden.contains("releasing items: [..]"), "{}", golden);
+
+    check("releasing items: [..]", false);
+    crate::test_util::assert_panics_with_message(
+        || check("releasing other items", false),
+        "differs from golden trace",
+    );
+}
+
+#[test]
+fn sim_link_model_latency() {
+    use std::time::Duration;
+
+    use crate::networking::TCP;
+    use crate::sim::compiled::virtual_time;
+    use crate::sim::network_model::{Latency, LinkModel};
+
+    let mut flow = FlowBuilder::new();
+    let p1 = flow.process::<()>();
+    let p2 = flow.process::<()>();
+
+    let (in_send, input) = p1.sim_input::<i32, _, _>();
+    let out_recv = input
+        .send(&p2, TCP.fail_stop().bincode().name("ch"))
+        .sim_output();
+
+    flow.sim()
+        .with_link_model(
+            &p1,
+            &p2,
+            LinkModel::new(Latency::Uniform {
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(20),
+            }),
+        )
+        .exhaustive(async || {
+            in_send.send(1);
+            out_recv.assert_yields([1]).await;
+            let elapsed = virtual_time();
+            assert!(elapsed >= Duration::from_millis(10));
+            assert!(elapsed <= Duration::from_millis(20));
+        });
+}
+
+#[test]
+fn sim_link_model_keeps_tcp_order() {
+    use std::time::Duration;
+
+    use crate::networking::TCP;
+    use crate::sim::network_model::{Latency, LinkModel};
+
+    let mut flow = FlowBuilder::new();
+    let p1 = flow.process::<()>();
+    let p2 = flow.process::<()>();
+
+    let (in_send, input) = p1.sim_input::<i32, _, _>();
+    let out_recv = input
+        .send(&p2, TCP.fail_stop().bincode().name("ch"))
+        .sim_output();
+
+    flow.sim()
+        .with_link_model(
+            &p1,
+            &p2,
+            LinkModel::new(Latency::Uniform {
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(20),
+            }),
+        )
+        .explore_latencies()
+        .exhaustive(async || {
+            in_send.send_many([1, 2, 3]);
+            out_recv.assert_yields_only([1, 2, 3]).await;
+        });
+}
+
+#[test]
+fn sim_interval_follows_virtual_clock() {
+    use std::time::Duration;
+
+    use crate::sim::compiled::virtual_time;
+
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let out_recv = node
+        .source_interval(q!(Duration::from_secs(1)))
+        .enumerate()
+        .map(q!(|(i, _)| i))
+        .sim_output();
+
+    flow.sim().exhaustive(async || {
+        out_recv.assert_yields([0, 1, 2]).await;
+        // The scheduler may already be waiting for the next tick.
+        let elapsed = virtual_time();
+        assert!(elapsed >= Duration::from_secs(2));
+        assert!(elapsed <= Duration::from_secs(3));
+    });
+}
+
+#[test]
+fn sim_metered() {
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<i32, _, _>();
+    let out_recv = input.metered("doubled").map(q!(|x| x * 2)).sim_output();
+
+    let compiled = flow.sim().compiled();
+    compiled.exhaustive(async || {
+        in_send.send_many([1, 2, 3]);
+        out_recv.assert_yields_only([2, 4, 6]).await;
+    });
+
+    let snapshot = compiled.meter("doubled").unwrap();
+    assert_eq!(3, snapshot.count);
+    assert_eq!(3, snapshot.batch_sizes.sum());
+    assert!(compiled.meter("missing").is_none());
+}
+
 #[test]
 fn sim_cluster_e2m_m2e() {
     let mut flow = FlowBuilder::new();
@@ -533,6 +865,161 @@ fn sim_fold_commutative_explores_all_subset_sums() {
     );
 }
 
+#[test]
+fn sim_fold_claimed_commutative_holds() {
+    use crate::live_collections::stream::NoOrder;
+    use crate::properties::manual_proof;
+
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<i32, NoOrder, ExactlyOnce>();
+    let folded = input.fold(
+        q!(|| 0),
+        q!(
+            |acc, v| *acc += v,
+            commutative = manual_proof!(/** addition is commutative */)
+        ),
+    );
+    let out_recv = sliced! {
+        let snapshot = use(folded, nondet!(/** test */));
+        snapshot.into_stream()
+    }
+    .sim_output();
+
+    flow.sim().check_manual_proofs().exhaustive(async || {
+        in_send.send_many_unordered([1, 2, 4]);
+        let all: Vec<i32> = out_recv.collect().await;
+        assert_eq!(*all.last().unwrap(), 7);
+    });
+}
+
+#[test]
+#[should_panic(expected = "claimed commutativity does not hold")]
+fn sim_fold_claimed_commutative_violated() {
+    use crate::live_collections::stream::NoOrder;
+    use crate::properties::manual_proof;
+
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<i32, NoOrder, ExactlyOnce>();
+    let folded = input.fold(
+        q!(|| 0),
+        q!(
+            |acc, v| *acc = *acc * 10 + v,
+            commutative = manual_proof!(/** WRONG: digit concatenation is not commutative */)
+        ),
+    );
+    let out_recv = sliced! {
+        let snapshot = use(folded, nondet!(/** test */));
+        snapshot.into_stream()
+    }
+    .sim_output();
+
+    flow.sim().check_manual_proofs().exhaustive(async || {
+        in_send.send_many_unordered([1, 2]);
+        let _: Vec<i32> = out_recv.collect().await;
+    });
+}
+
+#[test]
+#[should_panic(expected = "claimed idempotence does not hold")]
+fn sim_fold_claimed_idempotent_violated() {
+    use crate::live_collections::stream::{AtLeastOnce, NoOrder};
+    use crate::properties::manual_proof;
+
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<i32, NoOrder, ExactlyOnce>();
+    let folded = input.weaken_retries::<AtLeastOnce>().fold(
+        q!(|| 0),
+        q!(
+            |acc, v| *acc += v,
+            commutative = manual_proof!(/** addition is commutative */),
+            idempotent = manual_proof!(/** WRONG: addition is not idempotent */)
+        ),
+    );
+    let out_recv = sliced! {
+        let snapshot = use(folded, nondet!(/** test */));
+        snapshot.into_stream()
+    }
+    .sim_output();
+
+    flow.sim().check_manual_proofs().exhaustive(async || {
+        in_send.send_many_unordered([1]);
+        let _: Vec<i32> = out_recv.collect().await;
+    });
+}
+
+#[test]
+fn sim_merge_lattice_converges() {
+    use crate::live_collections::stream::{AtLeastOnce, NoOrder};
+
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<i32, NoOrder, ExactlyOnce>();
+    let merged = input
+        .weaken_retries::<AtLeastOnce>()
+        .map(q!(lattices::Max::new))
+        .merge_lattice()
+        .into_singleton()
+        .map(q!(|max| max.into_reveal()));
+    let out_recv = sliced! {
+        let snapshot = use(merged, nondet!(/** test */));
+        snapshot.into_stream()
+    }
+    .sim_output();
+
+    flow.sim().exhaustive(async || {
+        in_send.send_many_unordered([3, 7, 5]);
+        let all: Vec<i32> = out_recv.collect().await;
+        assert_eq!(*all.last().unwrap(), 7);
+    });
+}
+
+#[test]
+fn sim_send_lattice_converges() {
+    use std::time::Duration;
+
+    use crate::live_collections::stream::NoOrder;
+    use crate::networking::TCP;
+
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+    let node2 = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<i32, NoOrder, ExactlyOnce>();
+    let replicated = input
+        .map(q!(lattices::Max::new))
+        .merge_lattice()
+        .send_lattice(
+            &node2,
+            TCP.fail_stop().bincode().name("lattice"),
+            q!(Duration::from_secs(1)),
+        )
+        .into_singleton()
+        .map(q!(|max| max.into_reveal()));
+    let out_recv = sliced! {
+        let snapshot = use(replicated, nondet!(/** test */));
+        snapshot.into_stream()
+    }
+    .sim_output();
+
+    flow.sim().fuzz(async || {
+        in_send.send_many_unordered([3, 7, 5]);
+        // the replica only ever grows, and eventually reaches the value at the source
+        let mut last = i32::MIN;
+        while last != 7 {
+            let next = out_recv.next().await.unwrap();
+            assert!(next >= last && next <= 7);
+            last = next;
+        }
+    });
+}
+
 #[test]
 fn sim_fold_total_order_no_permutation() {
     // Non-commutative fold on TotalOrder: no hook emitted, order is fixed.
@@ -881,3 +1368,38 @@ fn sim_unbounded_optional_rejected_snapshot() {
 
     hydro_build_utils::assert_snapshot!(panic_msg);
 }
+
+#[test]
+fn sim_check_determinism_ordered() {
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    let (in_send, input) = node.sim_input::<i32, TotalOrder, ExactlyOnce>();
+    let out_recv = input.map(q!(|v| v * 2)).sim_output();
+
+    flow.sim()
+        .unit_test_fuzz_iterations(16)
+        .check_determinism(async || {
+            in_send.send_many([1, 2, 3]);
+            out_recv.assert_yields_only([2, 4, 6]).await;
+        });
+}
+
+#[test]
+#[should_panic(expected = "non-deterministic output")]
+fn sim_check_determinism_hash_set_iteration() {
+    let mut flow = FlowBuilder::new();
+    let node = flow.process::<()>();
+
+    // iterating over a `HashSet` depends on its random seed, which differs between executions
+    let out_recv = node
+        .source_iter(q!([(0..32).collect::<std::collections::HashSet<u32>>()]))
+        .flat_map_ordered(q!(|set| set))
+        .sim_output();
+
+    flow.sim()
+        .unit_test_fuzz_iterations(16)
+        .check_determinism(async || {
+            let _: Vec<u32> = out_recv.collect().await;
+        });
+}
@@ -0,0 +1,409 @@
+//! Probabilistic sketches which summarize large streams in a fixed amount of memory.
+//!
+//! Each sketch is a lattice (it implements [`lattices::Merge`]), so successive snapshots of a
+//! sketch can be combined with [`Stream::merge_lattice`] or sent with
+//! [`LatticeSingleton::send_lattice`](crate::live_collections::LatticeSingleton::send_lattice).
+//! The bottom ([`Default`]) value of each sketch is empty, and adopts the parameters of the
+//! first non-empty sketch it is merged with. Merging [`HyperLogLog`] sketches computed at
+//! different locations estimates the distinct items of their union, but the counts of
+//! [`CountMin`] sketches from different locations must be summed instead, which
+//! [`CountMinByMember`] does.
+//!
+//! Items are hashed with [`DefaultHasher`], so sketches should only be merged between binaries
+//! built with the same Rust toolchain.
+//!
+//! The provided sketches are:
+//! - [`HyperLogLog`]: estimates the number of distinct items, see [`Stream::approx_distinct`]
+//! - [`CountMin`]: estimates the number of occurrences of each key, see
+//!   [`KeyedStream::approx_count`](crate::live_collections::KeyedStream::approx_count)
+//! - [`CountMinByMember`]: combines the [`CountMin`] sketches of several members
+
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use lattices::Merge;
+use serde::{Deserialize, Serialize};
+
+#[cfg(doc)]
+use crate::live_collections::stream::Stream;
+
+fn hash_with_seed<T: Hash + ?Sized>(item: &T, seed: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A HyperLogLog sketch (Flajolet et al., 2007), which estimates the number of distinct items
+/// inserted into it.
+///
+/// With a precision of `p`, the sketch uses `2^p` one-byte registers and has a relative standard
+/// error of about `1.04 / sqrt(2^p)`. Merging two sketches produces the sketch of the union of
+/// their items.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HyperLogLog {
+    precision: u8,
+    /// Empty until the first item is inserted, which is equivalent to all zeros.
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog::new(Self::DEFAULT_PRECISION)
+    }
+}
+
+impl HyperLogLog {
+    /// The precision used by [`HyperLogLog::default`], which has a relative error of about 1.6%.
+    pub const DEFAULT_PRECISION: u8 = 12;
+
+    /// Creates an empty sketch with `2^precision` registers.
+    ///
+    /// # Panics
+    /// If `precision` is not between 4 and 16 (inclusive).
+    pub fn new(precision: u8) -> Self {
+        assert!(
+            (4..=16).contains(&precision),
+            "HyperLogLog precision must be between 4 and 16, got {precision}"
+        );
+        HyperLogLog {
+            precision,
+            registers: Vec::new(),
+        }
+    }
+
+    /// Inserts an item into the sketch.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        if self.registers.is_empty() {
+            self.registers = vec![0; 1 << self.precision];
+        }
+
+        let p = self.precision;
+        let hash = hash_with_seed(item, 0);
+        let index = (hash >> (64 - p)) as usize;
+        // the guard bit bounds the rank when the remaining bits are all zero
+        let rank = ((hash << p) | (1 << (p - 1))).leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// Returns the estimated number of distinct items inserted into the sketch.
+    pub fn estimate(&self) -> u64 {
+        if self.registers.is_empty() {
+            return 0;
+        }
+
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&r| 2f64.powi(-i32::from(r)))
+            .sum();
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+
+        let raw = alpha * m * m / sum;
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            // linear counting is more accurate for small cardinalities
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+impl Merge<HyperLogLog> for HyperLogLog {
+    fn merge(&mut self, other: HyperLogLog) -> bool {
+        if other.registers.is_empty() {
+            return false;
+        }
+        if self.registers.is_empty() {
+            *self = other;
+            return true;
+        }
+
+        assert_eq!(
+            self.precision, other.precision,
+            "cannot merge HyperLogLog sketches with different precisions"
+        );
+        let mut changed = false;
+        for (register, other) in self.registers.iter_mut().zip(other.registers) {
+            if other > *register {
+                *register = other;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// A Count-Min sketch (Cormode and Muthukrishnan, 2005), which estimates the number of times each
+/// key has been inserted into it.
+///
+/// The sketch is a `depth x width` table of counters. Estimates never undercount, and with
+/// probability at least `1 - e^-depth` overcount by at most `e / width` times the total number of
+/// insertions.
+///
+/// The lattice merge takes the maximum of each counter, which combines successive snapshots of a
+/// sketch that is only ever inserted into (such as the sketch of one cluster member, as it is
+/// gossiped to others). Merging the sketches of *disjoint* streams (such as those of different
+/// cluster members) undercounts, so they should be combined with [`CountMin::add`] or
+/// [`CountMinByMember`] instead.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CountMin {
+    width: usize,
+    depth: usize,
+    /// Row-major, empty until the first key is inserted, which is equivalent to all zeros.
+    counters: Vec<u64>,
+}
+
+impl Default for CountMin {
+    fn default() -> Self {
+        CountMin::new(Self::DEFAULT_WIDTH, Self::DEFAULT_DEPTH)
+    }
+}
+
+impl CountMin {
+    /// The width used by [`CountMin::default`].
+    pub const DEFAULT_WIDTH: usize = 1024;
+    /// The depth used by [`CountMin::default`].
+    pub const DEFAULT_DEPTH: usize = 4;
+
+    /// Creates an empty sketch with `depth` rows of `width` counters.
+    ///
+    /// # Panics
+    /// If `width` or `depth` is zero.
+    pub fn new(width: usize, depth: usize) -> Self {
+        assert!(
+            width > 0 && depth > 0,
+            "CountMin width and depth must be non-zero"
+        );
+        CountMin {
+            width,
+            depth,
+            counters: Vec::new(),
+        }
+    }
+
+    fn cell<K: Hash + ?Sized>(&self, row: usize, key: &K) -> usize {
+        row * self.width + (hash_with_seed(key, row as u64) % self.width as u64) as usize
+    }
+
+    /// Records `count` occurrences of `key`.
+    pub fn insert<K: Hash + ?Sized>(&mut self, key: &K, count: u64) {
+        if self.counters.is_empty() {
+            self.counters = vec![0; self.width * self.depth];
+        }
+
+        for row in 0..self.depth {
+            let cell = self.cell(row, key);
+            self.counters[cell] += count;
+        }
+    }
+
+    /// Returns the estimated number of occurrences of `key`, which is never less than the true
+    /// number.
+    pub fn estimate<K: Hash + ?Sized>(&self, key: &K) -> u64 {
+        if self.counters.is_empty() {
+            return 0;
+        }
+
+        (0..self.depth)
+            .map(|row| self.counters[self.cell(row, key)])
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Adds the counts of `other` to this sketch, producing the sketch of both streams together.
+    ///
+    /// Unlike [`Merge::merge`], this is not idempotent, so each sketch must only be added once.
+    ///
+    /// # Panics
+    /// If the sketches have different dimensions.
+    pub fn add(&mut self, other: &CountMin) {
+        if other.counters.is_empty() {
+            return;
+        }
+        if self.counters.is_empty() {
+            *self = other.clone();
+            return;
+        }
+
+        assert!(
+            self.width == other.width && self.depth == other.depth,
+            "cannot add CountMin sketches with different dimensions"
+        );
+        for (counter, other) in self.counters.iter_mut().zip(&other.counters) {
+            *counter += other;
+        }
+    }
+}
+
+impl Merge<CountMin> for CountMin {
+    fn merge(&mut self, other: CountMin) -> bool {
+        if other.counters.is_empty() {
+            return false;
+        }
+        if self.counters.is_empty() {
+            *self = other;
+            return true;
+        }
+
+        assert!(
+            self.width == other.width && self.depth == other.depth,
+            "cannot merge CountMin sketches with different dimensions"
+        );
+        let mut changed = false;
+        for (counter, other) in self.counters.iter_mut().zip(other.counters) {
+            if other > *counter {
+                *counter = other;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// The latest [`CountMin`] sketch of each member of a group (such as a cluster), which estimates
+/// the number of occurrences of each key across all members.
+///
+/// The lattice merge keeps the maximum of each member's counters (as [`CountMin`] does), so
+/// snapshots of the same member can be merged in any order and any number of times, while the
+/// sketches of different members are summed by [`CountMinByMember::estimate`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CountMinByMember<M: Ord> {
+    members: BTreeMap<M, CountMin>,
+}
+
+impl<M: Ord> Default for CountMinByMember<M> {
+    fn default() -> Self {
+        CountMinByMember {
+            members: BTreeMap::new(),
+        }
+    }
+}
+
+impl<M: Ord> CountMinByMember<M> {
+    /// Creates a sketch containing the latest snapshot of the sketch of a single `member`.
+    pub fn from_member(member: M, sketch: CountMin) -> Self {
+        CountMinByMember {
+            members: BTreeMap::from([(member, sketch)]),
+        }
+    }
+
+    /// Returns the sketch of the union of the streams of all members.
+    ///
+    /// # Panics
+    /// If the sketches of the members have different dimensions.
+    pub fn combined(&self) -> CountMin {
+        let mut combined = CountMin::default();
+        for sketch in self.members.values() {
+            combined.add(sketch);
+        }
+        combined
+    }
+
+    /// Returns the estimated number of occurrences of `key` across all members, which is never
+    /// less than the true number.
+    pub fn estimate<K: Hash + ?Sized>(&self, key: &K) -> u64 {
+        self.combined().estimate(key)
+    }
+}
+
+impl<M: Ord> Merge<CountMinByMember<M>> for CountMinByMember<M> {
+    fn merge(&mut self, other: CountMinByMember<M>) -> bool {
+        let mut changed = false;
+        for (member, sketch) in other.members {
+            changed |= self.members.entry(member).or_default().merge(sketch);
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lattices::Merge;
+
+    use super::{CountMin, CountMinByMember, HyperLogLog};
+
+    #[test]
+    fn hyperloglog_estimates_union_of_merged_sketches() {
+        let mut a = HyperLogLog::default();
+        let mut b = HyperLogLog::default();
+        for i in 0..10_000 {
+            a.insert(&i);
+            // half of the items overlap with `a`
+            b.insert(&(i + 5_000));
+        }
+
+        let estimate = a.estimate();
+        assert!((9_000..=11_000).contains(&estimate), "{estimate}");
+
+        assert!(a.merge(b.clone()));
+        assert!(!a.merge(b));
+        let estimate = a.estimate();
+        assert!((13_500..=16_500).contains(&estimate), "{estimate}");
+    }
+
+    #[test]
+    fn hyperloglog_bottom_adopts_precision() {
+        let mut sketch = HyperLogLog::new(8);
+        sketch.insert("a");
+        let merged = HyperLogLog::merge_owned(HyperLogLog::default(), sketch.clone());
+        assert_eq!(merged, sketch);
+        assert_eq!(merged.estimate(), 1);
+    }
+
+    #[test]
+    fn count_min_never_undercounts() {
+        let mut sketch = CountMin::new(16, 3);
+        for i in 0..100u32 {
+            sketch.insert(&i, u64::from(i % 5));
+        }
+        for i in 0..100u32 {
+            assert!(sketch.estimate(&i) >= u64::from(i % 5));
+        }
+        assert_eq!(CountMin::default().estimate("missing"), 0);
+    }
+
+    #[test]
+    fn count_min_merge_and_add() {
+        let mut earlier = CountMin::default();
+        earlier.insert("a", 1);
+        let mut later = earlier.clone();
+        later.insert("a", 2);
+
+        // merging snapshots of the same sketch keeps the latest counts
+        let merged = CountMin::merge_owned(later.clone(), earlier);
+        assert_eq!(merged.estimate("a"), 3);
+
+        // adding the sketches of disjoint streams sums their counts
+        let mut other = CountMin::default();
+        other.insert("a", 4);
+        let mut total = merged;
+        total.add(&other);
+        assert_eq!(total.estimate("a"), 7);
+    }
+
+    #[test]
+    fn count_min_by_member_sums_members() {
+        let mut first = CountMin::default();
+        first.insert("a", 2);
+        let mut second = CountMin::default();
+        second.insert("a", 3);
+        let mut second_later = second.clone();
+        second_later.insert("a", 1);
+
+        let mut sketch = CountMinByMember::from_member(1, first);
+        assert!(sketch.merge(CountMinByMember::from_member(2, second_later)));
+        // an earlier snapshot of the same member does not change the estimate
+        assert!(!sketch.merge(CountMinByMember::from_member(2, second)));
+        assert_eq!(sketch.estimate("a"), 6);
+        assert_eq!(sketch.estimate("b"), 0);
+    }
+}
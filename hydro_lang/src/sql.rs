@@ -0,0 +1,242 @@
+//! Sources and sinks for relational databases, built on [`sqlx`].
+//!
+//! Each process connects to the Postgres database at the URL in its `DATABASE_URL` environment
+//! variable. All SQL sources and sinks on a process share a single connection pool, which is
+//! created the first time one of them is used.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use futures::{Sink, Stream as FuturesStream, StreamExt};
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use sqlx::query_builder::Separated;
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use stageleft::{QuotedWithContext, q};
+
+use crate::live_collections::boundedness::{Boundedness, Unbounded};
+use crate::live_collections::stream::{
+    ExactlyOnce, IsExactlyOnce, IsOrdered, Ordering, Retries, Stream, TotalOrder,
+};
+use crate::location::{Location, Process};
+
+/// The environment variable that holds the URL of the database to connect to.
+pub const DATABASE_URL_ENV: &str = "DATABASE_URL";
+
+/// A type that can be written as a row of a table by [`Stream::sink_postgres`].
+///
+/// # Example
+/// ```rust,ignore
+/// struct Reading {
+///     sensor: i64,
+///     value: f64,
+/// }
+///
+/// impl SqlInsert for Reading {
+///     const COLUMNS: &'static [&'static str] = &["sensor", "value"];
+///
+///     fn bind_values(self, row: &mut Separated<'_, 'static, Postgres, &'static str>) {
+///         row.push_bind(self.sensor);
+///         row.push_bind(self.value);
+///     }
+/// }
+/// ```
+pub trait SqlInsert {
+    /// The columns that each row is inserted into, in the order their values are bound.
+    const COLUMNS: &'static [&'static str];
+
+    /// Binds the values of this row, one for each of the [`SqlInsert::COLUMNS`].
+    fn bind_values(self, row: &mut Separated<'_, 'static, Postgres, &'static str>);
+}
+
+static POOL: OnceLock<PgPool> = OnceLock::new();
+
+#[doc(hidden)]
+pub fn pool() -> &'static PgPool {
+    POOL.get_or_init(|| {
+        let url = std::env::var(DATABASE_URL_ENV).unwrap_or_else(|_| {
+            panic!("`{DATABASE_URL_ENV}` must be set to use SQL sources and sinks")
+        });
+        PgPoolOptions::new()
+            .connect_lazy(&url)
+            .unwrap_or_else(|e| panic!("invalid `{DATABASE_URL_ENV}`: {e}"))
+    })
+}
+
+#[doc(hidden)]
+pub fn poll_query<T>(
+    query: &'static str,
+    interval: Duration,
+) -> impl FuturesStream<Item = T> + Unpin
+where
+    T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'static,
+{
+    Box::pin(
+        tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(interval))
+            .then(move |_| async move {
+                match sqlx::query_as::<_, T>(query).fetch_all(pool()).await {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        tracing::error!(error = %e, query, "failed to poll SQL source");
+                        Vec::new()
+                    }
+                }
+            })
+            .flat_map(futures::stream::iter),
+    )
+}
+
+/// Quotes `name` as a Postgres identifier, so that it is never interpreted as SQL. A name with
+/// dots is treated as a path, such as `schema.table`, and each part is quoted separately.
+///
+/// # Panics
+/// If any part of `name` is empty or contains a NUL character, which Postgres does not allow.
+fn quote_identifier(name: &str) -> String {
+    name.split('.')
+        .map(|part| {
+            assert!(
+                !part.is_empty() && !part.contains('\0'),
+                "invalid SQL identifier `{name}`"
+            );
+            format!("\"{}\"", part.replace('"', "\"\""))
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Builds the query which inserts `row` into `table`.
+fn insert_query<T: SqlInsert>(table: &str, row: T) -> QueryBuilder<'static, Postgres> {
+    let columns = T::COLUMNS
+        .iter()
+        .map(|column| quote_identifier(column))
+        .collect::<Vec<_>>();
+    let mut builder = QueryBuilder::<Postgres>::new(format!(
+        "INSERT INTO {} ({}) ",
+        quote_identifier(table),
+        columns.join(", ")
+    ));
+    builder.push_values(std::iter::once(row), |mut values, row| {
+        row.bind_values(&mut values)
+    });
+    builder
+}
+
+#[doc(hidden)]
+pub fn insert_sink<T: SqlInsert>(table: &'static str) -> impl Sink<T, Error = sqlx::Error> + Unpin {
+    Box::pin(futures::sink::unfold((), move |(), row: T| async move {
+        insert_query(table, row).build().execute(pool()).await?;
+        Ok(())
+    }))
+}
+
+impl<'a, P: 'a> Process<'a, P> {
+    /// Polls a Postgres database by running `query` every `poll_interval`, emitting each row of
+    /// the result as an element of the stream.
+    ///
+    /// Every poll emits the full result of the query, so queries over tables that are not
+    /// append-only will emit the same rows many times. To only receive new rows, the query
+    /// should filter on a column that increases over time, or the stream should be deduplicated
+    /// downstream. Failed polls are logged and emit no rows.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// # use hydro_lang::prelude::*;
+    /// # let mut flow = FlowBuilder::new();
+    /// let process = flow.process::<()>();
+    /// let readings: Stream<(i64, f64), _> = process.source_postgres(
+    ///     q!("SELECT sensor, value FROM readings"),
+    ///     q!(std::time::Duration::from_secs(1)),
+    /// );
+    /// ```
+    pub fn source_postgres<T>(
+        &self,
+        query: impl QuotedWithContext<'a, &'static str, Self> + Copy + 'a,
+        poll_interval: impl QuotedWithContext<'a, Duration, Self> + Copy + 'a,
+    ) -> Stream<T, Self, Unbounded, TotalOrder, ExactlyOnce>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'static,
+    {
+        self.source_stream(q!(crate::sql::poll_query(query, poll_interval)))
+    }
+}
+
+impl<'a, T, L, B: Boundedness, O: Ordering, R: Retries> Stream<T, L, B, O, R>
+where
+    L: Location<'a>,
+{
+    /// Inserts each element of this stream as a row of `table` in a Postgres database. The
+    /// columns and values of each row are given by the [`SqlInsert`] implementation of the
+    /// element type.
+    ///
+    /// Rows are inserted one at a time, in the order of the stream. The table and column names are
+    /// quoted, so they are case-sensitive and cannot contain SQL. A table name with dots, such as
+    /// `"sensors.readings"`, refers to a table in a schema.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// # use hydro_lang::prelude::*;
+    /// # let mut flow = FlowBuilder::new();
+    /// let process = flow.process::<()>();
+    /// process
+    ///     .source_iter(q!(vec![Reading { sensor: 1, value: 0.5 }]))
+    ///     .sink_postgres(q!("readings"));
+    /// ```
+    pub fn sink_postgres(self, table: impl QuotedWithContext<'a, &'static str, L>)
+    where
+        T: SqlInsert,
+        O: IsOrdered,
+        R: IsExactlyOnce,
+    {
+        self.dest_sink(q!(crate::sql::insert_sink(table)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Reading {
+        sensor: i64,
+        value: f64,
+    }
+
+    impl SqlInsert for Reading {
+        const COLUMNS: &'static [&'static str] = &["sensor", "value"];
+
+        fn bind_values(self, row: &mut Separated<'_, 'static, Postgres, &'static str>) {
+            row.push_bind(self.sensor);
+            row.push_bind(self.value);
+        }
+    }
+
+    #[test]
+    fn insert_query_quotes_identifiers() {
+        let reading = || Reading {
+            sensor: 1,
+            value: 0.5,
+        };
+        assert_eq!(
+            r#"INSERT INTO "readings" ("sensor", "value") VALUES ($1, $2)"#,
+            insert_query("readings", reading()).sql()
+        );
+        assert_eq!(
+            r#"INSERT INTO "sensors"."readings" ("sensor", "value") VALUES ($1, $2)"#,
+            insert_query("sensors.readings", reading()).sql()
+        );
+        assert_eq!(
+            r#"INSERT INTO "readings; DROP TABLE ""users""" ("sensor", "value") VALUES ($1, $2)"#,
+            insert_query(r#"readings; DROP TABLE "users""#, reading()).sql()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid SQL identifier `sensors.`")]
+    fn insert_query_rejects_empty_identifier() {
+        insert_query(
+            "sensors.",
+            Reading {
+                sensor: 1,
+                value: 0.5,
+            },
+        );
+    }
+}
@@ -137,6 +137,14 @@ pub fn record_metrics_sidecar(
                     .await
                     .unwrap();
 
+                record_metrics_metered(namespace, location_name, timestamp, &mut writer)
+                    .await
+                    .unwrap();
+
+                record_metrics_sinks(namespace, location_name, timestamp, &mut writer)
+                    .await
+                    .unwrap();
+
                 writer.shutdown().await.unwrap();
             })
             .catch_unwind()
@@ -274,6 +282,107 @@ where
     Ok(())
 }
 
+#[cfg(feature = "runtime_support")]
+/// Records the metrics of each meter created by `Stream::metered`.
+async fn record_metrics_metered<W>(
+    namespace: &str,
+    location_name: &str,
+    timestamp: SystemTime,
+    writer: &mut W,
+) -> Result<(), std::io::Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    let ts_millis = timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    for (meter_name, snapshot) in super::meter::snapshot_all() {
+        let emf = json!({
+            "_aws": {
+                "Timestamp": ts_millis,
+                "CloudWatchMetrics": [
+                    {
+                        "Namespace": namespace,
+                        "Dimensions": [["LocationName"], ["LocationName", "MeterName"]],
+                        "Metrics": [
+                            {"Name": "TotalItemsCount", "Unit": Unit::Count},
+                            {"Name": "BatchSizeP50", "Unit": Unit::Count},
+                            {"Name": "BatchSizeP99", "Unit": Unit::Count},
+                            {"Name": "BatchSizeMax", "Unit": Unit::Count},
+                            {"Name": "BatchLatencyP50", "Unit": Unit::Microseconds},
+                            {"Name": "BatchLatencyP99", "Unit": Unit::Microseconds},
+                            {"Name": "BatchLatencyMax", "Unit": Unit::Microseconds},
+                        ]
+                    }
+                ]
+            },
+            "LocationName": location_name,
+            "MeterName": meter_name,
+            "StableOpId": snapshot.stable_id,
+            "TotalItemsCount": snapshot.count,
+            "BatchSizeP50": snapshot.batch_sizes.quantile(0.5).unwrap_or_default(),
+            "BatchSizeP99": snapshot.batch_sizes.quantile(0.99).unwrap_or_default(),
+            "BatchSizeMax": snapshot.batch_sizes.max().unwrap_or_default(),
+            "BatchLatencyP50": snapshot.batch_latency_micros.quantile(0.5).unwrap_or_default(),
+            "BatchLatencyP99": snapshot.batch_latency_micros.quantile(0.99).unwrap_or_default(),
+            "BatchLatencyMax": snapshot.batch_latency_micros.max().unwrap_or_default(),
+        })
+        .to_string();
+        writer.write_all(emf.as_bytes()).await?;
+        writer.write_u8(b'\n').await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "runtime_support")]
+/// Records the metrics of each sink instrumented with `sinktools::instrument`.
+async fn record_metrics_sinks<W>(
+    namespace: &str,
+    location_name: &str,
+    timestamp: SystemTime,
+    writer: &mut W,
+) -> Result<(), std::io::Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    let ts_millis = timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    for (sink_name, snapshot) in sinktools::instrument::snapshot_all() {
+        let emf = json!({
+            "_aws": {
+                "Timestamp": ts_millis,
+                "CloudWatchMetrics": [
+                    {
+                        "Namespace": namespace,
+                        "Dimensions": [["LocationName"], ["LocationName", "SinkName"]],
+                        "Metrics": [
+                            {"Name": "SinkItemsCount", "Unit": Unit::Count},
+                            {"Name": "SinkErrorsCount", "Unit": Unit::Count},
+                            {"Name": "SinkStallTime", "Unit": Unit::Microseconds},
+                        ]
+                    }
+                ]
+            },
+            "LocationName": location_name,
+            "SinkName": sink_name,
+            "SinkItemsCount": snapshot.items,
+            "SinkErrorsCount": snapshot.errors,
+            "SinkStallTime": snapshot.stall_time.as_micros() as u64,
+        })
+        .to_string();
+        writer.write_all(emf.as_bytes()).await?;
+        writer.write_u8(b'\n').await?;
+    }
+
+    Ok(())
+}
+
 /// AWS CloudWatch EMF units.
 ///
 /// <https://docs.aws.amazon.com/AmazonCloudWatch/latest/APIReference/API_MetricDatum.html#ACW-Type-MetricDatum-Unit>
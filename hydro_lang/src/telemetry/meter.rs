@@ -0,0 +1,257 @@
+//! Inline metrics recorded by [`Stream::metered`](crate::live_collections::stream::Stream::metered).
+//!
+//! Each metered stream records the number of elements passing through it, along with histograms
+//! of the batch size and batch latency in each tick, into a process-wide registry keyed by the
+//! meter's name. Meters with the same name (for example, the same metered stream on every member
+//! of a cluster simulated in one process) are aggregated together.
+//!
+//! In deployments, the registry is included in the metrics written by the EMF
+//! `RecordMetricsSidecar`. In simulations, it can be read after running an instance with
+//! `CompiledSim::meter`.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Number of buckets in a [`Histogram`], one for zero and one for each power of two.
+const NUM_BUCKETS: usize = u64::BITS as usize + 1;
+
+/// A histogram of `u64` values, with buckets for each power of two.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    /// `buckets[0]` counts zeros, `buckets[i]` counts values in `[2^(i-1), 2^i)`.
+    buckets: [u64; NUM_BUCKETS],
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; NUM_BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Records a single value.
+    pub fn record(&mut self, value: u64) {
+        let bucket = (u64::BITS - value.leading_zeros()) as usize;
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Adds all values recorded in `other` to this histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (bucket, other_bucket) in self.buckets.iter_mut().zip(other.buckets) {
+            *bucket += other_bucket;
+        }
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// The number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The sum of all values recorded.
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    /// The smallest value recorded, or `None` if empty.
+    pub fn min(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// The largest value recorded, or `None` if empty.
+    pub fn max(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// The mean of the values recorded, or `None` if empty.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    /// An upper bound on the `q`-quantile (for `q` in `[0, 1]`) of the values recorded, accurate
+    /// to within a factor of two. Returns `None` if empty.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket;
+            if seen >= rank {
+                let upper = if i == 0 {
+                    0
+                } else {
+                    u64::MAX >> (u64::BITS as usize - i)
+                };
+                return Some(upper.clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+}
+
+/// The metrics recorded by a meter, returned by [`snapshot`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeterSnapshot {
+    /// The [stable ID](crate::compile::ir::HydroIrOpMetadata::stable_id) of the metered
+    /// operator, which identifies it across versions of the program.
+    pub stable_id: Option<String>,
+    /// The total number of elements that passed through the meter.
+    pub count: u64,
+    /// The number of elements in each tick in which any element passed through the meter.
+    pub batch_sizes: Histogram,
+    /// For each tick in which any element passed through the meter, the time in microseconds
+    /// between the first and last element of the batch passing through.
+    pub batch_latency_micros: Histogram,
+}
+
+struct MeterState {
+    snapshot: MeterSnapshot,
+    /// The tick, number of elements, and first and last element times of the current batch.
+    batch: Option<(u64, u64, Instant, Instant)>,
+}
+
+impl MeterState {
+    fn finish_batch(snapshot: &mut MeterSnapshot, batch: (u64, u64, Instant, Instant)) {
+        let (_tick, count, first, last) = batch;
+        snapshot.batch_sizes.record(count);
+        snapshot
+            .batch_latency_micros
+            .record((last - first).as_micros() as u64);
+    }
+
+    /// The snapshot, including the batch currently in progress.
+    fn snapshot(&self) -> MeterSnapshot {
+        let mut snapshot = self.snapshot.clone();
+        if let Some(batch) = self.batch {
+            Self::finish_batch(&mut snapshot, batch);
+        }
+        snapshot
+    }
+}
+
+static METERS: Mutex<BTreeMap<String, Arc<Mutex<MeterState>>>> = Mutex::new(BTreeMap::new());
+
+/// A handle to a registered meter, used by the code generated for
+/// [`Stream::metered`](crate::live_collections::stream::Stream::metered).
+#[doc(hidden)]
+pub struct Meter(Arc<Mutex<MeterState>>);
+
+impl Meter {
+    /// Records a single element passing through the meter in the given tick.
+    pub fn record(&self, tick: u64) {
+        let now = Instant::now();
+        let mut state = self.0.lock().unwrap();
+        let state = &mut *state;
+        state.snapshot.count += 1;
+        match &mut state.batch {
+            Some((batch_tick, count, _first, last)) if *batch_tick == tick => {
+                *count += 1;
+                *last = now;
+            }
+            batch => {
+                if let Some(finished) = batch.replace((tick, 1, now, now)) {
+                    MeterState::finish_batch(&mut state.snapshot, finished);
+                }
+            }
+        }
+    }
+}
+
+/// Registers (or looks up) the meter with the given name, for the operator with the given stable
+/// ID.
+#[doc(hidden)]
+pub fn register(name: &str, stable_id: Option<&str>) -> Meter {
+    let mut meters = METERS.lock().unwrap();
+    let state = meters.entry(name.to_owned()).or_insert_with(|| {
+        Arc::new(Mutex::new(MeterState {
+            snapshot: MeterSnapshot {
+                stable_id: stable_id.map(str::to_owned),
+                ..MeterSnapshot::default()
+            },
+            batch: None,
+        }))
+    });
+    Meter(state.clone())
+}
+
+/// Returns the metrics recorded so far by the meter with the given name, or `None` if no such
+/// meter has been registered.
+pub fn snapshot(name: &str) -> Option<MeterSnapshot> {
+    let meters = METERS.lock().unwrap();
+    let state = meters.get(name)?;
+    Some(state.lock().unwrap().snapshot())
+}
+
+/// Returns the metrics recorded so far by all registered meters, by name.
+pub fn snapshot_all() -> BTreeMap<String, MeterSnapshot> {
+    let meters = METERS.lock().unwrap();
+    meters
+        .iter()
+        .map(|(name, state)| (name.clone(), state.lock().unwrap().snapshot()))
+        .collect()
+}
+
+/// Clears all recorded metrics. Meters which are still in use start again from zero.
+pub fn reset() {
+    let meters = METERS.lock().unwrap();
+    for state in meters.values() {
+        let mut state = state.lock().unwrap();
+        state.snapshot = MeterSnapshot {
+            stable_id: state.snapshot.stable_id.take(),
+            ..MeterSnapshot::default()
+        };
+        state.batch = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_quantiles() {
+        let mut histogram = Histogram::default();
+        for value in 1..=100 {
+            histogram.record(value);
+        }
+        assert_eq!(Some(1), histogram.min());
+        assert_eq!(Some(100), histogram.max());
+        assert_eq!(5050, histogram.sum());
+        // The median, 50, is in the bucket `[32, 64)`.
+        assert_eq!(Some(63), histogram.quantile(0.5));
+        assert_eq!(Some(100), histogram.quantile(1.0));
+    }
+
+    #[test]
+    fn meter_batches_by_tick() {
+        let meter = register("meter_batches_by_tick", None);
+        for tick in [0, 0, 0, 1, 3, 3] {
+            meter.record(tick);
+        }
+        let snapshot = snapshot("meter_batches_by_tick").unwrap();
+        assert_eq!(6, snapshot.count);
+        assert_eq!(3, snapshot.batch_sizes.count());
+        assert_eq!(Some(1), snapshot.batch_sizes.min());
+        assert_eq!(Some(3), snapshot.batch_sizes.max());
+    }
+}
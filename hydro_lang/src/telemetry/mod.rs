@@ -9,6 +9,12 @@ use crate::location::{LocationKey, LocationType};
 #[cfg(feature = "telemetry_emf")]
 pub mod emf;
 
+pub mod meter;
+#[cfg(feature = "network_tracing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "network_tracing")))]
+pub mod network_tracing;
+pub mod watchdog;
+
 struct Formatter;
 
 impl<S, N> FormatEvent<S, N> for Formatter
@@ -133,8 +139,13 @@ pub fn initialize_tracing_with_filter(filter: EnvFilter) {
     use tracing_subscriber::prelude::*;
     use tracing_subscriber::{Layer, fmt, registry};
 
+    let registry = registry();
+    // Replaced with a layer that exports spans if network tracing is enabled.
+    #[cfg(feature = "network_tracing")]
+    let registry = registry.with(network_tracing::reload_layer());
+
     set_global_default(
-        registry().with(
+        registry.with(
             fmt::layer()
                 .with_writer(std::io::stderr)
                 .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
@@ -0,0 +1,266 @@
+//! Distributed tracing across network channels, enabled by
+//! [`DeployFlow::with_network_tracing`](crate::compile::deploy::DeployFlow::with_network_tracing).
+//!
+//! When enabled, the sender of each message on a network channel records a `hydro_send` span,
+//! and prefixes the serialized message with the OpenTelemetry context of that span. The receiver
+//! strips the context and enters a `hydro_recv` span as its child, so that traces follow
+//! requests from process to process. Spans are exported over OTLP by
+//! [`tracing-opentelemetry`](tracing_opentelemetry).
+//!
+//! The `hydro_recv` span stays entered on the receiving thread until the next message with a
+//! trace context is received, so that spans recorded while processing the message, including the
+//! `hydro_send` spans of messages it causes to be sent onwards, are its children. As operators do
+//! not track which received message caused each output, messages sent after a receive which are
+//! not caused by it (e.g. from a `source_iter`) are also attributed to it.
+
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use opentelemetry::trace::{
+    SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, TracerProvider,
+};
+use opentelemetry_otlp::WithExportConfig;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::{Registry, reload};
+
+/// The layer which exports spans, installed by [`init`].
+type OtelLayer = OpenTelemetryLayer<Registry, opentelemetry_sdk::trace::Tracer>;
+
+/// Handle to the layer installed by [`super::initialize_tracing_with_filter`], which [`init`]
+/// replaces with a layer that exports spans.
+static RELOAD_HANDLE: OnceLock<reload::Handle<Option<OtelLayer>, Registry>> = OnceLock::new();
+
+/// Header byte of a message sent without a trace context (when spans are not being exported).
+const NO_CONTEXT: u8 = 0;
+/// Header byte of a message sent with a trace context, followed by the trace ID, span ID, and
+/// trace flags of the sending span.
+const WITH_CONTEXT: u8 = 1;
+/// Length of the header of a message sent with a trace context.
+const CONTEXT_LEN: usize = 1 + 16 + 8 + 1;
+
+thread_local! {
+    /// The `hydro_recv` span of the last message received with a trace context on this thread,
+    /// entered until the next such message is received, see [`extract`].
+    static CURRENT_RECV: RefCell<Option<tracing::span::EnteredSpan>> = const { RefCell::new(None) };
+}
+
+/// The (initially empty) layer which [`init`] replaces with a layer that exports spans.
+pub(super) fn reload_layer() -> reload::Layer<Option<OtelLayer>, Registry> {
+    let (layer, handle) = reload::Layer::new(None);
+    let _ = RELOAD_HANDLE.set(handle);
+    layer
+}
+
+/// Starts exporting spans to the OTLP (HTTP) collector at `endpoint`, under the given service
+/// name. Initializes [`tracing`] with [`super::initialize_tracing`] if it is not yet initialized.
+pub fn init(service_name: &str, endpoint: &str) {
+    if !tracing::dispatcher::has_been_set() {
+        super::initialize_tracing();
+    }
+    let Some(handle) = RELOAD_HANDLE.get() else {
+        tracing::warn!(
+            "Another `tracing` subscriber is installed, network spans will not be exported."
+        );
+        return;
+    };
+
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(err) => {
+            tracing::warn!("Failed to create OTLP exporter for network spans: {}", err);
+            return;
+        }
+    };
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(service_name.to_owned())
+                .build(),
+        )
+        .build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("hydro"));
+    opentelemetry::global::set_tracer_provider(provider);
+
+    if let Err(err) = handle.reload(Some(layer)) {
+        tracing::warn!("Failed to install OTLP exporter for network spans: {}", err);
+    }
+}
+
+/// A serialized message sent on a network channel: the payload, optionally with the ID of the
+/// member it is sent to.
+#[doc(hidden)]
+pub trait OutgoingMessage {
+    fn payload_mut(&mut self) -> &mut Bytes;
+}
+
+impl OutgoingMessage for Bytes {
+    fn payload_mut(&mut self) -> &mut Bytes {
+        self
+    }
+}
+
+impl<I> OutgoingMessage for (I, Bytes) {
+    fn payload_mut(&mut self) -> &mut Bytes {
+        &mut self.1
+    }
+}
+
+/// A serialized message received from a network channel: the payload, optionally with the ID of
+/// the member it was sent by.
+#[doc(hidden)]
+pub trait IncomingMessage {
+    fn payload_mut(&mut self) -> &mut BytesMut;
+}
+
+impl IncomingMessage for BytesMut {
+    fn payload_mut(&mut self) -> &mut BytesMut {
+        self
+    }
+}
+
+impl<I> IncomingMessage for (I, BytesMut) {
+    fn payload_mut(&mut self) -> &mut BytesMut {
+        &mut self.1
+    }
+}
+
+/// Records a `hydro_send` span for a message sent on `channel`, as a child of the current span
+/// (e.g. the `hydro_recv` span entered by [`extract`]), and prefixes the message with its trace
+/// context.
+#[doc(hidden)] // Called by generated code.
+pub fn inject<M: OutgoingMessage>(channel: &'static str, mut message: M) -> M {
+    let span = tracing::info_span!("hydro_send", channel);
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+
+    let payload = message.payload_mut();
+    let mut out;
+    if span_context.is_valid() {
+        out = BytesMut::with_capacity(CONTEXT_LEN + payload.len());
+        out.put_u8(WITH_CONTEXT);
+        out.put_slice(&span_context.trace_id().to_bytes());
+        out.put_slice(&span_context.span_id().to_bytes());
+        out.put_u8(span_context.trace_flags().to_u8());
+    } else {
+        out = BytesMut::with_capacity(1 + payload.len());
+        out.put_u8(NO_CONTEXT);
+    }
+    out.put_slice(&payload[..]);
+    *payload = out.freeze();
+    message
+}
+
+/// Strips the trace context prefixed by [`inject`] from a message received on `channel`, and
+/// enters a `hydro_recv` span as a child of the sending span, replacing the previously entered
+/// one.
+#[doc(hidden)] // Called by generated code.
+pub fn extract<M: IncomingMessage, E>(
+    channel: &'static str,
+    mut received: Result<M, E>,
+) -> Result<M, E> {
+    if let Ok(message) = &mut received {
+        let payload = message.payload_mut();
+        if let Some(span_context) = decode_context(payload) {
+            CURRENT_RECV.with_borrow_mut(|current| {
+                // Exit the previous span first, so that it is not the parent of the new one.
+                drop(current.take());
+                let span = tracing::info_span!(parent: None, "hydro_recv", channel);
+                span.set_parent(
+                    opentelemetry::Context::new().with_remote_span_context(span_context),
+                );
+                *current = Some(span.entered());
+            });
+        }
+    }
+    received
+}
+
+/// Removes the header written by [`inject`] from `payload`, returning the trace context it holds
+/// (if any).
+fn decode_context(payload: &mut BytesMut) -> Option<SpanContext> {
+    match payload.first() {
+        Some(&WITH_CONTEXT) if payload.len() >= CONTEXT_LEN => {
+            let mut header = payload.split_to(CONTEXT_LEN);
+            header.advance(1);
+            let trace_id = TraceId::from_bytes(header[..16].try_into().unwrap());
+            let span_id = SpanId::from_bytes(header[16..24].try_into().unwrap());
+            let trace_flags = TraceFlags::new(header[24]);
+            Some(SpanContext::new(
+                trace_id,
+                span_id,
+                trace_flags,
+                true,
+                TraceState::default(),
+            ))
+        }
+        Some(&NO_CONTEXT) => {
+            payload.advance(1);
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Bytes, BytesMut};
+    use opentelemetry::trace::{TraceContextExt, TraceId, TracerProvider};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::prelude::*;
+
+    use super::{CURRENT_RECV, extract, inject};
+
+    /// The trace ID in the header written by [`inject`].
+    fn header_trace_id(message: &Bytes) -> TraceId {
+        assert_eq!(super::WITH_CONTEXT, message[0]);
+        TraceId::from_bytes(message[1..17].try_into().unwrap())
+    }
+
+    #[test]
+    fn round_trip_without_context() {
+        let sent = inject("test", (3u32, Bytes::from_static(b"payload")));
+        let received = extract::<_, ()>("test", Ok((3u32, BytesMut::from(&sent.1[..]))));
+        assert_eq!(b"payload", &received.unwrap().1[..]);
+    }
+
+    #[test]
+    fn propagates_across_two_hops() {
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            // First hop: sent from outside any span, which starts a new trace.
+            let first = inject("first", Bytes::from_static(b"one"));
+            let trace_id = header_trace_id(&first);
+            let received = extract::<_, ()>("first", Ok(BytesMut::from(&first[..]))).unwrap();
+            assert_eq!(b"one", &received[..]);
+
+            // Second hop: sent while processing the first message, so it continues its trace.
+            let second = inject("second", Bytes::from_static(b"two"));
+            assert_eq!(trace_id, header_trace_id(&second));
+            assert_ne!(
+                first[17..25],
+                second[17..25],
+                "each hop records its own span"
+            );
+            let received = extract::<_, ()>("second", Ok(BytesMut::from(&second[..]))).unwrap();
+            assert_eq!(b"two", &received[..]);
+            assert_eq!(
+                trace_id,
+                tracing::Span::current()
+                    .context()
+                    .span()
+                    .span_context()
+                    .trace_id()
+            );
+
+            CURRENT_RECV.with_borrow_mut(|current| drop(current.take()));
+        });
+    }
+}
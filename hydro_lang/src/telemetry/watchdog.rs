@@ -0,0 +1,265 @@
+//! A sidecar which detects deployed flows that have stopped making progress.
+//!
+//! A flow is considered stuck when no tick has completed for the configured timeout, while
+//! either a tick is suspended (for example waiting on a sink which is not ready) or input has
+//! arrived which has not been processed. When this happens, the watchdog dumps the scheduler
+//! state (the current tick and the handoffs which still hold items) to stderr, and optionally
+//! aborts the process.
+//!
+//! The watchdog runs on the same thread as the flow, so it cannot detect an operator which blocks
+//! the thread synchronously.
+#[cfg(all(feature = "runtime_support", feature = "tokio"))]
+use std::rc::Rc;
+use std::time::Duration;
+
+#[cfg(all(feature = "runtime_support", feature = "tokio"))]
+use dfir_rs::Never;
+#[cfg(all(feature = "runtime_support", feature = "tokio"))]
+use dfir_rs::scheduled::context::DfirProgress;
+#[cfg(all(feature = "runtime_support", feature = "tokio"))]
+use dfir_rs::scheduled::metrics::DfirMetrics;
+use quote::quote;
+use syn::parse_quote;
+
+use crate::location::{LocationKey, LocationType};
+use crate::staging_util::get_this_crate;
+use crate::telemetry::Sidecar;
+
+/// Default timeout for [`WatchdogSidecar`].
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A sidecar which reports (and optionally aborts) flows that have made no progress for a while.
+pub struct WatchdogSidecar {
+    timeout: Duration,
+    abort: bool,
+}
+
+#[buildstructor::buildstructor]
+impl WatchdogSidecar {
+    /// Build an instance. Any `None` will be replaced with the default value, which is to wait
+    /// for [`DEFAULT_TIMEOUT`] and then report the stuck flow without aborting.
+    #[builder]
+    pub fn new(timeout: Option<Duration>, abort: Option<bool>) -> Self {
+        Self {
+            timeout: timeout.unwrap_or(DEFAULT_TIMEOUT),
+            abort: abort.unwrap_or(false),
+        }
+    }
+}
+
+impl Sidecar for WatchdogSidecar {
+    fn to_expr(
+        &self,
+        _flow_name: &str,
+        _location_key: LocationKey,
+        _location_type: LocationType,
+        location_name: &str,
+        dfir_ident: &syn::Ident,
+    ) -> syn::Expr {
+        let Self { timeout, abort } = self;
+
+        let root = get_this_crate();
+        let timeout: proc_macro2::TokenStream = {
+            let secs = timeout.as_secs();
+            let nanos = timeout.subsec_nanos();
+            quote!(::std::time::Duration::new(#secs, #nanos))
+        };
+
+        parse_quote! {
+            #root::telemetry::watchdog::watchdog_sidecar(#dfir_ident.progress(), #dfir_ident.metrics(), #location_name, #timeout, #abort)
+        }
+    }
+}
+
+/// Checks for progress several times per timeout, forever.
+#[cfg(all(feature = "runtime_support", feature = "tokio"))]
+#[doc(hidden)]
+pub fn watchdog_sidecar(
+    progress: DfirProgress,
+    metrics: Rc<DfirMetrics>,
+    location_name: &'static str,
+    timeout: Duration,
+    abort: bool,
+) -> impl 'static + Future<Output = Never> {
+    let check_interval = (timeout / 4).max(Duration::from_millis(100));
+
+    async move {
+        let mut detector = StallDetector::new(
+            timeout,
+            progress.ticks_completed(),
+            tokio::time::Instant::now(),
+        );
+
+        loop {
+            let _ = tokio::time::sleep(check_interval).await;
+
+            let Some(stalled_for) = detector.observe(
+                progress.ticks_completed(),
+                progress.in_tick() || progress.input_pending(),
+                tokio::time::Instant::now(),
+            ) else {
+                continue;
+            };
+
+            eprintln!(
+                "{}",
+                stall_report(&progress, &metrics, location_name, stalled_for)
+            );
+            if abort {
+                eprintln!("Watchdog aborting `{}`.", location_name);
+                std::process::abort();
+            }
+        }
+    }
+}
+
+/// Tracks when a flow last made progress, to decide when it is stuck.
+#[cfg(all(feature = "runtime_support", feature = "tokio"))]
+struct StallDetector {
+    timeout: Duration,
+    last_ticks: u64,
+    last_progress: tokio::time::Instant,
+    reported: bool,
+}
+
+#[cfg(all(feature = "runtime_support", feature = "tokio"))]
+impl StallDetector {
+    fn new(timeout: Duration, ticks: u64, now: tokio::time::Instant) -> Self {
+        Self {
+            timeout,
+            last_ticks: ticks,
+            last_progress: now,
+            reported: false,
+        }
+    }
+
+    /// Records the number of completed ticks at `now`, and whether the flow is waiting to make
+    /// progress. Returns how long the flow has been stuck if it should be reported, which happens
+    /// once per stall.
+    fn observe(
+        &mut self,
+        ticks: u64,
+        waiting: bool,
+        now: tokio::time::Instant,
+    ) -> Option<Duration> {
+        if ticks != self.last_ticks || !waiting {
+            self.last_ticks = ticks;
+            self.last_progress = now;
+            self.reported = false;
+            return None;
+        }
+
+        let stalled_for = now - self.last_progress;
+        if stalled_for < self.timeout || self.reported {
+            return None;
+        }
+        self.reported = true;
+        Some(stalled_for)
+    }
+}
+
+/// Describes the scheduler state of a stuck flow.
+#[cfg(all(feature = "runtime_support", feature = "tokio"))]
+fn stall_report(
+    progress: &DfirProgress,
+    metrics: &DfirMetrics,
+    location_name: &str,
+    stalled_for: Duration,
+) -> String {
+    use std::fmt::Write;
+
+    let mut report = format!(
+        "Watchdog: `{}` has made no progress for {:.1}s.\n  ticks completed: {}\n  tick suspended: {}\n  input pending: {}",
+        location_name,
+        stalled_for.as_secs_f64(),
+        progress.ticks_completed(),
+        progress.in_tick(),
+        progress.input_pending(),
+    );
+
+    let blocked = metrics
+        .handoffs
+        .iter()
+        .filter(|(_, handoff)| handoff.curr_items_count() > 0)
+        .collect::<Vec<_>>();
+    if blocked.is_empty() {
+        report.push_str("\n  blocked handoffs: none");
+    } else {
+        report.push_str("\n  blocked handoffs:");
+        for (handoff_id, handoff) in blocked {
+            write!(
+                report,
+                "\n    {:?}: {} items",
+                handoff_id,
+                handoff.curr_items_count()
+            )
+            .unwrap();
+        }
+    }
+    report
+}
+
+#[cfg(all(test, feature = "runtime_support", feature = "tokio"))]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::StallDetector;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn reports_once_per_stall() {
+        let start = Instant::now();
+        let mut detector = StallDetector::new(TIMEOUT, 0, start);
+
+        assert_eq!(
+            None,
+            detector.observe(0, true, start + Duration::from_secs(5))
+        );
+        assert_eq!(
+            Some(Duration::from_secs(11)),
+            detector.observe(0, true, start + Duration::from_secs(11))
+        );
+        assert_eq!(
+            None,
+            detector.observe(0, true, start + Duration::from_secs(20))
+        );
+
+        // Completing a tick ends the stall, so the next one is reported again.
+        assert_eq!(
+            None,
+            detector.observe(1, true, start + Duration::from_secs(21))
+        );
+        assert_eq!(
+            None,
+            detector.observe(1, true, start + Duration::from_secs(30))
+        );
+        assert_eq!(
+            Some(TIMEOUT),
+            detector.observe(1, true, start + Duration::from_secs(31))
+        );
+    }
+
+    #[test]
+    fn idle_flows_are_not_stuck() {
+        let start = Instant::now();
+        let mut detector = StallDetector::new(TIMEOUT, 0, start);
+
+        // No tick completes, but there is nothing to do either.
+        assert_eq!(
+            None,
+            detector.observe(0, false, start + Duration::from_secs(60))
+        );
+        // Once input arrives, the timeout starts from when the flow was last idle.
+        assert_eq!(
+            None,
+            detector.observe(0, true, start + Duration::from_secs(65))
+        );
+        assert_eq!(
+            Some(TIMEOUT),
+            detector.observe(0, true, start + Duration::from_secs(70))
+        );
+    }
+}
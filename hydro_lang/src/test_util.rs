@@ -1,21 +1,43 @@
 //! Various utilities for testing short Hydro programs, especially in doctests.
 
+#[cfg(feature = "deploy")]
 use std::future::Future;
+#[cfg(all(feature = "sim", stageleft_runtime))]
+use std::panic::RefUnwindSafe;
 use std::panic::{AssertUnwindSafe, catch_unwind};
+#[cfg(all(feature = "sim", stageleft_runtime))]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "deploy")]
 use std::pin::Pin;
+#[cfg(all(feature = "sim", stageleft_runtime))]
+use std::sync::Mutex;
 
+#[cfg(all(feature = "sim", stageleft_runtime))]
+use regex::Regex;
+#[cfg(feature = "deploy")]
 use serde::Serialize;
+#[cfg(feature = "deploy")]
 use serde::de::DeserializeOwned;
 
+#[cfg(feature = "deploy")]
 use crate::compile::builder::FlowBuilder;
+#[cfg(feature = "deploy")]
 use crate::live_collections::boundedness::{Boundedness, Unbounded};
+#[cfg(feature = "deploy")]
 use crate::live_collections::stream::{Ordering, Retries, Stream};
+#[cfg(feature = "deploy")]
 use crate::location::Process;
+#[cfg(all(feature = "sim", stageleft_runtime))]
+use crate::sim::compiled::SimStepper;
+#[cfg(all(feature = "sim", stageleft_runtime))]
+use crate::sim::flow::SimFlow;
 
 /// Sets up a test with multiple processes / clusters declared in the test logic (`thunk`). The test logic must return
 /// a single streaming output, which can then be read in `check` (an async closure) to perform assertions.
 ///
 /// Each declared process is deployed as a single local process, and each cluster is deployed as four local processes.
+#[cfg(feature = "deploy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
 pub async fn multi_location_test<'a, T, C, O: Ordering, R: Retries>(
     thunk: impl FnOnce(
         &mut FlowBuilder<'a>,
@@ -48,6 +70,8 @@ pub async fn multi_location_test<'a, T, C, O: Ordering, R: Retries>(
 
 /// Sets up a test declared in `thunk` that executes on a single [`Process`], returning a streaming output
 /// that can be read in `check` (an async closure) to perform assertions.
+#[cfg(feature = "deploy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
 pub async fn stream_transform_test<'a, T, C, B: Boundedness, O: Ordering, R: Retries>(
     thunk: impl FnOnce(&Process<'a>) -> Stream<T, Process<'a>, B, O, R>,
     check: impl FnOnce(Pin<Box<dyn futures::Stream<Item = T>>>) -> C,
@@ -93,3 +117,155 @@ pub fn assert_panics_with_message(func: impl FnOnce(), msg: &'static str) {
         .or_else(|err| err.downcast::<&'static str>().map(|s| chk(*s)))
         .expect("Unexpected panic type!");
 }
+
+/// Environment variable which, when set to `1`, makes [`GoldenTrace::check`] write golden
+/// traces instead of comparing against them.
+#[cfg(all(feature = "sim", stageleft_runtime))]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+pub const UPDATE_GOLDEN_ENV: &str = "HYDRO_UPDATE_GOLDEN";
+
+/// A regression test for the behavior of a protocol, which records the full trace of a
+/// simulation and compares it against a golden trace saved by a previous run.
+///
+/// The trace lists every step of the simulation (see [`SimStepper`]): the location (and cluster
+/// member) that was stepped, the tags of its operators (set with `ir_node_named`), and the
+/// messages released at that location along with their [`Debug`](std::fmt::Debug)
+/// representation. Fields which vary between otherwise identical runs, or which change with
+/// unrelated edits (such as the line numbers in the trace), can be masked with [`Self::mask`]:
+///
+/// ```rust,ignore
+/// GoldenTrace::new("tests/golden/echo.trace")
+///     .mask(r"\.rs:\d+:\d+", ".rs")
+///     .check(flow.sim(), async |_stepper| {
+///         in_send.send_many([1, 2, 3]);
+///     });
+/// ```
+#[cfg(all(feature = "sim", stageleft_runtime))]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+pub struct GoldenTrace {
+    path: PathBuf,
+    masks: Vec<(Regex, String)>,
+    update: bool,
+}
+
+#[cfg(all(feature = "sim", stageleft_runtime))]
+impl GoldenTrace {
+    /// A golden trace saved at `path`, relative to the manifest directory of the crate under test.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        Self {
+            path: manifest_dir.join(path),
+            masks: vec![],
+            update: std::env::var(UPDATE_GOLDEN_ENV).is_ok_and(|v| v == "1"),
+        }
+    }
+
+    /// Whether [`Self::check`] writes the golden trace instead of comparing against it. Defaults
+    /// to whether [`UPDATE_GOLDEN_ENV`] is set to `1`.
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Replaces every match of the regex `pattern` in the trace with `replacement` (which may
+    /// refer to capture groups, as in [`Regex::replace_all`]) before it is compared. Masks are
+    /// applied in the order they are added.
+    ///
+    /// # Panics
+    /// If `pattern` is not a valid regex.
+    pub fn mask(mut self, pattern: &str, replacement: impl Into<String>) -> Self {
+        let regex = Regex::new(pattern)
+            .unwrap_or_else(|e| panic!("Invalid mask pattern `{}`: {}", pattern, e));
+        self.masks.push((regex, replacement.into()));
+        self
+    }
+
+    /// Runs a single instance of the simulation one step at a time (see
+    /// [`SimFlow::interactive`]), first under the control of `thunk` and then until no progress
+    /// is possible, and compares the trace of every step against the golden trace.
+    ///
+    /// When updating (see [`Self::update`]), the golden trace is written instead.
+    ///
+    /// # Panics
+    /// If the trace differs from the golden trace, or there is no golden trace and it is not
+    /// being updated.
+    pub fn check(
+        self,
+        flow: SimFlow<'_>,
+        thunk: impl AsyncFnOnce(&mut SimStepper) + RefUnwindSafe,
+    ) {
+        let steps = Mutex::new(vec![]);
+        flow.interactive(async |mut stepper| {
+            stepper.record_trace();
+            thunk(&mut stepper).await;
+            stepper.run_until_quiescent().await;
+            *steps.lock().unwrap() = stepper.take_trace();
+        });
+
+        let actual = self.render(steps.into_inner().unwrap());
+        if self.update {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent).unwrap();
+            }
+            std::fs::write(&self.path, &actual).unwrap();
+            eprintln!("Wrote golden trace `{}`.", self.path.display());
+            return;
+        }
+
+        let expected = match std::fs::read_to_string(&self.path) {
+            Ok(expected) => expected.replace("\r\n", "\n"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => panic!(
+                "Golden trace `{}` does not exist, rerun with `{}=1` to create it. Full trace:\n{}",
+                self.path.display(),
+                UPDATE_GOLDEN_ENV,
+                actual
+            ),
+            Err(e) => panic!(
+                "Failed to read golden trace `{}`: {}",
+                self.path.display(),
+                e
+            ),
+        };
+
+        if expected != actual {
+            let (mut expected_lines, mut actual_lines) = (expected.lines(), actual.lines());
+            let mut line = 1;
+            let (expected_line, actual_line) = loop {
+                match (expected_lines.next(), actual_lines.next()) {
+                    (Some(e), Some(a)) if e == a => line += 1,
+                    mismatch => break mismatch,
+                }
+            };
+            panic!(
+                "Simulation trace differs from golden trace `{}` at line {}:\n  expected: {}\n  actual:   {}\nRerun with `{}=1` to update the golden trace. Full trace:\n{}",
+                self.path.display(),
+                line,
+                expected_line.unwrap_or("<end of trace>"),
+                actual_line.unwrap_or("<end of trace>"),
+                UPDATE_GOLDEN_ENV,
+                actual
+            );
+        }
+    }
+
+    /// Joins the rendered `steps` into the text of the trace, without colors and with the masks
+    /// applied.
+    fn render(&self, steps: Vec<String>) -> String {
+        let ansi_escapes = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        let mut trace = String::new();
+        for step in steps {
+            let step = ansi_escapes.replace_all(&step, "");
+            for line in step.lines() {
+                trace.push_str(line.trim_end());
+                trace.push('\n');
+            }
+            trace.push('\n');
+        }
+        for (regex, replacement) in &self.masks {
+            trace = regex.replace_all(&trace, replacement.as_str()).into_owned();
+        }
+        trace
+    }
+}
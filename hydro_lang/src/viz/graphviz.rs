@@ -1,9 +1,14 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Write;
 
+use slotmap::SecondaryMap;
+
 use super::render::{
     HydroEdgeProp, HydroGraphWrite, HydroNodeType, HydroWriteConfig, IndentedGraphWriter,
 };
+use crate::compile::builder::ClockId;
+use crate::compile::ir::CollectionKind;
 use crate::location::{LocationKey, LocationType};
 use crate::viz::render::VizNodeKey;
 
@@ -12,21 +17,71 @@ pub fn escape_dot(string: &str, newline: &str) -> String {
     string.replace('"', "\\\"").replace('\n', newline)
 }
 
+/// Describes a [`CollectionKind`] like the type of the live collection, for tooltips.
+pub fn collection_kind_tooltip(collection_kind: &CollectionKind) -> String {
+    match collection_kind {
+        CollectionKind::Stream {
+            bound,
+            order,
+            retry,
+            element_type,
+        } => format!("Stream<{element_type:?}, {bound:?}, {order:?}, {retry:?}>"),
+        CollectionKind::Singleton {
+            bound,
+            element_type,
+        } => format!("Singleton<{element_type:?}, {bound:?}>"),
+        CollectionKind::Optional {
+            bound,
+            element_type,
+        } => format!("Optional<{element_type:?}, {bound:?}>"),
+        CollectionKind::KeyedStream {
+            bound,
+            value_order,
+            value_retry,
+            key_type,
+            value_type,
+        } => format!(
+            "KeyedStream<{key_type:?}, {value_type:?}, {bound:?}, {value_order:?}, {value_retry:?}>"
+        ),
+        CollectionKind::KeyedSingleton {
+            bound,
+            key_type,
+            value_type,
+        } => format!("KeyedSingleton<{key_type:?}, {value_type:?}, {bound:?}>"),
+    }
+}
+
 /// DOT/Graphviz graph writer for Hydro IR.
+///
+/// Nodes are grouped into a cluster per location and, within it, a nested cluster per tick.
+/// Edges which cross the network are drawn bold and dashed, and each node has a tooltip with the
+/// kind of live collection it outputs.
 pub struct HydroDot<'a, W> {
     base: IndentedGraphWriter<'a, W>,
+    /// The tick of each node which runs inside one.
+    node_ticks: SecondaryMap<VizNodeKey, ClockId>,
+    /// The location cluster currently being written.
+    current_location: Option<LocationKey>,
+    /// The nodes of the current location in each tick, written as nested clusters when the
+    /// location ends.
+    tick_nodes: BTreeMap<ClockId, Vec<VizNodeKey>>,
 }
 
 impl<'a, W> HydroDot<'a, W> {
     pub fn new(write: W) -> Self {
-        Self {
-            base: IndentedGraphWriter::new(write),
-        }
+        Self::from_base(IndentedGraphWriter::new(write))
     }
 
     pub fn new_with_config(write: W, config: HydroWriteConfig<'a>) -> Self {
+        Self::from_base(IndentedGraphWriter::new_with_config(write, config))
+    }
+
+    fn from_base(base: IndentedGraphWriter<'a, W>) -> Self {
         Self {
-            base: IndentedGraphWriter::new_with_config(write, config),
+            base,
+            node_ticks: SecondaryMap::new(),
+            current_location: None,
+            tick_nodes: BTreeMap::new(),
         }
     }
 }
@@ -92,7 +147,14 @@ where
         _location_id: Option<LocationKey>,
         _location_type: Option<LocationType>,
         _backtrace: Option<&crate::compile::ir::backtrace::Backtrace>,
+        _stable_id: Option<&str>,
+        tick: Option<ClockId>,
+        collection_kind: Option<&CollectionKind>,
     ) -> Result<(), Self::Err> {
+        if let Some(tick) = tick {
+            self.node_ticks.insert(node_id, tick);
+        }
+
         // Create the full label string using DebugExpr::Display for expressions
         let full_label = match node_label {
             super::render::NodeLabel::Static(s) => s.clone(),
@@ -129,6 +191,13 @@ where
             i = self.base.indent,
         )?;
         write!(self.base.write, ", shape=box, fillcolor=\"#f5f5f5\"")?;
+        if let Some(collection_kind) = collection_kind {
+            write!(
+                self.base.write,
+                ", tooltip=\"{}\"",
+                escape_dot(&collection_kind_tooltip(collection_kind), "&#10;")
+            )?;
+        }
         writeln!(self.base.write, "]")?;
         Ok(())
     }
@@ -150,18 +219,24 @@ where
 
         properties.push(format!("color=\"{}\"", style.color).into());
 
-        if style.line_width > 1 {
-            properties.push("style=\"bold\"".into());
+        let mut line_styles = Vec::new();
+        match style.line_pattern {
+            super::render::LinePattern::Dotted => line_styles.push("dotted"),
+            super::render::LinePattern::Dashed => line_styles.push("dashed"),
+            super::render::LinePattern::Solid => {}
         }
 
-        match style.line_pattern {
-            super::render::LinePattern::Dotted => {
-                properties.push("style=\"dotted\"".into());
-            }
-            super::render::LinePattern::Dashed => {
-                properties.push("style=\"dashed\"".into());
-            }
-            _ => {}
+        // network edges are the expensive ones, so make them stand out
+        if style.line_width > 1 || edge_properties.contains(&HydroEdgeProp::Network) {
+            line_styles.push("bold");
+        }
+        if edge_properties.contains(&HydroEdgeProp::Network) {
+            properties.push("penwidth=2".into());
+            properties.push("arrowhead=\"vee\"".into());
+        }
+
+        if !line_styles.is_empty() {
+            properties.push(format!("style=\"{}\"", line_styles.join(",")).into());
         }
 
         write!(
@@ -189,6 +264,7 @@ where
         location_key: LocationKey,
         location_type: LocationType,
     ) -> Result<(), Self::Err> {
+        self.current_location = Some(location_key);
         writeln!(
             self.base.write,
             "{b:i$}subgraph cluster_{location_key} {{",
@@ -232,6 +308,11 @@ where
     }
 
     fn write_node(&mut self, node_id: VizNodeKey) -> Result<(), Self::Err> {
+        if let Some(&tick) = self.node_ticks.get(node_id) {
+            self.tick_nodes.entry(tick).or_default().push(node_id);
+            return Ok(());
+        }
+
         writeln!(
             self.base.write,
             "{b:i$}n{node_id}",
@@ -241,6 +322,45 @@ where
     }
 
     fn write_location_end(&mut self) -> Result<(), Self::Err> {
+        let location_key = self.current_location.take().unwrap();
+        for (tick, node_ids) in std::mem::take(&mut self.tick_nodes) {
+            writeln!(
+                self.base.write,
+                "{b:i$}subgraph cluster_{location_key}_tick_{tick} {{",
+                b = "",
+                i = self.base.indent,
+            )?;
+            self.base.indent += 4;
+            writeln!(
+                self.base.write,
+                "{b:i$}label = \"Tick {tick}\"",
+                b = "",
+                i = self.base.indent
+            )?;
+            writeln!(
+                self.base.write,
+                "{b:i$}style=\"dashed,rounded\"",
+                b = "",
+                i = self.base.indent
+            )?;
+            writeln!(
+                self.base.write,
+                "{b:i$}color=\"#9ca3af\"",
+                b = "",
+                i = self.base.indent
+            )?;
+            for node_id in node_ids {
+                writeln!(
+                    self.base.write,
+                    "{b:i$}n{node_id}",
+                    b = "",
+                    i = self.base.indent
+                )?;
+            }
+            self.base.indent -= 4;
+            writeln!(self.base.write, "{b:i$}}}", b = "", i = self.base.indent)?;
+        }
+
         self.base.indent -= 4;
         writeln!(self.base.write, "{b:i$}}}", b = "", i = self.base.indent)
     }
@@ -0,0 +1,115 @@
+//! Tests for GraphViz graph generation with ticks and network edges
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use hydro_build_utils::insta;
+
+    use crate::Counter;
+    use crate::compile::builder::ClockId;
+    use crate::compile::ir::{BoundKind, CollectionKind, StreamOrder, StreamRetry};
+    use crate::location::{LocationKey, LocationType};
+    use crate::viz::graphviz::HydroDot;
+    use crate::viz::render::{
+        HydroEdgeProp, HydroGraphWrite, HydroNodeType, HydroWriteConfig, NodeLabel, VizNodeKey,
+    };
+
+    #[test]
+    fn test_tick_cluster_and_network_edge() {
+        let mut output = String::new();
+        let config = HydroWriteConfig::default();
+        let mut writer = HydroDot::new_with_config(&mut output, config);
+
+        let source_id = VizNodeKey::TEST_KEY_1;
+        let fold_id = VizNodeKey::TEST_KEY_2;
+        let network_id = VizNodeKey::TEST_KEY_3;
+
+        let process_key = LocationKey::TEST_KEY_1;
+        let cluster_key = LocationKey::TEST_KEY_2;
+
+        let tick_id = Counter::<ClockId>::default().get_and_increment();
+        let element_type: syn::Type = syn::parse_quote!(i32);
+
+        writer.write_prologue().unwrap();
+
+        writer
+            .write_node_definition(
+                source_id,
+                &NodeLabel::Static("source".to_owned()),
+                HydroNodeType::Source,
+                Some(process_key),
+                Some(LocationType::Process),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        // A node inside a tick, which is nested in its own cluster
+        writer
+            .write_node_definition(
+                fold_id,
+                &NodeLabel::Static("fold".to_owned()),
+                HydroNodeType::Aggregation,
+                Some(process_key),
+                Some(LocationType::Process),
+                None,
+                None,
+                Some(tick_id),
+                Some(&CollectionKind::Stream {
+                    bound: BoundKind::Unbounded,
+                    order: StreamOrder::TotalOrder,
+                    retry: StreamRetry::ExactlyOnce,
+                    element_type: element_type.into(),
+                }),
+            )
+            .unwrap();
+
+        writer
+            .write_node_definition(
+                network_id,
+                &NodeLabel::Static("network".to_owned()),
+                HydroNodeType::Network,
+                Some(cluster_key),
+                Some(LocationType::Cluster),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        writer
+            .write_location_start(process_key, LocationType::Process)
+            .unwrap();
+        writer.write_node(source_id).unwrap();
+        writer.write_node(fold_id).unwrap();
+        writer.write_location_end().unwrap();
+
+        writer
+            .write_location_start(cluster_key, LocationType::Cluster)
+            .unwrap();
+        writer.write_node(network_id).unwrap();
+        writer.write_location_end().unwrap();
+
+        let local_props = HashSet::from([
+            HydroEdgeProp::Stream,
+            HydroEdgeProp::Unbounded,
+            HydroEdgeProp::TotalOrder,
+        ]);
+        writer
+            .write_edge(source_id, fold_id, &local_props, None)
+            .unwrap();
+
+        let network_props = HashSet::from([HydroEdgeProp::Stream, HydroEdgeProp::Network]);
+        writer
+            .write_edge(fold_id, network_id, &network_props, None)
+            .unwrap();
+
+        writer.write_epilogue().unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+}
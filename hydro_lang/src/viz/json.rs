@@ -8,8 +8,9 @@ use super::render::{
     GraphWriteError, HydroEdgeProp, HydroGraphWrite, HydroNodeType, HydroWriteConfig,
     write_hydro_ir_json,
 };
-use crate::compile::ir::HydroRoot;
+use crate::compile::builder::ClockId;
 use crate::compile::ir::backtrace::Backtrace;
+use crate::compile::ir::{CollectionKind, HydroRoot};
 use crate::location::{LocationKey, LocationType};
 use crate::viz::render::VizNodeKey;
 
@@ -41,6 +42,9 @@ struct NodeData {
     #[serde(rename = "locationType")]
     location_type: Option<LocationType>,
     backtrace: serde_json::Value,
+    /// Content-based ID of the operator, which is preserved across edits of the program.
+    #[serde(rename = "stableId", skip_serializing_if = "Option::is_none")]
+    stable_id: Option<String>,
 }
 
 /// A serializable node for JSON output.
@@ -320,6 +324,9 @@ where
         location_key: Option<LocationKey>,
         location_type: Option<LocationType>,
         backtrace: Option<&Backtrace>,
+        stable_id: Option<&str>,
+        _tick: Option<ClockId>,
+        _collection_kind: Option<&CollectionKind>,
     ) -> Result<(), Self::Err> {
         // Create the full label string using DebugExpr::Display for expressions
         let full_label = match node_label {
@@ -397,6 +404,7 @@ where
                 location_key,
                 location_type,
                 backtrace: backtrace_json,
+                stable_id: stable_id.map(str::to_owned),
             },
         };
         self.nodes
@@ -35,6 +35,9 @@ mod tests {
                 Some(loc_key_1),
                 Some(LocationType::Process),
                 None,
+                None,
+                None,
+                None,
             )
             .unwrap();
 
@@ -47,6 +50,9 @@ mod tests {
                 Some(loc_key_1),
                 Some(LocationType::Process),
                 None,
+                None,
+                None,
+                None,
             )
             .unwrap();
 
@@ -84,6 +90,9 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
             )
             .unwrap();
 
@@ -127,6 +136,9 @@ mod tests {
             Some(loc_key_1),
             Some(LocationType::Process),
             None,
+            None,
+            None,
+            None,
         )
         .unwrap();
         w1.write_node_definition(
@@ -136,6 +148,9 @@ mod tests {
             Some(loc_key_2),
             Some(LocationType::Process),
             None,
+            None,
+            None,
+            None,
         )
         .unwrap();
         w1.write_edge(node_id_1, node_id_2, &edge_props, None)
@@ -151,6 +166,9 @@ mod tests {
             Some(loc_key_2),
             Some(LocationType::Process),
             None,
+            None,
+            None,
+            None,
         )
         .unwrap();
         w2.write_node_definition(
@@ -160,6 +178,9 @@ mod tests {
             Some(loc_key_1),
             Some(LocationType::Process),
             None,
+            None,
+            None,
+            None,
         )
         .unwrap();
         w2.write_edge(node_id_1, node_id_2, &edge_props, None)
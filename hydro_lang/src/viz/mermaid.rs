@@ -4,6 +4,8 @@ use std::fmt::Write;
 use super::render::{
     HydroEdgeProp, HydroGraphWrite, HydroNodeType, HydroWriteConfig, IndentedGraphWriter,
 };
+use crate::compile::builder::ClockId;
+use crate::compile::ir::CollectionKind;
 use crate::location::{LocationKey, LocationType};
 use crate::viz::render::VizNodeKey;
 
@@ -74,6 +76,9 @@ where
         _location_id: Option<LocationKey>,
         _location_type: Option<LocationType>,
         _backtrace: Option<&crate::compile::ir::backtrace::Backtrace>,
+        _stable_id: Option<&str>,
+        _tick: Option<ClockId>,
+        _collection_kind: Option<&CollectionKind>,
     ) -> Result<(), Self::Err> {
         // Create the full label string using DebugExpr::Display for expressions
         let full_label = match node_label {
@@ -8,6 +8,8 @@ pub mod json;
 pub mod mermaid;
 pub mod render;
 
+#[cfg(test)]
+mod graphviz_test;
 #[cfg(test)]
 mod json_test;
 
@@ -11,8 +11,12 @@ pub use super::graphviz::{HydroDot, escape_dot};
 pub use super::json::HydroJson;
 // Re-export specific implementations
 pub use super::mermaid::{HydroMermaid, escape_mermaid};
+use crate::compile::builder::ClockId;
 use crate::compile::ir::backtrace::Backtrace;
-use crate::compile::ir::{DebugExpr, HydroIrMetadata, HydroNode, HydroRoot, HydroSource};
+use crate::compile::ir::{
+    CollectionKind, CounterKind, DebugExpr, HydroIrMetadata, HydroIrOpMetadata, HydroNode,
+    HydroRoot, HydroSource,
+};
 use crate::location::dynamic::LocationId;
 use crate::location::{LocationKey, LocationType};
 
@@ -103,7 +107,9 @@ pub trait HydroGraphWrite {
     /// Begin the graph. First method called.
     fn write_prologue(&mut self) -> Result<(), Self::Err>;
 
-    /// Write a node definition with styling.
+    /// Write a node definition with styling. `tick` is the tick the node runs in, if any, and
+    /// `collection_kind` is the kind of live collection it outputs, if known.
+    #[expect(clippy::too_many_arguments, reason = "node attributes")]
     fn write_node_definition(
         &mut self,
         node_id: VizNodeKey,
@@ -112,6 +118,9 @@ pub trait HydroGraphWrite {
         location_key: Option<LocationKey>,
         location_type: Option<LocationType>,
         backtrace: Option<&Backtrace>,
+        stable_id: Option<&str>,
+        tick: Option<ClockId>,
+        collection_kind: Option<&CollectionKind>,
     ) -> Result<(), Self::Err>;
 
     /// Write an edge between nodes with optional labeling.
@@ -518,6 +527,12 @@ pub struct HydroGraphNode {
     pub node_type: HydroNodeType,
     pub location_key: Option<LocationKey>,
     pub backtrace: Option<Backtrace>,
+    /// The [`HydroIrOpMetadata::stable_id`] of the operator this node was rendered from.
+    pub stable_id: Option<String>,
+    /// The tick the operator runs in, if it is inside one.
+    pub tick: Option<ClockId>,
+    /// The kind of live collection the operator outputs, if known.
+    pub collection_kind: Option<CollectionKind>,
 }
 
 slotmap::new_key_type! {
@@ -555,6 +570,10 @@ impl VizNodeKey {
     /// A key for testing with index 2.
     #[cfg(test)]
     pub const TEST_KEY_2: Self = Self(slotmap::KeyData::from_ffi(0x0000008F00000002)); // `2v143`
+
+    /// A key for testing with index 3.
+    #[cfg(test)]
+    pub const TEST_KEY_3: Self = Self(slotmap::KeyData::from_ffi(0x0000008F00000003)); // `3v143`
 }
 
 /// Edge information in the Hydro graph.
@@ -600,6 +619,28 @@ impl HydroGraphStructure {
             node_type,
             location_key,
             backtrace,
+            stable_id: None,
+            tick: None,
+            collection_kind: None,
+        })
+    }
+
+    /// Add a node for an operator, extracting its backtrace and stable ID.
+    pub fn add_node_with_op_metadata(
+        &mut self,
+        label: NodeLabel,
+        node_type: HydroNodeType,
+        location_key: Option<LocationKey>,
+        op_metadata: Option<&HydroIrOpMetadata>,
+    ) -> VizNodeKey {
+        self.nodes.insert(HydroGraphNode {
+            label,
+            node_type,
+            location_key,
+            backtrace: op_metadata.map(|op| op.backtrace.clone()),
+            stable_id: op_metadata.and_then(|op| op.stable_id.clone()),
+            tick: None,
+            collection_kind: None,
         })
     }
 
@@ -611,8 +652,17 @@ impl HydroGraphStructure {
         metadata: &HydroIrMetadata,
     ) -> VizNodeKey {
         let location_key = Some(setup_location(self, metadata));
-        let backtrace = Some(metadata.op.backtrace.clone());
-        self.add_node_with_backtrace(label, node_type, location_key, backtrace)
+        let node_id =
+            self.add_node_with_op_metadata(label, node_type, location_key, Some(&metadata.op));
+        self.set_node_collection(node_id, metadata);
+        node_id
+    }
+
+    /// Records the tick and collection kind from `metadata` on an existing node.
+    fn set_node_collection(&mut self, node_id: VizNodeKey, metadata: &HydroIrMetadata) {
+        let node = &mut self.nodes[node_id];
+        node.tick = tick_of(&metadata.location_id);
+        node.collection_kind = Some(metadata.collection_kind.clone());
     }
 
     pub fn add_edge(
@@ -704,6 +754,15 @@ pub fn extract_short_label(full_label: &str) -> String {
     }
 }
 
+/// The tick that an operator at `location_id` runs in, if it is inside one.
+fn tick_of(location_id: &LocationId) -> Option<ClockId> {
+    match location_id {
+        LocationId::Tick(tick, _) => Some(*tick),
+        LocationId::Atomic(tick) => tick_of(tick),
+        LocationId::Process(_) | LocationId::Cluster(_) => None,
+    }
+}
+
 /// Helper function to set up location in structure from metadata.
 fn setup_location(structure: &mut HydroGraphStructure, metadata: &HydroIrMetadata) -> LocationKey {
     let root = metadata.location_id.root();
@@ -776,6 +835,9 @@ where
             node.location_key,
             location_type,
             node.backtrace.as_ref(),
+            node.stable_id.as_deref(),
+            node.tick,
+            node.collection_kind.as_ref(),
         )?;
     }
 
@@ -848,12 +910,15 @@ impl HydroRoot {
             };
 
             let location_key = effective_metadata.map(|m| setup_location(structure, m));
-            let sink_id = structure.add_node_with_backtrace(
+            let sink_id = structure.add_node_with_op_metadata(
                 label,
                 HydroNodeType::Sink,
                 location_key,
-                effective_metadata.map(|m| m.op.backtrace.clone()),
+                effective_metadata.map(|m| &m.op),
             );
+            if let Some(metadata) = effective_metadata {
+                structure.set_node_collection(sink_id, metadata);
+            }
 
             // Extract semantic tags from input metadata
             let input_metadata = input.metadata();
@@ -1228,6 +1293,11 @@ impl HydroNode {
                 input: inner,
                 metadata,
             }
+            | HydroNode::UniqueWithin {
+                input: inner,
+                metadata,
+                ..
+            }
             | HydroNode::ResolveFutures {
                 input: inner,
                 metadata,
@@ -1239,6 +1309,11 @@ impl HydroNode {
             | HydroNode::ResolveFuturesOrdered {
                 input: inner,
                 metadata,
+            }
+            | HydroNode::RawDfir {
+                input: inner,
+                metadata,
+                ..
             } => build_simple_transform(TransformParams {
                 structure,
                 seen_tees,
@@ -1253,6 +1328,10 @@ impl HydroNode {
             HydroNode::Sort {
                 input: inner,
                 metadata,
+            }
+            | HydroNode::LatticeFold {
+                input: inner,
+                metadata,
             } => build_simple_transform(TransformParams {
                 structure,
                 seen_tees,
@@ -1270,6 +1349,9 @@ impl HydroNode {
             | HydroNode::Filter { f, input, metadata }
             | HydroNode::FlatMap { f, input, metadata }
             | HydroNode::FlatMapStreamBlocking { f, input, metadata }
+            | HydroNode::MapAsyncCached {
+                f, input, metadata, ..
+            }
             | HydroNode::FilterMap { f, input, metadata }
             | HydroNode::Inspect { f, input, metadata } => build_single_expr_transform(
                 TransformParams {
@@ -1450,12 +1532,10 @@ impl HydroNode {
             } => {
                 let input_id = input.build_graph_structure(structure, seen_tees, config);
                 let watermark_id = watermark.build_graph_structure(structure, seen_tees, config);
-                let location_key = Some(setup_location(structure, metadata));
-                let join_node_id = structure.add_node_with_backtrace(
+                let join_node_id = structure.add_node_with_metadata(
                     NodeLabel::Static(extract_op_name(self.print_root())),
                     HydroNodeType::Join,
-                    location_key,
-                    Some(metadata.op.backtrace.clone()),
+                    metadata,
                 );
 
                 // Extract semantic tags for input edge
@@ -1480,11 +1560,10 @@ impl HydroNode {
                     Some("watermark".to_owned()),
                 );
 
-                let node_id = structure.add_node_with_backtrace(
+                let node_id = structure.add_node_with_metadata(
                     NodeLabel::with_exprs(extract_op_name(self.print_root()), vec![f.expr.clone()]),
                     HydroNodeType::Aggregation,
-                    location_key,
-                    Some(metadata.op.backtrace.clone()),
+                    metadata,
                 );
 
                 // Edge from join to aggregation node
@@ -1542,12 +1621,13 @@ impl HydroNode {
                 }
                 label.push(')');
 
-                let network_id = structure.add_node_with_backtrace(
+                let network_id = structure.add_node_with_op_metadata(
                     NodeLabel::Static(label),
                     HydroNodeType::Network,
                     Some(to_location_key),
-                    Some(metadata.op.backtrace.clone()),
+                    Some(&metadata.op),
                 );
+                structure.set_node_collection(network_id, metadata);
 
                 // Extract semantic tags for network edge
                 let input_metadata = input.metadata();
@@ -1603,12 +1683,10 @@ impl HydroNode {
             } => {
                 let first_id = first.build_graph_structure(structure, seen_tees, config);
                 let second_id = second.build_graph_structure(structure, seen_tees, config);
-                let location_key = Some(setup_location(structure, metadata));
-                let chain_id = structure.add_node_with_backtrace(
+                let chain_id = structure.add_node_with_metadata(
                     NodeLabel::Static(extract_op_name(self.print_root())),
                     HydroNodeType::Transform,
-                    location_key,
-                    Some(metadata.op.backtrace.clone()),
+                    metadata,
                 );
 
                 // Extract semantic tags for first edge
@@ -1639,12 +1717,10 @@ impl HydroNode {
             HydroNode::VersionedNetworkFork {
                 senders, metadata, ..
             } => {
-                let location_key = Some(setup_location(structure, metadata));
-                let fork_id = structure.add_node_with_backtrace(
+                let fork_id = structure.add_node_with_metadata(
                     NodeLabel::Static(extract_op_name(self.print_root())),
                     HydroNodeType::NonDeterministic,
-                    location_key,
-                    Some(metadata.op.backtrace.clone()),
+                    metadata,
                 );
 
                 for (version, sender, _serialize) in senders {
@@ -1681,12 +1757,10 @@ impl HydroNode {
                     built
                 };
 
-                let branch_location = Some(setup_location(structure, metadata));
-                let branch_id = structure.add_node_with_backtrace(
+                let branch_id = structure.add_node_with_metadata(
                     NodeLabel::Static(extract_op_name(self.print_root())),
                     HydroNodeType::NonDeterministic,
-                    branch_location,
-                    Some(metadata.op.backtrace.clone()),
+                    metadata,
                 );
 
                 add_edge_with_metadata(
@@ -1708,12 +1782,10 @@ impl HydroNode {
             } => {
                 let first_id = first.build_graph_structure(structure, seen_tees, config);
                 let second_id = second.build_graph_structure(structure, seen_tees, config);
-                let location_key = Some(setup_location(structure, metadata));
-                let chain_id = structure.add_node_with_backtrace(
+                let chain_id = structure.add_node_with_metadata(
                     NodeLabel::Static(extract_op_name(self.print_root())),
                     HydroNodeType::Transform,
-                    location_key,
-                    Some(metadata.op.backtrace.clone()),
+                    metadata,
                 );
 
                 // Extract semantic tags for first edge
@@ -1743,12 +1815,11 @@ impl HydroNode {
 
             HydroNode::Counter {
                 tag: _,
-                prefix: _,
-                duration,
+                kind,
                 input,
                 metadata,
-            } => build_single_expr_transform(
-                TransformParams {
+            } => {
+                let params = TransformParams {
                     structure,
                     seen_tees,
                     config,
@@ -1756,9 +1827,16 @@ impl HydroNode {
                     metadata,
                     op_name: extract_op_name(self.print_root()),
                     node_type: HydroNodeType::Transform,
-                },
-                duration,
-            ),
+                };
+                match kind {
+                    CounterKind::Print { duration, .. } => {
+                        build_single_expr_transform(params, duration)
+                    }
+                    CounterKind::Metered | CounterKind::Breakpoint => {
+                        build_simple_transform(params)
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,180 @@
+//! Macros for the `hydro_lang` crate.
+//!
+//! See [`[derive(HydroMessage)]`](HydroMessage).
+#![warn(missing_docs)]
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{DeriveInput, Ident, LitInt, LitStr, Type, parse_macro_input};
+
+/// Tokens to reference the `hydro_lang` crate.
+fn root() -> TokenStream {
+    use std::env::{VarError, var as env_var};
+
+    use proc_macro_crate::FoundCrate;
+
+    let hydro_lang_crate_name = env!("CARGO_PKG_NAME").strip_suffix("_macro").unwrap();
+    let hydro_lang_crate_ident = hydro_lang_crate_name.replace('-', "_");
+    let hydro_lang_crate = proc_macro_crate::crate_name(hydro_lang_crate_name)
+        .unwrap_or_else(|_| panic!("`{hydro_lang_crate_name}` should be present in `Cargo.toml`"));
+    match hydro_lang_crate {
+        FoundCrate::Itself => {
+            if Err(VarError::NotPresent) == env_var("CARGO_BIN_NAME")
+                && Ok(&*hydro_lang_crate_ident) == env_var("CARGO_CRATE_NAME").as_deref()
+            {
+                // In the crate itself, including unit tests.
+                quote! { crate }
+            } else {
+                // In an integration test, example, bench, etc.
+                let ident = Ident::new(&hydro_lang_crate_ident, Span::call_site());
+                quote! { ::#ident }
+            }
+        }
+        FoundCrate::Name(name) => {
+            let ident = Ident::new(&name, Span::call_site());
+            quote! { ::#ident }
+        }
+    }
+}
+
+/// The contents of the `#[hydro_message(...)]` attribute.
+struct MessageArgs {
+    version: LitInt,
+    previous: Option<Type>,
+    name: Option<LitStr>,
+}
+
+impl MessageArgs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut version = None;
+        let mut previous = None;
+        let mut name = None;
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("hydro_message"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("version") {
+                    version = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("previous") {
+                    previous = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("name") {
+                    name = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("expected `version`, `previous`, or `name`"));
+                }
+                Ok(())
+            })?;
+        }
+        let version = version.ok_or_else(|| {
+            syn::Error::new_spanned(
+                &input.ident,
+                "`#[derive(HydroMessage)]` requires a `#[hydro_message(version = N)]` attribute",
+            )
+        })?;
+        Ok(Self {
+            version,
+            previous,
+            name,
+        })
+    }
+}
+
+/// Derives `HydroMessage`, so that the type can be sent over the network in a versioned envelope.
+///
+/// The version of the type is set with `#[hydro_message(version = N)]`. The attribute may also
+/// specify `previous = PreviousType`, the previous version of the message type, which must
+/// implement `HydroMessage` itself and be convertible to and from this type with [`From`]
+/// implementations, and `name = "Name"`, which defaults to the name of the type.
+///
+/// ```rust,ignore
+/// #[derive(Serialize, Deserialize, HydroMessage)]
+/// #[hydro_message(version = 2, previous = RequestV1)]
+/// struct Request {
+///     key: String,
+///     deadline_ms: Option<u64>,
+/// }
+/// ```
+#[proc_macro_derive(HydroMessage, attributes(hydro_message))]
+pub fn derive_hydro_message(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let args = match MessageArgs::parse(&input) {
+        Ok(args) => args,
+        Err(err) => return err.into_compile_error().into(),
+    };
+
+    let root = root();
+    let versioned = quote! { #root::networking::versioned };
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let version = &args.version;
+    let name = args
+        .name
+        .unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+
+    let (decode_older, encode_older) = match &args.previous {
+        Some(previous) => (
+            quote! {
+                <#previous as #versioned::HydroMessage>::decode_version(version, payload)
+                    .map(<Self as ::std::convert::From<#previous>>::from)
+            },
+            quote! {
+                <#previous as #versioned::HydroMessage>::encode_version(
+                    <#previous as ::std::convert::From<Self>>::from(self),
+                    version,
+                )
+            },
+        ),
+        None => {
+            let unsupported = quote! {
+                ::std::result::Result::Err(#versioned::VersionError::UnsupportedVersion {
+                    name: <Self as #versioned::HydroMessage>::NAME,
+                    version,
+                })
+            };
+            (unsupported.clone(), unsupported)
+        }
+    };
+
+    quote! {
+        impl #impl_generics #versioned::HydroMessage for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+
+            const VERSION: u32 = #version;
+
+            fn decode_version(
+                version: u32,
+                payload: &[u8],
+            ) -> ::std::result::Result<Self, #versioned::VersionError> {
+                if version == <Self as #versioned::HydroMessage>::VERSION {
+                    #versioned::decode_payload(payload)
+                } else if version < <Self as #versioned::HydroMessage>::VERSION {
+                    #decode_older
+                } else {
+                    ::std::result::Result::Err(#versioned::VersionError::UnsupportedVersion {
+                        name: <Self as #versioned::HydroMessage>::NAME,
+                        version,
+                    })
+                }
+            }
+
+            fn encode_version(
+                self,
+                version: u32,
+            ) -> ::std::result::Result<::std::vec::Vec<u8>, #versioned::VersionError> {
+                if version == <Self as #versioned::HydroMessage>::VERSION {
+                    #versioned::encode_payload(&self)
+                } else if version < <Self as #versioned::HydroMessage>::VERSION {
+                    #encode_older
+                } else {
+                    ::std::result::Result::Err(#versioned::VersionError::UnsupportedVersion {
+                        name: <Self as #versioned::HydroMessage>::NAME,
+                        version,
+                    })
+                }
+            }
+        }
+    }
+    .into()
+}
@@ -0,0 +1,802 @@
+//! A replicated log built on Multi-Paxos, where every member of a [`Cluster`] acts as both a
+//! proposer and an acceptor.
+//!
+//! Unlike the Paxos implementation in `hydro_test`, which places proposers and acceptors on
+//! separate clusters for benchmarking, this module exposes a single entry point,
+//! [`replicate_log`], that can be dropped into an application which only needs a totally
+//! ordered log of commands shared by `2f + 1` replicas.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+
+use hydro_lang::live_collections::sliced::yield_atomic;
+use hydro_lang::live_collections::stream::{AtLeastOnce, NoOrder, TotalOrder};
+use hydro_lang::location::cluster::CLUSTER_SELF_ID;
+use hydro_lang::location::{Location, MemberId, TaglessMemberId};
+use hydro_lang::networking::NetworkFor;
+use hydro_lang::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::quorum::{collect_quorum, collect_quorum_with_response};
+use crate::request_response::join_responses;
+
+/// Configuration for [`replicate_log`].
+#[derive(Clone, Copy, Debug)]
+pub struct ConsensusConfig {
+    /// Maximum number of faulty replicas. The cluster must have `2f + 1` members.
+    pub f: usize,
+    /// How often the leader sends heartbeats to the other replicas, in milliseconds.
+    pub heartbeat_interval_ms: u64,
+    /// How long a replica waits without a heartbeat before trying to become the leader, in
+    /// milliseconds. Replicas additionally stagger their first election by their member ID.
+    pub election_timeout_ms: u64,
+}
+
+/// A payload that can be sequenced by [`replicate_log`].
+pub trait LogPayload: Serialize + DeserializeOwned + PartialEq + Eq + Clone + Debug {}
+impl<T: Serialize + DeserializeOwned + PartialEq + Eq + Clone + Debug> LogPayload for T {}
+
+/// A Paxos ballot, which identifies a (possibly failed) leadership term.
+///
+/// Ballots are totally ordered by their number, with ties broken by the ID of the replica that
+/// created them.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Debug, Hash)]
+pub struct Ballot {
+    pub num: u32,
+    pub leader: TaglessMemberId,
+}
+
+/// An entry in the log of an acceptor.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogValue<P> {
+    pub ballot: Ballot,
+    pub value: Option<P>, // `None` fills a hole in the log
+}
+
+/// A request from the leader to accept a value at a slot.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct P2a<P> {
+    pub ballot: Ballot,
+    pub slot: usize,
+    pub value: Option<P>,
+}
+
+/// Sequences `proposals` made at any replica into a log that is replicated across all members
+/// of `replicas`, tolerating up to [`ConsensusConfig::f`] failed replicas.
+///
+/// Proposals are forwarded to the latest leader known to the replica that received them. The
+/// leader assigns each proposal a slot and commits it once a quorum of `f + 1` replicas has
+/// accepted it, after which the committed entry is broadcast to every replica.
+///
+/// Returns a stream of ballots, which emits a new ballot at a replica whenever it becomes the
+/// leader (this can be used as a hook to reconfigure the application, such as redirecting
+/// clients), and a stream of committed `(slot, payload)` entries at every replica. A payload of
+/// `None` is a hole that was filled by a new leader.
+///
+/// # Non-Determinism
+/// Which replica becomes the leader is non-deterministic. While leadership is changing,
+/// proposals may be dropped, and an entry may be committed more than once (always with the
+/// same payload). Committed entries at a slot never change across replicas or leaders.
+pub fn replicate_log<'a, R: 'a, P: LogPayload>(
+    replicas: &Cluster<'a, R>,
+    proposals: Stream<P, Cluster<'a, R>, Unbounded>,
+    config: ConsensusConfig,
+    nondet_leader: NonDet,
+) -> (
+    Stream<Ballot, Cluster<'a, R>, Unbounded>,
+    Stream<(usize, Option<P>), Cluster<'a, R>, Unbounded, NoOrder, AtLeastOnce>,
+) {
+    replicate_log_via(
+        replicas,
+        proposals,
+        config,
+        || TCP.fail_stop().bincode(),
+        nondet_leader,
+    )
+}
+
+/// The networks that [`replicate_log`] can send its messages over.
+trait ConsensusNetwork<P>:
+    NetworkFor<Ballot>
+    + NetworkFor<(Ballot, Result<HashMap<usize, LogValue<P>>, Ballot>)>
+    + NetworkFor<P>
+    + NetworkFor<P2a<P>>
+    + NetworkFor<((usize, Ballot), Result<(), Ballot>)>
+    + NetworkFor<(usize, Option<P>)>
+{
+}
+
+impl<P, N> ConsensusNetwork<P> for N where
+    N: NetworkFor<Ballot>
+        + NetworkFor<(Ballot, Result<HashMap<usize, LogValue<P>>, Ballot>)>
+        + NetworkFor<P>
+        + NetworkFor<P2a<P>>
+        + NetworkFor<((usize, Ballot), Result<(), Ballot>)>
+        + NetworkFor<(usize, Option<P>)>
+{
+}
+
+/// Like [`replicate_log`], but sends every message over a network created by `network`, so that
+/// tests can simulate networks which drop messages.
+fn replicate_log_via<'a, R: 'a, P: LogPayload, N: ConsensusNetwork<P>>(
+    replicas: &Cluster<'a, R>,
+    proposals: Stream<P, Cluster<'a, R>, Unbounded>,
+    config: ConsensusConfig,
+    network: impl Fn() -> N,
+    nondet_leader: NonDet,
+) -> (
+    Stream<Ballot, Cluster<'a, R>, Unbounded>,
+    Stream<(usize, Option<P>), Cluster<'a, R>, Unbounded, NoOrder, AtLeastOnce>,
+) {
+    let f = config.f;
+    let tick = replicas.tick();
+
+    let (heartbeats_complete, heartbeats) =
+        replicas.forward_ref::<Stream<Ballot, _, Unbounded, NoOrder, AtLeastOnce>>();
+    let (rejections_complete, rejections) =
+        replicas.forward_ref::<Stream<Ballot, _, Unbounded, NoOrder>>();
+    let (is_leader_complete, is_leader_forward_ref) = tick.forward_ref::<Singleton<bool, _, _>>();
+    let (accepted_log_complete, accepted_log) =
+        tick.forward_ref::<Singleton<HashMap<usize, LogValue<P>>, _, _>>();
+
+    let received_max_ballot = rejections
+        .merge_unordered(heartbeats.clone())
+        .max()
+        .unwrap_or(
+            replicas
+                .singleton(q!(Ballot {
+                    num: 0,
+                    leader: TaglessMemberId::from_raw_id(0)
+                }))
+                .into(),
+        );
+
+    let (ballot, has_largest_ballot) = ballot_calc(received_max_ballot.snapshot(
+        &tick,
+        nondet!(
+            /// A stale max ballot might result in us failing to become the leader, but which
+            /// replica becomes the leader is non-deterministic anyway.
+            nondet_leader
+        ),
+    ));
+
+    let (leader_heartbeats, trigger_election) = leader_heartbeat(
+        replicas,
+        &tick,
+        is_leader_forward_ref,
+        ballot.clone(),
+        config,
+        network(),
+        nondet!(
+            /// Delayed heartbeats may lead to additional election attempts, which only affects
+            /// which replica is elected.
+            nondet_leader
+        ),
+    );
+    heartbeats_complete.complete(leader_heartbeats);
+
+    // Phase 1: acceptors promise to ignore smaller ballots and reply with their logs.
+    let p1a = ballot
+        .clone()
+        .filter_if(trigger_election)
+        .all_ticks()
+        .broadcast(
+            replicas,
+            network(),
+            nondet!(/** replicas that join later will only observe later ballots */),
+        )
+        .values()
+        .batch(
+            &tick,
+            nondet!(
+                /// Batching may reject a ballot that is batched with a larger one, which only
+                /// affects which replica is elected.
+                nondet_leader
+            ),
+        );
+
+    let acceptor_max_ballot = p1a
+        .clone()
+        .across_ticks(|s| s.max())
+        .unwrap_or(tick.singleton(q!(Ballot {
+            num: 0,
+            leader: TaglessMemberId::from_raw_id(0)
+        })));
+
+    let p1b = p1a
+        .cross_singleton(acceptor_max_ballot.clone())
+        .cross_singleton(accepted_log)
+        .map(q!(|((ballot, max_ballot), log)| (
+            MemberId::from_tagless(ballot.leader.clone()),
+            (
+                ballot.clone(),
+                if ballot == max_ballot {
+                    Ok(log)
+                } else {
+                    Err(max_ballot)
+                }
+            )
+        )))
+        .all_ticks()
+        .demux(replicas, network())
+        .values();
+
+    let (p1b_quorums, p1b_fails) = collect_quorum_with_response(p1b, f + 1, 2 * f + 1);
+
+    let quorum_logs = p1b_quorums
+        .into_keyed()
+        .assume_ordering::<TotalOrder>(nondet!(/** we use `flatten_unordered` later */))
+        .fold_early_stop(
+            q!(|| vec![]),
+            q!(move |logs, log| {
+                logs.push(log);
+                logs.len() >= f + 1
+            }),
+        )
+        .get_max_key()
+        .snapshot(
+            &tick,
+            nondet!(
+                /// A stale quorum only delays us realizing that we are the leader, which does
+                /// not affect safety.
+            ),
+        )
+        .zip(ballot.clone())
+        .filter_map(q!(|((quorum_ballot, logs), my_ballot)| {
+            if quorum_ballot == my_ballot {
+                Some(logs)
+            } else {
+                None
+            }
+        }));
+
+    let is_leader = quorum_logs.clone().is_some().and(has_largest_ballot);
+    is_leader_complete.complete(is_leader.clone());
+
+    let was_not_leader = is_leader
+        .clone()
+        .map(q!(|is_leader| is_leader.then_some(())))
+        .into_optional()
+        .defer_tick()
+        .is_none();
+    let just_became_leader = is_leader.clone().and(was_not_leader);
+    let elected = ballot
+        .clone()
+        .filter_if(just_became_leader.clone())
+        .all_ticks();
+
+    // Phase 2: the leader re-proposes values it learned from phase 1, then sequences new
+    // proposals after them.
+    let (recovered, max_recovered_slot) = recover_log(
+        quorum_logs
+            .flatten_unordered()
+            .filter_if(just_became_leader.clone()),
+    );
+
+    let known_leader = heartbeats.max();
+    let forwarded = sliced! {
+        let mut unsent = use::state_null::<Stream<_, _, _, TotalOrder>>();
+
+        let batch = use(proposals, nondet!(/** see below */));
+        let leader = use(known_leader, nondet!(
+            /// Proposals may be forwarded to a stale leader, which will drop them. This is
+            /// documented non-determinism.
+            nondet_leader
+        ));
+
+        let all_proposals = unsent.chain(batch);
+        unsent = all_proposals.clone().filter_if(leader.clone().is_none());
+        all_proposals.cross_singleton(leader)
+    }
+    .map(q!(|(payload, leader)| (
+        MemberId::from_tagless(leader.leader),
+        payload
+    )))
+    .demux(replicas, network())
+    .values()
+    .assume_ordering::<TotalOrder>(nondet!(
+        /// proposals from different replicas are arbitrarily interleaved at the leader
+        nondet_leader
+    ));
+
+    let indexed = index_payloads(
+        max_recovered_slot,
+        forwarded
+            .batch(
+                &tick,
+                nondet!(
+                    /// Batching determines which slots proposals are assigned, which only
+                    /// matters while the leader is changing.
+                    nondet_leader
+                ),
+            )
+            .filter_if(is_leader.clone()),
+    );
+
+    let to_accept = indexed
+        .map(q!(|(slot, payload)| (slot, Some(payload))))
+        .chain(recovered)
+        .cross_singleton(ballot)
+        .map(q!(|((slot, value), ballot)| ((slot, ballot), value)))
+        .filter_if(is_leader);
+
+    let p2a = to_accept
+        .clone()
+        .all_ticks()
+        .map(q!(|((slot, ballot), value)| P2a {
+            ballot,
+            slot,
+            value
+        }))
+        .broadcast(
+            replicas,
+            network(),
+            nondet!(/** replicas that join later only accept later slots */),
+        )
+        .values()
+        .batch(
+            &tick,
+            nondet!(
+                /// We persist accepted values before replying, so batch boundaries do not
+                /// affect the eventual log.
+            ),
+        );
+
+    let log = p2a
+        .clone()
+        .cross_singleton(acceptor_max_ballot.clone())
+        .filter_map(q!(|(p2a, max_ballot)| {
+            if p2a.ballot >= max_ballot {
+                Some((
+                    p2a.slot,
+                    LogValue {
+                        ballot: p2a.ballot,
+                        value: p2a.value,
+                    },
+                ))
+            } else {
+                None
+            }
+        }))
+        .across_ticks(|s| {
+            s.into_keyed().reduce(q!(
+                |prev, entry| {
+                    if entry.ballot > prev.ballot {
+                        *prev = entry;
+                    }
+                },
+                commutative =
+                    manual_proof!(/** max by ballot, and a ballot has one value per slot */)
+            ))
+        });
+    accepted_log_complete.complete(log.entries().fold(
+        q!(|| HashMap::new()),
+        q!(
+            |map, (slot, entry)| {
+                map.insert(slot, entry);
+            },
+            commutative = manual_proof!(/** each slot appears at most once */)
+        ),
+    ));
+
+    let p2b = p2a
+        .cross_singleton(acceptor_max_ballot)
+        .map(q!(|(p2a, max_ballot)| (
+            MemberId::from_tagless(p2a.ballot.leader.clone()),
+            (
+                (p2a.slot, p2a.ballot.clone()),
+                if p2a.ballot >= max_ballot {
+                    Ok(())
+                } else {
+                    Err(max_ballot)
+                }
+            )
+        )))
+        .all_ticks()
+        .demux(replicas, network())
+        .values();
+
+    let (p2b_quorums, p2b_fails) = collect_quorum(p2b, f + 1, 2 * f + 1);
+    rejections_complete.complete(
+        p1b_fails
+            .map(q!(|(_, ballot)| ballot))
+            .merge_unordered(p2b_fails.map(q!(|(_, ballot)| ballot))),
+    );
+
+    let committed = join_responses(p2b_quorums.map(q!(|k| (k, ()))), to_accept)
+        .map(q!(|((slot, _ballot), (value, _))| (slot, value)))
+        .broadcast(
+            replicas,
+            network(),
+            nondet!(/** replicas that join later only learn later commits */),
+        )
+        .values()
+        .weaken_retries::<AtLeastOnce>();
+
+    (elected, committed)
+}
+
+/// Computes this replica's ballot from the largest ballot it has received, along with whether
+/// its ballot is (still) the largest one.
+fn ballot_calc<'a, R: 'a>(
+    received_max_ballot: Singleton<Ballot, Tick<Cluster<'a, R>>, Bounded>,
+) -> (
+    Singleton<Ballot, Tick<Cluster<'a, R>>, Bounded>,
+    Singleton<bool, Tick<Cluster<'a, R>>, Bounded>,
+) {
+    let tick = received_max_ballot.location().clone();
+    let (ballot, has_largest_ballot) = sliced! {
+        let received_max_ballot = use::atomic(received_max_ballot.latest_atomic(), nondet!(/** up to date with tick input */));
+        let mut ballot_num = use::state(|l| l.singleton(q!(0)));
+
+        ballot_num = received_max_ballot
+            .clone()
+            .zip(ballot_num)
+            .map(q!(move |(received_max_ballot, ballot_num)| {
+                if received_max_ballot
+                    > (Ballot {
+                        num: ballot_num,
+                        leader: CLUSTER_SELF_ID.into_tagless(),
+                    })
+                {
+                    received_max_ballot.num + 1
+                } else {
+                    ballot_num
+                }
+            }));
+
+        let ballot = ballot_num.clone().map(q!(move |num| Ballot {
+            num,
+            leader: CLUSTER_SELF_ID.into_tagless()
+        }));
+
+        let has_largest_ballot = received_max_ballot
+            .zip(ballot.clone())
+            .map(q!(|(received_max_ballot, cur_ballot)| received_max_ballot <= cur_ballot));
+
+        (yield_atomic(ballot), yield_atomic(has_largest_ballot))
+    };
+
+    (
+        ballot.snapshot_atomic(
+            &tick,
+            nondet!(/** always up to date with received ballots */),
+        ),
+        has_largest_ballot.snapshot_atomic(
+            &tick,
+            nondet!(/** always up to date with received ballots */),
+        ),
+    )
+}
+
+/// Sends heartbeats while this replica is the leader, and decides when to start an election
+/// because no heartbeats have been received recently.
+fn leader_heartbeat<'a, R: 'a>(
+    replicas: &Cluster<'a, R>,
+    tick: &Tick<Cluster<'a, R>>,
+    is_leader: Singleton<bool, Tick<Cluster<'a, R>>, Bounded>,
+    ballot: Singleton<Ballot, Tick<Cluster<'a, R>>, Bounded>,
+    config: ConsensusConfig,
+    via: impl NetworkFor<Ballot>,
+    nondet_reelection: NonDet,
+) -> (
+    Stream<Ballot, Cluster<'a, R>, Unbounded, NoOrder, AtLeastOnce>,
+    Singleton<bool, Tick<Cluster<'a, R>>, Bounded>,
+) {
+    let heartbeat_interval_ms = config.heartbeat_interval_ms;
+    let election_timeout_ms = config.election_timeout_ms;
+
+    let heartbeats = ballot
+        .filter_if(is_leader.clone())
+        .latest()
+        .sample_every(
+            q!(Duration::from_millis(heartbeat_interval_ms)),
+            nondet!(
+                /// Delayed heartbeats may trigger an election even if the leader is alive, in
+                /// which case the leader will observe a larger ballot and step down.
+                nondet_reelection
+            ),
+        )
+        .broadcast(
+            replicas,
+            via,
+            nondet!(/** replicas that join later will learn of the leader on the next heartbeat */),
+        )
+        .values();
+
+    let leader_expired = heartbeats
+        .clone()
+        .timeout(
+            q!(Duration::from_millis(election_timeout_ms)),
+            nondet!(
+                /// A delayed timeout only affects which replica wins the next election.
+                nondet_reelection
+            ),
+        )
+        .snapshot(tick, nondet!(/** absorbed into timeout */))
+        .filter_if(!is_leader);
+
+    // Stagger elections by member ID so that replicas do not all start an election at once
+    let trigger_election = leader_expired.is_some().and(
+        replicas
+            .source_interval_delayed(
+                q!(Duration::from_millis(
+                    CLUSTER_SELF_ID.get_raw_id() as u64 * election_timeout_ms
+                )),
+                q!(Duration::from_millis(election_timeout_ms)),
+            )
+            .batch(
+                tick,
+                nondet!(
+                    /// If the leader 'un-expires' due to a delay, we return to a stable leader.
+                    /// Otherwise, the delay only affects which replica is elected.
+                ),
+            )
+            .first()
+            .is_some(),
+    );
+
+    (heartbeats, trigger_election)
+}
+
+/// Given the logs reported by a quorum of acceptors, computes the entries that a new leader must
+/// re-propose, along with the largest slot among them.
+///
+/// For each slot, the value accepted with the largest ballot is chosen, since it may have been
+/// committed by a previous leader. Slots below the largest slot that no acceptor has accepted a
+/// value for are filled with holes (`None`).
+pub fn recover_log<'a, L: Location<'a>, P: LogPayload>(
+    quorum_logs: Stream<HashMap<usize, LogValue<P>>, Tick<L>, Bounded, NoOrder>,
+) -> (
+    Stream<(usize, Option<P>), Tick<L>, Bounded, NoOrder>,
+    Optional<usize, Tick<L>, Bounded>,
+) {
+    let highest_entries = quorum_logs.flatten_unordered().into_keyed().reduce(q!(
+        |curr, entry| {
+            if entry.ballot > curr.ballot {
+                *curr = entry;
+            }
+        },
+        commutative = manual_proof!(/** max by ballot, and a ballot has one value per slot */)
+    ));
+
+    let max_slot = highest_entries.clone().keys().max();
+    let holes = max_slot
+        .clone()
+        .flat_map_ordered(q!(|max_slot| 0..max_slot))
+        .filter_not_in(highest_entries.clone().keys())
+        .map(q!(|slot| (slot, None)));
+
+    (
+        highest_entries
+            .entries()
+            .map(q!(|(slot, entry)| (slot, entry.value)))
+            .chain(holes),
+        max_slot,
+    )
+}
+
+/// Assigns consecutive slots to payloads, starting after the largest slot that was recovered
+/// during the most recent election (if any).
+pub fn index_payloads<'a, L: Location<'a>, P: LogPayload>(
+    max_recovered_slot: Optional<usize, Tick<L>, Bounded>,
+    payloads: Stream<P, Tick<L>, Bounded>,
+) -> Stream<(usize, P), Tick<L::DropConsistency>, Bounded> {
+    let tick = payloads.location().clone();
+    let sliced_result = sliced! {
+        let mut next_slot = use::state(|l| l.singleton(q!(0)));
+        let max_recovered_slot = use::atomic(max_recovered_slot.latest_atomic(), nondet!(/** up to date with tick input */));
+        let payload_batch = use::atomic(payloads.all_ticks_atomic(), nondet!(/** up to date with tick input */));
+
+        let base_slot = max_recovered_slot.map(q!(|s| s + 1)).unwrap_or(next_slot);
+
+        let indexed_payloads = payload_batch
+            .enumerate()
+            .cross_singleton(base_slot.clone())
+            .map(q!(|((index, payload), base_slot)| (base_slot + index, payload)));
+
+        next_slot = indexed_payloads
+            .clone()
+            .count()
+            .zip(base_slot)
+            .map(q!(|(num_payloads, base_slot)| base_slot + num_payloads));
+
+        yield_atomic(indexed_payloads)
+    };
+    sliced_result.batch_atomic(&tick, nondet!(/** up to date with tick input */))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use hydro_lang::live_collections::stream::{AtLeastOnce, ExactlyOnce, NoOrder, TotalOrder};
+    use hydro_lang::location::TaglessMemberId;
+    use hydro_lang::prelude::*;
+    use hydro_lang::sim::SimClusterReceiver;
+    use hydro_lang::sim::compiled::virtual_time;
+    use hydro_lang::sim::network_model::{Latency, LinkModel};
+
+    use super::{Ballot, ConsensusConfig, LogValue, recover_log, replicate_log, replicate_log_via};
+
+    const REPLICAS: u32 = 3;
+
+    fn ballot(num: u32) -> Ballot {
+        Ballot {
+            num,
+            leader: TaglessMemberId::from_raw_id(0),
+        }
+    }
+
+    fn config() -> ConsensusConfig {
+        ConsensusConfig {
+            f: 1,
+            heartbeat_interval_ms: 10,
+            election_timeout_ms: 1000,
+        }
+    }
+
+    /// Sends the committed entries to the test, interleaved with a `None` every 100ms of virtual
+    /// time so that the test can stop waiting even if nothing more is committed.
+    fn observe_commits<'a>(
+        replicas: &Cluster<'a, ()>,
+        committed: Stream<(usize, Option<char>), Cluster<'a, ()>, Unbounded, NoOrder, AtLeastOnce>,
+    ) -> SimClusterReceiver<Option<(usize, Option<char>)>, TotalOrder, ExactlyOnce> {
+        committed
+            .map(q!(|entry| Some(entry)))
+            .merge_unordered(
+                replicas
+                    .source_interval(q!(Duration::from_millis(100)))
+                    .map(q!(|_| None)),
+            )
+            .assume_ordering::<TotalOrder>(nondet!(/** test */))
+            .assume_retries::<ExactlyOnce>(nondet!(/** test */))
+            .sim_cluster_output()
+    }
+
+    /// Records an entry committed at some replica, checking that every replica commits the same
+    /// payload at each slot and that the payload was proposed.
+    fn record_commit(log: &mut HashMap<usize, Option<char>>, (slot, value): (usize, Option<char>)) {
+        assert!(matches!(value, None | Some('a' | 'b' | 'c')));
+        assert_eq!(
+            *log.entry(slot).or_insert(value),
+            value,
+            "slot {slot} was committed with different payloads"
+        );
+    }
+
+    #[test]
+    fn recover_log_prefers_largest_ballot_and_fills_holes() {
+        let mut flow = FlowBuilder::new();
+        let node = flow.process::<()>();
+        let tick = node.tick();
+
+        let (in_send, logs) = node.sim_input::<Vec<HashMap<usize, LogValue<char>>>, _, _>();
+        let (recovered, max_slot) = recover_log(
+            logs.batch(&tick, nondet!(/** test */))
+                .flat_map_unordered(q!(|logs| logs)),
+        );
+
+        let recovered_recv = recovered.all_ticks().sim_output();
+        let max_slot_recv = max_slot.into_stream().all_ticks().sim_output();
+
+        flow.sim().exhaustive(async || {
+            in_send.send(vec![
+                HashMap::from([
+                    (
+                        0,
+                        LogValue {
+                            ballot: ballot(1),
+                            value: Some('a'),
+                        },
+                    ),
+                    (
+                        2,
+                        LogValue {
+                            ballot: ballot(1),
+                            value: Some('c'),
+                        },
+                    ),
+                ]),
+                HashMap::from([(
+                    2,
+                    LogValue {
+                        ballot: ballot(2),
+                        value: Some('d'),
+                    },
+                )]),
+            ]);
+
+            recovered_recv
+                .assert_yields_only_unordered([(0, Some('a')), (1, None), (2, Some('d'))])
+                .await;
+            max_slot_recv.assert_yields_only([2]).await;
+        });
+    }
+
+    #[test]
+    fn replicate_log_commits_under_reordering() {
+        let mut flow = FlowBuilder::new();
+        let replicas = flow.cluster::<()>();
+
+        let (in_send, proposals) = replicas.sim_input::<char, _, _>();
+        let (_elected, committed) =
+            replicate_log(&replicas, proposals, config(), nondet!(/** test */));
+        let out_recv = observe_commits(&replicas, committed);
+
+        flow.sim()
+            .with_cluster_size(&replicas, REPLICAS as usize)
+            .with_default_link_model(LinkModel::new(Latency::Uniform {
+                min: Duration::from_millis(1),
+                max: Duration::from_millis(20),
+            }))
+            .explore_latencies()
+            .unit_test_fuzz_iterations(20)
+            .fuzz(async || {
+                in_send.send(0, 'a');
+                in_send.send(1, 'b');
+                in_send.send(2, 'c');
+
+                // The first leader is stable, because heartbeats arrive well within the election
+                // timeout, so every proposal is eventually committed at every replica.
+                let mut log = HashMap::new();
+                for member in 0..REPLICAS {
+                    let mut committed = vec![];
+                    while committed.len() < 3 {
+                        assert!(
+                            virtual_time() < Duration::from_secs(5),
+                            "replica {member} only committed {committed:?}"
+                        );
+                        if let Some((slot, value)) = out_recv.next(member).await.flatten() {
+                            record_commit(&mut log, (slot, value));
+                            if let Some(payload) = value
+                                && !committed.contains(&payload)
+                            {
+                                committed.push(payload);
+                            }
+                        }
+                    }
+                }
+            });
+    }
+
+    #[test]
+    fn replicate_log_agrees_under_message_loss() {
+        let mut flow = FlowBuilder::new();
+        let replicas = flow.cluster::<()>();
+
+        let (in_send, proposals) = replicas.sim_input::<char, _, _>();
+        let (_elected, committed) = replicate_log_via(
+            &replicas,
+            proposals,
+            config(),
+            || TCP.lossy_delayed_forever().bincode(),
+            nondet!(/** test */),
+        );
+        let out_recv = observe_commits(&replicas, committed);
+
+        flow.sim()
+            .with_cluster_size(&replicas, REPLICAS as usize)
+            .test_safety_only()
+            .unit_test_fuzz_iterations(20)
+            .fuzz(async || {
+                in_send.send(0, 'a');
+                in_send.send(1, 'b');
+                in_send.send(2, 'c');
+
+                // Lost messages may stall the log, so only check that whatever is committed
+                // agrees across replicas.
+                let mut log = HashMap::new();
+                while virtual_time() < Duration::from_secs(2) {
+                    for member in 0..REPLICAS {
+                        if let Some(entry) = out_recv.next(member).await.flatten() {
+                            record_commit(&mut log, entry);
+                        }
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,242 @@
+//! Fixpoint computations that are partitioned across the members of a [`Cluster`].
+//!
+//! [`Tick::iterate_across_ticks`] runs a fixpoint on a single location. For larger inputs,
+//! [`iterate`] in this module shards the derived facts across a cluster of workers, and detects
+//! when the computation has globally terminated using acknowledgement counting
+//! (Dijkstra-Scholten): every message is eventually acknowledged to its sender, but only once all
+//! the messages that were sent as a consequence of it have been acknowledged themselves. When all the initial facts have been
+//! acknowledged back to the coordinator, no messages can be in flight and no worker can derive
+//! anything new.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use hydro_lang::live_collections::stream::{NoOrder, TotalOrder};
+use hydro_lang::location::cluster::CLUSTER_SELF_ID;
+use hydro_lang::location::{Location, MemberId};
+use hydro_lang::prelude::*;
+use hydro_lang::properties::StreamMapFuncAlgebra;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use stageleft::IntoQuotedMut;
+
+/// Computes the fixpoint of `step` across the members of `workers`, starting from the facts in
+/// `initial`, and detects when the computation has terminated.
+///
+/// Each fact is owned by the worker that `partition` assigns it to, which is the only worker
+/// that runs `step` on it. Like [`Tick::iterate_across_ticks`], `step` is given the facts that
+/// are new to the worker in each tick and returns the facts derived from them, which are then
+/// sent to their owners. Initial facts are spread across the workers and forwarded to their owners.
+///
+/// Returns a pair of:
+/// - a stream of the facts owned by each worker, which emits each fact once when it is derived
+/// - an optional on the coordinator which becomes `Some(())` once every initial fact, and every
+///   fact derived from it, has been processed by its owner
+///
+/// # Non-Determinism
+/// The initial facts are spread across the workers that are known to the coordinator when they
+/// are sent, so the workers must be running before the computation starts; facts sent while
+/// there are no workers are dropped and the computation will never be reported as terminated.
+/// The set of facts derived by the workers is deterministic.
+pub fn iterate<'a, T, P, C: 'a, F>(
+    workers: &Cluster<'a, C>,
+    initial: Stream<T, Process<'a, P>, Bounded>,
+    partition: impl IntoQuotedMut<'a, F, Tick<Cluster<'a, C>>, StreamMapFuncAlgebra>,
+    step: impl FnOnce(
+        Stream<T, Tick<Cluster<'a, C>>, Bounded, NoOrder>,
+    ) -> Stream<T, Tick<Cluster<'a, C>>, Bounded, NoOrder>,
+    nondet_membership: NonDet,
+) -> (
+    Stream<T, Cluster<'a, C>, Unbounded, NoOrder>,
+    Optional<(), Process<'a, P>, Unbounded>,
+)
+where
+    T: Clone + Eq + Hash + Serialize + DeserializeOwned,
+    F: Fn(T) -> (MemberId<C>, T) + 'a,
+{
+    let coordinator = initial.location().clone();
+    let tick = workers.tick();
+
+    let (facts_complete, facts) =
+        workers.forward_ref::<Stream<(T, (MemberId<C>, u64)), _, Unbounded, NoOrder>>();
+    let (acks_complete, acks) = workers.forward_ref::<Stream<u64, _, Unbounded, NoOrder>>();
+
+    let initial_count = initial.clone().count();
+    let root_facts = initial
+        .round_robin(workers, TCP.fail_stop().bincode(), nondet_membership)
+        .batch(
+            &tick,
+            nondet!(
+                /// Initial facts are only forwarded to their owners, so the tick they arrive in
+                /// does not matter.
+            ),
+        )
+        .weaken_ordering::<NoOrder>();
+    let facts = facts.batch(
+        &tick,
+        nondet!(
+            /// The fixpoint does not depend on which tick a fact is processed in, and the
+            /// acknowledgement for a fact is only sent once all its consequences are processed.
+        ),
+    );
+    let acks = acks.batch(
+        &tick,
+        nondet!(/** acknowledgements only decrement counters, which commute */),
+    );
+
+    // `None` is the coordinator, otherwise the worker and the tick the message was sent in
+    let senders = root_facts
+        .clone()
+        .map(q!(|_| None))
+        .chain(facts.clone().map(q!(|(_, sender)| Some(sender))));
+
+    let (reached_complete, reached) = tick.cycle::<Stream<T, _, Bounded, NoOrder>, _>();
+    let new_facts = facts
+        .map(q!(|(fact, _)| fact))
+        .unique()
+        .filter_not_in(reached.clone());
+    reached_complete.complete_next_tick(reached.chain(new_facts.clone()));
+
+    let outgoing = step(new_facts.clone())
+        .chain(root_facts)
+        .unique()
+        .map(partition);
+
+    let (batch_id_complete, batch_id) = tick.cycle_with_initial(tick.singleton(q!(0u64)));
+    batch_id_complete.complete_next_tick(batch_id.clone().map(q!(|id| id + 1)));
+
+    facts_complete.complete(
+        outgoing
+            .clone()
+            .cross_singleton(batch_id.clone())
+            .map(q!(|((owner, fact), batch_id)| (
+                owner,
+                (fact, (CLUSTER_SELF_ID.clone(), batch_id))
+            )))
+            .all_ticks()
+            .demux(workers, TCP.fail_stop().bincode()),
+    );
+
+    // For each tick that sent messages, the number of them that are still unacknowledged and
+    // the senders to acknowledge once they all are.
+    let (pending_complete, pending) = tick.cycle_with_initial(tick.singleton(q!(HashMap::new())));
+    let updated = pending
+        .zip(batch_id)
+        .zip(outgoing.count())
+        .zip(
+            senders
+                .assume_ordering::<TotalOrder>(
+                    nondet!(/** the order of acknowledgements does not matter */),
+                )
+                .collect_vec(),
+        )
+        .zip(
+            acks.assume_ordering::<TotalOrder>(
+                nondet!(/** acknowledgements only decrement counters, which commute */),
+            )
+            .collect_vec(),
+        )
+        .map(q!(|((((mut pending, batch_id), sent), senders), acks)| {
+            let mut to_ack = Vec::new();
+            if sent == 0 {
+                to_ack.extend(senders);
+            } else {
+                pending.insert(batch_id, (sent, senders));
+            }
+
+            for acked_batch in acks {
+                let remaining = &mut pending.get_mut(&acked_batch).unwrap().0;
+                *remaining -= 1;
+                if *remaining == 0 {
+                    to_ack.extend(pending.remove(&acked_batch).unwrap().1);
+                }
+            }
+
+            (pending, to_ack)
+        }));
+    pending_complete.complete_next_tick(updated.clone().map(q!(|(pending, _)| pending)));
+
+    let to_ack = updated.map(q!(|(_, to_ack)| to_ack)).flatten_ordered();
+    acks_complete.complete(
+        to_ack
+            .clone()
+            .filter_map(q!(|sender| sender))
+            .all_ticks()
+            .demux(workers, TCP.fail_stop().bincode()),
+    );
+
+    let root_acks = to_ack
+        .filter_map(q!(|sender| if sender.is_none() { Some(()) } else { None }))
+        .all_ticks()
+        .send(&coordinator, TCP.fail_stop().bincode())
+        .values();
+
+    let coordinator_tick = coordinator.tick();
+    let terminated = initial_count
+        .snapshot(
+            &coordinator_tick,
+            nondet!(/** the initial facts are bounded */),
+        )
+        .zip(root_acks.count().snapshot(
+            &coordinator_tick,
+            nondet!(
+                /// Acknowledgements are only counted, and once they match the number of initial
+                /// facts no more can arrive.
+            ),
+        ))
+        .filter(q!(|(sent, acked)| sent == acked))
+        .map(q!(|_| ()))
+        .latest();
+
+    (new_facts.all_ticks(), terminated)
+}
+
+#[cfg(test)]
+mod tests {
+    use hydro_lang::live_collections::stream::ExactlyOnce;
+    use hydro_lang::location::MemberId;
+    use hydro_lang::prelude::*;
+
+    use super::iterate;
+
+    #[test]
+    fn distributed_transitive_closure_terminates() {
+        let mut flow = FlowBuilder::new();
+        let coordinator = flow.process::<()>();
+        let workers = flow.cluster::<()>();
+
+        // paths in the chain 0 -> 1 -> 2 -> 3, where worker `from % 2` owns the paths from `from`
+        let (paths, terminated) = iterate(
+            &workers,
+            coordinator.source_iter(q!((0..3).map(|n| (n, n + 1)))),
+            q!(|(from, to)| (MemberId::from_raw_id(from % 2), (from, to))),
+            |new_paths| {
+                new_paths.filter_map(q!(|(from, to)| if to < 3 {
+                    Some((from, to + 1))
+                } else {
+                    None
+                }))
+            },
+            nondet!(/** test */),
+        );
+
+        let paths_recv = paths.sim_cluster_output();
+        let terminated_recv = terminated
+            .sample_eager(nondet!(/** test */))
+            .assume_retries::<ExactlyOnce>(nondet!(/** test */))
+            .sim_output();
+
+        flow.sim().with_cluster_size(&workers, 2).fuzz(async || {
+            assert_eq!(terminated_recv.next().await, Some(()));
+
+            assert_eq!(
+                paths_recv.collect_sorted::<Vec<_>>(0).await,
+                vec![(0, 1), (0, 2), (0, 3), (2, 3)]
+            );
+            assert_eq!(
+                paths_recv.collect_sorted::<Vec<_>>(1).await,
+                vec![(1, 2), (1, 3)]
+            );
+        });
+    }
+}
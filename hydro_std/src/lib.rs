@@ -3,6 +3,9 @@ hydro_lang::setup!();
 
 pub mod bench_client;
 pub mod compartmentalize;
+pub mod consensus;
+pub mod fixpoint;
 pub mod membership;
 pub mod quorum;
 pub mod request_response;
+pub mod tx;
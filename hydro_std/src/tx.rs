@@ -0,0 +1,459 @@
+//! Atomic commitment of transactions across the members of a [`Cluster`].
+//!
+//! [`two_phase_commit`] runs the classic two-phase commit protocol. A coordinator process asks
+//! every participant to prepare a transaction. If all participants vote to commit, the
+//! transaction is committed. Otherwise it is aborted. The decision is then sent to every
+//! participant.
+//!
+//! The protocol uses the presumed-abort optimization. The coordinator only records committed
+//! transactions. A participant that has not received the outcome of a transaction it prepared,
+//! for example because the message was lost, periodically asks the coordinator for it. The
+//! coordinator answers with the commit if it has a record of one, and otherwise presumes that
+//! the transaction was aborted. A transaction whose votes do not all arrive within the timeout,
+//! for example because a participant has failed, is aborted.
+//!
+//! Participants acknowledge every committed transaction once they have received its outcome,
+//! and the coordinator forgets a committed transaction once every participant has acknowledged
+//! it, since no participant will inquire about it again. The coordinator therefore only holds
+//! the committed transactions that some participant has not yet received. If a participant
+//! fails, it never acknowledges the transactions committed after it failed, so these are kept
+//! for as long as the coordinator runs.
+
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::time::Duration;
+
+use hydro_lang::live_collections::stream::NoOrder;
+use hydro_lang::location::{Location, MemberId};
+use hydro_lang::networking::NetworkFor;
+use hydro_lang::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::quorum::collect_quorum;
+
+/// The outcome of a transaction coordinated by [`two_phase_commit`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TxOutcome {
+    /// Every participant voted to commit the transaction.
+    Committed,
+    /// At least one participant voted to abort the transaction, or the votes did not all arrive
+    /// before the timeout.
+    Aborted,
+}
+
+/// Coordinates the atomic commitment of the transactions in `requests`, where each transaction
+/// is identified by a unique key of type `K`.
+///
+/// Every transaction is sent to all `num_participants` members of `participants`, which vote on
+/// it with `participant_votes`. A vote of `true` means that the participant has prepared the
+/// transaction and is able to commit it. A transaction is committed only if every participant
+/// votes `true`. A transaction that has not received all of its votes by the time `timeout` has
+/// elapsed (at least once, and at most twice) is aborted.
+///
+/// Returns the outcome of each transaction at the coordinator and at the participants. Each
+/// transaction has exactly one outcome, and every participant receives the same outcome as the
+/// coordinator. A participant that has not received the outcome of a transaction asks the
+/// coordinator for it every `timeout`, and acknowledges every committed transaction it
+/// receives so that the coordinator can stop recording it.
+pub fn two_phase_commit<'a, C: 'a, P: 'a, K, T>(
+    participants: &Cluster<'a, P>,
+    num_participants: usize,
+    requests: Stream<(K, T), Process<'a, C>, Unbounded, NoOrder>,
+    participant_votes: impl FnOnce(
+        Stream<(K, T), Cluster<'a, P>, Unbounded, NoOrder>,
+    ) -> Stream<(K, bool), Cluster<'a, P>, Unbounded, NoOrder>,
+    timeout: Duration,
+) -> (
+    Stream<(K, TxOutcome), Process<'a, C>, Unbounded, NoOrder>,
+    Stream<(K, TxOutcome), Cluster<'a, P>, Unbounded, NoOrder>,
+)
+where
+    K: Serialize + DeserializeOwned + Clone + Eq + Hash + Debug,
+    T: Serialize + DeserializeOwned + Clone,
+{
+    let timeout_ms = timeout.as_millis() as u64;
+    let timeout_checks = requests
+        .location()
+        .source_interval(q!(Duration::from_millis(timeout_ms)))
+        .weaken_ordering::<NoOrder>();
+    let inquiry_checks = participants
+        .source_interval(q!(Duration::from_millis(timeout_ms)))
+        .weaken_ordering::<NoOrder>();
+
+    coordinate(
+        participants,
+        num_participants,
+        requests,
+        participant_votes,
+        timeout_checks,
+        inquiry_checks,
+        TCP.fail_stop().bincode(),
+    )
+}
+
+/// Implements [`two_phase_commit`], with the timeout driven by `timeout_checks` and inquiries
+/// driven by `inquiry_checks`, and with outcomes sent to the participants over `outcomes_via`.
+/// A transaction is aborted once it has observed two elements of `timeout_checks` without being
+/// decided.
+fn coordinate<'a, C: 'a, P: 'a, K, T, N>(
+    participants: &Cluster<'a, P>,
+    num_participants: usize,
+    requests: Stream<(K, T), Process<'a, C>, Unbounded, NoOrder>,
+    participant_votes: impl FnOnce(
+        Stream<(K, T), Cluster<'a, P>, Unbounded, NoOrder>,
+    ) -> Stream<(K, bool), Cluster<'a, P>, Unbounded, NoOrder>,
+    timeout_checks: Stream<(), Process<'a, C>, Unbounded, NoOrder>,
+    inquiry_checks: Stream<(), Cluster<'a, P>, Unbounded, NoOrder>,
+    outcomes_via: N,
+) -> (
+    Stream<(K, TxOutcome), Process<'a, C>, Unbounded, NoOrder>,
+    Stream<(K, TxOutcome), Cluster<'a, P>, Unbounded, NoOrder>,
+)
+where
+    K: Serialize + DeserializeOwned + Clone + Eq + Hash + Debug,
+    T: Serialize + DeserializeOwned + Clone,
+    N: NetworkFor<(K, TxOutcome)>,
+{
+    let coordinator = requests.location().clone();
+
+    let (vote_decisions_complete, vote_decisions) =
+        coordinator.forward_ref::<Stream<(K, TxOutcome), Process<'a, C>, Unbounded, NoOrder>>();
+    let (inquiries_complete, inquiries) =
+        coordinator.forward_ref::<Stream<(MemberId<P>, K), Process<'a, C>, Unbounded, NoOrder>>();
+    let (acks_complete, acks) =
+        coordinator.forward_ref::<Stream<(MemberId<P>, K), Process<'a, C>, Unbounded, NoOrder>>();
+
+    let (outcomes, prepare, replies) = sliced! {
+        let new_txs = use(requests, nondet!(
+            /// the arrival time of a transaction only affects whether it times out
+        ));
+        let decided = use(vote_decisions, nondet!(
+            /// a vote decision that arrives after the timeout is ignored, in which case the
+            /// transaction is (safely) aborted instead
+        ));
+        let checks = use(timeout_checks, nondet!(
+            /// timeout checks only affect when undecided transactions are aborted
+        ));
+        let inquired = use(inquiries, nondet!(
+            /// an inquiry about an undecided transaction is ignored, and is answered by a later
+            /// inquiry once the transaction has been decided
+        ));
+        let acked = use(acks, nondet!(
+            /// an acknowledgement only affects when a committed transaction is forgotten, which
+            /// happens once no participant can inquire about it
+        ));
+        let mut pending = use::state_null::<Stream<(K, usize), Tick<_>, Bounded, NoOrder>>();
+        let mut committed = use::state_null::<Stream<K, Tick<_>, Bounded, NoOrder>>();
+        let mut partially_acked =
+            use::state_null::<Stream<(K, MemberId<P>), Tick<_>, Bounded, NoOrder>>();
+
+        let num_checks = checks.count();
+        let current = pending
+            .chain(new_txs.clone().map(q!(|(id, _)| (id, 0usize))))
+            .cross_singleton(num_checks)
+            .map(q!(|((id, seen), new)| (id, seen + new)));
+
+        let by_vote = current
+            .clone()
+            .join(decided)
+            .map(q!(|(id, (_, outcome))| (id, outcome)))
+            .unique();
+        let undecided = current.anti_join(by_vote.clone().map(q!(|(id, _)| id)));
+
+        let timed_out = undecided
+            .clone()
+            .filter(q!(|(_, seen)| *seen >= 2))
+            .map(q!(|(id, _)| (id, TxOutcome::Aborted)));
+
+        let still_pending = undecided.anti_join(timed_out.clone().map(q!(|(id, _)| id)));
+        let all_committed = committed.chain(
+            by_vote
+                .clone()
+                .filter(q!(|(_, outcome)| *outcome == TxOutcome::Committed))
+                .map(q!(|(id, _)| id)),
+        );
+
+        // Only committed transactions are recorded, so an inquiry about a transaction that is
+        // neither committed nor pending is answered with a (presumed) abort.
+        let inquired_by_id = inquired.map(q!(|(member, id)| (id, member)));
+        let answered_committed = inquired_by_id
+            .clone()
+            .join(all_committed.clone().map(q!(|id| (id, ()))))
+            .map(q!(|(id, (member, ()))| (member, (id, TxOutcome::Committed))));
+        let answered_aborted = inquired_by_id
+            .anti_join(still_pending.clone().map(q!(|(id, _)| id)))
+            .anti_join(all_committed.clone())
+            .map(q!(|(id, member)| (member, (id, TxOutcome::Aborted))));
+
+        // A participant only acknowledges a transaction after delivering its outcome, and never
+        // inquires about a delivered transaction, so a committed transaction that every
+        // participant has acknowledged can be forgotten. A stale inquiry that arrives afterwards
+        // is answered with an abort, which the participant ignores since it has already
+        // delivered the commit.
+        let all_acks = partially_acked
+            .chain(acked.map(q!(|(member, id)| (id, member))))
+            .unique();
+        let fully_acked = all_acks
+            .clone()
+            .into_keyed()
+            .value_counts()
+            .filter(q!(move |num_acks| *num_acks >= num_participants))
+            .keys();
+
+        pending = still_pending;
+        committed = all_committed.anti_join(fully_acked.clone());
+        partially_acked = all_acks.anti_join(fully_acked);
+        (
+            by_vote.chain(timed_out),
+            new_txs,
+            answered_committed.chain(answered_aborted),
+        )
+    };
+
+    // Transactions are only prepared once the coordinator is tracking them, so that an inquiry
+    // about a prepared transaction is never answered with a presumed abort before it is decided.
+    let prepare = prepare.broadcast(
+        participants,
+        TCP.fail_stop().bincode(),
+        nondet!(/** participants that join later do not vote on in-flight transactions */),
+    );
+
+    let votes = participant_votes(prepare.clone())
+        .send(&coordinator, TCP.fail_stop().bincode())
+        .values()
+        .map(q!(|(id, vote)| (id, if vote { Ok(()) } else { Err(()) })));
+
+    let (all_prepared, rejected) = collect_quorum(votes, num_participants, num_participants);
+    vote_decisions_complete.complete(
+        all_prepared
+            .map(q!(|id| (id, TxOutcome::Committed)))
+            .merge_unordered(rejected.map(q!(|(id, _)| (id, TxOutcome::Aborted)))),
+    );
+
+    let outcomes_at_participants = outcomes
+        .clone()
+        .broadcast(
+            participants,
+            outcomes_via,
+            nondet!(/** participants that join later do not vote on in-flight transactions */),
+        )
+        .merge_unordered(replies.demux(participants, TCP.fail_stop().bincode()));
+
+    let (participant_outcomes, to_inquire) = sliced! {
+        let prepared = use(prepare.map(q!(|(id, _)| id)), nondet!(
+            /// outcomes that arrive before the transaction is prepared are held until it is
+        ));
+        let learned = use(outcomes_at_participants, nondet!(
+            /// outcomes are final, so the tick they arrive in does not affect them
+        ));
+        let checks = use(inquiry_checks, nondet!(
+            /// inquiry checks only affect when missing outcomes are asked for
+        ));
+        let mut awaiting = use::state_null::<Stream<K, Tick<_>, Bounded, NoOrder>>();
+        let mut early = use::state_null::<Stream<(K, TxOutcome), Tick<_>, Bounded, NoOrder>>();
+
+        let current = awaiting.chain(prepared);
+        let known = early.chain(learned);
+        // An outcome may arrive both from the coordinator's broadcast and as the answer to an
+        // inquiry, so only the first is delivered.
+        let delivered = current
+            .clone()
+            .map(q!(|id| (id, ())))
+            .join(known.clone())
+            .map(q!(|(id, ((), outcome))| (id, outcome)))
+            .unique();
+        let delivered_ids = delivered.clone().map(q!(|(id, _)| id));
+        let remaining = current.anti_join(delivered_ids.clone());
+
+        let inquire = remaining
+            .clone()
+            .cross_singleton(checks.count())
+            .filter(q!(|(_, num_checks)| *num_checks > 0))
+            .map(q!(|(id, _)| id));
+
+        awaiting = remaining;
+        early = known.anti_join(delivered_ids);
+        (delivered, inquire)
+    };
+
+    inquiries_complete.complete(
+        to_inquire
+            .send(&coordinator, TCP.fail_stop().bincode())
+            .entries(),
+    );
+
+    acks_complete.complete(
+        participant_outcomes
+            .clone()
+            .filter(q!(|(_, outcome)| *outcome == TxOutcome::Committed))
+            .map(q!(|(id, _)| id))
+            .send(&coordinator, TCP.fail_stop().bincode())
+            .entries(),
+    );
+
+    (outcomes, participant_outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use hydro_lang::live_collections::stream::{ExactlyOnce, NoOrder};
+    use hydro_lang::prelude::*;
+
+    use super::{TxOutcome, coordinate};
+
+    #[test]
+    fn two_phase_commit_aborts_unless_all_vote_commit() {
+        let mut flow = FlowBuilder::new();
+        let coordinator = flow.process::<()>();
+        let participants = flow.cluster::<()>();
+
+        let (in_send, requests) = coordinator.sim_input::<(u32, u32), NoOrder, ExactlyOnce>();
+        let (_checks_send, checks) = coordinator.sim_input::<(), NoOrder, ExactlyOnce>();
+        let (_inquiry_send, inquiry_checks) = participants.sim_input::<(), NoOrder, ExactlyOnce>();
+        let (outcomes, participant_outcomes) = coordinate(
+            &participants,
+            2,
+            requests,
+            // only transactions with even payloads can be prepared
+            |prepared| prepared.map(q!(|(id, payload)| (id, payload % 2 == 0))),
+            // no timeout checks are sent, so transactions are only decided by their votes
+            checks,
+            inquiry_checks,
+            TCP.fail_stop().bincode(),
+        );
+
+        let out_recv = outcomes.sim_output();
+        let participant_recv = participant_outcomes.sim_cluster_output();
+
+        flow.sim()
+            .with_cluster_size(&participants, 2)
+            .exhaustive(async || {
+                in_send.send_many_unordered([(1, 2), (2, 3)]);
+
+                out_recv
+                    .assert_yields_only_unordered([
+                        (1, TxOutcome::Committed),
+                        (2, TxOutcome::Aborted),
+                    ])
+                    .await;
+
+                for member in 0..2 {
+                    assert_eq!(
+                        participant_recv.collect_sorted::<Vec<_>>(member).await,
+                        vec![(1, TxOutcome::Committed), (2, TxOutcome::Aborted)]
+                    );
+                }
+            });
+    }
+
+    #[test]
+    fn two_phase_commit_outcomes_are_atomic_under_timeouts() {
+        let mut flow = FlowBuilder::new();
+        let coordinator = flow.process::<()>();
+        let participants = flow.cluster::<()>();
+
+        let (in_send, requests) = coordinator.sim_input::<(u32, ()), _, _>();
+        let (_inquiry_send, inquiry_checks) = participants.sim_input::<(), NoOrder, ExactlyOnce>();
+        let (outcomes, participant_outcomes) = coordinate(
+            &participants,
+            2,
+            requests.clone().weaken_ordering::<NoOrder>(),
+            // the votes for transaction 1 are lost, as if every participant failed
+            |prepared| {
+                prepared
+                    .filter(q!(|(id, _)| *id != 1))
+                    .map(q!(|(id, _)| (id, true)))
+            },
+            // every new transaction also acts as a timeout check
+            requests.map(q!(|_| ())).weaken_ordering::<NoOrder>(),
+            inquiry_checks,
+            TCP.fail_stop().bincode(),
+        );
+
+        let out_recv = outcomes.sim_output();
+        let participant_recv = participant_outcomes.sim_cluster_output();
+
+        flow.sim()
+            .with_cluster_size(&participants, 2)
+            .exhaustive(async || {
+                in_send.send((1, ()));
+                in_send.send((2, ()));
+                in_send.send((3, ()));
+
+                // transaction 1 observes all three checks, so it must time out, while the other
+                // transactions may either commit or time out depending on when their votes arrive
+                let decided = out_recv.collect_sorted::<Vec<_>>().await;
+                let by_tx = decided.iter().cloned().collect::<HashMap<_, _>>();
+                assert_eq!(by_tx.len(), 3, "every transaction is decided exactly once");
+                assert_eq!(
+                    decided.len(),
+                    3,
+                    "every transaction is decided exactly once"
+                );
+                assert_eq!(by_tx.get(&1), Some(&TxOutcome::Aborted));
+
+                for member in 0..2 {
+                    assert_eq!(
+                        participant_recv.collect_sorted::<Vec<_>>(member).await,
+                        decided
+                    );
+                }
+            });
+    }
+
+    #[test]
+    fn two_phase_commit_recovers_lost_outcomes_by_presuming_abort() {
+        let mut flow = FlowBuilder::new();
+        let coordinator = flow.process::<()>();
+        let participants = flow.cluster::<()>();
+
+        let (in_send, requests) = coordinator.sim_input::<(u32, u32), NoOrder, ExactlyOnce>();
+        let (_checks_send, checks) = coordinator.sim_input::<(), NoOrder, ExactlyOnce>();
+        let (inquiry_send, inquiry_checks) = participants.sim_input::<(), NoOrder, ExactlyOnce>();
+        let (outcomes, participant_outcomes) = coordinate(
+            &participants,
+            2,
+            requests,
+            |prepared| prepared.map(q!(|(id, payload)| (id, payload % 2 == 0))),
+            checks,
+            inquiry_checks,
+            // outcomes broadcast by the coordinator may be lost, and are then only learned by
+            // inquiring about them
+            TCP.lossy_delayed_forever().bincode(),
+        );
+
+        let out_recv = outcomes.sim_output();
+        let participant_recv = participant_outcomes.sim_cluster_output();
+
+        flow.sim()
+            .with_cluster_size(&participants, 2)
+            .test_safety_only()
+            .fuzz(async || {
+                in_send.send_many_unordered([(1, 2), (2, 3)]);
+
+                let expected = vec![(1, TxOutcome::Committed), (2, TxOutcome::Aborted)];
+                assert_eq!(out_recv.collect_sorted::<Vec<_>>().await, expected);
+
+                let mut received = vec![vec![]; 2];
+                while received
+                    .iter()
+                    .any(|outcomes| outcomes.len() < expected.len())
+                {
+                    inquiry_send.send_many_unordered([(0, ()), (1, ())]);
+
+                    for member in 0..2 {
+                        received[member as usize]
+                            .extend(participant_recv.collect_sorted::<Vec<_>>(member).await);
+                    }
+                }
+
+                for mut outcomes in received {
+                    outcomes.sort();
+                    assert_eq!(outcomes, expected);
+                }
+            });
+    }
+}
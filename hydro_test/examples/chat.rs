@@ -14,7 +14,7 @@ type HostCreator = Box<dyn Fn(&mut Deployment) -> Arc<dyn Host>>;
 #[derive(Parser, Debug)]
 #[command(group(
     clap::ArgGroup::new("cloud")
-        .args(&["gcp", "aws"])
+        .args(&["gcp", "aws", "profile"])
         .multiple(false)
 ))]
 struct Args {
@@ -28,14 +28,24 @@ struct Args {
     /// Use AWS, make sure credentials are set up
     #[arg(long, action = ArgAction::SetTrue)]
     aws: bool,
+
+    /// Use a deployment profile from `hydro_test/hydro.toml`
+    #[arg(long)]
+    profile: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let mut deployment = Deployment::new();
+    let mut deployment = if let Some(profile) = &args.profile {
+        Deployment::from_profile_file(concat!(env!("CARGO_MANIFEST_DIR"), "/hydro.toml"), profile)?
+    } else {
+        Deployment::new()
+    };
 
-    let create_host: HostCreator = if let Some(project) = &args.gcp {
+    let create_host: HostCreator = if args.profile.is_some() {
+        Box::new(|deployment| deployment.profile_host("server").unwrap())
+    } else if let Some(project) = &args.gcp {
         let network = GcpNetwork::new(project, None);
         let project = project.clone();
 
@@ -88,13 +98,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    let mut server_host = TrybuildHost::new(create_host(&mut deployment)).features(["tokio"]);
+    for (key, value) in deployment.profile_env() {
+        server_host = server_host.env(key, value);
+    }
+
     // Now use the built flow for deployment with optimization
     let nodes = built
         .with_default_optimize()
-        .with_process(
-            &process,
-            TrybuildHost::new(create_host(&mut deployment)).features(["tokio"]),
-        )
+        .with_process(&process, server_host)
         .with_external(&external, deployment.Localhost())
         .deploy(&mut deployment);
 
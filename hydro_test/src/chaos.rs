@@ -0,0 +1,499 @@
+//! A harness for running cluster examples under randomized faults on real processes.
+//!
+//! This complements the Hydro simulator, which explores interleavings exhaustively but in a
+//! single process, by injecting faults into the members of a localhost deployment. The faults are
+//! chosen by a [`FaultSchedule`] generated from a seed, so a failing run can be reproduced by
+//! re-running with the seed printed in its [`ChaosReport`]:
+//! - [`Fault::Kill`] stops the member for the rest of the run.
+//! - [`Fault::Restart`] stops the member and launches it again. Hydro Deploy can only restart
+//!   members which no other service connects to, so this is skipped for most cluster members.
+//! - [`Fault::Partition`] suspends the member, cutting all of its links for a while. Messages to and
+//!   from the member are held back by the kernel (until buffers fill up) rather than lost.
+//! - [`Fault::Delay`] repeatedly suspends the member for short periods, delaying its messages.
+//!
+//! Faults apply to the whole process of a member: a partition cuts all of its links rather than
+//! a chosen one, and suspending a member also suspends any other locations deployed in the same
+//! process. Links between two particular members cannot be partitioned on their own.
+//!
+//! While faults are injected, a set of [`Invariant`]s (typically checking the outputs collected
+//! from the members' stdout) is checked periodically and at the end of the run.
+//!
+//! ```rust,ignore
+//! let members = ChaosMember::from_cluster("acceptor", &nodes.get_cluster(&acceptors));
+//! deployment.start().await.unwrap();
+//!
+//! let report = run_chaos(
+//!     &ChaosConfig::with_seed(42),
+//!     &members,
+//!     vec![Invariant::new("no conflicting decisions", move || check(&decided))],
+//! )
+//! .await;
+//! report.assert_ok();
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hydro_deploy::Service;
+use hydro_deploy::rust_crate::RustCrateService;
+use hydro_lang::deploy::{DeployCluster, DeployCrateWrapper};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How long a member is suspended for at a time by [`Fault::Delay`], alternating with running for
+/// the same period.
+const DELAY_PAUSE: Duration = Duration::from_millis(50);
+
+/// A process of a deployment which faults can be injected into, as a whole (see the
+/// [module docs](self)).
+#[derive(Clone)]
+pub struct ChaosMember {
+    name: String,
+    service: Arc<RustCrateService>,
+}
+
+impl ChaosMember {
+    /// Creates a member named `name` (used in reports) for a deployed process or cluster member.
+    pub fn new(name: impl Into<String>, node: &impl DeployCrateWrapper) -> Self {
+        Self {
+            name: name.into(),
+            service: node.underlying(),
+        }
+    }
+
+    /// Creates a member for each member of a deployed cluster, named `{name}/{index}`.
+    pub fn from_cluster(name: &str, cluster: &DeployCluster) -> Vec<Self> {
+        cluster
+            .members()
+            .iter()
+            .enumerate()
+            .map(|(i, member)| Self::new(format!("{name}/{i}"), member))
+            .collect()
+    }
+
+    /// The name of the member.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A kind of [`Fault`], used to select which faults a [`FaultSchedule`] may contain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FaultKind {
+    /// See [`Fault::Kill`].
+    Kill,
+    /// See [`Fault::Restart`].
+    Restart,
+    /// See [`Fault::Partition`].
+    Partition,
+    /// See [`Fault::Delay`].
+    Delay,
+}
+
+/// A fault injected into a member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Stops the member for the rest of the run.
+    Kill,
+    /// Stops the member and launches it again, losing its state.
+    Restart,
+    /// Suspends the member's process for `duration`, so that it neither sends nor receives
+    /// messages on any of its links.
+    Partition {
+        /// How long the member is partitioned for.
+        duration: Duration,
+    },
+    /// Alternately suspends and resumes the member for `duration`, delaying its messages.
+    Delay {
+        /// How long the member's messages are delayed for.
+        duration: Duration,
+    },
+}
+
+impl Fault {
+    /// The kind of this fault.
+    pub fn kind(&self) -> FaultKind {
+        match self {
+            Fault::Kill => FaultKind::Kill,
+            Fault::Restart => FaultKind::Restart,
+            Fault::Partition { .. } => FaultKind::Partition,
+            Fault::Delay { .. } => FaultKind::Delay,
+        }
+    }
+}
+
+/// Configuration for [`run_chaos`].
+#[derive(Clone, Debug)]
+pub struct ChaosConfig {
+    /// The seed for generating the [`FaultSchedule`].
+    pub seed: u64,
+    /// How long to inject faults for.
+    pub run_for: Duration,
+    /// How many faults to inject.
+    pub faults: usize,
+    /// The kinds of faults which may be injected.
+    pub kinds: Vec<FaultKind>,
+    /// The maximum duration of [`Fault::Partition`] and [`Fault::Delay`].
+    pub max_fault_duration: Duration,
+    /// How often the [`Invariant`]s are checked.
+    pub check_interval: Duration,
+}
+
+impl ChaosConfig {
+    /// The default configuration, with the given seed: ten faults of any kind over 30 seconds.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            run_for: Duration::from_secs(30),
+            faults: 10,
+            kinds: vec![
+                FaultKind::Kill,
+                FaultKind::Restart,
+                FaultKind::Partition,
+                FaultKind::Delay,
+            ],
+            max_fault_duration: Duration::from_secs(5),
+            check_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A fault to be injected into a member at some point of a run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledFault {
+    /// When to inject the fault, relative to the start of the run.
+    pub at: Duration,
+    /// The index of the member to inject the fault into.
+    pub member: usize,
+    /// The fault to inject.
+    pub fault: Fault,
+}
+
+/// The faults to inject during a run, in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaultSchedule {
+    /// The seed the schedule was generated from.
+    pub seed: u64,
+    /// The faults, sorted by [`ScheduledFault::at`].
+    pub faults: Vec<ScheduledFault>,
+}
+
+impl FaultSchedule {
+    /// Generates a schedule of faults across `members` members, which is the same for the same
+    /// configuration.
+    pub fn generate(config: &ChaosConfig, members: usize) -> Self {
+        assert!(members > 0, "Cannot inject faults without any members.");
+        assert!(
+            !config.kinds.is_empty(),
+            "Cannot inject faults without any fault kinds."
+        );
+
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let run_for_ms = config.run_for.as_millis() as u64;
+        let max_duration_ms = config.max_fault_duration.as_millis().max(1) as u64;
+
+        let mut faults = (0..config.faults)
+            .map(|_| {
+                let at = Duration::from_millis(rng.gen_range(0..run_for_ms.max(1)));
+                let member = rng.gen_range(0..members);
+                let duration = Duration::from_millis(rng.gen_range(1..=max_duration_ms));
+                let fault = match config.kinds.choose(&mut rng).unwrap() {
+                    FaultKind::Kill => Fault::Kill,
+                    FaultKind::Restart => Fault::Restart,
+                    FaultKind::Partition => Fault::Partition { duration },
+                    FaultKind::Delay => Fault::Delay { duration },
+                };
+                ScheduledFault { at, member, fault }
+            })
+            .collect::<Vec<_>>();
+        faults.sort_by_key(|fault| fault.at);
+
+        Self {
+            seed: config.seed,
+            faults,
+        }
+    }
+}
+
+/// A property of the deployment which must hold throughout a run, such as "no two members decided
+/// different values".
+pub struct Invariant {
+    name: String,
+    check: Box<dyn FnMut() -> Result<(), String>>,
+}
+
+impl Invariant {
+    /// Creates an invariant named `name`, which holds when `check` returns `Ok`.
+    pub fn new(
+        name: impl Into<String>,
+        check: impl FnMut() -> Result<(), String> + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            check: Box::new(check),
+        }
+    }
+}
+
+/// What happened when a fault was injected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FaultOutcome {
+    /// The fault was injected.
+    Injected,
+    /// The fault was not injected, for example because the member had already been killed.
+    Skipped(String),
+    /// Injecting the fault failed.
+    Failed(String),
+}
+
+/// A fault which was scheduled during a run, and its outcome.
+#[derive(Clone, Debug)]
+pub struct AppliedFault {
+    /// The scheduled fault.
+    pub fault: ScheduledFault,
+    /// The name of the member the fault was injected into.
+    pub member: String,
+    /// What happened when the fault was injected.
+    pub outcome: FaultOutcome,
+}
+
+/// A violation of an [`Invariant`].
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// When the violation was detected, relative to the start of the run.
+    pub at: Duration,
+    /// The name of the violated invariant.
+    pub invariant: String,
+    /// The message returned by the invariant's check.
+    pub message: String,
+}
+
+/// The result of [`run_chaos`].
+#[derive(Clone, Debug)]
+pub struct ChaosReport {
+    /// The seed of the run, which reproduces its [`FaultSchedule`].
+    pub seed: u64,
+    /// The faults scheduled during the run.
+    pub faults: Vec<AppliedFault>,
+    /// The first violation of each invariant which did not hold.
+    pub violations: Vec<Violation>,
+}
+
+impl ChaosReport {
+    /// Whether all invariants held throughout the run.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Panics with the report if any invariant was violated.
+    pub fn assert_ok(&self) {
+        assert!(self.is_ok(), "{}", self);
+    }
+}
+
+impl Display for ChaosReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Chaos run with seed {}: {} faults, {} invariant violations",
+            self.seed,
+            self.faults.len(),
+            self.violations.len()
+        )?;
+        for applied in &self.faults {
+            writeln!(
+                f,
+                "  {:>8?} {:?} on {}: {:?}",
+                applied.fault.at, applied.fault.fault, applied.member, applied.outcome
+            )?;
+        }
+        for violation in &self.violations {
+            writeln!(
+                f,
+                "  {:>8?} violated `{}`: {}",
+                violation.at, violation.invariant, violation.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Injects the faults of a [`FaultSchedule`] generated from `config` into `members`, which must
+/// already have been started, while checking `invariants`.
+///
+/// Suspended members are resumed before returning, but killed members are not relaunched.
+pub async fn run_chaos(
+    config: &ChaosConfig,
+    members: &[ChaosMember],
+    mut invariants: Vec<Invariant>,
+) -> ChaosReport {
+    let schedule = FaultSchedule::generate(config, members.len());
+    let start = Instant::now();
+
+    let mut faults = Vec::new();
+    let mut violations = Vec::new();
+    let mut violated = HashSet::new();
+    let mut killed = HashSet::new();
+    // Suspension tasks for partitioned or delayed members, by member index.
+    let mut suspended: HashMap<usize, (usize, JoinHandle<Result<(), String>>)> = HashMap::new();
+
+    let mut check_invariants = |violations: &mut Vec<Violation>| {
+        for invariant in invariants.iter_mut() {
+            if violated.contains(&invariant.name) {
+                continue;
+            }
+            if let Err(message) = (invariant.check)() {
+                violated.insert(invariant.name.clone());
+                violations.push(Violation {
+                    at: start.elapsed(),
+                    invariant: invariant.name.clone(),
+                    message,
+                });
+            }
+        }
+    };
+
+    let mut pending = schedule.faults.into_iter().peekable();
+    let mut next_check = config.check_interval;
+    while start.elapsed() < config.run_for {
+        let next = pending
+            .peek()
+            .map_or(next_check, |fault| fault.at.min(next_check))
+            .min(config.run_for);
+        tokio::time::sleep_until(start + next).await;
+
+        while let Some(fault) = pending.next_if(|fault| fault.at <= start.elapsed()) {
+            let member = &members[fault.member];
+
+            // Collect the outcome of a previous partition or delay which has ended.
+            if let Some((_, task)) = suspended.get(&fault.member)
+                && task.is_finished()
+            {
+                let (index, task) = suspended.remove(&fault.member).unwrap();
+                faults[index] = finished(faults[index].clone(), task).await;
+            }
+
+            let outcome = if killed.contains(&fault.member) {
+                FaultOutcome::Skipped("the member was killed".to_owned())
+            } else if suspended.contains_key(&fault.member) {
+                FaultOutcome::Skipped("the member is suspended".to_owned())
+            } else {
+                match fault.fault {
+                    Fault::Kill => {
+                        killed.insert(fault.member);
+                        match member.service.stop().await {
+                            Ok(()) => FaultOutcome::Injected,
+                            Err(err) => FaultOutcome::Failed(err.to_string()),
+                        }
+                    }
+                    Fault::Restart => match member.service.restart().await {
+                        Ok(()) => FaultOutcome::Injected,
+                        Err(err) => FaultOutcome::Skipped(err.to_string()),
+                    },
+                    Fault::Partition { duration } => {
+                        let service = member.service.clone();
+                        let task = tokio::spawn(async move {
+                            service.suspend().await.map_err(|e| e.to_string())?;
+                            tokio::time::sleep(duration).await;
+                            service.resume().await.map_err(|e| e.to_string())
+                        });
+                        suspended.insert(fault.member, (faults.len(), task));
+                        FaultOutcome::Injected
+                    }
+                    Fault::Delay { duration } => {
+                        let service = member.service.clone();
+                        let task = tokio::spawn(async move {
+                            let end = Instant::now() + duration;
+                            while Instant::now() < end {
+                                service.suspend().await.map_err(|e| e.to_string())?;
+                                tokio::time::sleep(DELAY_PAUSE).await;
+                                service.resume().await.map_err(|e| e.to_string())?;
+                                tokio::time::sleep(DELAY_PAUSE).await;
+                            }
+                            Ok(())
+                        });
+                        suspended.insert(fault.member, (faults.len(), task));
+                        FaultOutcome::Injected
+                    }
+                }
+            };
+
+            faults.push(AppliedFault {
+                member: member.name.clone(),
+                fault,
+                outcome,
+            });
+        }
+
+        if start.elapsed() >= next_check {
+            check_invariants(&mut violations);
+            next_check += config.check_interval;
+        }
+    }
+
+    for (index, task) in suspended.into_values() {
+        faults[index] = finished(faults[index].clone(), task).await;
+    }
+    check_invariants(&mut violations);
+
+    ChaosReport {
+        seed: config.seed,
+        faults,
+        violations,
+    }
+}
+
+/// Waits for the suspension task of a partition or delay, recording whether it failed.
+async fn finished(mut applied: AppliedFault, task: JoinHandle<Result<(), String>>) -> AppliedFault {
+    match task.await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => applied.outcome = FaultOutcome::Failed(err),
+        Err(err) => applied.outcome = FaultOutcome::Failed(err.to_string()),
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_is_reproducible() {
+        let config = ChaosConfig::with_seed(42);
+        let schedule = FaultSchedule::generate(&config, 3);
+
+        assert_eq!(schedule, FaultSchedule::generate(&config, 3));
+        assert_ne!(
+            schedule,
+            FaultSchedule::generate(&ChaosConfig::with_seed(43), 3)
+        );
+
+        assert_eq!(schedule.faults.len(), config.faults);
+        assert!(schedule.faults.is_sorted_by_key(|fault| fault.at));
+        for fault in &schedule.faults {
+            assert!(fault.at < config.run_for);
+            assert!(fault.member < 3);
+        }
+    }
+
+    #[test]
+    fn schedule_respects_kinds() {
+        let config = ChaosConfig {
+            kinds: vec![FaultKind::Partition],
+            max_fault_duration: Duration::from_millis(100),
+            ..ChaosConfig::with_seed(7)
+        };
+
+        for fault in FaultSchedule::generate(&config, 5).faults {
+            let Fault::Partition { duration } = fault.fault else {
+                panic!("unexpected fault {:?}", fault.fault);
+            };
+            assert!(duration > Duration::ZERO && duration <= config.max_fault_duration);
+        }
+    }
+}
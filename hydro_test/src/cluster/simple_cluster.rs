@@ -138,6 +138,85 @@ mod tests {
         }
     }
 
+    /// Runs the artifacts compiled by `ArtifactDeploy` without Hydro Deploy, starting the process
+    /// well before the cluster members it connects to.
+    #[tokio::test]
+    async fn simple_cluster_artifacts() {
+        use hydro_lang::deploy::deploy_graph_artifacts::INSTANCE_CONFIG_ENV;
+        use hydro_lang::deploy::{ArtifactClusterSpec, ArtifactDeployment, ArtifactProcessSpec};
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let mut builder = hydro_lang::compile::builder::FlowBuilder::new();
+        let (node, cluster) = super::simple_cluster(&mut builder);
+
+        let base_port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut deployment = ArtifactDeployment::new().base_port(base_port);
+        let _nodes = builder
+            .with_default_optimize()
+            .with_process(&node, ArtifactProcessSpec::new())
+            .with_cluster(&cluster, ArtifactClusterSpec::new(2))
+            .deploy(&mut deployment);
+
+        let out_dir = std::env::temp_dir().join(format!(
+            "hydro_simple_cluster_artifacts_{}",
+            std::process::id()
+        ));
+        let manifest = deployment.write_artifacts(&out_dir).await.unwrap();
+        assert_eq!(manifest.instances.len(), 3);
+
+        let launch = |name: &str| {
+            let instance = &manifest.instances[name];
+            let config_path = out_dir.join(format!("{}.json", name));
+            let config = instance.instance_config(|_| "127.0.0.1".parse().unwrap());
+            std::fs::write(&config_path, serde_json::to_string(&config).unwrap()).unwrap();
+            tokio::process::Command::new(out_dir.join(&instance.binary))
+                .env(INSTANCE_CONFIG_ENV, &config_path)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .unwrap()
+        };
+
+        let (process_name, _) = manifest
+            .instances
+            .iter()
+            .find(|(_, instance)| instance.member_id.is_none())
+            .unwrap();
+        let mut process = launch(process_name);
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        let _members = manifest
+            .instances
+            .iter()
+            .filter(|(_, instance)| instance.member_id.is_some())
+            .map(|(name, _)| launch(name))
+            .collect::<Vec<_>>();
+
+        let mut lines = BufReader::new(process.stdout.take().unwrap()).lines();
+        let mut node_outs = vec![];
+        tokio::time::timeout(std::time::Duration::from_secs(60), async {
+            while node_outs.len() < 10 {
+                node_outs.push(lines.next_line().await.unwrap().unwrap());
+            }
+        })
+        .await
+        .unwrap();
+        node_outs.sort();
+
+        for (i, n) in node_outs.into_iter().enumerate() {
+            assert_eq!(
+                n,
+                format!("node received: (MemberId::<()>({}), {})", i / 5, i % 5)
+            );
+        }
+
+        let _ = std::fs::remove_dir_all(&out_dir);
+    }
+
     #[tokio::test]
     async fn decouple_process() {
         let mut deployment = Deployment::new();
@@ -31,6 +31,7 @@ pub fn first_ten_distributed<'a>(
 mod tests {
     use futures::SinkExt;
     use hydro_deploy::Deployment;
+    use hydro_deploy::state::ServerPort;
     use hydro_lang::deploy::DeployCrateWrapper;
 
     #[test]
@@ -83,4 +84,85 @@ mod tests {
             assert_eq!(second_node_stdout.recv().await.unwrap(), i.to_string());
         }
     }
+
+    /// The TCP ports each service is bound to, by service and port name.
+    fn tcp_ports(
+        state: &hydro_deploy::DeploymentState,
+    ) -> std::collections::BTreeMap<(String, String), Vec<std::net::SocketAddr>> {
+        fn collect(port: &ServerPort, out: &mut Vec<std::net::SocketAddr>) {
+            match port {
+                ServerPort::TcpPort(addr) => out.push(*addr),
+                ServerPort::Demux(ports) => ports.values().for_each(|p| collect(p, out)),
+                ServerPort::Merge(ports) => ports.iter().for_each(|p| collect(p, out)),
+                ServerPort::Tagged(port, _) => collect(port, out),
+                ServerPort::UnixSocket(_) | ServerPort::Null => {}
+            }
+        }
+
+        state
+            .ports
+            .iter()
+            .flat_map(|(service, ports)| {
+                ports.iter().map(|(name, port)| {
+                    let mut addrs = vec![];
+                    collect(port, &mut addrs);
+                    ((service.clone(), name.clone()), addrs)
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn first_ten_distributed_resume() {
+        let state_path = std::env::temp_dir().join(format!(
+            "hydro-first-ten-resume-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&state_path);
+
+        let mut previous_ports = None;
+        for _ in 0..2 {
+            // Resuming from the state left by the first run reuses its ports.
+            let mut deployment = Deployment::resume(&state_path).unwrap();
+
+            let mut builder = hydro_lang::compile::builder::FlowBuilder::new();
+            let external = builder.external();
+            let p1 = builder.process();
+            let p2 = builder.process();
+            let external_port = super::first_ten_distributed(&external, &p1, &p2);
+
+            let nodes = builder
+                .with_default_optimize()
+                .with_process(&p1, deployment.Localhost())
+                .with_process(&p2, deployment.Localhost())
+                .with_external(&external, deployment.Localhost())
+                .deploy(&mut deployment);
+
+            deployment.deploy().await.unwrap();
+
+            let mut external_port = nodes.connect(external_port).await;
+            let mut first_node_stdout = nodes.get_process(&p1).stdout();
+            let mut second_node_stdout = nodes.get_process(&p2).stdout();
+
+            deployment.start().await.unwrap();
+
+            external_port.send("resumed".to_owned()).await.unwrap();
+            assert_eq!(first_node_stdout.recv().await.unwrap(), "hi: \"resumed\"");
+            for i in 0..10 {
+                assert_eq!(second_node_stdout.recv().await.unwrap(), i.to_string());
+            }
+
+            let state = hydro_deploy::DeploymentState::load(&state_path).unwrap();
+            assert!(!state.ports.is_empty());
+            let ports = tcp_ports(&state);
+            if let Some(previous_ports) = &previous_ports {
+                assert_eq!(previous_ports, &ports);
+            }
+            previous_ports = Some(ports);
+
+            deployment.stop().await.unwrap();
+        }
+
+        std::fs::remove_file(&state_path).unwrap();
+    }
 }
@@ -1,6 +1,9 @@
 #[cfg(stageleft_runtime)]
 hydro_lang::setup!();
 
+#[cfg(stageleft_runtime)]
+#[cfg(feature = "chaos")]
+pub mod chaos;
 pub mod cluster;
 pub mod distributed;
 pub mod embedded;
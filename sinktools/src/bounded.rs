@@ -0,0 +1,241 @@
+//! [`Bounded`] and related items.
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll, ready};
+
+use pin_project_lite::pin_project;
+
+use crate::Sink;
+
+/// What a [`Bounded`] sink does with an item sent while its buffer is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Apply backpressure: the sink is not ready until there is room in the buffer.
+    Block,
+    /// Drop the item being sent, keeping the buffered items.
+    DropNewest,
+    /// Drop the oldest buffered item to make room for the item being sent.
+    DropOldest,
+}
+
+/// Statistics of a [`Bounded`] sink, shared with the sink so they can be read while it is in use.
+#[derive(Clone, Debug, Default)]
+pub struct BoundedStats {
+    dropped: Arc<AtomicU64>,
+}
+
+impl BoundedStats {
+    /// The number of items dropped due to the buffer being full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+pin_project! {
+    /// Buffers up to a fixed number of items in front of the following sink, and applies an
+    /// [`OverflowPolicy`] to items sent while the buffer is full.
+    ///
+    /// Unless the policy is [`OverflowPolicy::Block`], this sink is always ready, and flushing it
+    /// sends as many buffered items as the following sink will accept without waiting for the
+    /// rest. The remaining items are sent by later calls.
+    #[must_use = "sinks do nothing unless polled"]
+    pub struct Bounded<Si, Item> {
+        #[pin]
+        sink: Si,
+        buffer: VecDeque<Item>,
+        capacity: usize,
+        policy: OverflowPolicy,
+        stats: BoundedStats,
+    }
+}
+
+impl<Si, Item> Bounded<Si, Item>
+where
+    Si: Sink<Item>,
+{
+    /// Creates with the buffer `capacity`, overflow `policy`, and next `sink`.
+    ///
+    /// # Panics
+    /// If `capacity` is zero.
+    pub fn new(capacity: usize, policy: OverflowPolicy, sink: Si) -> Self {
+        assert!(0 < capacity, "`Bounded` sink capacity must be positive");
+        Self {
+            sink,
+            buffer: VecDeque::with_capacity(capacity),
+            capacity,
+            policy,
+            stats: BoundedStats::default(),
+        }
+    }
+
+    /// Returns a handle to the statistics of this sink.
+    pub fn stats(&self) -> BoundedStats {
+        self.stats.clone()
+    }
+
+    /// Sends buffered items into the following sink until it is not ready or the buffer is empty.
+    fn poll_send_buffer(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Si::Error>> {
+        let mut this = self.project();
+        while !this.buffer.is_empty() {
+            ready!(this.sink.as_mut().poll_ready(cx)?);
+            let item = this.buffer.pop_front().unwrap();
+            this.sink.as_mut().start_send(item)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<Si, Item> Sink<Item> for Bounded<Si, Item>
+where
+    Si: Sink<Item>,
+{
+    type Error = Si::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let sent = self.as_mut().poll_send_buffer(cx)?;
+        if sent.is_pending()
+            && OverflowPolicy::Block == self.policy
+            && self.capacity <= self.buffer.len()
+        {
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = self.project();
+        if *this.capacity <= this.buffer.len() {
+            match this.policy {
+                // Only possible if `poll_ready` was not called, in which case allow overfilling.
+                OverflowPolicy::Block => {}
+                OverflowPolicy::DropNewest => {
+                    this.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                OverflowPolicy::DropOldest => {
+                    this.buffer.pop_front();
+                    this.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        this.buffer.push_back(item);
+        Ok(())
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let sent = self.as_mut().poll_send_buffer(cx)?;
+        if sent.is_pending() {
+            return match self.policy {
+                OverflowPolicy::Block => Poll::Pending,
+                OverflowPolicy::DropNewest | OverflowPolicy::DropOldest => Poll::Ready(Ok(())),
+            };
+        }
+        let this = self.project();
+        let flushed = this.sink.poll_flush(cx)?;
+        match this.policy {
+            OverflowPolicy::Block => flushed.map(Ok),
+            OverflowPolicy::DropNewest | OverflowPolicy::DropOldest => Poll::Ready(Ok(())),
+        }
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_send_buffer(cx)?);
+        self.project().sink.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::convert::Infallible;
+    use core::pin::pin;
+    use core::task::Context;
+
+    use futures_task::noop_waker_ref;
+
+    use super::*;
+
+    /// A sink which accepts only as many items as it has been given credits for.
+    struct Credits {
+        credits: usize,
+        received: Vec<u32>,
+    }
+    impl Sink<u32> for &mut Credits {
+        type Error = Infallible;
+        fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            if 0 < self.credits {
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        }
+        fn start_send(mut self: Pin<&mut Self>, item: u32) -> Result<(), Infallible> {
+            self.credits -= 1;
+            self.received.push(item);
+            Ok(())
+        }
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn send_all(policy: OverflowPolicy) -> (Vec<u32>, Poll<Result<(), Infallible>>, u64) {
+        let mut inner = Credits {
+            credits: 1,
+            received: Vec::new(),
+        };
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let (ready, dropped) = {
+            let mut sink = pin!(Bounded::new(2, policy, &mut inner));
+            let stats = sink.stats();
+            let mut ready = Poll::Ready(Ok(()));
+            for item in 0..5 {
+                ready = sink.as_mut().poll_ready(cx);
+                if ready.is_pending() {
+                    break;
+                }
+                sink.as_mut().start_send(item).unwrap();
+            }
+            let _ = sink.as_mut().poll_flush(cx);
+            (ready, stats.dropped())
+        };
+        (inner.received, ready, dropped)
+    }
+
+    #[test]
+    fn test_bounded_block() {
+        let (received, ready, dropped) = send_all(OverflowPolicy::Block);
+        // One item is sent, two are buffered, then the sink applies backpressure.
+        assert_eq!(vec![0], received);
+        assert!(ready.is_pending());
+        assert_eq!(0, dropped);
+    }
+
+    #[test]
+    fn test_bounded_drop_newest() {
+        let (received, ready, dropped) = send_all(OverflowPolicy::DropNewest);
+        assert_eq!(vec![0], received);
+        assert!(ready.is_ready());
+        // Items 1 and 2 are buffered, 3 and 4 are dropped.
+        assert_eq!(2, dropped);
+    }
+
+    #[test]
+    fn test_bounded_drop_oldest() {
+        let mut inner = Credits {
+            credits: 0,
+            received: Vec::new(),
+        };
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let mut sink = pin!(Bounded::new(2, OverflowPolicy::DropOldest, &mut inner));
+        let stats = sink.stats();
+        for item in 0..5 {
+            assert!(sink.as_mut().poll_ready(cx).is_ready());
+            sink.as_mut().start_send(item).unwrap();
+        }
+        assert_eq!(3, stats.dropped());
+        // The newest items are kept.
+        assert_eq!(&[3, 4], &*sink.as_mut().project().buffer.make_contiguous());
+    }
+}
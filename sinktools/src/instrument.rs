@@ -0,0 +1,206 @@
+//! [`Instrument`] and related items.
+//!
+//! Each instrumented sink records the number of items sent into it, the number of errors it
+//! returned, and the total time it spent not ready (between a `poll_ready` returning
+//! [`Poll::Pending`] and the next one returning [`Poll::Ready`]), into a process-wide registry
+//! keyed by the sink's name. Sinks with the same name are aggregated together.
+//!
+//! The registry can be read with [`snapshot`] or [`snapshot_all`]. In deployed Hydro binaries,
+//! it is included in the metrics written by the EMF metrics sidecar.
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use pin_project_lite::pin_project;
+
+use crate::{Sink, SinkBuild};
+
+/// The metrics recorded for an instrumented sink, returned by [`snapshot`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SinkSnapshot {
+    /// The number of items sent into the sink.
+    pub items: u64,
+    /// The number of errors returned by the sink, from any of its methods.
+    pub errors: u64,
+    /// The total time the sink spent not ready to receive items.
+    pub stall_time: Duration,
+}
+
+#[derive(Default)]
+struct SinkMetrics {
+    items: AtomicU64,
+    errors: AtomicU64,
+    stall_nanos: AtomicU64,
+}
+
+impl SinkMetrics {
+    fn snapshot(&self) -> SinkSnapshot {
+        SinkSnapshot {
+            items: self.items.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            stall_time: Duration::from_nanos(self.stall_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Counts an error if `result` is one.
+    fn record<T, E>(&self, result: Result<T, E>) -> Result<T, E> {
+        if result.is_err() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+static SINKS: Mutex<BTreeMap<String, Arc<SinkMetrics>>> = Mutex::new(BTreeMap::new());
+
+fn register(name: &str) -> Arc<SinkMetrics> {
+    SINKS
+        .lock()
+        .unwrap()
+        .entry(name.to_owned())
+        .or_default()
+        .clone()
+}
+
+/// Returns the metrics recorded so far for the sinks with the given name, or `None` if no such
+/// sink has been instrumented.
+pub fn snapshot(name: &str) -> Option<SinkSnapshot> {
+    let sinks = SINKS.lock().unwrap();
+    Some(sinks.get(name)?.snapshot())
+}
+
+/// Returns the metrics recorded so far for all instrumented sinks, by name.
+pub fn snapshot_all() -> BTreeMap<String, SinkSnapshot> {
+    let sinks = SINKS.lock().unwrap();
+    sinks
+        .iter()
+        .map(|(name, metrics)| (name.clone(), metrics.snapshot()))
+        .collect()
+}
+
+pin_project! {
+    /// Records metrics about the items sent into the following sink, under a name.
+    ///
+    /// See the [module documentation](self) for the metrics recorded.
+    #[must_use = "sinks do nothing unless polled"]
+    pub struct Instrument<Si> {
+        #[pin]
+        sink: Si,
+        metrics: Arc<SinkMetrics>,
+        stalled_since: Option<Instant>,
+    }
+}
+
+impl<Si> Instrument<Si> {
+    /// Creates with the metrics `name` and next `sink`.
+    pub fn new(name: &str, sink: Si) -> Self {
+        Self {
+            sink,
+            metrics: register(name),
+            stalled_since: None,
+        }
+    }
+}
+
+impl<Si, Item> Sink<Item> for Instrument<Si>
+where
+    Si: Sink<Item>,
+{
+    type Error = Si::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        match this.sink.poll_ready(cx) {
+            Poll::Pending => {
+                this.stalled_since.get_or_insert_with(Instant::now);
+                Poll::Pending
+            }
+            Poll::Ready(result) => {
+                if let Some(since) = this.stalled_since.take() {
+                    this.metrics
+                        .stall_nanos
+                        .fetch_add(since.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                }
+                Poll::Ready(this.metrics.record(result))
+            }
+        }
+    }
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = self.project();
+        this.metrics.items.fetch_add(1, Ordering::Relaxed);
+        this.metrics.record(this.sink.start_send(item))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        this.sink
+            .poll_flush(cx)
+            .map(|result| this.metrics.record(result))
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        this.sink
+            .poll_close(cx)
+            .map(|result| this.metrics.record(result))
+    }
+}
+
+/// [`SinkBuild`] for [`Instrument`].
+pub struct InstrumentBuilder<'a, Prev> {
+    pub(crate) prev: Prev,
+    pub(crate) name: &'a str,
+}
+impl<Prev> SinkBuild for InstrumentBuilder<'_, Prev>
+where
+    Prev: SinkBuild,
+{
+    type Item = Prev::Item;
+
+    type Output<Next: Sink<Prev::Item>> = Prev::Output<Instrument<Next>>;
+
+    fn send_to<Next>(self, next: Next) -> Self::Output<Next>
+    where
+        Next: Sink<Prev::Item>,
+    {
+        self.prev.send_to(Instrument::new(self.name, next))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures_util::sink::SinkExt;
+
+    use super::*;
+    use crate::{SinkBuilder, for_each, instrument, try_for_each};
+
+    #[tokio::test]
+    async fn test_instrument() {
+        let mut sink = SinkBuilder::<u32>::new()
+            .instrument("test_instrument")
+            .for_each(|_| {});
+        for item in 0..5 {
+            sink.send(item).await.unwrap();
+        }
+
+        // Sinks with the same name are aggregated.
+        let mut other = instrument("test_instrument", for_each(|_: u32| {}));
+        other.send(5).await.unwrap();
+
+        let mut failing = instrument(
+            "test_instrument_errors",
+            try_for_each(|item: u32| if item.is_multiple_of(2) { Ok(()) } else { Err(item) }),
+        );
+        for item in 0..4 {
+            let _ = failing.send(item).await;
+        }
+
+        assert_eq!(6, snapshot("test_instrument").unwrap().items);
+        assert_eq!(0, snapshot("test_instrument").unwrap().errors);
+        assert_eq!(4, snapshot("test_instrument_errors").unwrap().items);
+        assert_eq!(2, snapshot("test_instrument_errors").unwrap().errors);
+        assert!(snapshot_all().contains_key("test_instrument"));
+        assert_eq!(None, snapshot("test_instrument_missing"));
+    }
+}
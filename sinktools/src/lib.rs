@@ -52,6 +52,18 @@ pub mod demux_map_lazy;
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
 pub use demux_map_lazy::demux_map_lazy;
 
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod bounded;
+#[cfg(feature = "std")]
+use bounded::Bounded;
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod instrument;
+#[cfg(feature = "std")]
+use instrument::Instrument;
+
 #[cfg(feature = "variadics")]
 #[cfg_attr(docsrs, doc(cfg(feature = "variadics")))]
 pub mod demux_var;
@@ -161,6 +173,16 @@ pub trait SinkBuild {
         inspect::InspectBuilder { prev: self, func }
     }
 
+    /// Records metrics about the items passing through under `name`, see [`instrument`](mod@instrument).
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    fn instrument(self, name: &str) -> instrument::InstrumentBuilder<'_, Self>
+    where
+        Self: Sized,
+    {
+        instrument::InstrumentBuilder { prev: self, name }
+    }
+
     /// Splits items into two sinks based on tuple structure.
     fn unzip<Si0, Si1, Item0, Item1>(self, sink0: Si0, sink1: Si1) -> Self::Output<Unzip<Si0, Si1>>
     where
@@ -352,6 +374,29 @@ where
     Inspect::new(func, sink)
 }
 
+/// Creates a [`Bounded`] sink that buffers up to `capacity` items in front of `sink`, applying
+/// `policy` to items sent while the buffer is full.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn bounded<Item, Si>(
+    capacity: usize,
+    policy: bounded::OverflowPolicy,
+    sink: Si,
+) -> Bounded<Si, Item>
+where
+    Si: Sink<Item>,
+{
+    Bounded::new(capacity, policy, sink)
+}
+
+/// Creates an [`Instrument`] sink that records metrics about the items sent into `sink` under
+/// `name`.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn instrument<Si>(name: &str, sink: Si) -> Instrument<Si> {
+    Instrument::new(name, sink)
+}
+
 /// Creates an [`Unzip`] sink that splits tuple items into two separate sinks.
 pub fn unzip<Si0, Si1, Item0, Item1>(sink0: Si0, sink1: Si1) -> Unzip<Si0, Si1>
 where
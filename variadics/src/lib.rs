@@ -605,7 +605,7 @@ impl PartialEqVariadic for () {
 ///
 /// This is a sealed trait.
 #[sealed]
-pub trait HomogenousVariadic<T>: Variadic {
+pub trait HomogenousVariadic<T>: VariadicExt {
     /// Returns a reference to an element.
     fn get(&self, i: usize) -> Option<&T>;
     /// Returns an exclusive reference to an element.
@@ -615,6 +615,63 @@ pub trait HomogenousVariadic<T>: Variadic {
     type IntoIter: Iterator<Item = T>;
     /// Turns this `HomogenousVariadic<T>` into an iterator of items `T`.
     fn into_iter(self) -> Self::IntoIter;
+
+    /// Iterator type returned by `iter`.
+    type Iter<'a>: Iterator<Item = &'a T>
+    where
+        Self: 'a,
+        T: 'a;
+    /// Returns an iterator of references to the items of this `HomogenousVariadic<T>`.
+    fn iter(&self) -> Self::Iter<'_>;
+
+    /// Builds this variadic from the items yielded by `iter`, which must yield at least
+    /// [`VariadicExt::LEN`] items.
+    #[doc(hidden)]
+    fn take_from_iter(iter: &mut impl Iterator<Item = T>) -> Self;
+
+    /// Converts this variadic into an array of its items.
+    ///
+    /// Fails to compile if `N` is not the length of this variadic.
+    fn into_array<const N: usize>(self) -> [T; N]
+    where
+        Self: Sized,
+    {
+        const {
+            assert!(
+                N == Self::LEN,
+                "the array length must equal the length of the variadic"
+            )
+        };
+        let mut iter = self.into_iter();
+        core::array::from_fn(|_| iter.next().unwrap())
+    }
+
+    /// Creates a variadic from an array of its items.
+    ///
+    /// Fails to compile if `N` is not the length of this variadic.
+    fn from_array<const N: usize>(array: [T; N]) -> Self
+    where
+        Self: Sized,
+    {
+        const {
+            assert!(
+                N == Self::LEN,
+                "the array length must equal the length of the variadic"
+            )
+        };
+        Self::take_from_iter(&mut array.into_iter())
+    }
+
+    /// Clones the items of this variadic into a [`Vec`](alloc::vec::Vec), with a single
+    /// allocation.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[cfg(feature = "alloc")]
+    fn to_vec(&self) -> alloc::vec::Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().cloned().collect()
+    }
 }
 #[sealed]
 impl<T> HomogenousVariadic<T> for () {
@@ -629,6 +686,17 @@ impl<T> HomogenousVariadic<T> for () {
     fn into_iter(self) -> Self::IntoIter {
         core::iter::empty()
     }
+
+    type Iter<'a>
+        = core::iter::Empty<&'a T>
+    where
+        Self: 'a,
+        T: 'a;
+    fn iter(&self) -> Self::Iter<'_> {
+        core::iter::empty()
+    }
+
+    fn take_from_iter(_iter: &mut impl Iterator<Item = T>) -> Self {}
 }
 #[sealed]
 impl<T, Rest> HomogenousVariadic<T> for (T, Rest)
@@ -653,6 +721,23 @@ where
         let (item, rest) = self;
         core::iter::once(item).chain(rest.into_iter())
     }
+
+    type Iter<'a>
+        = core::iter::Chain<core::iter::Once<&'a T>, Rest::Iter<'a>>
+    where
+        Self: 'a,
+        T: 'a;
+    fn iter(&self) -> Self::Iter<'_> {
+        let (item, rest) = self;
+        core::iter::once(item).chain(rest.iter())
+    }
+
+    fn take_from_iter(iter: &mut impl Iterator<Item = T>) -> Self {
+        let item = iter
+            .next()
+            .expect("iterator should yield an item for each element of the variadic");
+        (item, Rest::take_from_iter(iter))
+    }
 }
 
 /// Helper trait for splitting a variadic into two parts. `Prefix` is the first part, everything
@@ -781,11 +866,20 @@ pub trait VecVariadic: VariadicExt {
     /// zip across all the vecs in this VariadicVec
     fn zip_vecs(&self) -> impl Iterator<Item = <Self::UnVec as VariadicExt>::AsRefVar<'_>>;
 
+    /// zip mutably across all the vecs in this VariadicVec
+    fn zip_vecs_mut(&mut self) -> impl Iterator<Item = <Self::UnVec as VariadicExt>::AsMutVar<'_>>;
+
     /// append an unvec'ed Variadic into this VariadicVec
     fn push(&mut self, item: Self::UnVec);
 
     /// get the unvec'ed Variadic at position `index`
-    fn get(&mut self, index: usize) -> Option<<Self::UnVec as VariadicExt>::AsRefVar<'_>>;
+    fn get(&self, index: usize) -> Option<<Self::UnVec as VariadicExt>::AsRefVar<'_>>;
+
+    /// get the unvec'ed Variadic at position `index` mutably
+    fn get_mut(&mut self, index: usize) -> Option<<Self::UnVec as VariadicExt>::AsMutVar<'_>>;
+
+    /// The number of rows, i.e. the length of the first vec. Zero if there are no vecs.
+    fn num_rows(&self) -> usize;
 
     /// result type from into_zip
     type IntoZip: Iterator<Item = Self::UnVec>;
@@ -815,6 +909,11 @@ where
         core::iter::zip(this.iter(), rest.zip_vecs())
     }
 
+    fn zip_vecs_mut(&mut self) -> impl Iterator<Item = <Self::UnVec as VariadicExt>::AsMutVar<'_>> {
+        let (this, rest) = self;
+        core::iter::zip(this.iter_mut(), rest.zip_vecs_mut())
+    }
+
     fn push(&mut self, row: Self::UnVec) {
         let (this_vec, rest_vecs) = self;
         let (this_col, rest_cols) = row;
@@ -822,7 +921,7 @@ where
         rest_vecs.push(rest_cols);
     }
 
-    fn get(&mut self, index: usize) -> Option<<Self::UnVec as VariadicExt>::AsRefVar<'_>> {
+    fn get(&self, index: usize) -> Option<<Self::UnVec as VariadicExt>::AsRefVar<'_>> {
         let (this_vec, rest_vecs) = self;
         if let Some(rest) = VecVariadic::get(rest_vecs, index) {
             this_vec.get(index).map(|item| var_expr!(item, ...rest))
@@ -831,6 +930,19 @@ where
         }
     }
 
+    fn get_mut(&mut self, index: usize) -> Option<<Self::UnVec as VariadicExt>::AsMutVar<'_>> {
+        let (this_vec, rest_vecs) = self;
+        if let Some(rest) = VecVariadic::get_mut(rest_vecs, index) {
+            this_vec.get_mut(index).map(|item| var_expr!(item, ...rest))
+        } else {
+            None
+        }
+    }
+
+    fn num_rows(&self) -> usize {
+        self.0.len()
+    }
+
     type IntoZip = core::iter::Zip<alloc::vec::IntoIter<Item>, Rest::IntoZip>;
     fn into_zip(self) -> Self::IntoZip {
         let (this, rest) = self;
@@ -859,12 +971,24 @@ impl VecVariadic for () {
         core::iter::repeat(var_expr!())
     }
 
+    fn zip_vecs_mut(&mut self) -> impl Iterator<Item = <Self::UnVec as VariadicExt>::AsMutVar<'_>> {
+        core::iter::repeat(var_expr!())
+    }
+
     fn push(&mut self, _item: Self::UnVec) {}
 
-    fn get(&mut self, _index: usize) -> Option<<Self::UnVec as VariadicExt>::AsRefVar<'_>> {
+    fn get(&self, _index: usize) -> Option<<Self::UnVec as VariadicExt>::AsRefVar<'_>> {
+        Some(())
+    }
+
+    fn get_mut(&mut self, _index: usize) -> Option<<Self::UnVec as VariadicExt>::AsMutVar<'_>> {
         Some(())
     }
 
+    fn num_rows(&self) -> usize {
+        0
+    }
+
     type IntoZip = core::iter::Repeat<var_type!()>;
     fn into_zip(self) -> Self::IntoZip {
         core::iter::repeat(var_expr!())
@@ -913,6 +1037,21 @@ mod test {
     type _ListB = var_type!(..._ListA, bool, Option<()>);
     type _ListC = var_type!(..._ListA, bool, Option::<()>);
 
+    #[test]
+    fn test_homogenous_array() {
+        let var = var_expr!(1_u32, 2, 3);
+        assert_eq!([1, 2, 3], var.into_array());
+        assert_eq!(var, <var_type!(u32, u32, u32)>::from_array([1, 2, 3]));
+        assert_eq!([0_u8; 0], ().into_array());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_homogenous_to_vec() {
+        let var = var_expr!(1_u32, 2, 3);
+        assert_eq!(alloc::vec![1, 2, 3], var.to_vec());
+    }
+
     #[cfg(feature = "alloc")]
     #[test]
     fn test_as_ref_var() {
@@ -1002,6 +1141,32 @@ mod test {
         assert_eq!(column_store.len(), 2);
         assert_eq!(column_store.get(0).unwrap(), first.as_ref_var());
         assert_eq!(column_store.get(1).unwrap(), second.as_ref_var());
+        assert_eq!(column_store.get(2), None);
+        assert_eq!(column_store.num_rows(), 2);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_vec_columns_mut() {
+        use alloc::vec::Vec;
+
+        use crate::VecVariadic;
+
+        let mut columns = <var_type!(u32, bool) as VariadicExt>::IntoVec::default();
+        assert_eq!(columns.num_rows(), 0);
+        columns.push(var_expr!(1, false));
+        columns.push(var_expr!(2, true));
+
+        let var_args!(num, flag) = columns.get_mut(0).unwrap();
+        *num += 10;
+        *flag = true;
+        for var_args!(num, _flag) in columns.zip_vecs_mut() {
+            *num *= 2;
+        }
+
+        let var_args!(nums, flags) = &columns;
+        assert_eq!(&Vec::from([22, 4]), nums);
+        assert_eq!(&Vec::from([true, true]), flags);
     }
 }
 
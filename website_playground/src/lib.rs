@@ -115,6 +115,7 @@ pub fn compile_dfir(
         no_loops,
         op_short_text,
         op_text_no_imports: false,
+        explain_handoffs: false,
     };
 
     let out = match syn::parse_str(&program) {